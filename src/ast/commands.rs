@@ -0,0 +1,34 @@
+//! Commands which only make sense in one language (like "extract variable" in an expression
+//! language, or "inline anchor" in YAML), which a language provides through
+//! [`Ast::language_commands`].  The editor lists them in its command palette, runs them with
+//! `:run <name>`, and lets them be bound to keys as `LanguageCommand("<name>")`.
+
+use super::Ast;
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+
+/// The function which carries out a [`LanguageCommand`].  It's given the root of the tree, the
+/// path to the cursor and the arena to allocate new nodes in, and returns the nodes which have to
+/// be replaced (like [`Ast::rename_symbol`]) or an error saying why the command can't be run
+/// there.  The replacements are made as a single undoable edit.
+pub type RunFn<'arena, Node> =
+    fn(&'arena Node, &CursorPath, &'arena Arena<Node>) -> Result<Vec<(CursorPath, Node)>, Message>;
+
+/// A command provided by a language
+#[derive(Debug, Clone)]
+pub struct LanguageCommand<'arena, Node: Ast<'arena>> {
+    /// The name which the command is run and bound by (e.g. `sort-keys`), which must not contain
+    /// whitespace
+    pub name: &'static str,
+    /// What the command does, shown next to its name in the command palette
+    pub description: Message,
+    pub run: RunFn<'arena, Node>,
+}
+
+/// Returns the command of the language `Node` with a given name, if it has one
+pub fn find<'arena, Node: Ast<'arena>>(name: &str) -> Option<LanguageCommand<'arena, Node>> {
+    Node::language_commands()
+        .into_iter()
+        .find(|command| command.name == name)
+}
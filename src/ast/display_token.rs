@@ -1,13 +1,51 @@
 use super::Ast;
 
 /// How many spaces corespond to one indentation level
-const INDENT_WIDTH: usize = 4;
+pub const INDENT_WIDTH: usize = 4;
+
+/// How each level of indentation is written when a tree is written as text.  The editor always
+/// shows [`INDENT_WIDTH`] spaces per level, but files can be written with whatever their project
+/// uses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Indentation {
+    /// The given number of spaces per level
+    Spaces(usize),
+    /// One tab per level
+    Tabs,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Indentation::Spaces(INDENT_WIDTH)
+    }
+}
+
+impl Indentation {
+    /// Returns the text of one level of indentation
+    fn unit(self) -> String {
+        match self {
+            Indentation::Spaces(width) => " ".repeat(width),
+            Indentation::Tabs => "\t".to_string(),
+        }
+    }
+}
 
 /// A single piece of a node that can be rendered to the screen
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DisplayToken {
     /// Some text should be rendered to the screen
     Text(String),
+    /// Some text which belongs to a [`SyntaxCategory`].  This is rendered exactly like
+    /// [`Text`](DisplayToken::Text), but is coloured according to its category by the user's
+    /// theme.
+    Categorised(String, SyntaxCategory),
+    /// Some text that opens a pair of delimiters (e.g. `[` or `{`).  This is rendered exactly like
+    /// [`Text`](DisplayToken::Text), but lets the editor match up and colour delimiter pairs.
+    /// Every `OpenDelim` must be closed by a [`CloseDelim`](DisplayToken::CloseDelim) generated by
+    /// the same node.
+    OpenDelim(String),
+    /// Some text that closes the last unclosed [`OpenDelim`](DisplayToken::OpenDelim)
+    CloseDelim(String),
     /// Add some number of spaces worth of whitespace
     Whitespace(usize),
     /// Put the next token onto a new line
@@ -18,6 +56,19 @@ pub enum DisplayToken {
     Dedent,
 }
 
+/// The kinds of text which a theme can colour differently
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SyntaxCategory {
+    /// Words which are part of the language itself, like `true` or `null`
+    Keyword,
+    /// Values written out in full, like strings or numbers
+    Literal,
+    /// Names chosen by the user, like variable names
+    Identifier,
+    /// Separators which only give the text its structure, like `,` or `:`
+    Punctuation,
+}
+
 /// A wrapper for [`DisplayToken`] that will be returned by [`Ast::display_tokens`] and allows for
 /// child references to be recursively expanded.
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -26,18 +77,36 @@ pub enum RecTok<'arena, Node> {
     Child(&'arena Node),
 }
 
-/// Write a stream of display tokens to a string
+/// Write the display tokens of a tree to a string
 pub fn write_tokens<'arena, Node: Ast<'arena>>(
     root: &'arena Node,
     string: &mut String,
     format_style: &Node::FormatStyle,
 ) {
+    write_token_stream(root.display_tokens(format_style), string);
+}
+
+/// Write a stream of display tokens to a string
+pub fn write_token_stream<T>(tokens: Vec<(T, DisplayToken)>, string: &mut String) {
+    write_indented_token_stream(tokens, string, Indentation::default());
+}
+
+/// Write a stream of display tokens to a string, indenting each level by `indentation`
+pub fn write_indented_token_stream<T>(
+    tokens: Vec<(T, DisplayToken)>,
+    string: &mut String,
+    indentation: Indentation,
+) {
+    let unit = indentation.unit();
     let mut indentation_string = String::new();
 
     // Process the token string
-    for (_id, tok) in root.display_tokens(format_style) {
+    for (_id, tok) in tokens {
         match tok {
-            DisplayToken::Text(s) => {
+            DisplayToken::Text(s)
+            | DisplayToken::Categorised(s, _)
+            | DisplayToken::OpenDelim(s)
+            | DisplayToken::CloseDelim(s) => {
                 // Push the string we've been given
                 string.push_str(&s);
             }
@@ -53,18 +122,127 @@ pub fn write_tokens<'arena, Node: Ast<'arena>>(
                 string.push_str(&indentation_string);
             }
             DisplayToken::Indent => {
-                // Add `INDENT_WIDTH` spaces to the indentation_string
-                for _ in 0..INDENT_WIDTH {
-                    indentation_string.push(' ');
-                }
+                // Add one level of indentation to the indentation_string
+                indentation_string.push_str(&unit);
             }
             DisplayToken::Dedent => {
-                // Remove `INDENT_WIDTH` spaces to the indentation_string
-                for _ in 0..INDENT_WIDTH {
-                    let popped_char = indentation_string.pop();
-                    debug_assert_eq!(popped_char, Some(' '));
+                // Remove one level of indentation from the indentation_string
+                debug_assert!(indentation_string.ends_with(&unit));
+                let len = indentation_string.len().saturating_sub(unit.len());
+                indentation_string.truncate(len);
+            }
+        }
+    }
+}
+
+/// The information about a single delimiter token, as found by [`match_delimiters`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DelimiterMatch {
+    /// How many pairs of delimiters enclose this delimiter's pair.  The outermost pairs have depth
+    /// `0`.
+    pub depth: usize,
+    /// The index of the other delimiter in this delimiter's pair
+    pub partner: usize,
+}
+
+/// Colours every categorised token of a stream of display tokens as `category`
+pub fn set_category<T>(tokens: &mut [(T, DisplayToken)], category: SyntaxCategory) {
+    for (_, tok) in tokens {
+        if let DisplayToken::Categorised(_, tok_category) = tok {
+            *tok_category = category;
+        }
+    }
+}
+
+/// Pairs up the delimiters in a stream of display tokens.  This returns a [`Vec`] with one entry
+/// per token, which is [`Some`] for every delimiter that has a partner, and [`None`] for all other
+/// tokens.
+pub fn match_delimiters<T>(tokens: &[(T, DisplayToken)]) -> Vec<Option<DelimiterMatch>> {
+    let mut matches = vec![None; tokens.len()];
+    // The indices of the delimiters which are still waiting to be closed
+    let mut open_delims: Vec<usize> = Vec::new();
+    for (i, (_, tok)) in tokens.iter().enumerate() {
+        match tok {
+            DisplayToken::OpenDelim(_) => open_delims.push(i),
+            DisplayToken::CloseDelim(_) => {
+                // A close delimiter with no partner is left unmatched
+                if let Some(open_index) = open_delims.pop() {
+                    let depth = open_delims.len();
+                    matches[open_index] = Some(DelimiterMatch { depth, partner: i });
+                    matches[i] = Some(DelimiterMatch {
+                        depth,
+                        partner: open_index,
+                    });
                 }
             }
+            _ => {}
         }
     }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        match_delimiters, write_indented_token_stream, DelimiterMatch, DisplayToken, Indentation,
+    };
+
+    #[test]
+    fn indentation() {
+        let tokens = || {
+            vec![
+                DisplayToken::OpenDelim("[".to_string()),
+                DisplayToken::Indent,
+                DisplayToken::Newline,
+                DisplayToken::Text("true".to_string()),
+                DisplayToken::Dedent,
+                DisplayToken::Newline,
+                DisplayToken::CloseDelim("]".to_string()),
+            ]
+            .into_iter()
+            .map(|tok| ((), tok))
+            .collect()
+        };
+        for (indentation, expected) in &[
+            (Indentation::default(), "[\n    true\n]"),
+            (Indentation::Spaces(2), "[\n  true\n]"),
+            (Indentation::Tabs, "[\n\ttrue\n]"),
+        ] {
+            let mut text = String::new();
+            write_indented_token_stream(tokens(), &mut text, *indentation);
+            assert_eq!(text, *expected);
+        }
+    }
+
+    #[test]
+    fn delimiters() {
+        let open = || ((), DisplayToken::OpenDelim("[".to_string()));
+        let close = || ((), DisplayToken::CloseDelim("]".to_string()));
+        let text = || ((), DisplayToken::Text("true".to_string()));
+        // [true, [[]]] followed by an unmatched ']'
+        let tokens = vec![
+            open(),
+            text(),
+            open(),
+            open(),
+            close(),
+            close(),
+            close(),
+            close(),
+        ];
+        let m = |depth, partner| Some(DelimiterMatch { depth, partner });
+        assert_eq!(
+            match_delimiters(&tokens),
+            vec![
+                m(0, 6),
+                None,
+                m(1, 5),
+                m(2, 4),
+                m(2, 3),
+                m(1, 2),
+                m(0, 0),
+                None
+            ]
+        );
+    }
 }
@@ -0,0 +1,67 @@
+//! Detection of duplicated siblings, like repeated keys in a JSON object or identical elements of
+//! a JSON array.
+
+use super::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use std::collections::HashMap;
+
+/// Returns the paths to every node in the tree under `root` which has a duplicate sibling, in
+/// depth-first pre-order.  Two siblings are duplicates if they are identical subtrees, or if they
+/// have the same [`outline_label`](Ast::outline_label) (e.g. JSON fields with the same key).
+pub fn duplicate_siblings<'arena, Node: Ast<'arena>>(root: &'arena Node) -> Vec<CursorPath> {
+    let mut paths = Vec::new();
+    for (path, node) in cursor_path::all_paths(root) {
+        let children = node.children();
+        let labels: Vec<Option<String>> = children.iter().map(|c| c.outline_label()).collect();
+        let mut subtree_counts: HashMap<&Node, usize> = HashMap::new();
+        let mut label_counts: HashMap<&str, usize> = HashMap::new();
+        for (child, label) in children.iter().zip(&labels) {
+            *subtree_counts.entry(*child).or_default() += 1;
+            if let Some(label) = label {
+                *label_counts.entry(label).or_default() += 1;
+            }
+        }
+        for (i, (child, label)) in children.iter().zip(&labels).enumerate() {
+            let is_duplicate_label = label
+                .as_deref()
+                .is_some_and(|label| label_counts[label] > 1);
+            if subtree_counts[child] > 1 || is_duplicate_label {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                paths.push(child_path);
+            }
+        }
+    }
+    // `all_paths` visits parents in pre-order, but their children have to be put in order too
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::duplicate_siblings;
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+
+    #[test]
+    fn duplicates() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Object(vec![
+                ("a".to_string(), TestJSON::True),
+                ("b".to_string(), TestJSON::True),
+                ("a".to_string(), TestJSON::False),
+            ]),
+            TestJSON::True,
+            TestJSON::Array(vec![TestJSON::False]),
+            TestJSON::True,
+        ])
+        .add_to_arena(&arena);
+        let paths: Vec<Vec<usize>> = duplicate_siblings(root)
+            .iter()
+            .map(|path| path.iter().copied().collect())
+            .collect();
+        // The two `true`s in the object are values of different fields, so aren't siblings
+        assert_eq!(paths, vec![vec![0, 0], vec![0, 2], vec![1], vec![3]]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Deterministic sample trees of a configurable size and shape, for demos, screenshots and tests.
+//! The same [`Shape`] always generates the same tree, so that anything built on them is
+//! reproducible.
+
+use super::test_json::TestJSON;
+
+/// The keys given to the fields of generated JSON objects
+const KEYS: &[&str] = &[
+    "id", "name", "enabled", "tags", "owner", "items", "visible", "meta",
+];
+
+/// The size and shape of a generated sample tree
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Shape {
+    /// How many levels of nodes with children are above the leaves
+    pub depth: usize,
+    /// How many children each node with children has
+    pub breadth: usize,
+    /// Decides which kinds of nodes are generated.  Different seeds give different trees of the
+    /// same shape.
+    pub seed: u64,
+}
+
+impl Shape {
+    /// The shape of the tree shown when Sapling is started without a file
+    pub const DEMO: Shape = Shape {
+        depth: 2,
+        breadth: 3,
+        seed: 0,
+    };
+
+    /// Creates a shape with the default seed
+    pub fn new(depth: usize, breadth: usize) -> Shape {
+        Shape {
+            depth,
+            breadth,
+            seed: 0,
+        }
+    }
+}
+
+/// A tiny pseudo-random number generator (SplitMix64), so that generated trees look varied
+/// without depending on a random number crate or changing between runs
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Generates a JSON tree of the given shape.  Nodes with children are a mixture of arrays and
+/// objects (whose fields don't count towards the depth), and the leaves are `true` or `false`.
+pub fn json(shape: Shape) -> TestJSON {
+    json_rec(&mut Rng(shape.seed), shape.depth, shape.breadth)
+}
+
+fn json_rec(rng: &mut Rng, depth: usize, breadth: usize) -> TestJSON {
+    let heads = (rng.next() >> 32) & 1 == 0;
+    if depth == 0 {
+        return if heads {
+            TestJSON::True
+        } else {
+            TestJSON::False
+        };
+    }
+    let children = (0..breadth).map(|_| json_rec(rng, depth - 1, breadth));
+    if heads {
+        TestJSON::Array(children.collect())
+    } else {
+        let fields = children.enumerate().map(|(i, child)| (key(i), child));
+        TestJSON::Object(fields.collect())
+    }
+}
+
+/// Returns the key of the field at some index of a generated object.  Keys repeat after running
+/// out, so they're numbered to keep them unique.
+fn key(index: usize) -> String {
+    let key = KEYS[index % KEYS.len()];
+    match index / KEYS.len() {
+        0 => key.to_string(),
+        n => format!("{}{}", key, n + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json, key, Shape};
+    use crate::ast::test_json::TestJSON;
+    use std::collections::HashSet;
+
+    /// Returns the depth of a tree, and checks that every node with children has `breadth` of
+    /// them
+    fn check_shape(node: &TestJSON, breadth: usize) -> usize {
+        let children: Vec<&TestJSON> = match node {
+            TestJSON::True | TestJSON::False => return 0,
+            TestJSON::Array(children) => children.iter().collect(),
+            TestJSON::Object(fields) => fields.iter().map(|(_, child)| child).collect(),
+        };
+        assert_eq!(children.len(), breadth);
+        let depths: Vec<usize> = children.iter().map(|c| check_shape(c, breadth)).collect();
+        1 + depths.iter().copied().max().unwrap_or(0)
+    }
+
+    #[test]
+    fn fixtures() {
+        for &(depth, breadth) in &[(0, 3), (1, 1), (3, 4), (2, 10)] {
+            let tree = json(Shape::new(depth, breadth));
+            assert_eq!(check_shape(&tree, breadth), depth);
+            assert_eq!(tree, json(Shape::new(depth, breadth)));
+        }
+        let other_seed = Shape {
+            seed: 1,
+            ..Shape::DEMO
+        };
+        assert_ne!(json(Shape::DEMO), json(other_seed));
+        // Keys are unique, even when there are more fields than keys to choose from
+        let keys: HashSet<String> = (0..20).map(key).collect();
+        assert_eq!(keys.len(), 20);
+    }
+}
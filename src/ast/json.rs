@@ -1,6 +1,13 @@
-use super::display_token::{DisplayToken, RecTok};
+use super::commands::LanguageCommand;
+use super::display_token::{DisplayToken, RecTok, SyntaxCategory};
+use super::pattern::{self, Pattern};
+use super::schema::Schema;
 use super::size::Size;
+use super::structured::Fragment;
 use super::Ast;
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
 
 /// An enum to hold the different ways that a JSON AST can be formatted
 #[derive(Eq, PartialEq, Copy, Clone)]
@@ -10,6 +17,10 @@ pub enum JSONFormat {
     Compact,
     /// A prettified representation, with pretty indenting and every element on a newline.
     Pretty,
+    /// Like [`Pretty`](JSONFormat::Pretty), but with the elements of any array or object that
+    /// fits within the given number of columns joined onto one line.
+    /// E.g. `{"foo": [true, false], "bar": false}` when it fits
+    Fit(usize),
 }
 
 const CHAR_TRUE: char = 't';
@@ -33,13 +44,18 @@ pub enum JSON<'arena> {
     /// A JSON object, represented as a map of [`String`]s to more JSON values.
     /// Corresponds to a string `{"<key1>": <v1>, "<key2>": <v2>, ...}` where `<key1>`, `<key2>`,
     /// ... are the keys, and `<v1>`, `<v2>`, ... are the corresponding JSON values.  The `Ref`s
-    /// contained inside this must be [`Field`](JSON::Field)s.
+    /// contained inside this must be [`Field`](JSON::Field)s (or [`Error`](JSON::Error)s, where a
+    /// field couldn't be parsed).
     Object(Vec<&'arena JSON<'arena>>),
     /// A JSON object field.  The first `Ref` must be a [`Str`](JSON::Str), and the second is any
     /// JSON object
     Field([&'arena JSON<'arena>; 2]),
     /// A JSON string
     Str(String),
+    /// Text which couldn't be parsed when the file was opened (see
+    /// [`json_recover`](super::json_recover)), kept exactly as it was written so that it can be
+    /// fixed by hand
+    Error(String),
 }
 
 impl JSON<'_> {
@@ -53,6 +69,46 @@ impl JSON<'_> {
     }
 }
 
+/// Writes `string` as the text of a JSON string, in quotes and with every char which can't appear
+/// in one (`"`, `\` and control chars) escaped
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<'arena> JSON<'arena> {
+    /// Returns the part of `schema` (which describes this whole tree) that describes the node at
+    /// `path`, or [`None`] if the schema doesn't describe that node
+    fn schema_at<'s>(
+        &'arena self,
+        path: &CursorPath,
+        mut schema: &'s Schema,
+    ) -> Option<&'s Schema> {
+        let mut node = self;
+        for &index in path.iter() {
+            match node {
+                JSON::Array(_) => schema = schema.items.as_deref()?,
+                // Fields are described by their object's schema until their key is known
+                JSON::Object(_) => {}
+                JSON::Field([JSON::Str(key), _]) if index == 1 => schema = schema.property(key)?,
+                _ => return None,
+            }
+            node = node.children().get(index)?;
+        }
+        Some(schema)
+    }
+}
+
 impl Default for JSON<'_> {
     fn default() -> JSON<'static> {
         JSON::Object(vec![])
@@ -62,31 +118,70 @@ impl Default for JSON<'_> {
 impl<'arena> Ast<'arena> for JSON<'arena> {
     type FormatStyle = JSONFormat;
 
+    fn language_name() -> &'static str {
+        "json"
+    }
+
     /* FORMATTING FUNCTIONS */
 
+    fn max_line_width(format_style: &JSONFormat) -> Option<usize> {
+        match format_style {
+            JSONFormat::Fit(max_width) => Some(*max_width),
+            _ => None,
+        }
+    }
+
+    fn with_max_line_width(
+        format_style: &JSONFormat,
+        max_width: Option<usize>,
+    ) -> Option<JSONFormat> {
+        Some(match (format_style, max_width) {
+            (_, Some(max_width)) => JSONFormat::Fit(max_width),
+            (JSONFormat::Fit(_), None) => JSONFormat::Pretty,
+            (format_style, None) => *format_style,
+        })
+    }
+
     fn display_tokens_rec(
         &'arena self,
         format_style: &Self::FormatStyle,
     ) -> Vec<RecTok<'arena, Self>> {
-        let is_pretty = format_style == &JSONFormat::Pretty;
+        // Layouts with a width start from the pretty layout, and join lines which fit
+        let is_pretty = matches!(format_style, JSONFormat::Pretty | JSONFormat::Fit(_));
         match self {
-            JSON::True => vec![RecTok::Tok(DisplayToken::Text("true".to_string()))],
-            JSON::False => vec![RecTok::Tok(DisplayToken::Text("false".to_string()))],
-            JSON::Str(string) => vec![RecTok::Tok(DisplayToken::Text(format!(r#""{}""#, string)))],
+            JSON::True => vec![RecTok::Tok(DisplayToken::Categorised(
+                "true".to_string(),
+                SyntaxCategory::Keyword,
+            ))],
+            JSON::False => vec![RecTok::Tok(DisplayToken::Categorised(
+                "false".to_string(),
+                SyntaxCategory::Keyword,
+            ))],
+            JSON::Str(string) => vec![RecTok::Tok(DisplayToken::Categorised(
+                quote(string),
+                SyntaxCategory::Literal,
+            ))],
+            JSON::Error(text) => vec![RecTok::Tok(DisplayToken::Text(text.clone()))],
             JSON::Field([key, value]) => vec![
                 RecTok::Child(key),
-                RecTok::Tok(DisplayToken::Text(": ".to_string())),
+                RecTok::Tok(DisplayToken::Categorised(
+                    ": ".to_string(),
+                    SyntaxCategory::Punctuation,
+                )),
                 RecTok::Child(value),
             ],
             JSON::Array(children) => {
                 // Special case: if this array is empty, render it as '[]'
                 if children.is_empty() {
-                    return vec![RecTok::Tok(DisplayToken::Text("[]".to_string()))];
+                    return vec![
+                        RecTok::Tok(DisplayToken::OpenDelim("[".to_string())),
+                        RecTok::Tok(DisplayToken::CloseDelim("]".to_string())),
+                    ];
                 }
 
                 let mut tokens: Vec<RecTok<'_, Self>> = Vec::with_capacity(6 + 3 * children.len());
                 // Push some initial tokens
-                tokens.push(RecTok::Tok(DisplayToken::Text("[".to_string())));
+                tokens.push(RecTok::Tok(DisplayToken::OpenDelim("[".to_string())));
                 if is_pretty {
                     tokens.push(RecTok::Tok(DisplayToken::Indent));
                     tokens.push(RecTok::Tok(DisplayToken::Newline));
@@ -96,7 +191,10 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
                 for c in children {
                     // Push the delimiting
                     if !is_first_child {
-                        tokens.push(RecTok::Tok(DisplayToken::Text(",".to_string())));
+                        tokens.push(RecTok::Tok(DisplayToken::Categorised(
+                            ",".to_string(),
+                            SyntaxCategory::Punctuation,
+                        )));
                         if is_pretty {
                             tokens.push(RecTok::Tok(DisplayToken::Newline));
                         } else {
@@ -112,19 +210,22 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
                     tokens.push(RecTok::Tok(DisplayToken::Dedent));
                     tokens.push(RecTok::Tok(DisplayToken::Newline));
                 }
-                tokens.push(RecTok::Tok(DisplayToken::Text("]".to_string())));
+                tokens.push(RecTok::Tok(DisplayToken::CloseDelim("]".to_string())));
                 // Return the token stream
                 tokens
             }
             JSON::Object(fields) => {
                 // Special case: if this object is empty, render it as '{}'
                 if fields.is_empty() {
-                    return vec![RecTok::Tok(DisplayToken::Text("{}".to_string()))];
+                    return vec![
+                        RecTok::Tok(DisplayToken::OpenDelim("{".to_string())),
+                        RecTok::Tok(DisplayToken::CloseDelim("}".to_string())),
+                    ];
                 }
 
                 let mut tokens: Vec<RecTok<'_, Self>> = Vec::with_capacity(6 + 3 * fields.len());
                 // Push some initial tokens
-                tokens.push(RecTok::Tok(DisplayToken::Text("{".to_string())));
+                tokens.push(RecTok::Tok(DisplayToken::OpenDelim("{".to_string())));
                 if is_pretty {
                     tokens.push(RecTok::Tok(DisplayToken::Indent));
                     tokens.push(RecTok::Tok(DisplayToken::Newline));
@@ -134,7 +235,10 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
                 for f in fields {
                     // Push the delimiting
                     if !is_first_child {
-                        tokens.push(RecTok::Tok(DisplayToken::Text(",".to_string())));
+                        tokens.push(RecTok::Tok(DisplayToken::Categorised(
+                            ",".to_string(),
+                            SyntaxCategory::Punctuation,
+                        )));
                         if is_pretty {
                             tokens.push(RecTok::Tok(DisplayToken::Newline));
                         } else {
@@ -150,22 +254,30 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
                     tokens.push(RecTok::Tok(DisplayToken::Dedent));
                     tokens.push(RecTok::Tok(DisplayToken::Newline));
                 }
-                tokens.push(RecTok::Tok(DisplayToken::Text("}".to_string())));
+                tokens.push(RecTok::Tok(DisplayToken::CloseDelim("}".to_string())));
                 // Return the token stream
                 tokens
             }
         }
     }
 
+    fn child_category(&self, index: usize) -> Option<SyntaxCategory> {
+        match (self, index) {
+            (JSON::Field(_), 0) => Some(SyntaxCategory::Identifier),
+            _ => None,
+        }
+    }
+
     fn size(&self, format_style: &Self::FormatStyle) -> Size {
         match format_style {
-            JSONFormat::Pretty => {
+            // The lines which are joined in `Fit` layouts depend on where a node is in the tree, so
+            // the size is that of the pretty layout (which is never smaller)
+            JSONFormat::Pretty | JSONFormat::Fit(_) => {
                 match self {
                     JSON::True => Size::new(0, 4),  // same as Size::from("true")
                     JSON::False => Size::new(0, 5), // same as Size::from("false")
-                    JSON::Str(string) => {
-                        Size::new(0, 1) + Size::from(string.as_str()) + Size::new(0, 1)
-                    }
+                    JSON::Str(string) => Size::from(quote(string).as_str()),
+                    JSON::Error(text) => Size::from(text.as_str()),
                     JSON::Field([key, value]) => {
                         key.size(format_style) + Size::new(0, 2) + value.size(format_style)
                     }
@@ -209,9 +321,8 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
                 match self {
                     JSON::True => Size::new(0, 4),  // same as Size::from("true")
                     JSON::False => Size::new(0, 5), // same as Size::from("false")
-                    JSON::Str(string) => {
-                        Size::new(0, 1) + Size::from(string.as_str()) + Size::new(0, 1)
-                    }
+                    JSON::Str(string) => Size::from(quote(string).as_str()),
+                    JSON::Error(text) => Size::from(text.as_str()),
                     JSON::Field([key, value]) => {
                         key.size(format_style) + Size::new(0, 2) + value.size(format_style)
                     }
@@ -256,16 +367,16 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
 
     fn children<'s>(&'s self) -> &'s [&'arena JSON<'arena>] {
         match self {
-            JSON::True | JSON::False | JSON::Str(_) => &[],
-            JSON::Array(children) => &children,
-            JSON::Object(fields) => &fields,
+            JSON::True | JSON::False | JSON::Str(_) | JSON::Error(_) => &[],
+            JSON::Array(children) => children,
+            JSON::Object(fields) => fields,
             JSON::Field(key_value) => &key_value[..],
         }
     }
 
     fn children_mut<'s>(&'s mut self) -> &'s mut [&'arena JSON<'arena>] {
         match self {
-            JSON::True | JSON::False | JSON::Str(_) => &mut [],
+            JSON::True | JSON::False | JSON::Str(_) | JSON::Error(_) => &mut [],
             JSON::Array(children) => children,
             JSON::Object(fields) => fields,
             JSON::Field(key_value) => &mut key_value[..],
@@ -279,8 +390,221 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
             JSON::Array(_) => "array".to_string(),
             JSON::Object(_) => "object".to_string(),
             JSON::Field(_) => "field".to_string(),
-            JSON::Str(content) => format!(r#""{}""#, content),
+            JSON::Str(content) => quote(content),
+            JSON::Error(text) => text.clone(),
+        }
+    }
+
+    fn summary(&'arena self) -> String {
+        /// Formats a count along with a noun, pluralising the noun if necessary
+        fn count(n: usize, noun: &str) -> String {
+            format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+        }
+
+        match self {
+            JSON::True | JSON::False | JSON::Str(_) | JSON::Error(_) => self.display_name(),
+            JSON::Array(children) => format!("[ {} ]", count(children.len(), "item")),
+            JSON::Object(fields) => format!("{{ {} }}", count(fields.len(), "key")),
+            JSON::Field([key, value]) => format!("{}: {}", key.display_name(), value.summary()),
+        }
+    }
+
+    fn is_error(&self) -> bool {
+        matches!(self, JSON::Error(_))
+    }
+
+    fn outline_label(&self) -> Option<String> {
+        match self {
+            // Fields are labelled by their keys, which are always the first child
+            JSON::Field([key, _]) => Some(key.display_name()),
+            _ => None,
+        }
+    }
+
+    /* STRUCTURED SERIALIZATION FUNCTIONS */
+
+    fn kind_name(&self) -> &'static str {
+        match self {
+            JSON::True => "true",
+            JSON::False => "false",
+            JSON::Array(_) => "array",
+            JSON::Object(_) => "object",
+            JSON::Field(_) => "field",
+            JSON::Str(_) => "string",
+            JSON::Error(_) => "error",
+        }
+    }
+
+    fn matches(&'arena self, pattern: &Pattern, format_style: &Self::FormatStyle) -> bool {
+        match pattern {
+            // `true` and `false` are separate kinds, but are usually looked for together
+            Pattern::Kind(name) if name == "bool" => matches!(self, JSON::True | JSON::False),
+            _ => pattern::matches_by_default(self, pattern, format_style),
+        }
+    }
+
+    fn value(&self) -> Option<String> {
+        match self {
+            JSON::Str(content) | JSON::Error(content) => Some(content.clone()),
+            _ => None,
+        }
+    }
+
+    fn from_parts(
+        kind: &str,
+        value: Option<&str>,
+        children: Vec<&'arena JSON<'arena>>,
+    ) -> Option<Self> {
+        match (kind, value, children.as_slice()) {
+            ("true", None, []) => Some(JSON::True),
+            ("false", None, []) => Some(JSON::False),
+            ("string", Some(content), []) => Some(JSON::Str(content.to_string())),
+            ("error", Some(text), []) => Some(JSON::Error(text.to_string())),
+            // Fields can only be inside objects
+            ("array", None, values) if !values.iter().any(|v| matches!(v, JSON::Field(_))) => {
+                Some(JSON::Array(children))
+            }
+            // Objects can only contain fields, or text where a field couldn't be parsed
+            ("object", None, fields)
+                if fields
+                    .iter()
+                    .all(|f| matches!(f, JSON::Field(_) | JSON::Error(_))) =>
+            {
+                Some(JSON::Object(children))
+            }
+            // Fields must have a string as their key
+            ("field", None, [key @ JSON::Str(_), value]) => Some(JSON::Field([key, value])),
+            _ => None,
+        }
+    }
+
+    fn is_valid_child(&self, index: usize, child: &Self) -> bool {
+        // These follow the rules of `from_parts`
+        match self {
+            JSON::Array(_) => !matches!(child, JSON::Field(_)),
+            JSON::Object(_) => matches!(child, JSON::Field(_) | JSON::Error(_)),
+            JSON::Field(_) => match index {
+                0 => matches!(child, JSON::Str(_)),
+                1 => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn parse_fragment(text: &str) -> Option<Fragment> {
+        super::json_stream::parse_fragment(text)
+    }
+
+    fn named_format_style(name: &str) -> Option<JSONFormat> {
+        match name {
+            "compact" => Some(JSONFormat::Compact),
+            "pretty" => Some(JSONFormat::Pretty),
+            _ => None,
+        }
+    }
+
+    fn new_file_template() -> Option<&'static str> {
+        Some("{}")
+    }
+
+    /* EVALUATION FUNCTIONS */
+
+    /// JSON expressions are paths into the tree, made of object keys and array indices separated
+    /// by `.` (e.g. `foo.0.bar`).  An empty path evaluates to the whole tree.
+    fn evaluate(&'arena self, expression: &str) -> Option<Result<String, String>> {
+        let mut node = self;
+        for segment in expression.trim().split('.').filter(|s| !s.is_empty()) {
+            let child = match node {
+                JSON::Array(values) => segment.parse::<usize>().ok().and_then(|i| values.get(i)),
+                JSON::Object(fields) => fields.iter().find_map(|field| match field {
+                    JSON::Field([JSON::Str(key), value]) if key == segment => Some(value),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            node = match child {
+                Some(child) => child,
+                None => {
+                    return Some(Err(format!(
+                        "{} has no item '{}'",
+                        node.display_name(),
+                        segment
+                    )))
+                }
+            };
+        }
+        Some(Ok(node.to_text(&JSONFormat::Compact)))
+    }
+
+    fn query(&'arena self, query: &str) -> Option<Result<Vec<CursorPath>, Message>> {
+        Some(super::json_query::query(self, query))
+    }
+
+    /* REFERENCE FUNCTIONS */
+
+    fn resolve_reference(&'arena self, path: &CursorPath) -> Option<Result<CursorPath, Message>> {
+        super::json_ref::resolve(self, path)
+    }
+
+    fn rename_symbol(
+        &'arena self,
+        path: &CursorPath,
+        new_name: &str,
+    ) -> Option<Result<Vec<(CursorPath, Self)>, Message>> {
+        super::json_ref::rename(self, path, new_name)
+    }
+
+    /* LANGUAGE COMMANDS */
+
+    fn language_commands() -> Vec<LanguageCommand<'arena, Self>> {
+        super::json_commands::commands()
+    }
+
+    /* SCHEMA FUNCTIONS */
+
+    fn schema_keys<'s>(
+        &'arena self,
+        path: &CursorPath,
+        schema: &'s Schema,
+    ) -> Vec<(&'s str, &'s Schema)> {
+        let (fields, object_schema) = match (path.cursor(self), self.schema_at(path, schema)) {
+            (JSON::Object(fields), Some(object_schema)) => (fields, object_schema),
+            _ => return Vec::new(),
+        };
+        object_schema
+            .properties
+            .iter()
+            .filter(|(key, _)| {
+                !fields.iter().any(|field| match field {
+                    JSON::Field([JSON::Str(k), _]) => k == key,
+                    _ => false,
+                })
+            })
+            .map(|(key, schema)| (key.as_str(), schema))
+            .collect()
+    }
+
+    fn new_child_from_schema(
+        &self,
+        key: &str,
+        schema: &Schema,
+        arena: &'arena Arena<Self>,
+    ) -> Option<(Self, CursorPath)> {
+        if !matches!(self, JSON::Object(_)) {
+            return None;
         }
+        // This subset of JSON has no numbers or nulls, so values of those types start out as
+        // empty strings
+        let value = match schema.kind.as_deref() {
+            Some("boolean") => JSON::False,
+            Some("array") => JSON::Array(vec![]),
+            Some("object") => JSON::Object(vec![]),
+            _ => JSON::Str(String::new()),
+        };
+        let field = JSON::Field([arena.alloc(JSON::Str(key.to_string())), arena.alloc(value)]);
+        // The key is already filled in, so the cursor goes to the value
+        Some((field, CursorPath::from_vec(vec![1])))
     }
 
     /* AST EDITING FUNCTIONS */
@@ -302,22 +626,216 @@ impl<'arena> Ast<'arena> for JSON<'arena> {
 
     fn insert_chars(&self) -> Box<dyn Iterator<Item = char>> {
         match self {
-            JSON::True | JSON::False | JSON::Field(_) | JSON::Str(_) => {
+            JSON::True | JSON::False | JSON::Field(_) | JSON::Str(_) | JSON::Error(_) => {
                 Box::new(std::iter::empty())
             }
             JSON::Object(_) => Box::new(std::iter::once(CHAR_FIELD)),
             JSON::Array(_) => Self::all_object_chars(),
         }
     }
+
+    fn new_child(&self, c: char, arena: &'arena Arena<Self>) -> Option<(Self, CursorPath)> {
+        if !self.is_insert_char(c) {
+            return None;
+        }
+        match self {
+            // New fields get an empty key and value, and the cursor goes to the key
+            JSON::Object(_) => {
+                let key = arena.alloc(JSON::Str(String::new()));
+                let value = arena.alloc(JSON::Str(String::new()));
+                Some((JSON::Field([key, value]), CursorPath::from_vec(vec![0])))
+            }
+            _ => Some((self.from_char(c)?, CursorPath::root())),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::display_token::{DisplayToken, SyntaxCategory};
+    use super::super::json_recover;
+    use super::super::schema::Schema;
     use super::super::size::Size;
     use super::super::test_json::TestJSON;
-    use super::JSONFormat;
+    use super::{JSONFormat, JSON};
     use crate::arena::Arena;
     use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn summary() {
+        for (tree, expected_summary) in &[
+            (TestJSON::True, "true"),
+            (TestJSON::Array(vec![]), "[ 0 items ]"),
+            (TestJSON::Array(vec![TestJSON::True]), "[ 1 item ]"),
+            (
+                TestJSON::Object(vec![
+                    ("foo".to_string(), TestJSON::True),
+                    ("bar".to_string(), TestJSON::Array(vec![TestJSON::False])),
+                ]),
+                "{ 2 keys }",
+            ),
+        ] {
+            let arena = Arena::new();
+            let root = tree.add_to_arena(&arena);
+            assert_eq!(root.summary(), *expected_summary);
+        }
+        // Fields are summarised by their key and a summary of their value
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![(
+            "foo".to_string(),
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+        )])
+        .add_to_arena(&arena);
+        assert_eq!(root.children()[0].summary(), r#""foo": [ 2 items ]"#);
+    }
+
+    #[test]
+    fn new_child() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::Object(vec![])]).add_to_arena(&arena);
+        let object = root.children()[0];
+        // Fields are created with an empty key and value, and the cursor goes to the key
+        let (field, path) = object.new_child('i', &arena).unwrap();
+        assert_eq!(field.to_text(&JSONFormat::Compact), r#""": """#);
+        assert_eq!(path.iter().copied().collect::<Vec<_>>(), vec![0]);
+        let (child, path) = root.new_child('t', &arena).unwrap();
+        assert_eq!(child, JSON::True);
+        assert!(path.is_root());
+        assert!(object.new_child('t', &arena).is_none());
+        assert!(root.new_child('i', &arena).is_none());
+    }
+
+    #[test]
+    fn schema_keys() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::Object(vec![(
+            "name".to_string(),
+            TestJSON::Object(vec![]),
+        )])])
+        .add_to_arena(&arena);
+        let schema = Schema::parse(
+            r#"{"type": "array", "items": {"properties": {
+                "name": {"type": "object", "properties": {"first": {"type": "string"}}},
+                "admin": {"type": "boolean"}
+            }}}"#,
+        )
+        .unwrap();
+        let keys = |path: Vec<usize>| -> Vec<&str> {
+            root.schema_keys(&CursorPath::from_vec(path), &schema)
+                .iter()
+                .map(|(key, _)| *key)
+                .collect()
+        };
+        // Keys which the object already has aren't suggested
+        assert_eq!(keys(vec![0]), vec!["admin"]);
+        assert_eq!(keys(vec![0, 0, 1]), vec!["first"]);
+        assert!(keys(vec![]).is_empty());
+        assert!(keys(vec![0, 0, 0]).is_empty());
+        // New fields are created with values of the right kind
+        let object = root.children()[0];
+        let admin = schema.items.as_ref().unwrap().property("admin").unwrap();
+        let (field, path) = object
+            .new_child_from_schema("admin", admin, &arena)
+            .unwrap();
+        assert_eq!(field.to_text(&JSONFormat::Compact), r#""admin": false"#);
+        assert_eq!(path.iter().copied().collect::<Vec<_>>(), vec![1]);
+        assert!(root.new_child_from_schema("admin", admin, &arena).is_none());
+    }
+
+    #[test]
+    fn evaluate() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![
+            (
+                "foo".to_string(),
+                TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+            ),
+            ("bar".to_string(), TestJSON::Object(vec![])),
+        ])
+        .add_to_arena(&arena);
+        for (expression, expected_result) in &[
+            ("", Ok(r#"{"foo": [true, false], "bar": {}}"#)),
+            ("foo", Ok("[true, false]")),
+            (" foo.1 ", Ok("false")),
+            ("bar", Ok("{}")),
+            ("baz", Err("object has no item 'baz'")),
+            ("foo.2", Err("array has no item '2'")),
+            ("foo.0.x", Err("true has no item 'x'")),
+        ] {
+            assert_eq!(
+                root.evaluate(expression),
+                Some(expected_result.map(str::to_string).map_err(str::to_string))
+            );
+        }
+    }
+
+    #[test]
+    fn categories() {
+        let arena = Arena::new();
+        let fragment = JSON::parse_fragment(r#"{"a": ["b", true]}"#).unwrap();
+        let root: &JSON = arena.alloc(fragment.node.to_node(&arena).unwrap());
+        let categorised: Vec<(String, SyntaxCategory)> = root
+            .display_tokens(&JSONFormat::Compact)
+            .into_iter()
+            .filter_map(|(_, tok)| match tok {
+                DisplayToken::Categorised(text, category) => Some((text, category)),
+                _ => None,
+            })
+            .collect();
+        // Keys are coloured as identifiers, even though they're strings
+        assert_eq!(
+            categorised,
+            vec![
+                ("\"a\"".to_string(), SyntaxCategory::Identifier),
+                (": ".to_string(), SyntaxCategory::Punctuation),
+                ("\"b\"".to_string(), SyntaxCategory::Literal),
+                (",".to_string(), SyntaxCategory::Punctuation),
+                ("true".to_string(), SyntaxCategory::Keyword),
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_children() {
+        let arena = Arena::new();
+        let field = TestJSON::Object(vec![("a".to_string(), TestJSON::True)]).add_to_arena(&arena);
+        let field = field.children()[0];
+        let array = JSON::Array(Vec::new());
+        let object = JSON::Object(Vec::new());
+        let key = JSON::Str("b".to_string());
+        // These agree with `from_parts`
+        assert!(!array.is_valid_child(0, field));
+        assert!(array.is_valid_child(0, &JSON::True));
+        assert!(object.is_valid_child(3, field));
+        assert!(!object.is_valid_child(0, &JSON::True));
+        assert!(field.is_valid_child(0, &key));
+        assert!(!field.is_valid_child(0, &JSON::True));
+        assert!(field.is_valid_child(1, &JSON::True));
+        assert!(!field.is_valid_child(2, &JSON::True));
+        assert!(!JSON::True.is_valid_child(0, &JSON::True));
+    }
+
+    #[test]
+    fn escapes() {
+        let arena = Arena::new();
+        let parse = |text: &str| -> &JSON {
+            let recovered = json_recover::parse(text).unwrap();
+            assert_eq!(recovered.errors, vec![]);
+            arena.alloc(recovered.node.to_node(&arena).unwrap())
+        };
+        let root = parse(r#"{"a\"b": ["c\\d", "e\nf\u0001", "é\/"]}"#);
+        let text = root.to_text(&JSONFormat::Compact);
+        assert_eq!(
+            text,
+            "{\"a\\\"b\": [\"c\\\\d\", \"e\\u000af\\u0001\", \"\u{e9}/\"]}"
+        );
+        assert_eq!(root.size(&JSONFormat::Compact), Size::from(text.as_str()));
+        // Writing the tree and reading it back gives the same tree
+        assert_eq!(parse(&text), root);
+        // Keys are escaped wherever they're shown
+        assert_eq!(root.children()[0].outline_label().unwrap(), r#""a\"b""#);
+    }
 
     #[test]
     fn to_text() {
@@ -416,4 +934,25 @@ mod tests {
             assert_eq!(s, *tree_string);
         }
     }
+
+    #[test]
+    fn subtree_size() {
+        let arena = Arena::new();
+        for (tree, expected_size) in &[
+            (TestJSON::True, 1),
+            (TestJSON::Array(vec![]), 1),
+            (TestJSON::Array(vec![TestJSON::True, TestJSON::False]), 3),
+            (
+                TestJSON::Object(vec![
+                    ("a".to_string(), TestJSON::True),
+                    ("b".to_string(), TestJSON::Array(vec![TestJSON::False])),
+                ]),
+                // The object, two fields with a key and a value each, and the array's element
+                8,
+            ),
+        ] {
+            let root = tree.add_to_arena(&arena);
+            assert_eq!(root.subtree_size(), *expected_size);
+        }
+    }
 }
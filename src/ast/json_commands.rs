@@ -0,0 +1,234 @@
+//! The [language commands](super::commands) of JSON.
+
+use super::commands::LanguageCommand;
+use super::json::JSON;
+use super::Ast;
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use crate::messages::Message;
+
+/// Returns every command which JSON provides
+pub fn commands<'arena>() -> Vec<LanguageCommand<'arena, JSON<'arena>>> {
+    vec![
+        LanguageCommand {
+            name: "sort-keys",
+            description: Message::DescribeSortKeys,
+            run: sort_keys,
+        },
+        LanguageCommand {
+            name: "wrap-in-array",
+            description: Message::DescribeWrapInArray,
+            run: wrap_in_array,
+        },
+        LanguageCommand {
+            name: "reparse",
+            description: Message::DescribeReparse,
+            run: reparse,
+        },
+    ]
+}
+
+/// Sorts the fields of every object under the cursor by their keys.  Fields with the same key
+/// stay in the same order.
+fn sort_keys<'arena>(
+    root: &'arena JSON<'arena>,
+    path: &CursorPath,
+    arena: &'arena Arena<JSON<'arena>>,
+) -> Result<Vec<(CursorPath, JSON<'arena>)>, Message> {
+    let cursor = path.cursor(root);
+    let has_object = cursor_path::all_paths(cursor)
+        .iter()
+        .any(|(_, node)| matches!(node, JSON::Object(_)));
+    if !has_object {
+        return Err(Message::NoObjectsToSort);
+    }
+    Ok(vec![(path.clone(), sorted(cursor, arena))])
+}
+
+/// Returns a copy of `node` with the fields of every object in it sorted by key
+fn sorted<'arena>(node: &'arena JSON<'arena>, arena: &'arena Arena<JSON<'arena>>) -> JSON<'arena> {
+    let sort_children = |children: &[&'arena JSON<'arena>]| -> Vec<&'arena JSON<'arena>> {
+        children
+            .iter()
+            .map(|child| arena.alloc(sorted(child, arena)))
+            .collect()
+    };
+    match node {
+        JSON::Object(fields) => {
+            let mut fields = sort_children(fields);
+            fields.sort_by_key(|field| field.outline_label());
+            JSON::Object(fields)
+        }
+        JSON::Array(elements) => JSON::Array(sort_children(elements)),
+        JSON::Field([key, value]) => JSON::Field([*key, arena.alloc(sorted(value, arena))]),
+        _ => node.clone(),
+    }
+}
+
+/// Wraps the value under the cursor in an array.  The cursor can also be on a field, whose value
+/// is wrapped.
+fn wrap_in_array<'arena>(
+    root: &'arena JSON<'arena>,
+    path: &CursorPath,
+    arena: &'arena Arena<JSON<'arena>>,
+) -> Result<Vec<(CursorPath, JSON<'arena>)>, Message> {
+    let mut path = path.clone();
+    let (cursor, parent) = path.cursor_and_parent(root);
+    let value = match cursor {
+        JSON::Field([_, value]) => {
+            path.push(1);
+            *value
+        }
+        _ => cursor,
+    };
+    // The key of a field isn't a value
+    if let Some(JSON::Field([key, _])) = parent {
+        if std::ptr::eq(*key, value) {
+            return Err(Message::CannotWrapKey);
+        }
+    }
+    Ok(vec![(path, JSON::Array(vec![arena.alloc(value.clone())]))])
+}
+
+/// Replaces the error node under the cursor with the JSON which its text now parses as.  Error
+/// nodes inside objects stand for fields, so their text has to parse as a field.
+fn reparse<'arena>(
+    root: &'arena JSON<'arena>,
+    path: &CursorPath,
+    arena: &'arena Arena<JSON<'arena>>,
+) -> Result<Vec<(CursorPath, JSON<'arena>)>, Message> {
+    let (cursor, parent) = path.cursor_and_parent(root);
+    let text = match cursor {
+        JSON::Error(text) => text,
+        _ => return Err(Message::NotErrorText),
+    };
+    let is_field = matches!(parent, Some(JSON::Object(_)));
+    let wrapped;
+    let text = if is_field {
+        wrapped = format!("{{{}}}", text);
+        &wrapped
+    } else {
+        text
+    };
+    let node = JSON::parse_fragment(text)
+        .filter(|fragment| fragment.holes.is_empty() && !fragment.is_truncated)
+        .and_then(|fragment| fragment.node.to_node(arena))
+        .ok_or(Message::StillInvalid)?;
+    let node = match node {
+        JSON::Object(fields) if is_field => match fields.as_slice() {
+            [field] => (*field).clone(),
+            _ => return Err(Message::NotSingleField),
+        },
+        node => node,
+    };
+    Ok(vec![(path.clone(), node)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::commands;
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::restructure;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::messages::Message;
+
+    /// Runs the JSON command with a given name on `text` with the cursor at `path`, returning the
+    /// text of the edited tree
+    fn run(name: &str, text: &str, path: Vec<usize>) -> Result<String, Message> {
+        let arena = Arena::new();
+        let root: &JSON = arena.alloc(
+            JSON::parse_fragment(text)
+                .unwrap()
+                .node
+                .to_node(&arena)
+                .unwrap(),
+        );
+        let command = commands().into_iter().find(|c| c.name == name).unwrap();
+        let replacements = (command.run)(root, &CursorPath::from_vec(path), &arena)?;
+        let new_root = restructure::replace_each(&arena, root, replacements).unwrap();
+        Ok(arena.alloc(new_root).to_text(&JSONFormat::Compact))
+    }
+
+    #[test]
+    fn sort_keys() {
+        assert_eq!(
+            run(
+                "sort-keys",
+                r#"{"b": true, "a": [{"z": true, "y": false}]}"#,
+                vec![]
+            ),
+            Ok(r#"{"a": [{"y": false, "z": true}], "b": true}"#.to_string())
+        );
+        // Only the cursor's subtree is sorted
+        assert_eq!(
+            run(
+                "sort-keys",
+                r#"{"b": {"d": true, "c": true}, "a": true}"#,
+                vec![0, 1]
+            ),
+            Ok(r#"{"b": {"c": true, "d": true}, "a": true}"#.to_string())
+        );
+        assert_eq!(
+            run("sort-keys", "[true]", vec![]),
+            Err(Message::NoObjectsToSort)
+        );
+    }
+
+    #[test]
+    fn reparse() {
+        let arena = Arena::new();
+        // Runs `reparse` on text which couldn't be parsed, inside an object or an array
+        let run = |in_object: bool, text: &str| -> Result<String, Message> {
+            let error: &JSON = arena.alloc(JSON::Error(text.to_string()));
+            let root: &JSON = arena.alloc(if in_object {
+                JSON::Object(vec![error])
+            } else {
+                JSON::Array(vec![error])
+            });
+            let command = commands()
+                .into_iter()
+                .find(|c| c.name == "reparse")
+                .unwrap();
+            let replacements = (command.run)(root, &CursorPath::from_vec(vec![0]), &arena)?;
+            let new_root = restructure::replace_each(&arena, root, replacements).unwrap();
+            Ok(arena.alloc(new_root).to_text(&JSONFormat::Compact))
+        };
+        assert_eq!(
+            run(false, "[true, false]"),
+            Ok("[[true, false]]".to_string())
+        );
+        // Text inside an object is parsed as a field
+        assert_eq!(run(true, r#""a": true"#), Ok(r#"{"a": true}"#.to_string()));
+        assert_eq!(
+            run(true, r#""a": true, "b": true"#),
+            Err(Message::NotSingleField)
+        );
+        assert_eq!(run(false, "[true,"), Err(Message::StillInvalid));
+        assert_eq!(
+            super::reparse(
+                arena.alloc(JSON::Array(vec![arena.alloc(JSON::True)])),
+                &CursorPath::from_vec(vec![0]),
+                &arena
+            ),
+            Err(Message::NotErrorText)
+        );
+    }
+
+    #[test]
+    fn wrap_in_array() {
+        assert_eq!(
+            run("wrap-in-array", r#"{"a": true}"#, vec![0]),
+            Ok(r#"{"a": [true]}"#.to_string())
+        );
+        assert_eq!(
+            run("wrap-in-array", "[false]", vec![0]),
+            Ok("[[false]]".to_string())
+        );
+        assert_eq!(
+            run("wrap-in-array", r#"{"a": true}"#, vec![0, 0]),
+            Err(Message::CannotWrapKey)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! JQ-style path queries over [`JSON`] trees, like `.users[3].address.city`.
+//!
+//! A query is a sequence of steps, each of which is applied to every node matched so far:
+//! - `.key` or `."key"` or `["key"]` moves to the value of an object's field
+//! - `[n]` moves to an array's `n`th value (counting from the end if `n` is negative)
+//! - `[]` moves to every value of an array or object
+//!
+//! A lone `.` matches the whole tree.  Steps that don't apply to a node (e.g. indexing an object)
+//! simply don't match anything, rather than being errors.
+
+use super::json::JSON;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A single step of a query
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Step {
+    Key(String),
+    Index(isize),
+    Each,
+}
+
+/// Returns the paths to every node matched by a query, in the order that they appear in the tree.
+/// Returns an error message if the query isn't valid.
+pub fn query<'arena>(root: &'arena JSON<'arena>, query: &str) -> Result<Vec<CursorPath>, Message> {
+    let mut matches = vec![(CursorPath::root(), root)];
+    for step in parse(query)? {
+        matches = matches
+            .into_iter()
+            .flat_map(|(path, node)| apply(&step, path, node))
+            .collect();
+    }
+    Ok(matches.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Returns the nodes reached by taking a step from a given node
+fn apply<'arena>(
+    step: &Step,
+    path: CursorPath,
+    node: &'arena JSON<'arena>,
+) -> Vec<(CursorPath, &'arena JSON<'arena>)> {
+    let child = |indices: &[usize], child: &'arena JSON<'arena>| {
+        let mut child_path = path.clone();
+        child_path.extend(indices.iter().copied());
+        (child_path, child)
+    };
+    match (step, node) {
+        (Step::Key(key), JSON::Object(fields)) => fields
+            .iter()
+            .enumerate()
+            .filter_map(|(i, field)| match field {
+                JSON::Field([JSON::Str(k), value]) if k == key => Some(child(&[i, 1], value)),
+                _ => None,
+            })
+            .take(1)
+            .collect(),
+        (Step::Index(index), JSON::Array(values)) => {
+            let index = if *index < 0 {
+                values.len().checked_sub(index.unsigned_abs())
+            } else {
+                Some(*index as usize)
+            };
+            index
+                .and_then(|i| Some(child(&[i], values.get(i)?)))
+                .into_iter()
+                .collect()
+        }
+        (Step::Each, JSON::Array(values)) => values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| child(&[i], value))
+            .collect(),
+        (Step::Each, JSON::Object(fields)) => fields
+            .iter()
+            .enumerate()
+            .filter_map(|(i, field)| match field {
+                JSON::Field([_, value]) => Some(child(&[i, 1], value)),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Splits a query into its steps
+fn parse(query: &str) -> Result<Vec<Step>, Message> {
+    let mut chars = query.trim().chars().peekable();
+    let mut steps = Vec::new();
+    if chars.peek().is_none() {
+        return Err(Message::EmptyQuery);
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => match chars.peek() {
+                Some('"') => steps.push(Step::Key(string(&mut chars)?)),
+                Some(c) if is_key_char(*c) => {
+                    let mut key = String::new();
+                    while let Some(c) = chars.next_if(|c| is_key_char(*c)) {
+                        key.push(c);
+                    }
+                    steps.push(Step::Key(key));
+                }
+                // `.` on its own (or before `[`) doesn't move anywhere
+                _ => {}
+            },
+            '[' => {
+                let step = match chars.peek() {
+                    Some(']') => Step::Each,
+                    Some('"') => Step::Key(string(&mut chars)?),
+                    _ => {
+                        let mut number = String::new();
+                        while let Some(c) = chars.next_if(|c| *c != ']') {
+                            number.push(c);
+                        }
+                        match number.trim().parse() {
+                            Ok(index) => Step::Index(index),
+                            Err(_) => return Err(Message::NotAnIndex(number)),
+                        }
+                    }
+                };
+                if chars.next() != Some(']') {
+                    return Err(Message::ExpectedChar(']'));
+                }
+                steps.push(step);
+            }
+            c => return Err(Message::UnexpectedText(c.to_string())),
+        }
+    }
+    Ok(steps)
+}
+
+/// Returns `true` if `c` can be part of an unquoted key
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Parses a quoted key, including its quotes.  Only `\"` and `\\` are escaped.
+fn string(chars: &mut Peekable<Chars>) -> Result<String, Message> {
+    chars.next();
+    let mut key = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(key),
+            Some('\\') => key.extend(chars.next()),
+            Some(c) => key.push(c),
+            None => return Err(Message::UnclosedString),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::query;
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+
+    #[test]
+    fn paths() {
+        let arena = Arena::new();
+        let user = |name: &str| {
+            TestJSON::Object(vec![
+                (
+                    "name".to_string(),
+                    TestJSON::Array(vec![TestJSON::True, TestJSON::Object(vec![])]),
+                ),
+                (name.to_string(), TestJSON::False),
+            ])
+        };
+        let root = TestJSON::Object(vec![
+            (
+                "users".to_string(),
+                TestJSON::Array(vec![user("a"), user("b c")]),
+            ),
+            ("ok".to_string(), TestJSON::True),
+        ])
+        .add_to_arena(&arena);
+        for (text, expected) in &[
+            (".", vec![vec![]]),
+            (".ok", vec![vec![1, 1]]),
+            (".users[1]", vec![vec![0, 1, 1]]),
+            (".users[-1].\"b c\"", vec![vec![0, 1, 1, 1, 1]]),
+            (
+                ".users[][\"name\"][0]",
+                vec![vec![0, 1, 0, 0, 1, 0], vec![0, 1, 1, 0, 1, 0]],
+            ),
+            (".[]", vec![vec![0, 1], vec![1, 1]]),
+            (".users[2]", vec![]),
+            (".users.name", vec![]),
+            (".ok[0]", vec![]),
+        ] {
+            let paths = query(root, text).unwrap();
+            let paths: Vec<Vec<usize>> =
+                paths.iter().map(|p| p.iter().copied().collect()).collect();
+            assert_eq!(&paths, expected, "{}", text);
+        }
+        for text in &["", "users", ".users[x]", ".users[0", ".\"abc"] {
+            assert!(query(root, text).is_err(), "{:?} should be invalid", text);
+        }
+    }
+}
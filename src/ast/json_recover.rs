@@ -0,0 +1,415 @@
+//! Lenient parsing of JSON documents which contain mistakes, so that a file which doesn't fully
+//! parse can still be opened.  Each region of text which can't be parsed becomes an `error` node
+//! (a [`JSON::Error`](super::json::JSON::Error)) holding the text exactly as it was written, so
+//! that it can be fixed by hand and is written back unchanged if it isn't.
+//!
+//! After an error, parsing resumes at the next `,` or closing bracket which isn't nested inside
+//! the broken text, so one mistake only swallows the value (or field) that it's in.  Missing
+//! commas are assumed, and stray commas are skipped.  Comments aren't understood here, so any
+//! comments end up inside error nodes.
+
+use super::json_stream;
+use super::structured::StructuredNode;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+
+/// A mistake found whilst parsing a document
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    /// The path to the node which contains the mistake (usually an error node)
+    pub path: CursorPath,
+    /// The line of the text where the mistake starts, counting from 1
+    pub line: usize,
+    pub message: Message,
+}
+
+/// A document parsed by [`parse`], along with every mistake which had to be recovered from
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Recovered {
+    pub node: StructuredNode,
+    /// The mistakes, in the order they appear in the text
+    pub errors: Vec<ParseError>,
+}
+
+/// Parses the text of a whole JSON document, turning any parts which aren't valid into error
+/// nodes.  Returns [`None`] if the text is empty.
+pub fn parse(text: &str) -> Option<Recovered> {
+    if text.trim().is_empty() {
+        return None;
+    }
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        path: CursorPath::root(),
+        errors: Vec::new(),
+    };
+    let mut node = parser.value();
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        // Text after the root is kept as an error node at the end of the root, so that the root
+        // itself is still kept.  Only arrays and objects have somewhere for it to go, so any
+        // other root is kept as text along with it.
+        let start = parser.pos;
+        parser.pos = text.len();
+        if node.kind == "array" || node.kind == "object" {
+            parser.path.push(node.children.len());
+            parser.report(start, Message::TextAfterDocument);
+            node.children.push(parser.error_text(start));
+        } else {
+            parser.errors.clear();
+            parser.report(start, Message::TextAfterDocument);
+            node = leaf("error", Some(text.trim().to_string()));
+        }
+    }
+    Some(Recovered {
+        node,
+        errors: parser.errors,
+    })
+}
+
+/// The state of a lenient parse.  Unlike [`json_stream`](super::json_stream), none of the parsing
+/// methods can fail, since every mistake is recovered from.
+struct Parser<'t> {
+    text: &'t str,
+    /// The byte index of the next char to be parsed
+    pos: usize,
+    /// The path to the node which is currently being parsed
+    path: CursorPath,
+    errors: Vec<ParseError>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// Records a mistake in the current node, which starts at the byte index `start`
+    fn report(&mut self, start: usize, message: Message) {
+        let line = 1 + self.text[..start].matches('\n').count();
+        self.errors.push(ParseError {
+            path: self.path.clone(),
+            line,
+            message,
+        });
+    }
+
+    /// Records a mistake which starts at the byte index `start`, and returns an error node
+    /// containing the text from there up to (but not including) the next `,` or closing bracket
+    /// which isn't nested inside that text
+    fn error_node(&mut self, start: usize, message: Message) -> StructuredNode {
+        self.report(start, message);
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    // Brackets inside strings don't count
+                    let _ = self.string();
+                    continue;
+                }
+                '[' | '{' => depth += 1,
+                ']' | '}' | ',' if depth == 0 => break,
+                ']' | '}' => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+        self.error_text(start)
+    }
+
+    /// Returns an error node containing the text from the byte index `start` to the current
+    /// position
+    fn error_text(&self, start: usize) -> StructuredNode {
+        leaf(
+            "error",
+            Some(self.text[start..self.pos].trim_end().to_string()),
+        )
+    }
+
+    /// Parses the child at index `index` of the current node, using a given parsing method
+    fn child(
+        &mut self,
+        index: usize,
+        parse: impl FnOnce(&mut Self) -> StructuredNode,
+    ) -> StructuredNode {
+        self.path.push(index);
+        let child = parse(self);
+        self.path.pop();
+        child
+    }
+
+    fn value(&mut self) -> StructuredNode {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek() {
+            Some('[') => self.container("array", ']'),
+            Some('{') => self.container("object", '}'),
+            Some('"') => match self.string() {
+                Ok(content) => leaf("string", Some(content)),
+                Err(message) => {
+                    self.report(start, message);
+                    self.error_text(start)
+                }
+            },
+            None | Some(',') | Some(']') | Some('}') => {
+                self.report(start, Message::ExpectedValue);
+                leaf("error", Some(String::new()))
+            }
+            Some(c) => {
+                let mut word = String::new();
+                while let Some(c) = self
+                    .peek()
+                    .filter(|c| c.is_alphanumeric() || "-+.".contains(*c))
+                {
+                    word.push(c);
+                    self.bump();
+                }
+                match word.as_str() {
+                    "true" | "false" => leaf(&word, None),
+                    "" => self.error_node(start, Message::UnexpectedText(c.to_string())),
+                    _ => self.error_node(start, Message::UnexpectedText(word)),
+                }
+            }
+        }
+    }
+
+    /// Parses a string, including its quotes.  The string is always consumed: up to its closing
+    /// quote, or to the end of the line if it isn't closed.  Returns an error message if the
+    /// string isn't valid.
+    fn string(&mut self) -> Result<String, Message> {
+        let rest = &self.text[self.pos..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        match json_stream::string_len(line) {
+            Some(len) => {
+                self.pos += len;
+                json_stream::unescape(&line[1..len - 1], false)
+            }
+            None => {
+                self.pos += line.len();
+                Err(Message::UnclosedString)
+            }
+        }
+    }
+
+    /// Parses an array or an object, whose kind and closing delimiter are given
+    fn container(&mut self, kind: &str, close: char) -> StructuredNode {
+        let start = self.pos;
+        self.bump();
+        let mut children = Vec::new();
+        let mut after_comma = false;
+        loop {
+            self.skip_whitespace();
+            let pos = self.pos;
+            match self.peek() {
+                Some(c) if c == close => {
+                    if after_comma {
+                        self.report(pos, Message::CommaBeforeEnd);
+                    }
+                    self.bump();
+                    break;
+                }
+                None => {
+                    let message = match close {
+                        ']' => Message::UnclosedArray,
+                        _ => Message::UnclosedObject,
+                    };
+                    self.report(start, message);
+                    break;
+                }
+                Some(',') => {
+                    if children.is_empty() || after_comma {
+                        self.report(pos, Message::ExpectedValueBeforeComma);
+                    }
+                    self.bump();
+                    after_comma = true;
+                }
+                Some(c) => {
+                    if !children.is_empty() && !after_comma {
+                        self.report(pos, Message::ExpectedCommaOr(close));
+                    }
+                    let child = self.child(children.len(), |p| match c {
+                        // The wrong closing delimiter can't start anything, so it's skipped
+                        ']' | '}' => {
+                            p.bump();
+                            p.error_node(pos, Message::UnexpectedText(c.to_string()))
+                        }
+                        _ if kind == "object" => p.field(),
+                        _ => p.value(),
+                    });
+                    children.push(child);
+                    after_comma = false;
+                }
+            }
+        }
+        StructuredNode {
+            kind: kind.to_string(),
+            value: None,
+            children,
+        }
+    }
+
+    /// Parses a field of an object.  If its key or colon are broken then the whole field becomes
+    /// an error node.
+    fn field(&mut self) -> StructuredNode {
+        let start = self.pos;
+        if self.peek() != Some('"') {
+            return self.error_node(start, Message::ExpectedKey);
+        }
+        let key = match self.string() {
+            Ok(key) => key,
+            Err(message) => return self.error_node(start, message),
+        };
+        self.skip_whitespace();
+        if self.peek() != Some(':') {
+            return self.error_node(start, Message::ExpectedColon);
+        }
+        self.bump();
+        let value = self.child(1, Self::value);
+        StructuredNode {
+            kind: "field".to_string(),
+            value: None,
+            children: vec![leaf("string", Some(key)), value],
+        }
+    }
+}
+
+/// Creates a [`StructuredNode`] with no children
+fn leaf(kind: &str, value: Option<String>) -> StructuredNode {
+    StructuredNode {
+        kind: kind.to_string(),
+        value,
+        children: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::Ast;
+    use crate::messages::Catalog;
+
+    /// Parses a document, returning its text along with the path, line and message of each error
+    fn recover(text: &str) -> (String, Vec<(Vec<usize>, usize, String)>) {
+        let recovered = parse(text).unwrap();
+        let arena = Arena::new();
+        let node: JSON = recovered.node.to_node(&arena).unwrap();
+        let catalog = Catalog::english();
+        let errors = recovered
+            .errors
+            .into_iter()
+            .map(|e| {
+                let message = catalog.format(&e.message);
+                (e.path.iter().copied().collect(), e.line, message)
+            })
+            .collect();
+        (node.to_text(&JSONFormat::Compact), errors)
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            recover(r#"{"a": [true, "x y"], "b": {}}"#),
+            (r#"{"a": [true, "x y"], "b": {}}"#.to_string(), vec![])
+        );
+        assert!(parse(" \n ").is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let error =
+            |path: Vec<usize>, line: usize, message: &str| (path, line, message.to_string());
+        // Values which can't be parsed are kept as text, along with anything nested in them
+        assert_eq!(
+            recover("[true, null,\n  12 [3, 4], false]"),
+            (
+                "[true, null, 12 [3, 4], false]".to_string(),
+                vec![
+                    error(vec![1], 1, "unexpected 'null'"),
+                    error(vec![2], 2, "unexpected '12'"),
+                ]
+            )
+        );
+        // Broken keys and colons make the whole field an error
+        assert_eq!(
+            recover(r#"{"a" true, b: false, "c": "\q", "d": true}"#),
+            (
+                r#"{"a" true, b: false, "c": "\q", "d": true}"#.to_string(),
+                vec![
+                    error(vec![0], 1, "expected ':' after a key"),
+                    error(vec![1], 1, "expected a key"),
+                    error(vec![2, 1], 1, "'\\q' is not an escape sequence"),
+                ]
+            )
+        );
+        // Missing and stray commas, and unclosed delimiters and strings
+        assert_eq!(
+            recover("[true false,, }]"),
+            (
+                "[true, false, }]".to_string(),
+                vec![
+                    error(vec![], 1, "expected ',' or ']'"),
+                    error(vec![], 1, "expected a value before ','"),
+                    error(vec![2], 1, "unexpected '}'"),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("{\"a\": \"abc\n, \"b\": [true,]"),
+            (
+                r#"{"a": "abc, "b": [true]}"#.to_string(),
+                vec![
+                    error(vec![0, 1], 1, "a string isn't closed"),
+                    error(vec![1, 1], 2, "a comma comes before the end"),
+                    error(vec![], 1, "an object isn't closed"),
+                ]
+            )
+        );
+        // Text after the document goes at the end of the root, unless the root has no children
+        assert_eq!(
+            recover("[true, {\"a\": false}]\n]  x\n"),
+            (
+                "[true, {\"a\": false}, ]  x]".to_string(),
+                vec![error(
+                    vec![2],
+                    2,
+                    "unexpected text after the end of the document"
+                )]
+            )
+        );
+        assert_eq!(
+            recover("{} x"),
+            (
+                "{x}".to_string(),
+                vec![error(
+                    vec![0],
+                    1,
+                    "unexpected text after the end of the document"
+                )]
+            )
+        );
+        assert_eq!(
+            recover("true false\n"),
+            (
+                "true false".to_string(),
+                vec![error(
+                    vec![],
+                    1,
+                    "unexpected text after the end of the document"
+                )]
+            )
+        );
+    }
+}
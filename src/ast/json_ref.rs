@@ -0,0 +1,265 @@
+//! JSON references like `{"$ref": "#/definitions/address"}`, which refer to another node of the
+//! same document through a [JSON Pointer](https://tools.ietf.org/html/rfc6901).  Renaming a
+//! field rewrites every pointer which passes through it.
+
+use super::json::JSON;
+use super::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use crate::messages::Message;
+
+/// The key of the field which makes an object a reference
+const REF_KEY: &str = "$ref";
+
+/// If the node at `path` is a reference (i.e. an object with a `$ref` field), or is inside the
+/// `$ref` field of one, returns the path to the node that the reference points to (or an error if
+/// it can't be resolved).  Returns [`None`] if the node isn't part of a reference.
+pub fn resolve<'arena>(
+    root: &'arena JSON<'arena>,
+    path: &CursorPath,
+) -> Option<Result<CursorPath, Message>> {
+    let nodes: Vec<&JSON> = path.node_iter(root).collect();
+    let indices: Vec<usize> = path.iter().copied().collect();
+    // The cursor can be on the reference itself, its `$ref` field, or that field's key or value
+    for depth in (nodes.len().saturating_sub(3)..nodes.len()).rev() {
+        if let Some((field_index, pointer)) = ref_field(nodes[depth]) {
+            if depth + 1 < nodes.len() && indices[depth] != field_index {
+                return None;
+            }
+            return Some(resolve_pointer(root, pointer));
+        }
+    }
+    None
+}
+
+/// Returns the index and the pointer of the `$ref` field of a node, or [`None`] if it isn't a
+/// reference
+fn ref_field<'arena>(node: &'arena JSON<'arena>) -> Option<(usize, &'arena str)> {
+    match node {
+        JSON::Object(fields) => fields
+            .iter()
+            .enumerate()
+            .find_map(|(i, field)| match field {
+                JSON::Field([JSON::Str(key), JSON::Str(pointer)]) if key == REF_KEY => {
+                    Some((i, pointer.as_str()))
+                }
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+/// Returns the path to the node which a pointer (like `#/definitions/address`) points to
+fn resolve_pointer<'arena>(
+    root: &'arena JSON<'arena>,
+    pointer: &str,
+) -> Result<CursorPath, Message> {
+    let fragment = pointer
+        .strip_prefix('#')
+        .ok_or_else(|| Message::ExternalReference(pointer.to_string()))?;
+    let mut path = CursorPath::root();
+    if fragment.is_empty() {
+        return Ok(path);
+    }
+    let tokens = fragment
+        .strip_prefix('/')
+        .ok_or_else(|| Message::NotAPointer(pointer.to_string()))?;
+    let mut node = root;
+    for token in tokens.split('/') {
+        // `~1` has to be unescaped first, so that `~01` becomes `~1` rather than `/`
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let (indices, child) = match node {
+            JSON::Object(fields) => fields
+                .iter()
+                .enumerate()
+                .find_map(|(i, field)| match field {
+                    JSON::Field([JSON::Str(key), value]) if *key == token => {
+                        Some((vec![i, 1], *value))
+                    }
+                    _ => None,
+                }),
+            JSON::Array(values) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| Some((vec![i], *values.get(i)?))),
+            _ => None,
+        }
+        .ok_or_else(|| Message::DanglingPointer(pointer.to_string()))?;
+        path.extend(indices);
+        node = child;
+    }
+    Ok(path)
+}
+
+/// Renames the field at `path` (or whose key or value is at `path`, or which the reference at
+/// `path` refers to), returning the replacements for its key and for the pointers of every
+/// reference which points into it.  Returns [`None`] if there is no field to rename.
+pub fn rename<'arena>(
+    root: &'arena JSON<'arena>,
+    path: &CursorPath,
+    new_name: &str,
+) -> Option<Result<Vec<(CursorPath, JSON<'arena>)>, Message>> {
+    let field_path = match resolve(root, path) {
+        Some(Ok(target)) => match field_of(root, &target) {
+            Some(field_path) => field_path,
+            None => return Some(Err(Message::NotAFieldValue)),
+        },
+        Some(Err(error)) => return Some(Err(error)),
+        None => field_of(root, path)?,
+    };
+    Some(rename_field(root, field_path, new_name))
+}
+
+/// Returns the path to the field at `path`, or whose key or value is at `path`
+fn field_of<'arena>(root: &'arena JSON<'arena>, path: &CursorPath) -> Option<CursorPath> {
+    let mut field_path = path.clone();
+    match path.cursor_and_parent(root) {
+        (JSON::Field(_), _) => {}
+        (_, Some(JSON::Field(_))) => {
+            field_path.pop();
+        }
+        _ => return None,
+    }
+    Some(field_path)
+}
+
+/// Returns the replacements which rename the field at `field_path`
+fn rename_field<'arena>(
+    root: &'arena JSON<'arena>,
+    field_path: CursorPath,
+    new_name: &str,
+) -> Result<Vec<(CursorPath, JSON<'arena>)>, Message> {
+    let mut object_path = field_path.clone();
+    object_path.pop();
+    let has_name =
+        |field: &&JSON| matches!(field, JSON::Field([JSON::Str(key), _]) if key == new_name);
+    if object_path.cursor(root).children().iter().any(has_name) {
+        return Err(Message::FieldExists(new_name.to_string()));
+    }
+    let mut value_path = field_path.clone();
+    value_path.push(1);
+    let old_pointer = pointer_to(root, &value_path).expect("a field's value always has a pointer");
+    let new_pointer = format!(
+        "{}/{}",
+        pointer_to(root, &object_path).expect("a field's object always has a pointer"),
+        new_name.replace('~', "~0").replace('/', "~1")
+    );
+    let mut key_path = field_path;
+    key_path.push(0);
+    let mut replacements = vec![(key_path, JSON::Str(new_name.to_string()))];
+    for (path, node) in cursor_path::all_paths(root) {
+        if let Some((field_index, pointer)) = ref_field(node) {
+            let rest = match pointer.strip_prefix(&old_pointer) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => continue,
+            };
+            let mut pointer_path = path;
+            pointer_path.extend(vec![field_index, 1]);
+            let new_pointer = format!("{}{}", new_pointer, rest);
+            replacements.push((pointer_path, JSON::Str(new_pointer)));
+        }
+    }
+    Ok(replacements)
+}
+
+/// Returns the pointer (like `#/definitions/address`) to the node at `path`, or [`None`] if the
+/// path goes through a field's key
+fn pointer_to<'arena>(root: &'arena JSON<'arena>, path: &CursorPath) -> Option<String> {
+    let mut pointer = "#".to_string();
+    let mut node = root;
+    let mut indices = path.iter().copied();
+    while let Some(index) = indices.next() {
+        let token = match node {
+            JSON::Object(fields) => match fields.get(index)? {
+                JSON::Field([JSON::Str(key), value]) if indices.next() == Some(1) => {
+                    node = *value;
+                    key.replace('~', "~0").replace('/', "~1")
+                }
+                _ => return None,
+            },
+            JSON::Array(values) => {
+                node = *values.get(index)?;
+                index.to_string()
+            }
+            _ => return None,
+        };
+        pointer.push('/');
+        pointer.push_str(&token);
+    }
+    Some(pointer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rename, resolve};
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn references() {
+        let arena = Arena::new();
+        let root: &JSON = arena.alloc(
+            JSON::parse_fragment(
+                r##"{"defs": {"a/b": [true, {"$ref": "#/defs/a~1b/0"}]},
+                    "x": {"$ref": "#/defs/a~1b"}, "y": {"$ref": "#/nope"},
+                    "z": {"$ref": "other.json#/defs"}, "w": {"$ref": "#"}}"##,
+            )
+            .unwrap()
+            .node
+            .to_node(&arena)
+            .unwrap(),
+        );
+        let resolve = |path: &[usize]| resolve(root, &CursorPath::from_vec(path.to_vec()));
+        let path = |path: &[usize]| Some(Ok(CursorPath::from_vec(path.to_vec())));
+        // The reference, its `$ref` field, and the field's key all resolve
+        assert_eq!(resolve(&[1, 1]), path(&[0, 1, 0, 1]));
+        assert_eq!(resolve(&[1, 1, 0]), path(&[0, 1, 0, 1]));
+        assert_eq!(resolve(&[1, 1, 0, 0]), path(&[0, 1, 0, 1]));
+        assert_eq!(resolve(&[0, 1, 0, 1, 1]), path(&[0, 1, 0, 1, 0]));
+        assert_eq!(resolve(&[4, 1]), path(&[]));
+        assert!(matches!(resolve(&[2, 1]), Some(Err(_))));
+        assert!(matches!(resolve(&[3, 1]), Some(Err(_))));
+        assert_eq!(resolve(&[0, 1]), None);
+        assert_eq!(resolve(&[]), None);
+        // Anything which refers to the same node is a reference to it
+        let references = root.find_references(&CursorPath::from_vec(vec![0, 1, 0, 1]));
+        assert_eq!(references, vec![CursorPath::from_vec(vec![1, 1])]);
+    }
+
+    #[test]
+    fn renames() {
+        let arena = Arena::new();
+        let root: &JSON = arena.alloc(
+            JSON::parse_fragment(
+                r##"{"defs": {"a": [true], "b": true},
+                    "x": {"$ref": "#/defs/a"}, "y": {"$ref": "#/defs/a/0"},
+                    "z": {"$ref": "#/defs/ab"}}"##,
+            )
+            .unwrap()
+            .node
+            .to_node(&arena)
+            .unwrap(),
+        );
+        let rename =
+            |path: &[usize], name: &str| rename(root, &CursorPath::from_vec(path.to_vec()), name);
+        let edit = |path: &[usize], s: &str| {
+            (
+                CursorPath::from_vec(path.to_vec()),
+                JSON::Str(s.to_string()),
+            )
+        };
+        // `#/defs/ab` starts with `#/defs/a`, but doesn't point into it
+        let expected = Some(Ok(vec![
+            edit(&[0, 1, 0, 0], "c/d"),
+            edit(&[1, 1, 0, 1], "#/defs/c~1d"),
+            edit(&[2, 1, 0, 1], "#/defs/c~1d/0"),
+        ]));
+        // The field can be renamed from its key, or from a reference to it
+        assert_eq!(rename(&[0, 1, 0, 0], "c/d"), expected);
+        assert_eq!(rename(&[1, 1], "c/d"), expected);
+        assert!(matches!(rename(&[0, 1, 0], "b"), Some(Err(_))));
+        assert!(matches!(rename(&[2, 1], "c"), Some(Err(_))));
+        assert_eq!(rename(&[], "c"), None);
+    }
+}
@@ -0,0 +1,734 @@
+//! A parser for the text of the subset of JSON that [`JSON`](super::json::JSON) can represent,
+//! which turns text into [`StructuredNode`]s.  The text is fed in one chunk at a time (e.g.
+//! whilst a gigantic file is being read), and the values which have been parsed since they were
+//! last taken can be [taken](StreamingParser::take_prefix) at any point so that the start of the
+//! file can be shown before the rest is parsed.
+//!
+//! Fragments of text which stop part-way through (e.g. `[true, {"a": fa`) are
+//! [recovered](parse_fragment) by closing every open string, array and object, and by filling in
+//! any values which are missing with holes.  Holes are the default node (an empty object), and
+//! the paths to them are returned so that the user can review them.
+//!
+//! Comments (`// ...` and `/* ... */`, as allowed in JSONC files) and blank lines are recorded as
+//! [trivia](super::trivia), so that they can be written back when the document is saved.
+
+use super::structured::{Fragment, StructuredNode};
+use super::trivia::{Place, Trivia, TriviaItem};
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+
+/// What the parser expects to read next
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Expect {
+    /// The first value of an array, or the `]` of an empty array
+    FirstValue,
+    /// A value (after a comma in an array, or after the colon of a field)
+    Value,
+    /// A `,` or the delimiter which closes the current array or object
+    CommaOrClose,
+    /// The first key of an object, or the `}` of an empty object
+    FirstKey,
+    /// A key (after a comma in an object)
+    Key,
+    /// The `:` between a field's key and its value
+    Colon,
+}
+
+/// An array, object or field which has been opened but not yet finished
+#[derive(Debug, Clone)]
+struct Frame {
+    /// The node, containing the children which have been parsed so far
+    node: StructuredNode,
+    expect: Expect,
+    /// A number which is different for every frame of a parse, so that a frame can be told apart
+    /// from one which was opened in the same place after it was closed
+    id: usize,
+}
+
+/// The values which a [`StreamingParser`] has parsed since its last prefix was
+/// [taken](StreamingParser::take_prefix).  The arrays, objects and fields which are still open
+/// form a spine down the document, and each update extends the spine given by the ones before.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PrefixUpdate {
+    /// The number of nodes at the top of the previous spine which are still open
+    pub kept: usize,
+    /// The children which have been finished since the last update, for each of the kept nodes
+    /// (outermost first)
+    pub added: Vec<Vec<StructuredNode>>,
+    /// The nodes which have been opened since the last update (outermost first), each with the
+    /// children of it which have been finished.  Each goes after the children of the one above it.
+    pub opened: Vec<StructuredNode>,
+}
+
+/// A single token of JSON text
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    /// One of `[`, `]`, `{`, `}`, `,` or `:`
+    Punct(char),
+    /// The contents of a string, with the escape sequences decoded
+    Str(String),
+    /// A word, which is only valid if it's `true` or `false`
+    Word(String),
+    /// A comment, including its delimiters
+    Comment(String),
+}
+
+/// A parser which is fed JSON text a chunk at a time
+#[derive(Debug, Clone, Default)]
+pub struct StreamingParser {
+    /// The arrays, objects and fields which are still open, outermost first
+    stack: Vec<Frame>,
+    /// The root value, once it has been completely parsed
+    root: Option<StructuredNode>,
+    /// Text which has been fed but not parsed, because it ends part-way through a token
+    pending: String,
+    /// The number of bytes of text which have been fed to the parser
+    bytes_read: usize,
+    /// The number of line breaks since the last token
+    newlines: usize,
+    /// The trivia which hasn't yet been attached to a place in the document
+    pending_trivia: Trivia,
+    /// The trivia which has been parsed so far, and where it goes
+    trivia: Vec<(Place<CursorPath>, Trivia)>,
+    /// The number of frames which have been opened
+    frames_opened: usize,
+    /// The id of each frame of the spine given by the last prefix, and the number of its children
+    /// which have been given
+    taken: Vec<(usize, usize)>,
+}
+
+/// Parses a fragment of JSON text, returning [`None`] if the text isn't a single JSON value (or
+/// the start of one)
+pub fn parse_fragment(text: &str) -> Option<Fragment> {
+    let mut parser = StreamingParser::new();
+    parser.feed(text).ok()?;
+    parser.finish_fragment()
+}
+
+impl StreamingParser {
+    /// Creates a parser which hasn't been fed any text
+    pub fn new() -> StreamingParser {
+        StreamingParser::default()
+    }
+
+    /// Returns the number of bytes of text which have been fed to the parser
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Parses the next chunk of text.  Tokens can be split between chunks.  Returns an error
+    /// message if the text isn't valid; the parser can't be used after an error.
+    pub fn feed(&mut self, chunk: &str) -> Result<(), Message> {
+        self.bytes_read += chunk.len();
+        self.pending.push_str(chunk);
+        self.parse_pending(false)
+    }
+
+    /// Returns the values which have been parsed since this was last called, or [`None`] if
+    /// nothing has been parsed since then or if the root value is complete (in which case the
+    /// whole tree is about to be [finished](StreamingParser::finish)).  Only the new values are
+    /// copied, so the text is only parsed once however often this is called.
+    pub fn take_prefix(&mut self) -> Option<PrefixUpdate> {
+        if self.root.is_some() {
+            return None;
+        }
+        let kept = self
+            .taken
+            .iter()
+            .zip(&self.stack)
+            .take_while(|((id, _), frame)| *id == frame.id)
+            .count();
+        let added: Vec<Vec<StructuredNode>> = self.taken[..kept]
+            .iter()
+            .zip(&self.stack)
+            .map(|((_, num_taken), frame)| frame.node.children[*num_taken..].to_vec())
+            .collect();
+        if kept == self.stack.len() && added.iter().all(Vec::is_empty) {
+            return None;
+        }
+        let opened = self.stack[kept..]
+            .iter()
+            .map(|frame| frame.node.clone())
+            .collect();
+        self.taken = self
+            .stack
+            .iter()
+            .map(|frame| (frame.id, frame.node.children.len()))
+            .collect();
+        Some(PrefixUpdate {
+            kept,
+            added,
+            opened,
+        })
+    }
+
+    /// Parses whatever text is left and returns the complete tree, or an error message if the
+    /// text ended before the tree was complete
+    pub fn finish(self) -> Result<StructuredNode, Message> {
+        self.finish_with_trivia().map(|(root, _)| root)
+    }
+
+    /// Like [`finish`](StreamingParser::finish), but also returns the trivia of the document
+    /// and where each piece of it goes (given by paths into the tree)
+    #[allow(clippy::type_complexity)]
+    pub fn finish_with_trivia(
+        mut self,
+    ) -> Result<(StructuredNode, Vec<(Place<CursorPath>, Trivia)>), Message> {
+        self.parse_pending(true)?;
+        if !self.stack.is_empty() {
+            return Err(Message::IncompleteDocument);
+        }
+        self.attach_trivia(Place::EndOfDocument);
+        let root = self.root.ok_or(Message::EmptyDocument)?;
+        Ok((root, self.trivia))
+    }
+
+    /// Finishes a fragment of text which might stop part-way through, by closing the token, the
+    /// fields, the arrays and the objects which are still open, and filling in any values which
+    /// are missing with holes.  Returns [`None`] if the text isn't the start of a value.
+    fn finish_fragment(mut self) -> Option<Fragment> {
+        let mut holes = Vec::new();
+        let pending = std::mem::take(&mut self.pending);
+        let is_in_comment = pending.starts_with('/');
+        let expect = self.expect();
+        let is_value = matches!(expect, Some(Expect::Value) | Some(Expect::FirstValue));
+        let is_key = matches!(expect, Some(Expect::Key) | Some(Expect::FirstKey));
+        if let Some(contents) = pending.strip_prefix('"') {
+            // The text ended inside a string, so keep what there is but mark it for review
+            let mut path = self.path_to_next_child();
+            if is_key {
+                path.push(0);
+            } else if !is_value {
+                return None;
+            }
+            holes.push(path);
+            self.handle(Token::Str(unescape(contents, true).ok()?))
+                .ok()?;
+        } else if pending.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            // A literal which is cut short by the end of the text becomes a hole, since it's not
+            // certain what the user meant
+            if pending == "true" || pending == "false" {
+                self.handle(Token::Word(pending)).ok()?;
+            } else if is_value && ["true", "false"].iter().any(|l| l.starts_with(&pending)) {
+                holes.push(self.path_to_next_child());
+                self.complete_value(leaf("object", None));
+            } else {
+                return None;
+            }
+        } else if !pending.is_empty() && !is_in_comment {
+            return None;
+        }
+        let is_truncated = is_in_comment || !holes.is_empty() || !self.stack.is_empty();
+        while let Some(frame) = self.stack.last() {
+            let needs_value = match frame.node.kind.as_str() {
+                // A comma means that another value was on its way
+                "array" => frame.expect == Expect::Value,
+                "field" => true,
+                // A field can't be invented without its key, so a trailing comma is just dropped
+                _ => false,
+            };
+            if needs_value {
+                holes.push(self.path_to_next_child());
+                self.complete_value(leaf("object", None));
+            } else {
+                self.close();
+            }
+        }
+        Some(Fragment {
+            node: self.root?,
+            holes,
+            is_truncated,
+        })
+    }
+
+    /// Parses every complete token in `pending`.  If `at_end` is `true`, then no more text is
+    /// coming and so the last token must be complete.
+    fn parse_pending(&mut self, at_end: bool) -> Result<(), Message> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut rest = pending.as_str();
+        loop {
+            let trimmed = rest.trim_start();
+            self.newlines += rest[..rest.len() - trimmed.len()].matches('\n').count();
+            rest = trimmed;
+            let (token, len) = match next_token(rest, at_end)? {
+                Some(token_and_len) => token_and_len,
+                None => break,
+            };
+            // Two line breaks in a row leave a blank line
+            if self.newlines >= 2 && self.pending_trivia.last() != Some(&TriviaItem::BlankLine) {
+                self.pending_trivia.push(TriviaItem::BlankLine);
+            }
+            self.newlines = 0;
+            match token {
+                Token::Comment(comment) => self.pending_trivia.push(TriviaItem::Comment(comment)),
+                token => self.handle(token)?,
+            }
+            rest = &rest[len..];
+        }
+        self.pending = rest.to_string();
+        Ok(())
+    }
+
+    fn expect(&self) -> Option<Expect> {
+        match (self.stack.last(), &self.root) {
+            (Some(frame), _) => Some(frame.expect),
+            (None, None) => Some(Expect::Value),
+            // Nothing can come after the root value
+            (None, Some(_)) => None,
+        }
+    }
+
+    fn handle(&mut self, token: Token) -> Result<(), Message> {
+        let expect = self.expect();
+        let is_value = matches!(expect, Some(Expect::Value) | Some(Expect::FirstValue));
+        let is_key = matches!(expect, Some(Expect::Key) | Some(Expect::FirstKey));
+        let in_field = self
+            .stack
+            .last()
+            .is_some_and(|frame| frame.node.kind == "field");
+        // Trivia goes before the next key or value (or before the field of a value), or at the
+        // end of the array or object which is being closed.  Paths are as long as the document is
+        // deep, so they're only found when there's trivia to attach.
+        let place = match &token {
+            _ if self.pending_trivia.is_empty() => None,
+            Token::Punct('[') | Token::Punct('{') | Token::Str(_) | Token::Word(_)
+                if is_value && in_field =>
+            {
+                Some(Place::Before(self.path_to_top()))
+            }
+            Token::Punct('[') | Token::Punct('{') | Token::Str(_) | Token::Word(_)
+                if is_value || is_key =>
+            {
+                Some(Place::Before(self.path_to_next_child()))
+            }
+            Token::Punct(']') | Token::Punct('}') => Some(Place::End(self.path_to_top())),
+            _ => None,
+        };
+        if let Some(place) = place {
+            self.attach_trivia(place);
+        }
+        let top_kind = self.stack.last().map(|frame| frame.node.kind.as_str());
+        match token {
+            Token::Punct('[') if is_value => self.open("array", Expect::FirstValue),
+            Token::Punct('{') if is_value => self.open("object", Expect::FirstKey),
+            Token::Str(s) if is_value => self.complete_value(leaf("string", Some(s))),
+            Token::Word(w) if is_value && (w == "true" || w == "false") => {
+                self.complete_value(leaf(&w, None))
+            }
+            Token::Str(s) if is_key => {
+                let field = StructuredNode {
+                    kind: "field".to_string(),
+                    value: None,
+                    children: vec![leaf("string", Some(s))],
+                };
+                self.push_frame(field, Expect::Colon);
+            }
+            Token::Punct(':') if expect == Some(Expect::Colon) => self.set_expect(Expect::Value),
+            Token::Punct(',') if expect == Some(Expect::CommaOrClose) => {
+                let next = if top_kind == Some("array") {
+                    Expect::Value
+                } else {
+                    Expect::Key
+                };
+                self.set_expect(next);
+            }
+            Token::Punct(']')
+                if top_kind == Some("array")
+                    && matches!(
+                        expect,
+                        Some(Expect::FirstValue) | Some(Expect::CommaOrClose)
+                    ) =>
+            {
+                self.close()
+            }
+            Token::Punct('}')
+                if top_kind == Some("object")
+                    && matches!(expect, Some(Expect::FirstKey) | Some(Expect::CommaOrClose)) =>
+            {
+                self.close()
+            }
+            Token::Punct(c) => return Err(Message::UnexpectedText(c.to_string())),
+            Token::Str(s) => return Err(Message::UnexpectedText(format!("{:?}", s))),
+            Token::Word(w) => return Err(Message::UnexpectedText(w)),
+            Token::Comment(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the path to the innermost array, object or field which is still open
+    fn path_to_top(&self) -> CursorPath {
+        // Each open node will be the next child of the node which contains it
+        let indices = self.stack[..self.stack.len().saturating_sub(1)]
+            .iter()
+            .map(|frame| frame.node.children.len());
+        CursorPath::from_vec(indices.collect())
+    }
+
+    /// Returns the path to the next child of the innermost open node (or to the root, if nothing
+    /// is open)
+    fn path_to_next_child(&self) -> CursorPath {
+        let indices = self.stack.iter().map(|frame| frame.node.children.len());
+        CursorPath::from_vec(indices.collect())
+    }
+
+    /// Attaches the trivia which has been read since the last token to a place in the document
+    fn attach_trivia(&mut self, place: Place<CursorPath>) {
+        let mut trivia = std::mem::take(&mut self.pending_trivia);
+        // Blank lines are only kept if something comes after them
+        if !matches!(place, Place::Before(_)) {
+            while trivia.last() == Some(&TriviaItem::BlankLine) {
+                trivia.pop();
+            }
+        }
+        if !trivia.is_empty() {
+            self.trivia.push((place, trivia));
+        }
+    }
+
+    fn set_expect(&mut self, expect: Expect) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.expect = expect;
+        }
+    }
+
+    fn open(&mut self, kind: &str, expect: Expect) {
+        self.push_frame(leaf(kind, None), expect);
+    }
+
+    fn push_frame(&mut self, node: StructuredNode, expect: Expect) {
+        self.stack.push(Frame {
+            node,
+            expect,
+            id: self.frames_opened,
+        });
+        self.frames_opened += 1;
+    }
+
+    /// Finishes the innermost array or object
+    fn close(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            self.complete_value(frame.node);
+        }
+    }
+
+    /// Adds a value which has been completely parsed to whatever contains it
+    fn complete_value(&mut self, node: StructuredNode) {
+        let frame = match self.stack.last_mut() {
+            Some(frame) => frame,
+            None => {
+                self.root = Some(node);
+                return;
+            }
+        };
+        frame.node.children.push(node);
+        frame.expect = Expect::CommaOrClose;
+        // A field is finished by its value, and then it's a value of its object
+        if frame.node.kind == "field" {
+            let field = self.stack.pop().map(|frame| frame.node);
+            if let Some(object) = self.stack.last_mut() {
+                object.node.children.extend(field);
+                object.expect = Expect::CommaOrClose;
+            }
+        }
+    }
+}
+
+/// Reads the token at the start of `text` (which doesn't start with whitespace), returning the
+/// token and its length in bytes.  Returns [`None`] if `text` is empty or stops part-way through
+/// the token (unless `at_end` is `true`, in which case that's an error).
+fn next_token(text: &str, at_end: bool) -> Result<Option<(Token, usize)>, Message> {
+    let c = match text.chars().next() {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+    match c {
+        '[' | ']' | '{' | '}' | ',' | ':' => Ok(Some((Token::Punct(c), 1))),
+        '/' => {
+            let len = if text.starts_with("//") {
+                text.find('\n').or(Some(text.len()).filter(|_| at_end))
+            } else if let Some(rest) = text.strip_prefix("/*") {
+                rest.find("*/").map(|i| i + 4)
+            } else if text.len() > 1 || at_end {
+                return Err(Message::UnexpectedText("/".to_string()));
+            } else {
+                None
+            };
+            match len {
+                // Line comments don't include the line break which ends them
+                Some(len) => Ok(Some((
+                    Token::Comment(text[..len].trim_end_matches('\r').to_string()),
+                    len,
+                ))),
+                None if at_end => Err(Message::UnclosedComment),
+                None => Ok(None),
+            }
+        }
+        '"' => match string_len(text) {
+            Some(len) => Ok(Some((Token::Str(unescape(&text[1..len - 1], false)?), len))),
+            None if at_end => Err(Message::UnclosedString),
+            None => Ok(None),
+        },
+        c if c.is_ascii_alphabetic() => {
+            let len = text
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(text.len());
+            // The word might carry on in the next chunk
+            if len == text.len() && !at_end {
+                return Ok(None);
+            }
+            Ok(Some((Token::Word(text[..len].to_string()), len)))
+        }
+        c => Err(Message::UnexpectedText(c.to_string())),
+    }
+}
+
+/// Returns the length in bytes of the string (including its quotes) at the start of `text`, or
+/// [`None`] if the string isn't closed
+pub fn string_len(text: &str) -> Option<usize> {
+    let mut is_escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '"' if !is_escaped => return Some(i + 1),
+            '\\' => is_escaped = !is_escaped,
+            _ => is_escaped = false,
+        }
+    }
+    None
+}
+
+/// Decodes the escape sequences in the contents of a string.  If `is_truncated` is `true`, then
+/// the text ended part-way through the string, so an escape sequence which was cut short is left
+/// out.
+pub fn unescape(contents: &str, is_truncated: bool) -> Result<String, Message> {
+    let mut chars = contents.chars();
+    let mut unescaped = String::with_capacity(contents.len());
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('u') => match unicode_escape(&mut chars, is_truncated)? {
+                    Some(c) => c,
+                    None => break,
+                },
+                Some(c @ ('"' | '\\' | '/')) => c,
+                Some(c) => return Err(Message::InvalidEscape(c)),
+                None if is_truncated => break,
+                None => return Err(Message::UnclosedString),
+            },
+            c => c,
+        };
+        unescaped.push(c);
+    }
+    Ok(unescaped)
+}
+
+/// Decodes the hex digits of a `\u` escape sequence (whose `\u` has already been read), along
+/// with a second escape sequence if the first is the high half of a UTF-16 surrogate pair (as
+/// characters outside the Basic Multilingual Plane are escaped, e.g. `\uD83D\uDE00`).  Returns
+/// [`None`] if `is_truncated` is `true` and the text ends part-way through the sequence.
+fn unicode_escape(
+    chars: &mut std::str::Chars,
+    is_truncated: bool,
+) -> Result<Option<char>, Message> {
+    let hex: String = chars.by_ref().take(4).collect();
+    if is_truncated && hex.len() < 4 {
+        return Ok(None);
+    }
+    let invalid = || Message::InvalidUnicodeEscape(hex.clone());
+    let code = u32::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+    if !(0xD800..0xDC00).contains(&code) {
+        return char::from_u32(code).map(Some).ok_or_else(invalid);
+    }
+    // A truncated string can end part-way through the second half of the pair
+    let rest = chars.as_str();
+    if is_truncated && rest.len() < 6 && rest.chars().zip("\\u".chars()).all(|(a, b)| a == b) {
+        return Ok(None);
+    }
+    let low = rest
+        .strip_prefix("\\u")
+        .and_then(|rest| rest.get(..4))
+        .and_then(|low_hex| u32::from_str_radix(low_hex, 16).ok())
+        .filter(|low| (0xDC00..0xE000).contains(low))
+        .ok_or_else(invalid)?;
+    chars.nth(5);
+    let c = char::from_u32(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00));
+    c.map(Some).ok_or_else(invalid)
+}
+
+/// Creates a [`StructuredNode`] with no children
+fn leaf(kind: &str, value: Option<String>) -> StructuredNode {
+    StructuredNode {
+        kind: kind.to_string(),
+        value,
+        children: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_fragment, StreamingParser};
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::structured::StructuredNode;
+    use crate::ast::Ast;
+
+    fn text(node: &StructuredNode) -> String {
+        let arena = Arena::new();
+        let node: JSON = node.to_node(&arena).unwrap();
+        node.to_text(&JSONFormat::Compact)
+    }
+
+    #[test]
+    fn chunks() {
+        let json = r#"{"a\"b": [true, false], "c": {"d": "\u00e9\n"}, "e": []}"#;
+        let expected = "{\"a\\\"b\": [true, false], \"c\": {\"d\": \"\u{e9}\\u000a\"}, \"e\": []}";
+        // However the text is split, the result is the same
+        for chunk_len in 1..=json.len() {
+            let mut parser = StreamingParser::new();
+            for chunk in json.as_bytes().chunks(chunk_len) {
+                parser.feed(std::str::from_utf8(chunk).unwrap()).unwrap();
+            }
+            assert_eq!(parser.bytes_read(), json.len());
+            assert_eq!(text(&parser.finish().unwrap()), expected, "{}", chunk_len);
+        }
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut parser = StreamingParser::new();
+        assert_eq!(parser.take_prefix(), None);
+        // The spine of open nodes, which each update extends
+        let mut spine: Vec<StructuredNode> = Vec::new();
+        for (chunk, expected, num_added) in &[
+            ("[tr", "[]", 0),
+            ("ue, {\"a\": ", "[true, {}]", 1),
+            ("[fal", "[true, {\"a\": []}]", 0),
+            ("se]}, \"x", "[true, {\"a\": [false]}]", 1),
+            ("\", \"y", "[true, {\"a\": [false]}, \"x\"]", 1),
+        ] {
+            parser.feed(chunk).unwrap();
+            let update = parser.take_prefix().unwrap();
+            // Only the values which weren't in the last update are given
+            let added: usize = update.added.iter().map(Vec::len).sum();
+            assert_eq!(added, *num_added, "{}", chunk);
+            spine.truncate(update.kept);
+            for (node, children) in spine.iter_mut().zip(update.added) {
+                node.children.extend(children);
+            }
+            spine.extend(update.opened);
+            // Close the spine, leaving out fields which are missing their values
+            let mut root = None;
+            for node in spine.iter().rev() {
+                let mut node = node.clone();
+                node.children.extend(root.take());
+                if node.kind != "field" || node.children.len() == 2 {
+                    root = Some(node);
+                }
+            }
+            assert_eq!(text(&root.unwrap()), *expected, "{}", chunk);
+            assert_eq!(parser.take_prefix(), None);
+        }
+        // Nothing more is given once the root is complete, since it's about to be finished
+        parser.feed("\"]").unwrap();
+        assert_eq!(parser.take_prefix(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        for json in &[
+            "",
+            "[true false]",
+            "[true,]",
+            "{\"a\" true}",
+            "{true: false}",
+            "true]",
+            "[nul]",
+            "[\"\\q\"]",
+            "[true",
+            "\"abc",
+            "[1]",
+        ] {
+            let mut parser = StreamingParser::new();
+            let result = parser.feed(json).and_then(|_| parser.finish().map(|_| ()));
+            assert!(result.is_err(), "{:?} should be invalid", json);
+        }
+    }
+
+    /// Parses a fragment, returning its text, the paths of its holes and whether it was truncated
+    fn parse(text: &str) -> Option<(String, Vec<Vec<usize>>, bool)> {
+        let fragment = parse_fragment(text)?;
+        let arena = Arena::new();
+        let node: JSON = fragment.node.to_node(&arena).unwrap();
+        let holes = fragment
+            .holes
+            .iter()
+            .map(|path| path.iter().copied().collect())
+            .collect();
+        Some((
+            node.to_text(&JSONFormat::Compact),
+            holes,
+            fragment.is_truncated,
+        ))
+    }
+
+    #[test]
+    fn complete_fragments() {
+        for (text, expected) in &[
+            ("true", "true"),
+            (" [ ] ", "[]"),
+            (
+                r#"{"a\"b": [true, false], "c": {}}"#,
+                r#"{"a\"b": [true, false], "c": {}}"#,
+            ),
+            (r#""A\n""#, r#""A\u000a""#),
+            (r#""\uD83D\uDE00""#, "\"\u{1F600}\""),
+        ] {
+            assert_eq!(parse(text), Some((expected.to_string(), vec![], false)));
+        }
+    }
+
+    #[test]
+    fn truncated_fragments() {
+        for (text, expected, holes) in &[
+            ("[true, [false", "[true, [false]]", vec![]),
+            ("[true,", "[true, {}]", vec![vec![1]]),
+            ("[tr", "[{}]", vec![vec![0]]),
+            (r#"{"a": true, "#, r#"{"a": true}"#, vec![]),
+            (r#"{"a": "#, r#"{"a": {}}"#, vec![vec![0, 1]]),
+            (r#"{"a"#, r#"{"a": {}}"#, vec![vec![0, 0], vec![0, 1]]),
+            (r#"["abc"#, r#"["abc"]"#, vec![vec![0]]),
+            (r#"["a\uD83D"#, r#"["a"]"#, vec![vec![0]]),
+            (r#"["a\uD83D\uDE"#, r#"["a"]"#, vec![vec![0]]),
+        ] {
+            assert_eq!(
+                parse(text),
+                Some((expected.to_string(), holes.clone(), true))
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_fragments() {
+        for text in &[
+            "",
+            "  ",
+            "null",
+            "[true false]",
+            "[true,]",
+            r#"{"a" true}"#,
+            "{true: false}",
+            "true]",
+            "[trux",
+            r#""\q""#,
+            r#""\uD83D""#,
+            r#""\uD83Dx""#,
+            r#""\uDE00""#,
+        ] {
+            assert_eq!(parse(text), None, "{:?} should be invalid", text);
+        }
+    }
+}
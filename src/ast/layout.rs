@@ -0,0 +1,215 @@
+//! A layout engine in the style of Wadler's "prettier printer", which joins the lines of any pair
+//! of delimiters whose contents fit within a maximum width and leaves the rest broken over several
+//! lines.  It works on the tokens of the fully broken layout (where every child is on its own
+//! line), treating each pair of delimiters as a group which is either flat or broken.
+//!
+//! When a group is flattened, its [`Indent`](DisplayToken::Indent)s and
+//! [`Dedent`](DisplayToken::Dedent)s are dropped, any [`Newline`](DisplayToken::Newline) which
+//! directly follows one of them disappears (like the line breaks just inside `[` and `]`), and
+//! every other `Newline` becomes a single space (like the line breaks after commas).
+
+use super::display_token::{match_delimiters, DisplayToken, INDENT_WIDTH};
+use super::size::Size;
+use std::ops::Range;
+
+/// Lays out a stream of display tokens so that (where possible) no line is longer than
+/// `max_width`.  Groups are flattened greedily from the outside in, so a group is only broken over
+/// several lines if it doesn't fit on the rest of its first line.
+pub fn fit_to_width<T: Copy>(
+    tokens: Vec<(T, DisplayToken)>,
+    max_width: usize,
+) -> Vec<(T, DisplayToken)> {
+    fit_to_width_with(tokens, max_width, |_| false)
+}
+
+/// Like [`fit_to_width`], but groups containing a token whose `T` is `is_fixed` are always
+/// broken over several lines (e.g. because a line comment can't have anything after it)
+pub fn fit_to_width_with<T: Copy>(
+    tokens: Vec<(T, DisplayToken)>,
+    max_width: usize,
+    is_fixed: impl Fn(&T) -> bool,
+) -> Vec<(T, DisplayToken)> {
+    let delimiters = match_delimiters(&tokens);
+    let totals = Totals::new(&tokens, is_fixed);
+    let mut laid_out = Vec::with_capacity(tokens.len());
+    let mut column = 0;
+    let mut indent = 0;
+    // The index of the token which closes the group being flattened, if any
+    let mut flat_until: Option<usize> = None;
+    for (i, (node, tok)) in tokens.iter().enumerate() {
+        if let (None, DisplayToken::OpenDelim(_), Some(m)) = (flat_until, tok, delimiters[i]) {
+            let group = i..m.partner + 1;
+            if totals.is_broken(group.clone())
+                && !totals.is_fixed(group.clone())
+                && column + totals.flat_width(group) + totals.trailing_widths[m.partner + 1]
+                    <= max_width
+            {
+                flat_until = Some(m.partner);
+            }
+        }
+        let tok = match flat_until {
+            Some(_) => flatten(tok, i.checked_sub(1).map(|j| &tokens[j].1)),
+            None => Some(tok.clone()),
+        };
+        if flat_until == Some(i) {
+            flat_until = None;
+        }
+        let tok = match tok {
+            Some(tok) => tok,
+            None => continue,
+        };
+        match &tok {
+            DisplayToken::Text(s)
+            | DisplayToken::Categorised(s, _)
+            | DisplayToken::OpenDelim(s)
+            | DisplayToken::CloseDelim(s) => column += text_width(s),
+            DisplayToken::Whitespace(n) => column += n,
+            DisplayToken::Newline => column = indent * INDENT_WIDTH,
+            DisplayToken::Indent => indent += 1,
+            DisplayToken::Dedent => indent = indent.saturating_sub(1),
+        }
+        laid_out.push((*node, tok));
+    }
+    laid_out
+}
+
+/// Returns the token which replaces `tok` in a flattened group (or [`None`] if it should be left
+/// out), given the token before it
+fn flatten(tok: &DisplayToken, prev: Option<&DisplayToken>) -> Option<DisplayToken> {
+    match tok {
+        DisplayToken::Indent | DisplayToken::Dedent => None,
+        DisplayToken::Newline => match prev {
+            Some(DisplayToken::Indent) | Some(DisplayToken::Dedent) => None,
+            _ => Some(DisplayToken::Whitespace(1)),
+        },
+        tok => Some(tok.clone()),
+    }
+}
+
+/// Running totals over a token stream, worked out in one pass so that each group can be measured
+/// without walking its tokens again.  Each `Vec` has one more entry than there are tokens, and the
+/// running totals for a group covering the tokens `start..end` are `totals[end] - totals[start]`.
+struct Totals {
+    /// The width of the tokens before each index once they have been flattened onto one line
+    flat_widths: Vec<usize>,
+    /// The number of [`Newline`](DisplayToken::Newline)s before each index
+    newlines: Vec<usize>,
+    /// The number of tokens which are `is_fixed` before each index
+    fixed: Vec<usize>,
+    /// The width of the text from each index which stays on the same line whatever happens (like
+    /// the comma after an element), which also has to fit for a group ending there to be flattened
+    trailing_widths: Vec<usize>,
+}
+
+impl Totals {
+    fn new<T>(tokens: &[(T, DisplayToken)], is_fixed: impl Fn(&T) -> bool) -> Self {
+        let mut totals = Totals {
+            flat_widths: vec![0],
+            newlines: vec![0],
+            fixed: vec![0],
+            trailing_widths: vec![0; tokens.len() + 1],
+        };
+        for (i, (t, tok)) in tokens.iter().enumerate() {
+            // Only the first token of a group is measured without the token before it, and that
+            // is always an `OpenDelim` (which flattens to itself whatever comes before it)
+            let prev = i.checked_sub(1).map(|j| &tokens[j].1);
+            let width = match flatten(tok, prev) {
+                Some(DisplayToken::Text(s))
+                | Some(DisplayToken::Categorised(s, _))
+                | Some(DisplayToken::OpenDelim(s))
+                | Some(DisplayToken::CloseDelim(s)) => text_width(&s),
+                Some(DisplayToken::Whitespace(n)) => n,
+                _ => 0,
+            };
+            let is_newline = matches!(tok, DisplayToken::Newline);
+            totals.flat_widths.push(totals.flat_widths[i] + width);
+            totals
+                .newlines
+                .push(totals.newlines[i] + is_newline as usize);
+            totals.fixed.push(totals.fixed[i] + is_fixed(t) as usize);
+        }
+        for (i, (_, tok)) in tokens.iter().enumerate().rev() {
+            totals.trailing_widths[i] = match tok {
+                DisplayToken::Text(s)
+                | DisplayToken::Categorised(s, _)
+                | DisplayToken::CloseDelim(s) => text_width(s) + totals.trailing_widths[i + 1],
+                _ => 0,
+            };
+        }
+        totals
+    }
+
+    /// Returns the width of a group of tokens once it has been flattened onto one line
+    fn flat_width(&self, group: Range<usize>) -> usize {
+        self.flat_widths[group.end] - self.flat_widths[group.start]
+    }
+
+    /// Returns `true` if a group of tokens is broken over several lines
+    fn is_broken(&self, group: Range<usize>) -> bool {
+        self.newlines[group.end] > self.newlines[group.start]
+    }
+
+    /// Returns `true` if a group of tokens contains a token which is `is_fixed`
+    fn is_fixed(&self, group: Range<usize>) -> bool {
+        self.fixed[group.end] > self.fixed[group.start]
+    }
+}
+
+/// Returns the width of the last line of some text
+fn text_width(s: &str) -> usize {
+    Size::from(s).last_line_length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fit_to_width;
+    use crate::arena::Arena;
+    use crate::ast::display_token::{write_token_stream, DisplayToken};
+    use crate::ast::json::JSONFormat;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+
+    #[test]
+    fn fits_to_width() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![
+            (
+                "short".to_string(),
+                TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+            ),
+            (
+                "long".to_string(),
+                TestJSON::Array(vec![
+                    TestJSON::Array(vec![TestJSON::True, TestJSON::True]),
+                    TestJSON::Array(vec![TestJSON::False, TestJSON::False]),
+                ]),
+            ),
+        ])
+        .add_to_arena(&arena);
+        let text = |width: usize| -> String {
+            let tokens: Vec<(_, DisplayToken)> = root.display_tokens(&JSONFormat::Pretty);
+            let mut text = String::new();
+            write_token_stream(fit_to_width(tokens, width), &mut text);
+            text
+        };
+        assert_eq!(
+            text(80),
+            r#"{"short": [true, false], "long": [[true, true], [false, false]]}"#
+        );
+        assert_eq!(
+            text(27),
+            r#"{
+    "short": [true, false],
+    "long": [
+        [true, true],
+        [false, false]
+    ]
+}"#
+        );
+        // The comma after "short"'s value has to fit as well
+        assert!(text(26).starts_with("{\n    \"short\": [\n"));
+        assert_eq!(text(0), root.to_text(&JSONFormat::Pretty));
+        // Laying out with a width is a format style of its own
+        assert_eq!(root.to_text(&JSONFormat::Fit(27)), text(27));
+    }
+}
@@ -1,13 +1,32 @@
 //! A module to contain Rust representations of ASTs in a format that sapling can work with.
 
+pub mod commands;
 pub mod display_token;
+pub mod duplicates;
+pub mod fixtures;
 pub mod json;
+pub mod json_commands;
+pub mod json_query;
+pub mod json_recover;
+pub mod json_ref;
+pub mod json_stream;
+pub mod layout;
+pub mod pattern;
+pub mod restructure;
+pub mod schema;
 pub mod size;
+pub mod stats;
+pub mod structured;
 pub mod test_json;
+pub mod trivia;
 
-use display_token::{write_tokens, DisplayToken, RecTok};
+use display_token::{write_tokens, DisplayToken, RecTok, SyntaxCategory};
+use pattern::Pattern;
 use size::Size;
 
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use crate::messages::Message;
 // Import used only for doc comments
 #[allow(unused_imports)]
 use crate::editable_tree::EditableTree;
@@ -17,6 +36,10 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
     /// A type parameter that will represent the different ways this AST can be rendered
     type FormatStyle;
 
+    /// The name of the language that this AST represents (e.g. `"json"`), which is used to look
+    /// up language-specific settings in the config
+    fn language_name() -> &'static str;
+
     /* FORMATTING FUNCTIONS */
 
     /// Returns an iterator of all the items that need to be rendered to the screen to make up this
@@ -26,22 +49,44 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
         format_style: &Self::FormatStyle,
     ) -> Vec<RecTok<'arena, Self>>;
 
+    /// Returns the tokens of this node and all its descendants, laid out to fit the
+    /// [`max_line_width`](Ast::max_line_width) of the format style if it has one
     fn display_tokens(
         &'arena self,
         format_style: &Self::FormatStyle,
     ) -> Vec<(&'arena Self, DisplayToken)> {
         let mut tok_pairs: Vec<(&'arena Self, DisplayToken)> = Vec::new();
-        for i in self.display_tokens_rec(format_style) {
-            match i {
-                RecTok::Tok(t) => {
-                    tok_pairs.push((self, t));
-                }
-                RecTok::Child(c) => {
-                    tok_pairs.extend(c.display_tokens(format_style));
-                }
-            }
+        add_display_tokens(self, format_style, &mut tok_pairs);
+        match Self::max_line_width(format_style) {
+            Some(max_width) => layout::fit_to_width(tok_pairs, max_width),
+            None => tok_pairs,
         }
-        tok_pairs
+    }
+
+    /// Returns the category that the text of the child at `index` is coloured as, if it isn't
+    /// coloured as it would be on its own (e.g. the keys of JSON objects are strings, but they're
+    /// coloured as identifiers)
+    fn child_category(&self, _index: usize) -> Option<SyntaxCategory> {
+        None
+    }
+
+    /// Returns the maximum width of the lines that a format style lays nodes out to fit (by
+    /// joining the lines of nodes which fit on one line), or [`None`] if the format style decides
+    /// where every line break goes.  [`display_tokens_rec`](Ast::display_tokens_rec) should put
+    /// every child on its own line in styles with a maximum width, so that there is a line break
+    /// wherever one may be needed.
+    fn max_line_width(_format_style: &Self::FormatStyle) -> Option<usize> {
+        None
+    }
+
+    /// Returns a format style like `format_style`, but which lays nodes out to fit within a given
+    /// maximum line width (or, if the width is [`None`], which decides every line break itself).
+    /// Returns [`None`] if this language's nodes can't be laid out to fit a width.
+    fn with_max_line_width(
+        _format_style: &Self::FormatStyle,
+        _max_width: Option<usize>,
+    ) -> Option<Self::FormatStyle> {
+        None
     }
 
     /// Determine the space on the screen occupied by this node in an AST
@@ -74,6 +119,44 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
     /// Get the display name of this node
     fn display_name(&self) -> String;
 
+    /// Returns `true` if this node holds text which couldn't be parsed (e.g. a JSON
+    /// [`Error`](json::JSON::Error)), so that it can be highlighted
+    fn is_error(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of nodes in the subtree rooted at this node, including this node
+    fn subtree_size(&'arena self) -> usize {
+        // The nodes are counted without recursion, because the tree might be deep enough to
+        // overflow the stack
+        let mut size = 0;
+        let mut nodes_to_visit = vec![self];
+        while let Some(node) = nodes_to_visit.pop() {
+            size += 1;
+            nodes_to_visit.extend(node.children().iter().copied());
+        }
+        size
+    }
+
+    /// Get a short, single-line summary of this node and its descendants, which is displayed in
+    /// place of the node when it is folded (e.g. `{ 12 keys }` or `[ 340 items ]`).  By default,
+    /// this is the node's [`display_name`](Ast::display_name) followed by its number of children.
+    fn summary(&'arena self) -> String {
+        match self.children().len() {
+            0 => self.display_name(),
+            1 => format!("{} {{ 1 child }}", self.display_name()),
+            n => format!("{} {{ {} children }}", self.display_name(), n),
+        }
+    }
+
+    /// Get the label that this node should be given in the outline sidebar, or [`None`] if this
+    /// node shouldn't appear in the outline (e.g. JSON fields are labelled by their keys, but
+    /// plain values are not labelled at all).  Only labelled nodes count towards the outline's
+    /// depth limit.
+    fn outline_label(&self) -> Option<String> {
+        None
+    }
+
     fn write_tree_view_recursive(
         &'arena self,
         string: &mut String,
@@ -113,6 +196,160 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
         s
     }
 
+    /* STRUCTURED SERIALIZATION FUNCTIONS */
+
+    /// Get the name of this node's kind (e.g. `"array"` or `"string"`), which identifies it in a
+    /// [`StructuredNode`](structured::StructuredNode).  This must not contain whitespace or
+    /// brackets.
+    fn kind_name(&self) -> &'static str;
+
+    /// Returns `true` if this node matches a search [`Pattern`].  By default, a kind pattern
+    /// matches the nodes whose [`kind_name`](Ast::kind_name) it names, and a text pattern matches
+    /// the nodes whose own tokens contain its text (see [`pattern::matches_by_default`]).
+    /// Languages can refine this, e.g. so that one name matches several kinds of node.  Large
+    /// trees are searched for text through an index which assumes the default text matching.
+    fn matches(&'arena self, pattern: &Pattern, format_style: &Self::FormatStyle) -> bool {
+        pattern::matches_by_default(self, pattern, format_style)
+    }
+
+    /// Get the data stored in this node other than its children (e.g. the contents of a string),
+    /// or [`None`] if the node's kind is all that's needed to describe it.
+    fn value(&self) -> Option<String> {
+        None
+    }
+
+    /// Build a node from its [`kind_name`](Ast::kind_name), [`value`](Ast::value) and children.
+    /// Returns [`None`] if these don't describe a valid node.
+    fn from_parts(kind: &str, value: Option<&str>, children: Vec<&'arena Self>) -> Option<Self>;
+
+    /// Returns `true` if `child` can be the child at `index` of a node of the same kind and value
+    /// as this one, as far as [`from_parts`](Ast::from_parts) is concerned.  This doesn't check
+    /// how many children the node can have.  Unlike building the new node, this doesn't allocate
+    /// anything, so it's used to work out which edits to offer before any of them are made.
+    fn is_valid_child(&self, _index: usize, _child: &Self) -> bool {
+        true
+    }
+
+    /// Convert a subtree copied from a buffer of another language (whose
+    /// [`language_name`](Ast::language_name) is `language`) into a subtree of this language, e.g.
+    /// mapping a YAML mapping onto a JSON object.  Returns [`None`] if this language can't convert
+    /// subtrees from `language`, in which case the subtree's text is pasted instead.
+    fn convert_from(
+        _language: &str,
+        _structure: &structured::StructuredNode,
+    ) -> Option<structured::StructuredNode> {
+        None
+    }
+
+    /// Parse a fragment of text in this language (e.g. text pasted from another program).  If the
+    /// text stops part-way through a node, then the fragment is completed and the nodes which had
+    /// to be made up are recorded in the [`Fragment`](structured::Fragment).  Returns [`None`] if
+    /// the text isn't a node (or the start of one), or if this language can't parse text.
+    fn parse_fragment(_text: &str) -> Option<structured::Fragment> {
+        None
+    }
+
+    /// Returns the format style with a given name (e.g. `compact`), as given in config files, or
+    /// [`None`] if this language doesn't have a format style of that name
+    fn named_format_style(_name: &str) -> Option<Self::FormatStyle> {
+        None
+    }
+
+    /// Returns the text of the document which new files in this language start as (e.g. `{}` for
+    /// JSON), or [`None`] if this language doesn't have a template
+    fn new_file_template() -> Option<&'static str> {
+        None
+    }
+
+    /* EVALUATION FUNCTIONS */
+
+    /// Evaluate an expression with this tree loaded as its environment, returning the result (or
+    /// an error) as text.  Returns [`None`] if this language can't evaluate expressions.
+    fn evaluate(&'arena self, _expression: &str) -> Option<Result<String, String>> {
+        None
+    }
+
+    /// Find the nodes matched by a path query (e.g. `.users[3].address.city` for JSON), returning
+    /// the paths to them (or an error if the query is invalid).  Returns [`None`] if this language
+    /// can't be queried.
+    fn query(&'arena self, _query: &str) -> Option<Result<Vec<CursorPath>, Message>> {
+        None
+    }
+
+    /* REFERENCE FUNCTIONS */
+
+    /// If the node at `path` in this tree is a reference to another node of the tree (e.g. a JSON
+    /// `$ref`), returns the path to the node it refers to, or an error if the reference can't be
+    /// resolved.  Returns [`None`] if the node isn't a reference, or this language doesn't have
+    /// references.
+    fn resolve_reference(&'arena self, _path: &CursorPath) -> Option<Result<CursorPath, Message>> {
+        None
+    }
+
+    /// Returns the paths to every reference in this tree which refers to the node at `path`, in
+    /// depth-first pre-order.  Where a reference's descendants also resolve to the same node
+    /// (e.g. the `$ref` field of a JSON reference), only the outermost node is returned.
+    fn find_references(&'arena self, path: &CursorPath) -> Vec<CursorPath> {
+        let target = Some(Ok(path.clone()));
+        let mut references: Vec<CursorPath> = Vec::new();
+        for (reference_path, _) in cursor_path::all_paths(self) {
+            if self.resolve_reference(&reference_path) != target {
+                continue;
+            }
+            let is_inside_last = references
+                .last()
+                .is_some_and(|last| reference_path.iter().take(last.depth()).eq(last.iter()));
+            if !is_inside_last {
+                references.push(reference_path);
+            }
+        }
+        references
+    }
+
+    /// Renames the symbol at `path` (e.g. a JSON field which `$ref`s point into) along with every
+    /// reference to it, returning the nodes which have to be replaced to do so.  Returns [`None`]
+    /// if there is no symbol at `path`, or this language doesn't have symbols.
+    fn rename_symbol(
+        &'arena self,
+        _path: &CursorPath,
+        _new_name: &str,
+    ) -> Option<Result<Vec<(CursorPath, Self)>, Message>> {
+        None
+    }
+
+    /* LANGUAGE COMMANDS */
+
+    /// Returns the commands which only make sense in this language (e.g. refactorings), which the
+    /// editor adds to its command palette.  Returns nothing by default.
+    fn language_commands() -> Vec<commands::LanguageCommand<'arena, Self>> {
+        Vec::new()
+    }
+
+    /* SCHEMA FUNCTIONS */
+
+    /// Returns the keys which a [`Schema`](schema::Schema) describing this whole tree allows to
+    /// be added to the node at `path`, along with the schema of each key's value.  Keys which the
+    /// node already has aren't returned.  Languages without keys return nothing.
+    fn schema_keys<'s>(
+        &'arena self,
+        _path: &CursorPath,
+        _schema: &'s schema::Schema,
+    ) -> Vec<(&'s str, &'s schema::Schema)> {
+        Vec::new()
+    }
+
+    /// Create a new child for the key `key`, whose value is of the kind described by `schema`.
+    /// Like [`Ast::new_child`], this also returns the path within the child to the node that
+    /// should be filled in first.  Returns [`None`] if this node can't have keys as children.
+    fn new_child_from_schema(
+        &self,
+        _key: &str,
+        _schema: &schema::Schema,
+        _arena: &'arena Arena<Self>,
+    ) -> Option<(Self, CursorPath)> {
+        None
+    }
+
     /* AST EDITING FUNCTIONS */
 
     /// Generate an iterator over the possible shorthand [`char`]s that a user could type to replace
@@ -127,6 +364,7 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
     /// Generate a new node from a [`char`] that a user typed as part of the `r` command.  If `c` is
     /// an element of [`get_replace_chars`](ASTSpec::replace_chars), this must return [`Some`] node,
     /// if it isn't, then this should return [`None`].
+    #[allow(clippy::wrong_self_convention)]
     fn from_char(&self, c: char) -> Option<Self>;
 
     /// Generate an iterator over the possible shorthand [`char`]s that a user could type to insert
@@ -137,4 +375,35 @@ pub trait Ast<'arena>: std::fmt::Debug + Clone + Eq + Default + std::hash::Hash
     fn is_insert_char(&self, c: char) -> bool {
         self.insert_chars().any(|x| x == c)
     }
+
+    /// Generate the node that is inserted into this one when the user types `c` as part of the `i`
+    /// command, adding any descendants it needs to `arena`.  Nodes which only make sense with some
+    /// structure inside them (like the key-value pairs of a map) should be created with that
+    /// structure.  Also returns the path, relative to the new node, of the node that the cursor
+    /// should be moved to (e.g. the key of a new key-value pair, so that it can be filled in).
+    /// Returns [`None`] if `c` isn't in [`Self::insert_chars`].
+    fn new_child(&self, c: char, arena: &'arena Arena<Self>) -> Option<(Self, CursorPath)>;
+}
+
+/// Adds the tokens of `node` and all its descendants to `tokens`, with every line break that the
+/// format style asks for
+fn add_display_tokens<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    format_style: &Node::FormatStyle,
+    tokens: &mut Vec<(&'arena Node, DisplayToken)>,
+) {
+    let mut child_index = 0;
+    for rec_tok in node.display_tokens_rec(format_style) {
+        match rec_tok {
+            RecTok::Tok(tok) => tokens.push((node, tok)),
+            RecTok::Child(child) => {
+                let start = tokens.len();
+                add_display_tokens(child, format_style, tokens);
+                if let Some(category) = node.child_category(child_index) {
+                    display_token::set_category(&mut tokens[start..], category);
+                }
+                child_index += 1;
+            }
+        }
+    }
 }
@@ -0,0 +1,96 @@
+//! Patterns which pick out nodes of a tree, as typed into a search.  A pattern is either the name
+//! of a kind of node in angle brackets (e.g. `<array>`), which matches the nodes of that
+//! [kind](super::Ast::kind_name), or a fragment of text, which matches the nodes whose own text
+//! contains it.
+
+use super::display_token::{DisplayToken, RecTok};
+use super::Ast;
+
+/// A pattern which matches some of the nodes of a tree (see [`Ast::matches`])
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Pattern {
+    /// Matches the nodes of the kind with this name
+    Kind(String),
+    /// Matches the nodes whose own tokens (i.e. not those of their children) contain this text
+    Text(String),
+}
+
+impl Pattern {
+    /// Parses a search query into a pattern.  A kind's name in angle brackets is a
+    /// [`Pattern::Kind`], and anything else is a [`Pattern::Text`].
+    pub fn parse(query: &str) -> Pattern {
+        // Kind names can't contain whitespace or brackets, so e.g. `<a b>` is text
+        let is_kind_char = |c: char| !c.is_whitespace() && !"<>()[]{}".contains(c);
+        match query
+            .strip_prefix('<')
+            .and_then(|rest| rest.strip_suffix('>'))
+        {
+            Some(name) if !name.is_empty() && name.chars().all(is_kind_char) => {
+                Pattern::Kind(name.to_string())
+            }
+            _ => Pattern::Text(query.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    /// Writes the pattern in the form that [`Pattern::parse`] reads
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Pattern::Kind(name) => write!(f, "<{}>", name),
+            Pattern::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Returns `true` if a single display token contains `text`.  Only tokens containing text can
+/// match.
+pub fn token_contains(tok: &DisplayToken, text: &str) -> bool {
+    match tok {
+        DisplayToken::Text(s)
+        | DisplayToken::Categorised(s, _)
+        | DisplayToken::OpenDelim(s)
+        | DisplayToken::CloseDelim(s) => s.contains(text),
+        _ => false,
+    }
+}
+
+/// Returns `true` if `node` matches `pattern` in the way that every language does unless it
+/// refines [`Ast::matches`]: by the name of its kind, or by the text of its own tokens
+pub fn matches_by_default<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    pattern: &Pattern,
+    format_style: &Node::FormatStyle,
+) -> bool {
+    match pattern {
+        Pattern::Kind(name) => node.kind_name() == name,
+        Pattern::Text(text) => {
+            node.display_tokens_rec(format_style)
+                .iter()
+                .any(|rec_tok| match rec_tok {
+                    RecTok::Tok(tok) => token_contains(tok, text),
+                    RecTok::Child(_) => false,
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    #[test]
+    fn parse() {
+        let kind = |name: &str| Pattern::Kind(name.to_string());
+        let text = |text: &str| Pattern::Text(text.to_string());
+        assert_eq!(Pattern::parse("<array>"), kind("array"));
+        assert_eq!(Pattern::parse("array"), text("array"));
+        assert_eq!(Pattern::parse("<>"), text("<>"));
+        assert_eq!(Pattern::parse("<a b>"), text("<a b>"));
+        assert_eq!(Pattern::parse("<[>"), text("<[>"));
+        assert_eq!(Pattern::parse("<string"), text("<string"));
+        for query in &["<field>", "tru", "<"] {
+            assert_eq!(Pattern::parse(query).to_string(), *query);
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Paredit-style edits which move nodes between a parent and its children.  Each edit takes the
+//! parent of the cursor and the cursor's index within it, and builds a replacement for the parent
+//! along with the index of the cursor's node within that replacement.  The sorting and grouping
+//! edits instead rearrange the children of the cursor's node, and build a replacement for it.
+//! The wrapping and converting edits also build a replacement for the cursor's node, which still
+//! has to be checked against the cursor's parent.
+
+use super::Ast;
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+
+/// Builds a node of the same kind and value as `node`, but with different children.  Returns
+/// [`None`] if `node` can't have those children.
+fn with_children<'arena, Node: Ast<'arena>>(
+    node: &Node,
+    children: Vec<&'arena Node>,
+) -> Option<Node> {
+    Node::from_parts(node.kind_name(), node.value().as_deref(), children)
+}
+
+/// Moves the sibling after the child at `index` into that child, as its last child
+pub fn slurp<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    parent: &'arena Node,
+    index: usize,
+) -> Result<(Node, usize), Message> {
+    let siblings = parent.children();
+    let node = siblings[index];
+    let next = *siblings.get(index + 1).ok_or(Message::NothingToSlurp)?;
+    let mut children = node.children().to_vec();
+    children.push(next);
+    let new_node = with_children(node, children).ok_or(Message::InvalidStructure)?;
+    let mut new_siblings = siblings.to_vec();
+    new_siblings.splice(index..index + 2, std::iter::once(arena.alloc(new_node)));
+    let new_parent = with_children(parent, new_siblings).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, index))
+}
+
+/// Moves the last child of the child at `index` out, so that it becomes that child's next sibling
+pub fn barf<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    parent: &'arena Node,
+    index: usize,
+) -> Result<(Node, usize), Message> {
+    let siblings = parent.children();
+    let node = siblings[index];
+    let (last, children) = node.children().split_last().ok_or(Message::NothingToBarf)?;
+    let new_node = with_children(node, children.to_vec()).ok_or(Message::InvalidStructure)?;
+    let mut new_siblings = siblings.to_vec();
+    new_siblings.splice(index..=index, vec![arena.alloc(new_node), *last]);
+    let new_parent = with_children(parent, new_siblings).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, index))
+}
+
+/// Merges the child at `index` with its next sibling, by appending the sibling's children to the
+/// child's children.  The two nodes must be of the same kind, and at least one of them must have
+/// children (so that leaves like `true` can't be joined).
+pub fn join<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    parent: &'arena Node,
+    index: usize,
+) -> Result<(Node, usize), Message> {
+    let siblings = parent.children();
+    let node = siblings[index];
+    let next = *siblings.get(index + 1).ok_or(Message::NothingToJoin)?;
+    if node.kind_name() != next.kind_name() || node.value() != next.value() {
+        return Err(Message::CannotJoin(
+            node.kind_name().to_string(),
+            next.kind_name().to_string(),
+        ));
+    }
+    if node.children().is_empty() && next.children().is_empty() {
+        return Err(Message::NothingToJoin);
+    }
+    let children = node.children().iter().chain(next.children()).copied();
+    let new_node = with_children(node, children.collect()).ok_or(Message::InvalidStructure)?;
+    let mut new_siblings = siblings.to_vec();
+    new_siblings.splice(index..index + 2, std::iter::once(arena.alloc(new_node)));
+    let new_parent = with_children(parent, new_siblings).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, index))
+}
+
+/// Builds a copy of `parent` without its child at `index`
+pub fn remove_child<'arena, Node: Ast<'arena>>(
+    parent: &'arena Node,
+    index: usize,
+) -> Result<Node, Message> {
+    let mut children = parent.children().to_vec();
+    children.remove(index);
+    with_children(parent, children).ok_or(Message::InvalidStructure)
+}
+
+/// Applies `edit` to the node at `relative_path` within every child of `parent`, leaving any child
+/// which doesn't have a node at that path (or whose node can't be edited) unchanged.  The
+/// returned number is how many children were edited.
+pub fn edit_each<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    parent: &'arena Node,
+    relative_path: &[usize],
+    mut edit: impl FnMut(&'arena Node) -> Option<Node>,
+) -> Result<(Node, usize), Message> {
+    let mut num_edited = 0;
+    let children = parent
+        .children()
+        .iter()
+        .map(
+            |child| match edit_at(arena, child, relative_path, &mut edit) {
+                Some(new_child) => {
+                    num_edited += 1;
+                    arena.alloc(new_child)
+                }
+                None => *child,
+            },
+        )
+        .collect();
+    if num_edited == 0 {
+        return Err(Message::NothingToBatchEdit);
+    }
+    let new_parent = with_children(parent, children).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, num_edited))
+}
+
+/// Rebuilds `node` with `edit` applied to its descendant at `path`, or returns [`None`] if there
+/// is no such descendant or it can't be edited
+fn edit_at<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    node: &'arena Node,
+    path: &[usize],
+    edit: &mut impl FnMut(&'arena Node) -> Option<Node>,
+) -> Option<Node> {
+    match path.split_first() {
+        None => edit(node),
+        Some((index, rest)) => {
+            let new_child = edit_at(arena, node.children().get(*index)?, rest, edit)?;
+            let mut children = node.children().to_vec();
+            children[*index] = arena.alloc(new_child);
+            with_children(node, children)
+        }
+    }
+}
+
+/// Rebuilds `root` with the node at each path replaced, returning [`None`] if any of the paths
+/// don't exist or their replacements can't go there.  The paths must stay valid as the earlier
+/// replacements are made.
+pub fn replace_each<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    root: &'arena Node,
+    replacements: Vec<(CursorPath, Node)>,
+) -> Option<Node> {
+    let mut new_root = root.clone();
+    for (path, node) in replacements {
+        let path: Vec<usize> = path.iter().copied().collect();
+        let mut node = Some(node);
+        new_root = edit_at(arena, arena.alloc(new_root), &path, &mut |_| node.take())?;
+    }
+    Some(new_root)
+}
+
+/// Replaces the child at `index` with its children, so that they become children of `parent`.
+/// The returned index is that of the first of them.
+pub fn unwrap<'arena, Node: Ast<'arena>>(
+    parent: &'arena Node,
+    index: usize,
+) -> Result<(Node, usize), Message> {
+    let siblings = parent.children();
+    let children = siblings[index].children();
+    if children.is_empty() {
+        return Err(Message::NothingToUnwrap);
+    }
+    let mut new_siblings = siblings.to_vec();
+    new_siblings.splice(index..=index, children.iter().copied());
+    let new_parent = with_children(parent, new_siblings).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, index))
+}
+
+/// Builds the node represented by `c` (as in [`Ast::from_char`]) with `node` as its only child
+pub fn wrap<'arena, Node: Ast<'arena>>(node: &'arena Node, c: char) -> Result<Node, Message> {
+    let wrapper = node.from_char(c).ok_or(Message::CannotWrap(c))?;
+    with_children(&wrapper, vec![node]).ok_or(Message::CannotWrap(c))
+}
+
+/// Builds the node represented by `c` (as in [`Ast::from_char`]) with the value and children of
+/// `node`, e.g. turning an empty JSON array into an empty object.  This fails if the children
+/// can't go inside the new kind of node, e.g. a JSON array of values can't be turned into an
+/// object because objects can only contain fields.
+pub fn convert<'arena, Node: Ast<'arena>>(node: &'arena Node, c: char) -> Result<Node, Message> {
+    let kind = node.from_char(c).ok_or(Message::CannotConvert(c))?;
+    if kind.kind_name() == node.kind_name() {
+        return Err(Message::AlreadyOfKind(node.kind_name().to_string()));
+    }
+    Node::from_parts(
+        kind.kind_name(),
+        node.value().as_deref(),
+        node.children().to_vec(),
+    )
+    .ok_or(Message::CannotConvert(c))
+}
+
+/// Swaps the child at `index` with its next sibling (if `forward` is `true`) or its previous
+/// sibling.  The returned index is the swapped child's new index, so that the cursor stays on it.
+pub fn transpose<'arena, Node: Ast<'arena>>(
+    parent: &'arena Node,
+    index: usize,
+    forward: bool,
+) -> Result<(Node, usize), Message> {
+    let other_index = if forward {
+        Some(index + 1).filter(|i| *i < parent.children().len())
+    } else {
+        index.checked_sub(1)
+    }
+    .ok_or(Message::NothingToTranspose)?;
+    let mut new_siblings = parent.children().to_vec();
+    new_siblings.swap(index, other_index);
+    let new_parent = with_children(parent, new_siblings).ok_or(Message::InvalidStructure)?;
+    Ok((new_parent, other_index))
+}
+
+/// Returns the value of the field of `record` whose key is `key` (e.g. `true` for the key `a` in
+/// the JSON object `{"a": true}`), or [`None`] if `record` has no such field.  Fields are the
+/// children with an [`outline_label`](Ast::outline_label), whose first child is their key.
+fn field_value<'arena, Node: Ast<'arena>>(record: &'arena Node, key: &str) -> Option<&'arena Node> {
+    record
+        .children()
+        .iter()
+        .filter(|field| field.outline_label().is_some())
+        .find(|field| field.children().first().and_then(|k| k.value()).as_deref() == Some(key))
+        .and_then(|field| field.children().last().copied())
+}
+
+/// Returns the text that `node` is compared by when sorting.  Leaves are compared by their values
+/// (or names, if they have no value), and other nodes by the text of their children in order, so
+/// that (for example) JSON fields are sorted by their keys.
+fn sort_text<'arena, Node: Ast<'arena>>(node: &'arena Node) -> String {
+    if node.children().is_empty() {
+        return node.value().unwrap_or_else(|| node.display_name());
+    }
+    let texts: Vec<String> = node
+        .children()
+        .iter()
+        .map(|child| sort_text(*child))
+        .collect();
+    texts.join(" ")
+}
+
+/// Returns the text that every child of `node` is sorted or grouped by.  If `key` is given, this
+/// is the text of that field's value within each child, or [`None`] for children without it.
+fn child_keys<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    key: Option<&str>,
+) -> Result<Vec<Option<String>>, Message> {
+    if node.children().is_empty() {
+        return Err(Message::NothingToSort);
+    }
+    let keys: Vec<Option<String>> = node
+        .children()
+        .iter()
+        .map(|child| match key {
+            Some(key) => field_value(*child, key).map(sort_text),
+            None => Some(sort_text(*child)),
+        })
+        .collect();
+    match key {
+        Some(key) if keys.iter().all(Option::is_none) => Err(Message::NoSuchKey(key.to_string())),
+        _ => Ok(keys),
+    }
+}
+
+/// Sorts the children of `node`, either by their own text or (if `key` is given) by the value of
+/// the field `key` inside each of them.  Children without that field are moved to the end, and
+/// children with equal keys keep their order.
+pub fn sort_children<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    key: Option<&str>,
+) -> Result<Node, Message> {
+    let keys = child_keys(node, key)?;
+    let mut children: Vec<_> = node.children().iter().copied().zip(keys).collect();
+    children.sort_by(|(_, a), (_, b)| (a.is_none(), a).cmp(&(b.is_none(), b)));
+    let children = children.into_iter().map(|(child, _)| child).collect();
+    with_children(node, children).ok_or(Message::InvalidStructure)
+}
+
+/// Groups the children of `node` by the value of the field `key` inside each of them, moving each
+/// group into a new node of the same kind as `node`.  The groups are in the order that their
+/// first child appeared, and children without that field are grouped together at the end.  The
+/// returned number is how many groups were made.
+pub fn group_children<'arena, Node: Ast<'arena>>(
+    arena: &'arena Arena<Node>,
+    node: &'arena Node,
+    key: &str,
+) -> Result<(Node, usize), Message> {
+    let keys = child_keys(node, Some(key))?;
+    let mut groups: Vec<(Option<String>, Vec<&'arena Node>)> = Vec::new();
+    for (child, key) in node.children().iter().zip(keys) {
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, members)) => members.push(child),
+            None => groups.push((key, vec![child])),
+        }
+    }
+    groups.sort_by_key(|(key, _)| key.is_none());
+    let num_groups = groups.len();
+    let mut children = Vec::with_capacity(num_groups);
+    for (_, members) in groups {
+        let group = with_children(node, members).ok_or(Message::InvalidStructure)?;
+        children.push(arena.alloc(group));
+    }
+    let new_node = with_children(node, children).ok_or(Message::InvalidStructure)?;
+    Ok((new_node, num_groups))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        barf, convert, edit_each, group_children, join, slurp, sort_children, transpose, unwrap,
+        wrap,
+    };
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::messages::Message;
+
+    /// Converts the result of an edit into the text of the new parent
+    fn text<'a>(
+        arena: &'a Arena<JSON<'a>>,
+        result: Result<(JSON<'a>, usize), Message>,
+    ) -> Result<(String, usize), Message> {
+        result.map(|(node, index)| (arena.alloc(node).to_text(&JSONFormat::Compact), index))
+    }
+
+    /// Converts the result of an edit which builds a single node into the text of that node
+    fn node_text<'a>(
+        arena: &'a Arena<JSON<'a>>,
+        result: Result<JSON<'a>, Message>,
+    ) -> Result<String, Message> {
+        result.map(|node| arena.alloc(node).to_text(&JSONFormat::Compact))
+    }
+
+    #[test]
+    fn slurp_and_barf() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::False,
+            TestJSON::Object(vec![]),
+        ])
+        .add_to_arena(&arena);
+        assert_eq!(
+            text(&arena, slurp(&arena, root, 0)),
+            Ok(("[[true, false], {}]".to_string(), 0))
+        );
+        assert_eq!(
+            text(&arena, barf(&arena, root, 0)),
+            Ok(("[[], true, false, {}]".to_string(), 0))
+        );
+        assert_eq!(slurp(&arena, root, 2), Err(Message::NothingToSlurp));
+        assert_eq!(barf(&arena, root, 2), Err(Message::NothingToBarf));
+        // `false` can't have children
+        assert_eq!(slurp(&arena, root, 1), Err(Message::InvalidStructure));
+    }
+
+    #[test]
+    fn join_siblings() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::False, TestJSON::True]),
+            TestJSON::Object(vec![("a".to_string(), TestJSON::True)]),
+            TestJSON::Object(vec![("b".to_string(), TestJSON::False)]),
+            TestJSON::True,
+            TestJSON::True,
+        ])
+        .add_to_arena(&arena);
+        assert_eq!(
+            text(&arena, join(&arena, root, 0)),
+            Ok((
+                r#"[[true, false, true], {"a": true}, {"b": false}, true, true]"#.to_string(),
+                0
+            ))
+        );
+        assert_eq!(
+            text(&arena, join(&arena, root, 2)),
+            Ok((
+                r#"[[true], [false, true], {"a": true, "b": false}, true, true]"#.to_string(),
+                2
+            ))
+        );
+        assert_eq!(
+            join(&arena, root, 1),
+            Err(Message::CannotJoin(
+                "array".to_string(),
+                "object".to_string()
+            ))
+        );
+        assert_eq!(join(&arena, root, 4), Err(Message::NothingToJoin));
+        assert_eq!(join(&arena, root, 5), Err(Message::NothingToJoin));
+    }
+
+    #[test]
+    fn transpose_siblings() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::False,
+            TestJSON::Object(vec![]),
+        ])
+        .add_to_arena(&arena);
+        assert_eq!(
+            text(&arena, transpose(root, 0, true)),
+            Ok(("[false, true, {}]".to_string(), 1))
+        );
+        assert_eq!(
+            text(&arena, transpose(root, 2, false)),
+            Ok(("[true, {}, false]".to_string(), 1))
+        );
+        assert_eq!(transpose(root, 0, false), Err(Message::NothingToTranspose));
+        assert_eq!(transpose(root, 2, true), Err(Message::NothingToTranspose));
+    }
+
+    #[test]
+    fn wrap_unwrap_and_convert() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Array(vec![TestJSON::False, TestJSON::True]),
+            TestJSON::Object(vec![("a".to_string(), TestJSON::True)]),
+            TestJSON::Array(vec![]),
+        ])
+        .add_to_arena(&arena);
+        let children = root.children();
+        assert_eq!(
+            node_text(&arena, wrap(children[0], 'a')),
+            Ok("[true]".to_string())
+        );
+        // Objects can only contain fields, and `true` can't contain anything
+        assert_eq!(wrap(children[0], 'o'), Err(Message::CannotWrap('o')));
+        assert_eq!(wrap(children[0], 't'), Err(Message::CannotWrap('t')));
+
+        assert_eq!(
+            text(&arena, unwrap(root, 1)),
+            Ok((r#"[true, false, true, {"a": true}, []]"#.to_string(), 1))
+        );
+        assert_eq!(unwrap(root, 3), Err(Message::NothingToUnwrap));
+        // The fields of an object can't be put in an array
+        assert_eq!(unwrap(root, 2), Err(Message::InvalidStructure));
+
+        // Fields can only be in objects, and objects can only contain fields
+        assert_eq!(convert(children[2], 'a'), Err(Message::CannotConvert('a')));
+        assert_eq!(convert(children[1], 'o'), Err(Message::CannotConvert('o')));
+        assert_eq!(
+            node_text(&arena, convert(children[3], 'o')),
+            Ok("{}".to_string())
+        );
+        assert_eq!(
+            convert(children[3], 'a'),
+            Err(Message::AlreadyOfKind("array".to_string()))
+        );
+    }
+
+    #[test]
+    fn sort_and_group() {
+        let arena = Arena::new();
+        let record = |a: TestJSON, b: TestJSON| {
+            TestJSON::Object(vec![("a".to_string(), a), ("b".to_string(), b)])
+        };
+        let root = TestJSON::Array(vec![
+            record(TestJSON::True, TestJSON::True),
+            TestJSON::Object(vec![]),
+            record(TestJSON::False, TestJSON::True),
+            record(TestJSON::True, TestJSON::False),
+        ])
+        .add_to_arena(&arena);
+        fn text<'a>(arena: &'a Arena<JSON<'a>>) -> impl Fn(JSON<'a>) -> String + 'a {
+            move |node| arena.alloc(node).to_text(&JSONFormat::Compact)
+        }
+        // Records without the key go last, and records with equal keys keep their order
+        assert_eq!(
+            sort_children(root, Some("a")).map(text(&arena)),
+            Ok(concat!(
+                r#"[{"a": false, "b": true}, {"a": true, "b": true}, "#,
+                r#"{"a": true, "b": false}, {}]"#
+            )
+            .to_string())
+        );
+        assert_eq!(
+            sort_children(root, None).map(text(&arena)),
+            Ok(concat!(
+                r#"[{"a": false, "b": true}, {"a": true, "b": false}, "#,
+                r#"{"a": true, "b": true}, {}]"#
+            )
+            .to_string())
+        );
+        assert_eq!(
+            group_children(&arena, root, "b").map(|(node, n)| (text(&arena)(node), n)),
+            Ok((
+                concat!(
+                    r#"[[{"a": true, "b": true}, {"a": false, "b": true}], "#,
+                    r#"[{"a": true, "b": false}], [{}]]"#
+                )
+                .to_string(),
+                3
+            ))
+        );
+        assert_eq!(
+            sort_children(root, Some("c")),
+            Err(Message::NoSuchKey("c".to_string()))
+        );
+        assert_eq!(
+            sort_children(root.children()[1], None),
+            Err(Message::NothingToSort)
+        );
+    }
+
+    #[test]
+    fn edit_every_child() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True, TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::False]),
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+        ])
+        .add_to_arena(&arena);
+        fn replace<'a>(c: char) -> impl FnMut(&'a JSON<'a>) -> Option<JSON<'a>> {
+            move |node| node.from_char(c)
+        }
+        // The second array has no node at [1], so it's left alone
+        assert_eq!(
+            text(&arena, edit_each(&arena, root, &[1], replace('t'))),
+            Ok(("[[true, true], [false], [true, true]]".to_string(), 2))
+        );
+        assert_eq!(
+            text(&arena, edit_each(&arena, root, &[], replace('f'))),
+            Ok(("[false, false, false]".to_string(), 3))
+        );
+        assert_eq!(
+            edit_each(&arena, root, &[2], replace('t')),
+            Err(Message::NothingToBatchEdit)
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Schemas, which describe the keys that objects may have (along with the type and documentation
+//! of each key's value).  These are read from [JSON Schema](https://json-schema.org) documents,
+//! of which only `type`, `description`, `properties` and `items` are understood.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The description of a single value, and of the values inside it
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Schema {
+    /// The JSON Schema type of the value (e.g. `"string"` or `"object"`), if it has one
+    pub kind: Option<String>,
+    /// The documentation of the value, if it has any
+    pub description: Option<String>,
+    /// The keys which an object value may have, in the order they were written in the schema
+    pub properties: Vec<(String, Schema)>,
+    /// The schema of every element, if the value is an array
+    pub items: Option<Box<Schema>>,
+}
+
+impl Schema {
+    /// Reads a schema from the text of a JSON Schema document, returning an error message if the
+    /// text isn't valid JSON
+    pub fn parse(text: &str) -> Result<Schema, String> {
+        let mut reader = Reader {
+            chars: text.chars().peekable(),
+        };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        match reader.chars.next() {
+            None => Ok(Schema::from_value(&value)),
+            Some(c) => Err(format!("unexpected '{}' after the schema", c)),
+        }
+    }
+
+    /// Returns the schema of the value of a given key of an object
+    pub fn property(&self, key: &str) -> Option<&Schema> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, schema)| schema)
+    }
+
+    /// Builds a schema out of a JSON value.  Anything which doesn't describe a value (e.g. a
+    /// non-object `properties`) is ignored.
+    fn from_value(value: &Value) -> Schema {
+        let mut schema = Schema::default();
+        let fields = match value {
+            Value::Object(fields) => fields,
+            _ => return schema,
+        };
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("type", Value::String(kind)) => schema.kind = Some(kind.clone()),
+                // Of a list of types, the first which isn't `null` is the interesting one
+                ("type", Value::Array(kinds)) => {
+                    schema.kind = kinds.iter().find_map(|kind| match kind {
+                        Value::String(kind) if kind != "null" => Some(kind.clone()),
+                        _ => None,
+                    })
+                }
+                ("description", Value::String(text)) => schema.description = Some(text.clone()),
+                ("properties", Value::Object(properties)) => {
+                    schema.properties = properties
+                        .iter()
+                        .map(|(key, value)| (key.clone(), Schema::from_value(value)))
+                        .collect()
+                }
+                ("items", value) => schema.items = Some(Box::new(Schema::from_value(value))),
+                _ => {}
+            }
+        }
+        schema
+    }
+}
+
+/// Any JSON value.  Schemas can contain values (like numbers) which the trees being edited can't,
+/// so they are read into this rather than into nodes.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// `null`, `true`, `false` or a number, none of which say anything about keys
+    Scalar,
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// The state of reading a JSON document
+struct Reader<'t> {
+    chars: Peekable<Chars<'t>>,
+}
+
+impl Reader<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    /// Consumes the next non-whitespace char, returning an error if it isn't `c`
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(next) if next == c => Ok(()),
+            Some(next) => Err(format!("expected '{}' but found '{}'", c, next)),
+            None => Err(format!("expected '{}' but the schema ended", c)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                let items = self.sequence(']', Self::value)?;
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                let fields = self.sequence('}', |reader| {
+                    let key = reader.string()?;
+                    reader.expect(':')?;
+                    Ok((key, reader.value()?))
+                })?;
+                Ok(Value::Object(fields))
+            }
+            Some(c) if c.is_alphanumeric() || *c == '-' => {
+                // Scalars are skipped over, since their values are never needed
+                while self
+                    .chars
+                    .next_if(|c| c.is_alphanumeric() || "+-.".contains(*c))
+                    .is_some()
+                {}
+                Ok(Value::Scalar)
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("the schema ended early".to_string()),
+        }
+    }
+
+    /// Reads the items of an array or object (whose opening delimiter is next), each of which is
+    /// read by `item`
+    fn sequence<T>(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.chars.next();
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&close).is_some() {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(c) if c == close => return Ok(items),
+                _ => return Err(format!("expected ',' or '{}'", close)),
+            }
+        }
+    }
+
+    /// Reads a string, including its quotes
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut content = String::new();
+        loop {
+            let c = match self.chars.next() {
+                Some('"') => return Ok(content),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let hex: String = self.chars.by_ref().take(4).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    Some(c) => c,
+                    None => break,
+                },
+                Some(c) => c,
+                None => break,
+            };
+            content.push(c);
+        }
+        Err("a string in the schema isn't closed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+
+    #[test]
+    fn parse() {
+        let schema = Schema::parse(
+            r#"{
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "The user's \"name\"" },
+                    "age": { "type": ["null", "integer"], "minimum": 0 },
+                    "tags": { "type": "array", "items": { "type": "boolean" } }
+                },
+                "required": ["name"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(schema.kind.as_deref(), Some("object"));
+        let keys: Vec<_> = schema.properties.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["name", "age", "tags"]);
+        let name = schema.property("name").unwrap();
+        assert_eq!(name.description.as_deref(), Some("The user's \"name\""));
+        assert_eq!(
+            schema.property("age").unwrap().kind.as_deref(),
+            Some("integer")
+        );
+        let tags = schema.property("tags").unwrap();
+        assert_eq!(
+            tags.items.as_ref().unwrap().kind.as_deref(),
+            Some("boolean")
+        );
+        assert_eq!(schema.property("missing"), None);
+
+        for text in &["", "{", r#"{"a" 1}"#, "[1, 2", "{} {}", r#""abc"#] {
+            assert!(Schema::parse(text).is_err(), "{:?} should be invalid", text);
+        }
+    }
+}
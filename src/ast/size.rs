@@ -113,6 +113,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assign_op_pattern)] // We're explicitly testing `+` as well as `+=`
     fn add() {
         let tests: &[&[&str]] = &[
             &["[", "]"],
@@ -0,0 +1,123 @@
+//! Simple statistics over the leaves of some subtrees, for getting a feel for the data in a file
+//! without leaving the editor.
+
+use super::Ast;
+use std::collections::HashMap;
+
+/// How many of the most common values are reported
+const NUM_COMMON_VALUES: usize = 5;
+
+/// Aggregates of the values of some leaves
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// The number of leaves
+    pub count: usize,
+    /// The number of different values among the leaves
+    pub distinct: usize,
+    /// The most common values, with how many times they appear, most common first
+    pub common: Vec<(String, usize)>,
+    /// The number of leaves whose values are numbers
+    pub numbers: usize,
+    /// The sum of the values which are numbers
+    pub sum: f64,
+    /// The smallest and largest values.  These are compared as numbers if any values are numbers
+    /// (in which case the other values are ignored), and as text otherwise.
+    pub range: Option<(String, String)>,
+}
+
+impl Stats {
+    /// Computes the statistics of every leaf under the given nodes.  Keys (the first children of
+    /// nodes with an [`outline_label`](Ast::outline_label), like JSON fields) aren't counted.
+    pub fn of<'arena, Node: Ast<'arena>>(nodes: &[&'arena Node]) -> Stats {
+        let mut values = Vec::new();
+        for node in nodes {
+            add_leaf_values(*node, &mut values);
+        }
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for value in &values {
+            *counts.entry(value).or_default() += 1;
+        }
+        let mut common: Vec<(String, usize)> = counts
+            .iter()
+            .map(|(value, count)| (value.to_string(), *count))
+            .collect();
+        // Ties are broken by value, so that the result doesn't depend on the order of the map
+        common.sort_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+        common.truncate(NUM_COMMON_VALUES);
+        // Words like "inf" and "NaN" parse as numbers, but they're almost certainly meant as text
+        let numbers: Vec<f64> = values
+            .iter()
+            .filter_map(|v| v.parse().ok())
+            .filter(|n: &f64| n.is_finite())
+            .collect();
+        let range = if numbers.is_empty() {
+            let min = values.iter().min();
+            let max = values.iter().max();
+            min.zip(max).map(|(min, max)| (min.clone(), max.clone()))
+        } else {
+            let min = numbers.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            Some((min.to_string(), max.to_string()))
+        };
+        Stats {
+            count: values.len(),
+            distinct: counts.len(),
+            common,
+            numbers: numbers.len(),
+            sum: numbers.iter().sum(),
+            range,
+        }
+    }
+}
+
+/// Appends the values of every leaf under `node` to `values`, skipping keys
+fn add_leaf_values<'arena, Node: Ast<'arena>>(node: &'arena Node, values: &mut Vec<String>) {
+    let children = node.children();
+    if children.is_empty() {
+        values.push(node.value().unwrap_or_else(|| node.display_name()));
+        return;
+    }
+    let skip = if node.outline_label().is_some() { 1 } else { 0 };
+    for child in &children[skip..] {
+        add_leaf_values(*child, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+
+    #[test]
+    fn stats() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Object(vec![("a".to_string(), TestJSON::True)]),
+            TestJSON::True,
+            TestJSON::False,
+        ])
+        .add_to_arena(&arena);
+        let stats = Stats::of(&[root]);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.distinct, 2);
+        let common = vec![("true".to_string(), 2), ("false".to_string(), 1)];
+        assert_eq!(stats.common, common);
+        assert_eq!(stats.numbers, 0);
+        assert_eq!(stats.range, Some(("false".to_string(), "true".to_string())));
+
+        // Numbers are compared as numbers, ignoring the other values
+        let numbers: Vec<&JSON> = ["10", "9.5", "x", "-2", "NaN"]
+            .iter()
+            .map(|n| arena.alloc(JSON::Str(n.to_string())))
+            .collect();
+        let stats = Stats::of(&numbers);
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.numbers, 3);
+        assert_eq!(stats.sum, 17.5);
+        assert_eq!(stats.range, Some(("-2".to_string(), "10".to_string())));
+
+        assert_eq!(Stats::of::<JSON>(&[]).range, None);
+    }
+}
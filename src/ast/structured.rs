@@ -0,0 +1,328 @@
+//! A language-independent serialization of subtrees, which records the kind and value of every
+//! node so that a subtree can be rebuilt exactly without having to re-parse its text.
+//!
+//! A serialized node looks like `(kind "value" child1 child2 ...)`, where the value is optional.
+//! For example, the JSON `{"foo": [true]}` is serialized as
+//! `(object (field (string "foo") (array (true))))`.
+
+use super::Ast;
+use crate::arena::Arena;
+use crate::editable_tree::cursor_path::CursorPath;
+
+/// A node of a subtree, stored independently of any [`Arena`] or language
+#[derive(Debug, Eq, PartialEq)]
+pub struct StructuredNode {
+    /// The [`kind_name`](Ast::kind_name) of the node
+    pub kind: String,
+    /// The [`value`](Ast::value) of the node, if it has one
+    pub value: Option<String>,
+    /// The node's children, in order
+    pub children: Vec<StructuredNode>,
+}
+
+/// A [`StructuredNode`] parsed from a fragment of text (e.g. text pasted from another program)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Fragment {
+    /// The parsed node
+    pub node: StructuredNode,
+    /// The paths (relative to `node`) of nodes which were invented or cut short to complete a
+    /// truncated fragment, and so should be reviewed by the user
+    pub holes: Vec<CursorPath>,
+    /// `true` if the text ended before the fragment was complete
+    pub is_truncated: bool,
+}
+
+impl StructuredNode {
+    /// Creates the `StructuredNode` representing the subtree rooted at a given node
+    pub fn from_node<'arena, Node: Ast<'arena>>(node: &'arena Node) -> StructuredNode {
+        StructuredNode {
+            kind: node.kind_name().to_string(),
+            value: node.value(),
+            children: node
+                .children()
+                .iter()
+                .map(|child| StructuredNode::from_node(*child))
+                .collect(),
+        }
+    }
+
+    /// Rebuilds the subtree represented by this `StructuredNode`, adding all the descendants to a
+    /// given [`Arena`].  The root is returned without being added to the [`Arena`], so that it can
+    /// be passed straight to [`EditableTree::replace_cursor`](crate::editable_tree::EditableTree).
+    /// Returns [`None`] if this doesn't represent a valid subtree of `Node`s.
+    pub fn to_node<'arena, Node: Ast<'arena>>(&self, arena: &'arena Arena<Node>) -> Option<Node> {
+        self.fold(|node, children: Vec<Node>| {
+            let children = children.into_iter().map(|c| arena.alloc(c)).collect();
+            Node::from_parts(&node.kind, node.value.as_deref(), children)
+        })
+    }
+
+    /// Returns `true` if this represents the subtree rooted at `node`, i.e. if
+    /// [`to_node`](StructuredNode::to_node) would rebuild an equal subtree.  Unlike rebuilding the
+    /// subtree, this doesn't add anything to an arena.  The trees are walked without recursion.
+    pub fn describes<'arena, Node: Ast<'arena>>(&self, node: &'arena Node) -> bool {
+        let mut stack = vec![(self, node)];
+        while let Some((structure, node)) = stack.pop() {
+            if structure.kind != node.kind_name()
+                || structure.value != node.value()
+                || structure.children.len() != node.children().len()
+            {
+                return false;
+            }
+            stack.extend(
+                structure
+                    .children
+                    .iter()
+                    .zip(node.children().iter().copied()),
+            );
+        }
+        true
+    }
+
+    /// Builds a value for every node of this subtree from the node and the values built for its
+    /// children, returning the value for the root or [`None`] as soon as `build` does.  The tree
+    /// is walked without recursion, because it might be deep enough to overflow the stack.
+    fn fold<T>(&self, mut build: impl FnMut(&StructuredNode, Vec<T>) -> Option<T>) -> Option<T> {
+        // Each entry holds a node and the values of the children of it which have been built
+        let mut stack = vec![(self, Vec::with_capacity(self.children.len()))];
+        loop {
+            let (node, children) = stack.last().unwrap();
+            if let Some(child) = node.children.get(children.len()) {
+                stack.push((child, Vec::with_capacity(child.children.len())));
+                continue;
+            }
+            let (node, children) = stack.pop().unwrap();
+            let value = build(node, children)?;
+            match stack.last_mut() {
+                Some((_, siblings)) => siblings.push(value),
+                None => return Some(value),
+            }
+        }
+    }
+
+    /// Returns the number of nodes in this subtree, including its root
+    pub fn size(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(StructuredNode::size)
+            .sum::<usize>()
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a leaf, so that a leaf
+    /// has a depth of 1
+    pub fn depth(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(StructuredNode::depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Writes the serialized form of this `StructuredNode` to a [`String`]
+    pub fn write_serialized(&self, string: &mut String) {
+        string.push('(');
+        string.push_str(&self.kind);
+        if let Some(value) = &self.value {
+            string.push_str(" \"");
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    string.push('\\');
+                }
+                string.push(c);
+            }
+            string.push('"');
+        }
+        for child in &self.children {
+            string.push(' ');
+            child.write_serialized(string);
+        }
+        string.push(')');
+    }
+
+    /// Returns the serialized form of this `StructuredNode`
+    pub fn serialize(&self) -> String {
+        let mut s = String::new();
+        self.write_serialized(&mut s);
+        s
+    }
+
+    /// Parses the serialized form of a `StructuredNode`, returning [`None`] if the text isn't a
+    /// valid serialization.  Whitespace between items is ignored.
+    pub fn parse(text: &str) -> Option<StructuredNode> {
+        let mut chars = text.chars().peekable();
+        let node = parse_node(&mut chars)?;
+        skip_whitespace(&mut chars);
+        // There shouldn't be anything after the root node
+        match chars.next() {
+            Some(_) => None,
+            None => Some(node),
+        }
+    }
+}
+
+impl Clone for StructuredNode {
+    /// Copies the descendants without recursion, for the same reason as dropping them
+    fn clone(&self) -> StructuredNode {
+        self.fold(|node, children| {
+            Some(StructuredNode {
+                kind: node.kind.clone(),
+                value: node.value.clone(),
+                children,
+            })
+        })
+        .unwrap()
+    }
+}
+
+impl Drop for StructuredNode {
+    /// Drops the descendants one at a time, because dropping them recursively would overflow the
+    /// stack for deep enough trees
+    fn drop(&mut self) {
+        let mut nodes = std::mem::take(&mut self.children);
+        while let Some(mut node) = nodes.pop() {
+            nodes.append(&mut node.children);
+        }
+    }
+}
+
+type Chars<'s> = std::iter::Peekable<std::str::Chars<'s>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Parses a single node (and its children) from the front of `chars`
+fn parse_node(chars: &mut Chars) -> Option<StructuredNode> {
+    skip_whitespace(chars);
+    if chars.next()? != '(' {
+        return None;
+    }
+    // Read the kind, which runs up to the next whitespace or bracket
+    let mut kind = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            break;
+        }
+        kind.push(c);
+        chars.next();
+    }
+    if kind.is_empty() {
+        return None;
+    }
+    skip_whitespace(chars);
+    // Read the value, if there is one
+    let mut value = None;
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        let mut s = String::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => s.push(chars.next()?),
+                c => s.push(c),
+            }
+        }
+        value = Some(s);
+    }
+    // Read the children, up to the closing bracket
+    let mut children = Vec::new();
+    loop {
+        skip_whitespace(chars);
+        match chars.peek()? {
+            ')' => {
+                chars.next();
+                break;
+            }
+            _ => children.push(parse_node(chars)?),
+        }
+    }
+    Some(StructuredNode {
+        kind,
+        value,
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StructuredNode;
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+
+    #[test]
+    fn round_trip() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Object(vec![(r#"a "quoted\" key"#.to_string(), TestJSON::False)]),
+            TestJSON::Array(vec![]),
+        ])
+        .add_to_arena(&arena);
+        let structure = StructuredNode::from_node(root);
+        let text = structure.serialize();
+        assert_eq!(
+            text,
+            r#"(array (true) (object (field (string "a \"quoted\\\" key") (false))) (array))"#
+        );
+        assert_eq!(StructuredNode::parse(&text), Some(structure.clone()));
+        // Rebuilding the tree gives back the same nodes
+        let rebuilt: JSON = structure.to_node(&arena).unwrap();
+        assert_eq!(&rebuilt, root);
+        assert!(structure.describes(root));
+        assert!(!StructuredNode::parse("(array (true))")
+            .unwrap()
+            .describes(root));
+        assert!(!structure.describes(root.children()[0]));
+        assert_eq!(structure.size(), 7);
+        assert_eq!(structure.depth(), 4);
+    }
+
+    #[test]
+    fn deep() {
+        // Trees far too deep to walk recursively can be copied, rebuilt and dropped
+        let mut node = StructuredNode::parse("(true)").unwrap();
+        for _ in 0..100_000 {
+            node = StructuredNode {
+                kind: "array".to_string(),
+                value: None,
+                children: vec![node],
+            };
+        }
+        let copy = node.clone();
+        let arena = Arena::new();
+        let root: &JSON = arena.alloc(copy.to_node(&arena).unwrap());
+        assert_eq!(root.subtree_size(), 100_001);
+    }
+
+    #[test]
+    fn invalid() {
+        let arena = Arena::<JSON>::new();
+        for text in &[
+            "",
+            "true",
+            "(",
+            "(true",
+            "()",
+            "(true) (false)",
+            r#"(string "abc)"#,
+        ] {
+            assert_eq!(StructuredNode::parse(text), None, "{:?}", text);
+        }
+        // Well-formed structures can still describe invalid trees
+        for text in &[
+            "(bogus)",
+            "(true (false))",
+            "(object (true))",
+            "(field (true) (true))",
+        ] {
+            let structure = StructuredNode::parse(text).unwrap();
+            assert_eq!(structure.to_node(&arena), None, "{:?}", text);
+        }
+    }
+}
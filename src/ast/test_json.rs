@@ -2,6 +2,7 @@ use super::json::JSON;
 use crate::arena::Arena;
 
 /// A copy of [`JSON`] where nodes own their children
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TestJSON {
     True,
     False,
@@ -0,0 +1,263 @@
+//! Comments and deliberate blank lines (trivia), which aren't part of the tree but are kept so that
+//! they can be written back when the tree is saved.  Parsers record trivia against the paths of
+//! the nodes it comes before, and these are turned into anchors which survive edits elsewhere in
+//! the tree: a field is found again by its [`outline_label`](Ast::outline_label) (e.g. its key),
+//! and any other node by its index.  Trivia whose node has been deleted is dropped.
+
+use super::display_token::{write_indented_token_stream, DisplayToken, Indentation, RecTok};
+use super::layout;
+use super::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+use std::collections::HashMap;
+
+/// A single piece of trivia
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TriviaItem {
+    /// One or more blank lines
+    BlankLine,
+    /// A comment, including its delimiters (e.g. `// note` or `/* note */`)
+    Comment(String),
+}
+
+/// The trivia at one place in a document, in the order it appears
+pub type Trivia = Vec<TriviaItem>;
+
+/// Where some trivia is in a document, given the path `P` to a node
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Place<P> {
+    /// Just before the node
+    Before(P),
+    /// Just before the delimiter which closes the node (i.e. after its last child)
+    End(P),
+    /// After the whole document
+    EndOfDocument,
+}
+
+/// One step of the path to a node, which says which child of its parent the node is
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Segment {
+    /// The child with a given [`outline_label`](Ast::outline_label)
+    Label(String),
+    /// The child with a given index, for children without labels
+    Index(usize),
+}
+
+/// The trivia of a whole document
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct TriviaMap {
+    trivia: HashMap<Place<Vec<Segment>>, Trivia>,
+}
+
+impl TriviaMap {
+    /// Creates a map with no trivia
+    pub fn new() -> TriviaMap {
+        TriviaMap::default()
+    }
+
+    /// Anchors the trivia found by a parser (at places given by [`CursorPath`]s into `root`) to
+    /// the nodes of `root`.  Trivia at paths which don't exist in `root` is dropped.
+    pub fn from_parsed<'arena, Node: Ast<'arena>>(
+        root: &'arena Node,
+        parsed: Vec<(Place<CursorPath>, Trivia)>,
+    ) -> TriviaMap {
+        let anchor = |path: &CursorPath| -> Option<Vec<Segment>> {
+            let mut node = root;
+            let mut segments = Vec::new();
+            for &index in path.iter() {
+                node = *node.children().get(index)?;
+                segments.push(segment(node, index));
+            }
+            Some(segments)
+        };
+        let trivia = parsed
+            .into_iter()
+            .filter_map(|(place, trivia)| {
+                let place = match place {
+                    Place::Before(path) => Place::Before(anchor(&path)?),
+                    Place::End(path) => Place::End(anchor(&path)?),
+                    Place::EndOfDocument => Place::EndOfDocument,
+                };
+                Some((place, trivia))
+            })
+            .collect();
+        TriviaMap { trivia }
+    }
+
+    /// Returns `true` if the document has no trivia
+    pub fn is_empty(&self) -> bool {
+        self.trivia.is_empty()
+    }
+
+    fn get(&self, place: &Place<Vec<Segment>>) -> &[TriviaItem] {
+        self.trivia.get(place).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Returns the [`Segment`] which leads to `child`, the child at `index` of its parent
+fn segment<'arena, Node: Ast<'arena>>(child: &'arena Node, index: usize) -> Segment {
+    match child.outline_label() {
+        Some(label) => Segment::Label(label),
+        None => Segment::Index(index),
+    }
+}
+
+/// Returns the text of the tree under `root` with its trivia put back, like
+/// [`Ast::to_text`].  Every comment is followed by a line break (so that line comments don't
+/// swallow what comes after them), and lines containing trivia are never joined to fit the
+/// [`max_line_width`](Ast::max_line_width) of the format style.  Each level of indentation is
+/// written as `indentation`.
+pub fn write_text<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    format_style: &Node::FormatStyle,
+    trivia: &TriviaMap,
+    indentation: Indentation,
+) -> String {
+    let mut tokens = Vec::new();
+    add_tokens(root, format_style, trivia, &mut Vec::new(), &mut tokens);
+    for item in trivia.get(&Place::EndOfDocument) {
+        if let TriviaItem::Comment(comment) = item {
+            tokens.push((true, DisplayToken::Newline));
+            tokens.push((true, DisplayToken::Text(comment.clone())));
+        }
+    }
+    if let Some(max_width) = Node::max_line_width(format_style) {
+        tokens = layout::fit_to_width_with(tokens, max_width, |is_trivia| *is_trivia);
+    }
+    let mut text = String::new();
+    write_indented_token_stream(tokens, &mut text, indentation);
+    // Blank lines are indented like any other line, but shouldn't be left with trailing spaces
+    if trivia.is_empty() {
+        return text;
+    }
+    text.split('\n')
+        .map(|line| if line.trim().is_empty() { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Adds the tokens of `node` (which is at `path`) and its descendants to `tokens`, along with
+/// their trivia.  Each token is paired with `true` if it's part of the trivia.
+fn add_tokens<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    format_style: &Node::FormatStyle,
+    trivia: &TriviaMap,
+    path: &mut Vec<Segment>,
+    tokens: &mut Vec<(bool, DisplayToken)>,
+) {
+    for item in trivia.get(&Place::Before(path.clone())) {
+        match item {
+            // A blank line at the very start of the document isn't deliberate
+            TriviaItem::BlankLine if !tokens.is_empty() => {
+                tokens.push((true, DisplayToken::Newline));
+            }
+            TriviaItem::BlankLine => {}
+            TriviaItem::Comment(comment) => {
+                tokens.push((true, DisplayToken::Text(comment.clone())));
+                tokens.push((true, DisplayToken::Newline));
+            }
+        }
+    }
+    let rec_toks = node.display_tokens_rec(format_style);
+    // Trivia after the last child goes on its own line before the node's last dedent, or just
+    // before its closing delimiter if it isn't indented
+    let end_trivia = trivia.get(&Place::End(path.clone()));
+    let end_index = rec_toks
+        .iter()
+        .rposition(|tok| matches!(tok, RecTok::Tok(DisplayToken::Dedent)))
+        .or_else(|| {
+            rec_toks
+                .iter()
+                .rposition(|tok| matches!(tok, RecTok::Tok(DisplayToken::CloseDelim(_))))
+        });
+    let is_indented = end_index.is_some_and(|i| rec_toks[i] == RecTok::Tok(DisplayToken::Dedent));
+    // The children are expected to appear in the tokens in the same order as `children()`
+    let mut child_index = 0;
+    for (i, rec_tok) in rec_toks.into_iter().enumerate() {
+        if Some(i) == end_index {
+            for item in end_trivia {
+                if let TriviaItem::Comment(comment) = item {
+                    if is_indented {
+                        tokens.push((true, DisplayToken::Newline));
+                        tokens.push((true, DisplayToken::Text(comment.clone())));
+                    } else {
+                        tokens.push((true, DisplayToken::Whitespace(1)));
+                        tokens.push((true, DisplayToken::Text(comment.clone())));
+                        tokens.push((true, DisplayToken::Newline));
+                    }
+                }
+            }
+        }
+        match rec_tok {
+            RecTok::Tok(tok) => tokens.push((false, tok)),
+            RecTok::Child(child) => {
+                path.push(segment(child, child_index));
+                add_tokens(child, format_style, trivia, path, tokens);
+                path.pop();
+                child_index += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_text, Place, TriviaItem, TriviaMap};
+    use crate::arena::Arena;
+    use crate::ast::display_token::Indentation;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::json_stream::StreamingParser;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn round_trip() {
+        let text = r#"// Settings
+{
+    "a": true,
+
+    // The b list
+    "b": [
+        true,
+        /* inline */
+        false
+        // nothing else
+    ]
+}
+// end"#;
+        let mut parser = StreamingParser::new();
+        parser.feed(text).unwrap();
+        let (node, parsed) = parser.finish_with_trivia().unwrap();
+        let arena = Arena::new();
+        let root: &JSON = arena.alloc(node.to_node(&arena).unwrap());
+        let trivia = TriviaMap::from_parsed(root, parsed);
+        assert_eq!(
+            write_text(root, &JSONFormat::Pretty, &trivia, Indentation::default()),
+            text
+        );
+        // Trivia stays with its field when the fields before it change
+        let edited: &JSON = arena.alloc(
+            JSON::parse_fragment(r#"{"new": false, "b": [true, false]}"#)
+                .unwrap()
+                .node
+                .to_node(&arena)
+                .unwrap(),
+        );
+        let written = write_text(
+            edited,
+            &JSONFormat::Fit(80),
+            &trivia,
+            Indentation::default(),
+        );
+        assert!(written.contains("\"new\": false,\n\n    // The b list\n    \"b\": [\n"));
+        assert!(written.ends_with("]\n}\n// end"));
+        // Trivia at paths which don't exist is dropped
+        let trivia = TriviaMap::from_parsed(
+            root,
+            vec![(
+                Place::Before(CursorPath::from_vec(vec![5])),
+                vec![TriviaItem::BlankLine],
+            )],
+        );
+        assert!(trivia.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Parsing of Sapling's command line arguments.
+
+use std::path::{Path, PathBuf};
+
+/// How to use Sapling, printed when the arguments can't be parsed
+pub const USAGE: &str =
+    "usage: sapling [--language <name>] [--keymap <preset>] [--demo <script>] [<path>]\n       sapling [--keymap <preset>] --new <language>";
+
+/// The options given to Sapling on the command line
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Args {
+    /// The file to open, if any
+    pub path: Option<String>,
+    /// The language that the user asked for with `--language`, which overrides the language
+    /// detected from the file's extension
+    pub language: Option<String>,
+    /// Whether to start with a new document made from the language's template, rather than
+    /// opening a file
+    pub new: bool,
+    /// The script of a session to replay (given with `--demo`), if any
+    pub demo: Option<String>,
+    /// The name of the built-in keymap to use (given with `--keymap`), which overrides the one
+    /// chosen in the config file
+    pub keymap: Option<String>,
+}
+
+impl Args {
+    /// Parses the command line arguments (not including the name of the program)
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--language" || arg == "-l" {
+                let name = args
+                    .next()
+                    .ok_or_else(|| format!("'{}' needs a language name", arg))?;
+                parsed.language = Some(name);
+            } else if arg == "--new" {
+                let name = args
+                    .next()
+                    .ok_or_else(|| "'--new' needs a language name".to_string())?;
+                parsed.new = true;
+                parsed.language = Some(name);
+            } else if arg == "--demo" {
+                let script = args
+                    .next()
+                    .ok_or_else(|| "'--demo' needs the path of a script".to_string())?;
+                parsed.demo = Some(script);
+            } else if arg == "--keymap" {
+                let name = args
+                    .next()
+                    .ok_or_else(|| "'--keymap' needs the name of a preset".to_string())?;
+                parsed.keymap = Some(name);
+            } else if let Some(name) = arg.strip_prefix("--language=") {
+                parsed.language = Some(name.to_string());
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option '{}'", arg));
+            } else if parsed.path.is_none() {
+                parsed.path = Some(arg);
+            } else {
+                return Err("only one file can be opened at a time".to_string());
+            }
+        }
+        if parsed.new && parsed.path.is_some() {
+            return Err("'--new' can't be used with a file".to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// Returns the name of the language that the file should be edited as: either the one asked
+    /// for with `--language` or the one detected from the path's extension.  Returns [`None`] if
+    /// neither says what the language is.
+    pub fn language(&self) -> Option<String> {
+        self.language
+            .clone()
+            .or_else(|| language_of_path(self.path.as_ref()?).map(str::to_string))
+    }
+
+    /// Returns the directory which the search for a project config starts from: the directory of
+    /// the file being opened, or `working_dir` if no file is being opened
+    pub fn project_dir(&self, working_dir: &Path) -> PathBuf {
+        match &self.path {
+            Some(path) => match working_dir.join(path).parent() {
+                Some(dir) => dir.to_path_buf(),
+                None => working_dir.to_path_buf(),
+            },
+            None => working_dir.to_path_buf(),
+        }
+    }
+}
+
+/// Returns the name of the language of the file at a path, judging by its extension
+fn language_of_path(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    match extension.to_ascii_lowercase().as_str() {
+        "json" | "geojson" | "jsonc" => Some("json"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+    use std::path::{Path, PathBuf};
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+        let args = parse(&["data.JSON"]).unwrap();
+        assert_eq!(args.path.as_deref(), Some("data.JSON"));
+        assert_eq!(args.language().as_deref(), Some("json"));
+        let args = parse(&["-l", "json", "data.txt"]).unwrap();
+        assert_eq!(args.language().as_deref(), Some("json"));
+        let args = parse(&["--language=yaml", "data.json"]).unwrap();
+        assert_eq!(args.language().as_deref(), Some("yaml"));
+        assert_eq!(parse(&["data.txt"]).unwrap().language(), None);
+        assert!(parse(&["--language"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+        assert!(parse(&["a.json", "b.json"]).is_err());
+        let args = parse(&["--new", "json"]).unwrap();
+        assert!(args.new);
+        assert_eq!(args.language().as_deref(), Some("json"));
+        assert!(parse(&["--new"]).is_err());
+        assert!(parse(&["--new", "json", "a.json"]).is_err());
+        let args = parse(&["--demo", "intro.keys"]).unwrap();
+        assert_eq!(args.demo.as_deref(), Some("intro.keys"));
+        assert_eq!(args.path, None);
+        assert!(parse(&["--demo"]).is_err());
+        let args = parse(&["--keymap", "vim", "data.json"]).unwrap();
+        assert_eq!(args.keymap.as_deref(), Some("vim"));
+        assert!(parse(&["--keymap"]).is_err());
+    }
+
+    #[test]
+    fn project_dir() {
+        let working_dir = Path::new("/home/user");
+        let dir_of = |args: &[&str]| parse(args).unwrap().project_dir(working_dir);
+        assert_eq!(dir_of(&[]), PathBuf::from("/home/user"));
+        assert_eq!(dir_of(&["data.json"]), PathBuf::from("/home/user"));
+        assert_eq!(
+            dir_of(&["project/data.json"]),
+            PathBuf::from("/home/user/project")
+        );
+        assert_eq!(dir_of(&["/srv/data.json"]), PathBuf::from("/srv"));
+    }
+}
@@ -0,0 +1,190 @@
+//! Checks that an [`EditableTree`]'s history can be trusted: that moving to each
+//! [`HistoryEntry`] (by undoing, redoing or checking it out) restores both the tree and the
+//! cursor recorded in that entry.  These checks only use the public methods of
+//! [`EditableTree`], so they apply to any implementation and can be run on a live editor as well
+//! as in tests.
+
+use super::{EditableTree, HistoryEntry};
+use crate::ast::Ast;
+use crate::messages::Message;
+
+/// A way in which moving through a tree's history didn't give the state recorded in one of its
+/// [`HistoryEntry`]s.  Each problem holds the index of the entry.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum HistoryProblem {
+    /// Moving to the entry didn't restore the entry's tree
+    WrongRoot(usize),
+    /// Moving to the entry didn't restore the entry's cursor
+    WrongCursor(usize),
+    /// The entry's cursor isn't the path of a node in the entry's tree
+    InvalidCursor(usize),
+    /// Undoing or redoing from the entry didn't move one step (or moved when it shouldn't have)
+    WrongStep(usize),
+}
+
+impl HistoryProblem {
+    /// Describes the problem, to be shown in the log
+    pub fn message(self) -> Message {
+        match self {
+            HistoryProblem::WrongRoot(index) => Message::HistoryWrongRoot(index),
+            HistoryProblem::WrongCursor(index) => Message::HistoryWrongCursor(index),
+            HistoryProblem::InvalidCursor(index) => Message::HistoryInvalidCursor(index),
+            HistoryProblem::WrongStep(index) => Message::HistoryWrongStep(index),
+        }
+    }
+}
+
+/// Returns the problems with the current state of `tree`, given that it should be the state
+/// recorded in the history entry at `index`
+fn compare<'arena, Node: Ast<'arena> + 'arena, E: EditableTree<'arena, Node>>(
+    tree: &E,
+    index: usize,
+    entry: &HistoryEntry<'arena, Node>,
+) -> Vec<HistoryProblem> {
+    let mut problems = Vec::new();
+    if tree.history_index() != index {
+        problems.push(HistoryProblem::WrongStep(index));
+    }
+    if !std::ptr::eq(tree.root(), entry.root) {
+        problems.push(HistoryProblem::WrongRoot(index));
+    }
+    if *tree.cursor_path() != entry.cursor_path {
+        problems.push(HistoryProblem::WrongCursor(index));
+    }
+    if !entry.cursor_path.is_valid(entry.root) {
+        problems.push(HistoryProblem::InvalidCursor(index));
+    }
+    problems
+}
+
+/// Checks every entry in the history of `tree`, by undoing to the oldest entry, redoing to the
+/// newest and then checking each entry out directly.  Returns every problem found, or an empty
+/// list if the history is consistent.  The tree is left at the entry it started at, with its
+/// cursor where it was.
+pub fn check_history<'arena, Node: Ast<'arena> + 'arena, E: EditableTree<'arena, Node>>(
+    tree: &mut E,
+) -> Vec<HistoryProblem> {
+    let start_index = tree.history_index();
+    let start_cursor = tree.cursor_path().clone();
+    let entries = tree.history().to_vec();
+    let last_index = entries.len() - 1;
+    let mut problems = Vec::new();
+
+    // Undo all the way back, then redo all the way forward
+    for index in (0..start_index).rev() {
+        if !tree.undo() {
+            problems.push(HistoryProblem::WrongStep(index + 1));
+        }
+        problems.extend(compare(tree, index, &entries[index]));
+    }
+    if tree.undo() {
+        problems.push(HistoryProblem::WrongStep(0));
+    }
+    for (index, entry) in entries.iter().enumerate().skip(1) {
+        if !tree.redo() {
+            problems.push(HistoryProblem::WrongStep(index - 1));
+        }
+        problems.extend(compare(tree, index, entry));
+    }
+    if tree.redo() {
+        problems.push(HistoryProblem::WrongStep(last_index));
+    }
+    // Checking out an entry should restore it just like undoing or redoing to it
+    for (index, entry) in entries.iter().enumerate() {
+        if !tree.checkout(index) {
+            problems.push(HistoryProblem::WrongStep(index));
+        }
+        problems.extend(compare(tree, index, entry));
+    }
+    if tree.checkout(entries.len()) {
+        problems.push(HistoryProblem::WrongStep(last_index));
+    }
+
+    tree.checkout(start_index);
+    tree.jump_to(start_cursor);
+    // Each entry is visited several times, but its problems only need reporting once
+    problems.sort();
+    problems.dedup();
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_history;
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::editable_tree::dag::DAG;
+    use crate::editable_tree::{Direction, EditableTree};
+    use std::time::Instant;
+
+    /// Returns the path of the cursor as a list of child indices
+    fn cursor<'a>(tree: &DAG<'a, JSON<'a>>) -> Vec<usize> {
+        tree.cursor_path().iter().copied().collect()
+    }
+
+    #[test]
+    fn undo_and_redo_restore_cursor() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        tree.move_cursor(Direction::Down);
+        tree.move_cursor(Direction::Next);
+        tree.replace_cursor(JSON::Array(Vec::new()));
+        tree.insert_child(JSON::True);
+        assert_eq!(cursor(&tree), vec![1, 0]);
+        // Moving after an edit doesn't change where its entry says the cursor was
+        tree.jump_to(CursorPath::root());
+        assert_eq!(
+            tree.current_entry().cursor_path,
+            CursorPath::from_vec(vec![1, 0])
+        );
+
+        assert!(tree.undo());
+        assert_eq!(cursor(&tree), vec![1]);
+        assert!(std::ptr::eq(tree.root(), tree.history()[1].root));
+        assert!(tree.undo());
+        assert_eq!(cursor(&tree), Vec::<usize>::new());
+        assert!(std::ptr::eq(tree.root(), root));
+        assert!(tree.redo());
+        assert!(tree.redo());
+        assert_eq!(cursor(&tree), vec![1, 0]);
+        assert!(std::ptr::eq(tree.root(), tree.latest_root()));
+        assert_eq!(check_history(&mut tree), vec![]);
+    }
+
+    #[test]
+    fn every_kind_of_edit() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![(
+            "a".to_string(),
+            TestJSON::Array(vec![TestJSON::True]),
+        )])
+        .add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        assert_eq!(check_history(&mut tree), vec![]);
+
+        tree.jump_to(CursorPath::from_vec(vec![0, 1, 0]));
+        tree.replace_cursor(JSON::False);
+        let now = Instant::now();
+        tree.jump_to(CursorPath::from_vec(vec![0, 0]));
+        tree.replace_cursor_coalesced(JSON::Str("b".to_string()), now);
+        tree.replace_cursor_coalesced(JSON::Str("bc".to_string()), now);
+        tree.jump_to(CursorPath::from_vec(vec![0, 1]));
+        tree.insert_child(JSON::True);
+        let parent = JSON::Array(vec![arena.alloc(JSON::True), tree.cursor()]);
+        tree.replace_parent(parent, 1);
+        // Undoing and then editing throws away the entries which could have been redone
+        tree.undo();
+        tree.undo();
+        tree.insert_child(JSON::Object(Vec::new()));
+        tree.move_cursor(Direction::Up);
+        tree.undo();
+
+        let (index, path) = (tree.history_index(), tree.cursor_path().clone());
+        assert_eq!(check_history(&mut tree), vec![]);
+        // Checking leaves the tree as it was
+        assert_eq!((tree.history_index(), tree.cursor_path()), (index, &path));
+    }
+}
@@ -1,7 +1,8 @@
 use crate::ast::Ast;
 
-/// A tree-independent struct for representing the locations of nodes within trees.
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// A tree-independent struct for representing the locations of nodes within trees.  Paths are
+/// ordered in the same way as their nodes are in a depth-first pre-order walk of the tree.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct CursorPath {
     child_indices: Vec<usize>,
 }
@@ -57,6 +58,12 @@ impl CursorPath {
         self.child_indices.push(new_child_index);
     }
 
+    /// Pushes several child indices onto the path, in order
+    #[inline]
+    pub fn extend(&mut self, child_indices: impl IntoIterator<Item = usize>) {
+        self.child_indices.extend(child_indices);
+    }
+
     /// Removes and returns the last child from the path (if it exists).  This has the effect of
     /// moving the cursor to its parent, and returning the old cursor's sibling index.  Returns
     /// `None` if the path referred to the root of the tree (and therefore the pop had no effect).
@@ -71,6 +78,41 @@ impl CursorPath {
         self.child_indices.is_empty()
     }
 
+    /// Returns how many levels below the root this path points (i.e. the number of segments in
+    /// the path).  The root has depth `0`.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.child_indices.len()
+    }
+
+    /// Returns `true` if every child index in this path refers to a child that exists when the
+    /// path is walked down from `root`.
+    pub fn is_valid<'arena, Node: Ast<'arena>>(&self, root: &'arena Node) -> bool {
+        // `NodeIter` stops as soon as it finds an invalid index, so the path is valid only if it
+        // yields one node per segment plus the root
+        self.node_iter(root).count() == self.depth() + 1
+    }
+
+    /// Returns the path to the nearest strict ancestor of this path's node whose
+    /// [`kind_name`](Ast::kind_name) is `kind`, or [`None`] if there is no such ancestor.
+    pub fn enclosing<'arena, Node: Ast<'arena>>(
+        &self,
+        root: &'arena Node,
+        kind: &str,
+    ) -> Option<CursorPath> {
+        // The `n`th node returned by `node_iter` is reached by the first `n` child indices
+        let nodes: Vec<&Node> = self.node_iter(root).collect();
+        let depth = nodes[..nodes.len() - 1]
+            .iter()
+            .rposition(|node| node.kind_name() == kind)?;
+        Some(CursorPath::from_vec(self.child_indices[..depth].to_vec()))
+    }
+
+    /// Returns `true` if this path goes through the node at `ancestor` (or is `ancestor` itself)
+    pub fn starts_with(&self, ancestor: &CursorPath) -> bool {
+        self.child_indices.starts_with(&ancestor.child_indices)
+    }
+
     /// Returns a mutable reference to the last child index in the path (if it exists).
     #[inline]
     pub fn last_mut(&mut self) -> Option<&mut usize> {
@@ -90,10 +132,140 @@ impl CursorPath {
     where
         Node: Ast<'arena>,
     {
-        NodeIter::new(root, &self)
+        NodeIter::new(root, self)
     }
 }
 
+/// Paths are shown as their child indices separated by dots (e.g. `0.2`), or as `root` if they
+/// point to the root
+impl std::fmt::Display for CursorPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_root() {
+            return write!(f, "root");
+        }
+        let indices: Vec<String> = self.iter().map(usize::to_string).collect();
+        write!(f, "{}", indices.join("."))
+    }
+}
+
+/// Finds the path of `node`, which was at `old_path` before an edit at `edit_path`: `old_path`
+/// itself if the edit didn't move the node, or wherever [`find_moved`] finds it.  Returns
+/// [`None`] if the node isn't in the tree any more.
+pub fn follow<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    old_path: &CursorPath,
+    node: &'arena Node,
+    edit_path: &CursorPath,
+) -> Option<CursorPath> {
+    if old_path.is_valid(root) && std::ptr::eq(old_path.cursor(root), node) {
+        return Some(old_path.clone());
+    }
+    find_moved(root, old_path, node, edit_path)
+}
+
+/// Finds the new path of `node`, which was at `old_path` before an edit at `edit_path`.  An edit
+/// can only move nodes around the parent of the edited node (e.g. by inserting or deleting a
+/// sibling, or wrapping or unwrapping the node), so only the part of the tree below that parent,
+/// and at most one level deeper than `old_path`, is searched.  Unchanged subtrees are shared
+/// between the old and new trees, so the node is recognised by identity.
+pub fn find_moved<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    old_path: &CursorPath,
+    node: &'arena Node,
+    edit_path: &CursorPath,
+) -> Option<CursorPath> {
+    let common = old_path
+        .iter()
+        .zip(edit_path.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let start: Vec<usize> = old_path
+        .iter()
+        .take(common.saturating_sub(1))
+        .copied()
+        .collect();
+    let start = CursorPath::from_vec(start);
+    if !start.is_valid(root) {
+        return None;
+    }
+    let max_depth = old_path.depth() + 1 - start.depth();
+    // Depth-first search, keeping the nodes being searched and the index of the next child to
+    // visit for each so that no paths have to be built until the node is found
+    let mut stack = vec![(start.cursor(root), 0)];
+    while let Some(&(parent, next)) = stack.last() {
+        let depth = stack.len();
+        if std::ptr::eq(parent, node) {
+            let mut path = start;
+            path.extend(stack[..depth - 1].iter().map(|(_, next)| next - 1));
+            return Some(path);
+        }
+        match parent.children().get(next) {
+            Some(child) if depth <= max_depth => {
+                stack[depth - 1].1 += 1;
+                stack.push((*child, 0));
+            }
+            _ => {
+                stack.pop();
+            }
+        }
+    }
+    None
+}
+
+/// Returns the path to the smallest subtree which holds every difference between two trees, or
+/// [`None`] if the trees are the same.  A tree made by editing another shares every subtree that
+/// the edit didn't touch, so unchanged subtrees are recognised by identity without comparing
+/// their contents.
+pub fn changed_subtree<'arena, Node: Ast<'arena>>(
+    old_root: &'arena Node,
+    new_root: &'arena Node,
+) -> Option<CursorPath> {
+    let mut path = CursorPath::root();
+    let (mut old, mut new) = (old_root, new_root);
+    // Follow the only changed child for as long as the node itself hasn't changed
+    while old.kind_name() == new.kind_name()
+        && old.value() == new.value()
+        && old.children().len() == new.children().len()
+    {
+        let mut changed = old
+            .children()
+            .iter()
+            .zip(new.children())
+            .enumerate()
+            .filter(|(_, (old_child, new_child))| !std::ptr::eq(**old_child, **new_child));
+        match (changed.next(), changed.next()) {
+            (Some((index, (old_child, new_child))), None) => {
+                path.push(index);
+                old = old_child;
+                new = new_child;
+            }
+            // Neither the node nor any of its children have changed
+            (None, _) => return None,
+            _ => break,
+        }
+    }
+    Some(path)
+}
+
+/// Returns every node in the tree under `root`, along with the path to that node, in depth-first
+/// pre-order (i.e. every node is returned before its children, and the children are returned in
+/// order).
+pub fn all_paths<'arena, Node: Ast<'arena>>(root: &'arena Node) -> Vec<(CursorPath, &'arena Node)> {
+    let mut paths = Vec::new();
+    // A stack of nodes which still have to be visited.  Children are pushed in reverse order, so
+    // that they are popped in the right order.
+    let mut nodes_to_visit = vec![(CursorPath::root(), root)];
+    while let Some((path, node)) = nodes_to_visit.pop() {
+        for (i, &child) in node.children().iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            nodes_to_visit.push((child_path, child));
+        }
+        paths.push((path, node));
+    }
+    paths
+}
+
 /// An iterator that walks down a tree following a [`CursorPath`].  The first item returned from
 /// this iterator is always the root of the tree.  As a consequence, this yields one more AST node
 /// than the original tree had.
@@ -174,6 +346,96 @@ mod tests {
         assert!(path.is_root());
     }
 
+    #[test]
+    fn display() {
+        assert_eq!(CursorPath::root().to_string(), "root");
+        assert_eq!(CursorPath::from_vec(vec![0, 2]).to_string(), "0.2");
+    }
+
+    #[test]
+    fn is_valid() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Object(vec![("value".to_string(), TestJSON::True)]),
+        ])
+        .add_to_arena(&arena);
+        for (indices, expected_validity) in &[
+            (vec![], true),
+            (vec![0], true),
+            (vec![1, 0, 1], true),
+            (vec![2], false),
+            (vec![0, 0], false),
+            (vec![1, 1], false),
+        ] {
+            let path = CursorPath::from_vec(indices.clone());
+            assert_eq!(path.is_valid(root), *expected_validity);
+            assert_eq!(path.depth(), indices.len());
+        }
+    }
+
+    #[test]
+    fn enclosing() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Array(vec![TestJSON::Object(vec![(
+                "value".to_string(),
+                TestJSON::Array(vec![TestJSON::False]),
+            )])]),
+        ])
+        .add_to_arena(&arena);
+        let path = CursorPath::from_vec(vec![1, 0, 0, 1, 0]);
+        for (kind, expected_path) in &[
+            ("array", Some(vec![1, 0, 0, 1])),
+            ("field", Some(vec![1, 0, 0])),
+            ("object", Some(vec![1, 0])),
+            ("false", None),
+            ("string", None),
+        ] {
+            assert_eq!(
+                path.enclosing(root, kind),
+                expected_path.clone().map(CursorPath::from_vec)
+            );
+        }
+        // The cursor itself doesn't count, so repeating the selection moves further out
+        let outer = CursorPath::from_vec(vec![1, 0, 0, 1]);
+        assert_eq!(
+            outer.enclosing(root, "array"),
+            Some(CursorPath::from_vec(vec![1]))
+        );
+        assert_eq!(CursorPath::root().enclosing(root, "array"), None);
+    }
+
+    #[test]
+    fn all_paths() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Object(vec![("value".to_string(), TestJSON::False)]),
+        ])
+        .add_to_arena(&arena);
+        let paths = super::all_paths(root);
+        assert_eq!(
+            paths
+                .iter()
+                .map(|(path, node)| (path.iter().copied().collect(), node.display_name()))
+                .collect::<Vec<(Vec<usize>, String)>>(),
+            vec![
+                (vec![], "array".to_string()),
+                (vec![0], "true".to_string()),
+                (vec![1], "object".to_string()),
+                (vec![1, 0], "field".to_string()),
+                (vec![1, 0, 0], r#""value""#.to_string()),
+                (vec![1, 0, 1], "false".to_string()),
+            ]
+        );
+        // Every path should lead to the node it's paired with
+        for (path, node) in paths {
+            assert!(std::ptr::eq(path.cursor(root), node));
+        }
+    }
+
     #[test]
     fn node_iter() {
         // Create some test JSON and add it to an arena
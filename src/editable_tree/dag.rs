@@ -1,7 +1,9 @@
 use super::cursor_path::CursorPath;
-use super::{Direction, EditableTree};
+use super::{is_same_tree, Direction, EditableTree, HistoryEntry, COALESCE_PAUSE};
 use crate::arena::Arena;
 use crate::ast::Ast;
+use crate::messages::Message;
+use std::time::{Instant, SystemTime};
 
 /// An [`EditableTree`] that stores the history as a DAG (Directed Acyclic Graph) of **immutable**
 /// nodes.
@@ -18,11 +20,21 @@ pub struct DAG<'arena, Node: Ast<'arena>> {
     arena: &'arena Arena<Node>,
     /// A [`Vec`] containing a reference to the root node at every edit in the undo history.  This
     /// is required to always have length at least one.
-    root_history: Vec<(&'arena Node, CursorPath)>,
+    root_history: Vec<HistoryEntry<'arena, Node>>,
     /// An index into [`root_history`](DAG::root_history) of the current edit.  This is required to
     /// be in `0..root_history.len()`.
     history_index: usize,
     current_cursor_path: CursorPath,
+    /// An index into [`root_history`](DAG::root_history) of the edit which was last saved, or
+    /// [`None`] if that edit has been removed from the history
+    saved_index: Option<usize>,
+    /// The root of the tree which was last saved
+    saved_root: &'arena Node,
+    /// The time of the latest edit made by
+    /// [`replace_cursor_coalesced`](EditableTree::replace_cursor_coalesced), or [`None`] if
+    /// anything else has happened since then (in which case the next edit can't be merged into
+    /// the latest history entry).
+    last_coalesced_edit: Option<Instant>,
 }
 
 impl<'arena, Node: Ast<'arena>> DAG<'arena, Node> {
@@ -30,27 +42,93 @@ impl<'arena, Node: Ast<'arena>> DAG<'arena, Node> {
     fn cursor_and_parent(&self) -> (&'arena Node, Option<&'arena Node>) {
         self.current_cursor_path.cursor_and_parent(self.root())
     }
+
+    /// Builds a new tree that is the same as the current tree, except that the node under the
+    /// cursor is replaced with `new_node`.  Returns the root of the new tree, without adding it to
+    /// the history.
+    fn root_with_cursor_replaced(&self, new_node: Node) -> &'arena Node {
+        // Generate a vec of pointers to the nodes that we will have to clone.  We have to store
+        // this as a vec because the iterator that produces them (cursor_path::NodeIter) can only
+        // yield values from the root downwards, whereas we need the nodes in the opposite order.
+        let mut nodes_to_clone: Vec<_> = self.current_cursor_path.node_iter(self.root()).collect();
+        // The last value of nodes_to_clone is the node under the cursor, which we do not need to
+        // clone, so we pop that reference.
+        assert!(nodes_to_clone.pop().is_some());
+        /* Because AST nodes are immutable, we make changes to nodes by entirely cloning the path
+         * down to the node under the cursor.  We do this starting at the node under the cursor and
+         * work our way up parent by parent until we reach the root of the tree.  At that point,
+         * this node becomes the root of the new tree.
+         */
+        let mut node = self.arena.alloc(new_node);
+        // Iterate backwards over the child indices and the nodes, whilst cloning the tree and
+        // replacing the correct child reference to point to the newly created node.
+        for (n, child_index) in nodes_to_clone
+            .iter()
+            .rev()
+            .zip(self.current_cursor_path.iter().rev())
+        {
+            let mut cloned_node = (*n).clone();
+            cloned_node.children_mut()[*child_index] = node;
+            node = self.arena.alloc(cloned_node);
+        }
+        node
+    }
+
+    /// Adds a new root to the end of the history, and makes it the current root
+    fn push_root(&mut self, root: &'arena Node) {
+        // Remove future trees from the history vector so that the currently 'checked-out' tree is
+        // the most recent tree in the history.
+        while self.history_index < self.root_history.len() - 1 {
+            // TODO: Deallocate the tree so that we don't get a 'memory leak'
+            self.root_history.pop();
+        }
+        if self.saved_index > Some(self.history_index) {
+            self.saved_index = None;
+        }
+        // Add the new root to the history, along with the cursor path.
+        self.root_history.push(HistoryEntry {
+            root,
+            cursor_path: self.current_cursor_path.clone(),
+            time: SystemTime::now(),
+            summary: None,
+        });
+        // Move the history index on by one so that we are pointing at the latest change
+        self.history_index = self.root_history.len() - 1;
+    }
 }
 
 impl<'arena, Node: Ast<'arena>> EditableTree<'arena, Node> for DAG<'arena, Node> {
     fn new(arena: &'arena Arena<Node>, root: &'arena Node) -> Self {
         DAG {
             arena,
-            root_history: vec![(root, CursorPath::root())],
+            root_history: vec![HistoryEntry {
+                root,
+                cursor_path: CursorPath::root(),
+                time: SystemTime::now(),
+                summary: None,
+            }],
             history_index: 0,
             current_cursor_path: CursorPath::root(),
+            saved_index: Some(0),
+            saved_root: root,
+            last_coalesced_edit: None,
         }
     }
 
+    fn arena(&self) -> &'arena Arena<Node> {
+        self.arena
+    }
+
     /* HISTORY METHODS */
 
     fn undo(&mut self) -> bool {
+        self.last_coalesced_edit = None;
         if self.history_index > 0 {
             self.history_index -= 1;
             // Follow the behaviour of other text editors and update the location of the cursor
             // with its location in the snapshot we are going back to
             self.current_cursor_path
-                .clone_from(&self.root_history[self.history_index].1);
+                .clone_from(&self.root_history[self.history_index].cursor_path);
             true
         } else {
             false
@@ -58,36 +136,93 @@ impl<'arena, Node: Ast<'arena>> EditableTree<'arena, Node> for DAG<'arena, Node>
     }
 
     fn redo(&mut self) -> bool {
+        self.last_coalesced_edit = None;
         if self.history_index < self.root_history.len() - 1 {
             self.history_index += 1;
             // Follow the behaviour of other text editors and update the location of the cursor
             // with its location in the snapshot we are going back to
             self.current_cursor_path
-                .clone_from(&self.root_history[self.history_index].1);
+                .clone_from(&self.root_history[self.history_index].cursor_path);
             true
         } else {
             false
         }
     }
 
+    fn redo_depth(&self) -> usize {
+        self.root_history.len() - 1 - self.history_index
+    }
+
+    fn latest_root(&self) -> &'arena Node {
+        // `root_history` always has at least one element
+        self.root_history.last().unwrap().root
+    }
+
+    fn history(&self) -> &[HistoryEntry<'arena, Node>] {
+        &self.root_history
+    }
+
+    fn history_index(&self) -> usize {
+        self.history_index
+    }
+
+    fn checkout(&mut self, index: usize) -> bool {
+        if index >= self.root_history.len() {
+            return false;
+        }
+        self.last_coalesced_edit = None;
+        self.history_index = index;
+        self.current_cursor_path
+            .clone_from(&self.root_history[index].cursor_path);
+        true
+    }
+
+    fn set_summary(&mut self, summary: String) {
+        self.root_history[self.history_index].summary = Some(summary);
+    }
+
+    fn mark_saved(&mut self) {
+        // Later typing mustn't change the entry which was saved
+        self.last_coalesced_edit = None;
+        self.saved_index = Some(self.history_index);
+        self.saved_root = self.root();
+    }
+
+    fn saved_index(&self) -> Option<usize> {
+        self.saved_index
+    }
+
+    fn saved_root(&self) -> &'arena Node {
+        self.saved_root
+    }
+
+    fn has_unsaved_changes(&self) -> bool {
+        !is_same_tree(self.saved_root, self.root())
+    }
+
     /* NAVIGATION METHODS */
 
     fn root(&self) -> &'arena Node {
         // This indexing shouldn't panic because we require that `self.history_index` is a valid index
         // into `self.root_history`, and `self.root_history` has at least one element
-        self.root_history[self.history_index].0
+        self.root_history[self.history_index].root
     }
 
     fn cursor(&self) -> &'arena Node {
         self.current_cursor_path.cursor(self.root())
     }
 
-    fn move_cursor(&mut self, direction: Direction) -> Option<String> {
+    fn cursor_path(&self) -> &CursorPath {
+        &self.current_cursor_path
+    }
+
+    fn move_cursor(&mut self, direction: Direction) -> Option<Message> {
+        self.last_coalesced_edit = None;
         let (current_cursor, cursor_parent) = self.cursor_and_parent();
         match direction {
             Direction::Down => {
                 if current_cursor.children().is_empty() {
-                    Some("Cannot move down the tree if the cursor has no children.".to_string())
+                    Some(Message::CannotMoveDown)
                 } else {
                     self.current_cursor_path.push(0);
                     None
@@ -95,7 +230,7 @@ impl<'arena, Node: Ast<'arena>> EditableTree<'arena, Node> for DAG<'arena, Node>
             }
             Direction::Up => {
                 if self.current_cursor_path.is_root() {
-                    return Some("Cannot move to the parent of the root.".to_string());
+                    return Some(Message::CannotMoveUp);
                 }
                 self.current_cursor_path.pop();
                 None
@@ -103,13 +238,13 @@ impl<'arena, Node: Ast<'arena>> EditableTree<'arena, Node> for DAG<'arena, Node>
             Direction::Prev => {
                 if let Some(index) = self.current_cursor_path.last_mut() {
                     if *index == 0 {
-                        Some("Cannot move before the first child of a node.".to_string())
+                        Some(Message::CannotMovePrev)
                     } else {
                         *index -= 1;
                         None
                     }
                 } else {
-                    Some("Cannot move to a sibling of the root.".to_string())
+                    Some(Message::CannotMoveToSiblingOfRoot)
                 }
             }
             Direction::Next => {
@@ -121,59 +256,299 @@ impl<'arena, Node: Ast<'arena>> EditableTree<'arena, Node> for DAG<'arena, Node>
                         *last_index += 1;
                         None
                     } else {
-                        Some("Cannot move past the last sibling of a node.".to_string())
+                        Some(Message::CannotMoveNext)
                     }
                 } else {
-                    Some("Cannot move to a sibling of the root.".to_string())
+                    Some(Message::CannotMoveToSiblingOfRoot)
                 }
             }
         }
     }
 
+    fn jump_to(&mut self, path: CursorPath) -> Option<Message> {
+        self.last_coalesced_edit = None;
+        if path.is_valid(self.root()) {
+            self.current_cursor_path = path;
+            None
+        } else {
+            Some(Message::CannotJump)
+        }
+    }
+
     fn replace_cursor(&mut self, new_node: Node) {
-        // Remove future trees from the history vector so that the currently 'checked-out' tree is
-        // the most recent tree in the history.
-        while self.history_index < self.root_history.len() - 1 {
-            // TODO: Deallocate the tree so that we don't get a 'memory leak'
-            self.root_history.pop();
+        self.last_coalesced_edit = None;
+        let new_root = self.root_with_cursor_replaced(new_node);
+        self.push_root(new_root);
+    }
+
+    fn replace_cursor_coalesced(&mut self, new_node: Node, time: Instant) {
+        let new_root = self.root_with_cursor_replaced(new_node);
+        let can_coalesce = self
+            .last_coalesced_edit
+            .is_some_and(|last_time| time.saturating_duration_since(last_time) < COALESCE_PAUSE);
+        if can_coalesce {
+            // Nothing has happened since the last coalesced edit, so the current root is the
+            // latest history entry and was made by that edit.  The cursor hasn't moved, so the
+            // entry's cursor path is still correct.
+            let entry = &mut self.root_history[self.history_index];
+            entry.root = new_root;
+            entry.time = SystemTime::now();
+        } else {
+            self.push_root(new_root);
         }
-        // Generate a vec of pointers to the nodes that we will have to clone.  We have to store
-        // this as a vec because the iterator that produces them (cursor_path::NodeIter) can only
-        // yield values from the root downwards, whereas we need the nodes in the opposite order.
-        let mut nodes_to_clone: Vec<_> = self.current_cursor_path.node_iter(self.root()).collect();
-        // The last value of nodes_to_clone is the node under the cursor, which we do not need to
-        // clone, so we pop that reference.
-        assert!(nodes_to_clone.pop().is_some());
-        /* Because AST nodes are immutable, we make changes to nodes by entirely cloning the path
-         * down to the node under the cursor.  We do this starting at the node under the cursor and
-         * work our way up parent by parent until we reach the root of the tree.  At that point,
-         * this node becomes the root of the new tree.
-         */
-        let mut node = self.arena.alloc(new_node);
-        // Iterate backwards over the child indices and the nodes, whilst cloning the tree and
-        // replacing the correct child reference to point to the newly created node.
-        for (n, child_index) in nodes_to_clone
-            .iter()
-            .rev()
-            .zip(self.current_cursor_path.iter().rev())
-        {
-            let mut cloned_node = (*n).clone();
-            cloned_node.children_mut()[*child_index] = node;
-            node = self.arena.alloc(cloned_node);
+        self.last_coalesced_edit = Some(time);
+    }
+
+    fn replace_parent(&mut self, new_parent: Node, cursor_index: usize) -> Option<Message> {
+        debug_assert!(cursor_index < new_parent.children().len());
+        self.last_coalesced_edit = None;
+        if self.current_cursor_path.pop().is_none() {
+            return Some(Message::RootHasNoParent);
         }
-        // At this point, `node` contains a reference to the root of the new tree, so we just add
-        // this to the history, along with the cursor path.
-        self.root_history
-            .push((node, self.current_cursor_path.clone()));
-        // Move the history index on by one so that we are pointing at the latest change
-        self.history_index = self.root_history.len() - 1;
+        let new_root = self.root_with_cursor_replaced(new_parent);
+        self.current_cursor_path.push(cursor_index);
+        self.push_root(new_root);
+        None
     }
 
-    fn insert_child(&mut self, _new_node: Node) {
-        unimplemented!();
+    fn insert_child(&mut self, new_node: Node) -> Option<Message> {
+        self.last_coalesced_edit = None;
+        let cursor = self.cursor();
+        let mut children: Vec<&Node> = vec![self.arena.alloc(new_node)];
+        children.extend_from_slice(cursor.children());
+        let new_cursor =
+            match Node::from_parts(cursor.kind_name(), cursor.value().as_deref(), children) {
+                Some(new_cursor) => new_cursor,
+                None => return Some(Message::InvalidStructure),
+            };
+        let new_root = self.root_with_cursor_replaced(new_cursor);
+        self.current_cursor_path.push(0);
+        self.push_root(new_root);
+        None
     }
 
     fn write_text(&self, string: &mut String, format: &Node::FormatStyle) {
         self.root().write_text(string, format);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DAG;
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::editable_tree::{Direction, EditableTree, COALESCE_PAUSE};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn coalesced_edits() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        tree.move_cursor(Direction::Down);
+        let str_node = |s: &str| JSON::Str(s.to_string());
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        // Undo moves the cursor to where it was in the older tree, so look at the first child
+        // of the root rather than the cursor
+        fn first_child<'a>(tree: &DAG<'a, JSON<'a>>) -> JSON<'a> {
+            tree.root().children()[0].clone()
+        }
+
+        // Typing "abc" quickly makes one history entry
+        tree.replace_cursor_coalesced(str_node("a"), ms(0));
+        tree.replace_cursor_coalesced(str_node("ab"), ms(100));
+        tree.replace_cursor_coalesced(str_node("abc"), ms(200));
+        assert_eq!(tree.cursor(), &str_node("abc"));
+        assert!(tree.undo());
+        assert_eq!(first_child(&tree), JSON::True);
+        assert!(!tree.undo());
+        assert!(tree.redo());
+        assert_eq!(first_child(&tree), str_node("abc"));
+
+        // A pause starts a new entry
+        tree.replace_cursor_coalesced(str_node("x"), ms(300));
+        tree.replace_cursor_coalesced(str_node("xy"), ms(400) + COALESCE_PAUSE);
+        assert!(tree.undo());
+        assert_eq!(first_child(&tree), str_node("x"));
+
+        // So does moving the cursor, even if it comes straight back
+        tree.replace_cursor_coalesced(str_node("1"), ms(500));
+        tree.move_cursor(Direction::Next);
+        tree.move_cursor(Direction::Prev);
+        tree.replace_cursor_coalesced(str_node("12"), ms(600));
+        assert!(tree.undo());
+        assert_eq!(first_child(&tree), str_node("1"));
+
+        // And so does any uncoalesced edit
+        tree.replace_cursor(JSON::False);
+        tree.replace_cursor_coalesced(str_node("z"), ms(700));
+        assert!(tree.undo());
+        assert_eq!(first_child(&tree), JSON::False);
+    }
+
+    #[test]
+    fn saving_breaks_coalescing() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        tree.move_cursor(Direction::Down);
+        let str_node = |s: &str| JSON::Str(s.to_string());
+        let start = Instant::now();
+        tree.replace_cursor_coalesced(str_node("a"), start);
+        tree.mark_saved();
+        tree.replace_cursor_coalesced(str_node("ab"), start + Duration::from_millis(100));
+        // The saved state is still in the history, with the text it was saved with
+        assert!(tree.has_unsaved_changes());
+        assert!(tree.undo());
+        assert_eq!(tree.saved_index(), Some(tree.history_index()));
+        assert_eq!(tree.root().children()[0], &str_node("a"));
+        assert!(!tree.has_unsaved_changes());
+    }
+
+    #[test]
+    fn replace_parent() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        let swapped = JSON::Array(vec![root.children()[1], root.children()[0]]);
+        // The root has no parent
+        assert!(tree.replace_parent(swapped.clone(), 1).is_some());
+        tree.move_cursor(Direction::Down);
+        assert!(tree.replace_parent(swapped.clone(), 1).is_none());
+        assert_eq!(tree.root(), &swapped);
+        assert_eq!(
+            tree.cursor_path().iter().copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(tree.cursor(), &JSON::True);
+        assert!(tree.undo());
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn insert_child() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Array(vec![TestJSON::Array(vec![TestJSON::False])]),
+        ])
+        .add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        fn path<'a>(tree: &DAG<'a, JSON<'a>>) -> Vec<usize> {
+            tree.cursor_path().iter().copied().collect()
+        }
+        // Insert at the root
+        assert!(tree.insert_child(JSON::False).is_none());
+        assert_eq!(path(&tree), vec![0]);
+        assert_eq!(tree.cursor(), &JSON::False);
+        assert_eq!(tree.root().children().len(), 3);
+        // Insert deep in the tree, leaving the rest of the tree unchanged
+        tree.jump_to(CursorPath::from_vec(vec![2, 0]));
+        assert!(tree.insert_child(JSON::True).is_none());
+        assert_eq!(path(&tree), vec![2, 0, 0]);
+        let expected = TestJSON::Array(vec![
+            TestJSON::False,
+            TestJSON::True,
+            TestJSON::Array(vec![TestJSON::Array(vec![TestJSON::True, TestJSON::False])]),
+        ])
+        .add_to_arena(&arena);
+        assert_eq!(tree.root(), expected);
+        assert!(std::ptr::eq(tree.root().children()[1], root.children()[0]));
+        // Leaves can't have children
+        assert!(tree.insert_child(JSON::True).is_some());
+        assert_eq!(tree.root(), expected);
+        // Each insertion is its own history entry
+        assert!(tree.undo());
+        assert!(tree.undo());
+        assert_eq!(tree.root(), root);
+        assert!(!tree.undo());
+    }
+
+    #[test]
+    fn redo_depth() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        tree.move_cursor(Direction::Down);
+        tree.replace_cursor(JSON::False);
+        let latest = tree.root();
+        assert_eq!(tree.redo_depth(), 0);
+        assert!(tree.undo());
+        assert_eq!(tree.redo_depth(), 1);
+        assert_eq!(tree.latest_root(), latest);
+        // Editing after undoing throws away the change that could have been redone
+        tree.replace_cursor(JSON::Str(String::new()));
+        assert_eq!(tree.redo_depth(), 0);
+        assert_ne!(tree.latest_root(), latest);
+    }
+
+    #[test]
+    fn checkout_and_saved() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        assert_eq!(tree.saved_index(), Some(0));
+        tree.move_cursor(Direction::Down);
+        tree.replace_cursor(JSON::False);
+        tree.replace_cursor(JSON::Array(vec![]));
+        tree.mark_saved();
+        tree.replace_cursor(JSON::Object(vec![]));
+        assert_eq!(tree.history_index(), 3);
+        // Checking out an entry restores its cursor, like undo does
+        assert!(tree.checkout(0));
+        assert!(tree.cursor_path().is_root());
+        assert_eq!(tree.root(), root);
+        assert!(!tree.checkout(4));
+        assert!(tree.checkout(3));
+        assert_eq!(tree.cursor(), &JSON::Object(vec![]));
+        // Throwing away the saved entry forgets it
+        assert!(tree.checkout(1));
+        assert_eq!(tree.saved_index(), Some(2));
+        tree.replace_cursor(JSON::True);
+        assert_eq!(tree.saved_index(), None);
+    }
+
+    #[test]
+    fn unsaved_changes() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        assert!(!tree.has_unsaved_changes());
+        tree.move_cursor(Direction::Down);
+        tree.replace_cursor(JSON::False);
+        assert!(tree.has_unsaved_changes());
+        // Putting back what was there is the same as never having changed it
+        tree.replace_cursor(JSON::True);
+        assert!(!tree.has_unsaved_changes());
+        tree.replace_cursor(JSON::False);
+        tree.mark_saved();
+        assert!(!tree.has_unsaved_changes());
+        assert!(tree.undo());
+        assert!(tree.has_unsaved_changes());
+        // The saved tree is still known after its history entry has been thrown away
+        tree.replace_cursor(JSON::False);
+        assert_eq!(tree.saved_index(), None);
+        assert!(!tree.has_unsaved_changes());
+    }
+
+    #[test]
+    fn summaries() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let mut tree = DAG::new(&arena, root);
+        tree.move_cursor(Direction::Down);
+        tree.replace_cursor(JSON::False);
+        tree.set_summary("replace".to_string());
+        tree.replace_cursor(JSON::True);
+        assert_eq!(tree.history()[1].summary.as_deref(), Some("replace"));
+        assert_eq!(tree.history()[2].summary, None);
+        // Undoing doesn't touch the summaries of either entry
+        assert!(tree.undo());
+        assert_eq!(tree.history()[2].summary, None);
+        assert_eq!(tree.history()[0].summary, None);
+    }
+}
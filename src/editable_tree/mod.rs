@@ -1,10 +1,18 @@
 //! Specification of an editable, undoable buffer of trees and some implementations thereof.
 
+pub mod conformance;
 pub mod cursor_path;
 pub mod dag;
 
 use crate::arena::Arena;
 use crate::ast::Ast;
+use crate::messages::Message;
+use cursor_path::CursorPath;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The longest pause between two coalesced edits (see
+/// [`EditableTree::replace_cursor_coalesced`]) for them to be merged into one history entry
+pub const COALESCE_PAUSE: Duration = Duration::from_secs(1);
 
 /// The possible ways you can move the cursor
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -15,6 +23,20 @@ pub enum Direction {
     Next,
 }
 
+/// A single state of the tree in the undo history
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HistoryEntry<'arena, Node> {
+    /// The root of the tree in this state
+    pub root: &'arena Node,
+    /// Where the cursor was when this state was made
+    pub cursor_path: CursorPath,
+    /// When this state was made
+    pub time: SystemTime,
+    /// A human-readable description of the change which made this state (e.g. `replace node at
+    /// 0.2 with string`), if one was recorded
+    pub summary: Option<String>,
+}
+
 /// A trait specifying an editable, undoable buffer of trees
 pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
     /* CONSTRUCTOR METHODS */
@@ -22,6 +44,9 @@ pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
     /// Build a new `EditableTree`, given a tree
     fn new(arena: &'arena Arena<Node>, root: &'arena Node) -> Self;
 
+    /// Returns the [`Arena`] in which this tree's nodes are stored
+    fn arena(&self) -> &'arena Arena<Node>;
+
     /* HISTORY METHODS */
 
     /// Move one step back in the tree history, returning `false` if there are no more changes
@@ -31,6 +56,51 @@ pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
     /// redone
     fn redo(&mut self) -> bool;
 
+    /// Returns the number of changes which can currently be redone.  Making any edit throws these
+    /// changes away.
+    fn redo_depth(&self) -> usize;
+
+    /// Returns the root of the tree as it would be after redoing every change which can be redone
+    fn latest_root(&self) -> &'arena Node;
+
+    /// Returns every entry in the history, oldest first, including the entries which can be
+    /// redone
+    fn history(&self) -> &[HistoryEntry<'arena, Node>];
+
+    /// Returns the index of the current entry in the history, where the original tree is entry
+    /// `0` and every change adds one entry
+    fn history_index(&self) -> usize;
+
+    /// Returns the current entry in the history, which holds the current root and the path of
+    /// the cursor when that root was made
+    fn current_entry(&self) -> &HistoryEntry<'arena, Node> {
+        &self.history()[self.history_index()]
+    }
+
+    /// Move straight to the entry at `index` in the history, restoring the cursor from that
+    /// entry as [`undo`](EditableTree::undo) does.  Returns `false` if there's no such entry.
+    fn checkout(&mut self, index: usize) -> bool;
+
+    /// Record a human-readable description of the change which made the current entry in the
+    /// history, replacing any description it already has
+    fn set_summary(&mut self, summary: String);
+
+    /// Record that the current entry in the history is the one which was last saved
+    fn mark_saved(&mut self);
+
+    /// Returns the index of the entry in the history which was last saved, or [`None`] if that
+    /// entry has been thrown away.  The original tree counts as saved until anything else is.
+    fn saved_index(&self) -> Option<usize>;
+
+    /// Returns the root of the tree which was last saved, which is kept even if its entry in the
+    /// history has been thrown away
+    fn saved_root(&self) -> &'arena Node;
+
+    /// Returns `true` if the current tree differs from the one which was last saved (as found
+    /// by [`is_same_tree`]).  Undoing back to the saved tree, or making an edit which puts back
+    /// what was there, counts as no change.
+    fn has_unsaved_changes(&self) -> bool;
+
     /* NAVIGATION METHODS */
 
     /// Returns a reference to the node that is currently the root of the AST.
@@ -39,9 +109,17 @@ pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
     /// Returns a reference to the node that is currently under the cursor.
     fn cursor(&self) -> &'arena Node;
 
-    /// Move the cursor in a given direction across the tree.  Returns [`Some`] error string if an
-    /// error is found, or [`None`] if the movement was possible.
-    fn move_cursor(&mut self, direction: Direction) -> Option<String>;
+    /// Returns the path from the root to the node under the cursor.
+    fn cursor_path(&self) -> &CursorPath;
+
+    /// Move the cursor in a given direction across the tree.  Returns [`Some`] error [`Message`] if
+    /// an error is found, or [`None`] if the movement was possible.
+    fn move_cursor(&mut self, direction: Direction) -> Option<Message>;
+
+    /// Move the cursor directly to the node at the end of a given [`CursorPath`].  Returns [`Some`]
+    /// error [`Message`] if the path doesn't point to a node in the current tree (in which case the
+    /// cursor doesn't move), or [`None`] if the jump was possible.
+    fn jump_to(&mut self, path: CursorPath) -> Option<Message>;
 
     /* EDIT METHODS */
 
@@ -49,9 +127,23 @@ pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
     /// `cursor`.
     fn replace_cursor(&mut self, new_node: Node);
 
+    /// Like [`replace_cursor`](EditableTree::replace_cursor), but merges the change into the
+    /// latest history entry if that entry was also made by `replace_cursor_coalesced` less than
+    /// [`COALESCE_PAUSE`] before `time`.  Any other change to the tree or the cursor (including
+    /// moving the cursor, undoing or redoing) stops later edits from being merged.  This is
+    /// intended for text edits, so that typing a string doesn't take one undo per character.
+    fn replace_cursor_coalesced(&mut self, new_node: Node, time: Instant);
+
+    /// Replaces the parent of the cursor with `new_parent`, and moves the cursor to the child of
+    /// `new_parent` at `cursor_index`.  Returns [`Some`] error [`Message`] if the cursor is at the
+    /// root (and so has no parent), in which case nothing is changed.
+    fn replace_parent(&mut self, new_parent: Node, cursor_index: usize) -> Option<Message>;
+
     /// Updates the internal state so that the tree now contains `new_node` inserted as the first
     /// child of the selected node.  Also moves the cursor so that the new node is selected.
-    fn insert_child(&mut self, new_node: Node);
+    /// Returns [`Some`] error [`Message`] if the selected node can't have `new_node` as a child,
+    /// in which case nothing is changed.
+    fn insert_child(&mut self, new_node: Node) -> Option<Message>;
 
     /* DISPLAY METHODS */
 
@@ -65,3 +157,45 @@ pub trait EditableTree<'arena, Node: Ast<'arena>>: Sized {
         s
     }
 }
+
+/// Returns `true` if the trees under `a` and `b` are the same.  Every edit shares the nodes it
+/// didn't touch with the tree it was made from, so subtrees which are the same node are skipped
+/// without being compared, and comparing two versions of a tree only visits the paths which were
+/// edited.
+pub fn is_same_tree<'arena, Node: Ast<'arena>>(a: &'arena Node, b: &'arena Node) -> bool {
+    if std::ptr::eq(a, b) {
+        return true;
+    }
+    a.kind_name() == b.kind_name()
+        && a.value() == b.value()
+        && a.children().len() == b.children().len()
+        && a.children()
+            .iter()
+            .zip(b.children())
+            .all(|(x, y)| is_same_tree(*x, *y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_same_tree;
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+
+    #[test]
+    fn same_trees() {
+        let arena = Arena::new();
+        let tree = |json: TestJSON| json.add_to_arena(&arena);
+        let a = tree(TestJSON::Array(vec![TestJSON::True, TestJSON::False]));
+        assert!(is_same_tree(a, a));
+        // Separately built trees are compared node by node
+        let b = tree(TestJSON::Array(vec![TestJSON::True, TestJSON::False]));
+        assert!(!std::ptr::eq(a, b));
+        assert!(is_same_tree(a, b));
+        let c = tree(TestJSON::Array(vec![TestJSON::True, TestJSON::True]));
+        assert!(!is_same_tree(a, c));
+        let d = tree(TestJSON::Array(vec![TestJSON::True]));
+        assert!(!is_same_tree(a, d));
+        let e = tree(TestJSON::Object(vec![]));
+        assert!(!is_same_tree(tree(TestJSON::Array(vec![])), e));
+    }
+}
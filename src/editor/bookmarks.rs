@@ -0,0 +1,298 @@
+//! Bookmarks, which give names to nodes so that the cursor can jump straight back to them (like
+//! marks in Vim).  The bookmarks of each file are saved in Sapling's data directory, so they are
+//! restored when the file is opened again.
+//!
+//! Bookmarks find their nodes by path.  Each bookmark also keeps a fingerprint of its node's
+//! contents, so that a bookmark whose node has changed since it was set (e.g. because the file
+//! was edited by another program) is reported as stale instead of silently leading to the wrong
+//! node.  Whilst the file is open, the editor also keeps track of which node each bookmark is of,
+//! so that the bookmarks follow their nodes when edits move them.
+
+use super::save;
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A single bookmark
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bookmark {
+    /// The path to the bookmarked node
+    pub path: CursorPath,
+    /// The [`fingerprint`] of the node at `path` when the bookmark was set or last saved
+    pub fingerprint: u64,
+}
+
+/// The bookmarks of one file, keyed by name
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Bookmarks {
+    bookmarks: BTreeMap<char, Bookmark>,
+}
+
+impl Bookmarks {
+    /// Creates an empty set of bookmarks
+    pub fn new() -> Bookmarks {
+        Bookmarks::default()
+    }
+
+    /// Returns `true` if `name` can be the name of a bookmark
+    pub fn is_name(name: char) -> bool {
+        name.is_ascii_alphanumeric()
+    }
+
+    /// Bookmarks `node`, which is at `path`, replacing any bookmark with the same name
+    pub fn set<'arena, Node: Ast<'arena>>(
+        &mut self,
+        name: char,
+        path: CursorPath,
+        node: &'arena Node,
+    ) {
+        let fingerprint = fingerprint(node);
+        self.bookmarks.insert(name, Bookmark { path, fingerprint });
+    }
+
+    /// Returns the bookmark with a given name, if there is one
+    pub fn get(&self, name: char) -> Option<&Bookmark> {
+        self.bookmarks.get(&name)
+    }
+
+    /// Moves the bookmark with a given name to `path`, because an edit has moved its node there
+    pub fn move_to(&mut self, name: char, path: CursorPath) {
+        if let Some(bookmark) = self.bookmarks.get_mut(&name) {
+            bookmark.path = path;
+        }
+    }
+
+    /// Returns `true` if there are no bookmarks
+    pub fn is_empty(&self) -> bool {
+        self.bookmarks.is_empty()
+    }
+
+    /// Returns the bookmarks in order of their names
+    pub fn iter(&self) -> impl Iterator<Item = (char, &Bookmark)> {
+        self.bookmarks
+            .iter()
+            .map(|(name, bookmark)| (*name, bookmark))
+    }
+
+    /// Returns the names of the bookmarks whose nodes have changed (or no longer exist) in the
+    /// tree under `root`
+    pub fn stale<'arena, Node: Ast<'arena>>(&self, root: &'arena Node) -> Vec<char> {
+        self.iter()
+            .filter(|(_, bookmark)| bookmark.is_stale(root))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Updates the fingerprints of the bookmarks to match the tree under `root`, which has just
+    /// been saved.  Only bookmarks for which `is_bookmarked_node` says that the node at their path
+    /// is still the node they were set on are updated.  The others are left as they are, and the
+    /// names of the ones which are stale are returned so that the user can be told.
+    pub fn refresh<'arena, Node: Ast<'arena>>(
+        &mut self,
+        root: &'arena Node,
+        mut is_bookmarked_node: impl FnMut(char, &'arena Node) -> bool,
+    ) -> Vec<char> {
+        let mut stale = Vec::new();
+        for (name, bookmark) in &mut self.bookmarks {
+            if bookmark.path.is_valid(root) {
+                let node = bookmark.path.cursor(root);
+                if is_bookmarked_node(*name, node) {
+                    bookmark.fingerprint = fingerprint(node);
+                    continue;
+                }
+            }
+            if bookmark.is_stale(root) {
+                stale.push(*name);
+            }
+        }
+        stale
+    }
+
+    /// Loads the bookmarks stored at `path`.  If there is no file at `path` then there are no
+    /// bookmarks.
+    pub fn load(path: &Path) -> Result<Bookmarks, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Bookmarks::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Bookmarks::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Saves the bookmarks to `path`, creating its directory if needed.  If there are no
+    /// bookmarks, the file is removed instead.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if self.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        save::write_atomically(path, self.to_text().as_bytes())
+    }
+
+    /// Returns the text of the file the bookmarks are saved in, which has one line for each
+    /// bookmark giving its name, path and fingerprint (e.g. `a 0.2 9c1f…`)
+    fn to_text(&self) -> String {
+        self.iter()
+            .map(|(name, bookmark)| {
+                format!("{} {} {:016x}\n", name, bookmark.path, bookmark.fingerprint)
+            })
+            .collect()
+    }
+
+    /// Parses the text written by [`Bookmarks::to_text`]
+    fn parse(text: &str) -> Result<Bookmarks, String> {
+        let mut bookmarks = Bookmarks::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = || format!("line {} isn't a bookmark", i + 1);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (name, path, fingerprint) = match words.as_slice() {
+                [] => continue,
+                [name, path, fingerprint] => (*name, *path, *fingerprint),
+                _ => return Err(error()),
+            };
+            let mut name_chars = name.chars();
+            let name = match (name_chars.next(), name_chars.next()) {
+                (Some(name), None) if Bookmarks::is_name(name) => name,
+                _ => return Err(error()),
+            };
+            let path = parse_path(path).ok_or_else(error)?;
+            let fingerprint = u64::from_str_radix(fingerprint, 16).map_err(|_| error())?;
+            bookmarks
+                .bookmarks
+                .insert(name, Bookmark { path, fingerprint });
+        }
+        Ok(bookmarks)
+    }
+}
+
+impl Bookmark {
+    /// Returns `true` if the bookmarked node has changed (or no longer exists) in the tree under
+    /// `root`
+    pub fn is_stale<'arena, Node: Ast<'arena>>(&self, root: &'arena Node) -> bool {
+        !self.path.is_valid(root) || fingerprint(self.path.cursor(root)) != self.fingerprint
+    }
+}
+
+/// Parses a [`CursorPath`] in the form it's displayed (e.g. `0.2`, or `root`)
+fn parse_path(text: &str) -> Option<CursorPath> {
+    if text == "root" {
+        return Some(CursorPath::root());
+    }
+    text.split('.')
+        .map(|index| index.parse().ok())
+        .collect::<Option<Vec<usize>>>()
+        .map(CursorPath::from_vec)
+}
+
+/// Returns a fingerprint of the contents of `node`, which changes if the node or any of its
+/// descendants is changed.  Only the tree is fingerprinted (not its text), so reformatting a file
+/// doesn't make its bookmarks stale.  This is an FNV-1a hash, which (unlike
+/// [`std::collections::hash_map::DefaultHasher`]) is the same in every version of Sapling.
+pub fn fingerprint<'arena, Node: Ast<'arena>>(node: &'arena Node) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    add_to_fingerprint(node, &mut hash);
+    hash
+}
+
+/// Adds the contents of `node` to a [`fingerprint`] which is being computed
+fn add_to_fingerprint<'arena, Node: Ast<'arena>>(node: &'arena Node, hash: &mut u64) {
+    let value = node.value().unwrap_or_default();
+    let header = format!("{}:{}:{}(", node.kind_name(), value.len(), value);
+    for byte in header.bytes() {
+        *hash = (*hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    for child in node.children() {
+        add_to_fingerprint(*child, hash);
+    }
+    *hash = (*hash ^ u64::from(b')')).wrapping_mul(0x0100_0000_01b3);
+}
+
+/// Returns the path of the file where the bookmarks of the file at `file` are saved, which is in
+/// the `bookmarks` directory of Sapling's [data directory](super::config::data_dir).  The file's
+/// absolute path is encoded into the name, so every file has its own bookmarks.
+pub fn bookmarks_path(file: &Path) -> Option<PathBuf> {
+    let file = std::fs::canonicalize(file).ok()?;
+    let name: String = file
+        .to_string_lossy()
+        .chars()
+        .map(|c| match c {
+            '%' => "%25".to_string(),
+            '/' => "%2F".to_string(),
+            c => c.to_string(),
+        })
+        .collect();
+    Some(super::config::data_dir()?.join("bookmarks").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint, Bookmarks};
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn bookmarks() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::Array(vec![TestJSON::False])])
+            .add_to_arena(&arena);
+        let path = |p: Vec<usize>| CursorPath::from_vec(p);
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.set('a', path(vec![1, 0]), path(vec![1, 0]).cursor(root));
+        bookmarks.set('b', CursorPath::root(), root);
+        assert_eq!(bookmarks.stale(root), Vec::<char>::new());
+
+        // Bookmarks survive being saved and loaded
+        let text = bookmarks.to_text();
+        assert!(text.starts_with("a 1.0 "));
+        assert_eq!(Bookmarks::parse(&text), Ok(bookmarks.clone()));
+        assert_eq!(
+            Bookmarks::parse("a 1.x 00"),
+            Err("line 1 isn't a bookmark".to_string())
+        );
+
+        // Changing a node makes the bookmarks of it and its ancestors stale
+        let changed = TestJSON::Array(vec![TestJSON::True, TestJSON::Array(vec![TestJSON::True])])
+            .add_to_arena(&arena);
+        assert_ne!(fingerprint(root), fingerprint(changed));
+        assert_eq!(bookmarks.stale(changed), vec!['a', 'b']);
+        let shorter = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        assert_eq!(bookmarks.stale(shorter), vec!['a', 'b']);
+        // Refreshing the bookmarks accepts the changes to the nodes which are still bookmarked
+        assert_eq!(bookmarks.refresh(changed, |name, _| name == 'a'), vec!['b']);
+        assert_eq!(bookmarks.stale(changed), vec!['b']);
+        assert_eq!(bookmarks.refresh(changed, |_, _| true), Vec::<char>::new());
+        assert!(bookmarks.stale(changed).is_empty());
+        // Bookmarks of missing nodes are kept, but reported as stale
+        assert_eq!(bookmarks.refresh(shorter, |_, _| true), vec!['a']);
+        assert_eq!(bookmarks.iter().count(), 2);
+        // Moving a bookmark changes its path but not its fingerprint
+        let fingerprint = bookmarks.get('a').unwrap().fingerprint;
+        bookmarks.move_to('a', path(vec![0]));
+        assert_eq!(bookmarks.get('a').unwrap().path, path(vec![0]));
+        assert_eq!(bookmarks.get('a').unwrap().fingerprint, fingerprint);
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("sapling-bookmarks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("bookmarks").join("file");
+        assert_eq!(Bookmarks::load(&path), Ok(Bookmarks::new()));
+        let arena = Arena::new();
+        let root = TestJSON::True.add_to_arena(&arena);
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.set('z', CursorPath::root(), root);
+        bookmarks.save(&path).unwrap();
+        assert_eq!(Bookmarks::load(&path), Ok(bookmarks));
+        // Saving no bookmarks removes the file
+        Bookmarks::new().save(&path).unwrap();
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+//! A clipboard for copying subtrees, which can be shared between multiple running instances of
+//! Sapling.  Subtrees are copied both as a [`StructuredNode`] (so that Sapling can paste them
+//! exactly, without re-parsing any text) and as plain text (for other applications).
+//!
+//! The language of the copied subtree is recorded too, since a subtree copied from a buffer of
+//! one language can't be pasted into a buffer of another as it is.  It is either converted by the
+//! other language's [`convert_from`](Ast::convert_from) hook, or (if the language has no way to
+//! convert it) pasted as text.
+
+use crate::ast::structured::{Fragment, StructuredNode};
+use crate::ast::Ast;
+use crate::messages::Message;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The start of the first line of a clipboard which contains a structured serialization, which
+/// is followed by a space and the name of the subtree's language.  Any clipboard text which
+/// doesn't start with this is treated as plain text from another application.
+pub const STRUCTURED_HEADER: &str = "sapling-structured-v1";
+
+/// The contents of the clipboard
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClipboardContents {
+    /// The structure of the copied subtree, or [`None`] if the contents were copied by an
+    /// application other than Sapling
+    pub structure: Option<StructuredNode>,
+    /// The [`language_name`](Ast::language_name) of the buffer that the subtree was copied from,
+    /// or [`None`] if it isn't known
+    pub language: Option<String>,
+    /// The text of the copied subtree
+    pub text: String,
+}
+
+impl ClipboardContents {
+    /// Creates the `ClipboardContents` for copying the subtree rooted at `node`
+    pub fn copy<'arena, Node: Ast<'arena>>(
+        node: &'arena Node,
+        format_style: &Node::FormatStyle,
+    ) -> ClipboardContents {
+        ClipboardContents {
+            structure: Some(StructuredNode::from_node(node)),
+            language: Some(Node::language_name().to_string()),
+            text: node.to_text(format_style),
+        }
+    }
+
+    /// Creates `ClipboardContents` containing only some plain text, as if it were copied from
+    /// another application
+    pub fn from_text(text: String) -> ClipboardContents {
+        ClipboardContents {
+            structure: None,
+            language: None,
+            text,
+        }
+    }
+
+    /// Returns the fragment which pasting these contents into a buffer of `Node`'s language
+    /// would insert, along with a message to tell the user if the contents had to be converted
+    /// from another language.  Subtrees from other languages are converted by
+    /// [`Ast::convert_from`] if possible, and otherwise their text is parsed like plain text.
+    /// Returns the reason if the contents can't be pasted.
+    pub fn fragment<'arena, Node: Ast<'arena>>(
+        &self,
+    ) -> Result<(Fragment, Option<Message>), Message> {
+        let whole = |node: StructuredNode| Fragment {
+            node,
+            holes: Vec::new(),
+            is_truncated: false,
+        };
+        let target = Node::language_name();
+        let structure = match &self.structure {
+            Some(structure) => structure,
+            None => {
+                let fragment = Node::parse_fragment(&self.text).ok_or(Message::CannotPasteText)?;
+                return Ok((fragment, None));
+            }
+        };
+        let source = match self.language.as_deref() {
+            Some(source) if source != target => source,
+            // Contents copied before languages were recorded are assumed to match
+            _ => return Ok((whole(structure.clone()), None)),
+        };
+        let (source, target) = (source.to_string(), target.to_string());
+        if let Some(converted) = Node::convert_from(&source, structure) {
+            return Ok((
+                whole(converted),
+                Some(Message::ConvertedPaste(source, target)),
+            ));
+        }
+        match Node::parse_fragment(&self.text) {
+            Some(fragment) => Ok((fragment, Some(Message::PastedAsText(source, target)))),
+            None => Err(Message::CannotConvertPaste(source, target)),
+        }
+    }
+
+    /// Converts these contents into the text stored in the shared clipboard.  If there is a
+    /// structure, then this is [`STRUCTURED_HEADER`] (followed by the language, if it's known), the
+    /// structure and the plain text on successive lines.  Otherwise, it is just the plain text.
+    pub fn serialize(&self) -> String {
+        let header = match &self.language {
+            Some(language) => format!("{} {}", STRUCTURED_HEADER, language),
+            None => STRUCTURED_HEADER.to_string(),
+        };
+        match &self.structure {
+            Some(structure) => format!("{}\n{}\n{}", header, structure.serialize(), self.text),
+            None => self.text.clone(),
+        }
+    }
+
+    /// Reads the contents of the shared clipboard (see [`ClipboardContents::serialize`]).  If the
+    /// structure can't be read, then the whole text is treated as plain text.
+    pub fn deserialize(text: &str) -> ClipboardContents {
+        let mut lines = text.splitn(3, '\n');
+        let language = match lines
+            .next()
+            .and_then(|line| line.strip_prefix(STRUCTURED_HEADER))
+        {
+            Some("") => Some(None),
+            Some(rest) => rest
+                .strip_prefix(' ')
+                .filter(|language| !language.is_empty())
+                .map(|language| Some(language.to_string())),
+            None => None,
+        };
+        if let Some(language) = language {
+            if let Some(structure) = lines.next().and_then(StructuredNode::parse) {
+                return ClipboardContents {
+                    structure: Some(structure),
+                    language,
+                    text: lines.next().unwrap_or("").to_string(),
+                };
+            }
+        }
+        ClipboardContents::from_text(text.to_string())
+    }
+}
+
+/// Returns the path of the file through which all instances of Sapling share their clipboard,
+/// which is `sapling/clipboard` in `$XDG_RUNTIME_DIR` (a directory private to the user), or in
+/// `$XDG_CACHE_HOME` (or `~/.cache`) if that isn't set.  Returns [`None`] if none of these
+/// directories are known, since a file anywhere else could be read by other users.
+pub fn shared_clipboard_path() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let dir = match var("XDG_RUNTIME_DIR").or_else(|| var("XDG_CACHE_HOME")) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(var("HOME")?).join(".cache"),
+    };
+    Some(dir.join("sapling").join("clipboard"))
+}
+
+/// Replaces the contents of the file at `path` with `text`, creating the file and its directory
+/// if needed.  The file can only be read and written by the user (and any directory created can
+/// only be used by them), since copied subtrees may be private.
+fn write_private(path: &Path, text: &str) -> std::io::Result<()> {
+    let mut dir_builder = DirBuilder::new();
+    dir_builder.recursive(true);
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+        dir_builder.mode(0o700);
+        options.mode(0o600);
+    }
+    if let Some(dir) = path.parent() {
+        dir_builder.create(dir)?;
+    }
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, and an existing file could have been created by hand
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(text.as_bytes())
+}
+
+/// A clipboard which stores its contents in a shared file, so that subtrees copied in one instance
+/// of Sapling can be pasted into another.  If the file can't be used then the contents are still
+/// kept in memory.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    /// The file shared with other instances, or [`None`] if the clipboard isn't shared
+    path: Option<PathBuf>,
+    /// The contents last copied by this instance
+    contents: Option<ClipboardContents>,
+}
+
+impl Clipboard {
+    /// Creates an empty `Clipboard` which isn't shared with any other instances
+    pub fn new() -> Clipboard {
+        Clipboard {
+            path: None,
+            contents: None,
+        }
+    }
+
+    /// Creates a `Clipboard` which shares its contents through the file at `path`
+    pub fn shared(path: PathBuf) -> Clipboard {
+        Clipboard {
+            path: Some(path),
+            contents: None,
+        }
+    }
+
+    /// Replaces the contents of the clipboard.  Returns [`Err`] if the shared file couldn't be
+    /// written, in which case the contents are only available to this instance.
+    pub fn copy(&mut self, contents: ClipboardContents) -> std::io::Result<()> {
+        let result = match &self.path {
+            Some(path) => write_private(path, &contents.serialize()),
+            None => Ok(()),
+        };
+        self.contents = Some(contents);
+        result
+    }
+
+    /// Returns the contents of the clipboard, reading the shared file so that anything copied by
+    /// other instances is included.  Returns [`None`] if nothing has been copied.
+    pub fn paste(&self) -> Option<ClipboardContents> {
+        self.path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|text| ClipboardContents::deserialize(&text))
+            .or_else(|| self.contents.clone())
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clipboard, ClipboardContents};
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::structured::StructuredNode;
+    use crate::ast::test_json::TestJSON;
+    use crate::messages::Message;
+
+    #[test]
+    fn serialize() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let contents = ClipboardContents::copy(root, &JSONFormat::Pretty);
+        assert_eq!(
+            contents.serialize(),
+            "sapling-structured-v1 json\n(array (true) (false))\n[\n    true,\n    false\n]"
+        );
+        assert_eq!(
+            ClipboardContents::deserialize(&contents.serialize()),
+            contents
+        );
+        // Clipboards written before the language was recorded can still be read
+        let old = ClipboardContents::deserialize("sapling-structured-v1\n(true)\ntrue");
+        assert_eq!((old.structure.is_some(), old.language), (true, None));
+        // Text from other applications (or a corrupted structure) is treated as plain text
+        for text in &[
+            "[true]",
+            "sapling-structured-v1\n(array\n[true]",
+            "sapling-structured-v1x\n(true)\ntrue",
+        ] {
+            assert_eq!(
+                ClipboardContents::deserialize(text),
+                ClipboardContents::from_text(text.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn other_languages() {
+        let yaml = |structure: &str, text: &str| ClipboardContents {
+            structure: StructuredNode::parse(structure),
+            language: Some("yaml".to_string()),
+            text: text.to_string(),
+        };
+        // JSON can't convert YAML subtrees, so their text is pasted instead
+        let (fragment, message) = yaml("(sequence (boolean \"true\"))", "[true]")
+            .fragment::<JSON>()
+            .unwrap();
+        assert_eq!(Some(fragment.node), StructuredNode::parse("(array (true))"));
+        assert_eq!(
+            message,
+            Some(Message::PastedAsText(
+                "yaml".to_string(),
+                "json".to_string()
+            ))
+        );
+        assert_eq!(
+            yaml("(sequence)", "- a").fragment::<JSON>(),
+            Err(Message::CannotConvertPaste(
+                "yaml".to_string(),
+                "json".to_string()
+            ))
+        );
+        // Subtrees of the same language are pasted as they are
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        let (fragment, message) = ClipboardContents::copy(root, &JSONFormat::Compact)
+            .fragment::<JSON>()
+            .unwrap();
+        assert_eq!(fragment.node, StructuredNode::from_node(root));
+        assert_eq!(message, None);
+        assert_eq!(
+            ClipboardContents::from_text("nope".to_string()).fragment::<JSON>(),
+            Err(Message::CannotPasteText)
+        );
+    }
+
+    #[test]
+    fn shared() {
+        let dir =
+            std::env::temp_dir().join(format!("sapling-clipboard-test-{}", std::process::id()));
+        let path = dir.join("clipboard");
+        let mut first = Clipboard::shared(path.clone());
+        let second = Clipboard::shared(path.clone());
+        let unshared = Clipboard::new();
+        first
+            .copy(ClipboardContents::from_text("true".to_string()))
+            .unwrap();
+        assert_eq!(
+            second.paste(),
+            Some(ClipboardContents::from_text("true".to_string()))
+        );
+        assert_eq!(unshared.paste(), None);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
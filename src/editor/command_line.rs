@@ -0,0 +1,190 @@
+//! Editing of line commands (like `:w` or a search) whilst they're typed in
+//! [`Mode::Command`](super::Mode::Command): the position of the cursor within the line, and the
+//! history of lines which have been run.  The history lasts for the whole session, and like in
+//! Vim, browsing it only visits the lines which start with whatever had been typed before browsing
+//! began (so typing `:s` and pressing up goes back through the `:set` commands).
+
+use super::keymap::{KeyInput, KeySequence};
+
+/// The state of the command line, which lasts between line commands
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct CommandLine {
+    /// The index of the key in the line which the cursor is before
+    cursor: usize,
+    /// The lines which have been run, oldest first
+    history: Vec<KeySequence>,
+    /// Whilst browsing the history, the index of the line being shown along with the line which
+    /// was being typed before browsing began
+    browsing: Option<(usize, KeySequence)>,
+}
+
+impl CommandLine {
+    /// Creates a command line with no history
+    pub fn new() -> CommandLine {
+        CommandLine::default()
+    }
+
+    /// Returns the index of the key in the line which the cursor is before
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Starts editing `line`, with the cursor at its end
+    pub fn start(&mut self, line: &[KeyInput]) {
+        self.cursor = line.len();
+        self.browsing = None;
+    }
+
+    /// Types `key` into `line` at the cursor
+    pub fn insert(&mut self, line: &mut Vec<KeyInput>, key: KeyInput) {
+        let cursor = self.cursor.min(line.len());
+        line.insert(cursor, key);
+        self.cursor = cursor + 1;
+    }
+
+    /// Deletes the key before the cursor from `line`, returning `false` if the cursor is at the
+    /// start of the line
+    pub fn delete_back(&mut self, line: &mut Vec<KeyInput>) -> bool {
+        let cursor = self.cursor.min(line.len());
+        if cursor == 0 {
+            return false;
+        }
+        line.remove(cursor - 1);
+        self.cursor = cursor - 1;
+        true
+    }
+
+    /// Moves the cursor one key to the left, but not before the first `start` keys (which start
+    /// the line command, like `:`)
+    pub fn move_left(&mut self, start: usize) {
+        self.cursor = self.cursor.saturating_sub(1).max(start);
+    }
+
+    /// Moves the cursor one key to the right, but not past the end of `line`
+    pub fn move_right(&mut self, line: &[KeyInput]) {
+        self.cursor = (self.cursor + 1).min(line.len());
+    }
+
+    /// Adds a line which has been run to the end of the history, unless it's empty.  Running a
+    /// line again moves it to the end, so each line is only in the history once.
+    pub fn record(&mut self, line: KeySequence) {
+        self.browsing = None;
+        if line.is_empty() {
+            return;
+        }
+        self.history.retain(|old_line| *old_line != line);
+        self.history.push(line);
+    }
+
+    /// Replaces `line` with the previous line in the history which starts with what was typed
+    /// before browsing began.  Returns `false` if there isn't one.
+    pub fn history_prev(&mut self, line: &mut Vec<KeyInput>) -> bool {
+        let (end, typed) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => (self.history.len(), line.clone()),
+        };
+        let found = self.history[..end]
+            .iter()
+            .rposition(|old_line| old_line.starts_with(&typed));
+        match found {
+            Some(index) => {
+                *line = self.history[index].clone();
+                self.cursor = line.len();
+                self.browsing = Some((index, typed));
+                true
+            }
+            None => {
+                // Stay on the oldest match
+                self.browsing = Some((end, typed)).filter(|(end, _)| *end < self.history.len());
+                false
+            }
+        }
+    }
+
+    /// Replaces `line` with the next line in the history which starts with what was typed before
+    /// browsing began, or with what was typed if there are no more.  Returns `false` if the
+    /// history wasn't being browsed.
+    pub fn history_next(&mut self, line: &mut Vec<KeyInput>) -> bool {
+        let (start, typed) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => return false,
+        };
+        let found = self.history[start + 1..]
+            .iter()
+            .position(|old_line| old_line.starts_with(&typed))
+            .map(|offset| start + 1 + offset);
+        match found {
+            Some(index) => {
+                *line = self.history[index].clone();
+                self.browsing = Some((index, typed));
+            }
+            None => *line = typed,
+        }
+        self.cursor = line.len();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandLine;
+    use crate::editor::keymap::{KeyInput, KeySequence};
+
+    fn keys(text: &str) -> KeySequence {
+        text.chars().map(KeyInput::from).collect()
+    }
+
+    #[test]
+    fn editing() {
+        let mut command_line = CommandLine::new();
+        let mut line = keys(":wq");
+        command_line.start(&line);
+        command_line.move_left(1);
+        command_line.move_left(1);
+        command_line.move_left(1);
+        // The cursor can't move into the `:`
+        assert_eq!(command_line.cursor(), 1);
+        command_line.insert(&mut line, KeyInput::from('x'));
+        assert_eq!(line, keys(":xwq"));
+        command_line.move_right(&line);
+        assert!(command_line.delete_back(&mut line));
+        assert_eq!(line, keys(":xq"));
+        command_line.move_right(&line);
+        command_line.move_right(&line);
+        assert_eq!(command_line.cursor(), 3);
+        command_line.start(&line[..0]);
+        assert!(!command_line.delete_back(&mut Vec::new()));
+    }
+
+    #[test]
+    fn history() {
+        let mut command_line = CommandLine::new();
+        for line in &[":set width=80", ":w", ":set maxdepth=3", ":w", ""] {
+            command_line.record(keys(line));
+        }
+        // Browsing back visits every line once, most recent first
+        let mut line = keys(":");
+        command_line.start(&line);
+        assert!(command_line.history_prev(&mut line));
+        assert_eq!(line, keys(":w"));
+        assert!(command_line.history_prev(&mut line));
+        assert_eq!(line, keys(":set maxdepth=3"));
+        assert!(command_line.history_prev(&mut line));
+        assert_eq!(line, keys(":set width=80"));
+        assert!(!command_line.history_prev(&mut line));
+        assert_eq!(line, keys(":set width=80"));
+        assert!(command_line.history_next(&mut line));
+        assert_eq!(line, keys(":set maxdepth=3"));
+        assert_eq!(command_line.cursor(), line.len());
+
+        // Only lines starting with what was typed are visited, and browsing forward past the
+        // newest line returns to what was typed
+        let mut line = keys(":s");
+        command_line.start(&line);
+        assert!(command_line.history_prev(&mut line));
+        assert_eq!(line, keys(":set maxdepth=3"));
+        assert!(command_line.history_next(&mut line));
+        assert_eq!(line, keys(":s"));
+        assert!(!command_line.history_next(&mut line));
+    }
+}
@@ -0,0 +1,133 @@
+//! The completion menu, which lists the keys that a schema allows to be added to the object under
+//! the cursor so that the user can pick one to insert.
+
+use crate::ast::schema::Schema;
+
+/// A key which can be added, along with the schema of its value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Completion {
+    pub key: String,
+    pub schema: Schema,
+}
+
+impl Completion {
+    /// Returns the text shown for this completion in the menu, which is its key followed by the
+    /// type and documentation of its value (if the schema gives them)
+    pub fn label(&self) -> String {
+        let mut label = self.key.clone();
+        if let Some(kind) = &self.schema.kind {
+            label.push_str(": ");
+            label.push_str(kind);
+        }
+        if let Some(description) = &self.schema.description {
+            label.push_str(" - ");
+            label.push_str(description);
+        }
+        label
+    }
+}
+
+/// An open completion menu, one of whose entries is selected
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompletionMenu {
+    completions: Vec<Completion>,
+    selected: usize,
+    /// The key which opened the menu, which still inserts its usual child if the menu is closed
+    /// without choosing a completion
+    insert_char: char,
+    /// Whether typing starts once something has been inserted
+    edit_text: bool,
+}
+
+impl CompletionMenu {
+    /// Opens a menu with the first completion selected, for an insertion made by `insert_char`.
+    /// Returns [`None`] if there are no completions.
+    pub fn new(completions: Vec<Completion>, insert_char: char) -> Option<CompletionMenu> {
+        if completions.is_empty() {
+            return None;
+        }
+        Some(CompletionMenu {
+            completions,
+            selected: 0,
+            insert_char,
+            edit_text: false,
+        })
+    }
+
+    /// Returns the key which opened the menu
+    pub fn insert_char(&self) -> char {
+        self.insert_char
+    }
+
+    /// Returns `true` if typing should start once something has been inserted
+    pub fn edits_text(&self) -> bool {
+        self.edit_text
+    }
+
+    /// Makes typing start once something has been inserted
+    pub fn edit_after_insert(&mut self) {
+        self.edit_text = true;
+    }
+
+    /// Returns every completion in the menu, in order
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    /// Returns the index of the selected completion
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// Returns the selected completion
+    pub fn selected(&self) -> &Completion {
+        &self.completions[self.selected]
+    }
+
+    /// Selects the next completion (or the previous one if `forward` is `false`), wrapping round
+    /// at either end of the menu
+    pub fn select(&mut self, forward: bool) {
+        let len = self.completions.len();
+        self.selected = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Completion, CompletionMenu};
+    use crate::ast::schema::Schema;
+
+    #[test]
+    fn menu() {
+        let completion = |key: &str, kind: Option<&str>, description: Option<&str>| Completion {
+            key: key.to_string(),
+            schema: Schema {
+                kind: kind.map(str::to_string),
+                description: description.map(str::to_string),
+                ..Schema::default()
+            },
+        };
+        assert!(CompletionMenu::new(vec![], 'i').is_none());
+        let mut menu = CompletionMenu::new(
+            vec![
+                completion("name", Some("string"), Some("The user's name")),
+                completion("admin", None, None),
+            ],
+            'i',
+        )
+        .unwrap();
+        assert_eq!(menu.insert_char(), 'i');
+        assert!(!menu.edits_text());
+        menu.edit_after_insert();
+        assert!(menu.edits_text());
+        assert_eq!(menu.selected().label(), "name: string - The user's name");
+        menu.select(false);
+        assert_eq!(menu.selected().label(), "admin");
+        menu.select(true);
+        assert_eq!(menu.selected_index(), 0);
+    }
+}
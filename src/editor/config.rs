@@ -0,0 +1,738 @@
+//! The user-configurable settings of the [`Editor`](super::Editor).
+
+use super::cursor_style::CursorStyle;
+use super::keymap;
+use super::limits::TreeLimits;
+use super::preview::Renderer;
+use super::prompt::ConfirmConfig;
+use super::status_bar::Segment;
+use super::theme::{self, Theme};
+use super::{journal, Command, KeySequence, Landing, LogLevel};
+use crate::ast::display_token::Indentation;
+use crate::messages::Message;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Returns the path of the user's config file, which is `sapling/config.toml` in
+/// `$XDG_CONFIG_HOME` (or in `~/.config` if that isn't set).  Returns [`None`] if neither
+/// directory is known.
+pub fn config_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("sapling").join("config.toml"))
+}
+
+/// Returns the directory where Sapling keeps the data it saves between sessions (like
+/// [bookmarks](super::bookmarks)), which is `sapling` in `$XDG_DATA_HOME` (or in
+/// `~/.local/share` if that isn't set).  Returns [`None`] if neither directory is known.
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+    Some(data_dir.join("sapling"))
+}
+
+/// Reads and parses the user's TOML config file at `path`.  The keymap, theme and settings are
+/// all loaded from the one parsed file, so that they can't come from different versions of it.
+/// Returns [`None`] if the file doesn't exist, or if it can't be read or parsed (in which case a
+/// warning to show the user is returned as well).
+pub fn read_config_file(path: &Path) -> (Option<toml::Value>, Option<Message>) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (None, None),
+        Err(e) => return (None, Some(load_error(path, e.to_string()))),
+    };
+    match text.parse() {
+        Ok(config) => (Some(config), None),
+        Err(e) => (None, Some(load_error(path, e.to_string()))),
+    }
+}
+
+/// Returns the warning shown when the config file at `path` can't be loaded
+fn load_error(path: &Path, reason: String) -> Message {
+    Message::CannotLoadConfig(path.to_string_lossy().into_owned(), reason)
+}
+
+/// The name of the config file which projects can use to share settings between everyone
+/// working on them
+pub const PROJECT_CONFIG_NAME: &str = ".sapling.toml";
+
+/// Returns the path of the project config file which applies to files in the directory `dir`,
+/// which is the [`PROJECT_CONFIG_NAME`] file in `dir` or in the nearest of its ancestors that has
+/// one.  Returns [`None`] if no directory has one.
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The settings in a project config file, which override the user's own settings.  The file
+/// can have a `[keymap]` table (in the same form as the user's config file), a `schema` (whose
+/// path is relative to the directory of the project config), a maximum line `width`, the name of
+/// the `format` style that files are written in and how they `indent` (in the same form as the
+/// user's config file).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProjectConfig {
+    /// Key bindings which replace the user's bindings of the same keys
+    pub bindings: Vec<(KeySequence, Command)>,
+    /// The JSON Schema describing the project's files
+    pub schema_path: Option<PathBuf>,
+    /// The maximum width of the lines that trees are laid out to
+    pub line_width: Option<usize>,
+    /// The name of the format style that trees are written in
+    pub format_name: Option<String>,
+    /// How each level of indentation is written in files
+    pub indentation: Option<Indentation>,
+}
+
+impl ProjectConfig {
+    /// Loads the project config file at `path`, or returns the reason it couldn't be loaded
+    pub fn load(path: &Path) -> Result<ProjectConfig, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        ProjectConfig::parse(&text, dir)
+    }
+
+    /// Parses the text of a project config file in the directory `dir`
+    fn parse(text: &str, dir: &Path) -> Result<ProjectConfig, String> {
+        let table: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let bindings = keymap::parse_config(text)?.unwrap_or_default();
+        let schema_path = match table.get("schema") {
+            Some(toml::Value::String(path)) => Some(dir.join(path)),
+            Some(_) => return Err("'schema' isn't a path".to_string()),
+            None => None,
+        };
+        let line_width = match table.get("width") {
+            Some(toml::Value::Integer(width)) if *width > 0 => Some(*width as usize),
+            Some(_) => return Err("'width' isn't a positive number".to_string()),
+            None => None,
+        };
+        Ok(ProjectConfig {
+            bindings,
+            schema_path,
+            line_width,
+            format_name: get_str(&table, "format")?.map(str::to_string),
+            indentation: get_indentation(&table, "indent")?,
+        })
+    }
+
+    /// Overrides the settings in `config` with the ones given by this project config
+    pub fn apply(self, config: &mut Config) {
+        config.project_bindings = self.bindings;
+        if self.schema_path.is_some() {
+            config.schema_path = self.schema_path;
+        }
+        if self.line_width.is_some() {
+            config.line_width = self.line_width;
+        }
+        if self.format_name.is_some() {
+            config.format_name = self.format_name;
+        }
+        if self.indentation.is_some() {
+            config.indentation = self.indentation;
+        }
+    }
+}
+
+/// Returns the table called `key` in a TOML table, or [`None`] if it doesn't have one
+fn get_table<'t>(table: &'t toml::Value, key: &str) -> Result<Option<&'t toml::Value>, String> {
+    match table.get(key) {
+        Some(value @ toml::Value::Table(_)) => Ok(Some(value)),
+        Some(_) => Err(format!("'{}' isn't a table", key)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the positive number called `key` in a TOML table, or [`None`] if it isn't given
+fn get_positive(table: &toml::Value, key: &str) -> Result<Option<usize>, String> {
+    match table.get(key) {
+        Some(toml::Value::Integer(n)) if *n > 0 => Ok(Some(*n as usize)),
+        Some(_) => Err(format!("'{}' isn't a positive number", key)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the boolean called `key` in a TOML table, or [`None`] if it isn't given
+fn get_bool(table: &toml::Value, key: &str) -> Result<Option<bool>, String> {
+    match table.get(key) {
+        Some(toml::Value::Boolean(b)) => Ok(Some(*b)),
+        Some(_) => Err(format!("'{}' isn't true or false", key)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the string called `key` in a TOML table, or [`None`] if it isn't given
+fn get_str<'t>(table: &'t toml::Value, key: &str) -> Result<Option<&'t str>, String> {
+    match table.get(key) {
+        Some(toml::Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(format!("'{}' isn't a string", key)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the path called `key` in a TOML table, or [`None`] if it isn't given
+fn get_path(table: &toml::Value, key: &str) -> Result<Option<PathBuf>, String> {
+    Ok(get_str(table, key)?.map(PathBuf::from))
+}
+
+/// Returns the number of milliseconds called `key` in a TOML table as a [`Duration`], or
+/// [`None`] if it isn't given
+fn get_millis(table: &toml::Value, key: &str) -> Result<Option<Duration>, String> {
+    Ok(get_positive(table, key)?.map(|ms| Duration::from_millis(ms as u64)))
+}
+
+/// Returns the log level named by the string called `key` in a TOML table, or [`None`] if it
+/// isn't given
+fn get_log_level(table: &toml::Value, key: &str) -> Result<Option<LogLevel>, String> {
+    get_str(table, key)?
+        .map(|name| {
+            LogLevel::from_name(name).ok_or_else(|| format!("'{}' isn't a log level", name))
+        })
+        .transpose()
+}
+
+/// Returns the indentation called `key` in a TOML table, which is either a number of spaces or
+/// `"tab"`, or [`None`] if it isn't given
+fn get_indentation(table: &toml::Value, key: &str) -> Result<Option<Indentation>, String> {
+    match table.get(key) {
+        Some(toml::Value::Integer(n)) if *n > 0 => Ok(Some(Indentation::Spaces(*n as usize))),
+        Some(toml::Value::String(s)) if s == "tab" => Ok(Some(Indentation::Tabs)),
+        Some(_) => Err(format!("'{}' isn't a number of spaces or \"tab\"", key)),
+        None => Ok(None),
+    }
+}
+
+/// Parses the `cursor-style` of a config file, which is `"reverse"`, `"underline"`, a table with
+/// the `background` colour or a table with a pair of `markers`
+fn parse_cursor_style(value: &toml::Value) -> Result<CursorStyle, String> {
+    let error = || "'cursor-style' isn't a cursor style".to_string();
+    match value {
+        toml::Value::String(name) if name == "reverse" => Ok(CursorStyle::Reverse),
+        toml::Value::String(name) if name == "underline" => Ok(CursorStyle::Underline),
+        toml::Value::Table(_) => {
+            if let Some(colour) = get_str(value, "background")? {
+                return Ok(CursorStyle::Background(theme::parse_colour(colour)?));
+            }
+            match value
+                .get("markers")
+                .and_then(toml::Value::as_array)
+                .map(Vec::as_slice)
+            {
+                Some([toml::Value::String(open), toml::Value::String(close)]) => {
+                    Ok(CursorStyle::Markers(open.clone(), close.clone()))
+                }
+                _ => Err(error()),
+            }
+        }
+        _ => Err(error()),
+    }
+}
+
+/// Parses the `preview` of a language in a config file, which is `"text"` or a list of the
+/// program and arguments that render the preview
+fn parse_renderer(value: &toml::Value) -> Result<Renderer, String> {
+    let error = || "'preview' isn't \"text\" or a command".to_string();
+    match value {
+        toml::Value::String(name) if name == "text" => Ok(Renderer::Text),
+        toml::Value::Array(args) if !args.is_empty() => args
+            .iter()
+            .map(|arg| arg.as_str().map(str::to_string).ok_or_else(error))
+            .collect::<Result<_, _>>()
+            .map(Renderer::Command),
+        _ => Err(error()),
+    }
+}
+
+/// All the settings that change how the [`Editor`](super::Editor) behaves
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config {
+    /// Which destructive operations ask for confirmation
+    pub confirm: ConfirmConfig,
+    /// How the node under the cursor is made to stand out
+    pub cursor_style: CursorStyle,
+    /// The colours which text is drawn in, by its syntax category
+    pub theme: Theme,
+    /// Where the cursor lands after a node is inserted or replaced
+    pub landing: Landing,
+    /// How deep and wide a tree can be before the editor warns that it will be slow to draw,
+    /// and offers to fold the parts which are too big
+    pub limits: TreeLimits,
+    /// Replacement templates for individual messages, keyed by
+    /// [`Message::key`](crate::messages::Message::key).  For example, mapping
+    /// `"normal-indicator"` to `"-- NORMAL --"` changes how the status bar shows the mode.  These
+    /// take priority over the templates for the user's locale.
+    pub message_overrides: HashMap<String, String>,
+    /// Extra key bindings for each language, keyed by
+    /// [`Ast::language_name`](crate::ast::Ast::language_name).  When editing a tree of that
+    /// language, these are added to the global keymap, replacing any global bindings of the same
+    /// keys.
+    pub language_bindings: HashMap<String, Vec<(KeySequence, Command)>>,
+    /// A file, named pipe or Unix socket to which an event is written after every edit (see
+    /// [`events`](super::events)), or [`None`] if events shouldn't be written anywhere
+    pub events_path: Option<PathBuf>,
+    /// How to render the live preview pane for each language, keyed by
+    /// [`Ast::language_name`](crate::ast::Ast::language_name).  Languages without a renderer
+    /// don't have a preview pane.
+    pub previewers: HashMap<String, Renderer>,
+    /// A JSON Schema describing the tree, whose keys are offered in a menu when inserting into
+    /// an object, or [`None`] if there is no schema
+    pub schema_path: Option<PathBuf>,
+    /// Extra key bindings from the [project config](ProjectConfig), which are added to the keymap
+    /// after the language's bindings, replacing any bindings of the same keys
+    pub project_bindings: Vec<(KeySequence, Command)>,
+    /// The maximum width of the lines that the tree is laid out to, or [`None`] to use the
+    /// format style that the editor was given
+    pub line_width: Option<usize>,
+    /// The name of the format style that the tree is written in (see
+    /// [`Ast::named_format_style`](crate::ast::Ast::named_format_style)), or [`None`] to use the
+    /// format style that the editor was given
+    pub format_name: Option<String>,
+    /// How each level of indentation is written when the tree is saved, or [`None`] to indent
+    /// files like the tree view does
+    pub indentation: Option<Indentation>,
+    /// The text which new documents start as for each language, keyed by
+    /// [`Ast::language_name`](crate::ast::Ast::language_name).  These replace the templates
+    /// provided by the languages themselves.
+    pub templates: HashMap<String, String>,
+    /// How many levels below the cursor's path are rendered before deeper nodes are replaced by
+    /// placeholders, or [`None`] to render the whole tree.  This keeps rendering fast for
+    /// pathologically deep documents.
+    pub max_render_depth: Option<usize>,
+    /// Which [segments](super::status_bar::Segment) the status bar shows, in order, or [`None`]
+    /// to use [`DEFAULT_SEGMENTS`](super::status_bar::DEFAULT_SEGMENTS)
+    pub status_segments: Option<Vec<Segment>>,
+    /// `true` if the lines of the tree view are numbered in a gutter down its left hand side
+    pub line_numbers: bool,
+    /// `true` if the tree is written to its file whenever the user is idle with unsaved changes,
+    /// as long as every part of the tree is valid
+    pub autosave: bool,
+    /// How long the user has to stop typing for before expensive work (like re-evaluating the
+    /// watched expressions) is done, or [`None`] to use
+    /// [`DEFAULT_IDLE_DELAY`](super::idle::DEFAULT_IDLE_DELAY)
+    pub idle_delay: Option<Duration>,
+    /// How many times per second the screen is redrawn at most, or [`None`] to use
+    /// [`DEFAULT_MAX_FPS`](super::frames::DEFAULT_MAX_FPS)
+    pub max_fps: Option<u32>,
+    /// How long to wait for the next key when the keys typed so far are a command as well as the
+    /// start of a longer binding, before running the shorter command, or [`None`] to use
+    /// [`DEFAULT_COMMAND_TIMEOUT`](super::keymap::DEFAULT_COMMAND_TIMEOUT)
+    pub command_timeout: Option<Duration>,
+    /// How many entries the log keeps before dropping the oldest, or [`None`] to use
+    /// [`DEFAULT_LOG_CAPACITY`](super::log::DEFAULT_LOG_CAPACITY)
+    pub log_capacity: Option<usize>,
+    /// The file which the [journal](super::journal) of every action in the session is written
+    /// to, or [`None`] if no journal should be kept
+    pub journal_path: Option<PathBuf>,
+    /// How many sessions' journals are kept before the oldest are deleted, or [`None`] to use
+    /// [`DEFAULT_KEPT_SESSIONS`](super::journal::DEFAULT_KEPT_SESSIONS)
+    pub journal_kept_sessions: Option<usize>,
+    /// The lowest level of log entries which are shown in the log pane, or [`None`] to show every
+    /// entry
+    pub log_level: Option<LogLevel>,
+    /// A file to which log entries are appended with the time they were logged, so that Sapling
+    /// can be debugged after it closes, or [`None`] if entries shouldn't be written to a file
+    pub log_file_path: Option<PathBuf>,
+    /// The lowest level of log entries which are written to the log file, or [`None`] to use
+    /// [`DEFAULT_LOG_FILE_LEVEL`](super::log::DEFAULT_LOG_FILE_LEVEL)
+    pub log_file_level: Option<LogLevel>,
+    /// A file to which entries dropped from the log are appended, or [`None`] if they should just
+    /// be thrown away
+    pub log_overflow_path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the settings in `config`, the parsed TOML config file at `path` (see
+    /// [`read_config_file`]), starting from the default settings.  If the settings can't be loaded (e.g. because one has the wrong type) then the default
+    /// settings are returned along with a warning to show the user.  For example:
+    ///
+    /// ```toml
+    /// cursor-style = { markers = ["»", "«"] }  # or "reverse", "underline", { background = "blue" }
+    /// landing = "new"             # or "parent", "hole"
+    /// line-numbers = true
+    /// autosave = true
+    /// format = "compact"          # the names depend on the language
+    /// indent = 2                  # or "tab"
+    /// status = ["mode", "file", "modified"]
+    /// idle-delay = 300            # milliseconds
+    /// command-timeout = 1000      # milliseconds
+    /// max-fps = 30
+    /// events = "/tmp/sapling-events"
+    ///
+    /// [journal]
+    /// enabled = true              # journals aren't kept unless this is given
+    /// dir = "/tmp/sapling-journals"
+    /// keep = 10                   # sessions
+    ///
+    /// [limits]
+    /// max-depth = 500
+    /// max-children = 10000
+    ///
+    /// [confirm]
+    /// discard-changes = false     # and the other fields of `ConfirmConfig`, in kebab-case
+    ///
+    /// [log]
+    /// level = "info"              # shown in the log pane
+    /// capacity = 1000
+    /// file = "/tmp/sapling.log"
+    /// file-level = "warning"
+    /// overflow = "/tmp/sapling-overflow.log"
+    ///
+    /// [messages]
+    /// normal-indicator = "-- NORMAL --"
+    ///
+    /// [languages.json]
+    /// template = "{}"
+    /// preview = ["jq", "."]       # or "text"
+    /// keymap = { x = "Undo" }
+    /// ```
+    pub fn from_config(path: &Path, config: &toml::Value) -> (Config, Option<Message>) {
+        match Config::parse_table(config) {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(load_error(path, e))),
+        }
+    }
+
+    /// Parses the settings in the text of a TOML config file
+    #[cfg(test)]
+    fn parse(text: &str) -> Result<Config, String> {
+        let table: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        Config::parse_table(&table)
+    }
+
+    /// Parses the settings in a TOML config file which has already been parsed as TOML
+    fn parse_table(table: &toml::Value) -> Result<Config, String> {
+        let mut config = Config::default();
+        if let Some(limits) = get_table(table, "limits")? {
+            if let Some(max_depth) = get_positive(limits, "max-depth")? {
+                config.limits.max_depth = max_depth;
+            }
+            if let Some(max_children) = get_positive(limits, "max-children")? {
+                config.limits.max_children = max_children;
+            }
+        }
+        if let Some(confirm) = get_table(table, "confirm")? {
+            let c = &mut config.confirm;
+            for (key, flag) in [
+                ("replace-large-subtree", &mut c.replace_large_subtree),
+                ("overwrite-file", &mut c.overwrite_file),
+                ("discard-changes", &mut c.discard_changes),
+                ("discard-redo", &mut c.discard_redo),
+                ("paste-large-subtree", &mut c.paste_large_subtree),
+                ("fold-oversized", &mut c.fold_oversized),
+            ] {
+                if let Some(value) = get_bool(confirm, key)? {
+                    *flag = value;
+                }
+            }
+            if let Some(size) = get_positive(confirm, "large-subtree-size")? {
+                c.large_subtree_size = size;
+            }
+            if let Some(size) = get_positive(confirm, "large-paste-size")? {
+                c.large_paste_size = size;
+            }
+        }
+        if let Some(style) = table.get("cursor-style") {
+            config.cursor_style = parse_cursor_style(style)?;
+        }
+        if let Some(name) = get_str(table, "landing")? {
+            config.landing =
+                Landing::from_name(name).ok_or_else(|| format!("'{}' isn't a landing", name))?;
+        }
+        if let Some(line_numbers) = get_bool(table, "line-numbers")? {
+            config.line_numbers = line_numbers;
+        }
+        if let Some(autosave) = get_bool(table, "autosave")? {
+            config.autosave = autosave;
+        }
+        config.format_name = get_str(table, "format")?.map(str::to_string);
+        config.indentation = get_indentation(table, "indent")?;
+        match table.get("status") {
+            Some(toml::Value::Array(names)) => {
+                let segments = names
+                    .iter()
+                    .map(|name| {
+                        name.as_str()
+                            .and_then(Segment::from_name)
+                            .ok_or_else(|| format!("{} isn't a status bar segment", name))
+                    })
+                    .collect::<Result<_, _>>()?;
+                config.status_segments = Some(segments);
+            }
+            Some(_) => return Err("'status' isn't a list of segments".to_string()),
+            None => {}
+        }
+        config.idle_delay = get_millis(table, "idle-delay")?;
+        config.command_timeout = get_millis(table, "command-timeout")?;
+        config.max_fps = get_positive(table, "max-fps")?.map(|fps| fps as u32);
+        config.events_path = get_path(table, "events")?;
+        if let Some(journal) = get_table(table, "journal")? {
+            if get_bool(journal, "enabled")? == Some(true) {
+                let dir = get_path(journal, "dir")?.or_else(journal::default_dir);
+                config.journal_path = dir.map(|dir| journal::session_path(&dir));
+            }
+            config.journal_kept_sessions = get_positive(journal, "keep")?;
+        }
+        if let Some(log) = get_table(table, "log")? {
+            config.log_level = get_log_level(log, "level")?;
+            config.log_capacity = get_positive(log, "capacity")?;
+            config.log_file_path = get_path(log, "file")?;
+            config.log_file_level = get_log_level(log, "file-level")?;
+            config.log_overflow_path = get_path(log, "overflow")?;
+        }
+        if let Some(messages) = get_table(table, "messages")? {
+            for (key, template) in messages.as_table().into_iter().flatten() {
+                let template = template
+                    .as_str()
+                    .ok_or_else(|| format!("the message '{}' isn't a string", key))?;
+                config
+                    .message_overrides
+                    .insert(key.clone(), template.to_string());
+            }
+        }
+        if let Some(languages) = get_table(table, "languages")? {
+            for (name, language) in languages.as_table().into_iter().flatten() {
+                if !language.is_table() {
+                    return Err(format!("'languages.{}' isn't a table", name));
+                }
+                if let Some(template) = get_str(language, "template")? {
+                    config.templates.insert(name.clone(), template.to_string());
+                }
+                if let Some(renderer) = language.get("preview") {
+                    config
+                        .previewers
+                        .insert(name.clone(), parse_renderer(renderer)?);
+                }
+                match language.get("keymap") {
+                    Some(toml::Value::Table(keymap)) => {
+                        let bindings = keymap::parse_bindings(keymap)?;
+                        config.language_bindings.insert(name.clone(), bindings);
+                    }
+                    Some(_) => return Err(format!("'languages.{}.keymap' isn't a table", name)),
+                    None => {}
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_project_config, read_config_file, Config, ProjectConfig, PROJECT_CONFIG_NAME,
+    };
+    use crate::ast::display_token::Indentation;
+    use crate::editor::cursor_style::CursorStyle;
+    use crate::editor::keymap::KeyInput;
+    use crate::editor::limits::TreeLimits;
+    use crate::editor::preview::Renderer;
+    use crate::editor::status_bar::Segment;
+    use crate::editor::{Command, Landing, LogLevel};
+    use crate::messages::Message;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use tuikit::prelude::Color;
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(
+            "[limits]\nmax-depth = 20\n[confirm]\nfold-oversized = false\n[keymap]\nx = \"Undo\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.limits,
+            TreeLimits {
+                max_depth: 20,
+                ..TreeLimits::default()
+            }
+        );
+        assert!(!config.confirm.fold_oversized);
+        // Settings which aren't given keep their defaults
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        for text in &[
+            "limits = 3",
+            "[limits]\nmax-children = 0",
+            "[confirm]\nfold-oversized = \"no\"",
+            "cursor-style = \"blink\"",
+            "cursor-style = { markers = [\"<\"] }",
+            "landing = \"nowhere\"",
+            "status = [\"mode\", \"weather\"]",
+            "idle-delay = -1",
+            "indent = 0",
+            "indent = \"spaces\"",
+            "[journal]\nenabled = 1",
+            "[log]\nfile-level = \"loud\"",
+            "[messages]\nnormal-indicator = 3",
+            "languages = 3",
+            "[languages.json]\npreview = []",
+            "[languages.json.keymap]\nx = \"Fly\"",
+        ] {
+            assert!(Config::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parse_every_setting() {
+        let text = r#"
+            cursor-style = { markers = ["»", "«"] }
+            landing = "new"
+            line-numbers = true
+            autosave = true
+            format = "compact"
+            indent = "tab"
+            status = ["mode", "file"]
+            idle-delay = 300
+            command-timeout = 1000
+            max-fps = 30
+            events = "events.fifo"
+
+            [journal]
+            enabled = true
+            dir = "journals"
+            keep = 3
+
+            [confirm]
+            discard-changes = false
+            large-paste-size = 50
+
+            [log]
+            level = "warning"
+            capacity = 20
+            file = "sapling.log"
+            file-level = "debug"
+            overflow = "overflow.log"
+
+            [messages]
+            normal-indicator = "-- NORMAL --"
+
+            [languages.json]
+            template = "[]"
+            preview = ["jq", "."]
+            keymap = { x = "Undo" }
+
+            [languages.markdown]
+            preview = "text"
+        "#;
+        let config = Config::parse(text).unwrap();
+        let path = |path: &str| Some(PathBuf::from(path));
+        assert_eq!(
+            config.cursor_style,
+            CursorStyle::Markers("»".to_string(), "«".to_string())
+        );
+        assert_eq!(config.landing, Landing::NewNode);
+        assert!(config.line_numbers);
+        assert!(config.autosave);
+        assert_eq!(config.format_name.as_deref(), Some("compact"));
+        assert_eq!(config.indentation, Some(Indentation::Tabs));
+        assert_eq!(
+            config.status_segments,
+            Some(vec![Segment::Mode, Segment::FileName])
+        );
+        assert_eq!(config.idle_delay, Some(Duration::from_millis(300)));
+        assert_eq!(config.command_timeout, Some(Duration::from_millis(1000)));
+        assert_eq!(config.max_fps, Some(30));
+        assert_eq!(config.events_path, path("events.fifo"));
+        let journal_path = config.journal_path.unwrap();
+        assert_eq!(journal_path.parent(), Some(Path::new("journals")));
+        assert_eq!(config.journal_kept_sessions, Some(3));
+        assert!(!config.confirm.discard_changes);
+        assert!(config.confirm.overwrite_file);
+        assert_eq!(config.confirm.large_paste_size, 50);
+        assert_eq!(config.log_level, Some(LogLevel::Warning));
+        assert_eq!(config.log_capacity, Some(20));
+        assert_eq!(config.log_file_path, path("sapling.log"));
+        assert_eq!(config.log_file_level, Some(LogLevel::Debug));
+        assert_eq!(config.log_overflow_path, path("overflow.log"));
+        assert_eq!(config.message_overrides["normal-indicator"], "-- NORMAL --");
+        assert_eq!(config.templates["json"], "[]");
+        assert_eq!(
+            config.previewers["json"],
+            Renderer::Command(vec!["jq".to_string(), ".".to_string()])
+        );
+        assert_eq!(config.previewers["markdown"], Renderer::Text);
+        assert_eq!(
+            config.language_bindings["json"],
+            vec![(vec![KeyInput::from('x')], Command::Undo)]
+        );
+
+        let config = Config::parse("cursor-style = { background = \"blue\" }").unwrap();
+        assert_eq!(config.cursor_style, CursorStyle::Background(Color::BLUE));
+        // Journals aren't kept unless they're turned on
+        let config = Config::parse("[journal]\ndir = \"journals\"").unwrap();
+        assert_eq!(config.journal_path, None);
+    }
+
+    #[test]
+    fn config_file() {
+        let dir = std::env::temp_dir().join(format!("sapling-config-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("config.toml");
+        // Missing files are fine, but files which aren't TOML give a warning
+        assert_eq!(read_config_file(&path), (None, None));
+        std::fs::write(&path, "[keymap").unwrap();
+        let (config, warning) = read_config_file(&path);
+        assert_eq!(config, None);
+        assert!(matches!(warning, Some(Message::CannotLoadConfig(..))));
+        std::fs::write(&path, "max-fps = 30").unwrap();
+        let (config, warning) = read_config_file(&path);
+        assert_eq!(warning, None);
+        let (config, warning) = Config::from_config(&path, &config.unwrap());
+        assert_eq!((config.max_fps, warning), (Some(30), None));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn project_config() {
+        let dir = std::env::temp_dir().join(format!("sapling-project-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let nested = dir.join("data").join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_config(&nested), None);
+
+        // The nearest project config is found by walking up from the file's directory
+        let path = dir.join(PROJECT_CONFIG_NAME);
+        let text = "schema = \"schema.json\"\nwidth = 60\nformat = \"pretty\"\nindent = 2\n\
+                    [keymap]\nx = \"Undo\"\n";
+        std::fs::write(&path, text).unwrap();
+        assert_eq!(find_project_config(&nested), Some(path.clone()));
+        let project = ProjectConfig::load(&path).unwrap();
+        assert_eq!(
+            project,
+            ProjectConfig {
+                bindings: vec![(vec![KeyInput::from('x')], Command::Undo)],
+                schema_path: Some(dir.join("schema.json")),
+                line_width: Some(60),
+                format_name: Some("pretty".to_string()),
+                indentation: Some(Indentation::Spaces(2)),
+            }
+        );
+
+        // Only the settings given by the project config are overridden
+        let mut config = Config {
+            schema_path: Some(PathBuf::from("user.json")),
+            line_width: Some(100),
+            ..Config::default()
+        };
+        ProjectConfig::default().apply(&mut config);
+        assert_eq!(config.line_width, Some(100));
+        project.apply(&mut config);
+        assert_eq!(config.schema_path, Some(dir.join("schema.json")));
+        assert_eq!(config.line_width, Some(60));
+        assert_eq!(config.format_name.as_deref(), Some("pretty"));
+        assert_eq!(config.indentation, Some(Indentation::Spaces(2)));
+        assert_eq!(config.project_bindings.len(), 1);
+
+        std::fs::write(&path, "width = -3").unwrap();
+        assert!(ProjectConfig::load(&path).is_err());
+        std::fs::write(&path, "[keymap]\nx = \"Fly\"").unwrap();
+        assert!(ProjectConfig::load(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
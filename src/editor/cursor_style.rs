@@ -0,0 +1,39 @@
+//! The different ways that the node under the cursor can be made to stand out.
+
+use tuikit::prelude::{Attr, Color, Effect};
+
+/// How the node under the cursor is indicated on the screen
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum CursorStyle {
+    /// Swap the node's foreground and background colours
+    #[default]
+    Reverse,
+    /// Underline the node, keeping its usual colours
+    Underline,
+    /// Draw the node on a background of a given colour, keeping its usual foreground colour
+    Background(Color),
+    /// Surround the node with a pair of markers (e.g. `»` and `«`), keeping its usual colours
+    Markers(String, String),
+}
+
+impl CursorStyle {
+    /// Returns the attributes with which to draw a token of the node under the cursor, given the
+    /// colour that the token would usually be drawn in
+    pub fn attr(&self, color: Color) -> Attr {
+        match self {
+            CursorStyle::Reverse => Attr::default().fg(Color::BLACK).bg(color),
+            CursorStyle::Underline => Attr::default().fg(color).effect(Effect::UNDERLINE),
+            CursorStyle::Background(bg) => Attr::default().fg(color).bg(*bg),
+            CursorStyle::Markers(..) => Attr::default().fg(color),
+        }
+    }
+
+    /// Returns the markers that should be drawn before and after the node under the cursor, or
+    /// [`None`] if the node isn't surrounded by markers
+    pub fn markers(&self) -> Option<(&str, &str)> {
+        match self {
+            CursorStyle::Markers(open, close) => Some((open, close)),
+            _ => None,
+        }
+    }
+}
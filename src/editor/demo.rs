@@ -0,0 +1,134 @@
+//! Scripted sessions, which replay a sequence of key presses with pauses between them (run with
+//! `sapling --demo <script>`).  These are used for recording GIFs of Sapling and for checking,
+//! before a release, that whole sessions still behave as they should.
+//!
+//! A script is a text file where each line is one of:
+//! - A sequence of keys, written in the same form as a macro (e.g. `cjrt<CR>`)
+//! - `delay <ms>`, which sets the pause before each of the following keys
+//! - `pause <ms>`, which pauses once before the next key
+//! - A comment starting with `#`, or a blank line, which is ignored
+
+use super::macros;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tuikit::key::Key;
+
+/// The pause before each key, until the script sets its own with `delay`
+pub const DEFAULT_KEY_DELAY: Duration = Duration::from_millis(150);
+
+/// A script which is being replayed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Demo {
+    /// The keys still to be pressed, each with how long to wait before pressing it
+    keys: VecDeque<(Duration, Key)>,
+    /// How long to wait after the last key before the demo finishes
+    final_pause: Duration,
+    /// When the next key (or the end of the demo) is due
+    next_due: Instant,
+}
+
+impl Demo {
+    /// Parses the text of a script, starting the demo from `now`.  Returns an error saying which
+    /// line is invalid if the script can't be parsed.
+    pub fn parse(text: &str, now: Instant) -> Result<Demo, String> {
+        let mut keys = VecDeque::new();
+        let mut delay = DEFAULT_KEY_DELAY;
+        let mut pause = Duration::from_secs(0);
+        for (i, line) in text.lines().enumerate() {
+            let error = |e: String| format!("line {}: {}", i + 1, e);
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(ms) = line.strip_prefix("delay ") {
+                delay = parse_millis(ms).map_err(error)?;
+            } else if let Some(ms) = line.strip_prefix("pause ") {
+                pause += parse_millis(ms).map_err(error)?;
+            } else {
+                let line_keys = macros::text_to_keys(line)
+                    .map_err(|name| error(format!("unknown key '{}'", name)))?;
+                for key in line_keys {
+                    keys.push_back((delay + pause, key));
+                    pause = Duration::from_secs(0);
+                }
+            }
+        }
+        let next_due = now + keys.front().map_or(pause, |(wait, _)| *wait);
+        Ok(Demo {
+            keys,
+            final_pause: pause,
+            next_due,
+        })
+    }
+
+    /// Returns how long until the next key is due to be pressed (or the demo is due to finish)
+    pub fn time_until_due(&self, now: Instant) -> Duration {
+        self.next_due.saturating_duration_since(now)
+    }
+
+    /// Returns `true` once every key has been pressed and the final pause is over
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.keys.is_empty() && now >= self.next_due
+    }
+
+    /// Returns the next key, if it is due to be pressed
+    pub fn take_due_key(&mut self, now: Instant) -> Option<Key> {
+        if now < self.next_due {
+            return None;
+        }
+        let (_, key) = self.keys.pop_front()?;
+        // The next wait is counted from now, so a slow key press doesn't rush the ones after it
+        let wait = self
+            .keys
+            .front()
+            .map_or(self.final_pause, |(wait, _)| *wait);
+        self.next_due = now + wait;
+        Some(key)
+    }
+}
+
+/// Parses a number of milliseconds
+fn parse_millis(text: &str) -> Result<Duration, String> {
+    text.trim()
+        .parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("'{}' is not a number of milliseconds", text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Demo, DEFAULT_KEY_DELAY};
+    use std::time::{Duration, Instant};
+    use tuikit::key::Key;
+
+    #[test]
+    fn replay() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let script = "# Move down\nc\npause 1000\ndelay 10\nrt<CR>\npause 500\n";
+        let mut demo = Demo::parse(script, start).unwrap();
+        assert_eq!(demo.time_until_due(start), DEFAULT_KEY_DELAY);
+        assert_eq!(demo.take_due_key(start), None);
+        let now = start + DEFAULT_KEY_DELAY;
+        assert_eq!(demo.take_due_key(now), Some(Key::Char('c')));
+        assert_eq!(demo.time_until_due(now), ms(1010));
+        let now = now + ms(1010);
+        assert_eq!(demo.take_due_key(now), Some(Key::Char('r')));
+        let now = now + ms(10);
+        assert_eq!(demo.take_due_key(now), Some(Key::Char('t')));
+        let now = now + ms(10);
+        assert_eq!(demo.take_due_key(now), Some(Key::Enter));
+        assert!(!demo.is_finished(now));
+        assert_eq!(demo.take_due_key(now + ms(500)), None);
+        assert!(demo.is_finished(now + ms(500)));
+
+        assert_eq!(
+            Demo::parse("c\ndelay soon", start),
+            Err("line 2: 'soon' is not a number of milliseconds".to_string())
+        );
+        assert_eq!(
+            Demo::parse("<Nope>", start),
+            Err("line 1: unknown key '<Nope>'".to_string())
+        );
+    }
+}
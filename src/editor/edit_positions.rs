@@ -0,0 +1,179 @@
+//! The list of places where the buffer was recently edited, which can be stepped through to get
+//! back to an edit after looking elsewhere.  Unlike the [motion history](super::motion_history),
+//! this only changes when an edit is made, so it isn't lost by moving around.  Each position
+//! remembers the edited node as well as its path, so that later edits which move the node (e.g.
+//! by inserting a sibling before it) move the position along with it.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+
+/// The maximum number of positions stored in an [`EditPositions`]
+pub const MAX_EDIT_POSITIONS: usize = 100;
+
+/// The paths of the nodes where the most recent edits were made, which can be stepped through
+/// from the newest to the oldest and back again
+#[derive(Debug, Clone)]
+pub struct EditPositions<'arena, Node> {
+    /// The paths of the edits along with the edited nodes, oldest first
+    positions: Vec<(CursorPath, &'arena Node)>,
+    /// The index into `positions` of the position that was last stepped to, which is
+    /// `positions.len()` if the list hasn't been stepped through since the last edit
+    index: usize,
+}
+
+impl<'arena, Node: Ast<'arena>> EditPositions<'arena, Node> {
+    /// Creates an empty list of edit positions
+    pub fn new() -> EditPositions<'arena, Node> {
+        EditPositions {
+            positions: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Records that an edit was made to `node` at `path`, which becomes the newest position.
+    /// Stepping through the list starts again from the newest position.
+    pub fn record(&mut self, path: &CursorPath, node: &'arena Node) {
+        // Repeated edits in the same place only need to be stepped back over once
+        match self.positions.last_mut() {
+            Some((last_path, last_node)) if last_path == path => *last_node = node,
+            _ => self.positions.push((path.clone(), node)),
+        }
+        if self.positions.len() > MAX_EDIT_POSITIONS {
+            let excess = self.positions.len() - MAX_EDIT_POSITIONS;
+            self.positions.drain(..excess);
+        }
+        self.index = self.positions.len();
+    }
+
+    /// Steps to the next older edit position, returning it or [`None`] if there are no older
+    /// positions
+    pub fn older(&mut self) -> Option<&CursorPath> {
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.positions.get(self.index).map(|(path, _)| path)
+    }
+
+    /// Steps to the next newer edit position, returning it or [`None`] if there are no newer
+    /// positions
+    pub fn newer(&mut self) -> Option<&CursorPath> {
+        if self.index + 1 >= self.positions.len() {
+            return None;
+        }
+        self.index += 1;
+        self.positions.get(self.index).map(|(path, _)| path)
+    }
+
+    /// Updates the positions after the tree has been edited at `edit_path` so that its root is
+    /// now `root`.  Positions follow their nodes when the edit moves them, and an edit at or
+    /// inside a position's node leaves the position on the edited copy.  Positions whose nodes
+    /// are no longer in the tree are dropped.
+    pub fn adjust(&mut self, root: &'arena Node, edit_path: &CursorPath) {
+        let mut index = self.index;
+        let mut adjusted = Vec::with_capacity(self.positions.len());
+        for (i, (path, node)) in self.positions.drain(..).enumerate() {
+            if let Some(new_path) = cursor_path::follow(root, &path, node, edit_path) {
+                adjusted.push((new_path, node));
+            } else if edit_path.starts_with(&path) && path.is_valid(root) {
+                let node = path.cursor(root);
+                adjusted.push((path, node));
+            } else if i < self.index {
+                // The position that was last stepped to keeps its place in the list
+                index -= 1;
+            }
+        }
+        self.positions = adjusted;
+        self.index = index;
+    }
+}
+
+impl<'arena, Node: Ast<'arena>> Default for EditPositions<'arena, Node> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditPositions, MAX_EDIT_POSITIONS};
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    fn path(indices: &[usize]) -> CursorPath {
+        CursorPath::from_vec(indices.to_vec())
+    }
+
+    #[test]
+    fn older_and_newer() {
+        let mut positions = EditPositions::new();
+        assert_eq!(positions.older(), None);
+        positions.record(&path(&[0]), &JSON::True);
+        positions.record(&path(&[1, 2]), &JSON::True);
+        positions.record(&path(&[1, 2]), &JSON::True);
+        positions.record(&path(&[3]), &JSON::True);
+        // The newest edit is the first one stepped to
+        assert_eq!(positions.older(), Some(&path(&[3])));
+        assert_eq!(positions.older(), Some(&path(&[1, 2])));
+        assert_eq!(positions.older(), Some(&path(&[0])));
+        assert_eq!(positions.older(), None);
+        assert_eq!(positions.newer(), Some(&path(&[1, 2])));
+        assert_eq!(positions.newer(), Some(&path(&[3])));
+        assert_eq!(positions.newer(), None);
+        // A new edit starts again from the newest position
+        positions.older();
+        positions.older();
+        positions.record(&path(&[4]), &JSON::True);
+        assert_eq!(positions.newer(), None);
+        assert_eq!(positions.older(), Some(&path(&[4])));
+        assert_eq!(positions.older(), Some(&path(&[3])));
+    }
+
+    #[test]
+    fn max_len() {
+        let mut positions = EditPositions::new();
+        for i in 0..MAX_EDIT_POSITIONS * 2 {
+            positions.record(&path(&[i]), &JSON::True);
+        }
+        let mut steps = 0;
+        while positions.older().is_some() {
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_EDIT_POSITIONS);
+    }
+
+    #[test]
+    fn adjusting() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::False]),
+        ])
+        .add_to_arena(&arena);
+        let (first, second) = (root.children()[0], root.children()[1]);
+        let mut positions = EditPositions::new();
+        positions.record(&path(&[0]), first);
+        positions.record(&path(&[1]), second);
+        // Inserting a node before the edited nodes moves their positions along
+        let new_root = arena.alloc(JSON::Array(vec![arena.alloc(JSON::True), first, second]));
+        positions.adjust(new_root, &path(&[0]));
+        assert_eq!(positions.older(), Some(&path(&[2])));
+        // Removing a node drops its position, without losing the place in the list
+        let new_root = arena.alloc(JSON::Array(vec![first]));
+        positions.adjust(new_root, &path(&[0]));
+        assert_eq!(positions.older(), Some(&path(&[0])));
+        assert_eq!(positions.newer(), None);
+        // Editing inside a node keeps its position
+        let new_first = arena.alloc(JSON::Array(vec![]));
+        let new_root = arena.alloc(JSON::Array(vec![new_first]));
+        positions.adjust(new_root, &path(&[0, 0]));
+        assert_eq!(positions.older(), None);
+        assert_eq!(positions.newer(), None);
+        positions.record(&path(&[0]), new_first);
+        assert_eq!(positions.older(), Some(&path(&[0])));
+        assert_eq!(positions.older(), None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Rendering of only the top few levels of deeply nested documents.  Every node on the path from
+//! the root to the cursor is rendered, along with a limited number of levels below each of them;
+//! anything deeper is replaced by a placeholder.  Moving the cursor into a placeholder's node puts
+//! it on the cursor's path, so its contents are revealed.  [Folded](super::folds) nodes are
+//! replaced by placeholders in the same way, whatever their depth, as are nodes which go over the
+//! [limits](super::limits) on the shape of the tree (so that pathological trees can always be
+//! drawn).
+
+use super::limits::TreeLimits;
+use crate::ast::display_token::{self, DisplayToken, RecTok};
+use crate::ast::layout;
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+
+/// The text which stands in for hidden nodes, given the number of children that were hidden
+pub struct Placeholders<'f> {
+    /// The text standing in for nodes which are too deep, or which go over the limits
+    pub elided: &'f dyn Fn(usize) -> String,
+    /// The text standing in for folded nodes
+    pub folded: &'f dyn Fn(usize) -> String,
+}
+
+/// Returns the display tokens of the tree under `root`, like [`Ast::display_tokens`], but with
+/// every node more than `max_depth` levels away from the cursor's path (if there is a maximum
+/// depth) replaced by a placeholder showing its delimiters and the elided placeholder text.  Nodes
+/// with an [`outline_label`](Ast::outline_label) (like JSON fields) are part of the same level as
+/// their parent, so that their labels are never hidden.  The `folded` nodes are replaced by the
+/// folded placeholder text instead, unless the cursor is inside them.  Nodes which aren't on the
+/// cursor's path and go over the `limits` are elided whatever the maximum depth.  The tokens are
+/// laid out to fit the [`max_line_width`](Ast::max_line_width) of the format style, if it has
+/// one.
+pub fn display_tokens<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    format_style: &Node::FormatStyle,
+    cursor_path: &CursorPath,
+    max_depth: Option<usize>,
+    limits: &TreeLimits,
+    folded: &[&'arena Node],
+    placeholders: &Placeholders,
+) -> Vec<(&'arena Node, DisplayToken)> {
+    let spine: Vec<&Node> = cursor_path.node_iter(root).collect();
+    let mut renderer = Renderer {
+        format_style,
+        spine,
+        max_depth,
+        limits,
+        folded,
+        placeholders,
+        tokens: Vec::new(),
+    };
+    renderer.add_tokens(root, Some(0), 0, 0);
+    match Node::max_line_width(format_style) {
+        Some(max_width) => layout::fit_to_width(renderer.tokens, max_width),
+        None => renderer.tokens,
+    }
+}
+
+/// The state of rendering a tree with elision
+struct Renderer<'arena, 'f, Node: Ast<'arena>> {
+    format_style: &'f Node::FormatStyle,
+    /// The nodes from the root down to the cursor
+    spine: Vec<&'arena Node>,
+    max_depth: Option<usize>,
+    limits: &'f TreeLimits,
+    folded: &'f [&'arena Node],
+    placeholders: &'f Placeholders<'f>,
+    tokens: Vec<(&'arena Node, DisplayToken)>,
+}
+
+impl<'arena, 'f, Node: Ast<'arena>> Renderer<'arena, 'f, Node> {
+    /// Adds the tokens of `node`, which is `spine_index` nodes down the cursor's path if it's on
+    /// that path, or otherwise `depth` levels away from it.  `level` is the length of the path
+    /// from the root to `node`.
+    fn add_tokens(
+        &mut self,
+        node: &'arena Node,
+        spine_index: Option<usize>,
+        depth: usize,
+        level: usize,
+    ) {
+        let num_children = node.children().len();
+        if num_children == 0 {
+            // Leaves have nothing to hide
+        } else if spine_index.is_none_or(|i| i + 1 == self.spine.len())
+            && self.folded.iter().any(|n| std::ptr::eq(*n, node))
+        {
+            // Folds stay closed when the cursor is on them, but open when it moves inside
+            return self.add_placeholder(node, self.placeholders.folded);
+        } else if spine_index.is_none()
+            && (self.max_depth.is_some_and(|max| depth > max)
+                || level >= self.limits.max_depth
+                || num_children > self.limits.max_children)
+        {
+            return self.add_placeholder(node, self.placeholders.elided);
+        }
+        let next_spine_node = spine_index.and_then(|i| self.spine.get(i + 1).copied());
+        let mut child_index = 0;
+        for rec_tok in node.display_tokens_rec(self.format_style) {
+            match rec_tok {
+                RecTok::Tok(tok) => self.tokens.push((node, tok)),
+                RecTok::Child(child) => {
+                    let start = self.tokens.len();
+                    if next_spine_node.is_some_and(|n| std::ptr::eq(n, child)) {
+                        self.add_tokens(child, spine_index.map(|i| i + 1), 0, level + 1);
+                    } else {
+                        let is_new_level = child.outline_label().is_none();
+                        let depth = depth + is_new_level as usize;
+                        self.add_tokens(child, None, depth, level + 1);
+                    }
+                    if let Some(category) = node.child_category(child_index) {
+                        display_token::set_category(&mut self.tokens[start..], category);
+                    }
+                    child_index += 1;
+                }
+            }
+        }
+    }
+
+    /// Adds the tokens which stand in for an elided node: the text and delimiters before its
+    /// first child and after its last child, with the placeholder in between
+    fn add_placeholder(&mut self, node: &'arena Node, placeholder: &dyn Fn(usize) -> String) {
+        let rec_toks = node.display_tokens_rec(self.format_style);
+        let is_child = |rec_tok: &RecTok<'arena, Node>| matches!(rec_tok, RecTok::Child(_));
+        let first_child = rec_toks.iter().position(is_child).unwrap_or(rec_toks.len());
+        let last_child = rec_toks.iter().rposition(is_child).unwrap_or(first_child);
+        let is_visible = |tok: &DisplayToken| {
+            matches!(
+                tok,
+                DisplayToken::Text(_)
+                    | DisplayToken::Categorised(..)
+                    | DisplayToken::OpenDelim(_)
+                    | DisplayToken::CloseDelim(_)
+            )
+        };
+        for (i, rec_tok) in rec_toks.into_iter().enumerate() {
+            if i == first_child {
+                let text = placeholder(node.children().len());
+                self.tokens.push((node, DisplayToken::Text(text)));
+            }
+            match rec_tok {
+                RecTok::Tok(tok) if (i < first_child || i > last_child) && is_visible(&tok) => {
+                    self.tokens.push((node, tok))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_tokens, Placeholders};
+    use crate::arena::Arena;
+    use crate::ast::display_token::DisplayToken;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::editor::limits::TreeLimits;
+
+    /// Renders a tree with elision, using `…n` as the placeholder of `n` elided children and `+n`
+    /// as the placeholder of `n` folded children
+    fn text<'arena>(
+        root: &'arena JSON<'arena>,
+        path: Vec<usize>,
+        max_depth: Option<usize>,
+        folded: &[&'arena JSON<'arena>],
+    ) -> String {
+        text_with_limits(root, path, max_depth, &TreeLimits::default(), folded)
+    }
+
+    /// Like `text`, but with limits on the shape of the tree
+    fn text_with_limits<'arena>(
+        root: &'arena JSON<'arena>,
+        path: Vec<usize>,
+        max_depth: Option<usize>,
+        limits: &TreeLimits,
+        folded: &[&'arena JSON<'arena>],
+    ) -> String {
+        let placeholders = Placeholders {
+            elided: &|n: usize| format!("…{}", n),
+            folded: &|n: usize| format!("+{}", n),
+        };
+        let path = CursorPath::from_vec(path);
+        display_tokens(
+            root,
+            &JSONFormat::Compact,
+            &path,
+            max_depth,
+            limits,
+            folded,
+            &placeholders,
+        )
+        .into_iter()
+        .filter_map(|(_, tok)| match tok {
+            DisplayToken::Text(s)
+            | DisplayToken::Categorised(s, _)
+            | DisplayToken::OpenDelim(s)
+            | DisplayToken::CloseDelim(s) => Some(s),
+            DisplayToken::Whitespace(n) => Some(" ".repeat(n)),
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn elision() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::Array(vec![TestJSON::True])]),
+            TestJSON::Object(vec![(
+                "a".to_string(),
+                TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+            )]),
+        ])
+        .add_to_arena(&arena);
+        // Fields don't count as a level, so their keys are shown
+        assert_eq!(text(root, vec![], Some(1), &[]), r#"[[[…1]], {"a": […2]}]"#);
+        assert_eq!(text(root, vec![], Some(0), &[]), r#"[[…1], {…1}]"#);
+        // Moving the cursor into an elided node reveals it
+        assert_eq!(text(root, vec![0, 0], Some(0), &[]), r#"[[[true]], {…1}]"#);
+        assert_eq!(
+            text(root, vec![], Some(5), &[]),
+            r#"[[[true]], {"a": [true, false]}]"#
+        );
+    }
+
+    #[test]
+    fn folding() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+            TestJSON::Object(vec![("a".to_string(), TestJSON::True)]),
+        ])
+        .add_to_arena(&arena);
+        let folded = root.children().to_vec();
+        assert_eq!(text(root, vec![], None, &folded), "[[+2], {+1}]");
+        // A fold stays closed when the cursor is on it, but opens when the cursor is inside it
+        assert_eq!(text(root, vec![0], None, &folded), "[[+2], {+1}]");
+        assert_eq!(
+            text(root, vec![1, 0], None, &folded),
+            r#"[[+2], {"a": true}]"#
+        );
+        // Leaves can't be folded
+        assert_eq!(
+            text(root, vec![], None, &[root.children()[0].children()[0]]),
+            r#"[[true, false], {"a": true}]"#
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::Array(vec![TestJSON::True])]),
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False, TestJSON::True]),
+        ])
+        .add_to_arena(&arena);
+        let limits = TreeLimits {
+            max_depth: 2,
+            max_children: 2,
+        };
+        assert_eq!(
+            text_with_limits(root, vec![], None, &limits, &[]),
+            "[[[…1]], […3]]"
+        );
+        // Nodes on the cursor's path are shown whatever the limits
+        assert_eq!(
+            text_with_limits(root, vec![1], None, &limits, &[]),
+            "[[[…1]], [true, false, true]]"
+        );
+        // A tree too deep to render recursively is cut off at the maximum depth
+        let mut deep: &JSON = arena.alloc(JSON::True);
+        for _ in 0..100_000 {
+            deep = arena.alloc(JSON::Array(vec![deep]));
+        }
+        let limits = TreeLimits::default();
+        let text = text_with_limits(deep, vec![], None, &limits, &[]);
+        assert_eq!(text, format!("{}…1{}", "[".repeat(101), "]".repeat(101)));
+    }
+}
@@ -0,0 +1,322 @@
+//! The encodings and line endings of files, which are detected when a file is opened so that it
+//! can be written back in the same format rather than always as UTF-8 with `\n` line endings.
+
+use std::fmt::{Display, Formatter};
+
+/// The byte order mark which starts UTF-8 files written by some editors
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// The ways that the text of a file can be encoded as bytes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO 8859-1, where every byte is the [`char`] with the same value
+    Latin1,
+}
+
+impl Encoding {
+    /// Returns the encoding with a given name (as used by `:set encoding=<name>`), or [`None`] if
+    /// there is no such encoding
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of this encoding, as shown in the bottom bar
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin-1",
+        }
+    }
+
+    /// Returns the byte order mark written at the start of files in this encoding
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Utf16Le => UTF16_LE_BOM,
+            Encoding::Utf16Be => UTF16_BE_BOM,
+            Encoding::Latin1 => &[],
+        }
+    }
+
+    /// Returns the first [`char`] of `text` which can't be encoded in this encoding, if any
+    pub fn unencodable_char(self, text: &str) -> Option<char> {
+        match self {
+            Encoding::Latin1 => text.chars().find(|c| *c as u32 > 0xFF),
+            _ => None,
+        }
+    }
+}
+
+/// The ways that lines can be ended
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Returns the line ending with a given name (as used by `:set newlines=<name>`), or [`None`]
+    /// if there is no such line ending
+    pub fn from_name(name: &str) -> Option<LineEnding> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Some(LineEnding::Lf),
+            "crlf" | "dos" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of this line ending, as shown in the bottom bar
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+        }
+    }
+
+    /// Returns the line ending used by `text`, which is decided by its first line break.  Text
+    /// without line breaks is assumed to use `\n`.
+    pub fn detect(text: &str) -> LineEnding {
+        match text.find('\n') {
+            Some(i) if text[..i].ends_with('\r') => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+}
+
+/// Everything about how the text of a file is stored, other than the text itself
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct FileFormat {
+    pub encoding: Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl FileFormat {
+    /// Detects the encoding of a file from its first bytes, returning the format (assuming `\n`
+    /// line endings until some text has been decoded) and the length of the byte order mark
+    /// which should be skipped.  Files without a byte order mark are UTF-16 if their first
+    /// character is ASCII padded with a zero byte, UTF-8 if they're valid UTF-8, and Latin-1
+    /// otherwise.
+    pub fn detect(start: &[u8]) -> (FileFormat, usize) {
+        let with_bom = |encoding: Encoding| {
+            let format = FileFormat {
+                encoding,
+                bom: true,
+                line_ending: LineEnding::Lf,
+            };
+            (format, encoding.bom().len())
+        };
+        if start.starts_with(UTF8_BOM) {
+            return with_bom(Encoding::Utf8);
+        }
+        if start.starts_with(UTF16_LE_BOM) {
+            return with_bom(Encoding::Utf16Le);
+        }
+        if start.starts_with(UTF16_BE_BOM) {
+            return with_bom(Encoding::Utf16Be);
+        }
+        let encoding = match start {
+            [c, 0, ..] if c.is_ascii() && *c != 0 => Encoding::Utf16Le,
+            [0, c, ..] if c.is_ascii() && *c != 0 => Encoding::Utf16Be,
+            _ => match std::str::from_utf8(start) {
+                Ok(_) => Encoding::Utf8,
+                // The start may end part-way through a character
+                Err(e) if e.error_len().is_none() => Encoding::Utf8,
+                Err(_) => Encoding::Latin1,
+            },
+        };
+        let format = FileFormat {
+            encoding,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        };
+        (format, 0)
+    }
+
+    /// Encodes text (which uses `\n` line endings) in this format, ready to be written to a file
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        if let Some(c) = self.encoding.unencodable_char(text) {
+            return Err(c);
+        }
+        let text = match self.line_ending {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::CrLf => text.replace('\n', "\r\n"),
+        };
+        let mut bytes = Vec::with_capacity(text.len());
+        if self.bom {
+            bytes.extend_from_slice(self.encoding.bom());
+        }
+        match self.encoding {
+            Encoding::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+            Encoding::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Encoding::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+            Encoding::Latin1 => bytes.extend(text.chars().map(|c| c as u8)),
+        }
+        Ok(bytes)
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let bom = if self.bom { " bom" } else { "" };
+        write!(
+            f,
+            "{}{} {}",
+            self.encoding.name(),
+            bom,
+            self.line_ending.name()
+        )
+    }
+}
+
+/// Decodes the bytes of a file a chunk at a time, keeping any bytes which end part-way through a
+/// character until the next chunk arrives
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    encoding: Encoding,
+    undecoded: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Decoder {
+        Decoder {
+            encoding,
+            undecoded: Vec::new(),
+        }
+    }
+
+    /// Decodes as much of the bytes received so far as possible
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<String, String> {
+        self.undecoded.extend_from_slice(bytes);
+        let (text, used) = match self.encoding {
+            Encoding::Utf8 => {
+                let valid_len = match std::str::from_utf8(&self.undecoded) {
+                    Ok(text) => text.len(),
+                    Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                    Err(_) => return Err(self.invalid()),
+                };
+                let text = std::str::from_utf8(&self.undecoded[..valid_len]).expect("checked");
+                (text.to_string(), valid_len)
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut units: Vec<u16> = self
+                    .undecoded
+                    .chunks_exact(2)
+                    .map(|pair| match self.encoding {
+                        Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                // A leading surrogate needs the next unit, which may be in the next chunk
+                if units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                    units.pop();
+                }
+                let text = char::decode_utf16(units.iter().copied())
+                    .collect::<Result<String, _>>()
+                    .map_err(|_| self.invalid())?;
+                (text, units.len() * 2)
+            }
+            Encoding::Latin1 => {
+                let text = self.undecoded.iter().map(|b| *b as char).collect();
+                (text, self.undecoded.len())
+            }
+        };
+        self.undecoded.drain(..used);
+        Ok(text)
+    }
+
+    /// Checks that the file didn't end part-way through a character
+    pub fn finish(&self) -> Result<(), String> {
+        if self.undecoded.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid())
+        }
+    }
+
+    /// Returns the error for bytes which aren't valid in this decoder's encoding
+    fn invalid(&self) -> String {
+        format!("the file is not valid {}", self.encoding.name())
+    }
+}
+
+/// Decodes the whole of a file, detecting its format from its first bytes
+pub fn decode(bytes: &[u8]) -> Result<(String, FileFormat), String> {
+    let (mut format, bom_len) = FileFormat::detect(bytes);
+    let mut decoder = Decoder::new(format.encoding);
+    let text = decoder.decode(&bytes[bom_len..])?;
+    decoder.finish()?;
+    format.line_ending = LineEnding::detect(&text);
+    Ok((text, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Encoding, FileFormat, LineEnding};
+
+    /// Encodes `text` in a format and decodes it again a byte at a time
+    fn round_trip(format: FileFormat, text: &str) -> (FileFormat, String) {
+        let bytes = format.encode(text).unwrap();
+        let (detected, bom_len) = FileFormat::detect(&bytes);
+        let mut decoder = Decoder::new(detected.encoding);
+        let mut decoded = String::new();
+        for byte in &bytes[bom_len..] {
+            decoded.push_str(&decoder.decode(&[*byte]).unwrap());
+        }
+        decoder.finish().unwrap();
+        let detected = FileFormat {
+            line_ending: LineEnding::detect(&decoded),
+            ..detected
+        };
+        (detected, decoded.replace("\r\n", "\n"))
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = "[\"é𝄞\",\n  true]\n";
+        for &encoding in &[Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            for &bom in &[false, true] {
+                for &line_ending in &[LineEnding::Lf, LineEnding::CrLf] {
+                    let format = FileFormat {
+                        encoding,
+                        bom,
+                        line_ending,
+                    };
+                    assert_eq!(round_trip(format, text), (format, text.to_string()));
+                }
+            }
+        }
+        let latin1 = FileFormat {
+            encoding: Encoding::Latin1,
+            ..FileFormat::default()
+        };
+        assert_eq!(latin1.encode("𝄞"), Err('𝄞'));
+        assert_eq!(round_trip(latin1, "\"é\""), (latin1, "\"é\"".to_string()));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut decoder = Decoder::new(Encoding::Utf8);
+        assert!(decoder.decode(&[b'"', 0xC3]).is_ok());
+        assert!(decoder.finish().is_err());
+        assert!(decoder.decode(b"\"").is_err());
+        let mut decoder = Decoder::new(Encoding::Utf16Le);
+        assert!(decoder.decode(&[0x00, 0xDC]).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! A stream of machine-readable events describing every edit, so that external tools (like
+//! live-reload servers) can react to edits without polling the file.  Each event is written as a
+//! single line of JSON, e.g. `{"operation":"replace","path":[2,0],"kind":"true"}`.
+
+use crate::editable_tree::cursor_path::CursorPath;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The kinds of edit which are reported as events
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operation {
+    Replace,
+    Insert,
+    Paste,
+    Cut,
+    Undo,
+    Redo,
+    Slurp,
+    Barf,
+    Transpose,
+    Join,
+    Sort,
+    Group,
+    Rename,
+    Wrap,
+    Unwrap,
+    Raise,
+    Convert,
+    LanguageCommand,
+}
+
+impl Operation {
+    /// The name of this operation in the events stream
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Replace => "replace",
+            Operation::Insert => "insert",
+            Operation::Paste => "paste",
+            Operation::Cut => "cut",
+            Operation::Undo => "undo",
+            Operation::Redo => "redo",
+            Operation::Slurp => "slurp",
+            Operation::Barf => "barf",
+            Operation::Transpose => "transpose",
+            Operation::Join => "join",
+            Operation::Sort => "sort",
+            Operation::Group => "group",
+            Operation::Rename => "rename",
+            Operation::Wrap => "wrap",
+            Operation::Unwrap => "unwrap",
+            Operation::Raise => "raise",
+            Operation::Convert => "convert",
+            Operation::LanguageCommand => "language-command",
+        }
+    }
+}
+
+/// A single edit, as reported to external tools
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EditEvent {
+    /// What kind of edit happened
+    pub operation: Operation,
+    /// The path to the node under the cursor after the edit
+    pub path: CursorPath,
+    /// The [`kind_name`](crate::ast::Ast::kind_name) of the node under the cursor after the edit
+    pub kind: String,
+}
+
+impl EditEvent {
+    /// Returns this event as a single line of JSON (without the trailing newline)
+    pub fn to_json(&self) -> String {
+        let path: Vec<String> = self.path.iter().map(usize::to_string).collect();
+        format!(
+            r#"{{"operation":"{}","path":[{}],"kind":"{}"}}"#,
+            self.operation.name(),
+            path.join(","),
+            escape_json(&self.kind)
+        )
+    }
+}
+
+/// Escapes a string so that it can be put between quotes in JSON
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Somewhere that [`EditEvent`]s are written to
+pub struct EventSink {
+    target: Target,
+}
+
+/// What an [`EventSink`] writes its events to
+enum Target {
+    /// Any [`Write`]r, which every event is written to
+    Writer(Box<dyn Write>),
+    /// The named pipe at `path`, which is only open (as `pipe`) whilst something is reading it
+    #[cfg(unix)]
+    Fifo { path: PathBuf, pipe: Option<File> },
+}
+
+impl EventSink {
+    /// Creates an `EventSink` which writes to the file, named pipe or Unix socket at `path`.  Files
+    /// are appended to, and are created if they don't exist.  Events sent to a named pipe whilst
+    /// nothing is reading it are dropped (see [`send_to_fifo`]).
+    pub fn open(path: &Path) -> std::io::Result<EventSink> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            let file_type = std::fs::metadata(path).map(|m| m.file_type());
+            if file_type.as_ref().is_ok_and(|t| t.is_socket()) {
+                let stream = std::os::unix::net::UnixStream::connect(path)?;
+                return Ok(EventSink::new(Box::new(stream)));
+            }
+            if file_type.is_ok_and(|t| t.is_fifo()) {
+                let target = Target::Fifo {
+                    path: path.to_path_buf(),
+                    pipe: None,
+                };
+                return Ok(EventSink { target });
+            }
+        }
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(EventSink::new(Box::new(file)))
+    }
+
+    /// Creates an `EventSink` which writes to any [`Write`]r
+    pub fn new(writer: Box<dyn Write>) -> EventSink {
+        EventSink {
+            target: Target::Writer(writer),
+        }
+    }
+
+    /// Writes a single event to the sink
+    pub fn send(&mut self, event: &EditEvent) -> std::io::Result<()> {
+        let line = format!("{}\n", event.to_json());
+        match &mut self.target {
+            Target::Writer(writer) => {
+                writer.write_all(line.as_bytes())?;
+                writer.flush()
+            }
+            #[cfg(unix)]
+            Target::Fifo { path, pipe } => send_to_fifo(path, pipe, line.as_bytes()),
+        }
+    }
+}
+
+/// Writes `bytes` to the named pipe at `path`, first opening it as `pipe` if it isn't open.  The
+/// pipe is opened and written without blocking, so that a missing or slow reader can't freeze the
+/// editor: if nothing has the other end open, or the reader has fallen so far behind that the pipe
+/// is full, then the bytes are dropped.  If the reader closes its end, the pipe is opened again
+/// for the next event (so that a new reader gets events from then on).
+#[cfg(unix)]
+fn send_to_fifo(path: &Path, pipe: &mut Option<File>, bytes: &[u8]) -> std::io::Result<()> {
+    use nix::libc::{ENXIO, O_NONBLOCK};
+    use std::io::ErrorKind;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = match pipe {
+        Some(file) => file,
+        None => match OpenOptions::new()
+            .write(true)
+            .custom_flags(O_NONBLOCK)
+            .open(path)
+        {
+            Ok(file) => pipe.insert(file),
+            // Opening a pipe for writing without blocking fails if nothing is reading it
+            Err(e) if e.raw_os_error() == Some(ENXIO) => return Ok(()),
+            Err(e) => return Err(e),
+        },
+    };
+    match file.write_all(bytes) {
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+            *pipe = None;
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSink")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditEvent, EventSink, Operation};
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn to_json() {
+        let event = EditEvent {
+            operation: Operation::Replace,
+            path: CursorPath::from_vec(vec![2, 0]),
+            kind: "true".to_string(),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"operation":"replace","path":[2,0],"kind":"true"}"#
+        );
+        let event = EditEvent {
+            operation: Operation::Undo,
+            path: CursorPath::root(),
+            kind: "a \"kind\"\n".to_string(),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"operation":"undo","path":[],"kind":"a \"kind\"\u000a"}"#
+        );
+    }
+
+    #[test]
+    fn file_sink() {
+        let path = std::env::temp_dir().join(format!("sapling-events-test-{}", std::process::id()));
+        let mut sink = EventSink::open(&path).unwrap();
+        for operation in &[Operation::Paste, Operation::Redo] {
+            sink.send(&EditEvent {
+                operation: *operation,
+                path: CursorPath::from_vec(vec![1]),
+                kind: "array".to_string(),
+            })
+            .unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            concat!(
+                r#"{"operation":"paste","path":[1],"kind":"array"}"#,
+                "\n",
+                r#"{"operation":"redo","path":[1],"kind":"array"}"#,
+                "\n"
+            )
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fifo_sink() {
+        use nix::libc::O_NONBLOCK;
+        use nix::sys::stat::Mode;
+        use std::io::Read;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = std::env::temp_dir().join(format!("sapling-events-fifo-{}", std::process::id()));
+        nix::unistd::mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR).unwrap();
+        let event = |operation| EditEvent {
+            operation,
+            path: CursorPath::root(),
+            kind: "true".to_string(),
+        };
+        // The sink still has its end open, so only what has been written so far can be read
+        let read = |reader: &mut std::fs::File| {
+            let mut bytes = [0; 256];
+            let len = reader.read(&mut bytes).unwrap();
+            String::from_utf8(bytes[..len].to_vec()).unwrap()
+        };
+        let open_reader = || {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(O_NONBLOCK)
+                .open(&path)
+                .unwrap()
+        };
+        // Events sent whilst nothing is reading the pipe are dropped without blocking
+        let mut sink = EventSink::open(&path).unwrap();
+        sink.send(&event(Operation::Cut)).unwrap();
+        // Events are read by a reader which opens the pipe later, until it closes its end
+        let mut reader = open_reader();
+        sink.send(&event(Operation::Paste)).unwrap();
+        assert_eq!(
+            read(&mut reader),
+            "{\"operation\":\"paste\",\"path\":[],\"kind\":\"true\"}\n"
+        );
+        drop(reader);
+        sink.send(&event(Operation::Undo)).unwrap();
+        // ... after which the pipe is opened again for the next reader
+        let mut reader = open_reader();
+        sink.send(&event(Operation::Redo)).unwrap();
+        assert_eq!(
+            read(&mut reader),
+            "{\"operation\":\"redo\",\"path\":[],\"kind\":\"true\"}\n"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! Folded subtrees, which are rendered as a one-line placeholder until they're unfolded.  Each
+//! fold remembers the node it folded as well as its path, so that when an edit moves the node
+//! (e.g. by inserting a sibling before it), the fold can follow it.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+
+/// The folded nodes of a tree
+#[derive(Debug, Clone)]
+pub struct Folds<'arena, Node> {
+    /// The path to each folded node, along with the node which was at that path when it was
+    /// folded (or when the folds were last [adjusted](Folds::adjust))
+    folds: Vec<(CursorPath, &'arena Node)>,
+}
+
+impl<'arena, Node: Ast<'arena>> Folds<'arena, Node> {
+    /// Creates an empty set of folds
+    pub fn new() -> Folds<'arena, Node> {
+        Folds { folds: Vec::new() }
+    }
+
+    /// Folds `node` (which is at `path`) if it isn't folded, or unfolds it if it is.  Returns
+    /// `true` if the node is now folded.
+    pub fn toggle(&mut self, path: &CursorPath, node: &'arena Node) -> bool {
+        let len = self.folds.len();
+        self.folds.retain(|(p, _)| p != path);
+        if self.folds.len() < len {
+            return false;
+        }
+        self.folds.push((path.clone(), node));
+        true
+    }
+
+    /// Folds `node` (which is at `path`) if it isn't already folded.  Returns `true` if it wasn't
+    /// folded before.
+    pub fn fold(&mut self, path: &CursorPath, node: &'arena Node) -> bool {
+        if self.folds.iter().any(|(p, _)| p == path) {
+            return false;
+        }
+        self.folds.push((path.clone(), node));
+        true
+    }
+
+    /// Unfolds every node, returning how many were folded
+    pub fn clear(&mut self) -> usize {
+        let len = self.folds.len();
+        self.folds.clear();
+        len
+    }
+
+    /// Returns `true` if nothing is folded
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    /// Returns the nodes which are folded
+    pub fn nodes(&self) -> Vec<&'arena Node> {
+        self.folds.iter().map(|(_, node)| *node).collect()
+    }
+
+    /// Updates the folds after the tree has been edited at `edit_path` so that its root is now
+    /// `root`.  Nodes which were moved by the edit stay folded at their new paths, and folds whose
+    /// nodes are no longer in the tree (because the edit was inside them, or removed them) are
+    /// unfolded.
+    pub fn adjust(&mut self, root: &'arena Node, edit_path: &CursorPath) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let is_unchanged = |(path, node): &(CursorPath, &'arena Node)| {
+            path.is_valid(root) && std::ptr::eq(path.cursor(root), *node)
+        };
+        if self.folds.iter().all(is_unchanged) {
+            return;
+        }
+        let mut adjusted = Vec::new();
+        for fold in self.folds.drain(..) {
+            if is_unchanged(&fold) {
+                adjusted.push(fold);
+            } else if let Some(path) = cursor_path::find_moved(root, &fold.0, fold.1, edit_path) {
+                adjusted.push((path, fold.1));
+            }
+        }
+        self.folds = adjusted;
+    }
+}
+
+impl<'arena, Node: Ast<'arena>> Default for Folds<'arena, Node> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Folds;
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn adjusting() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::False]),
+        ])
+        .add_to_arena(&arena);
+        let (first, second) = match root {
+            JSON::Array(children) => (children[0], children[1]),
+            _ => unreachable!(),
+        };
+        let path = |indices: &[usize]| CursorPath::from_vec(indices.to_vec());
+        let mut folds = Folds::new();
+        assert!(folds.toggle(&path(&[0]), first));
+        assert!(folds.toggle(&path(&[1]), second));
+        assert!(!folds.toggle(&path(&[0]), first));
+        assert!(folds.toggle(&path(&[0]), first));
+
+        // Inserting a node before the folded nodes moves their folds along
+        let new_root = arena.alloc(JSON::Array(vec![arena.alloc(JSON::True), first, second]));
+        folds.adjust(new_root, &path(&[0]));
+        assert!(!folds.toggle(&path(&[2]), second));
+        assert!(folds.toggle(&path(&[2]), second));
+        // Folds of nodes which aren't in the tree any more are dropped
+        let new_root = arena.alloc(JSON::Array(vec![second]));
+        folds.adjust(new_root, &path(&[0]));
+        assert_eq!(folds.nodes(), vec![second]);
+        assert!(!folds.toggle(&path(&[0]), second));
+        assert_eq!(folds.clear(), 0);
+        // Folding a node twice leaves it folded
+        assert!(folds.fold(&path(&[0]), second));
+        assert!(!folds.fold(&path(&[0]), second));
+        assert_eq!(folds.nodes(), vec![second]);
+        // Wrapping a folded node moves its fold one level down
+        let new_root = arena.alloc(JSON::Array(vec![arena.alloc(JSON::Array(vec![second]))]));
+        folds.adjust(new_root, &path(&[0]));
+        assert!(!folds.toggle(&path(&[0, 0]), second));
+    }
+
+    #[test]
+    fn adjusting_only_searches_near_the_edit() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::Array(vec![TestJSON::False])]),
+        ])
+        .add_to_arena(&arena);
+        let (first, second) = match root {
+            JSON::Array(children) => (children[0], children[1]),
+            _ => unreachable!(),
+        };
+        let inner = second.children()[0];
+        let path = |indices: &[usize]| CursorPath::from_vec(indices.to_vec());
+        let mut folds = Folds::new();
+        folds.fold(&path(&[1, 0]), inner);
+        // Inserting before the folded node inside its parent finds the node at its new path
+        let new_second = arena.alloc(JSON::Array(vec![arena.alloc(JSON::False), inner]));
+        let new_root = arena.alloc(JSON::Array(vec![first, new_second]));
+        folds.adjust(new_root, &path(&[1, 0]));
+        assert!(!folds.toggle(&path(&[1, 1]), inner));
+        // A node which has moved further than an edit could move it isn't searched for
+        let wrapped = arena.alloc(JSON::Array(
+            vec![arena.alloc(JSON::Array(vec![new_second]))],
+        ));
+        let new_root = arena.alloc(JSON::Array(vec![first, wrapped]));
+        folds.adjust(new_root, &path(&[0, 0]));
+        assert!(folds.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! Limiting how often the screen is redrawn.  Rather than redrawing after every event, the
+//! mainloop asks for a frame and the frame is only drawn once enough time has passed since the
+//! last one.  Any keys which arrive in the meantime are handled straight away, so holding down a
+//! key (or pasting a long line command) on a large tree doesn't wait for a redraw per key.
+
+use std::time::{Duration, Instant};
+
+/// How many frames are drawn per second at most, unless the config says otherwise
+pub const DEFAULT_MAX_FPS: u32 = 60;
+
+/// Keeps track of when the last frame was drawn, and whether a new one is wanted
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    /// The shortest time between two frames
+    interval: Duration,
+    /// When the last frame was drawn, or [`None`] if no frame has been drawn yet
+    last_frame: Option<Instant>,
+    /// `true` if something has changed since the last frame was drawn
+    is_requested: bool,
+}
+
+impl FrameLimiter {
+    /// Creates a limiter which draws at most `max_fps` frames per second (and always at least
+    /// one)
+    pub fn new(max_fps: u32) -> FrameLimiter {
+        FrameLimiter {
+            interval: Duration::from_secs(1) / max_fps.max(1),
+            last_frame: None,
+            is_requested: false,
+        }
+    }
+
+    /// Asks for a frame to be drawn, as soon as the limit allows
+    pub fn request(&mut self) {
+        self.is_requested = true;
+    }
+
+    /// Returns how long until the requested frame can be drawn, or [`None`] if no frame has been
+    /// requested
+    pub fn time_until_due(&self, now: Instant) -> Option<Duration> {
+        if !self.is_requested {
+            return None;
+        }
+        Some(match self.last_frame {
+            Some(last_frame) => (last_frame + self.interval).saturating_duration_since(now),
+            None => Duration::from_secs(0),
+        })
+    }
+
+    /// Returns `true` if a frame has been requested and can be drawn now
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.time_until_due(now) == Some(Duration::from_secs(0))
+    }
+
+    /// Records that a frame has just been drawn
+    pub fn drawn(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+        self.is_requested = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameLimiter;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn limits_frames() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut frames = FrameLimiter::new(20);
+        assert_eq!(frames.time_until_due(start), None);
+        // The first frame is drawn straight away
+        frames.request();
+        assert!(frames.is_due(start));
+        frames.drawn(start);
+        assert!(!frames.is_due(at(10)));
+        // Requests before the next frame is due are drawn together, once it's due
+        frames.request();
+        frames.request();
+        assert_eq!(
+            frames.time_until_due(at(10)),
+            Some(Duration::from_millis(40))
+        );
+        assert!(frames.is_due(at(50)));
+        frames.drawn(at(50));
+        assert_eq!(frames.time_until_due(at(200)), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! The history view, which lists every state in the undo history (along with how long ago it was
+//! made and what changed) so that the user can check one of them out.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path;
+use std::time::Duration;
+
+/// The state of the open history view
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HistoryView {
+    /// The index of the selected history entry
+    selected: usize,
+}
+
+impl HistoryView {
+    /// Opens the history view with a given entry (normally the current one) selected
+    pub fn new(selected: usize) -> HistoryView {
+        HistoryView { selected }
+    }
+
+    /// Returns the index of the selected history entry
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects the next (newer) entry, or the previous (older) one if `forward` is `false`.  The
+    /// selection stops at either end of the history, which has `len` entries.
+    pub fn select(&mut self, forward: bool, len: usize) {
+        self.selected = if forward {
+            (self.selected + 1).min(len.saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+}
+
+/// Returns a one-line summary of the difference between two trees, which is the path to the
+/// smallest subtree containing every change along with that subtree before and after the change
+/// (e.g. `0.2: true -> false`).  Returns [`None`] if the trees are the same.  The trees are
+/// compared by [`cursor_path::changed_subtree`], so `new_root` should have been made by editing
+/// `old_root` (as the trees in the undo history are).
+pub fn change_summary<'arena, Node: Ast<'arena>>(
+    old_root: &'arena Node,
+    new_root: &'arena Node,
+) -> Option<String> {
+    let path = cursor_path::changed_subtree(old_root, new_root)?;
+    let (old, new) = (path.cursor(old_root), path.cursor(new_root));
+    Some(format!("{}: {} -> {}", path, old.summary(), new.summary()))
+}
+
+/// Formats how long ago something happened in the most appropriate unit (e.g. `5s` or `3m`)
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{change_summary, format_age, HistoryView};
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::restructure;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use std::time::Duration;
+
+    #[test]
+    fn summaries() {
+        let arena = Arena::new();
+        let tree = |last: TestJSON| {
+            TestJSON::Array(vec![
+                TestJSON::True,
+                TestJSON::Object(vec![("a".to_string(), last)]),
+            ])
+            .add_to_arena(&arena)
+        };
+        let old = tree(TestJSON::True);
+        assert_eq!(change_summary(old, old), None);
+        // Like the trees in the undo history, the edited tree shares the nodes it didn't change
+        let edited = restructure::replace_each(
+            &arena,
+            old,
+            vec![(CursorPath::from_vec(vec![1, 0, 1]), JSON::False)],
+        )
+        .unwrap();
+        assert_eq!(
+            change_summary(old, arena.alloc(edited)),
+            Some("1.0.1: true -> false".to_string())
+        );
+        let added = TestJSON::Array(vec![TestJSON::True]).add_to_arena(&arena);
+        assert_eq!(
+            change_summary(old, added),
+            Some("root: [ 2 items ] -> [ 1 item ]".to_string())
+        );
+    }
+
+    #[test]
+    fn select() {
+        let mut view = HistoryView::new(1);
+        view.select(true, 3);
+        view.select(true, 3);
+        assert_eq!(view.selected(), 2);
+        view.select(false, 3);
+        view.select(false, 3);
+        view.select(false, 3);
+        assert_eq!(view.selected(), 0);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(Duration::from_secs(5)), "5s");
+        assert_eq!(format_age(Duration::from_secs(150)), "2m");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_age(Duration::from_secs(200_000)), "2d");
+    }
+}
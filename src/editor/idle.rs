@@ -0,0 +1,105 @@
+//! Scheduling of expensive work (like re-evaluating the watched expressions) for when the user
+//! has stopped typing, so that the work never slows down a key press.
+//!
+//! Nodes are never freed from the arena until Sapling closes (the undo history refers to them),
+//! so there is no garbage collection to schedule.
+
+use std::time::{Duration, Instant};
+
+/// How long the user has to be idle before scheduled work is done, unless the config says
+/// otherwise
+pub const DEFAULT_IDLE_DELAY: Duration = Duration::from_millis(500);
+
+/// A piece of expensive work which waits until the user is idle
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IdleTask {
+    /// Re-evaluate every watched expression against the current tree
+    UpdateWatches,
+    /// Recompute the text of every pinned node
+    UpdatePins,
+    /// Bring the search index up to date with the current tree (or build it, if the tree has
+    /// become large enough to need one)
+    RefreshSearchIndex,
+    /// Count the parts of the tree which aren't valid, and tell the user if the count changed
+    Validate,
+    /// Write the tree to its file if it has unsaved changes (and autosaving is turned on)
+    Autosave,
+}
+
+/// Keeps track of when the user last did something, and which work is waiting for them to stop
+#[derive(Debug, Clone)]
+pub struct IdleScheduler {
+    delay: Duration,
+    last_input: Instant,
+    /// The tasks waiting to be done, in the order that they were first scheduled
+    pending: Vec<IdleTask>,
+}
+
+impl IdleScheduler {
+    /// Creates a scheduler with nothing scheduled, which considers the user idle once they have
+    /// done nothing for `delay`
+    pub fn new(delay: Duration, now: Instant) -> IdleScheduler {
+        IdleScheduler {
+            delay,
+            last_input: now,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Records that the user has just done something, which postpones any scheduled work
+    pub fn note_input(&mut self, now: Instant) {
+        self.last_input = now;
+    }
+
+    /// Schedules a task for the next time the user is idle.  Scheduling a task that is already
+    /// waiting does nothing, so it is only done once.
+    pub fn schedule(&mut self, task: IdleTask) {
+        if !self.pending.contains(&task) {
+            self.pending.push(task);
+        }
+    }
+
+    /// Returns how long until the scheduled work is due, or [`None`] if nothing is scheduled
+    pub fn time_until_idle(&self, now: Instant) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some((self.last_input + self.delay).saturating_duration_since(now))
+    }
+
+    /// Returns (and stops waiting on) every scheduled task, if the user has been idle for long
+    /// enough.  Otherwise, nothing is returned.
+    pub fn take_due(&mut self, now: Instant) -> Vec<IdleTask> {
+        if self.time_until_idle(now) == Some(Duration::from_secs(0)) {
+            std::mem::take(&mut self.pending)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdleScheduler, IdleTask};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn waits_for_idle() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut scheduler = IdleScheduler::new(ms(500), start);
+        assert_eq!(scheduler.time_until_idle(start), None);
+        scheduler.schedule(IdleTask::UpdateWatches);
+        scheduler.schedule(IdleTask::UpdateWatches);
+        assert_eq!(scheduler.time_until_idle(start + ms(200)), Some(ms(300)));
+        // Typing postpones the work
+        scheduler.note_input(start + ms(400));
+        assert!(scheduler.take_due(start + ms(600)).is_empty());
+        assert_eq!(
+            scheduler.take_due(start + ms(900)),
+            vec![IdleTask::UpdateWatches]
+        );
+        assert!(scheduler.take_due(start + ms(2000)).is_empty());
+        assert_eq!(scheduler.time_until_idle(start + ms(2000)), None);
+    }
+}
@@ -0,0 +1,210 @@
+//! The session journal, which records every action performed in a session along with when it was
+//! performed.  Unlike the undo history, the journal keeps every action (including motions and
+//! actions which don't edit the tree) and lasts after Sapling closes, so it can be used to
+//! reproduce bugs, to turn real sessions into regression tests, or to work out how to recover
+//! from a mistake which is beyond the undo limit.
+//!
+//! Each session has its own journal file.  The first line says when the session started (in
+//! seconds since the Unix epoch), and each line after that is the number of seconds between the
+//! start of the session and an action, followed by a tab and the action itself, e.g.
+//! `12.345\tReplace('t')`.
+//!
+//! Journals are only kept if the user turns them on in their config file, and only the journals
+//! of the latest few sessions are kept.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// The number of entries shown by `:journal`
+pub const SHOWN_ENTRIES: usize = 20;
+
+/// The number of sessions whose journals are kept when the config doesn't say
+pub const DEFAULT_KEPT_SESSIONS: usize = 20;
+
+/// Returns the directory that journals are kept in by default, which is the `journal` directory
+/// of Sapling's [data directory](super::config::data_dir).  Returns [`None`] if the data
+/// directory isn't known.
+pub fn default_dir() -> Option<PathBuf> {
+    Some(super::config::data_dir()?.join("journal"))
+}
+
+/// Returns the path of a new journal file for this session in `dir`, which is named after the
+/// time the session started and the ID of the process
+pub fn session_path(dir: &Path) -> PathBuf {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    dir.join(format!("{}-{}.log", started, std::process::id()))
+}
+
+/// Deletes the oldest journal files in `dir` until there are fewer than `keep` of them, to make
+/// room for the journal of a new session.  Files which don't look like journals are left alone.
+fn remove_old_journals(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut journals = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "log") {
+            let modified = path.metadata()?.modified()?;
+            journals.push((modified, path));
+        }
+    }
+    journals.sort();
+    let excess = (journals.len() + 1).saturating_sub(keep);
+    for (_, path) in journals.into_iter().take(excess) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// The journal of the current session
+#[derive(Debug)]
+pub struct Journal {
+    /// The file which entries are appended to, or [`None`] if the journal isn't being kept
+    path: Option<PathBuf>,
+    /// How many sessions' journals are kept in the journal's directory, including this one
+    keep: usize,
+    /// When the session started, which the times of the entries are relative to
+    start: Instant,
+    /// The open journal file, once the first line has been written
+    file: Option<File>,
+}
+
+impl Journal {
+    /// Creates a journal which will be written to the file at `path` (creating the file and its
+    /// directory when the first action is recorded), or which isn't kept if `path` is [`None`].
+    /// When the file is created, the oldest journals in its directory are deleted so that only
+    /// `keep` sessions' journals are left.
+    pub fn new(path: Option<PathBuf>, keep: usize, start: Instant) -> Journal {
+        Journal {
+            path,
+            keep,
+            start,
+            file: None,
+        }
+    }
+
+    /// Opens the journal file, after creating its directory and making room for it
+    fn open(path: &Path, keep: usize) -> std::io::Result<File> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+            remove_old_journals(dir, keep)?;
+        }
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Returns the path of the journal file, or [`None`] if the journal isn't being kept
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Appends an action, which was performed at `time`, to the journal.  If the journal can't
+    /// be written, then it stops being kept and an error saying why is returned.
+    pub fn record(&mut self, time: Instant, action: &str) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut text = String::new();
+        if self.file.is_none() {
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs())
+                .saturating_sub(time.duration_since(self.start).as_secs());
+            text.push_str(&format!("# Session started at {}\n", started));
+        }
+        let secs = time.duration_since(self.start).as_secs_f64();
+        text.push_str(&format!("{:.3}\t{}\n", secs, action));
+        let written = match &mut self.file {
+            Some(file) => file.write_all(text.as_bytes()),
+            None => Journal::open(path, self.keep).and_then(|mut file| {
+                file.write_all(text.as_bytes())?;
+                self.file = Some(file);
+                Ok(())
+            }),
+        };
+        written.map_err(|e| {
+            self.path = None;
+            self.file = None;
+            e.to_string()
+        })
+    }
+
+    /// Returns the last `count` entries of the journal, oldest first, or an error saying why the
+    /// journal can't be read.  A journal which isn't being kept has no entries.
+    pub fn last_entries(&self, count: usize) -> Result<Vec<String>, String> {
+        let path = match &self.path {
+            Some(path) if self.file.is_some() => path,
+            _ => return Ok(Vec::new()),
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let entries: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        let first = entries.len().saturating_sub(count);
+        Ok(entries[first..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{session_path, Journal};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn record() {
+        let dir = std::env::temp_dir().join(format!("sapling-journal-{}", std::process::id()));
+        let path = dir.join("session.log");
+        let start = Instant::now();
+        let mut journal = Journal::new(Some(path.clone()), 5, start);
+        assert_eq!(journal.last_entries(5), Ok(vec![]));
+        let at = |ms| start + Duration::from_millis(ms);
+        journal.record(at(1500), "MoveCursor(Down, 1)").unwrap();
+        journal.record(at(2250), "Replace('t')").unwrap();
+        journal.record(at(61_000), "Undo").unwrap();
+        assert_eq!(
+            journal.last_entries(2),
+            Ok(vec![
+                "2.250\tReplace('t')".to_string(),
+                "61.000\tUndo".to_string()
+            ])
+        );
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# Session started at "));
+        assert_eq!(text.lines().count(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A journal which can't be written (here because its directory is a file) stops being
+        // kept
+        std::fs::write(&dir, "").unwrap();
+        let mut journal = Journal::new(Some(path), 5, start);
+        assert!(journal.record(at(0), "Undo").is_err());
+        assert_eq!(journal.path(), None);
+        assert_eq!(journal.record(at(0), "Undo"), Ok(()));
+        std::fs::remove_file(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_latest_sessions_are_kept() {
+        let dir = std::env::temp_dir().join(format!("sapling-journals-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in &["1.log", "2.log", "3.log", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+            // Make sure the files have different modification times
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let mut journal = Journal::new(Some(session_path(&dir)), 2, Instant::now());
+        journal.record(Instant::now(), "Undo").unwrap();
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert!(names.iter().any(|name| name == "3.log"));
+        assert!(names.iter().any(|name| name == "notes.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
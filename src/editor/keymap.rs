@@ -0,0 +1,1280 @@
+//! Keymaps, and validation of key bindings so that mistakes in a user's keymap are reported when
+//! it is loaded rather than causing confusing behaviour later.
+//!
+//! A keymap can be loaded from the `[keymap]` table of a TOML config file, which maps each key to
+//! the name of a [`Command`] as it's written in warnings (e.g. `Undo` or `MoveCursor(Down)`):
+//!
+//! ```toml
+//! [keymap]
+//! q = "Quit"
+//! h = "MoveCursor(Prev)"
+//! ```
+//!
+//! The table replaces the whole default keymap, so it should bind every command.  It can also
+//! bind keys to commands which aren't in the default keymap, like `InsertAndEdit('s')` (which
+//! adds a string and starts typing its contents in one key press) or `LanguageCommand("sort-keys")`
+//! (which runs a command provided by the buffer's language).
+//!
+//! Keys other than characters, and chords with modifiers, are written in angle brackets like the
+//! keys of a macro, with `C-`, `A-` and `S-` for control, alt and shift (e.g. `"<PageDown>"`,
+//! `"<C-d>"` or `"<S-Up>"`).
+//!
+//! A command can also be bound to a sequence of keys (e.g. `"gg"` or `"<Space>w"`), so that
+//! related commands can share a mnemonic prefix.  The keymap is a prefix tree, so whilst the keys
+//! typed so far are the start of some longer binding the command stays incomplete.  A binding
+//! which is also the start of a longer one is only used when the next key doesn't continue the
+//! longer one, and that key is then passed to it as an argument, or once the user has stopped
+//! typing for longer than the command timeout (see [`DEFAULT_COMMAND_TIMEOUT`]).  In a sequence,
+//! `<` is written `<lt>`.
+//!
+//! Instead of starting from the default keymap, a config can start from one of the built-in
+//! [presets](PRESET_NAMES) by naming it with `keymap-preset = "vim"`.  The `[keymap]` table then
+//! only has to bind the keys which differ from the preset.  The active keymap can be written out
+//! in this form with `:keymap export <path>`, so that it can be shared and loaded by others with
+//! `:keymap import <path>` or by copying it into their config.
+
+use super::macros::KEY_NAMES;
+use super::{Command, ScrollStep};
+use crate::editable_tree::Direction;
+use crate::messages::Message;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::time::Duration;
+use tuikit::key::Key;
+
+/// How long the editor waits for the next key when the keys typed so far are both a binding and
+/// the start of a longer one, before running the shorter binding's command
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// The modifier keys held down during a [`KeyInput`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    /// No modifiers
+    pub const NONE: Modifiers = Modifiers {
+        ctrl: false,
+        alt: false,
+        shift: false,
+    };
+}
+
+/// A key press which can be bound to a command: a key along with the modifiers held down with it.
+/// [`Key`] has separate variants for many chords (e.g. `Key::CtrlUp` and `Key::Alt('x')`), which
+/// are split up into their key and [`Modifiers`] so that every chord has exactly one `KeyInput`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct KeyInput {
+    /// The key without any modifiers (e.g. `Key::Up` or `Key::Char('x')`)
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl KeyInput {
+    /// Returns the `char` typed by this key press, or [`None`] if it isn't a character typed
+    /// without modifiers
+    pub fn as_char(self) -> Option<char> {
+        match (self.key, self.modifiers) {
+            (Key::Char(c), Modifiers::NONE) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Parses a key as it's written in the keymap config: either a single character, or a key's
+    /// name in angle brackets after any modifiers (e.g. `<C-A-Left>`).  Returns [`None`] if the
+    /// text isn't a key.
+    pub fn parse(text: &str) -> Option<KeyInput> {
+        let mut chars = text.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(KeyInput::from(c));
+        }
+        let mut name = text.strip_prefix('<')?.strip_suffix('>')?;
+        let mut modifiers = Modifiers::NONE;
+        loop {
+            let flag = match name.get(..2) {
+                Some("C-") | Some("c-") => &mut modifiers.ctrl,
+                Some("A-") | Some("a-") => &mut modifiers.alt,
+                Some("S-") | Some("s-") => &mut modifiers.shift,
+                _ => break,
+            };
+            *flag = true;
+            name = &name[2..];
+        }
+        let mut chars = name.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => Key::Char(c),
+            _ => KEY_NAMES
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|(k, _)| *k)
+                .or_else(|| {
+                    let number = name.strip_prefix('F').or_else(|| name.strip_prefix('f'))?;
+                    number.parse::<u8>().ok().map(Key::F)
+                })?,
+        };
+        // Named keys which are characters (like `<Space>`) can have modifiers too
+        Some(KeyInput {
+            modifiers,
+            ..KeyInput::from(key)
+        })
+    }
+
+    /// Parses a sequence of keys as it's written in the keymap config, with each key written as
+    /// for [`KeyInput::parse`] (e.g. `gg` or `<Space>w`).  Returns [`None`] if the text isn't a
+    /// sequence of keys.
+    pub fn parse_sequence(text: &str) -> Option<KeySequence> {
+        // A lone `<` is the key itself rather than the start of a key's name
+        if text == "<" {
+            return Some(vec![KeyInput::from('<')]);
+        }
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                let end = rest.find('>')?;
+                keys.push(KeyInput::parse(&rest[..=end])?);
+                rest = &rest[end + 1..];
+            } else {
+                keys.push(KeyInput::from(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        Some(keys).filter(|keys| !keys.is_empty())
+    }
+}
+
+/// A sequence of key presses which can be bound to a command
+pub type KeySequence = Vec<KeyInput>;
+
+/// Writes a sequence of keys in the form that [`KeyInput::parse_sequence`] reads
+pub fn sequence_text(keys: &[KeyInput]) -> String {
+    keys.iter().map(KeyInput::to_string).collect()
+}
+
+impl From<char> for KeyInput {
+    fn from(c: char) -> Self {
+        KeyInput {
+            key: Key::Char(c),
+            modifiers: Modifiers::NONE,
+        }
+    }
+}
+
+impl From<Key> for KeyInput {
+    fn from(key: Key) -> Self {
+        let (ctrl, alt, shift) = (true, true, true);
+        let (key, modifiers) = match key {
+            Key::Ctrl(c) => (
+                Key::Char(c),
+                Modifiers {
+                    ctrl,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::CtrlAlt(c) => (
+                Key::Char(c),
+                Modifiers {
+                    ctrl,
+                    alt,
+                    shift: false,
+                },
+            ),
+            Key::Alt(c) => (
+                Key::Char(c),
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::BackTab => (
+                Key::Tab,
+                Modifiers {
+                    shift,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltBackTab => (
+                Key::Tab,
+                Modifiers {
+                    alt,
+                    shift,
+                    ctrl: false,
+                },
+            ),
+            Key::AltTab => (
+                Key::Tab,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltEnter => (
+                Key::Enter,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltBackspace => (
+                Key::Backspace,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::CtrlUp => (
+                Key::Up,
+                Modifiers {
+                    ctrl,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::CtrlDown => (
+                Key::Down,
+                Modifiers {
+                    ctrl,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::CtrlLeft => (
+                Key::Left,
+                Modifiers {
+                    ctrl,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::CtrlRight => (
+                Key::Right,
+                Modifiers {
+                    ctrl,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::ShiftUp => (
+                Key::Up,
+                Modifiers {
+                    shift,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::ShiftDown => (
+                Key::Down,
+                Modifiers {
+                    shift,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::ShiftLeft => (
+                Key::Left,
+                Modifiers {
+                    shift,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::ShiftRight => (
+                Key::Right,
+                Modifiers {
+                    shift,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltUp => (
+                Key::Up,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltDown => (
+                Key::Down,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltLeft => (
+                Key::Left,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltRight => (
+                Key::Right,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltHome => (
+                Key::Home,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltEnd => (
+                Key::End,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltPageUp => (
+                Key::PageUp,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltPageDown => (
+                Key::PageDown,
+                Modifiers {
+                    alt,
+                    ..Modifiers::NONE
+                },
+            ),
+            Key::AltShiftUp => (
+                Key::Up,
+                Modifiers {
+                    alt,
+                    shift,
+                    ctrl: false,
+                },
+            ),
+            Key::AltShiftDown => (
+                Key::Down,
+                Modifiers {
+                    alt,
+                    shift,
+                    ctrl: false,
+                },
+            ),
+            Key::AltShiftLeft => (
+                Key::Left,
+                Modifiers {
+                    alt,
+                    shift,
+                    ctrl: false,
+                },
+            ),
+            Key::AltShiftRight => (
+                Key::Right,
+                Modifiers {
+                    alt,
+                    shift,
+                    ctrl: false,
+                },
+            ),
+            key => (key, Modifiers::NONE),
+        };
+        KeyInput { key, modifiers }
+    }
+}
+
+impl std::fmt::Display for KeyInput {
+    /// Writes the key in the form that [`KeyInput::parse`] reads
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(c) = self.as_char() {
+            return write!(f, "{}", c);
+        }
+        write!(f, "<")?;
+        for (is_held, prefix) in &[
+            (self.modifiers.ctrl, "C-"),
+            (self.modifiers.alt, "A-"),
+            (self.modifiers.shift, "S-"),
+        ] {
+            if *is_held {
+                write!(f, "{}", prefix)?;
+            }
+        }
+        match (KEY_NAMES.iter().find(|(k, _)| *k == self.key), self.key) {
+            (Some((_, name)), _) => write!(f, "{}>", name),
+            (None, Key::Char(c)) => write!(f, "{}>", c),
+            (None, Key::F(n)) => write!(f, "F{}>", n),
+            (None, key) => write!(f, "{:?}>", key),
+        }
+    }
+}
+
+/// Mapping of key sequences to commands, stored as a prefix tree so that the command bound to
+/// the keys typed so far can be found one key at a time
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct KeyMap {
+    root: KeyNode,
+}
+
+/// A node of a [`KeyMap`]'s prefix tree, reached by typing the keys on the path to it
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+struct KeyNode {
+    /// The command bound to the keys which lead to this node, if there is one
+    command: Option<Command>,
+    children: HashMap<KeyInput, KeyNode>,
+}
+
+impl KeyNode {
+    /// Returns the number of commands bound to this node and the nodes below it
+    fn len(&self) -> usize {
+        let own = self.command.is_some() as usize;
+        own + self.children.values().map(KeyNode::len).sum::<usize>()
+    }
+}
+
+/// What typing one more key after the start of a longer binding leads to, as shown in the hints
+/// for an incomplete command
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Continuation {
+    /// The key completes the binding of a command
+    Command(Command),
+    /// The key is the start of even longer bindings, of some number of commands
+    Prefix(usize),
+}
+
+/// The result of looking up the keys typed so far in a [`KeyMap`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Lookup<'k> {
+    /// The keys are the start of a longer binding, so more keys are needed
+    Incomplete,
+    /// The first `usize` keys are bound to the command.  Any keys after them are its arguments.
+    Bound(&'k Command, usize),
+    /// The keys don't start with any binding
+    Unbound,
+}
+
+impl KeyMap {
+    /// Loads the keymap from the `[keymap]` table of `config`, the parsed TOML config file at
+    /// `path` (see [`read_config_file`](super::config::read_config_file)).  If it has no
+    /// `[keymap]` table, the [default keymap](super::default_keymap) is returned.  If the keymap
+    /// can't be loaded (e.g. because it names a command which doesn't exist) then the default
+    /// keymap is returned along with a warning to show the user.
+    ///
+    /// If `preset_name` is given (e.g. from the command line), that [preset](preset) is used
+    /// instead of the one named by the config.
+    pub fn from_config(
+        path: &Path,
+        config: &toml::Value,
+        preset_name: Option<&str>,
+    ) -> (KeyMap, Option<Message>) {
+        let fallback = || {
+            preset_name
+                .and_then(preset)
+                .unwrap_or_else(super::default_keymap)
+        };
+        match parse_keymap_table(config, preset_name) {
+            Ok(keymap) => (keymap, None),
+            Err(e) => (fallback(), Some(load_error(path, e))),
+        }
+    }
+
+    /// Loads the keymap from a TOML file written by [`KeyMap::to_config`] (or any other config
+    /// file), or returns the reason it couldn't be loaded
+    pub fn import(path: &Path) -> Result<KeyMap, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        parse_keymap(&text, None)
+    }
+
+    /// Writes every binding as the `[keymap]` table of a TOML config file, which
+    /// [`KeyMap::from_config`] loads back into the same keymap
+    pub fn to_config(&self) -> String {
+        let table: toml::value::Table = self
+            .bindings()
+            .into_iter()
+            .map(|(keys, command)| {
+                let name = toml::Value::String(format!("{:?}", command));
+                (sequence_text(&keys), name)
+            })
+            .collect();
+        let mut config = toml::value::Table::new();
+        config.insert("keymap".to_string(), toml::Value::Table(table));
+        toml::Value::Table(config).to_string()
+    }
+
+    /// Returns the [`Command`] bound to the single key press `key`, if there is one
+    pub fn get(&self, key: impl Into<KeyInput>) -> Option<&Command> {
+        self.root.children.get(&key.into())?.command.as_ref()
+    }
+
+    /// Returns `true` if `key` is bound to a [`Command`] or is the first key of a binding
+    pub fn contains_key(&self, key: impl Into<KeyInput>) -> bool {
+        self.root.children.contains_key(&key.into())
+    }
+
+    /// Finds the binding which the keys typed so far start with.  If more than one binding
+    /// matches, the longest is used.
+    pub fn lookup(&self, keys: &[KeyInput]) -> Lookup<'_> {
+        self.lookup_with(keys, false)
+    }
+
+    /// Like [`KeyMap::lookup`], but for once no more keys are coming (because the command timed
+    /// out), so keys which are a binding as well as the start of a longer one are bound instead of
+    /// incomplete
+    pub fn lookup_final(&self, keys: &[KeyInput]) -> Lookup<'_> {
+        self.lookup_with(keys, true)
+    }
+
+    /// Returns `true` if `keys` are both a binding and the start of a longer binding, so that
+    /// they only run their own command once the command times out
+    pub fn is_ambiguous(&self, keys: &[KeyInput]) -> bool {
+        matches!(self.lookup(keys), Lookup::Incomplete)
+            && matches!(self.lookup_final(keys), Lookup::Bound(_, len) if len == keys.len())
+    }
+
+    fn lookup_with(&self, keys: &[KeyInput], is_final: bool) -> Lookup<'_> {
+        let mut node = &self.root;
+        let mut bound = None;
+        for (i, key) in keys.iter().enumerate() {
+            node = match node.children.get(key) {
+                Some(child) => child,
+                None => break,
+            };
+            if let Some(command) = &node.command {
+                bound = Some((command, i + 1));
+            }
+            let is_ambiguous = is_final && node.command.is_some();
+            if i + 1 == keys.len() && !node.children.is_empty() && !is_ambiguous {
+                return Lookup::Incomplete;
+            }
+        }
+        match bound {
+            _ if keys.is_empty() => Lookup::Incomplete,
+            Some((command, len)) => Lookup::Bound(command, len),
+            None => Lookup::Unbound,
+        }
+    }
+
+    /// Returns every key which can be typed after `keys` to continue a binding, along with what it
+    /// leads to, in order of the keys' text.  Returns nothing if `keys` isn't the start of a
+    /// binding.
+    pub fn continuations(&self, keys: &[KeyInput]) -> Vec<(KeyInput, Continuation)> {
+        let mut node = &self.root;
+        for key in keys {
+            node = match node.children.get(key) {
+                Some(child) => child,
+                None => return Vec::new(),
+            };
+        }
+        let mut continuations: Vec<(KeyInput, Continuation)> = node
+            .children
+            .iter()
+            .map(|(key, child)| {
+                let continuation = match &child.command {
+                    Some(command) if child.children.is_empty() => {
+                        Continuation::Command(command.clone())
+                    }
+                    _ => Continuation::Prefix(child.len()),
+                };
+                (*key, continuation)
+            })
+            .collect();
+        continuations.sort_by_key(|(key, _)| key.to_string());
+        continuations
+    }
+
+    /// Binds `keys` to `command`, replacing any existing binding of the same keys
+    pub fn insert(&mut self, keys: &[KeyInput], command: Command) {
+        if keys.is_empty() {
+            return;
+        }
+        let mut node = &mut self.root;
+        for key in keys {
+            node = node.children.entry(*key).or_default();
+        }
+        node.command = Some(command);
+    }
+
+    /// Returns every binding, in no particular order
+    pub fn bindings(&self) -> Vec<(KeySequence, Command)> {
+        let mut bindings = Vec::new();
+        let mut stack = vec![(Vec::new(), &self.root)];
+        while let Some((keys, node)) = stack.pop() {
+            if let Some(command) = &node.command {
+                bindings.push((keys.clone(), command.clone()));
+            }
+            for (key, child) in &node.children {
+                let mut child_keys = keys.clone();
+                child_keys.push(*key);
+                stack.push((child_keys, child));
+            }
+        }
+        bindings
+    }
+
+    /// Returns the number of key sequences which are bound
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    /// Returns `true` if no keys are bound
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty()
+    }
+}
+
+impl FromIterator<(KeySequence, Command)> for KeyMap {
+    fn from_iter<I: IntoIterator<Item = (KeySequence, Command)>>(iter: I) -> Self {
+        let mut keymap = KeyMap::default();
+        keymap.extend(iter);
+        keymap
+    }
+}
+
+impl Extend<(KeySequence, Command)> for KeyMap {
+    fn extend<I: IntoIterator<Item = (KeySequence, Command)>>(&mut self, iter: I) {
+        for (keys, command) in iter {
+            self.insert(&keys, command);
+        }
+    }
+}
+
+/// Returns the warning shown when the keymap in the config file at `path` can't be loaded
+fn load_error(path: &Path, reason: String) -> Message {
+    Message::CannotLoadKeymap(path.to_string_lossy().into_owned(), reason)
+}
+
+/// Parses the `[keymap]` table of a TOML config file into a list of bindings.  Returns
+/// `Ok(None)` if the config doesn't have a `[keymap]` table, or an error saying what is wrong
+/// with the config.
+pub fn parse_config(text: &str) -> Result<Option<Vec<(KeySequence, Command)>>, String> {
+    let config: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    parse_config_table(&config)
+}
+
+/// Like [`parse_config`], but for a config file which has already been parsed as TOML
+fn parse_config_table(config: &toml::Value) -> Result<Option<Vec<(KeySequence, Command)>>, String> {
+    match config.get("keymap") {
+        Some(toml::Value::Table(table)) => parse_bindings(table).map(Some),
+        Some(_) => Err("'keymap' isn't a table".to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Parses a TOML table mapping key sequences to command names (like the `[keymap]` table of a
+/// config file) into a list of bindings, or returns an error saying what is wrong with it
+pub fn parse_bindings(table: &toml::value::Table) -> Result<Vec<(KeySequence, Command)>, String> {
+    table
+        .iter()
+        .map(|(key, name)| {
+            let keys = KeyInput::parse_sequence(key)
+                .ok_or_else(|| format!("'{}' isn't a sequence of keys", key))?;
+            let name = name
+                .as_str()
+                .ok_or_else(|| format!("the command for '{}' isn't a string", key))?;
+            let command =
+                parse_command_name(name).ok_or_else(|| format!("'{}' isn't a command", name))?;
+            Ok((keys, command))
+        })
+        .collect()
+}
+
+/// Parses the keymap of a TOML config file: the [preset](preset) it names (or `preset_name`, if
+/// given) with the bindings of its `[keymap]` table on top.  Without a preset, the table replaces
+/// the whole default keymap.
+fn parse_keymap(text: &str, preset_name: Option<&str>) -> Result<KeyMap, String> {
+    let config: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    parse_keymap_table(&config, preset_name)
+}
+
+/// Like [`parse_keymap`], but for a config file which has already been parsed as TOML
+fn parse_keymap_table(config: &toml::Value, preset_name: Option<&str>) -> Result<KeyMap, String> {
+    let preset_name = match (preset_name, config.get("keymap-preset")) {
+        (Some(name), _) => Some(name),
+        (None, Some(toml::Value::String(name))) => Some(name.as_str()),
+        (None, Some(_)) => return Err("'keymap-preset' isn't a name".to_string()),
+        (None, None) => None,
+    };
+    let base = match preset_name {
+        Some(name) => {
+            Some(preset(name).ok_or_else(|| format!("'{}' isn't a keymap preset", name))?)
+        }
+        None => None,
+    };
+    Ok(match (base, parse_config_table(config)?) {
+        (Some(mut keymap), Some(bindings)) => {
+            keymap.extend(bindings);
+            keymap
+        }
+        (Some(keymap), None) => keymap,
+        (None, Some(bindings)) => bindings.into_iter().collect(),
+        (None, None) => super::default_keymap(),
+    })
+}
+
+/// The names of the built-in keymaps, which can be chosen with `keymap-preset` in the config file
+/// or with `--keymap` on the command line
+pub const PRESET_NAMES: [&str; 3] = ["default", "vim", "emacs"];
+
+/// The bindings which the `vim` preset adds to the default keymap, so that the cursor moves with
+/// `hjkl` and cutting and pasting work like they do in Vim
+const VIM_BINDINGS: &[(&str, Command)] = &[
+    ("h", Command::MoveCursor(Direction::Up)),
+    ("l", Command::MoveCursor(Direction::Down)),
+    ("x", Command::Cut),
+    ("p", Command::PasteAfter),
+    ("P", Command::Paste),
+    ("<C-r>", Command::Redo),
+    ("<C-d>", Command::ScrollView(ScrollStep::PageDown)),
+    ("<C-u>", Command::ScrollView(ScrollStep::PageUp)),
+];
+
+/// The bindings which the `emacs` preset adds to the default keymap, so that the common commands
+/// are on the same chords as in Emacs
+const EMACS_BINDINGS: &[(&str, Command)] = &[
+    ("<C-b>", Command::MoveCursor(Direction::Up)),
+    ("<C-f>", Command::MoveCursor(Direction::Down)),
+    ("<C-p>", Command::MoveCursor(Direction::Prev)),
+    ("<C-n>", Command::MoveCursor(Direction::Next)),
+    ("<C-x>u", Command::Undo),
+    ("<C-x>U", Command::Redo),
+    ("<C-s>", Command::Search),
+    ("<A-x>", Command::CommandPalette),
+    ("<A-w>", Command::Yank),
+    ("<C-w>", Command::Cut),
+    ("<C-y>", Command::Paste),
+    ("<C-x><C-s>", Command::Write),
+    ("<C-x><C-c>", Command::Quit),
+    ("<C-v>", Command::ScrollView(ScrollStep::PageDown)),
+    ("<A-v>", Command::ScrollView(ScrollStep::PageUp)),
+    // `<C-y>` pastes, so scrolling up by a line moves to the Alt version of `<C-e>`
+    ("<A-e>", Command::ScrollView(ScrollStep::LineUp)),
+];
+
+/// Returns the built-in keymap with a given name (one of [`PRESET_NAMES`]), or [`None`] if there
+/// isn't one.  Each preset is the default keymap with some extra bindings, so every command can
+/// still be reached by its default key unless the preset has taken that key for something else.
+pub fn preset(name: &str) -> Option<KeyMap> {
+    let bindings = match name {
+        "default" => &[][..],
+        "vim" => VIM_BINDINGS,
+        "emacs" => EMACS_BINDINGS,
+        _ => return None,
+    };
+    let mut keymap = super::default_keymap();
+    keymap.extend(bindings.iter().map(|(keys, command)| {
+        let keys = KeyInput::parse_sequence(keys).expect("preset bindings are valid keys");
+        (keys, command.clone())
+    }));
+    Some(keymap)
+}
+
+/// Returns the [`Command`] with a given name, as it's written by [`format_commands`]
+pub fn parse_command_name(name: &str) -> Option<Command> {
+    let name = name.trim();
+    // Commands which take a `char` (e.g. `InsertAndEdit('s')`) are built from that `char`
+    let insert_char = name
+        .strip_prefix("InsertAndEdit('")
+        .and_then(|rest| rest.strip_suffix("')"))
+        .and_then(|c| c.parse::<char>().ok());
+    // Language commands are named by a string, e.g. `LanguageCommand("sort-keys")`
+    let language_command = name
+        .strip_prefix("LanguageCommand(\"")
+        .and_then(|rest| rest.strip_suffix("\")"))
+        .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+        .map(|name| Command::LanguageCommand(name.to_string()));
+    all_commands()
+        .into_iter()
+        .chain(insert_char.map(Command::InsertAndEdit))
+        .chain(language_command)
+        .find(|command| format!("{:?}", command) == name)
+}
+
+/// Returns every [`Command`] which can be bound to a key
+pub fn all_commands() -> Vec<Command> {
+    vec![
+        Command::Quit,
+        Command::Replace,
+        Command::InsertChild,
+        Command::MoveCursor(Direction::Up),
+        Command::MoveCursor(Direction::Down),
+        Command::MoveCursor(Direction::Prev),
+        Command::MoveCursor(Direction::Next),
+        Command::Undo,
+        Command::Redo,
+        Command::ToggleOutline,
+        Command::Scroll,
+        Command::Search,
+        Command::NextMatch,
+        Command::PrevMatch,
+        Command::CommandLine,
+        Command::MotionBack,
+        Command::MotionForward,
+        Command::OlderEdit,
+        Command::NewerEdit,
+        Command::Yank,
+        Command::Cut,
+        Command::Paste,
+        Command::PasteAfter,
+        Command::PasteChild,
+        Command::Register,
+        Command::PlayMacro,
+        Command::RecordMacro,
+        Command::RepeatReplace,
+        Command::SelectEnclosing,
+        Command::Slurp,
+        Command::Barf,
+        Command::Transpose,
+        Command::Join,
+        Command::Sort,
+        Command::Group,
+        Command::Write,
+        Command::SetBookmark,
+        Command::JumpToBookmark,
+        Command::EditText,
+        Command::CommandPalette,
+        Command::TransformMenu,
+        Command::ScrollView(ScrollStep::LineDown),
+        Command::ScrollView(ScrollStep::LineUp),
+        Command::ScrollView(ScrollStep::PageDown),
+        Command::ScrollView(ScrollStep::PageUp),
+        Command::ToggleLog,
+        Command::ScrollLog(ScrollStep::LineDown),
+        Command::ScrollLog(ScrollStep::LineUp),
+        Command::ScrollLog(ScrollStep::PageDown),
+        Command::ScrollLog(ScrollStep::PageUp),
+    ]
+}
+
+/// The problems found in a list of key bindings
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct KeymapReport {
+    /// Keys which are bound more than once, along with every [`Command`] they are bound to (in the
+    /// order that they were bound)
+    pub duplicates: Vec<(KeySequence, Vec<Command>)>,
+    /// Commands which aren't bound to any key, and so can't be used
+    pub unbound: Vec<Command>,
+    /// Pairs of bindings where the first is the start of the second, so the first only runs when
+    /// the key after it doesn't continue the second
+    pub prefixes: Vec<(KeySequence, KeySequence)>,
+}
+
+impl KeymapReport {
+    /// Returns `true` if no problems were found
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.unbound.is_empty() && self.prefixes.is_empty()
+    }
+
+    /// Returns `true` if some key is bound to more than one different [`Command`], in which case
+    /// it's impossible to tell what the user meant.  Binding a key to the same command twice is
+    /// harmless.
+    pub fn is_ambiguous(&self) -> bool {
+        self.duplicates
+            .iter()
+            .any(|(_, commands)| commands.iter().any(|c| *c != commands[0]))
+    }
+
+    /// Summarises the problems as [`Message`]s, to be shown in the log
+    pub fn messages(&self) -> Vec<Message> {
+        let mut messages: Vec<Message> = self
+            .duplicates
+            .iter()
+            .map(|(key, commands)| {
+                Message::DuplicateBinding(sequence_text(key), format_commands(commands))
+            })
+            .collect();
+        messages.extend(self.prefixes.iter().map(|(short, long)| {
+            Message::PrefixBinding(sequence_text(short), sequence_text(long))
+        }));
+        if !self.unbound.is_empty() {
+            messages.push(Message::UnboundCommands(format_commands(&self.unbound)));
+        }
+        if self.is_ambiguous() {
+            messages.push(Message::AmbiguousKeymap);
+        }
+        messages
+    }
+}
+
+/// Formats a list of [`Command`]s as a comma-separated list
+fn format_commands(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|c| format!("{:?}", c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Checks a list of key bindings (as they were written in the config, before being collected into
+/// a [`KeyMap`]) for keys which are bound more than once, commands which aren't bound at all, and
+/// bindings which are the start of another binding.
+pub fn validate(bindings: &[(KeySequence, Command)]) -> KeymapReport {
+    let mut duplicates: Vec<(KeySequence, Vec<Command>)> = Vec::new();
+    for (i, (key, _)) in bindings.iter().enumerate() {
+        // Only report each key once, when its first binding is found
+        if bindings[..i].iter().any(|(k, _)| k == key) {
+            continue;
+        }
+        let commands: Vec<Command> = bindings[i..]
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, c)| c.clone())
+            .collect();
+        if commands.len() > 1 {
+            duplicates.push((key.clone(), commands));
+        }
+    }
+    let unbound = all_commands()
+        .into_iter()
+        .filter(|command| bindings.iter().all(|(_, c)| c != command))
+        .collect();
+    let mut prefixes = Vec::new();
+    for (short, _) in bindings {
+        for (long, _) in bindings {
+            let is_new = !prefixes.contains(&(short.clone(), long.clone()));
+            if long.len() > short.len() && long.starts_with(short) && is_new {
+                prefixes.push((short.clone(), long.clone()));
+            }
+        }
+    }
+    KeymapReport {
+        duplicates,
+        unbound,
+        prefixes,
+    }
+}
+
+/// Builds a [`KeyMap`] from a list of key bindings, along with a [`KeymapReport`] of any problems
+/// with the bindings.  Returns [`None`] instead of a [`KeyMap`] if the bindings are ambiguous.
+pub fn build(bindings: &[(KeySequence, Command)]) -> (Option<KeyMap>, KeymapReport) {
+    let report = validate(bindings);
+    if report.is_ambiguous() {
+        return (None, report);
+    }
+    (Some(bindings.iter().cloned().collect()), report)
+}
+
+/// Adds a language's extra bindings to a keymap, replacing any existing bindings of the same keys.
+/// If the extra bindings are ambiguous then the keymap is left unchanged.  Returns a
+/// [`KeymapReport`] of any keys bound more than once by the extra bindings (commands left unbound
+/// by the extra bindings aren't reported, because the keymap will still bind them).
+pub fn apply_overrides(keymap: &mut KeyMap, overrides: &[(KeySequence, Command)]) -> KeymapReport {
+    let report = KeymapReport {
+        unbound: Vec::new(),
+        ..validate(overrides)
+    };
+    if !report.is_ambiguous() {
+        keymap.extend(overrides.iter().cloned());
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        all_commands, apply_overrides, build, parse_config, parse_keymap, preset, validate,
+        Continuation, KeyInput, KeyMap, KeySequence, Lookup, Modifiers, ScrollStep, PRESET_NAMES,
+    };
+    use crate::editable_tree::Direction;
+    use crate::editor::Command;
+    use crate::messages::Message;
+    use tuikit::key::Key;
+
+    /// Converts bindings of characters into bindings of [`KeyInput`]s
+    fn char_bindings(bindings: &[(char, Command)]) -> Vec<(KeySequence, Command)> {
+        bindings
+            .iter()
+            .map(|(c, command)| (vec![KeyInput::from(*c)], command.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn default_keymap_is_valid() {
+        let mut bindings = char_bindings(crate::editor::DEFAULT_BINDINGS);
+        bindings.extend(
+            crate::editor::DEFAULT_KEY_BINDINGS
+                .iter()
+                .map(|(key, command)| (vec![KeyInput::from(*key)], command.clone())),
+        );
+        assert!(validate(&bindings).is_empty());
+    }
+
+    #[test]
+    fn problems() {
+        let bindings = char_bindings(&[
+            ('q', Command::Quit),
+            ('u', Command::Undo),
+            ('q', Command::Quit),
+            ('x', Command::Redo),
+            ('x', Command::MoveCursor(Direction::Up)),
+        ]);
+        let report = validate(&bindings);
+        assert_eq!(
+            report.duplicates,
+            vec![
+                (
+                    vec![KeyInput::from('q')],
+                    vec![Command::Quit, Command::Quit]
+                ),
+                (
+                    vec![KeyInput::from('x')],
+                    vec![Command::Redo, Command::MoveCursor(Direction::Up)]
+                ),
+            ]
+        );
+        assert_eq!(report.unbound.len(), all_commands().len() - 4);
+        assert!(!report.unbound.contains(&Command::Redo));
+        assert!(report.is_ambiguous());
+        assert_eq!(build(&bindings).0, None);
+        assert_eq!(report.messages().len(), 4);
+        // Binding a key to the same command twice is allowed
+        let (keymap, report) = build(&bindings[..3]);
+        assert!(!report.is_ambiguous());
+        assert_eq!(keymap.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn overrides() {
+        let mut keymap = crate::editor::default_keymap();
+        // Ambiguous overrides are ignored
+        let overrides = char_bindings(&[('(', Command::Undo), ('(', Command::Redo)]);
+        let report = apply_overrides(&mut keymap, &overrides);
+        assert!(report.is_ambiguous());
+        assert_eq!(keymap, crate::editor::default_keymap());
+        // Otherwise, the overrides are added and replace existing bindings
+        let overrides = char_bindings(&[('(', Command::Undo), ('q', Command::Redo)]);
+        let report = apply_overrides(&mut keymap, &overrides);
+        assert!(report.is_empty());
+        assert_eq!(keymap.get('('), Some(&Command::Undo));
+        assert_eq!(keymap.get('q'), Some(&Command::Redo));
+        assert_eq!(keymap.get('u'), Some(&Command::Undo));
+    }
+
+    #[test]
+    fn key_inputs() {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::NONE
+        };
+        // Chords are split into their key and modifiers, however tuikit reports them
+        assert_eq!(
+            KeyInput::from(Key::CtrlUp),
+            KeyInput {
+                key: Key::Up,
+                modifiers: ctrl
+            }
+        );
+        assert_eq!(KeyInput::parse("<C-Up>"), Some(KeyInput::from(Key::CtrlUp)));
+        assert_eq!(
+            KeyInput::parse("<c-z>"),
+            Some(KeyInput::from(Key::Ctrl('z')))
+        );
+        assert_eq!(
+            KeyInput::parse("<A-S-Left>"),
+            Some(KeyInput::from(Key::AltShiftLeft))
+        );
+        assert_eq!(
+            KeyInput::parse("<PageDown>"),
+            Some(KeyInput::from(Key::PageDown))
+        );
+        assert_eq!(KeyInput::parse("<F5>"), Some(KeyInput::from(Key::F(5))));
+        assert_eq!(KeyInput::parse("<"), Some(KeyInput::from('<')));
+        assert_eq!(KeyInput::parse("<Nope>"), None);
+        assert_eq!(KeyInput::parse("ab"), None);
+        // Keys are written back in the same form
+        for text in &["x", "<C-z>", "<A-S-Left>", "<PageDown>", "<F5>", "<S-Tab>"] {
+            assert_eq!(KeyInput::parse(text).unwrap().to_string(), *text);
+        }
+        assert_eq!(KeyInput::from(Key::Ctrl('x')).as_char(), None);
+    }
+
+    #[test]
+    fn sequences() {
+        let seq = |text: &str| KeyInput::parse_sequence(text).unwrap();
+        assert_eq!(
+            seq("<Space>w"),
+            vec![KeyInput::from(' '), KeyInput::from('w')]
+        );
+        assert_eq!(seq("<lt>a"), vec![KeyInput::from('<'), KeyInput::from('a')]);
+        assert_eq!(seq("<"), vec![KeyInput::from('<')]);
+        assert_eq!(KeyInput::parse_sequence("a<Nope>"), None);
+        assert_eq!(KeyInput::parse_sequence("<C-x"), None);
+        assert_eq!(KeyInput::parse_sequence(""), None);
+
+        let bindings = vec![
+            (seq("g"), Command::Group),
+            (seq("gg"), Command::MoveCursor(Direction::Up)),
+            (seq("<Space>w"), Command::Write),
+        ];
+        let keymap: KeyMap = bindings.iter().cloned().collect();
+        assert_eq!(keymap.len(), 3);
+        assert_eq!(keymap.lookup(&[]), Lookup::Incomplete);
+        assert_eq!(keymap.lookup(&seq("g")), Lookup::Incomplete);
+        assert_eq!(
+            keymap.lookup(&seq("gg")),
+            Lookup::Bound(&Command::MoveCursor(Direction::Up), 2)
+        );
+        // The longest binding is used, and the keys after it are its arguments
+        assert_eq!(
+            keymap.lookup(&seq("gab")),
+            Lookup::Bound(&Command::Group, 1)
+        );
+        assert_eq!(keymap.lookup(&seq("<Space>")), Lookup::Incomplete);
+        assert_eq!(keymap.lookup(&seq("<Space>x")), Lookup::Unbound);
+        assert_eq!(keymap.lookup(&seq("x")), Lookup::Unbound);
+        // Once the command times out, a binding which starts a longer one is used on its own
+        assert!(keymap.is_ambiguous(&seq("g")));
+        assert!(!keymap.is_ambiguous(&seq("gg")));
+        assert!(!keymap.is_ambiguous(&seq("<Space>")));
+        assert_eq!(
+            keymap.lookup_final(&seq("g")),
+            Lookup::Bound(&Command::Group, 1)
+        );
+        assert_eq!(keymap.lookup_final(&seq("<Space>")), Lookup::Incomplete);
+        assert!(keymap.contains_key(' '));
+        assert_eq!(keymap.get(' '), None);
+        // The hints for an incomplete command list the keys which can follow it
+        assert_eq!(
+            keymap.continuations(&[]),
+            vec![
+                (KeyInput::from(' '), Continuation::Prefix(1)),
+                (KeyInput::from('g'), Continuation::Prefix(2)),
+            ]
+        );
+        assert_eq!(
+            keymap.continuations(&seq("g")),
+            vec![(
+                KeyInput::from('g'),
+                Continuation::Command(Command::MoveCursor(Direction::Up))
+            )]
+        );
+        assert_eq!(keymap.continuations(&seq("x")), vec![]);
+
+        // Bindings which are the start of others are reported
+        let report = validate(&bindings);
+        assert_eq!(report.prefixes, vec![(seq("g"), seq("gg"))]);
+        assert!(!report.is_ambiguous());
+        assert_eq!(
+            parse_config("[keymap]\ngg = \"Undo\"\n\"<Space>w\" = \"Write\""),
+            Ok(Some(vec![
+                (seq("<Space>w"), Command::Write),
+                (seq("gg"), Command::Undo),
+            ]))
+        );
+    }
+
+    #[test]
+    fn config_files() {
+        let config = "width = 80\n[keymap]\nq = \"Quit\"\nh = \"MoveCursor(Prev)\"\n";
+        assert_eq!(
+            parse_config(config),
+            Ok(Some(char_bindings(&[
+                ('h', Command::MoveCursor(Direction::Prev)),
+                ('q', Command::Quit),
+            ])))
+        );
+        assert_eq!(parse_config("width = 80"), Ok(None));
+        assert_eq!(
+            parse_config("[keymap]\n\"<Nope>\" = \"Quit\""),
+            Err("'<Nope>' isn't a sequence of keys".to_string())
+        );
+        assert_eq!(
+            parse_config("[keymap]\nq = \"Leave\""),
+            Err("'Leave' isn't a command".to_string())
+        );
+        assert!(parse_config("[keymap").is_err());
+        assert_eq!(
+            parse_config("[keymap]\n\"<C-d>\" = \"MoveCursor(Next)\""),
+            Ok(Some(vec![(
+                vec![KeyInput::from(Key::Ctrl('d'))],
+                Command::MoveCursor(Direction::Next)
+            )]))
+        );
+        // Commands which aren't in the default keymap can be bound too
+        assert_eq!(
+            parse_config("[keymap]\nS = \"InsertAndEdit('s')\""),
+            Ok(Some(char_bindings(&[('S', Command::InsertAndEdit('s'))])))
+        );
+        assert_eq!(
+            parse_config("[keymap]\nS = \"InsertAndEdit('ss')\""),
+            Err("'InsertAndEdit('ss')' isn't a command".to_string())
+        );
+        assert_eq!(
+            parse_config("[keymap]\nS = 'LanguageCommand(\"sort-keys\")'"),
+            Ok(Some(char_bindings(&[(
+                'S',
+                Command::LanguageCommand("sort-keys".to_string())
+            )])))
+        );
+        assert_eq!(
+            parse_config("[keymap]\nS = 'LanguageCommand(\"\")'"),
+            Err("'LanguageCommand(\"\")' isn't a command".to_string())
+        );
+        assert_eq!(
+            parse_config("[keymap]\n\"<C-d>\" = \"ScrollView(PageDown)\""),
+            Ok(Some(vec![(
+                vec![KeyInput::from(Key::Ctrl('d'))],
+                Command::ScrollView(ScrollStep::PageDown)
+            )]))
+        );
+
+        // Configs without a keymap give the default keymap, and broken ones do too but with a
+        // warning
+        let path = std::path::Path::new("config.toml");
+        let toml = |text: &str| text.parse::<toml::Value>().unwrap();
+        let default_keymap = crate::editor::default_keymap();
+        assert_eq!(
+            KeyMap::from_config(path, &toml(""), None),
+            (default_keymap.clone(), None)
+        );
+        let (keymap, warning) = KeyMap::from_config(path, &toml("[keymap]\nx = 1"), None);
+        assert_eq!(keymap, default_keymap);
+        assert!(matches!(warning, Some(Message::CannotLoadKeymap(..))));
+        let (keymap, warning) = KeyMap::from_config(path, &toml(config), None);
+        assert_eq!((keymap.len(), warning), (2, None));
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(preset("default"), Some(crate::editor::default_keymap()));
+        assert_eq!(preset("nano"), None);
+        for name in PRESET_NAMES.iter() {
+            let bindings = preset(name).unwrap().bindings();
+            let report = validate(&bindings);
+            assert!(report.duplicates.is_empty() && report.prefixes.is_empty());
+            assert_eq!(report.unbound, vec![], "{} leaves commands unbound", name);
+        }
+        let vim = preset("vim").unwrap();
+        assert_eq!(vim.get('h'), Some(&Command::MoveCursor(Direction::Up)));
+        assert_eq!(vim.get('p'), Some(&Command::PasteAfter));
+        let emacs = preset("emacs").unwrap();
+        let keys = KeyInput::parse_sequence("<C-x><C-s>").unwrap();
+        assert_eq!(emacs.lookup(&keys), Lookup::Bound(&Command::Write, 2));
+
+        // A config's table is added to the preset it names, or to the one given instead
+        let config = "keymap-preset = \"vim\"\n[keymap]\nh = \"Undo\"\n";
+        let keymap = parse_keymap(config, None).unwrap();
+        assert_eq!(keymap.get('h'), Some(&Command::Undo));
+        assert_eq!(keymap.get('x'), Some(&Command::Cut));
+        let keymap = parse_keymap(config, Some("emacs")).unwrap();
+        assert_eq!(keymap.get('x'), None);
+        assert_eq!(
+            keymap.get(Key::Ctrl('n')),
+            Some(&Command::MoveCursor(Direction::Next))
+        );
+        assert_eq!(
+            parse_keymap("keymap-preset = \"nano\"", None),
+            Err("'nano' isn't a keymap preset".to_string())
+        );
+        assert!(parse_keymap("keymap-preset = 1", None).is_err());
+    }
+
+    #[test]
+    fn export() {
+        for name in PRESET_NAMES.iter() {
+            let keymap = preset(name).unwrap();
+            assert_eq!(parse_keymap(&keymap.to_config(), None), Ok(keymap));
+        }
+        let keymap: KeyMap = vec![
+            (vec![KeyInput::from('"')], Command::InsertAndEdit('"')),
+            (
+                KeyInput::parse_sequence("<Space>s").unwrap(),
+                Command::LanguageCommand("sort-keys".to_string()),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(parse_keymap(&keymap.to_config(), None), Ok(keymap));
+    }
+}
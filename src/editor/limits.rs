@@ -0,0 +1,113 @@
+//! Soft limits on the shape of a tree.  Trees which are nested very deeply, or which have nodes
+//! with huge numbers of children, are slow to draw and to move around in, so the parts of a tree
+//! which go over the limits can be found and [folded](super::folds) out of the way.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+
+/// The limits on the shape of a tree, beyond which it's drawn slowly
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TreeLimits {
+    /// How deeply nodes can be nested below the root
+    pub max_depth: usize,
+    /// How many children a node can have
+    pub max_children: usize,
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        TreeLimits {
+            max_depth: 100,
+            max_children: 5_000,
+        }
+    }
+}
+
+/// How a node goes over the [`TreeLimits`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Excess {
+    /// The node is at the deepest level allowed, but still has children
+    TooDeep,
+    /// The node has more children than allowed
+    TooWide,
+}
+
+/// Returns the path of every node in the tree under `root` which goes over `limits`, along with
+/// the node and how it goes over them, in depth-first pre-order.  The descendants of these nodes
+/// aren't checked, since folding the nodes hides their descendants anyway.
+pub fn find_oversized<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    limits: &TreeLimits,
+) -> Vec<(CursorPath, &'arena Node, Excess)> {
+    let mut oversized = Vec::new();
+    // The tree is walked without recursion, because it might be deep enough to overflow the stack
+    let mut stack = vec![(Vec::new(), root)];
+    while let Some((path, node)) = stack.pop() {
+        let children = node.children();
+        let excess = if children.len() > limits.max_children {
+            Some(Excess::TooWide)
+        } else if path.len() >= limits.max_depth && !children.is_empty() {
+            Some(Excess::TooDeep)
+        } else {
+            None
+        };
+        if let Some(excess) = excess {
+            oversized.push((CursorPath::from_vec(path), node, excess));
+            continue;
+        }
+        // Push the children in reverse, so that they're popped in order
+        for (i, child) in children.iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            stack.push((child_path, *child));
+        }
+    }
+    oversized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_oversized, Excess, TreeLimits};
+    use crate::arena::Arena;
+    use crate::ast::json::JSON;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn oversized() {
+        let arena = Arena::new();
+        let nested =
+            |depth: usize| (0..depth).fold(TestJSON::True, |json, _| TestJSON::Array(vec![json]));
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True; 5]),
+            nested(3),
+            nested(6),
+            TestJSON::Array(vec![TestJSON::False; 4]),
+        ])
+        .add_to_arena(&arena);
+        let limits = TreeLimits {
+            max_depth: 4,
+            max_children: 4,
+        };
+        let found: Vec<_> = find_oversized(root, &limits)
+            .into_iter()
+            .map(|(path, _, excess)| (path.to_string(), excess))
+            .collect();
+        let path = |indices: &[usize]| CursorPath::from_vec(indices.to_vec()).to_string();
+        assert_eq!(
+            found,
+            vec![
+                (path(&[0]), Excess::TooWide),
+                (path(&[2, 0, 0, 0]), Excess::TooDeep),
+            ]
+        );
+        // Nothing in a small tree goes over the default limits
+        assert!(find_oversized(root, &TreeLimits::default()).is_empty());
+        // A tree too deep to walk recursively can still be checked
+        let mut deep: &JSON = arena.alloc(JSON::True);
+        for _ in 0..100_000 {
+            deep = arena.alloc(JSON::Array(vec![deep]));
+        }
+        assert_eq!(find_oversized(deep, &TreeLimits::default()).len(), 1);
+    }
+}
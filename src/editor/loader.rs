@@ -0,0 +1,357 @@
+//! Loading of documents in a background thread, so that gigantic files can be opened without
+//! freezing the editor.  The file is parsed a chunk at a time by a
+//! [`StreamingParser`](crate::ast::json_stream::StreamingParser), and the values parsed since the
+//! last update are sent back regularly so that the start of the document can be shown whilst the
+//! rest is parsed.  If the file doesn't parse, it's parsed again
+//! [leniently](crate::ast::json_recover) so that it can still be opened, with the parts which
+//! couldn't be parsed kept as text.
+
+use super::encoding::{Decoder, FileFormat, LineEnding};
+use super::progress::ProgressSender;
+use crate::arena::Arena;
+use crate::ast::json_recover::{self, ParseError};
+use crate::ast::json_stream::{PrefixUpdate, StreamingParser};
+use crate::ast::structured::StructuredNode;
+use crate::ast::trivia::{Place, Trivia};
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How many bytes of the file are read and parsed at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How often the values parsed since the last update are sent back to be shown
+const PREFIX_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many times further through the file the parser must be than it was at the last update
+/// before another is sent.  Showing each update rebuilds the arrays and objects which are still
+/// open (though not their children), so this stops a document from being rebuilt more than a
+/// few times as often as it doubles in size.
+const PREFIX_GROWTH: usize = 2;
+
+/// A message sent from the loading thread
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum LoadUpdate {
+    /// The values which have been parsed since the last update
+    Prefix(PrefixUpdate),
+    /// The whole document, or an error message saying why it couldn't be loaded.  This is always
+    /// the last update.
+    Finished(Result<LoadedDocument, Message>),
+}
+
+/// What has happened to a load since it was last [polled](Loader::poll)
+#[derive(Debug)]
+pub enum Loaded<'arena, Node: Ast<'arena>> {
+    /// More of the document has been parsed, and this is the part of it parsed so far
+    Prefix(&'arena Node),
+    /// The whole document, or an error message saying why it couldn't be loaded
+    Finished(Result<LoadedDocument, Message>),
+}
+
+/// A document which has been completely loaded
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LoadedDocument {
+    pub root: StructuredNode,
+    /// The comments and blank lines of the file, and where they go in the tree
+    pub trivia: Vec<(Place<CursorPath>, Trivia)>,
+    /// The format of the file that the document was read from
+    pub format: FileFormat,
+    /// The mistakes in the file which had to be recovered from to load it, or nothing if the
+    /// whole file parsed
+    pub errors: Vec<ParseError>,
+}
+
+/// An array, object or field of the document being loaded which was still open at the last
+/// update, along with the children of it which had been loaded
+#[derive(Debug)]
+struct OpenNode<'arena, Node: Ast<'arena>> {
+    kind: String,
+    value: Option<String>,
+    children: Vec<&'arena Node>,
+}
+
+/// A document which is being loaded in a background thread
+#[derive(Debug)]
+pub struct Loader<'arena, Node: Ast<'arena>> {
+    path: String,
+    receiver: Receiver<LoadUpdate>,
+    /// The nodes which were still open at the last update (outermost first), or [`None`] if an
+    /// update couldn't be turned into nodes (in which case nothing is shown until the load is
+    /// finished)
+    spine: Option<Vec<OpenNode<'arena, Node>>>,
+}
+
+impl<'arena, Node: Ast<'arena>> Loader<'arena, Node> {
+    /// Starts loading the file at `path` as a document in a given language, reporting progress
+    /// (labelled by `label`) through `progress`.  Returns [`None`] if documents in that language
+    /// can't be loaded.
+    pub fn start(
+        path: &str,
+        language: &str,
+        label: &str,
+        progress: &ProgressSender,
+    ) -> Option<Loader<'arena, Node>> {
+        let parser = match language {
+            "json" => StreamingParser::new(),
+            _ => return None,
+        };
+        let (sender, receiver) = channel();
+        let thread_path = path.to_string();
+        let label = label.to_string();
+        let progress = progress.clone();
+        thread::spawn(move || {
+            let result = load(&thread_path, parser, &label, &progress, &sender)
+                .map_or_else(|| recover(&thread_path), Ok);
+            // If the editor has stopped listening, then nobody wants the document
+            let _ = sender.send(LoadUpdate::Finished(result));
+        });
+        Some(Loader {
+            path: path.to_string(),
+            receiver,
+            spine: Some(Vec::new()),
+        })
+    }
+
+    /// Returns the path of the file being loaded
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns what has happened to the load since this was last called, if anything.  The
+    /// nodes which have been parsed are added to `arena`, and those which were added by earlier
+    /// calls are reused.
+    pub fn poll(&mut self, arena: &'arena Arena<Node>) -> Option<Loaded<'arena, Node>> {
+        let mut has_grown = false;
+        for update in self.receiver.try_iter() {
+            match update {
+                LoadUpdate::Prefix(update) => {
+                    if let Some(spine) = &mut self.spine {
+                        if extend_spine(spine, update, arena).is_none() {
+                            self.spine = None;
+                        }
+                    }
+                    has_grown = true;
+                }
+                LoadUpdate::Finished(result) => return Some(Loaded::Finished(result)),
+            }
+        }
+        if !has_grown {
+            return None;
+        }
+        close_spine(self.spine.as_ref()?, arena).map(Loaded::Prefix)
+    }
+}
+
+/// Adds the values which have been parsed since the last update to the spine of open nodes,
+/// returning [`None`] if they aren't valid nodes
+fn extend_spine<'arena, Node: Ast<'arena>>(
+    spine: &mut Vec<OpenNode<'arena, Node>>,
+    update: PrefixUpdate,
+    arena: &'arena Arena<Node>,
+) -> Option<()> {
+    let alloc = |child: &StructuredNode| Some(arena.alloc(child.to_node(arena)?));
+    spine.truncate(update.kept);
+    for (open, added) in spine.iter_mut().zip(&update.added) {
+        for child in added {
+            open.children.push(alloc(child)?);
+        }
+    }
+    for node in &update.opened {
+        spine.push(OpenNode {
+            kind: node.kind.clone(),
+            value: node.value.clone(),
+            children: node.children.iter().map(alloc).collect::<Option<_>>()?,
+        });
+    }
+    Some(())
+}
+
+/// Returns the part of the document which has been loaded, by closing every node of the spine.
+/// Fields which are still missing their values are left out.
+fn close_spine<'arena, Node: Ast<'arena>>(
+    spine: &[OpenNode<'arena, Node>],
+    arena: &'arena Arena<Node>,
+) -> Option<&'arena Node> {
+    let mut child = None;
+    for open in spine.iter().rev() {
+        let mut children = open.children.clone();
+        children.extend(child.take());
+        if open.kind == "field" && children.len() < 2 {
+            continue;
+        }
+        let node = Node::from_parts(&open.kind, open.value.as_deref(), children)?;
+        child = Some(arena.alloc(node));
+    }
+    child
+}
+
+/// Reads and parses the file at `path`, sending the values parsed as it goes.  The file's
+/// encoding is detected from its first chunk, and its line endings from its first line break.
+/// Returns [`None`] if the file can't be read or doesn't parse, in which case it's
+/// [recovered](recover), which finds out why.
+fn load(
+    path: &str,
+    mut parser: StreamingParser,
+    label: &str,
+    progress: &ProgressSender,
+    sender: &Sender<LoadUpdate>,
+) -> Option<LoadedDocument> {
+    let mut file = File::open(path).ok()?;
+    let total = file.metadata().ok().map(|metadata| metadata.len() as usize);
+    let task = progress.start(label, total);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut format_and_decoder: Option<(FileFormat, Decoder)> = None;
+    let mut bytes_read = 0;
+    let mut line_ending = None;
+    let mut last_prefix: Option<(Instant, usize)> = None;
+    loop {
+        let num_read = file.read(&mut chunk).ok()?;
+        if num_read == 0 {
+            break;
+        }
+        bytes_read += num_read;
+        let mut bytes = &chunk[..num_read];
+        let (_, decoder) = format_and_decoder.get_or_insert_with(|| {
+            let (format, bom_len) = FileFormat::detect(bytes);
+            bytes = &bytes[bom_len..];
+            (format, Decoder::new(format.encoding))
+        });
+        let text = decoder.decode(bytes).ok()?;
+        if line_ending.is_none() && text.contains('\n') {
+            line_ending = Some(LineEnding::detect(&text));
+        }
+        parser.feed(&text).ok()?;
+        task.advance(bytes_read);
+        // The first prefix is sent straight away, so that something is shown quickly
+        let is_due = last_prefix.is_none_or(|(time, bytes)| {
+            time.elapsed() >= PREFIX_INTERVAL && bytes_read >= bytes * PREFIX_GROWTH
+        });
+        if is_due {
+            if let Some(prefix) = parser.take_prefix() {
+                sender.send(LoadUpdate::Prefix(prefix)).ok()?;
+                last_prefix = Some((Instant::now(), bytes_read));
+            }
+        }
+    }
+    let mut format = match format_and_decoder {
+        Some((format, decoder)) => {
+            decoder.finish().ok()?;
+            format
+        }
+        None => FileFormat::default(),
+    };
+    format.line_ending = line_ending.unwrap_or_default();
+    let (root, trivia) = parser.finish_with_trivia().ok()?;
+    Some(LoadedDocument {
+        root,
+        trivia,
+        format,
+        errors: Vec::new(),
+    })
+}
+
+/// Loads the file at `path` leniently after it failed to load, returning an error message if the
+/// file still can't be loaded (e.g. because it can't be read).  Comments aren't understood by the
+/// lenient parser, so no trivia is kept.
+fn recover(path: &str) -> Result<LoadedDocument, Message> {
+    let cannot_open = |reason: String| Message::CannotOpen(path.to_string(), reason);
+    let bytes = std::fs::read(path).map_err(|e| cannot_open(e.to_string()))?;
+    let (mut format, bom_len) = FileFormat::detect(&bytes);
+    let mut decoder = Decoder::new(format.encoding);
+    let text = decoder.decode(&bytes[bom_len..]).map_err(cannot_open)?;
+    decoder.finish().map_err(cannot_open)?;
+    if text.contains('\n') {
+        format.line_ending = LineEnding::detect(&text);
+    }
+    let recovered =
+        json_recover::parse(&text).ok_or_else(|| Message::EmptyFile(path.to_string()))?;
+    Ok(LoadedDocument {
+        root: recovered.node,
+        trivia: Vec::new(),
+        format,
+        errors: recovered.errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{close_spine, extend_spine, LoadUpdate, Loader};
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::json_stream::StreamingParser;
+    use crate::ast::Ast;
+    use crate::editor::progress::ProgressTracker;
+    use crate::messages::Message;
+
+    /// Waits for a load to finish, returning its result along with the number of parse errors
+    fn finish<'arena>(loader: &Loader<'arena, JSON<'arena>>) -> Result<(String, usize), Message> {
+        loop {
+            match loader.receiver.recv().unwrap() {
+                LoadUpdate::Finished(result) => {
+                    return result
+                        .map(|document| (document.root.serialize(), document.errors.len()));
+                }
+                LoadUpdate::Prefix(_) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join(format!("sapling-loader-{}", std::process::id()));
+        // Make the file long enough to take a few chunks, with 'é' straddling chunk boundaries
+        let items = vec!["\"é\""; 40_000].join(",");
+        std::fs::write(&path, format!("[{}]", items)).unwrap();
+        let tracker = ProgressTracker::new();
+        let path_str = path.to_string_lossy();
+        let loader = Loader::start(&path_str, "json", "", &tracker.sender()).unwrap();
+        assert_eq!(loader.path(), path_str);
+        let (node, num_errors) = finish(&loader).unwrap();
+        assert_eq!(num_errors, 0);
+        assert_eq!(node.matches("(string \"é\")").count(), 40_000);
+
+        // Files which don't parse are still loaded, keeping the broken parts as text
+        std::fs::write(&path, "[true, nul").unwrap();
+        let loader = Loader::start(&path_str, "json", "", &tracker.sender()).unwrap();
+        assert_eq!(
+            finish(&loader),
+            Ok(("(array (true) (error \"nul\"))".to_string(), 2))
+        );
+        std::fs::write(&path, "  ").unwrap();
+        let loader = Loader::start(&path_str, "json", "", &tracker.sender()).unwrap();
+        assert_eq!(
+            finish(&loader),
+            Err(Message::EmptyFile(path_str.to_string()))
+        );
+        std::fs::remove_file(&path).unwrap();
+        let loader = Loader::start(&path_str, "json", "", &tracker.sender()).unwrap();
+        assert!(finish(&loader).is_err());
+        assert!(Loader::<JSON>::start(&path_str, "yaml", "", &tracker.sender()).is_none());
+    }
+
+    #[test]
+    fn prefixes() {
+        let arena = Arena::new();
+        let mut parser = StreamingParser::new();
+        let mut spine = Vec::new();
+        let mut prefix = |text: &str| {
+            parser.feed(text).unwrap();
+            extend_spine::<JSON>(&mut spine, parser.take_prefix().unwrap(), &arena).unwrap();
+            close_spine(&spine, &arena).unwrap()
+        };
+        let first = prefix("[[true], {\"a\": [fal");
+        assert_eq!(first.to_text(&JSONFormat::Compact), "[[true], {\"a\": []}]");
+        let second = prefix("se], \"b\": true}, fal");
+        assert_eq!(
+            second.to_text(&JSONFormat::Compact),
+            "[[true], {\"a\": [false], \"b\": true}]"
+        );
+        // The nodes which were loaded before are reused rather than being built again
+        assert!(std::ptr::eq(first.children()[0], second.children()[0]));
+    }
+}
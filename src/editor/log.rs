@@ -0,0 +1,228 @@
+//! The in-memory log of messages shown to the user, which only keeps a bounded number of entries
+//! so that it can't grow forever over a long session, and the log file, which keeps every entry
+//! (along with any panic) for debugging after Sapling has closed.  The log file is rotated once
+//! it reaches [`MAX_LOG_FILE_SIZE`], so it can't grow forever either.
+
+use super::LogLevel;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many entries the log keeps, unless the config says otherwise
+pub const DEFAULT_LOG_CAPACITY: usize = 1000;
+
+/// The lowest level of entries written to the log file, unless the config says otherwise
+pub const DEFAULT_LOG_FILE_LEVEL: LogLevel = LogLevel::Info;
+
+/// The size in bytes which the log file can reach before it's moved aside (replacing any file
+/// moved aside before) and a new one is started
+pub const MAX_LOG_FILE_SIZE: u64 = 1 << 20;
+
+/// Returns the path of the log file, which is `sapling.log` in Sapling's
+/// [data directory](super::config::data_dir), or [`None`] if the data directory isn't known
+pub fn default_file_path() -> Option<PathBuf> {
+    Some(super::config::data_dir()?.join("sapling.log"))
+}
+
+/// Returns the path which a full log file at `path` is moved to, which is `path` with `.1` added
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// Appends a line to the log file at `path` (creating the file and its directory if needed),
+/// starting with the time in seconds since the Unix epoch.  If the file has reached
+/// [`MAX_LOG_FILE_SIZE`], it's [rotated](rotated_path) first.
+fn append_line(path: &Path, text: &str) -> std::io::Result<()> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since_epoch| since_epoch.as_secs_f64());
+    path.parent().map_or(Ok(()), std::fs::create_dir_all)?;
+    if std::fs::metadata(path).is_ok_and(|meta| meta.len() >= MAX_LOG_FILE_SIZE) {
+        std::fs::rename(path, rotated_path(path))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{:.3} {}", secs, text)
+}
+
+/// The file which log entries at or above a given level are appended to, one line each
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    /// The file which entries are appended to, or [`None`] if no file is being written
+    path: Option<PathBuf>,
+    min_level: LogLevel,
+}
+
+impl LogFile {
+    /// Creates a log file which entries at or above `min_level` will be appended to, or which
+    /// isn't written if `path` is [`None`]
+    pub fn new(path: Option<PathBuf>, min_level: LogLevel) -> LogFile {
+        LogFile { path, min_level }
+    }
+
+    /// Appends an entry to the file, if it's at or above the file's level.  If the file can't be
+    /// written, then it stops being written and an error saying why is returned.
+    pub fn write(&mut self, level: &LogLevel, text: &str) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) if *level >= self.min_level => path,
+            _ => return Ok(()),
+        };
+        append_line(path, &format!("{:?}: {}", level, text)).map_err(|e| {
+            self.path = None;
+            e.to_string()
+        })
+    }
+
+    /// Makes any panic append its message to the file before the existing panic hook runs (which
+    /// may restore the terminal and print the message), so that crashes can be debugged after
+    /// the terminal has been cleared
+    pub fn log_panics(&self) {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = append_line(&path, &format!("Panic: {}", info));
+            hook(info);
+        }));
+    }
+}
+
+/// A log which holds at most a fixed number of entries.  Once it's full, adding an entry drops
+/// the oldest one, appending it to the overflow file if there is one.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    entries: VecDeque<(LogLevel, String)>,
+    capacity: usize,
+    /// The file which dropped entries are appended to, or [`None`] if they are thrown away
+    overflow_path: Option<PathBuf>,
+}
+
+impl LogBuffer {
+    /// Creates an empty log which holds at most `capacity` entries (and always at least one)
+    pub fn new(capacity: usize, overflow_path: Option<PathBuf>) -> LogBuffer {
+        let capacity = capacity.max(1);
+        LogBuffer {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            overflow_path,
+        }
+    }
+
+    /// Adds an entry to the end of the log, making room for it if the log is full
+    pub fn push(&mut self, level: LogLevel, text: String) {
+        if self.entries.len() == self.capacity {
+            if let Some((level, text)) = self.entries.pop_front() {
+                self.write_overflow(&level, &text);
+            }
+        }
+        self.entries.push_back((level, text));
+    }
+
+    /// Returns the entries in the log, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &(LogLevel, String)> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of entries in the log
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the log has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Appends an entry which has been dropped from the log to the overflow file.  If the file
+    /// can't be written, then it's forgotten and later entries are thrown away instead (there is
+    /// nowhere to report the error, since reporting it would need the log).
+    fn write_overflow(&mut self, level: &LogLevel, text: &str) {
+        let path = match &self.overflow_path {
+            Some(path) => path,
+            None => return,
+        };
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{:?}: {}", level, text));
+        if written.is_err() {
+            self.overflow_path = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rotated_path, LogBuffer, LogFile, MAX_LOG_FILE_SIZE};
+    use crate::editor::LogLevel;
+
+    #[test]
+    fn rotation() {
+        let path = std::env::temp_dir().join(format!("sapling-log-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut log = LogBuffer::new(2, Some(path.clone()));
+        log.push(LogLevel::Info, "a".to_string());
+        log.push(LogLevel::Warning, "b".to_string());
+        log.push(LogLevel::Error, "c".to_string());
+        log.push(LogLevel::Debug, "d".to_string());
+        let texts: Vec<_> = log.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(texts, ["c", "d"]);
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Info: a\nWarning: b\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let mut log = LogBuffer::new(0, None);
+        log.push(LogLevel::Info, "a".to_string());
+        log.push(LogLevel::Info, "b".to_string());
+        assert_eq!(log.len(), 1);
+    }
+
+    #[test]
+    fn file() {
+        let dir = std::env::temp_dir().join(format!("sapling-log-file-{}", std::process::id()));
+        let path = dir.join("sapling.log");
+        let mut file = LogFile::new(Some(path.clone()), LogLevel::Info);
+        file.write(&LogLevel::Debug, "hidden").unwrap();
+        file.write(&LogLevel::Info, "shown").unwrap();
+        file.write(&LogLevel::Error, "also shown").unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<&str> = text
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(entries, ["Info: shown", "Error: also shown"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A file which can't be written (here because its directory is a file) stops being
+        // written
+        std::fs::write(&dir, "").unwrap();
+        let mut file = LogFile::new(Some(path), LogLevel::Info);
+        assert!(file.write(&LogLevel::Info, "a").is_err());
+        assert_eq!(file.write(&LogLevel::Info, "b"), Ok(()));
+        std::fs::remove_file(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_rotation() {
+        let dir = std::env::temp_dir().join(format!("sapling-log-rot-{}", std::process::id()));
+        let path = dir.join("sapling.log");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, vec![b'a'; MAX_LOG_FILE_SIZE as usize]).unwrap();
+        let mut file = LogFile::new(Some(path.clone()), LogLevel::Info);
+        file.write(&LogLevel::Info, "new").unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with(" Info: new\n") && text.lines().count() == 1);
+        let rotated = std::fs::metadata(rotated_path(&path)).unwrap();
+        assert_eq!(rotated.len(), MAX_LOG_FILE_SIZE);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Keyboard macros, which are stored as sequences of key presses in named registers.  Macros can
+//! be converted to and from a readable text form (e.g. `"cjrt<CR>"`) so that they can be edited.
+
+use std::collections::HashMap;
+use tuikit::key::Key;
+
+/// The maximum number of macros which can be playing at once.  This stops a macro which plays
+/// itself from running forever.
+pub const MAX_MACRO_DEPTH: usize = 20;
+
+/// The names of keys which are written as `<name>` in the text form of a macro (and in the keymap
+/// config)
+pub const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::Enter, "CR"),
+    (Key::ESC, "Esc"),
+    (Key::Backspace, "BS"),
+    (Key::Tab, "Tab"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::Delete, "Del"),
+    (Key::Char(' '), "Space"),
+    (Key::Char('<'), "lt"),
+];
+
+/// Returns the text representing a single key in a macro, or [`None`] if the key can't be part of
+/// a macro.  Most characters represent themselves, and other keys are written as `<name>`.
+pub fn key_to_text(key: Key) -> Option<String> {
+    if let Some((_, name)) = KEY_NAMES.iter().find(|(k, _)| *k == key) {
+        return Some(format!("<{}>", name));
+    }
+    match key {
+        Key::Char(c) => Some(c.to_string()),
+        Key::Ctrl(c) => Some(format!("<C-{}>", c)),
+        _ => None,
+    }
+}
+
+/// Converts a sequence of keys into the text form of a macro.  Keys which can't be part of a macro
+/// are skipped.
+pub fn keys_to_text(keys: &[Key]) -> String {
+    keys.iter().filter_map(|key| key_to_text(*key)).collect()
+}
+
+/// Converts the text form of a macro back into a sequence of keys.  Returns [`Err`] containing the
+/// offending text if the text contains an unknown `<name>` or an unclosed `<`.
+pub fn text_to_keys(text: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(Key::Char(c));
+            continue;
+        }
+        // Read the name up to the closing '>'
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('>') => break,
+                Some(c) => name.push(c),
+                None => return Err(format!("<{}", name)),
+            }
+        }
+        let key = KEY_NAMES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(&name))
+            .map(|(k, _)| *k)
+            .or_else(|| {
+                let mut name_chars = name.strip_prefix("C-")?.chars();
+                match (name_chars.next(), name_chars.next()) {
+                    (Some(c), None) => Some(Key::Ctrl(c)),
+                    _ => None,
+                }
+            });
+        match key {
+            Some(key) => keys.push(key),
+            None => return Err(format!("<{}>", name)),
+        }
+    }
+    Ok(keys)
+}
+
+/// A set of macros, each stored in a register named by a [`char`]
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct MacroRegisters {
+    registers: HashMap<char, Vec<Key>>,
+}
+
+impl MacroRegisters {
+    /// Creates a set of empty registers
+    pub fn new() -> MacroRegisters {
+        MacroRegisters::default()
+    }
+
+    /// Returns the keys stored in a register, or [`None`] if the register is empty
+    pub fn get(&self, register: char) -> Option<&[Key]> {
+        self.registers.get(&register).map(Vec::as_slice)
+    }
+
+    /// Stores a sequence of keys in a register, replacing whatever was there
+    pub fn set(&mut self, register: char, keys: Vec<Key>) {
+        self.registers.insert(register, keys);
+    }
+}
+
+/// A macro which is being recorded from the keys that the user presses
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Recording {
+    /// The register that the macro will be stored in
+    pub register: char,
+    keys: Vec<Key>,
+    /// The number of keys recorded before the command which is being typed
+    command_start: usize,
+}
+
+impl Recording {
+    /// Starts recording a macro into a register
+    pub fn new(register: char) -> Recording {
+        Recording {
+            register,
+            keys: Vec::new(),
+            command_start: 0,
+        }
+    }
+
+    /// Records a key press.  `starts_command` should be `true` if the key is the first key of a
+    /// command (i.e. nothing has been typed into the command box).
+    pub fn push(&mut self, key: Key, starts_command: bool) {
+        if starts_command {
+            self.command_start = self.keys.len();
+        }
+        self.keys.push(key);
+    }
+
+    /// Stops recording, returning the keys of the macro.  The keys of the command which stopped
+    /// the recording are left out.
+    pub fn finish(mut self) -> Vec<Key> {
+        self.keys.truncate(self.command_start);
+        self.keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keys_to_text, text_to_keys, Recording};
+    use tuikit::key::Key;
+
+    #[test]
+    fn text_round_trip() {
+        let keys = vec![
+            Key::Char('c'),
+            Key::Char('j'),
+            Key::Char('/'),
+            Key::Char('<'),
+            Key::Char(' '),
+            Key::Enter,
+            Key::Ctrl('z'),
+            Key::ESC,
+        ];
+        let text = keys_to_text(&keys);
+        assert_eq!(text, "cj/<lt><Space><CR><C-z><Esc>");
+        assert_eq!(text_to_keys(&text), Ok(keys));
+        // Names are case-insensitive
+        assert_eq!(text_to_keys("<cr><esc>"), Ok(vec![Key::Enter, Key::ESC]));
+    }
+
+    #[test]
+    fn invalid_text() {
+        assert_eq!(text_to_keys("ab<Bogus>c"), Err("<Bogus>".to_string()));
+        assert_eq!(text_to_keys("ab<CR"), Err("<CR".to_string()));
+        assert_eq!(text_to_keys("<C-ab>"), Err("<C-ab>".to_string()));
+    }
+
+    #[test]
+    fn recording() {
+        let mut recording = Recording::new('a');
+        for (i, key) in text_to_keys("cjrt:macro stop")
+            .unwrap()
+            .into_iter()
+            .enumerate()
+        {
+            // `c`, `j`, `rt` and `:macro stop` are separate commands
+            recording.push(key, [0, 1, 2, 4].contains(&i));
+        }
+        assert_eq!(recording.finish(), text_to_keys("cjrt").unwrap());
+    }
+}
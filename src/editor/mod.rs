@@ -0,0 +1,6094 @@
+//! The top-level functionality of Sapling
+
+pub mod bookmarks;
+pub mod clipboard;
+pub mod command_line;
+pub mod completion;
+pub mod config;
+pub mod cursor_style;
+pub mod demo;
+pub mod edit_positions;
+pub mod elide;
+pub mod encoding;
+pub mod events;
+pub mod folds;
+pub mod frames;
+pub mod history;
+pub mod idle;
+pub mod journal;
+pub mod keymap;
+pub mod limits;
+pub mod loader;
+pub mod log;
+pub mod macros;
+pub mod motion_history;
+pub mod notification;
+pub mod outline;
+pub mod palette;
+pub mod pins;
+pub mod preview;
+pub mod progress;
+pub mod prompt;
+pub mod quickfix;
+pub mod registers;
+pub mod render;
+pub mod save;
+pub mod search;
+pub mod status_bar;
+pub mod substitute;
+pub mod table;
+pub mod term_guard;
+pub mod theme;
+pub mod watch;
+
+use crate::ast::commands;
+use crate::ast::display_token::DisplayToken;
+use crate::ast::json_recover::ParseError;
+use crate::ast::pattern::Pattern;
+use crate::ast::schema::Schema;
+use crate::ast::stats::Stats;
+use crate::ast::structured::Fragment;
+use crate::ast::trivia::{self, TriviaMap};
+use crate::ast::{duplicates, restructure, size, Ast};
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use crate::editable_tree::{conformance, Direction, EditableTree};
+use crate::messages::{Catalog, Message};
+use bookmarks::Bookmarks;
+use clipboard::{Clipboard, ClipboardContents};
+use command_line::CommandLine;
+use completion::{Completion, CompletionMenu};
+use config::Config;
+use demo::Demo;
+use edit_positions::EditPositions;
+use encoding::{Encoding, FileFormat, LineEnding};
+use events::{EditEvent, EventSink};
+use folds::Folds;
+use frames::{FrameLimiter, DEFAULT_MAX_FPS};
+use history::HistoryView;
+use idle::{IdleScheduler, IdleTask, DEFAULT_IDLE_DELAY};
+use journal::Journal;
+pub use keymap::{KeyInput, KeyMap, KeySequence, Lookup};
+use loader::{Loaded, Loader};
+use log::{LogBuffer, LogFile, DEFAULT_LOG_CAPACITY, DEFAULT_LOG_FILE_LEVEL};
+use macros::{MacroRegisters, Recording};
+use motion_history::MotionHistory;
+use notification::{Notification, Visibility};
+use outline::{Outline, OUTLINE_DEPTH, OUTLINE_WIDTH};
+use palette::{Palette, PaletteEntry};
+use pins::Pins;
+use preview::Preview;
+use progress::{ProgressSender, ProgressTracker, SPINNER_INTERVAL};
+use prompt::{Answer, Operation, Prompt};
+use quickfix::Quickfix;
+use registers::Registers;
+use search::SearchIndex;
+use status_bar::Segment;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime};
+use table::Table;
+use term_guard::TermGuard;
+use tuikit::prelude::*;
+use watch::Watches;
+
+/// The possible log levels
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum LogLevel {
+    /// Logs that give lots of minute details.  Intended to be used only when debugging Sapling.
+    VerboseDebug = 0,
+    /// Less verbose debugging, intended to be used only when debugging Sapling.
+    Debug = 1,
+    /// Logs that just give information to the user
+    Info = 2,
+    /// Logs that represent warnings about an Error that is either going to happen or might have
+    /// already happened.
+    Warning = 3,
+    /// Logs that will always be logged.
+    Error = 4,
+}
+
+impl LogLevel {
+    /// Returns a [`Color`] with which to display this log entry
+    pub fn to_color(&self) -> Color {
+        match self {
+            LogLevel::VerboseDebug => Color::BLUE,
+            LogLevel::Debug => Color::LIGHT_BLUE,
+            LogLevel::Info => Color::GREEN,
+            LogLevel::Warning => Color::YELLOW,
+            LogLevel::Error => Color::RED,
+        }
+    }
+
+    /// The name of this level in `:set loglevel=`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::VerboseDebug => "verbose",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+
+    /// Returns the level with a given [`name`](LogLevel::name), or [`None`] if there isn't one
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        [
+            LogLevel::VerboseDebug,
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warning,
+            LogLevel::Error,
+        ]
+        .iter()
+        .find(|level| level.name() == name)
+        .cloned()
+    }
+}
+
+/// The possible command typed by user without any parameters.
+/// It can be mapped to a single key.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Command {
+    /// Quit Sapling
+    Quit,
+    /// Replace the selected node, expects an argument
+    Replace,
+    /// Insert a new node, expects an argument
+    InsertChild,
+    /// Move cursor in given direction
+    /// This is not considered a parameter as the direction is still specified by pressing specific
+    /// key.
+    MoveCursor(Direction),
+    /// Undo the last change
+    Undo,
+    /// Redo a change
+    Redo,
+    /// Open, focus or close the outline sidebar
+    ToggleOutline,
+    /// Scroll the view relative to the cursor, or fold the subtree under the cursor (like Vim's
+    /// `z` commands), expects an argument
+    Scroll,
+    /// Search for some text or a kind of node (written like `<array>`), expects a line of text
+    /// terminated by enter
+    Search,
+    /// Move the cursor to the next match of the last search
+    NextMatch,
+    /// Move the cursor to the previous match of the last search
+    PrevMatch,
+    /// Run an ex-style command, expects a line of text terminated by enter
+    CommandLine,
+    /// Move the cursor back to where it was before its last motion
+    MotionBack,
+    /// Move the cursor forward again after a [`Command::MotionBack`]
+    MotionForward,
+    /// Move the cursor to the position of the last edit, or to older edits if repeated
+    OlderEdit,
+    /// Move the cursor forward again after a [`Command::OlderEdit`]
+    NewerEdit,
+    /// Copy the subtree under the cursor to the clipboard
+    Yank,
+    /// Copy the subtree under the cursor to the clipboard, and remove it from the tree
+    Cut,
+    /// Replace the subtree under the cursor with the contents of the clipboard
+    Paste,
+    /// Insert the contents of the clipboard as the next sibling of the cursor
+    PasteAfter,
+    /// Insert the contents of the clipboard as the first child of the cursor
+    PasteChild,
+    /// Make the next yank, cut or paste use a named register instead of the clipboard, expects
+    /// the register's name followed by the command
+    Register,
+    /// Play the keys stored in a macro register, expects an argument
+    PlayMacro,
+    /// Start recording the keys that the user presses into a macro register, expects an argument.
+    /// Whilst a macro is being recorded, this stops the recording instead.
+    RecordMacro,
+    /// Replace the selected node using the same [`char`] as the last replacement
+    RepeatReplace,
+    /// Move the cursor to the nearest enclosing node of some kind, expects the kind's name
+    /// terminated by enter
+    SelectEnclosing,
+    /// Move the next sibling of the cursor's node into it, as its last child
+    Slurp,
+    /// Move the last child of the cursor's node out, so that it becomes the node's next sibling
+    Barf,
+    /// Swap the cursor's node with a sibling, expects the key that moves the cursor to that
+    /// sibling
+    Transpose,
+    /// Merge the cursor's node with its next sibling of the same kind
+    Join,
+    /// Sort the children of the cursor's node, expects the key of the field to sort them by (or
+    /// nothing, to sort them by their own text) terminated by enter
+    Sort,
+    /// Group the children of the cursor's node by the value of a field, expects the field's key
+    /// terminated by enter
+    Group,
+    /// Write the tree to the file that it was opened from
+    Write,
+    /// Give the cursor's node a bookmark, expects the bookmark's name
+    SetBookmark,
+    /// Move the cursor to a bookmarked node, expects the bookmark's name
+    JumpToBookmark,
+    /// Insert a new node (represented by the [`char`]) as the first child of the selected node,
+    /// and start typing its text straight away (e.g. adding a string and typing its contents).
+    /// These aren't bound by default, but can be bound to keys in the keymap config.
+    InsertAndEdit(char),
+    /// Start typing into the text of the node under the cursor (switching to [`Mode::Insert`])
+    EditText,
+    /// Run the command with a given name which the buffer's language provides (see
+    /// [`Ast::language_commands`]).  These aren't bound by default, but can be bound to keys in the
+    /// keymap config.
+    LanguageCommand(String),
+    /// Open the command palette, to find a command by name and run it
+    CommandPalette,
+    /// Open a menu of the structural edits which can be made to the node under the cursor
+    TransformMenu,
+    /// Scroll the view by a line or a page, without moving the cursor
+    ScrollView(ScrollStep),
+    /// Show or hide the log pane
+    ToggleLog,
+    /// Scroll the log pane by a line or a page, towards older (up) or newer (down) entries
+    ScrollLog(ScrollStep),
+}
+
+/// The default key bindings of characters.  Along with [`DEFAULT_KEY_BINDINGS`], these must bind
+/// every [`Command`] exactly once.
+pub const DEFAULT_BINDINGS: &[(char, Command)] = &[
+    ('q', Command::Quit),
+    ('i', Command::InsertChild),
+    ('r', Command::Replace),
+    ('c', Command::MoveCursor(Direction::Down)),
+    ('p', Command::MoveCursor(Direction::Up)),
+    ('k', Command::MoveCursor(Direction::Prev)),
+    ('j', Command::MoveCursor(Direction::Next)),
+    ('u', Command::Undo),
+    ('R', Command::Redo),
+    ('O', Command::ToggleOutline),
+    ('z', Command::Scroll),
+    ('/', Command::Search),
+    ('n', Command::NextMatch),
+    ('N', Command::PrevMatch),
+    (':', Command::CommandLine),
+    ('b', Command::MotionBack),
+    ('B', Command::MotionForward),
+    (';', Command::OlderEdit),
+    (',', Command::NewerEdit),
+    ('y', Command::Yank),
+    ('d', Command::Cut),
+    ('v', Command::Paste),
+    ('a', Command::PasteAfter),
+    ('V', Command::PasteChild),
+    ('"', Command::Register),
+    ('@', Command::PlayMacro),
+    ('M', Command::RecordMacro),
+    ('.', Command::RepeatReplace),
+    ('e', Command::SelectEnclosing),
+    (')', Command::Slurp),
+    ('(', Command::Barf),
+    ('t', Command::Transpose),
+    ('J', Command::Join),
+    ('s', Command::Sort),
+    ('g', Command::Group),
+    ('w', Command::Write),
+    ('m', Command::SetBookmark),
+    ('\'', Command::JumpToBookmark),
+    ('I', Command::EditText),
+    ('C', Command::CommandPalette),
+    ('T', Command::TransformMenu),
+    ('L', Command::ToggleLog),
+];
+
+/// The default key bindings of keys which aren't characters
+pub const DEFAULT_KEY_BINDINGS: &[(Key, Command)] = &[
+    (Key::Ctrl('e'), Command::ScrollView(ScrollStep::LineDown)),
+    (Key::Ctrl('y'), Command::ScrollView(ScrollStep::LineUp)),
+    (Key::PageDown, Command::ScrollView(ScrollStep::PageDown)),
+    (Key::PageUp, Command::ScrollView(ScrollStep::PageUp)),
+    (Key::AltDown, Command::ScrollLog(ScrollStep::LineDown)),
+    (Key::AltUp, Command::ScrollLog(ScrollStep::LineUp)),
+    (Key::AltPageDown, Command::ScrollLog(ScrollStep::PageDown)),
+    (Key::AltPageUp, Command::ScrollLog(ScrollStep::PageUp)),
+];
+
+pub fn default_keymap() -> KeyMap {
+    let char_bindings = DEFAULT_BINDINGS
+        .iter()
+        .map(|(c, command)| (vec![KeyInput::from(*c)], command.clone()));
+    let key_bindings = DEFAULT_KEY_BINDINGS
+        .iter()
+        .map(|(key, command)| (vec![KeyInput::from(*key)], command.clone()));
+    char_bindings.chain(key_bindings).collect()
+}
+
+/// The contents of the command line whilst the REPL is waiting for an expression
+const REPL_COMMAND: &str = ":eval ";
+
+/// The most times that a count can repeat an action, so that a mistyped count can't make Sapling
+/// hang
+const MAX_REPEAT_COUNT: usize = 1000;
+
+/// How often the countdown of a command which is waiting for its timeout is redrawn
+const COUNTDOWN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The places, relative to the cursor, that copied subtrees can be pasted
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PastePlace {
+    /// Replace the node under the cursor
+    Replace,
+    /// Insert as the next sibling of the node under the cursor
+    After,
+    /// Insert as the first child of the node under the cursor
+    Child,
+}
+
+/// Where the cursor lands after a node is inserted or replaced
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Landing {
+    /// Stay on the node which the new node was inserted into.  After a replace, move to the
+    /// parent of the new node (unless it's the root).
+    Parent,
+    /// Move to the new node itself
+    NewNode,
+    /// Move into the new node, to the first place in it which should be filled in (see
+    /// [`Ast::new_child`]).  Nodes created by a replace don't have anything to fill in, so this
+    /// stays on the new node.
+    #[default]
+    FirstHole,
+}
+
+impl Landing {
+    /// The name of this landing in `:set landing=`
+    pub fn name(self) -> &'static str {
+        match self {
+            Landing::Parent => "parent",
+            Landing::NewNode => "new",
+            Landing::FirstHole => "hole",
+        }
+    }
+
+    /// Returns the landing with a given [`name`](Landing::name), or [`None`] if there isn't one
+    pub fn from_name(name: &str) -> Option<Landing> {
+        [Landing::Parent, Landing::NewNode, Landing::FirstHole]
+            .iter()
+            .copied()
+            .find(|landing| landing.name() == name)
+    }
+}
+
+/// What choosing an entry of the command palette does
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum PaletteAction {
+    /// Type these keys, as if the user had pressed them
+    Keys(KeySequence),
+    /// Run the language command with this name
+    LanguageCommand(String),
+    /// Perform this action, as if its command had been typed
+    Perform(Action),
+}
+
+/// The modes of the editor, which decide what key presses do
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    /// Key presses are built up into commands (see [`parse_command`])
+    Normal,
+    /// Key presses type into the text of the node under the cursor, until enter or escape is
+    /// pressed
+    Insert,
+    /// Key presses type a line command (like `:w` or a search) into the command box, which is run
+    /// when enter is pressed
+    Command,
+}
+
+/// The places on the screen that the scroll commands can move the cursor to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScrollAnchor {
+    /// Scroll so that the first line of the cursor is at the top of the screen
+    Top,
+    /// Scroll so that the first line of the cursor is in the middle of the screen
+    Center,
+    /// Scroll so that the first line of the cursor is at the bottom of the screen
+    Bottom,
+}
+
+impl ScrollAnchor {
+    /// Returns the `ScrollAnchor` represented by a [`char`] typed after the `z` command (`t`,
+    /// `z` or `b`, like Vim), or [`None`] if that [`char`] doesn't represent an anchor.
+    pub fn from_char(c: char) -> Option<ScrollAnchor> {
+        match c {
+            't' => Some(ScrollAnchor::Top),
+            'z' => Some(ScrollAnchor::Center),
+            'b' => Some(ScrollAnchor::Bottom),
+            _ => None,
+        }
+    }
+}
+
+/// The amounts that the view can be scrolled by without moving the cursor, like Vim's `Ctrl-E`,
+/// `Ctrl-Y`, `PageDown` and `PageUp`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ScrollStep {
+    LineDown,
+    LineUp,
+    /// Scroll down by the height of the view, keeping one line of the old view on screen
+    PageDown,
+    /// Scroll up by the height of the view, keeping one line of the old view on screen
+    PageUp,
+}
+
+/// The possible meanings of a user-typed command
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Action {
+    /// The user typed a command that isn't defined, but the command box should still be cleared
+    Undefined,
+    /// Quit Sapling, first asking whether to throw away any unsaved changes
+    Quit,
+    /// Quit Sapling, throwing away any unsaved changes without asking
+    ForceQuit,
+    /// Write the tree like [`Action::Write`], and then quit if it was written
+    WriteQuit(Option<String>),
+    /// Perform an action a given number of times (typed as a count before the action's command,
+    /// like the `3` in `3j`), stopping early if one of the times fails
+    Repeat(usize, Box<Action>),
+    /// Replace the selected node with a node represented by some [`char`]
+    Replace(char),
+    /// Insert a new node (given by some [`char`]) as the first child of the selected node
+    InsertChild(char),
+    /// Insert a new node like [`Action::InsertChild`], and then start typing its text if it has
+    /// any
+    InsertAndEdit(char),
+    /// Start typing into the text of the node under the cursor
+    EditText,
+    /// Run the command with a given name which the buffer's language provides
+    RunLanguageCommand(String),
+    /// Open the command palette
+    OpenPalette,
+    /// Open the menu of edits which can be made to the node under the cursor
+    OpenTransformMenu,
+    /// Wrap the node under the cursor in a new node (given by some [`char`])
+    Wrap(char),
+    /// Replace the node under the cursor with its children
+    Unwrap,
+    /// Replace the parent of the node under the cursor with that node
+    Raise,
+    /// Convert the node under the cursor into a node of another kind (given by some [`char`]),
+    /// keeping its children
+    Convert(char),
+    /// Move the cursor a given number of steps in a given direction
+    MoveCursor(Direction, usize),
+    /// Undo the last change
+    Undo,
+    /// Redo a change
+    Redo,
+    /// Open, focus or close the outline sidebar
+    ToggleOutline,
+    /// Scroll the view so that the cursor is at a given place on the screen
+    Scroll(ScrollAnchor),
+    /// Fold the subtree under the cursor into a placeholder, or unfold it if it's folded
+    ToggleFold,
+    /// Unfold every folded subtree
+    OpenAllFolds,
+    /// Fold every part of the tree which goes over the limits on its shape
+    FoldOversized,
+    /// Warn about trees whose nodes are nested more than this many levels deep
+    SetDepthLimit(usize),
+    /// Warn about trees which have nodes with more than this many children
+    SetChildLimit(usize),
+    /// Scroll the view by a given number of lines or pages
+    ScrollView(ScrollStep, usize),
+    /// Show or hide the log pane
+    ToggleLog,
+    /// Scroll the log pane a given number of lines or pages
+    ScrollLog(ScrollStep, usize),
+    /// Only show the log entries at or above a given level
+    SetLogLevel(LogLevel),
+    /// Change where the cursor lands after a node is inserted or replaced
+    SetLanding(Landing),
+    /// Redraw the screen at most a given number of times per second
+    SetMaxFps(u32),
+    /// Highlight every match of a search query, and move the cursor to the next match
+    Search(String),
+    /// Move the cursor to the next match of the last search, or the previous match if `false`
+    StepSearch(bool),
+    /// Remove the highlighting from the last search
+    ClearSearch,
+    /// Move the cursor back to where it was before its last motion
+    MotionBack,
+    /// Move the cursor forward again after an [`Action::MotionBack`]
+    MotionForward,
+    /// Move the cursor to the position of the last edit, or to older edits if repeated
+    OlderEdit,
+    /// Move the cursor forward again after an [`Action::OlderEdit`]
+    NewerEdit,
+    /// Copy the subtree under the cursor to a named register, or to the clipboard if no register
+    /// is given
+    Yank(Option<char>),
+    /// Copy the subtree under the cursor like [`Action::Yank`], and remove it from the tree
+    Cut(Option<char>),
+    /// Paste the contents of a named register (or the clipboard if no register is given) at some
+    /// place relative to the cursor
+    Paste(PastePlace, Option<char>),
+    /// Replace the subtree under the cursor with the document in the file at a given path
+    ReadFile(String),
+    /// Play the keys stored in the macro register named by some [`char`]
+    PlayMacro(char),
+    /// Put the text form of a macro into the command line, so that it can be edited and stored
+    /// again with [`Action::SetMacro`]
+    EditMacro(char),
+    /// Store the macro given in text form in the register named by some [`char`]
+    SetMacro(char, String),
+    /// Start recording the keys that the user presses into the macro register named by some
+    /// [`char`]
+    RecordMacro(char),
+    /// Stop recording a macro, and store the keys pressed since it started
+    StopRecording,
+    /// Replace the selected node using the same [`char`] as the last replacement
+    RepeatReplace,
+    /// Move the next sibling of the selected node into it, as its last child
+    Slurp,
+    /// Move the last child of the selected node out, so that it becomes the node's next sibling
+    Barf,
+    /// Swap the selected node with its next sibling (if `true`) or its previous sibling, keeping
+    /// the cursor on the selected node
+    Transpose(bool),
+    /// Merge the selected node with its next sibling, which must be of the same kind
+    Join,
+    /// Evaluate an expression with the tree as its environment, and log the result
+    Evaluate(String),
+    /// Open or close the REPL, which keeps the command line ready to evaluate expressions
+    ToggleRepl,
+    /// Move the cursor to the nearest ancestor with a given [`kind_name`](Ast::kind_name)
+    SelectEnclosing(String),
+    /// Re-read the text of the buffer as the language with a given name
+    SetLanguage(String),
+    /// Change the encoding that the buffer will be saved in
+    SetEncoding(Encoding),
+    /// Change the line endings that the buffer will be saved with
+    SetLineEnding(LineEnding),
+    /// Render only this many levels below the cursor's path, or the whole tree if [`None`]
+    SetMaxDepth(Option<usize>),
+    /// Show (if `true`) or hide the line numbers in the gutter of the tree view
+    SetLineNumbers(bool),
+    /// Choose which segments the status bar shows, in order
+    SetStatusSegments(Vec<Segment>),
+    /// Join the lines of nodes which fit within this many columns, or stop joining lines if
+    /// [`None`]
+    SetLineWidth(Option<usize>),
+    /// Bind a sequence of keys to a command, replacing any command they were bound to
+    Map(KeySequence, Command),
+    /// Run commands which are the start of longer bindings once no key has been pressed for this
+    /// long, or only when the next key is pressed if [`None`]
+    SetCommandTimeout(Option<Duration>),
+    /// Turn the table view on or off
+    ToggleTable,
+    /// Make the next edit inside a child of the cursor to every child of the cursor.  Replacing,
+    /// inserting, cutting, wrapping, unwrapping, converting, raising, slurping, barfing, joining
+    /// and transposing can be batched; any other edit is only made once.
+    BatchEdit,
+    /// Undo a given number of changes at once
+    UndoSteps(usize),
+    /// Redo a given number of changes at once
+    RedoSteps(usize),
+    /// Move back or forward through the history to the state which was last saved
+    UndoToSaved,
+    /// Open the history view
+    ShowHistory,
+    /// Check that moving to each entry in the history restores its tree and cursor
+    CheckHistory,
+    /// Move the cursor to the nodes matched by a path query
+    Query(String),
+    /// Load the schema from the file at a given path
+    LoadSchema(String),
+    /// Write the active keymap to a TOML file at a given path, so that it can be shared
+    ExportKeymap(String),
+    /// Replace the active keymap with the one in the TOML file at a given path
+    ImportKeymap(String),
+    /// Replace the tree with the document in the file at a given path, which is loaded in the
+    /// background
+    Open(String),
+    /// Replace the tree with a new document, made from the language's template
+    NewDocument,
+    /// Write the tree to a file, which becomes the buffer's file if it doesn't have one yet.
+    /// Without a path, the tree is written to the buffer's file.
+    Write(Option<String>),
+    /// Move the cursor to the next (if `true`) or previous entry of the quickfix list
+    StepQuickfix(bool),
+    /// Open a nested session to edit the text inside the selected node as a tree, which is
+    /// written back into the node when the nested session is quit
+    EditEmbedded,
+    /// Show the value of an expression in the watches pane, updating it after every edit
+    Watch(String),
+    /// Stop watching the expression with a given number (counting from 1), or every expression
+    /// if no number is given
+    Unwatch(Option<usize>),
+    /// Keep the text of the selected node on screen in the pins pane
+    Pin,
+    /// Unpin the node with a given number (counting from 1), or every node if no number is given
+    Unpin(Option<usize>),
+    /// Give the selected node a bookmark with a given name, which is saved with the file
+    SetBookmark(char),
+    /// Move the cursor to the node with a given bookmark
+    JumpToBookmark(char),
+    /// Sort the children of the selected node by the value of the field with a given key inside
+    /// each of them, or by their own text if no key is given
+    Sort(Option<String>),
+    /// Group the children of the selected node into new nodes, one for each value of the field
+    /// with a given key
+    Group(String),
+    /// Rename the selected symbol and every reference to it
+    Rename(String),
+    /// Move the cursor to the node which the selected reference refers to
+    GoToDefinition,
+    /// Put every reference to the selected node in the quickfix list
+    FindReferences,
+    /// Log statistics about the leaves under the nodes in the quickfix list, or under the
+    /// selected node if the quickfix list is empty
+    ShowStats,
+    /// Log the latest entries of the session's journal
+    ShowJournal,
+    /// Highlight every node which has a duplicate sibling, and put them in the quickfix list
+    FindDuplicates,
+    /// Replace the field containing the cursor with the field of the same name in the next (if
+    /// `true`) or previous sibling record
+    CopyFromSibling(bool),
+}
+
+impl Action {
+    /// Returns `true` if this action changes the tree (and so throws away any changes which could
+    /// have been redone)
+    fn is_edit(&self) -> bool {
+        if let Action::Repeat(_, action) = self {
+            return action.is_edit();
+        }
+        matches!(
+            self,
+            Action::Replace(_)
+                | Action::InsertChild(_)
+                | Action::InsertAndEdit(_)
+                | Action::EditText
+                | Action::Cut(_)
+                | Action::Paste(..)
+                | Action::ReadFile(_)
+                | Action::Slurp
+                | Action::Barf
+                | Action::Transpose(_)
+                | Action::Join
+                | Action::SetLanguage(_)
+                | Action::CopyFromSibling(_)
+                | Action::Sort(_)
+                | Action::Group(_)
+                | Action::Rename(_)
+                | Action::RunLanguageCommand(_)
+                | Action::Wrap(_)
+                | Action::Unwrap
+                | Action::Raise
+                | Action::Convert(_)
+        )
+    }
+}
+
+/// Attempt to convert a command as a `&`[`str`] into an [`Action`].
+/// This parses the string from the start, and returns when it finds a valid command.
+///
+/// Therefore, `"q489flshb"` will be treated like `"q"`, and will return `Some(Action::Quit)` even
+/// though `"q489flshb"` is not technically valid.
+/// This function is run every time the user types a command character, and so the user would not
+/// be able to input `"q489flshb"` to this function because doing so would require them to first
+/// input every possible prefix of `"q489flshb"`, including `"q"`.
+///
+/// This returns:
+/// - [`None`] if the command is incomplete.
+/// - [`Action::Undefined`] if the command is not defined (like the command "X").
+/// - The corresponding [`Action`], otherwise.
+///
+/// A command can be preceded by a count (as long as the digits aren't bound to commands), so
+/// `"3j"` moves the cursor three siblings along and `"5u"` undoes five changes.  Commands can be
+/// bound to sequences of keys, and the command stays incomplete whilst the keys typed so far are
+/// the start of a longer binding (see [`KeyMap::lookup`]).
+fn parse_command(keymap: &KeyMap, command: &[KeyInput]) -> Option<Action> {
+    parse_command_with(keymap, command, false)
+}
+
+/// Like [`parse_command`], but for a command which has timed out, so that keys which are both a
+/// binding and the start of a longer one run the shorter binding (see [`KeyMap::lookup_final`])
+fn parse_timed_out_command(keymap: &KeyMap, command: &[KeyInput]) -> Option<Action> {
+    parse_command_with(keymap, command, true)
+}
+
+fn parse_command_with(keymap: &KeyMap, command: &[KeyInput], is_final: bool) -> Option<Action> {
+    let lookup = |keys| match is_final {
+        true => keymap.lookup_final(keys),
+        false => keymap.lookup(keys),
+    };
+    let (count, rest) = split_count(keymap, command);
+    if !count.is_empty() {
+        let action = parse_command_with(keymap, rest, is_final)?;
+        return Some(match count.parse::<usize>() {
+            Ok(count) => with_count(action, count),
+            Err(_) => Action::Undefined,
+        });
+    }
+
+    // The arguments of most commands are characters, so any other key makes the command undefined
+    let char_arg =
+        |key: KeyInput, action: fn(char) -> Action| key.as_char().map_or(Action::Undefined, action);
+
+    // Consume the keys bound to the command, leaving its arguments
+    let (bound_command, args) = match lookup(command) {
+        Lookup::Incomplete => return None,
+        Lookup::Bound(bound_command, len) => (bound_command, &command[len..]),
+        Lookup::Unbound => return Some(Action::Undefined),
+    };
+    let mut keys = args.iter().copied();
+    match bound_command {
+        // "q" quits Sapling
+        Command::Quit => {
+            return Some(Action::Quit);
+        }
+        Command::InsertAndEdit(insert_char) => {
+            return Some(Action::InsertAndEdit(*insert_char));
+        }
+        Command::EditText => {
+            return Some(Action::EditText);
+        }
+        Command::LanguageCommand(name) => {
+            return Some(Action::RunLanguageCommand(name.clone()));
+        }
+        Command::CommandPalette => {
+            return Some(Action::OpenPalette);
+        }
+        Command::TransformMenu => {
+            return Some(Action::OpenTransformMenu);
+        }
+        Command::ScrollView(step) => {
+            return Some(Action::ScrollView(*step, 1));
+        }
+        Command::ToggleLog => {
+            return Some(Action::ToggleLog);
+        }
+        Command::ScrollLog(step) => {
+            return Some(Action::ScrollLog(*step, 1));
+        }
+        Command::InsertChild => {
+            // Consume the key after the command
+            if let Some(insert_key) = keys.next() {
+                return Some(char_arg(insert_key, Action::InsertChild));
+            }
+        }
+        Command::Replace => {
+            // Consume the key after the command
+            if let Some(replace_key) = keys.next() {
+                return Some(char_arg(replace_key, Action::Replace));
+            }
+        }
+        Command::MoveCursor(direction) => {
+            return Some(Action::MoveCursor(*direction, 1));
+        }
+        Command::Undo => {
+            return Some(Action::Undo);
+        }
+        Command::Redo => {
+            return Some(Action::Redo);
+        }
+        Command::ToggleOutline => {
+            return Some(Action::ToggleOutline);
+        }
+        Command::MotionBack => {
+            return Some(Action::MotionBack);
+        }
+        Command::MotionForward => {
+            return Some(Action::MotionForward);
+        }
+        Command::OlderEdit => {
+            return Some(Action::OlderEdit);
+        }
+        Command::NextMatch => {
+            return Some(Action::StepSearch(true));
+        }
+        Command::PrevMatch => {
+            return Some(Action::StepSearch(false));
+        }
+        Command::NewerEdit => {
+            return Some(Action::NewerEdit);
+        }
+        Command::Yank
+        | Command::Cut
+        | Command::Paste
+        | Command::PasteAfter
+        | Command::PasteChild => {
+            return register_action(Some(bound_command), None);
+        }
+        Command::Register => {
+            // Consume the register's name, and then the command which uses it
+            let register = match keys.next()?.as_char() {
+                Some(register) if Registers::is_name(register) => register,
+                _ => return Some(Action::Undefined),
+            };
+            return match lookup(&args[1..]) {
+                Lookup::Incomplete => None,
+                Lookup::Bound(command, _) => Some(
+                    register_action(Some(command), Some(register)).unwrap_or(Action::Undefined),
+                ),
+                Lookup::Unbound => Some(Action::Undefined),
+            };
+        }
+        Command::RepeatReplace => {
+            return Some(Action::RepeatReplace);
+        }
+        Command::Slurp => {
+            return Some(Action::Slurp);
+        }
+        Command::Barf => {
+            return Some(Action::Barf);
+        }
+        Command::Join => {
+            return Some(Action::Join);
+        }
+        Command::Write => {
+            return Some(Action::Write(None));
+        }
+        Command::SetBookmark | Command::JumpToBookmark => {
+            // Consume the bookmark's name
+            let name = match keys.next()?.as_char() {
+                Some(name) if Bookmarks::is_name(name) => name,
+                _ => return Some(Action::Undefined),
+            };
+            return Some(match bound_command {
+                Command::SetBookmark => Action::SetBookmark(name),
+                _ => Action::JumpToBookmark(name),
+            });
+        }
+        Command::Transpose => {
+            // Consume the keys of the movement which says which sibling to use
+            return match lookup(args) {
+                Lookup::Incomplete => None,
+                Lookup::Bound(Command::MoveCursor(Direction::Next), _) => {
+                    Some(Action::Transpose(true))
+                }
+                Lookup::Bound(Command::MoveCursor(Direction::Prev), _) => {
+                    Some(Action::Transpose(false))
+                }
+                _ => Some(Action::Undefined),
+            };
+        }
+        Command::PlayMacro => {
+            // Consume the key after the command
+            if let Some(register) = keys.next() {
+                return Some(char_arg(register, Action::PlayMacro));
+            }
+        }
+        Command::RecordMacro => {
+            // Consume the key after the command
+            if let Some(register) = keys.next() {
+                return Some(char_arg(register, Action::RecordMacro));
+            }
+        }
+        Command::Scroll => {
+            // Consume the key after the command
+            if let Some(anchor_key) = keys.next() {
+                return Some(match anchor_key.as_char() {
+                    Some('a') => Action::ToggleFold,
+                    Some('R') => Action::OpenAllFolds,
+                    c => c
+                        .and_then(ScrollAnchor::from_char)
+                        .map_or(Action::Undefined, Action::Scroll),
+                });
+            }
+        }
+        // Line commands are never complete until enter is pressed (see `parse_line_command`)
+        Command::Search
+        | Command::CommandLine
+        | Command::SelectEnclosing
+        | Command::Sort
+        | Command::Group => {}
+    }
+
+    None
+}
+
+/// Returns how many nodes in the tree under `root` aren't valid (see [`Ast::is_error`])
+fn count_invalid<'arena, Node: Ast<'arena>>(root: &'arena Node) -> usize {
+    // The tree is walked without recursion, because it might be deep enough to overflow the stack
+    let mut num_invalid = 0;
+    let mut nodes_to_visit = vec![root];
+    while let Some(node) = nodes_to_visit.pop() {
+        if node.is_error() {
+            num_invalid += 1;
+        }
+        nodes_to_visit.extend(node.children().iter().copied());
+    }
+    num_invalid
+}
+
+/// Splits the count (the digits which aren't bound to commands) off the front of a command,
+/// returning the count's digits and the rest of the command
+fn split_count<'c>(keymap: &KeyMap, command: &'c [KeyInput]) -> (String, &'c [KeyInput]) {
+    let count: String = command
+        .iter()
+        .enumerate()
+        .map_while(|(i, key)| {
+            let c = key.as_char()?;
+            Some(c)
+                .filter(|c| c.is_ascii_digit() && (i > 0 || *c != '0') && !keymap.contains_key(*c))
+        })
+        .collect();
+    let rest = &command[count.len()..];
+    (count, rest)
+}
+
+/// Returns the [`Action`] of a command which was preceded by a count.  Commands for which a count
+/// makes no sense (like quitting) ignore it.
+fn with_count(action: Action, count: usize) -> Action {
+    match action {
+        Action::MoveCursor(direction, _) => Action::MoveCursor(direction, count),
+        Action::ScrollView(step, _) => Action::ScrollView(step, count),
+        Action::ScrollLog(step, _) => Action::ScrollLog(step, count),
+        Action::Undo => Action::UndoSteps(count),
+        Action::Redo => Action::RedoSteps(count),
+        Action::InsertChild(_)
+        | Action::Paste(PastePlace::After, _)
+        | Action::Paste(PastePlace::Child, _)
+        | Action::Slurp
+        | Action::Barf
+        | Action::Transpose(_)
+        | Action::Join
+        | Action::PlayMacro(_)
+        | Action::MotionBack
+        | Action::MotionForward
+        | Action::OlderEdit
+        | Action::NewerEdit
+        | Action::StepSearch(_) => Action::Repeat(count.min(MAX_REPEAT_COUNT), Box::new(action)),
+        action => action,
+    }
+}
+
+/// Returns the [`Action`] of a command which can use a register (like yanking), or [`None`] if the
+/// command can't use a register
+fn register_action(command: Option<&Command>, register: Option<char>) -> Option<Action> {
+    Some(match command? {
+        Command::Yank => Action::Yank(register),
+        Command::Cut => Action::Cut(register),
+        Command::Paste => Action::Paste(PastePlace::Replace, register),
+        Command::PasteAfter => Action::Paste(PastePlace::After, register),
+        Command::PasteChild => Action::Paste(PastePlace::Child, register),
+        _ => return None,
+    })
+}
+
+/// Attempt to convert a line command (i.e. a command that is terminated by the user pressing
+/// enter) into an [`Action`].  This returns [`None`] if `command` isn't a line command, in which
+/// case pressing enter has no effect.
+fn parse_line_command(keymap: &KeyMap, command: &[KeyInput]) -> Option<Action> {
+    // Once enter is pressed no more keys are coming, so a line command which is also the start of
+    // a longer binding (like `g` when `gg` is bound) is complete
+    let (bound_command, rest) = match keymap.lookup_final(command) {
+        Lookup::Bound(bound_command, len) => (bound_command, &command[len..]),
+        _ => return None,
+    };
+    // Only characters are added to line commands (see `Editor::handle_key`)
+    let line: String = rest.iter().filter_map(|key| key.as_char()).collect();
+    match bound_command {
+        Command::Search => Some(Action::Search(line)),
+        Command::CommandLine => Some(parse_ex_command(&line)),
+        Command::SelectEnclosing => Some(Action::SelectEnclosing(line.trim().to_string())),
+        Command::Sort => Some(parse_sort_key(&line)),
+        Command::Group => Some(parse_group_key(&line)),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `command` (the keys typed so far) is a line command, which is typed as a
+/// line of text and completed by pressing enter.  Keys which are the start of a longer binding as
+/// well as a line command count as a line command.
+fn is_line_command(keymap: &KeyMap, command: &[KeyInput]) -> bool {
+    matches!(
+        keymap.lookup_final(command),
+        Lookup::Bound(Command::Search, _)
+            | Lookup::Bound(Command::CommandLine, _)
+            | Lookup::Bound(Command::SelectEnclosing, _)
+            | Lookup::Bound(Command::Sort, _)
+            | Lookup::Bound(Command::Group, _)
+    )
+}
+
+/// Returns the keys which type out some text, e.g. to fill the command box
+fn text_keys(text: &str) -> Vec<KeyInput> {
+    text.chars().map(KeyInput::from).collect()
+}
+
+/// Convert the text typed after `:` into an [`Action`], returning [`Action::Undefined`] if the
+/// text isn't a valid command.
+fn parse_ex_command(line: &str) -> Action {
+    // Expressions are everything after the command's name, so that their spacing is kept
+    if let Some(expression) = strip_command_name(line, "eval") {
+        return Action::Evaluate(expression.to_string());
+    }
+    if let Some(expression) = strip_command_name(line, "watch") {
+        return Action::Watch(expression.to_string());
+    }
+    if let Some(query) = strip_command_name(line, "jq") {
+        return Action::Query(query.to_string());
+    }
+    match strip_command_name(line, "read").or_else(|| strip_command_name(line, "r")) {
+        Some("") => return Action::Undefined,
+        Some(path) => return Action::ReadFile(path.to_string()),
+        None => {}
+    }
+    match strip_command_name(line, "wq") {
+        Some("") => return Action::WriteQuit(None),
+        Some(path) => return Action::WriteQuit(Some(path.to_string())),
+        None => {}
+    }
+    match strip_command_name(line, "write").or_else(|| strip_command_name(line, "w")) {
+        Some("") => return Action::Write(None),
+        Some(path) => return Action::Write(Some(path.to_string())),
+        None => {}
+    }
+    match strip_command_name(line, "open") {
+        Some("") => return Action::Undefined,
+        Some(path) => return Action::Open(path.to_string()),
+        None => {}
+    }
+    if let Some(key) = strip_command_name(line, "sort") {
+        return parse_sort_key(key);
+    }
+    if let Some(key) = strip_command_name(line, "group") {
+        return parse_group_key(key);
+    }
+    match strip_command_name(line, "rename") {
+        Some("") => return Action::Undefined,
+        Some(name) => return Action::Rename(name.to_string()),
+        None => {}
+    }
+    match strip_command_name(line, "schema") {
+        Some("") => return Action::Undefined,
+        Some(path) => return Action::LoadSchema(path.to_string()),
+        None => {}
+    }
+    if let Some(rest) = strip_command_name(line, "keymap") {
+        return match rest.split_once(char::is_whitespace) {
+            Some(("export", path)) => Action::ExportKeymap(path.trim().to_string()),
+            Some(("import", path)) => Action::ImportKeymap(path.trim().to_string()),
+            _ => Action::Undefined,
+        };
+    }
+    if let Some(binding) = strip_command_name(line, "map") {
+        let mut words = binding.split_whitespace();
+        let keys = words.next().and_then(KeyInput::parse_sequence);
+        let command = words.next().and_then(keymap::parse_command_name);
+        return match (keys, command, words.next()) {
+            (Some(keys), Some(command), None) => Action::Map(keys, command),
+            _ => Action::Undefined,
+        };
+    }
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("q"), None, _) | (Some("quit"), None, _) => Action::Quit,
+        (Some("q!"), None, _) | (Some("quit!"), None, _) => Action::ForceQuit,
+        (Some("noh"), None, _) | (Some("nohlsearch"), None, _) => Action::ClearSearch,
+        (Some("repl"), None, _) => Action::ToggleRepl,
+        (Some("embedded"), None, _) => Action::EditEmbedded,
+        (Some("table"), None, _) => Action::ToggleTable,
+        (Some("each"), None, _) => Action::BatchEdit,
+        (Some("dups"), None, _) => Action::FindDuplicates,
+        (Some("stats"), None, _) => Action::ShowStats,
+        (Some("journal"), None, _) => Action::ShowJournal,
+        (Some("log"), None, _) => Action::ToggleLog,
+        (Some("fold"), None, _) => Action::ToggleFold,
+        (Some("unfold"), None, _) => Action::OpenAllFolds,
+        (Some("fold"), Some("oversized"), None) => Action::FoldOversized,
+        (Some("transforms"), None, _) => Action::OpenTransformMenu,
+        (Some("unwrap"), None, _) => Action::Unwrap,
+        (Some("raise"), None, _) => Action::Raise,
+        (Some(name @ ("wrap" | "convert")), Some(kind), None) => {
+            let mut chars = kind.chars();
+            match (chars.next(), chars.next(), name) {
+                (Some(c), None, "wrap") => Action::Wrap(c),
+                (Some(c), None, _) => Action::Convert(c),
+                _ => Action::Undefined,
+            }
+        }
+        (Some("new"), None, _) => Action::NewDocument,
+        (Some("def"), None, _) | (Some("definition"), None, _) => Action::GoToDefinition,
+        (Some("refs"), None, _) | (Some("references"), None, _) => Action::FindReferences,
+        (Some("run"), Some(name), None) => Action::RunLanguageCommand(name.to_string()),
+        (Some("copy-up"), None, _) => Action::CopyFromSibling(false),
+        (Some("copy-down"), None, _) => Action::CopyFromSibling(true),
+        (Some("cn"), None, _) | (Some("cnext"), None, _) => Action::StepQuickfix(true),
+        (Some("cp"), None, _) | (Some("cprevious"), None, _) => Action::StepQuickfix(false),
+        (Some("set"), Some("number"), None) => Action::SetLineNumbers(true),
+        (Some("set"), Some("nonumber"), None) => Action::SetLineNumbers(false),
+        (Some("set"), Some(option), None) => match option.split_once('=') {
+            Some(("language", name)) if !name.is_empty() => Action::SetLanguage(name.to_string()),
+            Some(("encoding", name)) => {
+                Encoding::from_name(name).map_or(Action::Undefined, Action::SetEncoding)
+            }
+            Some(("newlines", name)) => {
+                LineEnding::from_name(name).map_or(Action::Undefined, Action::SetLineEnding)
+            }
+            Some(("maxdepth", "off")) => Action::SetMaxDepth(None),
+            Some(("maxdepth", depth)) => depth
+                .parse()
+                .map_or(Action::Undefined, |depth| Action::SetMaxDepth(Some(depth))),
+            Some(("width", "off")) => Action::SetLineWidth(None),
+            Some(("width", width)) => width
+                .parse()
+                .map_or(Action::Undefined, |width| Action::SetLineWidth(Some(width))),
+            Some(("timeout", "off")) => Action::SetCommandTimeout(None),
+            Some(("timeout", ms)) => ms.parse().map_or(Action::Undefined, |ms| {
+                Action::SetCommandTimeout(Some(Duration::from_millis(ms)))
+            }),
+            Some(("fps", fps)) => match fps.parse() {
+                Ok(fps) if fps > 0 => Action::SetMaxFps(fps),
+                _ => Action::Undefined,
+            },
+            Some(("loglevel", name)) => {
+                LogLevel::from_name(name).map_or(Action::Undefined, Action::SetLogLevel)
+            }
+            Some(("status", names)) => status_bar::parse_segments(names)
+                .map_or(Action::Undefined, Action::SetStatusSegments),
+            Some(("landing", name)) => {
+                Landing::from_name(name).map_or(Action::Undefined, Action::SetLanding)
+            }
+            Some(("depthlimit", depth)) => match depth.parse() {
+                Ok(depth) if depth > 0 => Action::SetDepthLimit(depth),
+                _ => Action::Undefined,
+            },
+            Some(("childlimit", count)) => match count.parse() {
+                Ok(count) if count > 0 => Action::SetChildLimit(count),
+                _ => Action::Undefined,
+            },
+            _ => Action::Undefined,
+        },
+        (Some("undo"), None, _) => Action::Undo,
+        (Some("redo"), None, _) => Action::Redo,
+        (Some(name @ ("undo" | "redo")), Some(count), None) => match count.parse::<usize>() {
+            Ok(count) if count > 0 && name == "undo" => Action::UndoSteps(count),
+            Ok(count) if count > 0 => Action::RedoSteps(count),
+            _ => Action::Undefined,
+        },
+        (Some("undo-to-saved"), None, _) => Action::UndoToSaved,
+        (Some("history"), None, _) => Action::ShowHistory,
+        (Some("history"), Some("check"), None) => Action::CheckHistory,
+        (Some("pin"), None, _) => Action::Pin,
+        (Some("unpin"), None, _) => Action::Unpin(None),
+        (Some("unpin"), Some(number), None) => match number.parse::<usize>() {
+            Ok(number) if number > 0 => Action::Unpin(Some(number)),
+            _ => Action::Undefined,
+        },
+        (Some("unwatch"), None, _) => Action::Unwatch(None),
+        (Some("unwatch"), Some(number), None) => match number.parse::<usize>() {
+            Ok(number) if number > 0 => Action::Unwatch(Some(number)),
+            _ => Action::Undefined,
+        },
+        (Some("macro"), Some(subcommand), Some(register)) => {
+            let mut register_chars = register.chars();
+            let register = match (register_chars.next(), register_chars.next()) {
+                (Some(c), None) => c,
+                _ => return Action::Undefined,
+            };
+            // The macro's text is everything after the register, so that it can contain spaces
+            let text = words.collect::<Vec<_>>().join(" ");
+            match subcommand {
+                "edit" if text.is_empty() => Action::EditMacro(register),
+                "set" => Action::SetMacro(register, text),
+                "record" if text.is_empty() => Action::RecordMacro(register),
+                _ => Action::Undefined,
+            }
+        }
+        (Some("macro"), Some("stop"), None) => Action::StopRecording,
+        _ => Action::Undefined,
+    }
+}
+
+/// Rebuilds `node` with a new child (given by [`Ast::new_child`]) at the front of its children.
+/// Returns the new node, along with the path within the new child to the node that should be
+/// filled in first.
+fn with_new_child<'arena, Node: Ast<'arena>>(
+    arena: &'arena crate::arena::Arena<Node>,
+    node: &'arena Node,
+    c: char,
+) -> Option<(Node, CursorPath)> {
+    let (new_child, child_cursor) = node.new_child(c, arena)?;
+    Some((with_child_first(arena, node, new_child)?, child_cursor))
+}
+
+/// Rebuilds `node` with `child` at the front of its children, or returns [`None`] if `node`
+/// can't have that child
+fn with_child_first<'arena, Node: Ast<'arena>>(
+    arena: &'arena crate::arena::Arena<Node>,
+    node: &'arena Node,
+    child: Node,
+) -> Option<Node> {
+    let mut children = vec![arena.alloc(child)];
+    children.extend_from_slice(node.children());
+    Node::from_parts(node.kind_name(), node.value().as_deref(), children)
+}
+
+/// Convert the key typed after a sort command into an [`Action::Sort`].  Sorting without a key
+/// sorts the children by their own text.
+fn parse_sort_key(key: &str) -> Action {
+    match key.trim() {
+        "" => Action::Sort(None),
+        key => Action::Sort(Some(key.to_string())),
+    }
+}
+
+/// Convert the key typed after a group command into an [`Action::Group`]
+fn parse_group_key(key: &str) -> Action {
+    match key.trim() {
+        "" => Action::Undefined,
+        key => Action::Group(key.to_string()),
+    }
+}
+
+/// If `line` is the command `name` followed by whitespace or nothing, returns the (trimmed) text
+/// after the name.  Otherwise, returns [`None`].
+fn strip_command_name<'l>(line: &'l str, name: &str) -> Option<&'l str> {
+    let rest = line.trim_start().strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// A struct to hold the top-level components of the editor.
+pub struct Editor<'arena, Node: Ast<'arena>, E: EditableTree<'arena, Node> + 'arena> {
+    /// The [`EditableTree`] that the `Editor` is editing
+    tree: &'arena mut E,
+    /// The most recent logged messages
+    log: LogBuffer,
+    /// The file which logged messages are also written to
+    log_file: LogFile,
+    /// How many parts of the tree weren't valid when it was last validated
+    num_invalid: usize,
+    /// How many warnings and errors have been logged, so that a repeated action can tell when one
+    /// of its steps failed
+    num_warnings: usize,
+    /// The most recent message which the user should see, shown above the bottom bar until it
+    /// fades away
+    notification: Option<Notification>,
+    /// The style that the tree is being printed to the screen
+    format_style: Node::FormatStyle,
+    /// The `tuikit` terminal that the `Editor` is rendering to
+    term: TermGuard,
+    /// The keys typed so far of the command which is being entered
+    command: Vec<KeyInput>,
+    /// The configured key map
+    keymap: KeyMap,
+    /// The state of the outline sidebar, or [`None`] if the sidebar is closed
+    outline: Option<Outline>,
+    /// `true` if nodes whose children are records with the same keys are shown as tables, whenever
+    /// the cursor is inside one
+    table_view: bool,
+    /// The line of the rendered tree that is displayed at the top of the screen
+    scroll_row: usize,
+    /// The column of the rendered tree that is displayed at the left edge of the view
+    scroll_col: usize,
+    /// The node which was under the cursor when the view last followed it.  The view only follows
+    /// the cursor when it moves (or its node is edited), so the view can be scrolled away from it.
+    followed_cursor: Option<&'arena Node>,
+    /// `true` if the log pane is shown
+    log_visible: bool,
+    /// How many of the log entries shown by the log pane are newer than its last line, so that
+    /// `0` means that the newest entries are shown
+    log_scroll: usize,
+    /// The pattern of the last search, whose matches stay highlighted until it's cleared with
+    /// `:noh`
+    search_pattern: Option<Pattern>,
+    /// The index used to search large trees, which may have been built for an older version of
+    /// the tree.  [`None`] if the tree is too small to need one.
+    search_index: Option<SearchIndex<'arena, Node>>,
+    /// The recent positions of the cursor.  Edits are stored by the [`EditableTree`], so this only
+    /// has to store the motions.
+    motion_history: MotionHistory,
+    /// The positions of the recent edits, which (unlike the motion history) only change when the
+    /// tree is edited
+    edit_positions: EditPositions<'arena, Node>,
+    /// The folded subtrees, which are rendered as placeholders
+    folds: Folds<'arena, Node>,
+    /// The trees (and motion histories, edit positions and folds) that the open nested sessions
+    /// were opened from, innermost last.  The tree being edited is always the innermost nested
+    /// session.
+    hosts: Vec<(
+        E,
+        MotionHistory,
+        EditPositions<'arena, Node>,
+        Folds<'arena, Node>,
+    )>,
+    /// The user's settings
+    config: Config,
+    /// A question waiting to be answered by the user, along with the [`Action`] which will be
+    /// performed if the user confirms it
+    prompt: Option<(Prompt, Action)>,
+    /// The progress of any long-running operations
+    progress: ProgressTracker,
+    /// The [`char`] used by the last successful replacement, which can be repeated by
+    /// [`Action::RepeatReplace`]
+    last_replace_char: Option<char>,
+    /// `true` if the REPL is open, in which case the command line is refilled with `:eval ` after
+    /// every evaluation
+    repl_open: bool,
+    /// The macros which the user has stored
+    macros: MacroRegisters,
+    /// The subtrees which the user has copied into named registers
+    registers: Registers,
+    /// The number of macros which are currently being played
+    macro_depth: usize,
+    /// The macro which is being recorded, if any
+    recording: Option<Recording>,
+    /// The cursor and history of the line commands typed in [`Mode::Command`]
+    command_line: CommandLine,
+    /// The current mode, which decides what key presses do
+    mode: Mode,
+    /// How long a command which is also the start of a longer binding waits for the next key
+    /// before running anyway, or [`None`] if it waits however long it takes
+    command_timeout: Option<Duration>,
+    /// When the last key of the command box was typed, which the command timeout counts from
+    command_typed_at: Option<Instant>,
+    /// The scripted session being replayed, if Sapling was started with `--demo`.  Sapling quits
+    /// once the script has finished.
+    demo: Option<Demo>,
+    /// The clipboard, which is shared with other running instances of Sapling
+    clipboard: Clipboard,
+    /// The live preview pane, or [`None`] if the tree's language can't be previewed
+    preview: Option<Preview>,
+    /// Nodes which were made up to complete a truncated paste or which have duplicate siblings, and
+    /// are highlighted so that the user reviews them.  They stop being highlighted once they are
+    /// replaced, or by `:noh`.
+    review_nodes: Vec<&'arena Node>,
+    /// The expressions shown in the watches pane
+    watches: Watches,
+    /// The nodes whose text is shown in the pins pane
+    pins: Pins<'arena, Node>,
+    /// The bookmarks of the buffer's file
+    bookmarks: Bookmarks,
+    /// The node that each bookmark was set on, which is followed as edits move it around.  A
+    /// bookmark whose node has been lost (e.g. because it was deleted) isn't in here.
+    bookmarked_nodes: HashMap<char, &'arena Node>,
+    /// The expensive work waiting for the user to stop typing
+    idle: IdleScheduler,
+    /// Limits how often the screen is redrawn
+    frames: FrameLimiter,
+    /// The record of every action performed in this session
+    journal: Journal,
+    /// The schema describing the tree, if one has been loaded
+    schema: Option<Schema>,
+    /// The menu of keys which can be inserted into the cursor, if it's open
+    completion: Option<CompletionMenu>,
+    /// The command palette, if it's open
+    palette: Option<Palette<PaletteAction>>,
+    /// The history view, if it's open.  It's shown instead of the tree.
+    history_view: Option<HistoryView>,
+    /// The nodes matched by the latest query which matched more than one node
+    quickfix: Quickfix,
+    /// The path to the node whose children will all receive the next edit made inside one of
+    /// them, or [`None`] if the next edit will only be made once
+    batch: Option<CursorPath>,
+    /// The file which is being opened in the background, if there is one
+    loader: Option<Loader<'arena, Node>>,
+    /// The file which couldn't be fully opened, if the last file to be opened failed.  The tree
+    /// only holds part of this file, so it mustn't be written over.
+    failed_open: Option<String>,
+    /// The file that the buffer was opened from and is written to, or [`None`] if the buffer
+    /// hasn't been given a file
+    path: Option<String>,
+    /// How the buffer's file is encoded, which is detected when a file is opened
+    file_format: FileFormat,
+    /// The comments and blank lines of the buffer's file, which are written back when it's saved
+    trivia: TriviaMap,
+    /// Where edit events are written for external tools, if anywhere
+    events: Option<EventSink>,
+    /// The text of every message shown to the user, in the user's language
+    messages: Catalog,
+}
+
+impl<'arena, Node: Ast<'arena> + 'arena, E: EditableTree<'arena, Node> + 'arena>
+    Editor<'arena, Node, E>
+{
+    /// Create a new [`Editor`] with a given tree
+    pub fn new(
+        tree: &'arena mut E,
+        format_style: Node::FormatStyle,
+        mut keymap: KeyMap,
+        config: Config,
+    ) -> Editor<'arena, Node, E> {
+        let term = TermGuard::new().unwrap();
+        let language_report = match config.language_bindings.get(Node::language_name()) {
+            Some(overrides) => keymap::apply_overrides(&mut keymap, overrides),
+            None => keymap::KeymapReport::default(),
+        };
+        // The project's bindings override everything the user has bound
+        let project_report = keymap::apply_overrides(&mut keymap, &config.project_bindings);
+        let mut messages = Catalog::from_env();
+        let unknown_keys = messages.apply_overrides(&config.message_overrides);
+        let events = config
+            .events_path
+            .as_ref()
+            .map(|path| EventSink::open(path));
+        let preview = config
+            .previewers
+            .get(Node::language_name())
+            .cloned()
+            .map(Preview::new);
+        let idle_delay = config.idle_delay.unwrap_or(DEFAULT_IDLE_DELAY);
+        let frames = FrameLimiter::new(config.max_fps.unwrap_or(DEFAULT_MAX_FPS));
+        let journal = Journal::new(
+            config.journal_path.clone(),
+            config
+                .journal_kept_sessions
+                .unwrap_or(journal::DEFAULT_KEPT_SESSIONS),
+            Instant::now(),
+        );
+        let log_file = LogFile::new(
+            config.log_file_path.clone(),
+            config
+                .log_file_level
+                .clone()
+                .unwrap_or(DEFAULT_LOG_FILE_LEVEL),
+        );
+        // The terminal has already set its panic hook, so panics are written to the log file
+        // before the terminal is restored
+        log_file.log_panics();
+        let command_timeout = config
+            .command_timeout
+            .unwrap_or(keymap::DEFAULT_COMMAND_TIMEOUT);
+        let mut editor = Editor {
+            tree,
+            log: LogBuffer::new(
+                config.log_capacity.unwrap_or(DEFAULT_LOG_CAPACITY),
+                config.log_overflow_path.clone(),
+            ),
+            log_file,
+            num_invalid: 0,
+            num_warnings: 0,
+            notification: None,
+            term,
+            format_style,
+            command: Vec::new(),
+            keymap,
+            outline: None,
+            table_view: false,
+            scroll_row: 0,
+            scroll_col: 0,
+            log_visible: true,
+            log_scroll: 0,
+            followed_cursor: None,
+            search_pattern: None,
+            search_index: None,
+            motion_history: MotionHistory::new(),
+            edit_positions: EditPositions::new(),
+            folds: Folds::new(),
+            hosts: Vec::new(),
+            config,
+            prompt: None,
+            progress: ProgressTracker::new(),
+            last_replace_char: None,
+            repl_open: false,
+            macros: MacroRegisters::new(),
+            registers: Registers::new(),
+            macro_depth: 0,
+            recording: None,
+            command_line: CommandLine::new(),
+            mode: Mode::Normal,
+            command_timeout: Some(command_timeout),
+            command_typed_at: None,
+            demo: None,
+            clipboard: clipboard::shared_clipboard_path()
+                .map_or_else(Clipboard::new, Clipboard::shared),
+            events: None,
+            preview,
+            watches: Watches::new(),
+            pins: Pins::new(),
+            bookmarks: Bookmarks::new(),
+            bookmarked_nodes: HashMap::new(),
+            idle: IdleScheduler::new(idle_delay, Instant::now()),
+            frames,
+            journal,
+            schema: None,
+            completion: None,
+            palette: None,
+            history_view: None,
+            quickfix: Quickfix::default(),
+            batch: None,
+            loader: None,
+            failed_open: None,
+            path: None,
+            file_format: FileFormat::default(),
+            trivia: TriviaMap::new(),
+            review_nodes: Vec::new(),
+            messages,
+        };
+        match events {
+            Some(Ok(sink)) => editor.events = Some(sink),
+            Some(Err(e)) => {
+                editor.log(LogLevel::Warning, Message::EventsUnavailable(e.to_string()))
+            }
+            None => {}
+        }
+        if let Some(path) = editor.config.schema_path.clone() {
+            editor.load_schema(&path.to_string_lossy());
+        }
+        if let Some(name) = editor.config.format_name.clone() {
+            match Node::named_format_style(&name) {
+                Some(format_style) => editor.format_style = format_style,
+                None => {
+                    let language = Node::language_name().to_string();
+                    editor.log(
+                        LogLevel::Warning,
+                        Message::NoSuchFormatStyle(name, language),
+                    );
+                }
+            }
+        }
+        if let Some(width) = editor.config.line_width {
+            match Node::with_max_line_width(&editor.format_style, Some(width)) {
+                Some(format_style) => editor.format_style = format_style,
+                None => {
+                    let language = Node::language_name().to_string();
+                    editor.log(LogLevel::Warning, Message::CannotSetLineWidth(language));
+                }
+            }
+        }
+        // Large trees are indexed as soon as the user gives Sapling a moment to do so
+        editor.idle.schedule(IdleTask::RefreshSearchIndex);
+        for key in unknown_keys {
+            editor.log(LogLevel::Warning, Message::UnknownMessageKey(key));
+        }
+        let keymap_messages = language_report.messages().into_iter();
+        for message in keymap_messages.chain(project_report.messages()) {
+            editor.log(LogLevel::Warning, message);
+        }
+        // Warn the user about any commands that their keymap makes impossible to use
+        for message in keymap::validate(&editor.keymap.bindings()).messages() {
+            editor.log(LogLevel::Warning, message);
+        }
+        editor
+    }
+
+    /// Returns a [`ProgressSender`] through which long-running operations can report their
+    /// progress to the status bar
+    pub fn progress_sender(&self) -> ProgressSender {
+        self.progress.sender()
+    }
+
+    /// Log a warning which was found before the editor was created (e.g. about the user's
+    /// config), so that it's shown along with the editor's own warnings
+    pub fn warn(&mut self, message: Message) {
+        self.log(LogLevel::Warning, message);
+    }
+
+    /// Log a message to whatever console is appropriate, in the user's language
+    fn log(&mut self, level: LogLevel, message: Message) {
+        let text = self.messages.format(&message);
+        if level >= LogLevel::Warning {
+            self.num_warnings += 1;
+        }
+        if level >= LogLevel::Info {
+            self.notification = Some(Notification::new(
+                level.clone(),
+                text.clone(),
+                Instant::now(),
+            ));
+        }
+        // The log file can't report its own errors through the log, so they go straight into it
+        if let Err(error) = self.log_file.write(&level, &text) {
+            let text = self.messages.format(&Message::CannotWriteLogFile(error));
+            self.log.push(LogLevel::Warning, text);
+        }
+        // A scrolled log pane keeps showing the same entries
+        if self.log_scroll > 0 && self.is_shown_in_log(&level) {
+            self.log_scroll += 1;
+        }
+        self.log.push(level, text);
+    }
+
+    /// Returns `true` if log entries of a given level are shown in the log pane
+    fn is_shown_in_log(&self, level: &LogLevel) -> bool {
+        self.config
+            .log_level
+            .as_ref()
+            .is_none_or(|min_level| level >= min_level)
+    }
+
+    /// Returns the log entries which are shown in the log pane, oldest first
+    fn shown_log_entries(&self) -> Vec<&(LogLevel, String)> {
+        self.log
+            .iter()
+            .filter(|(level, _)| self.is_shown_in_log(level))
+            .collect()
+    }
+
+    /// Scroll the log pane `count` lines or pages towards older (up) or newer (down) entries,
+    /// stopping when the oldest entry is on the last line
+    fn scroll_log(&mut self, step: ScrollStep, count: usize) {
+        let page = (self.view_height() / 2).max(1);
+        let max_scroll = self.shown_log_entries().len().saturating_sub(1);
+        self.log_scroll = match step {
+            ScrollStep::LineUp => self.log_scroll.saturating_add(count),
+            ScrollStep::LineDown => self.log_scroll.saturating_sub(count),
+            ScrollStep::PageUp => self.log_scroll.saturating_add(page.saturating_mul(count)),
+            ScrollStep::PageDown => self.log_scroll.saturating_sub(page.saturating_mul(count)),
+        }
+        .min(max_scroll);
+    }
+
+    /// Respond to an edit of the tree by scheduling the preview to be re-rendered and telling any
+    /// external tools.  If the event can't be sent, then the error is logged and no more events
+    /// are sent.
+    fn notify_edit(&mut self, operation: events::Operation) {
+        // A batch edit only waits for the very next edit, even if it can't be made to every child
+        if self.batch.take().is_some() {
+            self.log(LogLevel::Warning, Message::EditNotBatched);
+        }
+        if self.current_summary().is_none() {
+            self.record_summary(operation);
+        }
+        // Undoing and redoing move the cursor to the edit anyway, so they don't count as new
+        // edit positions
+        if !matches!(operation, events::Operation::Undo | events::Operation::Redo) {
+            self.edit_positions
+                .record(self.tree.cursor_path(), self.tree.cursor());
+        }
+        let edit_path = self.tree.cursor_path().clone();
+        self.adjust_to_edit(&edit_path);
+        if let Some(preview) = &mut self.preview {
+            preview.mark_dirty(Instant::now());
+        }
+        self.idle.schedule(IdleTask::UpdateWatches);
+        self.idle.schedule(IdleTask::UpdatePins);
+        self.idle.schedule(IdleTask::RefreshSearchIndex);
+        // Autosaving waits for validation, because trees which aren't valid aren't autosaved
+        self.idle.schedule(IdleTask::Validate);
+        self.idle.schedule(IdleTask::Autosave);
+        let sink = match &mut self.events {
+            Some(sink) => sink,
+            None => return,
+        };
+        let event = EditEvent {
+            operation,
+            path: self.tree.cursor_path().clone(),
+            kind: self.tree.cursor().kind_name().to_string(),
+        };
+        if let Err(e) = sink.send(&event) {
+            self.events = None;
+            self.log(LogLevel::Warning, Message::EventsUnavailable(e.to_string()));
+        }
+    }
+
+    /// Returns a summary of an edit which has just been made, or [`None`] if the operation
+    /// doesn't make a new history entry.  Insertions are assumed to have left the cursor on the
+    /// node that was inserted into.
+    fn edit_summary(&self, operation: events::Operation) -> Option<Message> {
+        let path = self.tree.cursor_path().to_string();
+        let cursor = self.tree.cursor();
+        let kind = cursor.kind_name().to_string();
+        Some(match operation {
+            events::Operation::Replace => Message::SummaryReplace(path, kind),
+            events::Operation::Insert => {
+                let child_kind = cursor.children().first().map(|child| child.kind_name());
+                Message::SummaryInsert(path, child_kind.unwrap_or_default().to_string())
+            }
+            events::Operation::Paste => Message::SummaryPaste(path, kind),
+            events::Operation::Cut => Message::SummaryCut(path),
+            events::Operation::Slurp => Message::SummarySlurp(path),
+            events::Operation::Barf => Message::SummaryBarf(path),
+            events::Operation::Transpose => Message::SummaryTranspose(path),
+            events::Operation::Join => Message::SummaryJoin(path),
+            events::Operation::Sort => Message::SummarySort(path),
+            events::Operation::Group => Message::SummaryGroup(path),
+            events::Operation::Rename => Message::SummaryRename(path),
+            events::Operation::Wrap => Message::SummaryWrap(path, kind),
+            events::Operation::Unwrap => Message::SummaryUnwrap(path),
+            events::Operation::Raise => Message::SummaryRaise(path),
+            events::Operation::Convert => Message::SummaryConvert(path, kind),
+            // Language commands record their own summaries, which name the command
+            events::Operation::Undo
+            | events::Operation::Redo
+            | events::Operation::LanguageCommand => return None,
+        })
+    }
+
+    /// Record the summary of an edit which has just been made in the tree's history
+    fn record_summary(&mut self, operation: events::Operation) {
+        if let Some(summary) = self.edit_summary(operation) {
+            let summary = self.messages.format(&summary);
+            self.tree.set_summary(summary);
+        }
+    }
+
+    /// Returns the summary of the change which made the current history entry, if there is one
+    fn current_summary(&self) -> Option<String> {
+        self.tree.current_entry().summary.clone()
+    }
+
+    /* ===== COMMAND FUNCTIONS ===== */
+
+    /// Replace the node under the cursor with the node represented by a given [`char`]
+    fn replace_cursor(&mut self, c: char) {
+        let replace = |node: &'arena Node| node.from_char(c).filter(|_| node.is_replace_char(c));
+        if self.batch_edit(replace, events::Operation::Replace) {
+            self.last_replace_char = Some(c);
+            return;
+        }
+        if self.tree.cursor().is_replace_char(c) {
+            // We know that `c` corresponds to a valid node, so we can unwrap
+            let new_node = self.tree.cursor().from_char(c).unwrap();
+            self.log(
+                LogLevel::Debug,
+                Message::Replacing(c, format!("{:?}", new_node)),
+            );
+            self.tree.replace_cursor(new_node);
+            self.last_replace_char = Some(c);
+            // The edit is recorded at the replaced node, before the cursor lands anywhere else
+            self.notify_edit(events::Operation::Replace);
+            if self.config.landing == Landing::Parent {
+                let mut path = self.tree.cursor_path().clone();
+                if path.pop().is_some() {
+                    self.jump_to(path);
+                }
+            }
+        } else {
+            self.log(LogLevel::Warning, Message::CannotReplace(c));
+        }
+    }
+
+    /// Wait for the next edit inside a child of the cursor, and then make that edit to every child
+    /// of the cursor
+    fn arm_batch_edit(&mut self) {
+        let cursor = self.tree.cursor();
+        if cursor.children().is_empty() {
+            return self.log(LogLevel::Warning, Message::NothingToBatchEdit);
+        }
+        self.batch = Some(self.tree.cursor_path().clone());
+        let kind = cursor.display_name().to_string();
+        self.log(LogLevel::Info, Message::BatchEditArmed(kind));
+    }
+
+    /// Returns the path to the node that a waiting batch edit was armed on, and the path from the
+    /// cursor's child of that node to the cursor.  Returns [`None`] if no batch edit is waiting or
+    /// the cursor is outside it.
+    fn batch_target(&self) -> Option<(CursorPath, Vec<usize>)> {
+        let batch_path = self.batch.as_ref()?;
+        let cursor_path = self.tree.cursor_path().iter().copied().collect::<Vec<_>>();
+        match cursor_path.strip_prefix(&batch_path.iter().copied().collect::<Vec<_>>()[..]) {
+            Some([_, rest @ ..]) => Some((batch_path.clone(), rest.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Stop waiting for a batch edit, returning its target as given by
+    /// [`batch_target`](Editor::batch_target)
+    fn take_batch(&mut self) -> Option<(CursorPath, Vec<usize>)> {
+        let target = self.batch_target();
+        if self.batch.take().is_some() && target.is_none() {
+            self.log(LogLevel::Warning, Message::EditOutsideBatch);
+        }
+        target
+    }
+
+    /// If a batch edit is waiting, make an edit to the node at the cursor's position within every
+    /// child of the batch's node, as a single undoable edit.  Returns `false` if the edit should
+    /// instead be made normally, because no batch edit is waiting or the cursor is outside it.
+    fn batch_edit(
+        &mut self,
+        edit: impl FnMut(&'arena Node) -> Option<Node>,
+        operation: events::Operation,
+    ) -> bool {
+        match self.take_batch() {
+            Some((batch_path, relative_path)) => {
+                self.make_batch_edit(batch_path, &relative_path, edit, operation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Like [`batch_edit`](Editor::batch_edit), but for edits which change the parent of the
+    /// cursor, which are given the parent and the cursor's index within it
+    fn batch_edit_parent(
+        &mut self,
+        mut edit: impl FnMut(&'arena Node, usize) -> Option<Node>,
+        operation: events::Operation,
+    ) -> bool {
+        let (batch_path, mut relative_path) = match self.take_batch() {
+            Some(target) => target,
+            None => return false,
+        };
+        match relative_path.pop() {
+            Some(index) => self.make_batch_edit(
+                batch_path,
+                &relative_path,
+                // Children too short to have a node at the cursor's index are left unchanged
+                |parent: &'arena Node| {
+                    if index < parent.children().len() {
+                        edit(parent, index)
+                    } else {
+                        None
+                    }
+                },
+                operation,
+            ),
+            // The parent of the cursor is the batch's node itself, which only has one copy
+            None => self.log(LogLevel::Warning, Message::EditChangesBatchNode),
+        }
+        true
+    }
+
+    /// Make an edit to the node at `relative_path` within every child of the node at
+    /// `batch_path`, as a single undoable edit
+    fn make_batch_edit(
+        &mut self,
+        batch_path: CursorPath,
+        relative_path: &[usize],
+        edit: impl FnMut(&'arena Node) -> Option<Node>,
+        operation: events::Operation,
+    ) {
+        let cursor_path = self.tree.cursor_path().clone();
+        let batch_node = batch_path.cursor(self.tree.root());
+        match restructure::edit_each(self.tree.arena(), batch_node, relative_path, edit) {
+            Ok((new_node, num_edited)) => {
+                // Replace the whole batch node at once, so that the edit is undone in one go
+                self.tree.jump_to(batch_path);
+                self.tree.replace_cursor(new_node);
+                // The edit may have removed the cursor's node, in which case the cursor moves to
+                // the nearest node which is still there
+                let mut cursor_path = cursor_path;
+                while !cursor_path.is_valid(self.tree.root()) {
+                    cursor_path.pop();
+                }
+                self.tree.jump_to(cursor_path);
+                if let Some(summary) = self.edit_summary(operation) {
+                    let summary = self.messages.format(&summary);
+                    let batch_summary = Message::SummaryBatch(summary, num_edited);
+                    let batch_summary = self.messages.format(&batch_summary);
+                    self.tree.set_summary(batch_summary);
+                }
+                self.log(LogLevel::Info, Message::BatchEdited(num_edited));
+                self.notify_edit(operation);
+            }
+            Err(error_message) => self.log(LogLevel::Warning, error_message),
+        }
+    }
+
+    /// Replace the parent of the cursor with a node built by one of the functions in
+    /// [`restructure`], which are given the arena, the parent and the cursor's index in the parent
+    fn restructure(
+        &mut self,
+        mut edit: impl FnMut(
+            &'arena crate::arena::Arena<Node>,
+            &'arena Node,
+            usize,
+        ) -> std::result::Result<(Node, usize), Message>,
+        operation: events::Operation,
+    ) {
+        let arena = self.tree.arena();
+        let batch_edit = |parent, index| edit(arena, parent, index).ok().map(|(node, _)| node);
+        if self.batch_edit_parent(batch_edit, operation) {
+            return;
+        }
+        let (parent, index) = match self.cursor_parent() {
+            Some(parent_and_index) => parent_and_index,
+            None => return self.log(LogLevel::Warning, Message::RootHasNoParent),
+        };
+        match edit(self.tree.arena(), parent, index) {
+            Ok((new_parent, cursor_index)) => {
+                if let Some(error_message) = self.tree.replace_parent(new_parent, cursor_index) {
+                    return self.log(LogLevel::Warning, error_message);
+                }
+                self.notify_edit(operation);
+            }
+            Err(error_message) => self.log(LogLevel::Warning, error_message),
+        }
+    }
+
+    /// Returns the parent of the cursor and the cursor's index within it, or [`None`] if the
+    /// cursor is at the root
+    fn cursor_parent(&self) -> Option<(&'arena Node, usize)> {
+        let path = self.tree.cursor_path();
+        match (
+            path.cursor_and_parent(self.tree.root()).1,
+            path.iter().last(),
+        ) {
+            (Some(parent), Some(index)) => Some((parent, *index)),
+            _ => None,
+        }
+    }
+
+    /// Replace the node at `path` with a node built by one of the functions in [`restructure`],
+    /// as a single undoable edit which leaves the cursor on the new node.  Unlike replacing the
+    /// cursor, this checks that the new node is allowed inside its parent.
+    fn replace_at(
+        &mut self,
+        path: CursorPath,
+        new_node: std::result::Result<Node, Message>,
+        operation: events::Operation,
+    ) {
+        let new_node = match new_node {
+            Ok(new_node) => new_node,
+            Err(error_message) => return self.log(LogLevel::Warning, error_message),
+        };
+        let root = self.tree.root();
+        match restructure::replace_each(self.tree.arena(), root, vec![(path.clone(), new_node)]) {
+            Some(new_root) => {
+                self.tree.jump_to(CursorPath::root());
+                self.tree.replace_cursor(new_root);
+                self.tree.jump_to(path);
+                self.notify_edit(operation);
+            }
+            None => self.log(LogLevel::Warning, Message::InvalidStructure),
+        }
+    }
+
+    /// Replace the parent of the cursor with the node under the cursor
+    fn raise(&mut self) {
+        let raise = |parent: &'arena Node, index: usize| {
+            parent.children().get(index).map(|&child| child.clone())
+        };
+        if self.batch_edit_parent(raise, events::Operation::Raise) {
+            return;
+        }
+        let mut parent_path = self.tree.cursor_path().clone();
+        if parent_path.pop().is_none() {
+            return self.log(LogLevel::Warning, Message::RootHasNoParent);
+        }
+        let cursor = self.tree.cursor().clone();
+        self.replace_at(parent_path, Ok(cursor), events::Operation::Raise);
+    }
+
+    /// Sort the children of the node under the cursor, by the field `key` inside each of them if
+    /// a key is given
+    fn sort_children(&mut self, key: Option<String>) {
+        match restructure::sort_children(self.tree.cursor(), key.as_deref()) {
+            Ok(new_node) => {
+                self.tree.replace_cursor(new_node);
+                self.notify_edit(events::Operation::Sort);
+            }
+            Err(error_message) => self.log(LogLevel::Warning, error_message),
+        }
+    }
+
+    /// Group the children of the node under the cursor by the value of the field `key` inside
+    /// each of them
+    fn group_children(&mut self, key: String) {
+        let cursor = self.tree.cursor();
+        match restructure::group_children(self.tree.arena(), cursor, &key) {
+            Ok((new_node, num_groups)) => {
+                self.tree.replace_cursor(new_node);
+                self.log(LogLevel::Info, Message::Grouped(num_groups, key));
+                self.notify_edit(events::Operation::Group);
+            }
+            Err(error_message) => self.log(LogLevel::Warning, error_message),
+        }
+    }
+
+    /// Rename the symbol under the cursor and every reference to it, as a single undoable edit
+    fn rename_symbol(&mut self, new_name: String) {
+        let cursor_path = self.tree.cursor_path().clone();
+        let root = self.tree.root();
+        let replacements = match root.rename_symbol(&cursor_path, &new_name) {
+            Some(Ok(replacements)) => replacements,
+            Some(Err(error)) => {
+                let message = Message::CannotRename(self.messages.format(&error));
+                return self.log(LogLevel::Warning, message);
+            }
+            None => return self.log(LogLevel::Info, Message::NotASymbol),
+        };
+        // The first replacement is the symbol itself, and the rest are its references
+        let num_references = replacements.len().saturating_sub(1);
+        match restructure::replace_each(self.tree.arena(), root, replacements) {
+            Some(new_root) => {
+                // Replace the whole tree at once, so that the edit is undone in one go
+                self.tree.jump_to(CursorPath::root());
+                self.tree.replace_cursor(new_root);
+                self.tree.jump_to(cursor_path);
+                self.log(LogLevel::Info, Message::Renamed(new_name, num_references));
+                self.notify_edit(events::Operation::Rename);
+            }
+            None => self.log(LogLevel::Warning, Message::InvalidStructure),
+        }
+    }
+
+    /// Run the command with a given name which the buffer's language provides, making all of its
+    /// replacements as a single undoable edit
+    fn run_language_command(&mut self, name: &str) {
+        let command = match commands::find::<Node>(name) {
+            Some(command) => command,
+            None => {
+                let message = Message::NoSuchLanguageCommand(name.to_string());
+                return self.log(LogLevel::Warning, message);
+            }
+        };
+        let cursor_path = self.tree.cursor_path().clone();
+        let root = self.tree.root();
+        let replacements = match (command.run)(root, &cursor_path, self.tree.arena()) {
+            Ok(replacements) => replacements,
+            Err(error) => {
+                let reason = self.messages.format(&error);
+                let message = Message::LanguageCommandFailed(name.to_string(), reason);
+                return self.log(LogLevel::Warning, message);
+            }
+        };
+        match restructure::replace_each(self.tree.arena(), root, replacements) {
+            Some(new_root) => {
+                // Replace the whole tree at once, so that the edit is undone in one go
+                self.tree.jump_to(CursorPath::root());
+                self.tree.replace_cursor(new_root);
+                self.tree.jump_to(cursor_path.clone());
+                // The summary names the command, which the operation alone can't
+                let summary =
+                    Message::SummaryLanguageCommand(cursor_path.to_string(), name.to_string());
+                let summary = self.messages.format(&summary);
+                self.tree.set_summary(summary);
+                self.log(
+                    LogLevel::Info,
+                    Message::RanLanguageCommand(name.to_string()),
+                );
+                self.notify_edit(events::Operation::LanguageCommand);
+            }
+            None => self.log(LogLevel::Warning, Message::InvalidStructure),
+        }
+    }
+
+    /// Move the cursor `count` steps in a given direction, stopping early if it can go no further
+    fn move_cursor(&mut self, direction: Direction, count: usize) {
+        let old_path = self.tree.cursor_path().clone();
+        for _ in 0..count {
+            // A count which goes too far leaves the cursor as far along as it could go
+            if let Some(error_message) = self.step_cursor(direction) {
+                self.log(LogLevel::Warning, error_message);
+                break;
+            }
+        }
+        // The whole of a counted motion is retraced at once
+        if *self.tree.cursor_path() != old_path {
+            self.motion_history
+                .record(&old_path, self.tree.cursor_path());
+        }
+    }
+
+    /// Move the cursor one step in a given direction, returning the reason if it can't move
+    fn step_cursor(&mut self, direction: Direction) -> Option<Message> {
+        // In the table view, the cursor moves between the cells of the table
+        if let Some(table) = self.visible_table() {
+            if let Some(cell) = table.cell(self.tree.cursor_path()) {
+                return match (table.move_cell(cell, direction), direction) {
+                    (Some((row, column)), _) => self.tree.jump_to(table.cell_path(row, column)),
+                    // Moving up from the top row leaves the table
+                    (None, Direction::Up) => self.tree.jump_to(table.path),
+                    (None, Direction::Down) => Some(Message::CannotMoveDown),
+                    (None, Direction::Prev) => Some(Message::CannotMovePrev),
+                    (None, Direction::Next) => Some(Message::CannotMoveNext),
+                };
+            }
+        }
+        self.tree.move_cursor(direction)
+    }
+
+    /// Move the cursor straight to the node at the end of a given [`CursorPath`]
+    fn jump_to(&mut self, path: CursorPath) {
+        let old_path = self.tree.cursor_path().clone();
+        if let Some(error_message) = self.tree.jump_to(path) {
+            self.log(LogLevel::Warning, error_message);
+        } else {
+            self.motion_history
+                .record(&old_path, self.tree.cursor_path());
+        }
+    }
+
+    /// Move the cursor back to where it was before its last motion (or forward again if `forward`
+    /// is `true`).  Unlike [`Editor::jump_to`], this doesn't record a new motion.
+    fn retrace_motion(&mut self, forward: bool) {
+        let path = if forward {
+            self.motion_history.forward()
+        } else {
+            self.motion_history.back()
+        };
+        match path.cloned() {
+            Some(path) => {
+                if let Some(error_message) = self.tree.jump_to(path) {
+                    self.log(LogLevel::Warning, error_message);
+                }
+            }
+            None if forward => self.log(LogLevel::Info, Message::NoNewerPositions),
+            None => self.log(LogLevel::Info, Message::NoOlderPositions),
+        }
+    }
+
+    /// Fold the subtree under the cursor, or unfold it if it's already folded
+    fn toggle_fold(&mut self) {
+        let cursor = self.tree.cursor();
+        if cursor.children().is_empty() {
+            let kind = cursor.display_name().to_string();
+            return self.log(LogLevel::Warning, Message::NothingToFold(kind));
+        }
+        self.folds.toggle(self.tree.cursor_path(), cursor);
+    }
+
+    /// Fold every part of the tree which goes over the limits on its shape, so that it can be
+    /// drawn quickly
+    fn fold_oversized(&mut self) {
+        let oversized = limits::find_oversized(self.tree.root(), &self.config.limits);
+        if oversized.is_empty() {
+            return self.log(LogLevel::Info, Message::NothingOversized);
+        }
+        let count = oversized
+            .into_iter()
+            .filter(|(path, node, _)| self.folds.fold(path, node))
+            .count();
+        self.log(LogLevel::Info, Message::FoldedOversized(count));
+    }
+
+    /// Warn about the parts of a newly opened tree which go over the limits on its shape, and
+    /// offer to fold them (or fold them straight away, if the user has said not to ask)
+    fn check_limits(&mut self) {
+        let limits = self.config.limits;
+        let oversized = limits::find_oversized(self.tree.root(), &limits);
+        if oversized.is_empty() {
+            return;
+        }
+        let too_deep = oversized
+            .iter()
+            .filter(|(_, _, excess)| *excess == limits::Excess::TooDeep)
+            .count();
+        let too_wide = oversized.len() - too_deep;
+        if too_deep > 0 {
+            let message = Message::TreeTooDeep(too_deep, limits.max_depth);
+            self.log(LogLevel::Warning, message);
+        }
+        if too_wide > 0 {
+            let message = Message::TreeTooWide(too_wide, limits.max_children);
+            self.log(LogLevel::Warning, message);
+        }
+        let operation = Operation::FoldOversized;
+        if self.config.confirm.is_enabled(operation) {
+            let question = Message::ConfirmFoldOversized(oversized.len());
+            let prompt = Prompt::new(operation, self.messages.format(&question));
+            self.prompt = Some((prompt, Action::FoldOversized));
+        } else {
+            self.fold_oversized();
+        }
+    }
+
+    /// Move the cursor to the next older edit position (or the next newer one if `newer` is
+    /// `true`).  This counts as a motion, so [`Action::MotionBack`] returns to where the cursor
+    /// was before.
+    fn retrace_edit(&mut self, newer: bool) {
+        let path = if newer {
+            self.edit_positions.newer()
+        } else {
+            self.edit_positions.older()
+        };
+        match path.cloned() {
+            Some(path) => self.jump_to(path),
+            None if newer => self.log(LogLevel::Info, Message::NoNewerEdits),
+            None => self.log(LogLevel::Info, Message::NoOlderEdits),
+        }
+    }
+
+    /// Copy the subtree under the cursor to a named register, or to the clipboard if no register
+    /// is given
+    fn yank(&mut self, register: Option<char>) {
+        let cursor = self.tree.cursor();
+        let contents = ClipboardContents::copy(cursor, &self.format_style);
+        match register {
+            Some(register) => {
+                self.registers.set(register, contents);
+                let message = Message::CopiedToRegister(cursor.subtree_size(), register);
+                self.log(LogLevel::Info, message);
+            }
+            None => {
+                if let Err(e) = self.clipboard.copy(contents) {
+                    self.log(
+                        LogLevel::Warning,
+                        Message::ClipboardNotShared(e.to_string()),
+                    );
+                }
+                self.log(LogLevel::Info, Message::Copied(cursor.subtree_size()));
+            }
+        }
+    }
+
+    /// Copy the subtree under the cursor like [`Editor::yank`], and then remove it from its
+    /// parent.  The cursor moves to the node which took its place, or to the parent if the parent
+    /// has no children left.
+    fn cut(&mut self, register: Option<char>) {
+        // A batch edit copies the node under the cursor, and removes it from every child
+        if self.batch_target().is_some() {
+            self.yank(register);
+        }
+        let remove = |parent, index| restructure::remove_child(parent, index).ok();
+        if self.batch_edit_parent(remove, events::Operation::Cut) {
+            return;
+        }
+        let mut parent_path = self.tree.cursor_path().clone();
+        let index = match parent_path.pop() {
+            Some(index) => index,
+            None => return self.log(LogLevel::Warning, Message::CannotCutRoot),
+        };
+        let parent = parent_path.cursor(self.tree.root());
+        let new_parent = match restructure::remove_child(parent, index) {
+            Ok(new_parent) => new_parent,
+            Err(error_message) => return self.log(LogLevel::Warning, error_message),
+        };
+        self.yank(register);
+        let num_children = new_parent.children().len();
+        if num_children == 0 {
+            self.tree.jump_to(parent_path);
+            self.tree.replace_cursor(new_parent);
+        } else {
+            self.tree
+                .replace_parent(new_parent, index.min(num_children - 1));
+        }
+        self.notify_edit(events::Operation::Cut);
+    }
+
+    /// Paste the contents of a named register (or the clipboard if no register is given) at some
+    /// place relative to the cursor
+    fn paste(&mut self, place: PastePlace, register: Option<char>) {
+        let contents = match self.register_contents(register) {
+            Ok(contents) => contents,
+            Err((level, message)) => return self.log(level, message),
+        };
+        match contents.fragment::<Node>() {
+            Ok((fragment, conversion_message)) => {
+                if let Some(message) = conversion_message {
+                    self.log(LogLevel::Info, message);
+                }
+                self.insert_fragment(fragment, place);
+            }
+            Err(message) => self.log(LogLevel::Warning, message),
+        }
+    }
+
+    /// Replace the node under the cursor with the document in the file at a given path, as if
+    /// the file's text had been pasted
+    fn read_file(&mut self, path: &str) {
+        match Self::file_fragment(path) {
+            Ok((fragment, _)) => self.insert_fragment(fragment, PastePlace::Replace),
+            Err((level, message)) => self.log(level, message),
+        }
+    }
+
+    /// Returns the contents of a named register, or of the clipboard if no register is given, or
+    /// the message saying why there is nothing to paste
+    fn register_contents(
+        &self,
+        register: Option<char>,
+    ) -> std::result::Result<ClipboardContents, (LogLevel, Message)> {
+        match register {
+            Some(register) => self
+                .registers
+                .get(register)
+                .cloned()
+                .ok_or((LogLevel::Info, Message::EmptyRegister(register))),
+            None => self
+                .clipboard
+                .paste()
+                .ok_or((LogLevel::Info, Message::NothingToPaste)),
+        }
+    }
+
+    /// Returns the fragment which would be pasted from a named register, or from the clipboard if
+    /// no register is given, along with its text, or the message saying why nothing can be
+    /// pasted.  Plain text (e.g. copied from another program) is parsed, and may have to be
+    /// completed if it has been cut short.  Subtrees copied from buffers of other languages are
+    /// converted (see [`ClipboardContents::fragment`]).
+    fn register_fragment(
+        &self,
+        register: Option<char>,
+    ) -> std::result::Result<(Fragment, String), (LogLevel, Message)> {
+        let contents = self.register_contents(register)?;
+        match contents.fragment::<Node>() {
+            Ok((fragment, _)) => Ok((fragment, contents.text)),
+            Err(message) => Err((LogLevel::Warning, message)),
+        }
+    }
+
+    /// Returns the fragment parsed from the file at a given path, along with the file's text, or
+    /// the message saying why it can't be read
+    fn file_fragment(path: &str) -> std::result::Result<(Fragment, String), (LogLevel, Message)> {
+        let cannot_read = |error: String| {
+            (
+                LogLevel::Warning,
+                Message::CannotRead(path.to_string(), error),
+            )
+        };
+        let bytes = std::fs::read(path).map_err(|e| cannot_read(e.to_string()))?;
+        let (text, _) = encoding::decode(&bytes).map_err(cannot_read)?;
+        match Node::parse_fragment(&text) {
+            Some(fragment) => Ok((fragment, text)),
+            None => Err((LogLevel::Warning, Message::CannotPasteText)),
+        }
+    }
+
+    /// Replace the node under the cursor with a pasted fragment, highlighting any nodes which
+    /// had to be made up to complete it
+    fn insert_fragment(&mut self, fragment: Fragment, place: PastePlace) {
+        match fragment.node.to_node(self.tree.arena()) {
+            Some(node) => {
+                let error_message = match place {
+                    PastePlace::Replace => {
+                        self.tree.replace_cursor(node);
+                        None
+                    }
+                    PastePlace::After => self.insert_sibling_after(node),
+                    PastePlace::Child => self.tree.insert_child(node),
+                };
+                if let Some(error_message) = error_message {
+                    return self.log(LogLevel::Warning, error_message);
+                }
+                let pasted = self.tree.cursor();
+                self.review_nodes
+                    .extend(fragment.holes.iter().map(|path| path.cursor(pasted)));
+                if fragment.is_truncated {
+                    let message = Message::CompletedPaste(fragment.holes.len());
+                    self.log(LogLevel::Warning, message);
+                }
+                self.notify_edit(events::Operation::Paste);
+            }
+            None => self.log(LogLevel::Warning, Message::CannotPasteStructure),
+        }
+    }
+
+    /// Insert a node as the next sibling of the cursor, and move the cursor to it.  Returns the
+    /// reason if the node can't go there.
+    fn insert_sibling_after(&mut self, node: Node) -> Option<Message> {
+        let (_, parent) = self.tree.cursor_path().cursor_and_parent(self.tree.root());
+        let parent = match parent {
+            Some(parent) => parent,
+            None => return Some(Message::CannotMoveToSiblingOfRoot),
+        };
+        let index = *self.tree.cursor_path().iter().last()? + 1;
+        let mut children = parent.children().to_vec();
+        children.insert(index, self.tree.arena().alloc(node));
+        let new_parent = Node::from_parts(parent.kind_name(), parent.value().as_deref(), children);
+        match new_parent {
+            Some(new_parent) => self.tree.replace_parent(new_parent, index),
+            None => Some(Message::InvalidStructure),
+        }
+    }
+
+    /// Replay the keys stored in a macro register, exactly as if the user had typed them.
+    /// Returns `true` if the macro quits Sapling.
+    fn play_macro(&mut self, register: char) -> bool {
+        let keys = match self.macros.get(register) {
+            Some(keys) => keys.to_vec(),
+            None => {
+                self.log(LogLevel::Warning, Message::EmptyMacroRegister(register));
+                return false;
+            }
+        };
+        if self.macro_depth >= macros::MAX_MACRO_DEPTH {
+            self.log(LogLevel::Error, Message::MacrosTooDeep);
+            return false;
+        }
+        // The macro may have been played part of the way through a command, which shouldn't
+        // affect the macro's own commands
+        let old_command = std::mem::take(&mut self.command);
+        self.macro_depth += 1;
+        let mut should_quit = false;
+        for key in keys {
+            if self.handle_key(key) {
+                should_quit = true;
+                break;
+            }
+        }
+        self.macro_depth -= 1;
+        self.command = old_command;
+        // A line command which the macro left unfinished is abandoned along with its keys
+        if self.mode == Mode::Command && !is_line_command(&self.keymap, &self.command) {
+            self.mode = Mode::Normal;
+        }
+        should_quit
+    }
+
+    /// Start recording the keys that the user presses into a macro register.  A macro which is
+    /// already being recorded is stored first.
+    fn record_macro(&mut self, register: char) {
+        if self.recording.is_some() {
+            self.stop_recording();
+        }
+        self.recording = Some(Recording::new(register));
+        self.log(LogLevel::Info, Message::RecordingMacro(register));
+    }
+
+    /// Stop recording a macro, and store the keys that were recorded in its register
+    fn stop_recording(&mut self) {
+        match self.recording.take() {
+            Some(recording) => {
+                let register = recording.register;
+                let keys = recording.finish();
+                let num_keys = keys.len();
+                self.macros.set(register, keys);
+                self.log(LogLevel::Info, Message::MacroRecorded(num_keys, register));
+            }
+            None => self.log(LogLevel::Warning, Message::NotRecording),
+        }
+    }
+
+    /// Fill the command line with a command that will store a macro again, so that the user can
+    /// edit the macro's text before pressing enter
+    fn edit_macro(&mut self, register: char) {
+        match self.macros.get(register) {
+            Some(keys) => {
+                let text = format!(":macro set {} {}", register, macros::keys_to_text(keys));
+                self.fill_command_line(&text);
+            }
+            None => self.log(LogLevel::Warning, Message::EmptyMacroRegister(register)),
+        }
+    }
+
+    /// Evaluate an expression with the tree as its environment, and log the result.  If the REPL
+    /// is open, then the command line is made ready for the next expression.
+    fn evaluate(&mut self, expression: &str) {
+        match self.tree.root().evaluate(expression) {
+            Some(Ok(result)) => self.log(
+                LogLevel::Info,
+                Message::Evaluated(expression.to_string(), result),
+            ),
+            Some(Err(error)) => self.log(
+                LogLevel::Warning,
+                Message::EvaluationFailed(expression.to_string(), error),
+            ),
+            None => {
+                let language = Node::language_name().to_string();
+                self.log(LogLevel::Warning, Message::CannotEvaluate(language));
+                self.repl_open = false;
+            }
+        }
+        if self.repl_open {
+            self.fill_command_line(REPL_COMMAND);
+        }
+    }
+
+    /// Re-read the text of the buffer as the language with a given name, for example to fix up a
+    /// buffer that was edited as text.  Only the language of this `Editor`'s nodes is available.
+    fn set_language(&mut self, name: String) {
+        if name != Node::language_name() {
+            let current = Node::language_name().to_string();
+            return self.log(
+                LogLevel::Warning,
+                Message::UnsupportedLanguage(name, current),
+            );
+        }
+        let text = self.tree.to_text(&self.format_style);
+        let structure = match Node::parse_fragment(&text).filter(|fragment| !fragment.is_truncated)
+        {
+            Some(fragment) => fragment.node,
+            None => return self.log(LogLevel::Warning, Message::CannotReparse(name)),
+        };
+        // The buffer is already in this language, so re-reading it usually gives the same tree
+        // (unless some of its text couldn't be parsed before).  That doesn't need an edit.
+        if structure.describes(self.tree.root()) {
+            return self.log(LogLevel::Info, Message::LanguageUnchanged(name));
+        }
+        match structure.to_node(self.tree.arena()) {
+            Some(node) => {
+                self.jump_to(CursorPath::root());
+                self.tree.replace_cursor(node);
+                self.log(LogLevel::Info, Message::Reparsed(name));
+                self.notify_edit(events::Operation::Replace);
+            }
+            None => self.log(LogLevel::Warning, Message::CannotReparse(name)),
+        }
+    }
+
+    /// Change the width which the tree is laid out to fit, both on screen and when it is written
+    /// to a file
+    fn set_line_width(&mut self, max_width: Option<usize>) {
+        match Node::with_max_line_width(&self.format_style, max_width) {
+            Some(format_style) => {
+                self.format_style = format_style;
+                let message = match max_width {
+                    Some(width) => Message::LineWidthSet(width),
+                    None => Message::LineWidthOff,
+                };
+                self.log(LogLevel::Info, message);
+            }
+            None => {
+                let language = Node::language_name().to_string();
+                self.log(LogLevel::Warning, Message::CannotSetLineWidth(language));
+            }
+        }
+    }
+
+    /// Change the encoding that the buffer will be saved in, as long as every character of the
+    /// buffer can be encoded in it
+    fn set_encoding(&mut self, encoding: Encoding) {
+        let text = self.tree.to_text(&self.format_style);
+        if let Some(c) = encoding.unencodable_char(&text) {
+            let message = Message::CannotEncode(c.to_string(), encoding.name().to_string());
+            return self.log(LogLevel::Warning, message);
+        }
+        self.file_format.encoding = encoding;
+        // Byte order marks are kept for UTF-16, which is hard to detect without one
+        self.file_format.bom = match encoding {
+            Encoding::Utf8 => self.file_format.bom,
+            Encoding::Utf16Le | Encoding::Utf16Be => true,
+            Encoding::Latin1 => false,
+        };
+        let format = self.file_format.to_string();
+        self.log(LogLevel::Info, Message::FileFormatSet(format));
+    }
+
+    /// Change the line endings that the buffer will be saved with
+    fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.file_format.line_ending = line_ending;
+        let format = self.file_format.to_string();
+        self.log(LogLevel::Info, Message::FileFormatSet(format));
+    }
+
+    /// Open a nested session to edit the text of the node under the cursor (e.g. JSON stored inside
+    /// a JSON string) as a tree of its own
+    fn edit_embedded(&mut self) {
+        let cursor = self.tree.cursor();
+        let node = cursor
+            .value()
+            .filter(|_| cursor.children().is_empty())
+            .and_then(|text| Node::parse_fragment(&text))
+            .filter(|fragment| !fragment.is_truncated)
+            .and_then(|fragment| fragment.node.to_node(self.tree.arena()));
+        let node = match node {
+            Some(node) => node,
+            None => {
+                let language = Node::language_name().to_string();
+                return self.log(LogLevel::Warning, Message::NotEmbedded(language));
+            }
+        };
+        let nested = E::new(self.tree.arena(), self.tree.arena().alloc(node));
+        let host = std::mem::replace(&mut *self.tree, nested);
+        let motion_history = std::mem::take(&mut self.motion_history);
+        let edit_positions = std::mem::take(&mut self.edit_positions);
+        let folds = std::mem::take(&mut self.folds);
+        self.hosts
+            .push((host, motion_history, edit_positions, folds));
+        self.log(LogLevel::Info, Message::EditingEmbedded);
+    }
+
+    /// Returns the node that the text of the innermost nested session would be written back into
+    /// the tree it was opened from as, or [`None`] if there is no nested session or its text
+    /// can't be written back
+    fn embedded_node(&self) -> Option<Node> {
+        let (host, ..) = self.hosts.last()?;
+        let text = self.tree.to_text(&self.format_style);
+        Node::from_parts(host.cursor().kind_name(), Some(&text), Vec::new())
+    }
+
+    /// Close the innermost nested session, writing its text back into the node it was opened
+    /// from.  Returns `false` if there are no nested sessions to close.
+    fn close_embedded(&mut self) -> bool {
+        let node = self.embedded_node();
+        let (host, motion_history, edit_positions, folds) = match self.hosts.pop() {
+            Some(host) => host,
+            None => return false,
+        };
+        *self.tree = host;
+        self.motion_history = motion_history;
+        self.edit_positions = edit_positions;
+        self.folds = folds;
+        let leaf = self.tree.cursor();
+        match node {
+            // Don't add an edit to the history if nothing changed
+            Some(node) if &node == leaf => {}
+            Some(node) => {
+                self.tree.replace_cursor(node);
+                self.log(LogLevel::Info, Message::EmbeddedWritten);
+                self.notify_edit(events::Operation::Replace);
+            }
+            None => self.log(LogLevel::Warning, Message::CannotWriteEmbedded),
+        }
+        true
+    }
+
+    /// Start watching an expression, unless the language can't evaluate expressions
+    fn watch(&mut self, expression: String) {
+        if self.tree.root().evaluate(&expression).is_none() {
+            let language = Node::language_name().to_string();
+            return self.log(LogLevel::Warning, Message::CannotEvaluate(language));
+        }
+        self.watches.add(expression);
+        self.update_watches();
+    }
+
+    /// Stop watching the expression with a given number (counting from 1), or every expression
+    fn unwatch(&mut self, number: Option<usize>) {
+        match number {
+            Some(number) => {
+                if !self.watches.remove(number - 1) {
+                    self.log(LogLevel::Warning, Message::NoSuchWatch(number));
+                }
+            }
+            None => {
+                self.watches.clear();
+                self.log(LogLevel::Info, Message::WatchesCleared);
+            }
+        }
+    }
+
+    /// Recompute the values of the watched expressions from the current tree
+    fn update_watches(&mut self) {
+        let root = self.tree.root();
+        self.watches.update(|expression| root.evaluate(expression));
+    }
+
+    /// Pin the node under the cursor, so that its text stays on screen
+    fn pin(&mut self) {
+        let path = self.tree.cursor_path().clone();
+        let text = self.pin_text(self.tree.cursor());
+        if let Some(removed) = self.pins.pin(path.clone(), self.tree.cursor(), text) {
+            self.log(LogLevel::Info, Message::Unpinned(removed.path.to_string()));
+        }
+        self.log(LogLevel::Info, Message::Pinned(path.to_string()));
+    }
+
+    /// Unpin the node with a given number (counting from 1), or every node
+    fn unpin(&mut self, number: Option<usize>) {
+        match number {
+            Some(number) => {
+                if !self.pins.remove(number - 1) {
+                    self.log(LogLevel::Warning, Message::NoSuchPin(number));
+                }
+            }
+            None => {
+                self.pins.clear();
+                self.log(LogLevel::Info, Message::PinsCleared);
+            }
+        }
+    }
+
+    /// Recompute the text of every pinned node from the current tree.  Pins follow their nodes
+    /// as edits move them (see [`Pins::adjust`]).
+    fn update_pins(&mut self) {
+        let root = self.tree.root();
+        let mut pins = std::mem::take(&mut self.pins);
+        pins.update(root, |node| self.pin_text(node));
+        self.pins = pins;
+    }
+
+    /// Move the folds, pins, edit positions and bookmarks along with their nodes after an edit at
+    /// `edit_path`
+    fn adjust_to_edit(&mut self, edit_path: &CursorPath) {
+        let root = self.tree.root();
+        self.folds.adjust(root, edit_path);
+        self.pins.adjust(root, edit_path);
+        self.edit_positions.adjust(root, edit_path);
+        let bookmarks = &mut self.bookmarks;
+        self.bookmarked_nodes.retain(|name, node| {
+            let path = match bookmarks.get(*name) {
+                Some(bookmark) => bookmark.path.clone(),
+                None => return false,
+            };
+            if let Some(new_path) = cursor_path::follow(root, &path, node, edit_path) {
+                if new_path != path {
+                    bookmarks.move_to(*name, new_path);
+                }
+                return true;
+            }
+            // An edit inside the bookmarked node replaces it with a changed copy, which is still
+            // the same node as far as the bookmark is concerned
+            if edit_path.depth() > path.depth() && edit_path.starts_with(&path) {
+                *node = path.cursor(root);
+                return true;
+            }
+            false
+        });
+    }
+
+    /// Give the node under the cursor a bookmark, and save the bookmarks of the buffer's file
+    fn set_bookmark(&mut self, name: char) {
+        if !self.hosts.is_empty() {
+            return self.log(LogLevel::Warning, Message::BookmarksInNestedSession);
+        }
+        let path = self.tree.cursor_path().clone();
+        self.bookmarks.set(name, path.clone(), self.tree.cursor());
+        self.bookmarked_nodes.insert(name, self.tree.cursor());
+        self.log(LogLevel::Info, Message::BookmarkSet(name, path.to_string()));
+        self.save_bookmarks();
+    }
+
+    /// Move the cursor to the node with a given bookmark, warning the user if that node has
+    /// changed since the bookmark was set
+    fn jump_to_bookmark(&mut self, name: char) {
+        if !self.hosts.is_empty() {
+            return self.log(LogLevel::Warning, Message::BookmarksInNestedSession);
+        }
+        let bookmark = match self.bookmarks.get(name) {
+            Some(bookmark) => bookmark.clone(),
+            None => return self.log(LogLevel::Warning, Message::NoSuchBookmark(name)),
+        };
+        let root = self.tree.root();
+        if !bookmark.path.is_valid(root) {
+            return self.log(LogLevel::Warning, Message::BookmarkMissing(name));
+        }
+        if bookmark.is_stale(root) {
+            self.log(LogLevel::Warning, Message::StaleBookmark(name));
+        }
+        self.jump_to(bookmark.path);
+    }
+
+    /// Load the bookmarks of the buffer's file, which has just been opened, and warn the user
+    /// about any whose nodes have changed since they were saved
+    fn load_bookmarks(&mut self) {
+        let path = match self
+            .path
+            .as_deref()
+            .and_then(|p| bookmarks::bookmarks_path(p.as_ref()))
+        {
+            Some(path) => path,
+            None => return,
+        };
+        match Bookmarks::load(&path) {
+            Ok(bookmarks) => self.bookmarks = bookmarks,
+            Err(error) => {
+                let path = path.to_string_lossy().into_owned();
+                return self.log(LogLevel::Warning, Message::CannotLoadBookmarks(path, error));
+            }
+        }
+        let root = self.tree.root();
+        let stale = self.bookmarks.stale(root);
+        self.bookmarked_nodes = self
+            .bookmarks
+            .iter()
+            .filter(|(name, _)| !stale.contains(name))
+            .map(|(name, bookmark)| (name, bookmark.path.cursor(root)))
+            .collect();
+        self.warn_stale_bookmarks(&stale);
+    }
+
+    /// Warn the user about bookmarks whose nodes have changed since they were saved
+    fn warn_stale_bookmarks(&mut self, stale: &[char]) {
+        if !stale.is_empty() {
+            let names: Vec<String> = stale.iter().map(char::to_string).collect();
+            self.log(LogLevel::Warning, Message::StaleBookmarks(names.join(", ")));
+        }
+    }
+
+    /// Save the bookmarks of the buffer's file.  Buffers without a file keep their bookmarks
+    /// until they are written to one.
+    fn save_bookmarks(&mut self) {
+        let path = match self
+            .path
+            .as_deref()
+            .and_then(|p| bookmarks::bookmarks_path(p.as_ref()))
+        {
+            Some(path) => path,
+            None => return,
+        };
+        if let Err(e) = self.bookmarks.save(&path) {
+            let path = path.to_string_lossy().into_owned();
+            self.log(
+                LogLevel::Warning,
+                Message::CannotSaveBookmarks(path, e.to_string()),
+            );
+        }
+    }
+
+    /// Returns the text of a node squashed onto one line, to be shown in the pins pane
+    fn pin_text(&self, node: &'arena Node) -> String {
+        let text = node.to_text(&self.format_style);
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        lines.join(" ")
+    }
+
+    /// Do every piece of scheduled work which is waiting for the user to be idle, if they have
+    /// been idle for long enough.  Returns `true` if any work was done.
+    fn do_idle_work(&mut self, now: Instant) -> bool {
+        let tasks = self.idle.take_due(now);
+        for task in &tasks {
+            match task {
+                IdleTask::UpdateWatches => self.update_watches(),
+                IdleTask::UpdatePins => self.update_pins(),
+                IdleTask::RefreshSearchIndex => self.refresh_search_index(),
+                IdleTask::Validate => self.validate(),
+                IdleTask::Autosave => self.autosave(),
+            }
+        }
+        !tasks.is_empty()
+    }
+
+    /// Count the parts of the tree which aren't valid, and tell the user if there are more or fewer
+    /// than last time
+    fn validate(&mut self) {
+        let num_invalid = count_invalid(self.tree.root());
+        if num_invalid == self.num_invalid {
+            return;
+        }
+        self.num_invalid = num_invalid;
+        match num_invalid {
+            0 => self.log(LogLevel::Info, Message::TreeValid),
+            n => self.log(LogLevel::Warning, Message::TreeInvalid(n)),
+        }
+    }
+
+    /// Write the tree to its file if autosaving is turned on and the tree has unsaved changes.
+    /// Trees with parts which aren't valid, nested sessions and files which are still being
+    /// opened are only written when the user asks.
+    fn autosave(&mut self) {
+        let is_saved = self.tree.saved_index() == Some(self.tree.history_index());
+        if !self.config.autosave
+            || is_saved
+            || self.path.is_none()
+            || self.num_invalid > 0
+            || !self.hosts.is_empty()
+            || self.loader.is_some()
+        {
+            return;
+        }
+        self.write(None);
+    }
+
+    /// Open or close the REPL
+    fn toggle_repl(&mut self) {
+        self.repl_open = !self.repl_open;
+        if self.repl_open {
+            self.fill_command_line(REPL_COMMAND);
+            self.log(LogLevel::Info, Message::ReplOpened);
+        } else {
+            self.log(LogLevel::Info, Message::ReplClosed);
+        }
+    }
+
+    /// Store the text form of a macro in a register
+    fn set_macro(&mut self, register: char, text: &str) {
+        match macros::text_to_keys(text) {
+            Ok(keys) => {
+                self.macros.set(register, keys);
+                self.log(LogLevel::Info, Message::MacroSet(register));
+            }
+            Err(invalid_text) => {
+                self.log(LogLevel::Warning, Message::InvalidMacroKey(invalid_text))
+            }
+        }
+    }
+
+    /// Insert new child as the first child of the selected node
+    fn insert_child(&mut self, c: char) {
+        let arena = self.tree.arena();
+        let insert = |node: &'arena Node| with_new_child(arena, node, c).map(|(node, _)| node);
+        if self.batch_edit(insert, events::Operation::Insert) || self.open_completion(c) {
+            return;
+        }
+        self.insert_new_child(c);
+    }
+
+    /// Insert the child that `c` inserts into the selected node, without offering completions
+    fn insert_new_child(&mut self, c: char) {
+        let arena = self.tree.arena();
+        let (new_node, child_cursor) = match with_new_child(arena, self.tree.cursor(), c) {
+            Some(node) => node,
+            None => return self.log(LogLevel::Warning, Message::CannotInsert(c)),
+        };
+        self.log(LogLevel::Debug, Message::Inserting(c));
+        self.replace_with_inserted(new_node, child_cursor);
+    }
+
+    /// Insert a new child like [`insert_child`](Editor::insert_child), and start typing into its
+    /// text if the insertion moved the cursor to a node which has text
+    fn insert_and_edit(&mut self, c: char) {
+        let history_index = self.tree.history_index();
+        self.insert_child(c);
+        // If the completion menu opened instead, then typing starts once it inserts something
+        match &mut self.completion {
+            Some(menu) => menu.edit_after_insert(),
+            None => self.edit_inserted_text(history_index),
+        }
+    }
+
+    /// Start typing into the text of the node under the cursor, if an insertion has been made
+    /// since the undo history was at `history_index` and moved the cursor to a node with text
+    fn edit_inserted_text(&mut self, history_index: usize) {
+        if self.tree.history_index() != history_index && self.tree.cursor().value().is_some() {
+            self.mode = Mode::Insert;
+            self.log(LogLevel::Info, Message::TypingText);
+        }
+    }
+
+    /// Start typing into the text of the node under the cursor, if it has any
+    fn edit_text(&mut self) {
+        let cursor = self.tree.cursor();
+        if cursor.value().is_none() {
+            let kind = cursor.kind_name().to_string();
+            return self.log(LogLevel::Warning, Message::NoTextToEdit(kind));
+        }
+        self.mode = Mode::Insert;
+        self.log(LogLevel::Info, Message::TypingText);
+    }
+
+    /// Respond to a key press whilst typing into the text of the node under the cursor.  Every
+    /// change made by typing is merged into one entry in the undo history.
+    fn handle_text_key(&mut self, key: Key) {
+        let cursor = self.tree.cursor();
+        let mut text = match cursor.value() {
+            Some(text) => text,
+            None => {
+                self.mode = Mode::Normal;
+                return;
+            }
+        };
+        match key {
+            Key::Char(c) => text.push(c),
+            Key::Backspace => {
+                if text.pop().is_none() {
+                    return;
+                }
+            }
+            Key::Enter | Key::ESC => {
+                self.mode = Mode::Normal;
+                return;
+            }
+            _ => return,
+        }
+        // Languages can refuse some text, in which case the key press is ignored
+        if let Some(node) =
+            Node::from_parts(cursor.kind_name(), Some(&text), cursor.children().to_vec())
+        {
+            self.tree.replace_cursor_coalesced(node, Instant::now());
+            self.notify_edit(events::Operation::Replace);
+        }
+    }
+
+    /// Open the completion menu, if `c` inserts a child into the cursor and the schema allows
+    /// keys to be added to it.  Returns `false` if the menu wasn't opened.
+    fn open_completion(&mut self, c: char) -> bool {
+        let schema = match &self.schema {
+            Some(schema) => schema,
+            None => return false,
+        };
+        if !self.tree.cursor().is_insert_char(c) {
+            return false;
+        }
+        let completions = self
+            .tree
+            .root()
+            .schema_keys(self.tree.cursor_path(), schema)
+            .into_iter()
+            .map(|(key, schema)| Completion {
+                key: key.to_string(),
+                schema: schema.clone(),
+            })
+            .collect();
+        self.completion = CompletionMenu::new(completions, c);
+        self.completion.is_some()
+    }
+
+    /// Respond to a key press whilst the completion menu is open
+    fn handle_completion_key(&mut self, key: Key) {
+        if key == Key::Enter || key == Key::ESC {
+            let menu = match self.completion.take() {
+                Some(menu) => menu,
+                None => return,
+            };
+            let history_index = self.tree.history_index();
+            // Closing the menu without choosing a key still inserts what the key press would
+            // have inserted without a schema
+            if key == Key::Enter {
+                self.insert_completion(menu.selected().clone());
+            } else {
+                self.insert_new_child(menu.insert_char());
+            }
+            if menu.edits_text() {
+                self.edit_inserted_text(history_index);
+            }
+            return;
+        }
+        let menu = match &mut self.completion {
+            Some(menu) => menu,
+            None => return,
+        };
+        match key {
+            Key::Down => menu.select(true),
+            Key::Up => menu.select(false),
+            key => match self.keymap.get(key) {
+                Some(Command::MoveCursor(Direction::Next)) => menu.select(true),
+                Some(Command::MoveCursor(Direction::Prev)) => menu.select(false),
+                _ => {}
+            },
+        }
+    }
+
+    /// Open the command palette, listing every bound command followed by the commands of the
+    /// buffer's language
+    fn open_palette(&mut self) {
+        let mut bindings = self.keymap.bindings();
+        // Commands bound to more than one sequence of keys are listed under the shortest one
+        bindings.sort_by_key(|(keys, command)| (format!("{:?}", command), keys.len()));
+        bindings.dedup_by(|(_, a), (_, b)| a == b);
+        let entries = bindings
+            .into_iter()
+            .filter(|(_, command)| *command != Command::CommandPalette)
+            .map(|(keys, command)| PaletteEntry {
+                name: format!("{:?}", command),
+                description: keymap::sequence_text(&keys),
+                action: PaletteAction::Keys(keys),
+            })
+            .chain(
+                Node::language_commands()
+                    .into_iter()
+                    .map(|command| PaletteEntry {
+                        name: command.name.to_string(),
+                        description: self.messages.format(&command.description),
+                        action: PaletteAction::LanguageCommand(command.name.to_string()),
+                    }),
+            )
+            .collect();
+        self.palette = Some(Palette::new(entries));
+    }
+
+    /// Open the transform menu, which is a palette listing the structural edits which can be made
+    /// to the node under the cursor.  The edits are only checked cheaply (e.g. whether the new
+    /// node could go inside the cursor's parent), so that opening the menu doesn't build every
+    /// edit.  The chosen edit is checked in full when it is made.  The language's own commands are
+    /// always listed, and say why they can't be run if they are chosen somewhere they don't apply.
+    fn open_transform_menu(&mut self) {
+        let root = self.tree.root();
+        let cursor = self.tree.cursor();
+        let parent = self.cursor_parent();
+        let mut parent_path = self.tree.cursor_path().clone();
+        parent_path.pop();
+        // Whether a new node could replace the cursor inside its parent
+        let fits = |new_node: &Node| {
+            parent.is_none_or(|(parent, index)| parent.is_valid_child(index, new_node))
+        };
+        let mut entries = Vec::new();
+        let messages = &self.messages;
+        let mut add = |name: String, description: Message, action: Action| {
+            entries.push(PaletteEntry {
+                name,
+                description: messages.format(&description),
+                action: PaletteAction::Perform(action),
+            });
+        };
+        let kind_of = |c: char| cursor.from_char(c).map_or("", |node| node.kind_name());
+        for c in cursor.replace_chars() {
+            if restructure::wrap(cursor, c).is_ok_and(|wrapper| fits(&wrapper)) {
+                let description = Message::DescribeWrap(kind_of(c).to_string());
+                add(format!("wrap {}", c), description, Action::Wrap(c));
+            }
+        }
+        let children = cursor.children();
+        if let Some((parent, index)) = parent {
+            let unwraps = children
+                .iter()
+                .all(|child| parent.is_valid_child(index, child));
+            if !children.is_empty() && unwraps {
+                add(
+                    "unwrap".to_string(),
+                    Message::DescribeUnwrap,
+                    Action::Unwrap,
+                );
+            }
+            // The parent is replaced inside the grandparent, if there is one
+            let raises = match (
+                parent_path.cursor_and_parent(root).1,
+                parent_path.iter().last(),
+            ) {
+                (Some(grandparent), Some(parent_index)) => {
+                    grandparent.is_valid_child(*parent_index, cursor)
+                }
+                _ => true,
+            };
+            if raises {
+                add("raise".to_string(), Message::DescribeRaise, Action::Raise);
+            }
+        }
+        for c in cursor.replace_chars() {
+            if restructure::convert(cursor, c).is_ok_and(|new_node| fits(&new_node)) {
+                let description = Message::DescribeConvert(kind_of(c).to_string());
+                add(format!("convert {}", c), description, Action::Convert(c));
+            }
+        }
+        // Sorting can put any child first or last
+        let last = children.len().saturating_sub(1);
+        let sorts = children
+            .iter()
+            .all(|child| cursor.is_valid_child(0, child) && cursor.is_valid_child(last, child));
+        if !children.is_empty() && sorts {
+            add(
+                "sort".to_string(),
+                Message::DescribeSort,
+                Action::Sort(None),
+            );
+        }
+        if let Some((parent, index)) = parent {
+            let siblings = parent.children();
+            let next = siblings.get(index + 1).copied();
+            if next.is_some_and(|next| cursor.is_valid_child(children.len(), next)) {
+                add("slurp".to_string(), Message::DescribeSlurp, Action::Slurp);
+            }
+            if children
+                .last()
+                .is_some_and(|last| parent.is_valid_child(index + 1, last))
+            {
+                add("barf".to_string(), Message::DescribeBarf, Action::Barf);
+            }
+            let joins = next.is_some_and(|next| {
+                next.kind_name() == cursor.kind_name()
+                    && next.value() == cursor.value()
+                    && !(children.is_empty() && next.children().is_empty())
+                    && (next.children().iter().enumerate())
+                        .all(|(i, child)| cursor.is_valid_child(children.len() + i, child))
+            });
+            if joins {
+                add("join".to_string(), Message::DescribeJoin, Action::Join);
+            }
+            let swaps = |other_index: usize| {
+                siblings.get(other_index).is_some_and(|other| {
+                    parent.is_valid_child(other_index, cursor)
+                        && parent.is_valid_child(index, other)
+                })
+            };
+            if swaps(index + 1) {
+                let description = Message::DescribeTransposeNext;
+                add(
+                    "transpose next".to_string(),
+                    description,
+                    Action::Transpose(true),
+                );
+            }
+            if index.checked_sub(1).is_some_and(swaps) {
+                let description = Message::DescribeTransposePrev;
+                add(
+                    "transpose prev".to_string(),
+                    description,
+                    Action::Transpose(false),
+                );
+            }
+        }
+        for command in Node::language_commands() {
+            entries.push(PaletteEntry {
+                name: command.name.to_string(),
+                description: self.messages.format(&command.description),
+                action: PaletteAction::Perform(Action::RunLanguageCommand(
+                    command.name.to_string(),
+                )),
+            });
+        }
+        if entries.is_empty() {
+            return self.log(LogLevel::Info, Message::NoTransforms);
+        }
+        self.palette = Some(Palette::new(entries));
+    }
+
+    /// Respond to a key press whilst the command palette is open.  Returns `true` if the chosen
+    /// command means that Sapling should quit.
+    fn handle_palette_key(&mut self, key: Key) -> bool {
+        let palette = match &mut self.palette {
+            Some(palette) => palette,
+            None => return false,
+        };
+        match key {
+            Key::Enter => {
+                let action = palette.selected().map(|entry| entry.action.clone());
+                self.palette = None;
+                match action {
+                    // Commands which expect arguments wait for them as if their keys were typed
+                    Some(PaletteAction::Keys(keys)) => {
+                        self.command = keys;
+                        return self.continue_command();
+                    }
+                    Some(PaletteAction::LanguageCommand(name)) => {
+                        return self.execute_command(Action::RunLanguageCommand(name));
+                    }
+                    Some(PaletteAction::Perform(action)) => {
+                        return self.execute_command(action);
+                    }
+                    None => {}
+                }
+            }
+            Key::ESC => self.palette = None,
+            Key::Down => palette.select(true),
+            Key::Up => palette.select(false),
+            Key::Backspace => palette.delete_back(),
+            key => {
+                if let Some(c) = KeyInput::from(key).as_char() {
+                    palette.type_char(c);
+                }
+            }
+        }
+        false
+    }
+
+    /// Insert a key chosen from the completion menu into the cursor, with a value of the kind
+    /// given by its schema
+    fn insert_completion(&mut self, completion: Completion) {
+        let arena = self.tree.arena();
+        let cursor = self.tree.cursor();
+        let inserted = cursor
+            .new_child_from_schema(&completion.key, &completion.schema, arena)
+            .and_then(|(child, child_cursor)| {
+                Some((with_child_first(arena, cursor, child)?, child_cursor))
+            });
+        match inserted {
+            Some((new_node, child_cursor)) => self.replace_with_inserted(new_node, child_cursor),
+            None => self.log(LogLevel::Warning, Message::CannotInsertKey(completion.key)),
+        }
+    }
+
+    /// Replace the cursor with a copy of itself that has a new first child, and move the cursor
+    /// to wherever the [`Landing`] says (`child_cursor` being the path to the node within the new
+    /// child that should be filled in first)
+    fn replace_with_inserted(&mut self, new_node: Node, child_cursor: CursorPath) {
+        self.tree.replace_cursor(new_node);
+        self.record_summary(events::Operation::Insert);
+        let mut path = self.tree.cursor_path().clone();
+        match self.config.landing {
+            Landing::Parent => {}
+            Landing::NewNode => {
+                path.push(0);
+                self.jump_to(path);
+            }
+            Landing::FirstHole => {
+                path.push(0);
+                path.extend(child_cursor.iter().copied());
+                self.jump_to(path);
+            }
+        }
+        self.notify_edit(events::Operation::Insert);
+    }
+
+    /// Undo the latest change
+    fn undo(&mut self) {
+        let summary = self.current_summary();
+        // Undoing moves the cursor to where it was before the change rather than to the change
+        let undone_path = self.tree.current_entry().cursor_path.clone();
+        if self.tree.undo() {
+            self.adjust_to_edit(&undone_path);
+            match summary {
+                Some(summary) => self.log(LogLevel::Info, Message::UndoneChange(summary)),
+                None => self.log(LogLevel::Debug, Message::UndoSuccessful),
+            }
+            self.notify_edit(events::Operation::Undo);
+        } else {
+            self.log(LogLevel::Info, Message::NothingToUndo);
+        }
+    }
+
+    /// Move one change forward in the history
+    fn redo(&mut self) {
+        if self.tree.redo() {
+            match self.current_summary() {
+                Some(summary) => self.log(LogLevel::Info, Message::RedoneChange(summary)),
+                None => self.log(LogLevel::Debug, Message::RedoSuccessful),
+            }
+            self.notify_edit(events::Operation::Redo);
+        } else {
+            self.log(LogLevel::Info, Message::NothingToRedo);
+        }
+    }
+
+    /// Check that the history restores the tree and cursor of every entry, logging any problems
+    fn check_history(&mut self) {
+        let problems = conformance::check_history(self.tree);
+        if problems.is_empty() {
+            let num_entries = self.tree.history().len();
+            self.log(LogLevel::Info, Message::HistoryConsistent(num_entries));
+        }
+        for problem in problems {
+            self.log(LogLevel::Warning, problem.message());
+        }
+    }
+
+    /// Move straight to a different entry in the history, undoing or redoing every change in
+    /// between
+    fn move_through_history(&mut self, index: usize) {
+        let current_index = self.tree.history_index();
+        let undone_path = self.tree.current_entry().cursor_path.clone();
+        self.tree.checkout(index);
+        if index < current_index {
+            self.adjust_to_edit(&undone_path);
+            self.log(LogLevel::Info, Message::UndoneSteps(current_index - index));
+            self.notify_edit(events::Operation::Undo);
+        } else {
+            self.log(LogLevel::Info, Message::RedoneSteps(index - current_index));
+            self.notify_edit(events::Operation::Redo);
+        }
+    }
+
+    /// Respond to a key press whilst the history view is open
+    fn handle_history_key(&mut self, key: Key) {
+        let num_entries = self.tree.history().len();
+        let view = match &mut self.history_view {
+            Some(view) => view,
+            None => return,
+        };
+        match key {
+            Key::Enter => {
+                let index = view.selected();
+                self.history_view = None;
+                if index != self.tree.history_index() {
+                    self.move_through_history(index);
+                }
+            }
+            Key::ESC => self.history_view = None,
+            Key::Down => view.select(true, num_entries),
+            Key::Up => view.select(false, num_entries),
+            key => match self.keymap.get(key) {
+                Some(Command::MoveCursor(Direction::Next)) => view.select(true, num_entries),
+                Some(Command::MoveCursor(Direction::Prev)) => view.select(false, num_entries),
+                Some(Command::Quit) => self.history_view = None,
+                _ => {}
+            },
+        }
+    }
+
+    /// Move back or forward through the history to the state which was last saved
+    fn undo_to_saved(&mut self) {
+        match self.tree.saved_index() {
+            Some(index) if index == self.tree.history_index() => {
+                self.log(LogLevel::Info, Message::AlreadySaved)
+            }
+            Some(index) => {
+                self.move_through_history(index);
+                self.log(LogLevel::Info, Message::ReturnedToSaved);
+            }
+            None => self.log(LogLevel::Warning, Message::SavedStateLost),
+        }
+    }
+
+    /// Highlight every match of a search query, and move the cursor to the next match.  The query
+    /// is either some text or a kind of node (see [`Pattern::parse`]).
+    fn search(&mut self, query: String) {
+        if query.is_empty() {
+            self.log(LogLevel::Warning, Message::EmptySearch);
+            return;
+        }
+        self.search_pattern = Some(Pattern::parse(&query));
+        self.step_search(true);
+    }
+
+    /// Move the cursor to the next match of the last search (or to the previous match, if
+    /// `forward` is `false`)
+    fn step_search(&mut self, forward: bool) {
+        let pattern = match &self.search_pattern {
+            Some(pattern) => pattern,
+            None => return self.log(LogLevel::Info, Message::NoSearch),
+        };
+        let root = self.tree.root();
+        let cursor = self.tree.cursor_path();
+        let next_match = match (&self.search_index, pattern) {
+            (Some(index), Pattern::Text(text)) if index.is_for(root) => {
+                index.next_match(text, cursor, forward)
+            }
+            // Without an up-to-date index, every node has to be searched
+            _ => search::next_match(root, &self.format_style, pattern, cursor, forward),
+        };
+        match next_match {
+            Some(path) => self.jump_to(path),
+            None => self.log(LogLevel::Info, Message::NoMatches(pattern.to_string())),
+        }
+    }
+
+    /// Make sure that the search index is up to date with the tree, if the tree is large enough to
+    /// need one.  An existing index is updated (which only re-indexes the edited subtree) rather
+    /// than being built again.
+    fn refresh_search_index(&mut self) {
+        let root = self.tree.root();
+        if let Some(index) = &mut self.search_index {
+            return index.update(root, &self.format_style);
+        }
+        // The index holds the path of every node, so trees which are too deep are searched
+        // without one
+        let is_too_deep = limits::find_oversized(root, &self.config.limits)
+            .iter()
+            .any(|(_, _, excess)| *excess == limits::Excess::TooDeep);
+        if root.subtree_size() >= search::INDEX_THRESHOLD && !is_too_deep {
+            self.search_index = Some(SearchIndex::build(root, &self.format_style));
+        }
+    }
+
+    /// Start opening the file at a given path in the background.  The tree is replaced by the
+    /// start of the file as soon as it has been parsed, and any errors are shown in the log.
+    pub fn open(&mut self, path: &str) {
+        let language = Node::language_name();
+        let label = self.messages.format(&Message::Opening(path.to_string()));
+        let loader = match Loader::start(path, language, &label, &self.progress.sender()) {
+            Some(loader) => loader,
+            None => {
+                let language = language.to_string();
+                return self.log(LogLevel::Warning, Message::CannotOpenLanguage(language));
+            }
+        };
+        self.loader = Some(loader);
+        self.failed_open = None;
+        self.hosts.clear();
+        self.path = Some(path.to_string());
+        self.trivia = TriviaMap::new();
+        self.bookmarks = Bookmarks::new();
+        self.bookmarked_nodes.clear();
+        self.edit_positions = EditPositions::new();
+        self.pins.clear();
+        self.folds = Folds::new();
+    }
+
+    /// Replace the tree with a new document, made from the user's template for this language or
+    /// (if they haven't configured one) the language's own template
+    pub fn new_document(&mut self) {
+        let language = Node::language_name();
+        let template = match self.config.templates.get(language) {
+            Some(template) => Some(template.as_str()),
+            None => Node::new_file_template(),
+        };
+        let template = match template {
+            Some(template) => template,
+            None => return self.log(LogLevel::Warning, Message::NoTemplate(language.to_string())),
+        };
+        let arena = self.tree.arena();
+        let root = Node::parse_fragment(template)
+            .filter(|fragment| !fragment.is_truncated)
+            .and_then(|fragment| fragment.node.to_node(arena));
+        let root = match root {
+            Some(root) => arena.alloc(root),
+            None => {
+                let language = language.to_string();
+                return self.log(LogLevel::Warning, Message::InvalidTemplate(language));
+            }
+        };
+        // Like opening a file, this starts a new history rather than adding to the old one
+        self.loader = None;
+        self.failed_open = None;
+        self.hosts.clear();
+        *self.tree = E::new(arena, root);
+        self.path = None;
+        self.file_format = FileFormat::default();
+        self.trivia = TriviaMap::new();
+        self.bookmarks = Bookmarks::new();
+        self.bookmarked_nodes.clear();
+        self.edit_positions = EditPositions::new();
+        self.pins.clear();
+        self.folds = Folds::new();
+        self.idle.schedule(IdleTask::UpdateWatches);
+        self.idle.schedule(IdleTask::UpdatePins);
+        self.idle.schedule(IdleTask::RefreshSearchIndex);
+        self.log(LogLevel::Info, Message::NewDocument(language.to_string()));
+    }
+
+    /// Write the text of the tree to a file (or, if no path is given, to the buffer's file) in the
+    /// buffer's file format.  Like in Vim, writing to another file (e.g. a backup) leaves the
+    /// buffer's file unchanged, unless the buffer didn't have one.  Nothing is written whilst a
+    /// file is being opened, or over a file which couldn't be fully opened.  Any nested sessions
+    /// are closed first, so that the whole file is written.  Returns `true` if the file was
+    /// written.
+    fn write(&mut self, path: Option<String>) -> bool {
+        let path = match path.or_else(|| self.path.clone()) {
+            Some(path) => path,
+            None => {
+                self.log(LogLevel::Warning, Message::NoFileName);
+                return false;
+            }
+        };
+        // Until a file has been fully read, the tree only holds part of it
+        if let Some(loader) = &self.loader {
+            let message = Message::CannotWriteWhileOpening(loader.path().to_string());
+            self.log(LogLevel::Warning, message);
+            return false;
+        }
+        if self.failed_open.as_deref() == Some(path.as_str()) {
+            self.log(LogLevel::Warning, Message::CannotWriteFailedOpen(path));
+            return false;
+        }
+        while !self.hosts.is_empty() {
+            // Closing a session whose text isn't valid would throw its changes away
+            if self.embedded_node().is_none() {
+                self.log(LogLevel::Warning, Message::CannotWriteEmbedded);
+                return false;
+            }
+            self.close_embedded();
+        }
+        let mut text = trivia::write_text(
+            self.tree.root(),
+            &self.format_style,
+            &self.trivia,
+            self.config.indentation.unwrap_or_default(),
+        );
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let bytes = match self.file_format.encode(&text) {
+            Ok(bytes) => bytes,
+            Err(c) => {
+                let encoding = self.file_format.encoding.name().to_string();
+                self.log(
+                    LogLevel::Error,
+                    Message::CannotEncode(c.to_string(), encoding),
+                );
+                return false;
+            }
+        };
+        match save::write_atomically(std::path::Path::new(&path), &bytes) {
+            Ok(()) => {
+                self.log(LogLevel::Info, Message::Written(path.clone(), bytes.len()));
+                if self.path.is_none() {
+                    self.path = Some(path.clone());
+                }
+                // Only the buffer's own file holds what the tree has been saved as
+                if self.path.as_deref() == Some(path.as_str()) {
+                    self.tree.mark_saved();
+                    // The bookmarks of nodes which are still known now describe the file as it
+                    // has been saved
+                    let nodes = &self.bookmarked_nodes;
+                    let stale = self.bookmarks.refresh(self.tree.root(), |name, node| {
+                        nodes.get(&name).is_some_and(|n| std::ptr::eq(*n, node))
+                    });
+                    self.warn_stale_bookmarks(&stale);
+                    self.save_bookmarks();
+                }
+                true
+            }
+            Err(e) => {
+                self.log(LogLevel::Error, Message::CannotWrite(path, e.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Replace the tree with whatever has been loaded since this was last called.  Returns `true`
+    /// if anything changed.
+    fn poll_loader(&mut self) -> bool {
+        let arena = self.tree.arena();
+        let (update, path) = match &mut self.loader {
+            Some(loader) => match loader.poll(arena) {
+                Some(update) => (update, loader.path().to_string()),
+                None => return false,
+            },
+            None => return false,
+        };
+        let mut parse_errors = Vec::new();
+        let (root, parsed_trivia) = match update {
+            Loaded::Prefix(root) => (root, None),
+            Loaded::Finished(result) => {
+                self.loader = None;
+                match result {
+                    Ok(document) => {
+                        self.file_format = document.format;
+                        self.log(LogLevel::Info, Message::Opened(path));
+                        parse_errors = document.errors;
+                        let root = match document.root.to_node(arena) {
+                            Some(root) => arena.alloc(root),
+                            None => return false,
+                        };
+                        (root, Some(document.trivia))
+                    }
+                    Err(error) => {
+                        // Whatever was loaded before the error stays, so it can still be read (or
+                        // written to another file)
+                        self.failed_open = Some(path);
+                        self.log(LogLevel::Error, error);
+                        return true;
+                    }
+                }
+            }
+        };
+        let is_finished = parsed_trivia.is_some();
+        if let Some(parsed_trivia) = parsed_trivia {
+            self.trivia = TriviaMap::from_parsed(root, parsed_trivia);
+        }
+        // Loading isn't an edit, so it starts a new history rather than adding to the old one.
+        // The cursor stays where it was, if it can.
+        let cursor_path = self.tree.cursor_path().clone();
+        *self.tree = E::new(arena, root);
+        self.tree.jump_to(cursor_path);
+        if is_finished {
+            self.load_bookmarks();
+            self.check_limits();
+            // The parse errors are reported below, so validation only reports later changes
+            self.num_invalid = count_invalid(self.tree.root());
+        }
+        if !parse_errors.is_empty() {
+            self.show_parse_errors(parse_errors);
+        }
+        self.idle.schedule(IdleTask::UpdateWatches);
+        self.idle.schedule(IdleTask::UpdatePins);
+        self.idle.schedule(IdleTask::RefreshSearchIndex);
+        true
+    }
+
+    /// Put the parts of a file which couldn't be parsed when it was opened in the quickfix list,
+    /// and move the cursor to the first one
+    fn show_parse_errors(&mut self, errors: Vec<ParseError>) {
+        let count = errors.len();
+        let entries = errors
+            .into_iter()
+            .map(|error| {
+                let reason = self.messages.format(&error.message);
+                (error.path, Message::ParseError(error.line, reason))
+            })
+            .collect();
+        self.quickfix = Quickfix::with_notes(entries);
+        self.log(LogLevel::Warning, Message::ParseErrorsRecovered(count));
+        if let Some(path) = self.quickfix.current().cloned() {
+            self.jump_to(path);
+        }
+        if let Some(note) = self.quickfix.note().cloned() {
+            self.log(LogLevel::Warning, note);
+        }
+    }
+
+    /// Load the schema from the file at a given path, replacing any schema that was loaded before
+    fn load_schema(&mut self, path: &str) {
+        let schema = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Schema::parse(&text));
+        match schema {
+            Ok(schema) => {
+                self.schema = Some(schema);
+                self.log(LogLevel::Info, Message::SchemaLoaded(path.to_string()));
+            }
+            Err(error) => self.log(
+                LogLevel::Warning,
+                Message::CannotLoadSchema(path.to_string(), error),
+            ),
+        }
+    }
+
+    /// Write the active keymap to the file at `path`, as the `[keymap]` table of a config file
+    fn export_keymap(&mut self, path: &str) {
+        match std::fs::write(path, self.keymap.to_config()) {
+            Ok(()) => self.log(LogLevel::Info, Message::KeymapExported(path.to_string())),
+            Err(e) => self.log(
+                LogLevel::Warning,
+                Message::CannotExportKeymap(path.to_string(), e.to_string()),
+            ),
+        }
+    }
+
+    /// Replace the active keymap with the one in the config file at `path`.  The language's and
+    /// the project's bindings still override it, as they did the keymap it replaces.
+    fn import_keymap(&mut self, path: &str) {
+        let mut keymap = match KeyMap::import(std::path::Path::new(path)) {
+            Ok(keymap) => keymap,
+            Err(error) => {
+                let message = Message::CannotImportKeymap(path.to_string(), error);
+                return self.log(LogLevel::Warning, message);
+            }
+        };
+        if let Some(overrides) = self.config.language_bindings.get(Node::language_name()) {
+            keymap::apply_overrides(&mut keymap, overrides);
+        }
+        keymap::apply_overrides(&mut keymap, &self.config.project_bindings);
+        self.keymap = keymap;
+        self.log(LogLevel::Info, Message::KeymapImported(path.to_string()));
+    }
+
+    /// Move the cursor to the first node matched by a path query.  If several nodes match, they
+    /// are all put in the quickfix list.
+    fn query(&mut self, query: &str) {
+        let paths = match self.tree.root().query(query) {
+            Some(Ok(paths)) => paths,
+            Some(Err(error)) => {
+                let message = Message::InvalidQuery(self.messages.format(&error));
+                return self.log(LogLevel::Warning, message);
+            }
+            None => {
+                let language = Node::language_name().to_string();
+                return self.log(LogLevel::Warning, Message::CannotQuery(language));
+            }
+        };
+        let num_matches = paths.len();
+        match paths.first() {
+            Some(path) => self.jump_to(path.clone()),
+            None => return self.log(LogLevel::Info, Message::NoMatches(query.to_string())),
+        }
+        if num_matches > 1 {
+            self.quickfix = Quickfix::new(paths);
+            self.log(LogLevel::Info, Message::QueryMatches(num_matches));
+        }
+    }
+
+    /// Move the cursor to the node which the reference under the cursor refers to
+    fn go_to_definition(&mut self) {
+        let cursor_path = self.tree.cursor_path().clone();
+        match self.tree.root().resolve_reference(&cursor_path) {
+            Some(Ok(path)) => self.jump_to(path),
+            Some(Err(error)) => {
+                let message = Message::InvalidReference(self.messages.format(&error));
+                self.log(LogLevel::Warning, message)
+            }
+            None => self.log(LogLevel::Info, Message::NotAReference),
+        }
+    }
+
+    /// Put every reference to the node under the cursor in the quickfix list, and move the
+    /// cursor to the first of them
+    fn find_references(&mut self) {
+        let references = self.tree.root().find_references(self.tree.cursor_path());
+        let first = match references.first() {
+            Some(path) => path.clone(),
+            None => return self.log(LogLevel::Info, Message::NoReferences),
+        };
+        self.log(LogLevel::Info, Message::ReferencesFound(references.len()));
+        self.quickfix = Quickfix::new(references);
+        self.jump_to(first);
+    }
+
+    /// Log statistics about the leaves matched by the latest query (i.e. the quickfix list), or
+    /// under the cursor if no query has matched several nodes
+    fn show_stats(&mut self) {
+        let root = self.tree.root();
+        let mut nodes: Vec<&'arena Node> = self
+            .quickfix
+            .paths()
+            .iter()
+            .filter(|path| path.is_valid(root))
+            .map(|path| path.cursor(root))
+            .collect();
+        if nodes.is_empty() {
+            nodes.push(self.tree.cursor());
+        }
+        let stats = Stats::of(&nodes);
+        if stats.count == 0 {
+            return self.log(LogLevel::Info, Message::NothingToAggregate);
+        }
+        let common: Vec<String> = stats
+            .common
+            .iter()
+            .map(|(value, count)| format!("{} ({})", value, count))
+            .collect();
+        self.log(LogLevel::Info, Message::StatsCommon(common.join(", ")));
+        if stats.numbers > 0 {
+            let sum = stats.sum.to_string();
+            self.log(LogLevel::Info, Message::StatsNumbers(stats.numbers, sum));
+        }
+        if let Some((min, max)) = stats.range {
+            self.log(LogLevel::Info, Message::StatsRange(min, max));
+        }
+        self.log(
+            LogLevel::Info,
+            Message::StatsCount(stats.count, stats.distinct, nodes.len()),
+        );
+    }
+
+    /// Highlight every node with a duplicate sibling (e.g. repeated keys in an object), putting
+    /// them in the quickfix list
+    fn find_duplicates(&mut self) {
+        let root = self.tree.root();
+        let paths = duplicates::duplicate_siblings(root);
+        if paths.is_empty() {
+            return self.log(LogLevel::Info, Message::NoDuplicates);
+        }
+        self.review_nodes = paths.iter().map(|path| path.cursor(root)).collect();
+        self.log(LogLevel::Info, Message::DuplicatesFound(paths.len()));
+        self.quickfix = Quickfix::new(paths);
+    }
+
+    /// Replace the field containing the cursor with the field of the same name in the next (or
+    /// previous) record, so that values repeated between records don't have to be typed again
+    fn copy_from_sibling(&mut self, forward: bool) {
+        let cursor_path = self.tree.cursor_path().clone();
+        let (field_path, source_path) =
+            match table::corresponding_field(self.tree.root(), &cursor_path, forward) {
+                Some(paths) => paths,
+                None => return self.log(LogLevel::Info, Message::NoCorrespondingField),
+            };
+        let source = source_path.cursor(self.tree.root());
+        let key = source.outline_label().unwrap_or_default();
+        let new_node = source.clone();
+        self.jump_to(field_path);
+        self.tree.replace_cursor(new_node);
+        self.notify_edit(events::Operation::Replace);
+        // The copied field may not have the same shape, so the cursor might not fit inside it
+        if cursor_path.is_valid(self.tree.root()) {
+            self.jump_to(cursor_path);
+        }
+        let message = if forward {
+            Message::CopiedFromBelow(key)
+        } else {
+            Message::CopiedFromAbove(key)
+        };
+        self.log(LogLevel::Info, message);
+    }
+
+    /// Move the cursor to the next (or previous) entry of the quickfix list
+    fn step_quickfix(&mut self, forward: bool) {
+        match self.quickfix.step(forward) {
+            Some(path) => {
+                let path = path.clone();
+                self.jump_to(path);
+                let entry = Message::QuickfixEntry(self.quickfix.position(), self.quickfix.len());
+                self.log(LogLevel::Info, entry);
+                if let Some(note) = self.quickfix.note().cloned() {
+                    self.log(LogLevel::Warning, note);
+                }
+            }
+            None => self.log(LogLevel::Info, Message::QuickfixEmpty),
+        }
+    }
+
+    /// Move the cursor to the nearest ancestor of a given kind
+    fn select_enclosing(&mut self, kind: String) {
+        match self.tree.cursor_path().enclosing(self.tree.root(), &kind) {
+            Some(path) => self.jump_to(path),
+            None => self.log(LogLevel::Info, Message::NoEnclosing(kind)),
+        }
+    }
+
+    /// Open the outline sidebar, or give it focus if it's already open
+    fn toggle_outline(&mut self) {
+        match &mut self.outline {
+            Some(outline) => outline.set_focus(true),
+            None => self.outline = Some(Outline::new()),
+        }
+    }
+
+    /// Respond to a key press whilst the outline sidebar has focus
+    fn handle_outline_key(&mut self, key: Key) {
+        let entries = outline::entries(self.tree.root(), OUTLINE_DEPTH);
+        let outline = match &mut self.outline {
+            Some(outline) => outline,
+            None => return,
+        };
+        match key {
+            Key::Enter => {
+                // Jump the main cursor to the selected node, and give focus back to the tree
+                outline.set_focus(false);
+                if let Some(index) = outline.selected(entries.len()) {
+                    self.jump_to(entries[index].path.clone());
+                }
+            }
+            Key::ESC => outline.set_focus(false),
+            Key::Down => outline.select_next(entries.len()),
+            Key::Up => outline.select_prev(entries.len()),
+            key => match self.keymap.get(key) {
+                Some(Command::MoveCursor(Direction::Next)) => outline.select_next(entries.len()),
+                Some(Command::MoveCursor(Direction::Prev)) => outline.select_prev(entries.len()),
+                Some(Command::ToggleOutline) => self.outline = None,
+                _ => {}
+            },
+        }
+    }
+
+    /// Scroll the view so that the first line of the cursor is at a given place on the screen
+    fn scroll(&mut self, anchor: ScrollAnchor) {
+        let cursor_row = self.cursor_row();
+        let view_height = self.view_height();
+        self.scroll_row = match anchor {
+            ScrollAnchor::Top => cursor_row,
+            ScrollAnchor::Center => cursor_row.saturating_sub(view_height / 2),
+            ScrollAnchor::Bottom => cursor_row.saturating_sub(view_height.saturating_sub(1)),
+        };
+    }
+
+    /// Scroll the view by `count` lines or pages, without moving the cursor.  The view can't be
+    /// scrolled past the last line of the tree.
+    fn scroll_view(&mut self, step: ScrollStep, count: usize) {
+        let page = self.view_height().saturating_sub(1).max(1);
+        let last_row = self.tree_layout().height.saturating_sub(1);
+        self.scroll_row = match step {
+            ScrollStep::LineDown => self.scroll_row.saturating_add(count).min(last_row),
+            ScrollStep::LineUp => self.scroll_row.saturating_sub(count),
+            ScrollStep::PageDown => self
+                .scroll_row
+                .saturating_add(page.saturating_mul(count))
+                .min(last_row),
+            ScrollStep::PageUp => self.scroll_row.saturating_sub(page.saturating_mul(count)),
+        };
+    }
+
+    /// If the cursor has moved since the view last followed it, scroll the view (if needed) so
+    /// that the start of the cursor's subtree is on screen
+    fn follow_cursor(&mut self) {
+        let cursor = self.tree.cursor();
+        if self
+            .followed_cursor
+            .is_some_and(|node| std::ptr::eq(node, cursor))
+        {
+            return;
+        }
+        self.followed_cursor = Some(cursor);
+        let layout = self.tree_layout();
+        let (row, col) = match layout.cursor {
+            Some(start) => start,
+            None => return,
+        };
+        let height = self.view_height().max(1);
+        if row < self.scroll_row {
+            self.scroll_row = row;
+        } else if row >= self.scroll_row + height {
+            self.scroll_row = row + 1 - height;
+        }
+        // Scrolling sideways jumps by half a view, so that the text around the cursor is shown
+        let width = self
+            .tree_width()
+            .saturating_sub(self.gutter_width(&layout))
+            .max(1);
+        if col < self.scroll_col || col >= self.scroll_col + width {
+            self.scroll_col = col.saturating_sub(width / 2);
+        }
+    }
+
+    /// Returns the display tokens of the tree as it is rendered, which leave out anything deeper
+    /// than the configured maximum depth below the cursor's path, and the parts of the tree which
+    /// go over its limits
+    fn visible_tokens(&self) -> Vec<(&'arena Node, DisplayToken)> {
+        // Even without a maximum depth or any folds, the tree is elided so that the parts which go
+        // over the limits on its shape can't make it too slow (or too deep) to draw
+        let placeholders = elide::Placeholders {
+            elided: &|n| self.messages.format(&Message::Elided(n)),
+            folded: &|n| self.messages.format(&Message::Folded(n)),
+        };
+        elide::display_tokens(
+            self.tree.root(),
+            &self.format_style,
+            self.tree.cursor_path(),
+            self.config.max_render_depth,
+            &self.config.limits,
+            &self.folds.nodes(),
+            &placeholders,
+        )
+    }
+
+    /// Returns the line of the rendered tree on which the node under the cursor starts
+    fn cursor_row(&self) -> usize {
+        // Nodes are unique within a tree, so the cursor's subtree occupies exactly the tokens
+        // generated by the nodes in that subtree
+        let mut subtree_nodes = HashSet::new();
+        let mut nodes_to_visit = vec![self.tree.cursor()];
+        while let Some(node) = nodes_to_visit.pop() {
+            subtree_nodes.insert(node as *const Node);
+            nodes_to_visit.extend(node.children().iter().copied());
+        }
+        // Count the lines before the first token of the subtree
+        let mut row = 0;
+        for (node, tok) in self.visible_tokens() {
+            if subtree_nodes.contains(&(node as *const Node)) {
+                break;
+            }
+            match tok {
+                DisplayToken::Text(s)
+                | DisplayToken::Categorised(s, _)
+                | DisplayToken::OpenDelim(s)
+                | DisplayToken::CloseDelim(s) => row += size::Size::from(s.as_str()).lines(),
+                DisplayToken::Newline => row += 1,
+                _ => {}
+            }
+        }
+        row
+    }
+
+    /// Returns the column of the screen that the tree starts at, which is after the outline
+    /// sidebar if it's open
+    fn tree_col(&self) -> usize {
+        match self.outline {
+            Some(_) => OUTLINE_WIDTH,
+            None => 0,
+        }
+    }
+
+    /// Returns the column of the screen that the side panes (the log, preview, pins and watches)
+    /// start at, which is the middle of the screen unless they are all hidden
+    fn side_col(&self) -> usize {
+        let (width, _height) = self.term.term_size().unwrap();
+        let has_side_pane = self.log_visible
+            || self.preview.is_some()
+            || !self.pins.is_empty()
+            || !self.watches.is_empty();
+        match has_side_pane {
+            true => width / 2,
+            false => width,
+        }
+    }
+
+    /// Returns the number of columns of the screen that are available for rendering the tree,
+    /// which is the space left of the side panes apart from the outline sidebar
+    fn tree_width(&self) -> usize {
+        self.side_col().saturating_sub(self.tree_col())
+    }
+
+    /// Returns the number of lines of the screen that are available for rendering the tree
+    fn view_height(&self) -> usize {
+        let (_width, height) = self.term.term_size().unwrap();
+        // The bottom line of the screen is taken by the bottom bar
+        height.saturating_sub(1)
+    }
+
+    /// Render the outline sidebar down the left hand side of the screen
+    fn render_outline(&self, outline: &Outline, height: usize) {
+        let entries = outline::entries(self.tree.root(), OUTLINE_DEPTH);
+        let selected = outline.selected(entries.len());
+        for (i, entry) in entries.iter().enumerate().take(height) {
+            // Indent the label by its depth, and truncate it so that it doesn't overlap the border
+            let text: String = "  "
+                .repeat(entry.depth)
+                .chars()
+                .chain(entry.label.chars())
+                .take(OUTLINE_WIDTH - 1)
+                .collect();
+            let attr = if outline.is_focused() && Some(i) == selected {
+                Attr::default().fg(Color::BLACK).bg(Color::WHITE)
+            } else if &entry.path == self.tree.cursor_path() {
+                Attr::default().effect(Effect::BOLD)
+            } else {
+                Attr::default()
+            };
+            self.term.print_with_attr(i, 0, &text, attr).unwrap();
+        }
+        // Draw the border between the sidebar and the tree
+        for row in 0..height {
+            self.term.print(row, OUTLINE_WIDTH - 1, "│").unwrap();
+        }
+    }
+
+    /// Lays out the tree as it is rendered
+    fn tree_layout(&self) -> render::Layout {
+        let tokens = self.visible_tokens();
+        render::layout(
+            &tokens,
+            &render::Highlights {
+                cursor: self.tree.cursor(),
+                cursor_style: &self.config.cursor_style,
+                review_nodes: &self.review_nodes,
+                search_pattern: self.search_pattern.as_ref(),
+                format_style: &self.format_style,
+                theme: &self.config.theme,
+            },
+        )
+    }
+
+    /// Returns the number of columns taken by the gutter down the left hand side of the tree
+    /// view, which is 0 if line numbers aren't shown
+    fn gutter_width(&self, layout: &render::Layout) -> usize {
+        match self.config.line_numbers {
+            true => render::gutter_width(layout.height),
+            false => 0,
+        }
+    }
+
+    /// Render the tree to the screen, into the region starting at `(start_row, start_col)` that is
+    /// `height` lines tall and `width` chars wide.  Only the lines from [`Editor::scroll_row`]
+    /// onwards and the columns from [`Editor::scroll_col`] onwards are rendered, after the gutter
+    /// if line numbers are shown.
+    fn render_tree(&self, start_row: usize, start_col: usize, height: usize, width: usize) {
+        let layout = self.tree_layout();
+        let gutter_width = self.gutter_width(&layout);
+        if gutter_width > 0 {
+            for row in (self.scroll_row..layout.height).take(height) {
+                let (text, attr) = render::gutter(&layout, row, gutter_width);
+                self.term
+                    .print_with_attr(start_row + row - self.scroll_row, start_col, &text, attr)
+                    .unwrap();
+            }
+        }
+        let (start_col, width) = (start_col + gutter_width, width.saturating_sub(gutter_width));
+        // Only paint the parts of lines which are inside the visible region
+        for span in &layout.spans {
+            if span.row < self.scroll_row || span.row - self.scroll_row >= height {
+                continue;
+            }
+            if let Some((col, text)) = span.clip(self.scroll_col, width) {
+                self.term
+                    .print_with_attr(
+                        start_row + span.row - self.scroll_row,
+                        start_col + col,
+                        &text,
+                        span.attr,
+                    )
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Returns the table which is being shown instead of the tree, if the table view is on and the
+    /// cursor is inside a table
+    fn visible_table(&self) -> Option<Table<'arena, Node>> {
+        if !self.table_view {
+            return None;
+        }
+        Table::enclosing(self.tree.root(), self.tree.cursor_path())
+    }
+
+    /// Render a table into the main view, starting at column `start_col` and at most `width` chars
+    /// wide.  The rows are scrolled so that the cursor's row is visible.
+    fn render_table(&self, table: &Table<'arena, Node>, start_col: usize, width: usize) {
+        let widths = table.column_widths();
+        let cursor_cell = table.cell(self.tree.cursor_path());
+        let height = self.view_height();
+        let first_row =
+            cursor_cell.map_or(0, |(row, _)| row.saturating_sub(height.saturating_sub(2)));
+        // Print each line one cell at a time, stopping at the edge of the view
+        let print_line = |screen_row: usize, cells: &mut dyn Iterator<Item = (String, Attr)>| {
+            let mut col = start_col;
+            for ((text, attr), cell_width) in cells.zip(&widths) {
+                if col >= start_col + width {
+                    break;
+                }
+                let text: String = text.chars().take(*cell_width).collect();
+                let text = format!("{:w$}", text, w = *cell_width);
+                self.term
+                    .print_with_attr(screen_row, col, &text, attr)
+                    .unwrap();
+                col += cell_width + 1;
+                self.term.print(screen_row, col - 1, "│").unwrap();
+            }
+        };
+        let header_attr = Attr::default().effect(Effect::BOLD | Effect::UNDERLINE);
+        print_line(
+            0,
+            &mut table.keys.iter().map(|key| (key.clone(), header_attr)),
+        );
+        for (screen_row, row) in (first_row..table.rows.len())
+            .take(height.saturating_sub(1))
+            .enumerate()
+        {
+            print_line(
+                screen_row + 1,
+                &mut (0..table.keys.len()).map(|column| {
+                    let attr = if cursor_cell == Some((row, column)) {
+                        self.config.cursor_style.attr(Color::WHITE)
+                    } else {
+                        Attr::default()
+                    };
+                    (table.cell_text(row, column), attr)
+                }),
+            );
+        }
+    }
+
+    /// Render the history view into the main view, with a title line followed by one line per
+    /// history entry.  The entries are scrolled so that the selected entry is visible.
+    fn render_history(&self, view: &HistoryView, start_col: usize, width: usize) {
+        let title_attr = Attr::default().effect(Effect::BOLD);
+        let title = self.messages.format(&Message::HistoryTitle);
+        self.term
+            .print_with_attr(0, start_col, &title, title_attr)
+            .unwrap();
+        let entries = self.tree.history();
+        let num_rows = self.view_height().saturating_sub(1);
+        let first_entry = (view.selected() + 1).saturating_sub(num_rows);
+        let now = SystemTime::now();
+        for (row, index) in (first_entry..entries.len()).take(num_rows).enumerate() {
+            // Entries without a recorded summary are summarised by what changed in them
+            let summary = match (index.checked_sub(1), &entries[index].summary) {
+                (None, _) => self.messages.format(&Message::HistoryOriginal),
+                (Some(_), Some(summary)) => summary.clone(),
+                (Some(previous), None) => {
+                    history::change_summary(entries[previous].root, entries[index].root)
+                        .unwrap_or_else(|| self.messages.format(&Message::HistoryUnchanged))
+                }
+            };
+            let age =
+                history::format_age(now.duration_since(entries[index].time).unwrap_or_default());
+            let current_marker = if index == self.tree.history_index() {
+                '*'
+            } else {
+                ' '
+            };
+            let saved_marker = if Some(index) == self.tree.saved_index() {
+                'S'
+            } else {
+                ' '
+            };
+            let line = format!(
+                "{}{} {}",
+                current_marker,
+                saved_marker,
+                self.messages
+                    .format(&Message::HistoryEntry(index, age, summary))
+            );
+            let line: String = line.chars().take(width).collect();
+            let attr = if index == view.selected() {
+                Attr::default().fg(Color::BLACK).bg(Color::WHITE)
+            } else {
+                Attr::default()
+            };
+            self.term
+                .print_with_attr(row + 1, start_col, &line, attr)
+                .unwrap();
+        }
+    }
+
+    /// Render the completion menu over the tree, just below the first line of the cursor
+    fn render_completion(&self, menu: &CompletionMenu, start_col: usize, width: usize) {
+        let height = self.view_height();
+        let num_rows = menu.completions().len().min(height);
+        let first_row =
+            (self.cursor_row().saturating_sub(self.scroll_row) + 1).min(height - num_rows);
+        let labels: Vec<String> = menu
+            .completions()
+            .iter()
+            .map(|completion| completion.label().chars().take(width).collect())
+            .collect();
+        let menu_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        // Scroll the menu so that the selected entry is visible
+        let first_entry = (menu.selected_index() + 1).saturating_sub(num_rows);
+        for (i, label) in labels.iter().enumerate().skip(first_entry).take(num_rows) {
+            let attr = if i == menu.selected_index() {
+                Attr::default().fg(Color::BLACK).bg(Color::WHITE)
+            } else {
+                Attr::default().fg(Color::WHITE).bg(Color::LIGHT_BLACK)
+            };
+            let text = format!("{:w$}", label, w = menu_width);
+            self.term
+                .print_with_attr(first_row + i - first_entry, start_col, &text, attr)
+                .unwrap();
+        }
+    }
+
+    /// Render the command palette over the top of the tree: the filter, and then the entries
+    /// which match it
+    fn render_palette(&self, palette: &Palette<PaletteAction>, start_col: usize, width: usize) {
+        let num_rows = self.view_height().saturating_sub(1);
+        let matches = palette.matches();
+        let labels: Vec<String> = matches
+            .iter()
+            .map(|entry| entry.label().chars().take(width).collect())
+            .collect();
+        let menu_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let filter: String = format!("> {}", palette.filter())
+            .chars()
+            .take(width)
+            .collect();
+        let menu_width = menu_width.max(filter.chars().count());
+        let filter_attr = Attr::default().fg(Color::WHITE).bg(Color::BLACK);
+        let text = format!("{:w$}", filter, w = menu_width);
+        self.term
+            .print_with_attr(0, start_col, &text, filter_attr)
+            .unwrap();
+        // Scroll the entries so that the selected one is visible
+        let first_entry = (palette.selected_index() + 1).saturating_sub(num_rows);
+        for (i, label) in labels.iter().enumerate().skip(first_entry).take(num_rows) {
+            let attr = if i == palette.selected_index() {
+                Attr::default().fg(Color::BLACK).bg(Color::WHITE)
+            } else {
+                Attr::default().fg(Color::WHITE).bg(Color::LIGHT_BLACK)
+            };
+            let text = format!("{:w$}", label, w = menu_width);
+            self.term
+                .print_with_attr(1 + i - first_entry, start_col, &text, attr)
+                .unwrap();
+        }
+    }
+
+    /// Returns the hints for the command being typed: each key which could be typed next, along
+    /// with a description of what it would do.  There are only hints whilst the command is
+    /// incomplete, either because it's the start of a longer binding or because it's waiting for
+    /// the [`char`] of a node to replace the cursor with or insert.
+    fn command_hints(&self) -> Vec<(String, String)> {
+        let (_, command) = split_count(&self.keymap, &self.command);
+        if command.is_empty() {
+            return Vec::new();
+        }
+        let cursor = self.tree.cursor();
+        let char_hints = |chars: Box<dyn Iterator<Item = char>>| -> Vec<(String, String)> {
+            chars
+                .map(|c| {
+                    let kind = cursor.from_char(c).map_or("", |node| node.kind_name());
+                    (c.to_string(), kind.to_string())
+                })
+                .collect()
+        };
+        match self.keymap.lookup(command) {
+            Lookup::Incomplete => self
+                .keymap
+                .continuations(command)
+                .into_iter()
+                .map(|(key, continuation)| {
+                    let description = match continuation {
+                        keymap::Continuation::Command(command) => format!("{:?}", command),
+                        keymap::Continuation::Prefix(n) => {
+                            self.messages.format(&Message::HintPrefix(n))
+                        }
+                    };
+                    (key.to_string(), description)
+                })
+                .collect(),
+            Lookup::Bound(Command::Replace, len) if len == command.len() => {
+                char_hints(cursor.replace_chars())
+            }
+            Lookup::Bound(Command::InsertChild, len) if len == command.len() => {
+                char_hints(cursor.insert_chars())
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Render the hints for the command being typed (see [`Editor::command_hints`]) in columns
+    /// at the bottom of the tree view, which starts at `start_col` and is `width` chars wide
+    fn render_hints(&self, start_col: usize, width: usize) {
+        let hints = self.command_hints();
+        if hints.is_empty() {
+            return;
+        }
+        let entries: Vec<String> = hints
+            .iter()
+            .map(|(key, description)| format!(" {}  {} ", key, description))
+            .collect();
+        let entry_width = entries.iter().map(|e| e.chars().count()).max().unwrap_or(0);
+        let num_rows = (self.view_height() / 2).max(1).min(entries.len());
+        let num_columns = (width / entry_width.max(1)).max(1);
+        let first_row = self.view_height() - num_rows;
+        let attr = Attr::default().fg(Color::WHITE).bg(Color::LIGHT_BLACK);
+        for row in 0..num_rows {
+            let line: String = entries
+                .iter()
+                .skip(row)
+                .step_by(num_rows)
+                .take(num_columns)
+                .map(|entry| format!("{:w$}", entry, w = entry_width))
+                .collect();
+            let line = format!("{:w$}", line, w = width);
+            let line: String = line.chars().take(width).collect();
+            self.term
+                .print_with_attr(first_row + row, start_col, &line, attr)
+                .unwrap();
+        }
+    }
+
+    /// Render the watches pane into a region of the screen, starting with a title line
+    fn render_watches(&self, start_row: usize, start_col: usize, height: usize) {
+        if height == 0 {
+            return;
+        }
+        self.term
+            .print_with_attr(
+                start_row,
+                start_col,
+                &self.messages.format(&Message::WatchesTitle),
+                Attr::default().effect(Effect::BOLD | Effect::UNDERLINE),
+            )
+            .unwrap();
+        for (i, watch) in self.watches.iter().take(height - 1).enumerate() {
+            let (value, color) = match &watch.value {
+                Some(Ok(value)) => (value.as_str(), Color::GREEN),
+                Some(Err(error)) => (error.as_str(), Color::RED),
+                None => ("", Color::default()),
+            };
+            let label = format!("{}. {} = ", i + 1, watch.expression);
+            let row = start_row + 1 + i;
+            self.term.print(row, start_col, &label).unwrap();
+            self.term
+                .print_with_attr(
+                    row,
+                    start_col + label.chars().count(),
+                    value,
+                    Attr::default().fg(color),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Render the pins pane into a region of the screen, starting with a title line
+    fn render_pins(&self, start_row: usize, start_col: usize, height: usize) {
+        if height == 0 {
+            return;
+        }
+        self.term
+            .print_with_attr(
+                start_row,
+                start_col,
+                &self.messages.format(&Message::PinsTitle),
+                Attr::default().effect(Effect::BOLD | Effect::UNDERLINE),
+            )
+            .unwrap();
+        for (i, pin) in self.pins.iter().take(height - 1).enumerate() {
+            let label = format!("{}. {}: ", i + 1, pin.path);
+            let (text, color) = match &pin.text {
+                Some(text) => (text.clone(), Color::default()),
+                None => (self.messages.format(&Message::PinMissing), Color::RED),
+            };
+            let row = start_row + 1 + i;
+            self.term.print(row, start_col, &label).unwrap();
+            self.term
+                .print_with_attr(
+                    row,
+                    start_col + label.chars().count(),
+                    &text,
+                    Attr::default().fg(color),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Render the preview pane into a region of the screen, starting with a title line
+    fn render_preview(&self, preview: &Preview, start_row: usize, start_col: usize, height: usize) {
+        if height == 0 {
+            return;
+        }
+        self.term
+            .print_with_attr(
+                start_row,
+                start_col,
+                &self.messages.format(&Message::PreviewTitle),
+                Attr::default().effect(Effect::BOLD | Effect::UNDERLINE),
+            )
+            .unwrap();
+        let attr = if preview.is_error() {
+            Attr::default().fg(Color::RED)
+        } else {
+            Attr::default()
+        };
+        for (i, line) in preview.lines().iter().take(height - 1).enumerate() {
+            self.term
+                .print_with_attr(start_row + 1 + i, start_col, line, attr)
+                .unwrap();
+        }
+    }
+
+    /* ===== MAIN FUNCTIONS ===== */
+
+    /// Update the terminal UI display
+    fn update_display(&mut self) {
+        self.follow_cursor();
+        // Put the terminal size into some convenient variables
+        let (width, height) = self.term.term_size().unwrap();
+
+        // Clear the terminal
+        self.term.clear().unwrap();
+
+        /* RENDER OUTLINE SIDEBAR */
+        let tree_col = match &self.outline {
+            Some(outline) => {
+                self.render_outline(outline, height - 1);
+                OUTLINE_WIDTH
+            }
+            None => 0,
+        };
+
+        /* RENDER MAIN TEXT VIEW */
+        let side_col = self.side_col();
+        match (&self.history_view, self.visible_table()) {
+            (Some(view), _) => self.render_history(view, tree_col, side_col - tree_col),
+            (None, Some(table)) => self.render_table(&table, tree_col, side_col - tree_col),
+            (None, None) => self.render_tree(0, tree_col, self.view_height(), self.tree_width()),
+        }
+
+        /* RENDER COMPLETION MENU */
+        if let Some(menu) = &self.completion {
+            self.render_completion(menu, tree_col, side_col - tree_col);
+        }
+
+        /* RENDER COMMAND PALETTE */
+        if let Some(palette) = &self.palette {
+            self.render_palette(palette, tree_col, side_col - tree_col);
+        }
+
+        /* RENDER COMMAND HINTS */
+        self.render_hints(tree_col, side_col - tree_col);
+
+        /* RENDER LOG SECTION */
+        // The watches pane sits at the bottom of the log's space with the pins pane above it, and
+        // then if there's a preview pane, it takes up the bottom half of what's left
+        let watches_row = if self.watches.is_empty() {
+            height - 1
+        } else {
+            height - 1 - (self.watches.len() + 1).min((height - 1) / 2)
+        };
+        let pins_row = if self.pins.is_empty() {
+            watches_row
+        } else {
+            watches_row - (self.pins.len() + 1).min(watches_row / 2)
+        };
+        let preview_row = match &self.preview {
+            Some(_) => pins_row / 2,
+            None => pins_row,
+        };
+        if self.log_visible {
+            self.render_log(width / 2, preview_row);
+        }
+
+        /* RENDER PREVIEW PANE */
+        if let Some(preview) = &self.preview {
+            self.render_preview(preview, preview_row, width / 2, pins_row - preview_row);
+        }
+
+        /* RENDER PINS PANE */
+        self.render_pins(pins_row, width / 2, watches_row - pins_row);
+
+        /* RENDER WATCHES PANE */
+        self.render_watches(watches_row, width / 2, height - 1 - watches_row);
+
+        /* RENDER NOTIFICATION */
+        if let Some(notification) = &self.notification {
+            self.render_notification(notification, height - 2, side_col);
+        }
+
+        /* RENDER BOTTOM BAR */
+        // The file's format goes in the bottom right corner, with the macro being recorded just
+        // before it
+        let file_format = self.file_format.to_string();
+        let format_width = file_format.chars().count();
+        self.term
+            .print_with_attr(
+                height - 1,
+                width.saturating_sub(format_width + 1),
+                &file_format,
+                Attr::default().fg(Color::LIGHT_BLACK),
+            )
+            .unwrap();
+        let mut status_width = width.saturating_sub(format_width + 2);
+        if let Some(recording) = &self.recording {
+            let indicator = self
+                .messages
+                .format(&Message::RecordingIndicator(recording.register));
+            status_width = status_width.saturating_sub(indicator.chars().count() + 1);
+            self.term
+                .print_with_attr(
+                    height - 1,
+                    status_width,
+                    &indicator,
+                    Attr::default().fg(Color::LIGHT_RED),
+                )
+                .unwrap();
+        }
+        match &self.prompt {
+            Some((prompt, _)) => {
+                self.term
+                    .print_with_attr(
+                        height - 1,
+                        0,
+                        &prompt.text(),
+                        Attr::default().fg(Color::BLACK).bg(Color::YELLOW),
+                    )
+                    .unwrap();
+            }
+            // Line commands are typed into the bottom bar, with the terminal's cursor showing
+            // where the next key goes
+            None if self.mode == Mode::Command => {
+                let cursor = self.command_line.cursor().min(self.command.len());
+                let before_cursor = keymap::sequence_text(&self.command[..cursor]);
+                self.term
+                    .print(height - 1, 0, &keymap::sequence_text(&self.command))
+                    .unwrap();
+                self.term
+                    .set_cursor(height - 1, before_cursor.chars().count())
+                    .unwrap();
+            }
+            None => {
+                // Progress covers the right half of the status bar whilst there is any
+                if self.progress.status_text().is_some() {
+                    status_width = status_width.min((width / 2).saturating_sub(1));
+                }
+                self.term
+                    .print(height - 1, 0, &self.status_text(status_width))
+                    .unwrap();
+            }
+        }
+        self.term
+            .show_cursor(self.prompt.is_none() && self.mode == Mode::Command)
+            .unwrap();
+        if let Some(progress_text) = self.progress.status_text() {
+            self.term
+                .print_with_attr(
+                    height - 1,
+                    width / 2,
+                    &progress_text,
+                    Attr::default().fg(Color::CYAN),
+                )
+                .unwrap();
+        }
+
+        // Update the terminal screen
+        self.term.present().unwrap();
+    }
+
+    /// Render the log pane, which is `height` lines tall and starts at `col` on the top line.  It
+    /// shows the newest entries at or above the configured level, unless it has been scrolled
+    /// back, in which case the last line says how many newer entries there are.
+    fn render_log(&self, col: usize, height: usize) {
+        let entries = self.shown_log_entries();
+        let last_entry = entries.len().saturating_sub(self.log_scroll);
+        let num_rows = match self.log_scroll {
+            0 => height,
+            _ => height.saturating_sub(1),
+        };
+        let first_entry = last_entry.saturating_sub(num_rows);
+        for (i, (level, message)) in entries[first_entry..last_entry].iter().enumerate() {
+            self.term
+                .print_with_attr(i, col, message, Attr::default().fg(level.to_color()))
+                .unwrap();
+        }
+        if self.log_scroll > 0 && height > 0 {
+            let text = self
+                .messages
+                .format(&Message::NewerLogEntries(self.log_scroll));
+            self.term
+                .print_with_attr(
+                    height - 1,
+                    col,
+                    &text,
+                    Attr::default().fg(Color::LIGHT_BLACK),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Returns the text of the status bar, at most `width` chars wide, made of the configured
+    /// [segments](status_bar::Segment)
+    fn status_text(&self, width: usize) -> String {
+        let segments = self
+            .config
+            .status_segments
+            .as_deref()
+            .unwrap_or(status_bar::DEFAULT_SEGMENTS);
+        let texts: Vec<(Segment, String)> = segments
+            .iter()
+            .map(|segment| (*segment, self.segment_text(*segment)))
+            .collect();
+        status_bar::compose(&texts, width)
+    }
+
+    /// Returns the text of one segment of the status bar, which is empty if the segment has
+    /// nothing to show
+    fn segment_text(&self, segment: Segment) -> String {
+        match segment {
+            Segment::Mode => match self.mode {
+                Mode::Insert => self.messages.format(&Message::TypingIndicator),
+                _ => self.messages.format(&Message::NormalIndicator),
+            },
+            Segment::FileName => match &self.path {
+                Some(path) => path.clone(),
+                None => self.messages.format(&Message::UnnamedFile),
+            },
+            Segment::Modified if self.tree.has_unsaved_changes() => {
+                self.messages.format(&Message::ModifiedIndicator)
+            }
+            Segment::Modified => String::new(),
+            Segment::Path => {
+                let path = self.tree.cursor_path();
+                let indices = std::iter::once(None).chain(path.iter().map(|index| Some(*index)));
+                let nodes: Vec<(&str, Option<usize>)> = path
+                    .node_iter(self.tree.root())
+                    .map(|node| node.kind_name())
+                    .zip(indices)
+                    .collect();
+                status_bar::path_text(&nodes)
+            }
+            Segment::Position => match self.cursor_parent() {
+                Some((parent, index)) => self.messages.format(&Message::PositionIndicator(
+                    index + 1,
+                    parent.children().len(),
+                    self.tree.cursor_path().depth(),
+                )),
+                None => self.messages.format(&Message::RootPositionIndicator),
+            },
+            Segment::UndoDepth => {
+                let depth = self.tree.history_index();
+                self.messages.format(&Message::UndoDepthIndicator(depth))
+            }
+            Segment::Command if self.mode == Mode::Insert => String::new(),
+            Segment::Command => match self.command_time_left(Instant::now()) {
+                Some(left) => {
+                    let seconds = format!("{:.1}", left.as_secs_f32());
+                    let command = keymap::sequence_text(&self.command);
+                    self.messages
+                        .format(&Message::CommandCountdown(command, seconds))
+                }
+                None => keymap::sequence_text(&self.command),
+            },
+        }
+    }
+
+    /// Render a notification on the given row, at most `width` chars wide, if it hasn't faded away
+    fn render_notification(&self, notification: &Notification, row: usize, width: usize) {
+        let attr = Attr::default()
+            .fg(Color::BLACK)
+            .bg(notification.level.to_color());
+        let attr = match notification.visibility(Instant::now()) {
+            Visibility::Shown => attr,
+            Visibility::Fading => attr.effect(Effect::DIM),
+            Visibility::Hidden => return,
+        };
+        let text: String = format!(" {} ", notification.text)
+            .chars()
+            .take(width)
+            .collect();
+        self.term.print_with_attr(row, 0, &text, attr).unwrap();
+    }
+
+    /// Returns [`Some`] [`Prompt`] if an [`Action`] is destructive enough that the user should
+    /// confirm it before it happens, or [`None`] if it can go ahead straight away.
+    fn confirmation_prompt(&self, action: &Action) -> Option<Prompt> {
+        // Repeated actions are only confirmed once
+        if let Action::Repeat(_, action) = action {
+            return self.confirmation_prompt(action);
+        }
+        let redo_depth = self.tree.redo_depth();
+        let operation = Operation::DiscardRedo;
+        if action.is_edit() && redo_depth > 0 && self.config.confirm.is_enabled(operation) {
+            return Some(Prompt::new(
+                operation,
+                self.messages
+                    .format(&Message::ConfirmDiscardRedo(redo_depth)),
+            ));
+        }
+        match action {
+            // Quitting a nested session keeps its changes in the tree it was opened from
+            Action::Quit if self.hosts.is_empty() && self.tree.has_unsaved_changes() => {
+                let operation = Operation::DiscardChanges;
+                self.config
+                    .confirm
+                    .is_enabled(operation)
+                    .then(|| Prompt::new(operation, self.messages.format(&Message::ConfirmQuit)))
+            }
+            // A new document replaces every nested session as well as the file's own tree
+            Action::NewDocument
+                if self.tree.has_unsaved_changes()
+                    || self
+                        .hosts
+                        .first()
+                        .is_some_and(|(host, ..)| host.has_unsaved_changes()) =>
+            {
+                let operation = Operation::DiscardChanges;
+                self.config.confirm.is_enabled(operation).then(|| {
+                    Prompt::new(
+                        operation,
+                        self.messages.format(&Message::ConfirmNewDocument),
+                    )
+                })
+            }
+            Action::Replace(c) if self.tree.cursor().is_replace_char(*c) => {
+                let num_nodes = self.tree.cursor().subtree_size();
+                let operation = Operation::ReplaceLargeSubtree;
+                if self.config.confirm.is_enabled(operation)
+                    && num_nodes >= self.config.confirm.large_subtree_size
+                {
+                    Some(Prompt::new(
+                        operation,
+                        self.messages.format(&Message::ConfirmReplace(num_nodes)),
+                    ))
+                } else {
+                    None
+                }
+            }
+            Action::Paste(..) | Action::ReadFile(_) => {
+                let operation = Operation::PasteLargeSubtree;
+                if !self.config.confirm.is_enabled(operation) {
+                    return None;
+                }
+                let (fragment, text) = match action {
+                    Action::ReadFile(path) => Self::file_fragment(path),
+                    Action::Paste(_, register) => self.register_fragment(*register),
+                    _ => self.register_fragment(None),
+                }
+                .ok()?;
+                let num_nodes = fragment.node.size();
+                if num_nodes < self.config.confirm.large_paste_size {
+                    return None;
+                }
+                let first_line = text.lines().map(str::trim).find(|line| !line.is_empty());
+                let mut preview: String = first_line.unwrap_or("").chars().take(30).collect();
+                if first_line.is_some_and(|line| line.chars().count() > 30) {
+                    preview.push_str("...");
+                }
+                let message = Message::ConfirmPaste(num_nodes, fragment.node.depth(), preview);
+                Some(Prompt::new(operation, self.messages.format(&message)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Perform the effect of a parsed [`Action`], first asking the user for confirmation if the
+    /// action is destructive.  Returns `true` if the action means that Sapling should quit.
+    fn execute_action(&mut self, action: Action) -> bool {
+        // Repeated replacements behave exactly like the original replacement (including asking
+        // for confirmation)
+        let action = match action {
+            Action::RepeatReplace => match self.last_replace_char {
+                Some(c) => Action::Replace(c),
+                None => {
+                    self.log(LogLevel::Info, Message::NothingToRepeat);
+                    return false;
+                }
+            },
+            action => action,
+        };
+        // Edits made whilst a file is opening would be thrown away when more of the file arrives
+        if let Some(loader) = self.loader.as_ref().filter(|_| action.is_edit()) {
+            let path = loader.path().to_string();
+            self.log(LogLevel::Warning, Message::CannotEditWhileOpening(path));
+            return false;
+        }
+        if let Some(prompt) = self.confirmation_prompt(&action) {
+            // Wait for the user to answer before doing anything
+            self.prompt = Some((prompt, action));
+            return false;
+        }
+        self.record_in_journal(&action);
+        self.perform_action(action)
+    }
+
+    /// Append an action which is about to be performed to the session's journal
+    fn record_in_journal(&mut self, action: &Action) {
+        // Undefined commands don't do anything, so there is nothing to reproduce
+        if *action == Action::Undefined {
+            return;
+        }
+        if let Err(e) = self
+            .journal
+            .record(Instant::now(), &format!("{:?}", action))
+        {
+            self.log(LogLevel::Warning, Message::CannotWriteJournal(e));
+        }
+    }
+
+    /// Log the latest entries of the session's journal
+    fn show_journal(&mut self) {
+        let path = match self.journal.path() {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => return self.log(LogLevel::Info, Message::NoJournal),
+        };
+        match self.journal.last_entries(journal::SHOWN_ENTRIES) {
+            Ok(entries) => {
+                self.log(LogLevel::Info, Message::JournalHeading(path));
+                for entry in entries {
+                    let (time, action) = entry.split_once('\t').unwrap_or(("", &entry));
+                    let message = Message::JournalEntry(time.to_string(), action.to_string());
+                    self.log(LogLevel::Info, message);
+                }
+            }
+            Err(e) => self.log(LogLevel::Warning, Message::CannotReadJournal(e)),
+        }
+    }
+
+    /// Respond to a key press whilst the user is being asked a question.  Returns `true` if the
+    /// confirmed action means that Sapling should quit.
+    fn answer_prompt(&mut self, key: Key) -> bool {
+        let answer = match Answer::from_key(key) {
+            Some(answer) => answer,
+            // Any other key leaves the question open
+            None => return false,
+        };
+        let (prompt, action) = match self.prompt.take() {
+            Some(p) => p,
+            None => return false,
+        };
+        if answer == Answer::Snapshot && !prompt.operation.can_snapshot() {
+            self.prompt = Some((prompt, action));
+            return false;
+        }
+        if answer != Answer::No {
+            self.record_in_journal(&action);
+        }
+        match answer {
+            Answer::Yes => self.perform_action(action),
+            Answer::All => {
+                self.config.confirm.disable(prompt.operation);
+                self.perform_action(action)
+            }
+            Answer::Snapshot => {
+                self.snapshot_redo();
+                self.perform_action(action)
+            }
+            Answer::No => {
+                self.log(LogLevel::Info, Message::Cancelled);
+                false
+            }
+        }
+    }
+
+    /// Copy the tree as it would be after redoing every change to the clipboard, so that the
+    /// changes aren't lost when they are thrown away
+    fn snapshot_redo(&mut self) {
+        let latest_root = self.tree.latest_root();
+        let result = self
+            .clipboard
+            .copy(ClipboardContents::copy(latest_root, &self.format_style));
+        if let Err(e) = result {
+            self.log(
+                LogLevel::Warning,
+                Message::ClipboardNotShared(e.to_string()),
+            );
+        }
+        let redo_depth = self.tree.redo_depth();
+        self.log(LogLevel::Info, Message::RedoSnapshotted(redo_depth));
+    }
+
+    /// Perform the effect of a parsed [`Action`] without asking for confirmation.  Returns `true`
+    /// if the action means that Sapling should quit.
+    fn perform_action(&mut self, action: Action) -> bool {
+        match action {
+            // Undefined commands are reported by `execute_command`, which knows what was typed
+            Action::Undefined => {}
+            Action::Quit | Action::ForceQuit => {
+                // Quitting a nested session only returns to the tree it was opened from
+                return !self.close_embedded();
+            }
+            Action::WriteQuit(path) => {
+                // Writing closes any nested sessions, and quitting a nested session only returns
+                // to the tree it was opened from
+                let is_nested = !self.hosts.is_empty();
+                if self.write(path) {
+                    return !is_nested;
+                }
+            }
+            Action::Repeat(count, action) => {
+                for _ in 0..count {
+                    let num_warnings = self.num_warnings;
+                    if self.perform_action((*action).clone()) {
+                        return true;
+                    }
+                    // The remaining steps would fail in the same way, so they aren't attempted
+                    if self.num_warnings > num_warnings {
+                        break;
+                    }
+                }
+            }
+            Action::MoveCursor(direction, count) => {
+                self.move_cursor(direction, count);
+            }
+            Action::Replace(c) => {
+                self.replace_cursor(c);
+            }
+            Action::InsertChild(c) => {
+                self.insert_child(c);
+            }
+            Action::InsertAndEdit(c) => {
+                self.insert_and_edit(c);
+            }
+            Action::EditText => {
+                self.edit_text();
+            }
+            Action::RunLanguageCommand(name) => {
+                self.run_language_command(&name);
+            }
+            Action::OpenPalette => {
+                self.open_palette();
+            }
+            Action::OpenTransformMenu => {
+                self.open_transform_menu();
+            }
+            Action::Wrap(c) => {
+                let wrap = |node| restructure::wrap(node, c).ok();
+                if self.batch_edit(wrap, events::Operation::Wrap) {
+                    return false;
+                }
+                let path = self.tree.cursor_path().clone();
+                let new_node = restructure::wrap(self.tree.cursor(), c);
+                self.replace_at(path, new_node, events::Operation::Wrap);
+            }
+            Action::Unwrap => {
+                self.restructure(
+                    |_, parent, index| restructure::unwrap(parent, index),
+                    events::Operation::Unwrap,
+                );
+            }
+            Action::Raise => {
+                self.raise();
+            }
+            Action::Convert(c) => {
+                let convert = |node| restructure::convert(node, c).ok();
+                if self.batch_edit(convert, events::Operation::Convert) {
+                    return false;
+                }
+                let path = self.tree.cursor_path().clone();
+                let new_node = restructure::convert(self.tree.cursor(), c);
+                self.replace_at(path, new_node, events::Operation::Convert);
+            }
+            Action::Undo => {
+                self.undo();
+            }
+            Action::Redo => {
+                self.redo();
+            }
+            Action::ToggleOutline => {
+                self.toggle_outline();
+            }
+            Action::ScrollView(step, count) => {
+                self.scroll_view(step, count);
+            }
+            Action::ToggleLog => {
+                self.log_visible = !self.log_visible;
+                // Hiding the log can widen the tree view
+                self.followed_cursor = None;
+            }
+            Action::ScrollLog(step, count) => {
+                self.scroll_log(step, count);
+            }
+            Action::SetMaxFps(max_fps) => {
+                self.config.max_fps = Some(max_fps);
+                self.frames = FrameLimiter::new(max_fps);
+                self.log(LogLevel::Info, Message::MaxFpsSet(max_fps as usize));
+            }
+            Action::SetLanding(landing) => {
+                self.config.landing = landing;
+                let name = landing.name().to_string();
+                self.log(LogLevel::Info, Message::LandingSet(name));
+            }
+            Action::SetLogLevel(level) => {
+                self.log_scroll = 0;
+                let name = level.name().to_string();
+                self.config.log_level = Some(level);
+                self.log(LogLevel::Info, Message::LogLevelSet(name));
+            }
+            Action::ToggleFold => {
+                self.toggle_fold();
+            }
+            Action::OpenAllFolds => {
+                let count = self.folds.clear();
+                self.log(LogLevel::Info, Message::FoldsOpened(count));
+            }
+            Action::FoldOversized => {
+                self.fold_oversized();
+            }
+            Action::SetDepthLimit(depth) => {
+                self.config.limits.max_depth = depth;
+                self.log(LogLevel::Info, Message::DepthLimitSet(depth));
+            }
+            Action::SetChildLimit(count) => {
+                self.config.limits.max_children = count;
+                self.log(LogLevel::Info, Message::ChildLimitSet(count));
+            }
+            Action::Scroll(anchor) => {
+                self.scroll(anchor);
+            }
+            Action::Search(query) => {
+                self.search(query);
+            }
+            Action::ClearSearch => {
+                self.search_pattern = None;
+                self.review_nodes.clear();
+            }
+            Action::StepSearch(forward) => {
+                self.step_search(forward);
+            }
+            Action::MotionBack => {
+                self.retrace_motion(false);
+            }
+            Action::MotionForward => {
+                self.retrace_motion(true);
+            }
+            Action::OlderEdit => {
+                self.retrace_edit(false);
+            }
+            Action::NewerEdit => {
+                self.retrace_edit(true);
+            }
+            Action::Yank(register) => {
+                self.yank(register);
+            }
+            Action::Cut(register) => {
+                self.cut(register);
+            }
+            Action::Paste(place, register) => {
+                self.paste(place, register);
+            }
+            Action::ReadFile(path) => {
+                self.read_file(&path);
+            }
+            Action::PlayMacro(register) => {
+                return self.play_macro(register);
+            }
+            Action::EditMacro(register) => {
+                self.edit_macro(register);
+            }
+            Action::RecordMacro(register) => {
+                self.record_macro(register);
+            }
+            Action::StopRecording => {
+                self.stop_recording();
+            }
+            Action::SetMacro(register, text) => {
+                self.set_macro(register, &text);
+            }
+            Action::Evaluate(expression) => {
+                self.evaluate(&expression);
+            }
+            Action::ToggleRepl => {
+                self.toggle_repl();
+            }
+            Action::Slurp => {
+                self.restructure(restructure::slurp, events::Operation::Slurp);
+            }
+            Action::Barf => {
+                self.restructure(restructure::barf, events::Operation::Barf);
+            }
+            Action::Join => {
+                self.restructure(restructure::join, events::Operation::Join);
+            }
+            Action::Transpose(forward) => {
+                self.restructure(
+                    |_, parent, index| restructure::transpose(parent, index, forward),
+                    events::Operation::Transpose,
+                );
+            }
+            Action::SelectEnclosing(kind) => {
+                self.select_enclosing(kind);
+            }
+            Action::EditEmbedded => {
+                self.edit_embedded();
+            }
+            Action::ToggleTable => {
+                self.table_view = !self.table_view;
+            }
+            Action::BatchEdit => {
+                self.arm_batch_edit();
+            }
+            Action::UndoSteps(count) => match self.tree.history_index() {
+                0 => self.log(LogLevel::Info, Message::NothingToUndo),
+                index => self.move_through_history(index.saturating_sub(count)),
+            },
+            Action::RedoSteps(count) => match self.tree.redo_depth() {
+                0 => self.log(LogLevel::Info, Message::NothingToRedo),
+                depth => self.move_through_history(self.tree.history_index() + count.min(depth)),
+            },
+            Action::UndoToSaved => {
+                self.undo_to_saved();
+            }
+            Action::ShowHistory => {
+                self.history_view = Some(HistoryView::new(self.tree.history_index()));
+            }
+            Action::CheckHistory => {
+                self.check_history();
+            }
+            Action::Query(query) => {
+                self.query(&query);
+            }
+            Action::LoadSchema(path) => {
+                self.load_schema(&path);
+            }
+            Action::ExportKeymap(path) => {
+                self.export_keymap(&path);
+            }
+            Action::ImportKeymap(path) => {
+                self.import_keymap(&path);
+            }
+            Action::Open(path) => {
+                self.open(&path);
+            }
+            Action::NewDocument => {
+                self.new_document();
+            }
+            Action::Write(path) => {
+                self.write(path);
+            }
+            Action::StepQuickfix(forward) => {
+                self.step_quickfix(forward);
+            }
+            Action::Sort(key) => {
+                self.sort_children(key);
+            }
+            Action::Group(key) => {
+                self.group_children(key);
+            }
+            Action::FindDuplicates => {
+                self.find_duplicates();
+            }
+            Action::ShowStats => {
+                self.show_stats();
+            }
+            Action::ShowJournal => {
+                self.show_journal();
+            }
+            Action::Rename(new_name) => {
+                self.rename_symbol(new_name);
+            }
+            Action::GoToDefinition => {
+                self.go_to_definition();
+            }
+            Action::FindReferences => {
+                self.find_references();
+            }
+            Action::CopyFromSibling(forward) => {
+                self.copy_from_sibling(forward);
+            }
+            Action::SetEncoding(encoding) => {
+                self.set_encoding(encoding);
+            }
+            Action::SetMaxDepth(max_depth) => {
+                self.config.max_render_depth = max_depth;
+                let message = match max_depth {
+                    Some(depth) => Message::MaxDepthSet(depth),
+                    None => Message::MaxDepthOff,
+                };
+                self.log(LogLevel::Info, message);
+            }
+            Action::SetStatusSegments(segments) => {
+                let names = status_bar::segment_names(&segments);
+                self.config.status_segments = Some(segments);
+                self.log(LogLevel::Info, Message::StatusSegmentsSet(names));
+            }
+            Action::SetLineNumbers(line_numbers) => {
+                self.config.line_numbers = line_numbers;
+                // The gutter narrows the view, so the cursor may have to be followed again
+                self.followed_cursor = None;
+                let message = match line_numbers {
+                    true => Message::LineNumbersOn,
+                    false => Message::LineNumbersOff,
+                };
+                self.log(LogLevel::Info, message);
+            }
+            Action::SetLineWidth(max_width) => {
+                self.set_line_width(max_width);
+            }
+            Action::Map(keys, command) => {
+                let message =
+                    Message::KeyMapped(keymap::sequence_text(&keys), format!("{:?}", command));
+                self.keymap.insert(&keys, command);
+                self.log(LogLevel::Info, message);
+            }
+            Action::SetCommandTimeout(timeout) => {
+                self.command_timeout = timeout;
+                let message = match timeout {
+                    Some(timeout) => Message::CommandTimeoutSet(timeout.as_millis() as usize),
+                    None => Message::CommandTimeoutOff,
+                };
+                self.log(LogLevel::Info, message);
+            }
+            Action::SetLineEnding(line_ending) => {
+                self.set_line_ending(line_ending);
+            }
+            Action::SetLanguage(name) => {
+                self.set_language(name);
+            }
+            Action::Watch(expression) => {
+                self.watch(expression);
+            }
+            Action::Unwatch(number) => {
+                self.unwatch(number);
+            }
+            Action::Pin => {
+                self.pin();
+            }
+            Action::Unpin(number) => {
+                self.unpin(number);
+            }
+            Action::SetBookmark(name) => {
+                self.set_bookmark(name);
+            }
+            Action::JumpToBookmark(name) => {
+                self.jump_to_bookmark(name);
+            }
+            // Repeated replacements are converted into replacements by `execute_action`
+            Action::RepeatReplace => {}
+        }
+        false
+    }
+
+    /// Respond to a single key press, whether it was typed by the user or replayed from a macro.
+    /// Returns `true` if the key press means that Sapling should quit.
+    fn handle_key(&mut self, key: Key) -> bool {
+        // Ctrl-Z always suspends, whatever has focus, so that job control works like it does in
+        // other terminal programs
+        if key == Key::Ctrl('z') {
+            self.suspend();
+            return false;
+        }
+        // Keys played by a macro are already part of the macro being recorded
+        if self.macro_depth == 0 {
+            if let Some(recording) = &mut self.recording {
+                recording.push(key, self.command.is_empty());
+            }
+        }
+        // If a question is waiting to be answered, then the answer consumes the key press
+        if self.prompt.is_some() {
+            return self.answer_prompt(key);
+        }
+        // If the outline sidebar has focus, then it consumes all the key presses
+        if self.outline.as_ref().is_some_and(Outline::is_focused) {
+            self.handle_outline_key(key);
+            return false;
+        }
+        // Likewise for the completion menu and the history view
+        if self.completion.is_some() {
+            self.handle_completion_key(key);
+            return false;
+        }
+        if self.palette.is_some() {
+            return self.handle_palette_key(key);
+        }
+        if self.history_view.is_some() {
+            self.handle_history_key(key);
+            return false;
+        }
+        match self.mode {
+            Mode::Normal => self.handle_normal_key(key),
+            Mode::Insert => {
+                self.handle_text_key(key);
+                false
+            }
+            Mode::Command => self.handle_command_line_key(key),
+        }
+    }
+
+    /// Respond to a key press in [`Mode::Normal`], by adding it to the command and executing the
+    /// command once it's complete.  Returns `true` if Sapling should quit.
+    fn handle_normal_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Backspace => {
+                self.command.pop();
+                self.command_typed_at = Some(Instant::now());
+            }
+            Key::ESC => {
+                self.command.clear();
+                if self.repl_open {
+                    self.toggle_repl();
+                }
+                if self.batch.take().is_some() {
+                    self.log(LogLevel::Info, Message::BatchEditCancelled);
+                }
+            }
+            key => {
+                let input = KeyInput::from(key);
+                // Whilst recording, the command which starts recording stops it instead (like `q`
+                // in Vim)
+                if self.recording.is_some()
+                    && self.command.is_empty()
+                    && self.keymap.get(input) == Some(&Command::RecordMacro)
+                {
+                    return self.execute_command(Action::StopRecording);
+                }
+                // Keys other than characters are ignored unless they're bound to a command or
+                // are part of one
+                if self.command.is_empty()
+                    && input.as_char().is_none()
+                    && !self.keymap.contains_key(input)
+                {
+                    return false;
+                }
+                // Add the new keypress to the command
+                self.command.push(input);
+                return self.continue_command();
+            }
+        }
+        false
+    }
+
+    /// Respond to keys being added to the command: run it if it's complete, or start typing the
+    /// rest of the line if it's a line command.  Returns `true` if Sapling should quit.
+    fn continue_command(&mut self) -> bool {
+        self.command_typed_at = Some(Instant::now());
+        // Line commands are typed in their own mode, since they're only complete once enter is
+        // pressed
+        let is_incomplete = self.keymap.lookup(&self.command) == Lookup::Incomplete;
+        if !is_incomplete && is_line_command(&self.keymap, &self.command) {
+            self.enter_command_mode();
+            return false;
+        }
+        // Attempt to parse the command, and take action if the command is complete
+        if let Some(action) = parse_command(&self.keymap, &self.command) {
+            return self.execute_command(action);
+        }
+        false
+    }
+
+    /// Respond to a key press in [`Mode::Command`], by editing the line command and running it
+    /// when enter is pressed.  Up and down go back and forth through the lines which have already
+    /// been run.  Returns `true` if Sapling should quit.
+    fn handle_command_line_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Enter => {
+                // The action is free to start another line command (like the REPL does)
+                self.mode = Mode::Normal;
+                self.command_line.record(self.command.clone());
+                if let Some(action) = parse_line_command(&self.keymap, &self.command) {
+                    return self.execute_command(action);
+                }
+                self.command.clear();
+            }
+            Key::ESC => {
+                self.mode = Mode::Normal;
+                self.command.clear();
+                if self.repl_open {
+                    self.toggle_repl();
+                }
+            }
+            Key::Backspace => {
+                // Deleting the keys which started an empty line command abandons it
+                let start = self.line_command_start();
+                if self.command.len() <= start {
+                    self.mode = Mode::Normal;
+                    self.command.clear();
+                } else if self.command_line.cursor() > start {
+                    self.command_line.delete_back(&mut self.command);
+                }
+            }
+            Key::Left => self.command_line.move_left(self.line_command_start()),
+            Key::Right => self.command_line.move_right(&self.command),
+            Key::Up => {
+                self.command_line.history_prev(&mut self.command);
+            }
+            Key::Down => {
+                self.command_line.history_next(&mut self.command);
+            }
+            // Line commands are made only of characters
+            key => {
+                if let Some(c) = KeyInput::from(key).as_char() {
+                    self.command_line
+                        .insert(&mut self.command, KeyInput::from(c));
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns how long is left before the command in the command box runs anyway, if it's a
+    /// command as well as the start of a longer binding (see [`KeyMap::is_ambiguous`])
+    fn command_time_left(&self, now: Instant) -> Option<Duration> {
+        if self.mode != Mode::Normal {
+            return None;
+        }
+        let (_, command) = split_count(&self.keymap, &self.command);
+        if !self.keymap.is_ambiguous(command) {
+            return None;
+        }
+        let timeout = self.command_timeout?;
+        let typed_at = self.command_typed_at?;
+        Some(timeout.saturating_sub(now.saturating_duration_since(typed_at)))
+    }
+
+    /// Run the command in the command box once it has timed out, using the binding which the keys
+    /// typed so far complete rather than waiting for a longer one.  Returns `true` if Sapling
+    /// should quit.
+    fn run_timed_out_command(&mut self) -> bool {
+        self.command_typed_at = None;
+        if is_line_command(&self.keymap, &self.command) {
+            self.enter_command_mode();
+            return false;
+        }
+        match parse_timed_out_command(&self.keymap, &self.command) {
+            Some(action) => self.execute_command(action),
+            None => false,
+        }
+    }
+
+    /// Fill the command box with the start of a line command, so that the user can finish it
+    /// before pressing enter
+    fn fill_command_line(&mut self, text: &str) {
+        self.command = text_keys(text);
+        self.enter_command_mode();
+    }
+
+    /// Start typing the rest of the line command in the command box
+    fn enter_command_mode(&mut self) {
+        self.mode = Mode::Command;
+        self.command_line.start(&self.command);
+    }
+
+    /// Returns the number of keys at the start of the command box which are bound to the line
+    /// command being typed (like the `:` of `:w`), which the rest of the line is typed after
+    fn line_command_start(&self) -> usize {
+        match self.keymap.lookup_final(&self.command) {
+            Lookup::Bound(_, len) => len,
+            _ => 0,
+        }
+    }
+
+    /// Suspend Sapling so that the shell can use the terminal, and redraw the whole screen once
+    /// the shell resumes it
+    fn suspend(&mut self) {
+        match self.term.suspend() {
+            Ok(()) => self.log(LogLevel::Debug, Message::Resumed),
+            Err(e) => self.log(LogLevel::Error, Message::CannotSuspend(e.to_string())),
+        }
+        self.update_display();
+    }
+
+    /// Execute the [`Action`] parsed from the command box.  The command box is cleared before the
+    /// action is executed, so that actions are free to fill it (like [`Action::EditMacro`]).
+    /// Returns `true` if the action means that Sapling should quit.
+    fn execute_command(&mut self, action: Action) -> bool {
+        let command = std::mem::take(&mut self.command);
+        if action == Action::Undefined {
+            self.log(
+                LogLevel::Warning,
+                Message::NotACommand(keymap::sequence_text(&command)),
+            );
+            return false;
+        }
+        match self.substitute_paths(action) {
+            Ok(action) => self.execute_action(action),
+            Err(error_message) => {
+                self.log(LogLevel::Warning, error_message);
+                false
+            }
+        }
+    }
+
+    /// Expand the placeholders (like `%` for the buffer's file) in the path given to an action,
+    /// for the actions which take paths
+    fn substitute_paths(&self, action: Action) -> std::result::Result<Action, Message> {
+        let expand =
+            |path: String| substitute::expand(&path, self.path.as_deref(), self.tree.cursor_path());
+        Ok(match action {
+            Action::Write(Some(path)) => Action::Write(Some(expand(path)?)),
+            Action::WriteQuit(Some(path)) => Action::WriteQuit(Some(expand(path)?)),
+            Action::ReadFile(path) => Action::ReadFile(expand(path)?),
+            Action::Open(path) => Action::Open(expand(path)?),
+            Action::LoadSchema(path) => Action::LoadSchema(expand(path)?),
+            Action::ExportKeymap(path) => Action::ExportKeymap(expand(path)?),
+            Action::ImportKeymap(path) => Action::ImportKeymap(expand(path)?),
+            action => action,
+        })
+    }
+
+    fn mainloop(&mut self) {
+        self.frames.request();
+        // Sit in the infinte mainloop
+        loop {
+            // Whilst tasks are running, wake up regularly to animate their progress, and wake up
+            // when the preview is due to be re-rendered (or to be checked for a rendering running
+            // in the background) or when the user becomes idle.  A timeout
+            // is reported as an error, so in that case we just redraw the screen.
+            self.progress.update();
+            if self.poll_loader() {
+                self.frames.request();
+            }
+            let now = Instant::now();
+            if self.do_idle_work(now) {
+                self.frames.request();
+            }
+            // A command which has waited long enough for a longer binding runs on its own
+            if self.command_time_left(now) == Some(Duration::from_secs(0)) {
+                if self.run_timed_out_command() {
+                    break;
+                }
+                self.frames.request();
+            }
+            // Keys from a demo script are handled exactly like keys typed by the user
+            if let Some(demo) = &mut self.demo {
+                if let Some(key) = demo.take_due_key(now) {
+                    if self.handle_key(key) {
+                        break;
+                    }
+                    self.frames.request();
+                    continue;
+                }
+                if demo.is_finished(now) {
+                    break;
+                }
+            }
+            if let Some(preview) = &mut self.preview {
+                let tree = &self.tree;
+                let format_style = &self.format_style;
+                if preview.update(now, || tree.to_text(format_style)) {
+                    self.frames.request();
+                }
+            }
+            // The screen is only redrawn once the frame limit allows, so that keys which arrive
+            // faster than frames can be drawn are all handled before the next frame
+            if self.frames.is_due(now) {
+                self.update_display();
+                self.frames.drawn(Instant::now());
+            }
+            let frame_timeout = self.frames.time_until_due(now);
+            let spinner_timeout = Some(SPINNER_INTERVAL).filter(|_| self.progress.is_busy());
+            let preview_timeout = self.preview.as_ref().and_then(|p| p.time_until_due(now));
+            let idle_timeout = self.idle.time_until_idle(now);
+            // A file being opened is checked for more of the document as often as the spinner
+            // is animated
+            let loader_timeout = self.loader.as_ref().map(|_| SPINNER_INTERVAL);
+            let notification_timeout = self
+                .notification
+                .as_ref()
+                .and_then(|n| n.time_until_change(now));
+            let demo_timeout = self.demo.as_ref().map(|d| d.time_until_due(now));
+            // The countdown of a waiting command is redrawn as it runs down
+            let command_timeout = self
+                .command_time_left(now)
+                .map(|left| left.min(COUNTDOWN_INTERVAL));
+            let timeout = [
+                spinner_timeout,
+                preview_timeout,
+                idle_timeout,
+                loader_timeout,
+                notification_timeout,
+                demo_timeout,
+                command_timeout,
+                frame_timeout,
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+            let event = if let Some(timeout) = timeout {
+                match self.term.peek_event(timeout) {
+                    Ok(event) => event,
+                    Err(_) => {
+                        self.frames.request();
+                        continue;
+                    }
+                }
+            } else {
+                match self.term.poll_event() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                }
+            };
+
+            // A signal asking Sapling to close is forwarded to the mainloop as a user event
+            if let Event::User(()) = event {
+                if let Some(signal) = term_guard::received_signal() {
+                    self.log(LogLevel::Info, Message::ReceivedSignal(signal.to_string()));
+                }
+                break;
+            }
+
+            /* RESPOND TO THE USER'S INPUT */
+            if let Event::Key(key) = event {
+                self.idle.note_input(Instant::now());
+                if self.handle_key(key) {
+                    // Break the mainloop to quit
+                    break;
+                }
+            }
+
+            // Every input asks for the screen to be updated, which happens once the frame limit
+            // allows
+            self.frames.request();
+        }
+    }
+
+    /// Replay a scripted session once the editor starts, quitting when the script is finished
+    pub fn play_demo(&mut self, demo: Demo) {
+        self.demo = Some(demo);
+    }
+
+    /// Start the editor and enter the mainloop
+    pub fn run(mut self) {
+        // Log the startup of the code
+        self.log(LogLevel::Info, Message::StartingUp);
+        // Start the mainloop
+        self.mainloop();
+        // The terminal is restored when `self.term` is dropped, however the editor exits
+        // Log that the editor is closing
+        self.log(LogLevel::Info, Message::Closing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_line_command, parse_command, parse_line_command, parse_timed_out_command, text_keys,
+        Action, Command, Encoding, KeyInput, Landing, LineEnding, LogLevel, PastePlace,
+        ScrollAnchor, ScrollStep, Segment,
+    };
+    use crate::editable_tree::Direction;
+    use std::time::Duration;
+    use tuikit::key::Key;
+
+    #[test]
+    fn invalid_parts() {
+        let arena: crate::arena::Arena<crate::ast::json::JSON> = crate::arena::Arena::new();
+        let count = |text: &str| {
+            let recovered = crate::ast::json_recover::parse(text).unwrap();
+            super::count_invalid(arena.alloc(recovered.node.to_node(&arena).unwrap()))
+        };
+        assert_eq!(count("[true, {\"a\": false}]"), 0);
+        assert_eq!(count("[true, x, {\"a\": y}]"), 2);
+    }
+
+    #[test]
+    fn edits() {
+        for action in &[
+            Action::Replace('t'),
+            Action::EditText,
+            Action::Convert('o'),
+            Action::Repeat(3, Box::new(Action::EditText)),
+        ] {
+            assert!(action.is_edit(), "{:?}", action);
+        }
+        for action in &[
+            Action::Undo,
+            Action::MoveCursor(Direction::Down, 1),
+            Action::Repeat(3, Box::new(Action::Undo)),
+        ] {
+            assert!(!action.is_edit(), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn parse_command_complete() {
+        let keymap = super::default_keymap();
+        for (command, expected_effect) in &[
+            ("q", Action::Quit),
+            ("x", Action::Undefined),
+            ("pajlbsi", Action::MoveCursor(Direction::Up, 1)),
+            ("Pxx", Action::Undefined),
+            ("Qsx", Action::Undefined),
+            ("ra", Action::Replace('a')),
+            ("rg", Action::Replace('g')),
+            ("iX", Action::InsertChild('X')),
+            ("iP", Action::InsertChild('P')),
+            ("zz", Action::Scroll(ScrollAnchor::Center)),
+            ("ztq", Action::Scroll(ScrollAnchor::Top)),
+            ("zb", Action::Scroll(ScrollAnchor::Bottom)),
+            ("zx", Action::Undefined),
+            ("za", Action::ToggleFold),
+            ("zR", Action::OpenAllFolds),
+            ("b", Action::MotionBack),
+            ("B", Action::MotionForward),
+            (";", Action::OlderEdit),
+            ("n", Action::StepSearch(true)),
+            ("2N", Action::Repeat(2, Box::new(Action::StepSearch(false)))),
+            ("3,", Action::Repeat(3, Box::new(Action::NewerEdit))),
+            ("y", Action::Yank(None)),
+            ("d", Action::Cut(None)),
+            ("v", Action::Paste(PastePlace::Replace, None)),
+            ("a", Action::Paste(PastePlace::After, None)),
+            ("V", Action::Paste(PastePlace::Child, None)),
+            ("\"ay", Action::Yank(Some('a'))),
+            ("\"zd", Action::Cut(Some('z'))),
+            ("\"bV", Action::Paste(PastePlace::Child, Some('b'))),
+            ("\"Ay", Action::Undefined),
+            ("\"aj", Action::Undefined),
+            ("@a", Action::PlayMacro('a')),
+            ("Mq", Action::RecordMacro('q')),
+            ("3@a", Action::Repeat(3, Box::new(Action::PlayMacro('a')))),
+            (".", Action::RepeatReplace),
+            (")", Action::Slurp),
+            ("(", Action::Barf),
+            ("tj", Action::Transpose(true)),
+            ("tk", Action::Transpose(false)),
+            ("tc", Action::Undefined),
+            ("J", Action::Join),
+            ("w", Action::Write(None)),
+            ("ma", Action::SetBookmark('a')),
+            ("'7", Action::JumpToBookmark('7')),
+            ("m!", Action::Undefined),
+            ("I", Action::EditText),
+            ("C", Action::OpenPalette),
+            ("T", Action::OpenTransformMenu),
+            ("L", Action::ToggleLog),
+            // Counts
+            ("3j", Action::MoveCursor(Direction::Next, 3)),
+            ("12k", Action::MoveCursor(Direction::Prev, 12)),
+            ("5u", Action::UndoSteps(5)),
+            ("2R", Action::RedoSteps(2)),
+            ("3)", Action::Repeat(3, Box::new(Action::Slurp))),
+            (
+                "2\"aa",
+                Action::Repeat(2, Box::new(Action::Paste(PastePlace::After, Some('a')))),
+            ),
+            ("4q", Action::Quit),
+            ("0j", Action::Undefined),
+            ("3x", Action::Undefined),
+            ("99999999999999999999999j", Action::Undefined),
+            (
+                "5000iX",
+                Action::Repeat(super::MAX_REPEAT_COUNT, Box::new(Action::InsertChild('X'))),
+            ),
+        ] {
+            assert_eq!(
+                parse_command(&keymap, &text_keys(command)),
+                Some(expected_effect.clone())
+            );
+        }
+        // Scrolling the view is bound to keys which aren't characters, and can be counted
+        let scroll_keys =
+            |keys: &[Key]| keys.iter().map(|k| KeyInput::from(*k)).collect::<Vec<_>>();
+        assert_eq!(
+            parse_command(&keymap, &scroll_keys(&[Key::Ctrl('e')])),
+            Some(Action::ScrollView(ScrollStep::LineDown, 1))
+        );
+        assert_eq!(
+            parse_command(&keymap, &scroll_keys(&[Key::Char('3'), Key::PageUp])),
+            Some(Action::ScrollView(ScrollStep::PageUp, 3))
+        );
+        assert_eq!(
+            parse_command(&keymap, &scroll_keys(&[Key::Char('2'), Key::AltUp])),
+            Some(Action::ScrollLog(ScrollStep::LineUp, 2))
+        );
+        // Keys bound to insert-and-edit commands complete them straight away
+        let mut keymap = keymap;
+        keymap.extend(vec![(
+            vec![KeyInput::from('S')],
+            Command::InsertAndEdit('s'),
+        )]);
+        assert_eq!(
+            parse_command(&keymap, &text_keys("S")),
+            Some(Action::InsertAndEdit('s'))
+        );
+        // Keys other than characters can start commands, or be the arguments of commands
+        keymap.extend(vec![
+            (
+                vec![KeyInput::from(Key::PageDown)],
+                Command::MoveCursor(Direction::Next),
+            ),
+            (vec![KeyInput::from(Key::Ctrl('r'))], Command::Replace),
+        ]);
+        let keys = |keys: &[Key]| keys.iter().map(|k| KeyInput::from(*k)).collect::<Vec<_>>();
+        assert_eq!(
+            parse_command(&keymap, &keys(&[Key::PageDown])),
+            Some(Action::MoveCursor(Direction::Next, 1))
+        );
+        assert_eq!(
+            parse_command(&keymap, &keys(&[Key::Char('3'), Key::PageDown])),
+            Some(Action::MoveCursor(Direction::Next, 3))
+        );
+        assert_eq!(
+            parse_command(&keymap, &keys(&[Key::Char('t'), Key::PageDown])),
+            Some(Action::Transpose(true))
+        );
+        assert_eq!(
+            parse_command(&keymap, &keys(&[Key::Ctrl('r'), Key::Char('t')])),
+            Some(Action::Replace('t'))
+        );
+        assert_eq!(
+            parse_command(&keymap, &keys(&[Key::Char('r'), Key::PageDown])),
+            Some(Action::Undefined)
+        );
+    }
+
+    #[test]
+    fn parse_key_sequences() {
+        let mut keymap = super::default_keymap();
+        keymap.extend(vec![
+            (text_keys("gg"), Command::MoveCursor(Direction::Up)),
+            (
+                KeyInput::parse_sequence("<Space>w").unwrap(),
+                Command::Write,
+            ),
+            (
+                KeyInput::parse_sequence("<Space>j").unwrap(),
+                Command::MoveCursor(Direction::Next),
+            ),
+            (KeyInput::parse_sequence("<Space>y").unwrap(), Command::Yank),
+        ]);
+        for (command, expected_effect) in &[
+            // Commands stay incomplete whilst they are the start of a longer binding
+            ("g", None),
+            (" ", None),
+            ("gg", Some(Action::MoveCursor(Direction::Up, 1))),
+            (" w", Some(Action::Write(None))),
+            (" x", Some(Action::Undefined)),
+            ("3 j", Some(Action::MoveCursor(Direction::Next, 3))),
+            ("\"a y", Some(Action::Yank(Some('a')))),
+            ("\"a ", None),
+            // `g` is still the start of a line command if the next key doesn't continue `gg`
+            ("gs", None),
+        ] {
+            assert_eq!(
+                parse_command(&keymap, &text_keys(command)),
+                *expected_effect,
+                "{:?}",
+                command
+            );
+        }
+        assert_eq!(
+            parse_line_command(&keymap, &text_keys("gtype")),
+            Some(Action::Group("type".to_string()))
+        );
+        // Single keys are still bound as before
+        assert_eq!(parse_command(&keymap, &text_keys("u")), Some(Action::Undo));
+
+        // Once a command times out, the binding it completes is used without waiting for more
+        keymap.insert(&text_keys("uu"), Command::Redo);
+        assert_eq!(parse_command(&keymap, &text_keys("u")), None);
+        assert_eq!(
+            parse_timed_out_command(&keymap, &text_keys("3u")),
+            Some(Action::UndoSteps(3))
+        );
+        assert_eq!(parse_timed_out_command(&keymap, &text_keys(" ")), None);
+        // A line command which starts a longer binding is typed as a line command once it times
+        // out
+        assert!(is_line_command(&keymap, &text_keys("g")));
+    }
+
+    #[test]
+    fn parse_command_incomplete() {
+        let keymap = super::default_keymap();
+        for command in &[
+            "", "r", "i", "z", "/", "/tru", ":", ":noh", "@", "e", "eobj", "3", "10", "3i", "M",
+            "m",
+        ] {
+            assert_eq!(parse_command(&keymap, &text_keys(command)), None);
+        }
+    }
+
+    #[test]
+    fn parse_line_commands() {
+        let keymap = super::default_keymap();
+        for (command, expected_effect) in &[
+            ("/tru", Some(Action::Search("tru".to_string()))),
+            ("/", Some(Action::Search("".to_string()))),
+            (":noh", Some(Action::ClearSearch)),
+            (":q", Some(Action::Quit)),
+            (":quit", Some(Action::Quit)),
+            (":q!", Some(Action::ForceQuit)),
+            (":quit!", Some(Action::ForceQuit)),
+            (":q! now", Some(Action::Undefined)),
+            (":wq", Some(Action::WriteQuit(None))),
+            (
+                ":wq out.json",
+                Some(Action::WriteQuit(Some("out.json".to_string()))),
+            ),
+            (
+                ":map <Space>w Write",
+                Some(Action::Map(text_keys(" w"), Command::Write)),
+            ),
+            (
+                ":map gg MoveCursor(Up)",
+                Some(Action::Map(
+                    text_keys("gg"),
+                    Command::MoveCursor(Direction::Up),
+                )),
+            ),
+            (":map gg Nope", Some(Action::Undefined)),
+            (":map gg", Some(Action::Undefined)),
+            (":map gg Write Quit", Some(Action::Undefined)),
+            (
+                ":map S LanguageCommand(\"sort-keys\")",
+                Some(Action::Map(
+                    text_keys("S"),
+                    Command::LanguageCommand("sort-keys".to_string()),
+                )),
+            ),
+            (
+                ":run sort-keys",
+                Some(Action::RunLanguageCommand("sort-keys".to_string())),
+            ),
+            (":run", Some(Action::Undefined)),
+            (": nohlsearch ", Some(Action::ClearSearch)),
+            (":bogus", Some(Action::Undefined)),
+            (":noh please", Some(Action::Undefined)),
+            (":macro edit a", Some(Action::EditMacro('a'))),
+            (":macro record a", Some(Action::RecordMacro('a'))),
+            (":macro stop", Some(Action::StopRecording)),
+            (":macro edit ab", Some(Action::Undefined)),
+            (":macro edit a cj", Some(Action::Undefined)),
+            (
+                ":macro set q cj<CR>  x",
+                Some(Action::SetMacro('q', "cj<CR> x".to_string())),
+            ),
+            (":macro set q", Some(Action::SetMacro('q', "".to_string()))),
+            (":macro", Some(Action::Undefined)),
+            (":eval foo.0", Some(Action::Evaluate("foo.0".to_string()))),
+            (":eval  a b ", Some(Action::Evaluate("a b".to_string()))),
+            (":eval", Some(Action::Evaluate("".to_string()))),
+            (":evaluate", Some(Action::Undefined)),
+            (":repl", Some(Action::ToggleRepl)),
+            (":embedded", Some(Action::EditEmbedded)),
+            (":table", Some(Action::ToggleTable)),
+            (":each", Some(Action::BatchEdit)),
+            (":dups", Some(Action::FindDuplicates)),
+            (":stats", Some(Action::ShowStats)),
+            (":journal", Some(Action::ShowJournal)),
+            (":def", Some(Action::GoToDefinition)),
+            (":new", Some(Action::NewDocument)),
+            (":w", Some(Action::Write(None))),
+            (
+                ":write out.json",
+                Some(Action::Write(Some("out.json".to_string()))),
+            ),
+            (":rename id", Some(Action::Rename("id".to_string()))),
+            (":rename", Some(Action::Undefined)),
+            (":references", Some(Action::FindReferences)),
+            (":sort", Some(Action::Sort(None))),
+            (":wrap a", Some(Action::Wrap('a'))),
+            (":wrap ab", Some(Action::Undefined)),
+            (":convert o", Some(Action::Convert('o'))),
+            (":unwrap", Some(Action::Unwrap)),
+            (":raise", Some(Action::Raise)),
+            (":transforms", Some(Action::OpenTransformMenu)),
+            (":sort  name ", Some(Action::Sort(Some("name".to_string())))),
+            (":group", Some(Action::Undefined)),
+            (":group name", Some(Action::Group("name".to_string()))),
+            (":copy-up", Some(Action::CopyFromSibling(false))),
+            (":copy-down", Some(Action::CopyFromSibling(true))),
+            (
+                ":jq .users[3]",
+                Some(Action::Query(".users[3]".to_string())),
+            ),
+            (":cn", Some(Action::StepQuickfix(true))),
+            (":undo", Some(Action::Undo)),
+            (":undo 5", Some(Action::UndoSteps(5))),
+            (":redo 3", Some(Action::RedoSteps(3))),
+            (":redo 0", Some(Action::Undefined)),
+            (":undo-to-saved", Some(Action::UndoToSaved)),
+            (":history", Some(Action::ShowHistory)),
+            (":history check", Some(Action::CheckHistory)),
+            (":history check all", Some(Action::Undefined)),
+            (
+                ":schema  my schema.json",
+                Some(Action::LoadSchema("my schema.json".to_string())),
+            ),
+            (":schema", Some(Action::Undefined)),
+            (
+                ":keymap export ~/keys.toml",
+                Some(Action::ExportKeymap("~/keys.toml".to_string())),
+            ),
+            (
+                ":keymap import  my keys.toml",
+                Some(Action::ImportKeymap("my keys.toml".to_string())),
+            ),
+            (":keymap export", Some(Action::Undefined)),
+            (":keymap vim", Some(Action::Undefined)),
+            (":r a.json", Some(Action::ReadFile("a.json".to_string()))),
+            (":read", Some(Action::Undefined)),
+            (":cprevious", Some(Action::StepQuickfix(false))),
+            (
+                ":set language=json",
+                Some(Action::SetLanguage("json".to_string())),
+            ),
+            (":set language=", Some(Action::Undefined)),
+            (
+                ":set encoding=UTF-16LE",
+                Some(Action::SetEncoding(Encoding::Utf16Le)),
+            ),
+            (":set encoding=ebcdic", Some(Action::Undefined)),
+            (
+                ":set newlines=crlf",
+                Some(Action::SetLineEnding(LineEnding::CrLf)),
+            ),
+            (":set maxdepth=3", Some(Action::SetMaxDepth(Some(3)))),
+            (":set maxdepth=off", Some(Action::SetMaxDepth(None))),
+            (":set number", Some(Action::SetLineNumbers(true))),
+            (":set nonumber", Some(Action::SetLineNumbers(false))),
+            (":set maxdepth=deep", Some(Action::Undefined)),
+            (":set width=80", Some(Action::SetLineWidth(Some(80)))),
+            (":set width=off", Some(Action::SetLineWidth(None))),
+            (
+                ":set timeout=250",
+                Some(Action::SetCommandTimeout(Some(Duration::from_millis(250)))),
+            ),
+            (":set timeout=off", Some(Action::SetCommandTimeout(None))),
+            (
+                ":set status=mode,path",
+                Some(Action::SetStatusSegments(vec![
+                    Segment::Mode,
+                    Segment::Path,
+                ])),
+            ),
+            (":set status=", Some(Action::SetStatusSegments(vec![]))),
+            (":set status=colour", Some(Action::Undefined)),
+            (
+                ":set loglevel=debug",
+                Some(Action::SetLogLevel(LogLevel::Debug)),
+            ),
+            (":set loglevel=loud", Some(Action::Undefined)),
+            (":log", Some(Action::ToggleLog)),
+            (":fold", Some(Action::ToggleFold)),
+            (":unfold", Some(Action::OpenAllFolds)),
+            (":fold oversized", Some(Action::FoldOversized)),
+            (":set depthlimit=50", Some(Action::SetDepthLimit(50))),
+            (":set childlimit=0", Some(Action::Undefined)),
+            (":set fps=30", Some(Action::SetMaxFps(30))),
+            (":set fps=0", Some(Action::Undefined)),
+            (
+                ":set landing=new",
+                Some(Action::SetLanding(Landing::NewNode)),
+            ),
+            (
+                ":set landing=hole",
+                Some(Action::SetLanding(Landing::FirstHole)),
+            ),
+            (":set landing=child", Some(Action::Undefined)),
+            (":set timeout=soon", Some(Action::Undefined)),
+            (":set width=wide", Some(Action::Undefined)),
+            (":set colour=red", Some(Action::Undefined)),
+            (
+                "eobject",
+                Some(Action::SelectEnclosing("object".to_string())),
+            ),
+            (
+                "e array ",
+                Some(Action::SelectEnclosing("array".to_string())),
+            ),
+            (":watch foo. 0", Some(Action::Watch("foo. 0".to_string()))),
+            (":unwatch", Some(Action::Unwatch(None))),
+            (":unwatch 2", Some(Action::Unwatch(Some(2)))),
+            (":unwatch 0", Some(Action::Undefined)),
+            (":unwatch x", Some(Action::Undefined)),
+            (":unwatch 1 2", Some(Action::Undefined)),
+            (":pin", Some(Action::Pin)),
+            (":unpin", Some(Action::Unpin(None))),
+            (":unpin 3", Some(Action::Unpin(Some(3)))),
+            (":unpin 0", Some(Action::Undefined)),
+            ("r", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                parse_line_command(&keymap, &text_keys(command)),
+                *expected_effect
+            );
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! A history of the cursor's recent positions, which is kept separately from the undo history so
+//! that undo/redo only ever affect edits.
+
+use crate::editable_tree::cursor_path::CursorPath;
+
+/// The maximum number of positions stored in a [`MotionHistory`]
+pub const MAX_MOTION_HISTORY_LEN: usize = 100;
+
+/// A history of cursor positions that can be moved backwards and forwards through, like the
+/// history of a web browser.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MotionHistory {
+    /// The positions that the cursor has occupied, oldest first
+    positions: Vec<CursorPath>,
+    /// The index into `positions` of the position that the cursor currently occupies.  This is
+    /// required to be in `0..positions.len()` whenever `positions` is non-empty.
+    index: usize,
+}
+
+impl MotionHistory {
+    /// Creates an empty `MotionHistory`
+    pub fn new() -> MotionHistory {
+        MotionHistory {
+            positions: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Records that the cursor has moved from `from` to `to`.  Any positions that had been
+    /// retraced with [`MotionHistory::back`] are forgotten.
+    pub fn record(&mut self, from: &CursorPath, to: &CursorPath) {
+        if self.positions.is_empty() {
+            self.positions.push(from.clone());
+        }
+        // Forget the positions ahead of us, then add the new position
+        self.positions.truncate(self.index + 1);
+        self.positions.push(to.clone());
+        // Forget the oldest positions if the history is too long
+        if self.positions.len() > MAX_MOTION_HISTORY_LEN {
+            let excess = self.positions.len() - MAX_MOTION_HISTORY_LEN;
+            self.positions.drain(..excess);
+        }
+        self.index = self.positions.len() - 1;
+    }
+
+    /// Moves one step back through the history, returning the position to move the cursor to or
+    /// [`None`] if there are no older positions.
+    pub fn back(&mut self) -> Option<&CursorPath> {
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.positions.get(self.index)
+    }
+
+    /// Moves one step forward through the history, returning the position to move the cursor to or
+    /// [`None`] if there are no newer positions.
+    pub fn forward(&mut self) -> Option<&CursorPath> {
+        if self.index + 1 >= self.positions.len() {
+            return None;
+        }
+        self.index += 1;
+        self.positions.get(self.index)
+    }
+}
+
+impl Default for MotionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MotionHistory, MAX_MOTION_HISTORY_LEN};
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    fn path(indices: &[usize]) -> CursorPath {
+        CursorPath::from_vec(indices.to_vec())
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut history = MotionHistory::new();
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), None);
+        history.record(&path(&[]), &path(&[0]));
+        history.record(&path(&[0]), &path(&[1]));
+        assert_eq!(history.back(), Some(&path(&[0])));
+        assert_eq!(history.back(), Some(&path(&[])));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some(&path(&[0])));
+        // Moving somewhere new forgets the positions we went back over
+        history.record(&path(&[0]), &path(&[0, 2]));
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back(), Some(&path(&[0])));
+        assert_eq!(history.forward(), Some(&path(&[0, 2])));
+    }
+
+    #[test]
+    fn max_len() {
+        let mut history = MotionHistory::new();
+        for i in 0..MAX_MOTION_HISTORY_LEN * 2 {
+            history.record(&path(&[i]), &path(&[i + 1]));
+        }
+        let mut steps_back = 0;
+        while history.back().is_some() {
+            steps_back += 1;
+        }
+        assert_eq!(steps_back, MAX_MOTION_HISTORY_LEN - 1);
+    }
+}
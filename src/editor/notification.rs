@@ -0,0 +1,93 @@
+//! Transient notifications, which show the most recent message just above the bottom bar for a
+//! few seconds (dimming before they disappear) so that the user notices it without having to
+//! look at the log pane.
+
+use super::LogLevel;
+use std::time::{Duration, Instant};
+
+/// How long a notification is shown at full brightness
+pub const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+/// How long a notification is shown dimmed after [`NOTIFICATION_DURATION`], before it disappears
+pub const FADE_DURATION: Duration = Duration::from_secs(1);
+
+/// How a notification should currently be shown
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Visibility {
+    Shown,
+    Fading,
+    Hidden,
+}
+
+/// A message shown briefly above the bottom bar
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Notification {
+    pub level: LogLevel,
+    pub text: String,
+    shown_at: Instant,
+}
+
+impl Notification {
+    /// Creates a notification which is first shown at `now`
+    pub fn new(level: LogLevel, text: String, now: Instant) -> Notification {
+        Notification {
+            level,
+            text,
+            shown_at: now,
+        }
+    }
+
+    /// Returns how this notification should be shown at `now`
+    pub fn visibility(&self, now: Instant) -> Visibility {
+        let age = now.saturating_duration_since(self.shown_at);
+        if age < NOTIFICATION_DURATION {
+            Visibility::Shown
+        } else if age < NOTIFICATION_DURATION + FADE_DURATION {
+            Visibility::Fading
+        } else {
+            Visibility::Hidden
+        }
+    }
+
+    /// Returns how long until this notification next changes how it's shown, or [`None`] if it
+    /// has already disappeared
+    pub fn time_until_change(&self, now: Instant) -> Option<Duration> {
+        let next_change = match self.visibility(now) {
+            Visibility::Shown => NOTIFICATION_DURATION,
+            Visibility::Fading => NOTIFICATION_DURATION + FADE_DURATION,
+            Visibility::Hidden => return None,
+        };
+        Some((self.shown_at + next_change).saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Notification, Visibility};
+    use crate::editor::LogLevel;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn fades() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let notification = Notification::new(LogLevel::Info, "hi".to_string(), start);
+        assert_eq!(notification.visibility(start), Visibility::Shown);
+        assert_eq!(
+            notification.time_until_change(start + ms(1000)),
+            Some(ms(2000))
+        );
+        assert_eq!(
+            notification.visibility(start + ms(3500)),
+            Visibility::Fading
+        );
+        assert_eq!(
+            notification.time_until_change(start + ms(3500)),
+            Some(ms(500))
+        );
+        assert_eq!(
+            notification.visibility(start + ms(4000)),
+            Visibility::Hidden
+        );
+        assert_eq!(notification.time_until_change(start + ms(4000)), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! The outline sidebar, which lists the labelled nodes near the top of the tree so that the user
+//! can jump straight to them.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+
+/// How many levels of labelled nodes are listed in the outline
+pub const OUTLINE_DEPTH: usize = 2;
+/// How many columns of the screen are occupied by the outline sidebar (including its border)
+pub const OUTLINE_WIDTH: usize = 24;
+
+/// A single line of the outline sidebar
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OutlineEntry {
+    /// The path from the root to the node that this entry refers to
+    pub path: CursorPath,
+    /// The label given to the node by [`Ast::outline_label`]
+    pub label: String,
+    /// How many labelled ancestors the node has.  This is used to indent the entry.
+    pub depth: usize,
+}
+
+/// Walks the tree under `root` and returns an [`OutlineEntry`] for every labelled node that has
+/// fewer than `max_depth` labelled ancestors.  The entries are returned in the order that their
+/// nodes appear in the tree.
+pub fn entries<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    max_depth: usize,
+) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    add_entries_rec(root, &mut CursorPath::root(), 0, max_depth, &mut entries);
+    entries
+}
+
+/// Recursive helper function for [`entries`].  `path` must point to `node`, and `depth` is the
+/// number of labelled ancestors of `node`.
+fn add_entries_rec<'arena, Node: Ast<'arena>>(
+    node: &'arena Node,
+    path: &mut CursorPath,
+    depth: usize,
+    max_depth: usize,
+    entries: &mut Vec<OutlineEntry>,
+) {
+    // If we're already too deep then neither this node nor its descendants can be listed
+    if depth >= max_depth {
+        return;
+    }
+    // Add this node to the outline if it has a label, and work out the depth of its children
+    let child_depth = match node.outline_label() {
+        Some(label) => {
+            entries.push(OutlineEntry {
+                path: path.clone(),
+                label,
+                depth,
+            });
+            depth + 1
+        }
+        None => depth,
+    };
+    // Add the children's entries, keeping `path` pointing at whichever node we're visiting
+    for (i, &child) in node.children().iter().enumerate() {
+        path.push(i);
+        add_entries_rec(child, path, child_depth, max_depth, entries);
+        path.pop();
+    }
+}
+
+/// The state of the outline sidebar whilst it is open
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Outline {
+    /// The index of the selected entry.  This may be out of range if the tree has been edited, so
+    /// it should always be read through [`Outline::selected`].
+    selected: usize,
+    /// Whether key presses are sent to the outline (rather than being used to edit the tree)
+    is_focused: bool,
+}
+
+impl Outline {
+    /// Creates a newly opened `Outline`, which has focus and has its first entry selected.
+    pub fn new() -> Outline {
+        Outline {
+            selected: 0,
+            is_focused: true,
+        }
+    }
+
+    /// Returns `true` if key presses should be sent to the outline
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    /// Gives or takes away the keyboard focus from the outline
+    pub fn set_focus(&mut self, is_focused: bool) {
+        self.is_focused = is_focused;
+    }
+
+    /// Returns the index of the selected entry, given that there are `num_entries` entries.
+    /// Returns [`None`] if the outline is empty.
+    pub fn selected(&self, num_entries: usize) -> Option<usize> {
+        num_entries
+            .checked_sub(1)
+            .map(|last| self.selected.min(last))
+    }
+
+    /// Moves the selection to the next entry, if such an entry exists
+    pub fn select_next(&mut self, num_entries: usize) {
+        if let Some(index) = self.selected(num_entries) {
+            self.selected = (index + 1).min(num_entries - 1);
+        }
+    }
+
+    /// Moves the selection to the previous entry, if such an entry exists
+    pub fn select_prev(&mut self, num_entries: usize) {
+        if let Some(index) = self.selected(num_entries) {
+            self.selected = index.saturating_sub(1);
+        }
+    }
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, Outline};
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn outline_entries() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Object(vec![
+                (
+                    "foo".to_string(),
+                    TestJSON::Object(vec![(
+                        "bar".to_string(),
+                        TestJSON::Object(vec![("baz".to_string(), TestJSON::True)]),
+                    )]),
+                ),
+                ("qux".to_string(), TestJSON::False),
+            ]),
+        ])
+        .add_to_arena(&arena);
+        // Only the labels are affected by the depth limit, so the unlabelled array and object at
+        // the top of the tree don't stop 'bar' from being listed
+        let entries = entries(root, 2);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.label.as_str(), e.depth))
+                .collect::<Vec<_>>(),
+            vec![(r#""foo""#, 0), (r#""bar""#, 1), (r#""qux""#, 0)]
+        );
+        assert_eq!(entries[0].path, CursorPath::from_vec(vec![1, 0]));
+        assert_eq!(entries[1].path, CursorPath::from_vec(vec![1, 0, 1, 0]));
+        assert_eq!(entries[2].path, CursorPath::from_vec(vec![1, 1]));
+        // A depth of 0 lists nothing
+        assert!(super::entries(root, 0).is_empty());
+    }
+
+    #[test]
+    fn selection() {
+        let mut outline = Outline::new();
+        assert_eq!(outline.selected(0), None);
+        outline.select_next(3);
+        outline.select_next(3);
+        outline.select_next(3);
+        assert_eq!(outline.selected(3), Some(2));
+        // If the outline shrinks, then the selection is clamped to the last entry
+        assert_eq!(outline.selected(2), Some(1));
+        outline.select_prev(2);
+        outline.select_prev(2);
+        assert_eq!(outline.selected(2), Some(0));
+    }
+}
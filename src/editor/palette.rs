@@ -0,0 +1,136 @@
+//! The command palette, which lists every command that can be run (the bound commands, along with
+//! the [language commands](crate::ast::commands) of the buffer's language) so that the user can
+//! find one by typing part of its name or description, and run it without knowing its keys.
+
+/// A command which can be chosen from the palette
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PaletteEntry<A> {
+    pub name: String,
+    pub description: String,
+    /// What choosing this entry does
+    pub action: A,
+}
+
+impl<A> PaletteEntry<A> {
+    /// Returns `true` if `filter` appears in this entry's name or description, ignoring case
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        self.name.to_lowercase().contains(&filter)
+            || self.description.to_lowercase().contains(&filter)
+    }
+
+    /// Returns the text shown for this entry in the palette
+    pub fn label(&self) -> String {
+        format!("{} - {}", self.name, self.description)
+    }
+}
+
+/// An open command palette, which shows the entries matching the text typed so far, one of which
+/// is selected
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Palette<A> {
+    entries: Vec<PaletteEntry<A>>,
+    filter: String,
+    /// The index of the selected entry among the entries which match the filter
+    selected: usize,
+}
+
+impl<A> Palette<A> {
+    /// Opens a palette with an empty filter, so that every entry is shown
+    pub fn new(entries: Vec<PaletteEntry<A>>) -> Palette<A> {
+        Palette {
+            entries,
+            filter: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// Returns the text which entries are being filtered by
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Types a character onto the end of the filter
+    pub fn type_char(&mut self, c: char) {
+        self.filter.push(c);
+        self.selected = 0;
+    }
+
+    /// Deletes the last character of the filter
+    pub fn delete_back(&mut self) {
+        self.filter.pop();
+        self.selected = 0;
+    }
+
+    /// Returns the entries which match the filter, in order
+    pub fn matches(&self) -> Vec<&PaletteEntry<A>> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.matches(&self.filter))
+            .collect()
+    }
+
+    /// Returns the index of the selected entry in [`Palette::matches`]
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// Returns the selected entry, or [`None`] if no entries match the filter
+    pub fn selected(&self) -> Option<&PaletteEntry<A>> {
+        self.matches().get(self.selected).copied()
+    }
+
+    /// Selects the next matching entry (or the previous one if `forward` is `false`), wrapping
+    /// round at either end of the list
+    pub fn select(&mut self, forward: bool) {
+        let len = self.matches().len();
+        if len == 0 {
+            return;
+        }
+        self.selected = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Palette, PaletteEntry};
+
+    #[test]
+    fn palette() {
+        let entry = |name: &str, description: &str, action: usize| PaletteEntry {
+            name: name.to_string(),
+            description: description.to_string(),
+            action,
+        };
+        let mut palette = Palette::new(vec![
+            entry("Undo", "u", 0),
+            entry("sort-keys", "Sort the fields of objects", 1),
+            entry("Sort", "s", 2),
+        ]);
+        assert_eq!(palette.matches().len(), 3);
+        palette.select(false);
+        assert_eq!(palette.selected().map(|e| e.action), Some(2));
+        // Filtering ignores case, looks at descriptions too, and selects the first match
+        palette.type_char('S');
+        palette.type_char('o');
+        assert_eq!(palette.filter(), "So");
+        assert_eq!(palette.selected_index(), 0);
+        let names: Vec<&str> = palette.matches().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["sort-keys", "Sort"]);
+        palette.select(true);
+        palette.select(true);
+        assert_eq!(palette.selected().map(|e| e.action), Some(1));
+        palette.type_char('x');
+        assert_eq!(palette.selected(), None);
+        palette.select(true);
+        palette.delete_back();
+        assert_eq!(
+            palette.selected().map(|e| e.label()),
+            Some("sort-keys - Sort the fields of objects".to_string())
+        );
+    }
+}
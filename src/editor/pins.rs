@@ -0,0 +1,189 @@
+//! Pinned nodes, whose text is kept on screen in a small pane so that the user can refer to them
+//! whilst editing somewhere else in the tree.  Like folds, each pin remembers its node as well as
+//! its path, so that the pin follows the node when an edit moves it.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use std::collections::VecDeque;
+
+/// How many nodes can be pinned at once.  Pinning another node unpins the oldest one.
+pub const MAX_PINS: usize = 4;
+
+/// A single pinned node
+#[derive(Debug, Clone)]
+pub struct Pin<'arena, Node> {
+    /// The path to the pinned node
+    pub path: CursorPath,
+    /// The pinned node, which was at `path` when the pins were last [adjusted](Pins::adjust)
+    pub node: &'arena Node,
+    /// The text of the node when the pins were last updated, or [`None`] if the node is no
+    /// longer in the tree
+    pub text: Option<String>,
+}
+
+/// The pinned nodes, oldest first
+#[derive(Debug, Clone)]
+pub struct Pins<'arena, Node> {
+    pins: VecDeque<Pin<'arena, Node>>,
+}
+
+impl<'arena, Node: Ast<'arena>> Pins<'arena, Node> {
+    /// Creates an empty list of pins
+    pub fn new() -> Pins<'arena, Node> {
+        Pins {
+            pins: VecDeque::new(),
+        }
+    }
+
+    /// Pins `node` (which is at `path`), whose current text is `text`.  Returns the pin which had
+    /// to be removed to make room, if any.  Pinning a path which is already pinned moves it to
+    /// the end.
+    pub fn pin(
+        &mut self,
+        path: CursorPath,
+        node: &'arena Node,
+        text: String,
+    ) -> Option<Pin<'arena, Node>> {
+        self.pins.retain(|pin| pin.path != path);
+        let removed = if self.pins.len() == MAX_PINS {
+            self.pins.pop_front()
+        } else {
+            None
+        };
+        self.pins.push_back(Pin {
+            path,
+            node,
+            text: Some(text),
+        });
+        removed
+    }
+
+    /// Unpins the pin at some index, returning `false` if there is no such pin
+    pub fn remove(&mut self, index: usize) -> bool {
+        self.pins.remove(index).is_some()
+    }
+
+    /// Unpins every node
+    pub fn clear(&mut self) {
+        self.pins.clear();
+    }
+
+    /// Returns `true` if no nodes are pinned
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Returns the number of pinned nodes
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Returns the pins, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Pin<'arena, Node>> {
+        self.pins.iter()
+    }
+
+    /// Updates the pins after the tree has been edited at `edit_path` so that its root is now
+    /// `root`.  Pins follow their nodes when the edit moves them, and an edit inside a pinned node
+    /// pins the edited copy instead.  Pins whose nodes are no longer in the tree are kept, in case
+    /// an undo brings the nodes back.
+    pub fn adjust(&mut self, root: &'arena Node, edit_path: &CursorPath) {
+        for pin in &mut self.pins {
+            if let Some(path) = cursor_path::follow(root, &pin.path, pin.node, edit_path) {
+                pin.path = path;
+            } else if edit_path.depth() > pin.path.depth()
+                && edit_path.starts_with(&pin.path)
+                && pin.path.is_valid(root)
+            {
+                pin.node = pin.path.cursor(root);
+            }
+        }
+    }
+
+    /// Recomputes the text of every pin from the tree under `root`, using a function which
+    /// returns the text of a node
+    pub fn update(&mut self, root: &'arena Node, mut text_of: impl FnMut(&'arena Node) -> String) {
+        for pin in &mut self.pins {
+            let is_in_tree =
+                pin.path.is_valid(root) && std::ptr::eq(pin.path.cursor(root), pin.node);
+            pin.text = Some(pin.node).filter(|_| is_in_tree).map(&mut text_of);
+        }
+    }
+}
+
+impl<'arena, Node: Ast<'arena>> Default for Pins<'arena, Node> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pins, MAX_PINS};
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editable_tree::cursor_path::CursorPath;
+
+    #[test]
+    fn pin() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True; MAX_PINS + 1]).add_to_arena(&arena);
+        let path = |i: usize| CursorPath::from_vec(vec![i]);
+        let mut pins = Pins::new();
+        for i in 0..MAX_PINS {
+            let node = root.children()[i];
+            assert!(pins.pin(path(i), node, i.to_string()).is_none());
+        }
+        // Re-pinning a path doesn't make room for another
+        assert!(pins
+            .pin(path(0), root.children()[0], "0".to_string())
+            .is_none());
+        let removed = pins
+            .pin(path(4), root.children()[4], "4".to_string())
+            .unwrap();
+        assert_eq!(removed.path, path(1));
+        assert_eq!(pins.len(), MAX_PINS);
+        // Only the nodes which are still in the tree have text
+        let new_root = arena.alloc(JSON::Array(root.children()[..4].to_vec()));
+        pins.update(new_root, |node| node.to_text(&JSONFormat::Compact));
+        let texts: Vec<_> = pins.iter().map(|pin| pin.text.clone()).collect();
+        let tru = Some("true".to_string());
+        assert_eq!(texts, [tru.clone(), tru.clone(), tru, None]);
+        assert!(pins.remove(3));
+        assert!(!pins.remove(3));
+        pins.clear();
+        assert!(pins.is_empty());
+    }
+
+    #[test]
+    fn adjusting() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True]),
+            TestJSON::Array(vec![TestJSON::False]),
+        ])
+        .add_to_arena(&arena);
+        let (first, second) = (root.children()[0], root.children()[1]);
+        let path = |indices: &[usize]| CursorPath::from_vec(indices.to_vec());
+        let paths = |pins: &Pins<JSON>| pins.iter().map(|pin| pin.path.clone()).collect::<Vec<_>>();
+        let mut pins = Pins::new();
+        pins.pin(path(&[0]), first, String::new());
+        pins.pin(path(&[1]), second, String::new());
+        // Inserting a node before the pinned nodes moves their pins along
+        let new_root = arena.alloc(JSON::Array(vec![arena.alloc(JSON::True), first, second]));
+        pins.adjust(new_root, &path(&[0]));
+        assert_eq!(paths(&pins), [path(&[1]), path(&[2])]);
+        // Editing inside a pinned node pins the edited copy
+        let new_second = arena.alloc(JSON::Array(vec![arena.alloc(JSON::True)]));
+        let new_root = arena.alloc(JSON::Array(vec![new_root.children()[0], first, new_second]));
+        pins.adjust(new_root, &path(&[2, 0]));
+        assert_eq!(paths(&pins), [path(&[1]), path(&[2])]);
+        assert!(std::ptr::eq(pins.iter().nth(1).unwrap().node, new_second));
+        // Pins of removed nodes stay where they were
+        let new_root = arena.alloc(JSON::Array(vec![new_second]));
+        pins.adjust(new_root, &path(&[0]));
+        assert_eq!(paths(&pins), [path(&[1]), path(&[0])]);
+    }
+}
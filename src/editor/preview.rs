@@ -0,0 +1,288 @@
+//! A live preview of the document, which is re-rendered into a side pane whenever the document is
+//! edited.  Rendering waits until the user has stopped editing for a moment, so that fast typing
+//! doesn't cause a render per key press.  External renderers run in a background thread (and are
+//! killed if they take too long), so that a slow renderer can't freeze the editor.
+
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long the document has to be left unchanged before the preview is re-rendered
+pub const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How long an external renderer can run before it's killed and the rendering fails
+pub const RENDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a rendering running in the background is checked to see if it has finished
+pub const RENDER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often a running external renderer is checked to see if it has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The ways that a document's text can be rendered into a preview
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Renderer {
+    /// Show the document's text as it is (e.g. for Markdown, which is readable as text)
+    Text,
+    /// Pipe the document's text into an external program (e.g. `["dot", "-Tascii"]`) and show
+    /// whatever it prints.  The first item is the program, and the rest are its arguments.
+    Command(Vec<String>),
+}
+
+impl Renderer {
+    /// Renders the text of a document, returning [`Err`] describing what went wrong if the
+    /// renderer failed.  An external program which runs for longer than `timeout` is killed.
+    pub fn render(&self, text: &str, timeout: Duration) -> Result<String, String> {
+        match self {
+            Renderer::Text => Ok(text.to_string()),
+            Renderer::Command(args) => {
+                let (program, args) = args
+                    .split_first()
+                    .ok_or_else(|| "No preview command given".to_string())?;
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("{}: {}", program, e))?;
+                // Write the input from another thread, so that a program which prints lots of
+                // output before it's finished reading can't deadlock us
+                let mut stdin = child.stdin.take().expect("stdin is piped");
+                let input = text.to_string();
+                let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+                // The output is read by other threads too, so that the program can't fill its
+                // pipes and stop whilst we wait for it to exit
+                let stdout = read_to_end(child.stdout.take().expect("stdout is piped"));
+                let stderr = read_to_end(child.stderr.take().expect("stderr is piped"));
+                let status = match wait_with_timeout(&mut child, timeout) {
+                    Ok(Some(status)) => status,
+                    Ok(None) => {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(format!(
+                            "{}: Timed out after {}s",
+                            program,
+                            timeout.as_secs_f64()
+                        ));
+                    }
+                    Err(e) => return Err(format!("{}: {}", program, e)),
+                };
+                // The program is allowed to stop reading early, so write errors are ignored
+                let _ = writer.join();
+                let output = if status.success() { stdout } else { stderr };
+                let output = output.join().unwrap_or_default();
+                let output = String::from_utf8_lossy(&output).into_owned();
+                if status.success() {
+                    Ok(output)
+                } else {
+                    Err(output)
+                }
+            }
+        }
+    }
+}
+
+/// Reads everything from `reader` in a new thread, returning the thread's handle
+fn read_to_end(mut reader: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = reader.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// Waits for `child` to exit, returning its exit status, or [`None`] if it's still running after
+/// `timeout`
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
+/// The state of the preview pane
+#[derive(Debug)]
+pub struct Preview {
+    renderer: Renderer,
+    /// The time of the latest edit which isn't in the preview yet, or [`None`] if the preview is
+    /// up to date
+    dirty_since: Option<Instant>,
+    /// Where the result of the rendering running in the background will be sent, or [`None`] if
+    /// no rendering is running
+    rendering: Option<Receiver<Result<String, String>>>,
+    /// The lines of the latest rendering
+    lines: Vec<String>,
+    /// `true` if the latest rendering failed, in which case `lines` contains the error
+    is_error: bool,
+}
+
+impl Preview {
+    /// Creates a `Preview` using a given [`Renderer`].  It will be rendered as soon as it is
+    /// updated.
+    pub fn new(renderer: Renderer) -> Preview {
+        Preview {
+            renderer,
+            dirty_since: Some(Instant::now() - PREVIEW_DEBOUNCE),
+            rendering: None,
+            lines: Vec::new(),
+            is_error: false,
+        }
+    }
+
+    /// Records that the document was edited at time `now`, so the preview needs re-rendering
+    pub fn mark_dirty(&mut self, now: Instant) {
+        self.dirty_since = Some(now);
+    }
+
+    /// Returns how long to wait before the preview should be updated, or [`None`] if it's up to
+    /// date.  Whilst a rendering is running in the background, this is how long to wait before
+    /// checking whether it has finished.
+    pub fn time_until_due(&self, now: Instant) -> Option<Duration> {
+        if self.rendering.is_some() {
+            return Some(RENDER_POLL_INTERVAL);
+        }
+        self.time_until_render(now)
+    }
+
+    /// Returns how long to wait before the preview should be re-rendered, or [`None`] if it's up
+    /// to date
+    fn time_until_render(&self, now: Instant) -> Option<Duration> {
+        self.dirty_since
+            .map(|t| (t + PREVIEW_DEBOUNCE).saturating_duration_since(now))
+    }
+
+    /// Shows the result of any rendering which has finished in the background, then re-renders
+    /// the preview from the document's text if the document has been left unchanged for long
+    /// enough (and no rendering is still running).  `text` is only called if the preview is
+    /// re-rendered.  [`Text`](Renderer::Text) is rendered straight away, but external programs
+    /// are run in the background and their output is shown by a later update.  Returns `true` if
+    /// the preview changed.
+    pub fn update(&mut self, now: Instant, text: impl FnOnce() -> String) -> bool {
+        let mut is_changed = false;
+        if let Some(receiver) = &self.rendering {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    self.show(result);
+                    is_changed = true;
+                }
+                Err(TryRecvError::Empty) => return false,
+                // The rendering thread panicked, so the next edit is rendered instead
+                Err(TryRecvError::Disconnected) => {}
+            }
+            self.rendering = None;
+        }
+        if self.time_until_render(now) != Some(Duration::from_secs(0)) {
+            return is_changed;
+        }
+        self.dirty_since = None;
+        let text = text();
+        if let Renderer::Text = self.renderer {
+            self.show(Ok(text));
+            return true;
+        }
+        let (sender, receiver) = channel();
+        let renderer = self.renderer.clone();
+        thread::spawn(move || {
+            // If the preview has been closed, then nobody wants the rendering
+            let _ = sender.send(renderer.render(&text, RENDER_TIMEOUT));
+        });
+        self.rendering = Some(receiver);
+        is_changed
+    }
+
+    /// Replaces the preview with the result of a rendering
+    fn show(&mut self, result: Result<String, String>) {
+        let (rendered, is_error) = match result {
+            Ok(rendered) => (rendered, false),
+            Err(error) => (error, true),
+        };
+        self.lines = rendered.lines().map(str::to_string).collect();
+        self.is_error = is_error;
+    }
+
+    /// Returns the lines of the latest rendering
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Returns `true` if the latest rendering failed, in which case [`Preview::lines`] describes
+    /// the error
+    pub fn is_error(&self) -> bool {
+        self.is_error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Preview, Renderer, PREVIEW_DEBOUNCE, RENDER_TIMEOUT};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn debounce() {
+        let mut preview = Preview::new(Renderer::Text);
+        let now = Instant::now();
+        // New previews are rendered straight away
+        assert!(preview.update(now, || "a\nb".to_string()));
+        assert_eq!(preview.lines(), ["a", "b"]);
+        assert_eq!(preview.time_until_due(now), None);
+        // Edits are only rendered once the document has been left alone for long enough
+        preview.mark_dirty(now);
+        preview.mark_dirty(now + Duration::from_millis(100));
+        assert!(!preview.update(now + PREVIEW_DEBOUNCE, || unreachable!()));
+        assert_eq!(
+            preview.time_until_due(now + PREVIEW_DEBOUNCE),
+            Some(Duration::from_millis(100))
+        );
+        assert!(preview.update(now + PREVIEW_DEBOUNCE * 2, || "c".to_string()));
+        assert_eq!(preview.lines(), ["c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command() {
+        let renderer =
+            Renderer::Command(vec!["tr".to_string(), "a-z".to_string(), "A-Z".to_string()]);
+        assert_eq!(
+            renderer.render("[true]", RENDER_TIMEOUT),
+            Ok("[TRUE]".to_string())
+        );
+        let renderer = Renderer::Command(vec!["sapling-no-such-program".to_string()]);
+        assert!(renderer.render("", RENDER_TIMEOUT).is_err());
+        assert!(Renderer::Command(vec![])
+            .render("", RENDER_TIMEOUT)
+            .is_err());
+        // Programs which take too long are killed
+        let renderer = Renderer::Command(vec!["sleep".to_string(), "10".to_string()]);
+        let start = Instant::now();
+        assert!(renderer.render("", Duration::from_millis(100)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn background() {
+        let renderer =
+            Renderer::Command(vec!["tr".to_string(), "a-z".to_string(), "A-Z".to_string()]);
+        let mut preview = Preview::new(renderer);
+        let start = Instant::now();
+        // The program is started by the first update, and its output is shown by a later one
+        assert!(!preview.update(start, || "[true]".to_string()));
+        while !preview.update(Instant::now(), || unreachable!()) {
+            assert!(start.elapsed() < RENDER_TIMEOUT, "rendering never finished");
+            assert!(preview.time_until_due(Instant::now()).is_some());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(preview.lines(), ["[TRUE]"]);
+        assert!(!preview.is_error());
+        assert_eq!(preview.time_until_due(Instant::now()), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Progress reporting for long-running operations (like parsing large files), which can be running
+//! in background threads whilst the editor stays responsive.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the spinner should be animated whilst there are tasks running
+pub const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The frames of the animated spinner shown for tasks that don't know how much work they have to
+/// do
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// A unique identifier for a task which is reporting progress
+pub type TaskId = usize;
+
+/// A single change in the progress of a task, sent from the task to the [`ProgressTracker`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProgressEvent {
+    /// A new task has started.  `total` is the number of units of work that the task will do, or
+    /// [`None`] if that isn't known.
+    Start {
+        id: TaskId,
+        label: String,
+        total: Option<usize>,
+    },
+    /// A task has finished `done` units of work
+    Advance { id: TaskId, done: usize },
+    /// A task has finished all of its work
+    Finish { id: TaskId },
+}
+
+/// A cloneable handle which background tasks use to report their progress to a
+/// [`ProgressTracker`]
+#[derive(Debug, Clone)]
+pub struct ProgressSender {
+    sender: Sender<ProgressEvent>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl ProgressSender {
+    /// Reports that a new task has started, returning a [`TaskHandle`] through which the task can
+    /// report its progress.  The task is finished when the handle is dropped.
+    pub fn start(&self, label: &str, total: Option<usize>) -> TaskHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // If the tracker has gone away then nobody is interested in the progress, so errors from
+        // sending are ignored
+        let _ = self.sender.send(ProgressEvent::Start {
+            id,
+            label: label.to_string(),
+            total,
+        });
+        TaskHandle {
+            id,
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+/// A handle to a running task, used to report how much of its work has been done.  The task is
+/// reported as finished when this is dropped.
+#[derive(Debug)]
+pub struct TaskHandle {
+    id: TaskId,
+    sender: Sender<ProgressEvent>,
+}
+
+impl TaskHandle {
+    /// Reports that `done` units of work have been finished
+    pub fn advance(&self, done: usize) {
+        let _ = self
+            .sender
+            .send(ProgressEvent::Advance { id: self.id, done });
+    }
+}
+
+impl Drop for TaskHandle {
+    fn drop(&mut self) {
+        let _ = self.sender.send(ProgressEvent::Finish { id: self.id });
+    }
+}
+
+/// The progress of a single running task
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Task {
+    id: TaskId,
+    label: String,
+    done: usize,
+    total: Option<usize>,
+}
+
+/// Receives [`ProgressEvent`]s from running tasks, and summarises them for the status bar
+#[derive(Debug)]
+pub struct ProgressTracker {
+    receiver: Receiver<ProgressEvent>,
+    sender: ProgressSender,
+    tasks: Vec<Task>,
+    spinner_frame: usize,
+}
+
+impl ProgressTracker {
+    /// Creates a `ProgressTracker` with no running tasks
+    pub fn new() -> ProgressTracker {
+        let (sender, receiver) = channel();
+        ProgressTracker {
+            receiver,
+            sender: ProgressSender {
+                sender,
+                next_id: Arc::new(AtomicUsize::new(0)),
+            },
+            tasks: Vec::new(),
+            spinner_frame: 0,
+        }
+    }
+
+    /// Returns a [`ProgressSender`] which reports to this tracker
+    pub fn sender(&self) -> ProgressSender {
+        self.sender.clone()
+    }
+
+    /// Processes all the [`ProgressEvent`]s that have been sent since the last update, and
+    /// advances the spinner animation.
+    pub fn update(&mut self) {
+        for event in self.receiver.try_iter() {
+            match event {
+                ProgressEvent::Start { id, label, total } => self.tasks.push(Task {
+                    id,
+                    label,
+                    done: 0,
+                    total,
+                }),
+                ProgressEvent::Advance { id, done } => {
+                    if let Some(task) = self.tasks.iter_mut().find(|t| t.id == id) {
+                        task.done = done;
+                    }
+                }
+                ProgressEvent::Finish { id } => self.tasks.retain(|t| t.id != id),
+            }
+        }
+        self.spinner_frame = (self.spinner_frame + 1) % SPINNER_FRAMES.len();
+    }
+
+    /// Returns `true` if any tasks are running
+    pub fn is_busy(&self) -> bool {
+        !self.tasks.is_empty()
+    }
+
+    /// Returns a short description of the progress of the running tasks, or [`None`] if no tasks
+    /// are running.  For example, `"| Parsing 42%"` or `"/ Collecting garbage (+1 more)"`.
+    pub fn status_text(&self) -> Option<String> {
+        let task = self.tasks.first()?;
+        let mut text = format!("{} {}", SPINNER_FRAMES[self.spinner_frame], task.label);
+        if let Some(total) = task.total {
+            // A task with no work to do is already finished
+            let percentage = (task.done.min(total) * 100)
+                .checked_div(total)
+                .unwrap_or(100);
+            text.push_str(&format!(" {}%", percentage));
+        }
+        if self.tasks.len() > 1 {
+            text.push_str(&format!(" (+{} more)", self.tasks.len() - 1));
+        }
+        Some(text)
+    }
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressTracker;
+
+    #[test]
+    fn tasks() {
+        let mut tracker = ProgressTracker::new();
+        assert!(!tracker.is_busy());
+        assert_eq!(tracker.status_text(), None);
+
+        let sender = tracker.sender();
+        let parse = sender.start("Parsing", Some(200));
+        parse.advance(50);
+        tracker.update();
+        assert!(tracker.is_busy());
+        assert_eq!(tracker.status_text().unwrap()[1..], *" Parsing 25%");
+        // Tasks can be run from other threads, and tasks of unknown length have no percentage
+        std::thread::spawn(move || {
+            let _gc = sender.start("Collecting garbage", None);
+            // Move the parse task into this thread so that it's finished when it's dropped
+            drop(parse);
+        })
+        .join()
+        .unwrap();
+        tracker.update();
+        assert!(!tracker.is_busy());
+
+        let sender = tracker.sender();
+        let _gc = sender.start("Collecting garbage", None);
+        let _format = sender.start("Formatting", Some(0));
+        tracker.update();
+        assert_eq!(
+            tracker.status_text().unwrap()[1..],
+            *" Collecting garbage (+1 more)"
+        );
+    }
+}
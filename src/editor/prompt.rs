@@ -0,0 +1,182 @@
+//! Yes/no/all prompts, which ask the user to confirm destructive operations before they happen.
+
+use tuikit::key::Key;
+
+/// The operations which can ask the user for confirmation, which are mostly destructive
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Operation {
+    /// Replacing a subtree which contains lots of nodes
+    ReplaceLargeSubtree,
+    /// Writing over a file which already exists
+    OverwriteFile,
+    /// Throwing away changes which haven't been saved
+    DiscardChanges,
+    /// Making an edit after undoing, which throws away the changes that could have been redone
+    DiscardRedo,
+    /// Pasting (or reading from a file) a subtree which contains lots of nodes
+    PasteLargeSubtree,
+    /// Folding the parts of a newly opened tree which go over the
+    /// [limits on its shape](super::limits::TreeLimits)
+    FoldOversized,
+}
+
+impl Operation {
+    /// Returns `true` if what this operation throws away can be kept as a snapshot
+    pub fn can_snapshot(self) -> bool {
+        self == Operation::DiscardRedo
+    }
+}
+
+/// The configuration of which [`Operation`]s should ask for confirmation.  Power users can turn
+/// off the prompts for individual operations.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConfirmConfig {
+    /// Whether replacing a subtree of at least `large_subtree_size` nodes needs confirming
+    pub replace_large_subtree: bool,
+    /// Whether overwriting an existing file needs confirming
+    pub overwrite_file: bool,
+    /// Whether discarding unsaved changes needs confirming
+    pub discard_changes: bool,
+    /// Whether throwing away redoable changes needs confirming
+    pub discard_redo: bool,
+    /// Whether pasting a subtree of at least `large_paste_size` nodes needs confirming
+    pub paste_large_subtree: bool,
+    /// Whether folding the oversized parts of a newly opened tree needs confirming.  If not, they
+    /// are folded as soon as the tree is opened.
+    pub fold_oversized: bool,
+    /// How many nodes a subtree has to contain before replacing it needs confirmation
+    pub large_subtree_size: usize,
+    /// How many nodes a subtree has to contain before pasting it needs confirmation.  Pasting
+    /// enormous subtrees by accident can make the editor unresponsive for a long time.
+    pub large_paste_size: usize,
+}
+
+impl ConfirmConfig {
+    /// Returns `true` if a given [`Operation`] should ask for confirmation
+    pub fn is_enabled(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::ReplaceLargeSubtree => self.replace_large_subtree,
+            Operation::OverwriteFile => self.overwrite_file,
+            Operation::DiscardChanges => self.discard_changes,
+            Operation::DiscardRedo => self.discard_redo,
+            Operation::PasteLargeSubtree => self.paste_large_subtree,
+            Operation::FoldOversized => self.fold_oversized,
+        }
+    }
+
+    /// Stop a given [`Operation`] from asking for confirmation
+    pub fn disable(&mut self, operation: Operation) {
+        match operation {
+            Operation::ReplaceLargeSubtree => self.replace_large_subtree = false,
+            Operation::OverwriteFile => self.overwrite_file = false,
+            Operation::DiscardChanges => self.discard_changes = false,
+            Operation::DiscardRedo => self.discard_redo = false,
+            Operation::PasteLargeSubtree => self.paste_large_subtree = false,
+            Operation::FoldOversized => self.fold_oversized = false,
+        }
+    }
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig {
+            replace_large_subtree: true,
+            overwrite_file: true,
+            discard_changes: true,
+            discard_redo: true,
+            paste_large_subtree: true,
+            fold_oversized: true,
+            large_subtree_size: 20,
+            large_paste_size: 10_000,
+        }
+    }
+}
+
+/// The possible answers to a [`Prompt`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Answer {
+    /// Go ahead with the operation
+    Yes,
+    /// Cancel the operation
+    No,
+    /// Go ahead with the operation, and don't ask again for this kind of operation
+    All,
+    /// Keep a snapshot of what would be thrown away, and then go ahead with the operation.  Only
+    /// some operations can be snapshotted (see [`Operation::can_snapshot`]).
+    Snapshot,
+}
+
+impl Answer {
+    /// Converts a key press into an `Answer`, returning [`None`] if the key doesn't answer the
+    /// prompt.  Escape always cancels the operation.
+    pub fn from_key(key: Key) -> Option<Answer> {
+        match key {
+            Key::Char('y') | Key::Char('Y') => Some(Answer::Yes),
+            Key::Char('n') | Key::Char('N') | Key::ESC => Some(Answer::No),
+            Key::Char('a') | Key::Char('A') => Some(Answer::All),
+            Key::Char('s') | Key::Char('S') => Some(Answer::Snapshot),
+            _ => None,
+        }
+    }
+}
+
+/// A question which is waiting for the user to answer it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Prompt {
+    /// The operation which is waiting to be confirmed
+    pub operation: Operation,
+    /// The question to display to the user
+    pub question: String,
+}
+
+impl Prompt {
+    /// Creates a new `Prompt` asking a given question about an [`Operation`]
+    pub fn new(operation: Operation, question: String) -> Prompt {
+        Prompt {
+            operation,
+            question,
+        }
+    }
+
+    /// Returns the text that should be displayed to the user, including the possible answers
+    pub fn text(&self) -> String {
+        if self.operation.can_snapshot() {
+            format!("{} [y]es/[n]o/[a]ll/[s]napshot", self.question)
+        } else {
+            format!("{} [y]es/[n]o/[a]ll", self.question)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Answer, ConfirmConfig, Operation, Prompt};
+    use tuikit::key::Key;
+
+    #[test]
+    fn disable() {
+        let mut config = ConfirmConfig::default();
+        assert!(config.is_enabled(Operation::OverwriteFile));
+        config.disable(Operation::OverwriteFile);
+        assert!(!config.is_enabled(Operation::OverwriteFile));
+        assert!(config.is_enabled(Operation::ReplaceLargeSubtree));
+        assert!(config.is_enabled(Operation::DiscardChanges));
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(Answer::from_key(Key::Char('y')), Some(Answer::Yes));
+        assert_eq!(Answer::from_key(Key::ESC), Some(Answer::No));
+        assert_eq!(Answer::from_key(Key::Char('A')), Some(Answer::All));
+        assert_eq!(Answer::from_key(Key::Char('s')), Some(Answer::Snapshot));
+        assert_eq!(Answer::from_key(Key::Char('x')), None);
+    }
+
+    #[test]
+    fn text() {
+        let prompt = Prompt::new(Operation::DiscardRedo, "Edit?".to_string());
+        assert_eq!(prompt.text(), "Edit? [y]es/[n]o/[a]ll/[s]napshot");
+        let prompt = Prompt::new(Operation::OverwriteFile, "Save?".to_string());
+        assert_eq!(prompt.text(), "Save? [y]es/[n]o/[a]ll");
+    }
+}
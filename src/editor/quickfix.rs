@@ -0,0 +1,114 @@
+//! The quickfix list, which holds the nodes found by the latest query so that the user can step
+//! through them one at a time.  Entries can have notes saying why they're in the list (like the
+//! parse errors of a file which didn't fully parse), which are shown when they're stepped to.
+
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+
+/// A list of paths to nodes, one of which is the current entry
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Quickfix {
+    paths: Vec<CursorPath>,
+    /// The note of each entry, if it has one
+    notes: Vec<Option<Message>>,
+    /// The index of the current entry in `paths`
+    index: usize,
+}
+
+impl Quickfix {
+    /// Creates a list of paths, where the first path is the current entry
+    pub fn new(paths: Vec<CursorPath>) -> Quickfix {
+        let notes = vec![None; paths.len()];
+        Quickfix {
+            paths,
+            notes,
+            index: 0,
+        }
+    }
+
+    /// Creates a list of paths which each have a note, where the first path is the current entry
+    pub fn with_notes(entries: Vec<(CursorPath, Message)>) -> Quickfix {
+        let (paths, notes) = entries
+            .into_iter()
+            .map(|(path, note)| (path, Some(note)))
+            .unzip();
+        Quickfix {
+            paths,
+            notes,
+            index: 0,
+        }
+    }
+
+    /// Returns the number of entries in the list
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns `true` if the list has no entries
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Returns the paths of every entry, in order
+    pub fn paths(&self) -> &[CursorPath] {
+        &self.paths
+    }
+
+    /// Returns the 1-based position of the current entry, for showing to the user
+    pub fn position(&self) -> usize {
+        self.index + 1
+    }
+
+    /// Returns the path of the current entry, or [`None`] if the list is empty
+    pub fn current(&self) -> Option<&CursorPath> {
+        self.paths.get(self.index)
+    }
+
+    /// Returns the note of the current entry, if it has one
+    pub fn note(&self) -> Option<&Message> {
+        self.notes.get(self.index)?.as_ref()
+    }
+
+    /// Moves to the next entry (or the previous one if `forward` is `false`), wrapping round at
+    /// either end of the list, and returns the path of the new current entry
+    pub fn step(&mut self, forward: bool) -> Option<&CursorPath> {
+        let len = self.paths.len();
+        if len == 0 {
+            return None;
+        }
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quickfix;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::messages::Message;
+
+    #[test]
+    fn step() {
+        let path = |i| CursorPath::from_vec(vec![i]);
+        let mut quickfix = Quickfix::new(vec![path(0), path(1), path(2)]);
+        assert_eq!(quickfix.current(), Some(&path(0)));
+        assert_eq!(quickfix.step(false), Some(&path(2)));
+        assert_eq!(quickfix.position(), 3);
+        assert_eq!(quickfix.step(true), Some(&path(0)));
+        assert_eq!(quickfix.step(true), Some(&path(1)));
+        let mut empty = Quickfix::new(vec![]);
+        assert_eq!(empty.current(), None);
+        assert_eq!(empty.step(true), None);
+        assert_eq!(quickfix.note(), None);
+
+        let note = |line| Message::ParseError(line, "expected a value".to_string());
+        let mut quickfix = Quickfix::with_notes(vec![(path(0), note(1)), (path(4), note(3))]);
+        assert_eq!(quickfix.note(), Some(&note(1)));
+        assert_eq!(quickfix.step(true), Some(&path(4)));
+        assert_eq!(quickfix.note(), Some(&note(3)));
+    }
+}
@@ -0,0 +1,56 @@
+//! Named registers (`"a` to `"z`, like Vim's) which each hold a copied subtree, so that several
+//! fragments can be held at once.  Unlike the clipboard, these only last as long as the editor.
+
+use super::clipboard::ClipboardContents;
+use std::collections::HashMap;
+
+/// The char which is typed before a register's name to use that register
+pub const REGISTER_PREFIX: char = '"';
+
+/// The subtrees which the user has copied into named registers
+#[derive(Debug, Clone, Default)]
+pub struct Registers {
+    registers: HashMap<char, ClipboardContents>,
+}
+
+impl Registers {
+    /// Creates a set of empty registers
+    pub fn new() -> Registers {
+        Registers::default()
+    }
+
+    /// Returns `true` if `name` is the name of a register
+    pub fn is_name(name: char) -> bool {
+        name.is_ascii_lowercase()
+    }
+
+    /// Returns the contents of a register, or [`None`] if the register is empty
+    pub fn get(&self, register: char) -> Option<&ClipboardContents> {
+        self.registers.get(&register)
+    }
+
+    /// Stores a copied subtree in a register, replacing whatever was there
+    pub fn set(&mut self, register: char, contents: ClipboardContents) {
+        debug_assert!(Registers::is_name(register));
+        self.registers.insert(register, contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registers;
+    use crate::editor::clipboard::ClipboardContents;
+
+    #[test]
+    fn registers() {
+        let mut registers = Registers::new();
+        assert!(Registers::is_name('a') && Registers::is_name('z'));
+        assert!(!Registers::is_name('A') && !Registers::is_name('"') && !Registers::is_name('1'));
+        assert_eq!(registers.get('a'), None);
+        registers.set('a', ClipboardContents::from_text("true".to_string()));
+        registers.set('b', ClipboardContents::from_text("false".to_string()));
+        registers.set('a', ClipboardContents::from_text("[]".to_string()));
+        assert_eq!(registers.get('a').map(|c| c.text.as_str()), Some("[]"));
+        assert_eq!(registers.get('b').map(|c| c.text.as_str()), Some("false"));
+    }
+}
@@ -0,0 +1,629 @@
+//! Laying out the display tokens of a tree, which decides where every piece of text goes and which
+//! attributes it's drawn with.  This doesn't touch the terminal, so that the layout can be tested
+//! on its own; [`Editor::render_tree`](super::Editor) only has to paint the spans which are inside
+//! the visible region.
+
+use super::cursor_style::CursorStyle;
+use super::theme::Theme;
+use crate::ast::display_token::{match_delimiters, DelimiterMatch, DisplayToken, INDENT_WIDTH};
+use crate::ast::pattern::{self, Pattern};
+use crate::ast::Ast;
+use std::collections::HashSet;
+use tuikit::prelude::{Attr, Color, Effect};
+
+/// The colours which pairs of delimiters are drawn in, by their depth
+const COLOURS: [Color; 14] = [
+    Color::MAGENTA,
+    Color::RED,
+    Color::YELLOW,
+    Color::GREEN,
+    Color::CYAN,
+    Color::BLUE,
+    Color::WHITE,
+    Color::LIGHT_RED,
+    Color::LIGHT_BLUE,
+    Color::LIGHT_CYAN,
+    Color::LIGHT_GREEN,
+    Color::LIGHT_YELLOW,
+    Color::LIGHT_MAGENTA,
+    Color::LIGHT_WHITE,
+];
+
+/// The attributes of nodes which are highlighted for the user to review
+pub const REVIEW_ATTR: Attr = Attr {
+    fg: Color::BLACK,
+    bg: Color::LIGHT_RED,
+    effect: Effect::empty(),
+};
+
+/// The attributes of the matches of the last search
+pub const SEARCH_ATTR: Attr = Attr {
+    fg: Color::BLACK,
+    bg: Color::YELLOW,
+    effect: Effect::empty(),
+};
+
+/// The attributes of text which couldn't be parsed when the file was opened (see
+/// [`Ast::is_error`])
+pub const ERROR_ATTR: Attr = Attr {
+    fg: Color::LIGHT_RED,
+    bg: Color::Default,
+    effect: Effect::UNDERLINE,
+};
+
+/// The attributes of the markers drawn around the cursor by [`CursorStyle::Markers`]
+const MARKER_ATTR: Attr = Attr {
+    fg: Color::WHITE,
+    bg: Color::Default,
+    effect: Effect::BOLD,
+};
+
+/// The attributes of the line numbers in the gutter
+const LINE_NUMBER_ATTR: Attr = Attr {
+    fg: Color::LIGHT_BLACK,
+    bg: Color::Default,
+    effect: Effect::empty(),
+};
+
+/// The attributes of the number of the line which the cursor starts on
+const CURSOR_LINE_NUMBER_ATTR: Attr = Attr {
+    fg: Color::WHITE,
+    bg: Color::Default,
+    effect: Effect::BOLD,
+};
+
+/// The fewest columns that line numbers are padded to, so that the gutter doesn't change width
+/// whilst small trees are edited
+const MIN_NUMBER_WIDTH: usize = 3;
+
+/// Everything apart from the tokens themselves which changes how a tree is drawn
+pub struct Highlights<'h, 'arena, Node: Ast<'arena>> {
+    /// The node under the cursor
+    pub cursor: &'arena Node,
+    /// How the node under the cursor is made to stand out
+    pub cursor_style: &'h CursorStyle,
+    /// The nodes which are highlighted for the user to review
+    pub review_nodes: &'h [&'arena Node],
+    /// The pattern of the current search, if any, whose matches are highlighted
+    pub search_pattern: Option<&'h Pattern>,
+    /// The format style that the tokens were generated with, which search patterns are matched
+    /// with
+    pub format_style: &'h Node::FormatStyle,
+    /// The colours of the text, by its syntax category
+    pub theme: &'h Theme,
+}
+
+/// A piece of text on a single line, placed relative to the top-left corner of the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// The line of the rendered tree (not of the screen) that the text is on
+    pub row: usize,
+    /// The column that the text starts at
+    pub col: usize,
+    pub text: String,
+    pub attr: Attr,
+}
+
+impl Span {
+    /// Returns the column and text of the part of this span which is inside the columns
+    /// `first_col..first_col + width`, with the column relative to `first_col`.  Returns [`None`]
+    /// if none of the span is inside them.
+    pub fn clip(&self, first_col: usize, width: usize) -> Option<(usize, String)> {
+        let skip = first_col.saturating_sub(self.col);
+        let col = self.col.saturating_sub(first_col);
+        let text: String = self
+            .text
+            .chars()
+            .skip(skip)
+            .take(width.saturating_sub(col))
+            .collect();
+        Some((col, text)).filter(|(_, text)| !text.is_empty())
+    }
+}
+
+/// A whole tree, laid out ready to be drawn
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Layout {
+    /// The spans of text, in the order of the tokens which they came from
+    pub spans: Vec<Span>,
+    /// The number of lines the tree takes up
+    pub height: usize,
+    /// The line and column where the text of the cursor's subtree starts, or [`None`] if none of
+    /// its text is shown
+    pub cursor: Option<(usize, usize)>,
+}
+
+impl Layout {
+    /// Returns the spans which are on a given line
+    pub fn row(&self, row: usize) -> impl Iterator<Item = &Span> {
+        self.spans.iter().filter(move |span| span.row == row)
+    }
+}
+
+/// Lays out the display tokens of a tree (as generated by [`Ast::display_tokens`]).  Each line
+/// starts at the indentation level of the token which ended the previous line, except for the
+/// later lines of multi-line text, which start at column 0.
+pub fn layout<'arena, Node: Ast<'arena>>(
+    tokens: &[(&'arena Node, DisplayToken)],
+    highlights: &Highlights<'_, 'arena, Node>,
+) -> Layout {
+    let mut placer = Placer::default();
+    // The delimiters are paired up before anything is placed, so that they can be coloured by
+    // their depth
+    let delimiters = match_delimiters(tokens);
+    let highlighted_pair = delimiters_nearest_cursor(tokens, &delimiters, highlights.cursor);
+    // Find the first and last visible tokens of the cursor, so that they can be surrounded by
+    // markers if the cursor style uses them
+    let cursor_markers = highlights.cursor_style.markers();
+    let is_visible_cursor_token = |(node, tok): &(&Node, DisplayToken)| {
+        std::ptr::eq(*node, highlights.cursor)
+            && matches!(
+                tok,
+                DisplayToken::Text(_)
+                    | DisplayToken::Categorised(..)
+                    | DisplayToken::OpenDelim(_)
+                    | DisplayToken::CloseDelim(_)
+            )
+    };
+    let first_cursor_token = tokens.iter().position(is_visible_cursor_token);
+    let last_cursor_token = tokens.iter().rposition(is_visible_cursor_token);
+    let is_for_review = |node: &Node| {
+        highlights
+            .review_nodes
+            .iter()
+            .any(|n| std::ptr::eq(*n, node))
+    };
+    // The cursor's subtree generates a contiguous run of tokens, so the cursor starts at the first
+    // text of any node in the subtree
+    let mut subtree_nodes = HashSet::new();
+    let mut nodes_to_visit = vec![highlights.cursor];
+    while let Some(node) = nodes_to_visit.pop() {
+        subtree_nodes.insert(node as *const Node);
+        nodes_to_visit.extend(node.children().iter().copied());
+    }
+    let mut cursor_start = None;
+
+    for (i, (node, tok)) in tokens.iter().enumerate() {
+        if let Some((open_marker, _)) = cursor_markers {
+            if Some(i) == first_cursor_token {
+                placer.place(open_marker, MARKER_ATTR);
+            }
+        }
+        let is_text = matches!(
+            tok,
+            DisplayToken::Text(_)
+                | DisplayToken::Categorised(..)
+                | DisplayToken::OpenDelim(_)
+                | DisplayToken::CloseDelim(_)
+        );
+        let is_in_subtree = subtree_nodes.contains(&(*node as *const Node));
+        if cursor_start.is_none() && is_text && is_in_subtree {
+            cursor_start = Some((placer.row, placer.col));
+        }
+        // The rest of the cursor's subtree is drawn on top of the theme's subtree attributes, so
+        // that it's clear what an edit of the cursor will affect
+        let in_subtree = |attr: Attr| {
+            if is_in_subtree {
+                attr.extend(highlights.theme.subtree)
+            } else {
+                attr
+            }
+        };
+        // Text patterns highlight just the text which matched them, but other patterns highlight
+        // all of every node which matches them
+        let is_search_match = match highlights.search_pattern {
+            Some(Pattern::Text(text)) => pattern::token_contains(tok, text),
+            Some(pattern) => node.matches(pattern, highlights.format_style),
+            None => false,
+        };
+        match tok {
+            DisplayToken::OpenDelim(s) | DisplayToken::CloseDelim(s) => {
+                // Colour delimiters by their depth, so that pairs can be recognised, falling
+                // back on white for any unmatched delimiters
+                let col = match delimiters[i] {
+                    Some(m) => COLOURS[m.depth % COLOURS.len()],
+                    None => Color::WHITE,
+                };
+                let mut attr = if std::ptr::eq(*node, highlights.cursor) {
+                    highlights.cursor_style.attr(col)
+                } else if is_for_review(node) {
+                    REVIEW_ATTR
+                } else if is_search_match {
+                    SEARCH_ATTR
+                } else {
+                    in_subtree(Attr::default().fg(col))
+                };
+                // Make the pair nearest to the cursor stand out
+                if let Some((open, close)) = highlighted_pair {
+                    if i == open || i == close {
+                        attr = attr.effect(Effect::BOLD | Effect::UNDERLINE);
+                    }
+                }
+                placer.place(s, attr);
+            }
+            DisplayToken::Text(s) | DisplayToken::Categorised(s, _) => {
+                // The theme decides on the colour, from the category of the text
+                let category = match tok {
+                    DisplayToken::Categorised(_, category) => Some(*category),
+                    _ => None,
+                };
+                let theme_attr = highlights.theme.attr(category);
+                let attr = if std::ptr::eq(*node, highlights.cursor) {
+                    highlights.cursor_style.attr(theme_attr.fg)
+                } else if is_for_review(node) {
+                    REVIEW_ATTR
+                } else if node.is_error() {
+                    ERROR_ATTR
+                } else if is_search_match {
+                    SEARCH_ATTR
+                } else {
+                    in_subtree(theme_attr)
+                };
+                placer.place(s, attr);
+            }
+            // Whitespace inside the subtree is drawn, so that the subtree's background (if it
+            // has one) doesn't have gaps in it
+            DisplayToken::Whitespace(n) if is_in_subtree => {
+                placer.place(&" ".repeat(*n), in_subtree(Attr::default()))
+            }
+            DisplayToken::Whitespace(n) => placer.col += n,
+            DisplayToken::Newline => {
+                placer.row += 1;
+                placer.col = placer.indentation;
+            }
+            DisplayToken::Indent => placer.indentation += INDENT_WIDTH,
+            DisplayToken::Dedent => {
+                placer.indentation = placer.indentation.saturating_sub(INDENT_WIDTH)
+            }
+        }
+        if let Some((_, close_marker)) = cursor_markers {
+            if Some(i) == last_cursor_token {
+                placer.place(close_marker, MARKER_ATTR);
+            }
+        }
+    }
+    Layout {
+        spans: placer.spans,
+        height: placer.row + 1,
+        cursor: cursor_start,
+    }
+}
+
+/// Returns the number of columns taken by the gutter of a tree which is `num_lines` lines tall,
+/// including the column between the gutter and the tree
+pub fn gutter_width(num_lines: usize) -> usize {
+    num_lines.to_string().len().max(MIN_NUMBER_WIDTH) + 1
+}
+
+/// Returns the text and attributes of the gutter next to a line of a laid out tree, which is
+/// `width` columns wide (as returned by [`gutter_width`])
+pub fn gutter(layout: &Layout, row: usize, width: usize) -> (String, Attr) {
+    let attr = match layout.cursor {
+        Some((cursor_row, _)) if cursor_row == row => CURSOR_LINE_NUMBER_ATTR,
+        _ => LINE_NUMBER_ATTR,
+    };
+    (format!("{:>w$} ", row + 1, w = width - 1), attr)
+}
+
+/// Keeps track of where the next piece of text goes whilst a tree is being laid out
+#[derive(Default)]
+struct Placer {
+    row: usize,
+    col: usize,
+    indentation: usize,
+    spans: Vec<Span>,
+}
+
+impl Placer {
+    /// Places some text at the current position, splitting it into one span per line
+    fn place(&mut self, text: &str, attr: Attr) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.row += 1;
+                self.col = 0;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            self.spans.push(Span {
+                row: self.row,
+                col: self.col,
+                text: line.to_string(),
+                attr,
+            });
+            self.col += line.chars().count();
+        }
+    }
+}
+
+/// Returns the indices of the pair of delimiters that is nearest to the cursor, given the display
+/// tokens of the whole tree and the result of running [`match_delimiters`] on them.  This is the
+/// cursor node's own delimiters if it has any, otherwise it is the innermost pair which encloses
+/// the cursor node.
+fn delimiters_nearest_cursor<'arena, Node: Ast<'arena>>(
+    tokens: &[(&'arena Node, DisplayToken)],
+    delimiters: &[Option<DelimiterMatch>],
+    cursor: &'arena Node,
+) -> Option<(usize, usize)> {
+    let cursor_token_indices = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, (node, _))| std::ptr::eq(*node, cursor))
+        .map(|(i, _)| i);
+    let mut first_cursor_index = None;
+    for i in cursor_token_indices {
+        first_cursor_index.get_or_insert(i);
+        // If the cursor has a pair of delimiters of its own, then use those
+        if let (DisplayToken::OpenDelim(_), Some(m)) = (&tokens[i].1, delimiters[i]) {
+            return Some((i, m.partner));
+        }
+    }
+    // Otherwise, search backwards for the first opening delimiter whose partner is after the
+    // cursor.  Delimiters are always properly nested, so this finds the innermost pair.
+    let first_cursor_index = first_cursor_index?;
+    (0..first_cursor_index)
+        .rev()
+        .find_map(|i| match delimiters[i] {
+            Some(m) if m.partner > first_cursor_index => Some((i, m.partner)),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        gutter_width, layout, Highlights, Layout, Span, Theme, CURSOR_LINE_NUMBER_ATTR,
+        LINE_NUMBER_ATTR, REVIEW_ATTR, SEARCH_ATTR,
+    };
+    use crate::arena::Arena;
+    use crate::ast::display_token::DisplayToken;
+    use crate::ast::json::JSONFormat;
+    use crate::ast::pattern::Pattern;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::Ast;
+    use crate::editor::cursor_style::CursorStyle;
+    use tuikit::prelude::{Attr, Color, Effect};
+
+    /// Returns the text of each line of a layout, with the spans at their columns
+    fn lines(layout: &Layout) -> Vec<String> {
+        (0..layout.height)
+            .map(|row| {
+                let mut line = String::new();
+                for span in layout.row(row) {
+                    line.push_str(&" ".repeat(span.col - line.chars().count()));
+                    line.push_str(&span.text);
+                }
+                line
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indentation() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![(
+            "a".to_string(),
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+        )])
+        .add_to_arena(&arena);
+        let highlights = Highlights {
+            cursor: root,
+            cursor_style: &CursorStyle::Reverse,
+            review_nodes: &[],
+            search_pattern: None,
+            format_style: &JSONFormat::Pretty,
+            theme: &Theme::default(),
+        };
+        let tokens = root.display_tokens(&JSONFormat::Pretty);
+        let laid_out = layout(&tokens, &highlights);
+        assert_eq!(
+            lines(&laid_out),
+            root.to_text(&JSONFormat::Pretty)
+                .lines()
+                .collect::<Vec<_>>()
+        );
+        let first_elem = laid_out.row(2).next().unwrap();
+        assert_eq!((first_elem.col, first_elem.text.as_str()), (8, "true"));
+        // The cursor's subtree starts with its first text, which is the field's key
+        assert_eq!(laid_out.cursor, Some((0, 0)));
+        let highlights = Highlights {
+            cursor: root.children()[0],
+            ..highlights
+        };
+        assert_eq!(layout(&tokens, &highlights).cursor, Some((1, 4)));
+    }
+
+    #[test]
+    fn gutter() {
+        assert_eq!(gutter_width(7), 4);
+        assert_eq!(gutter_width(12345), 6);
+        let layout = Layout {
+            spans: vec![],
+            height: 12,
+            cursor: Some((9, 4)),
+        };
+        assert_eq!(
+            super::gutter(&layout, 0, 4),
+            ("  1 ".to_string(), LINE_NUMBER_ATTR)
+        );
+        assert_eq!(
+            super::gutter(&layout, 9, 4),
+            (" 10 ".to_string(), CURSOR_LINE_NUMBER_ATTR)
+        );
+    }
+
+    #[test]
+    fn clip() {
+        let span = Span {
+            row: 0,
+            col: 4,
+            text: "abcdef".to_string(),
+            attr: Attr::default(),
+        };
+        assert_eq!(span.clip(0, 80), Some((4, "abcdef".to_string())));
+        assert_eq!(span.clip(0, 6), Some((4, "ab".to_string())));
+        assert_eq!(span.clip(6, 3), Some((0, "cde".to_string())));
+        assert_eq!(span.clip(10, 5), None);
+        assert_eq!(span.clip(0, 4), None);
+    }
+
+    #[test]
+    fn wrapping() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let highlights = Highlights {
+            cursor: root,
+            cursor_style: &CursorStyle::Reverse,
+            review_nodes: &[],
+            search_pattern: None,
+            format_style: &JSONFormat::Pretty,
+            theme: &Theme::default(),
+        };
+        // Text containing line breaks is split into one span per line, and the lines after the
+        // first start at the left edge
+        let tokens = vec![
+            (root, DisplayToken::Indent),
+            (root, DisplayToken::Newline),
+            (root, DisplayToken::Text("one\ntwo".to_string())),
+            (root, DisplayToken::Whitespace(1)),
+            (root, DisplayToken::Text("three".to_string())),
+        ];
+        let laid_out = layout(&tokens, &highlights);
+        assert_eq!(lines(&laid_out), vec!["", "    one", "two three"]);
+        assert_eq!(laid_out.height, 3);
+        // Lines which are fitted to a width are placed where they are broken
+        let tokens = root.display_tokens(&JSONFormat::Fit(80));
+        assert_eq!(lines(&layout(&tokens, &highlights)), vec!["[true, false]"]);
+    }
+
+    #[test]
+    fn cursor_highlight() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![TestJSON::True, TestJSON::False]).add_to_arena(&arena);
+        let cursor = root.children()[1];
+        let tokens = root.display_tokens(&JSONFormat::Compact);
+        let span_of = |laid_out: &Layout, text: &str| {
+            laid_out
+                .spans
+                .iter()
+                .find(|span| span.text == text)
+                .cloned()
+                .unwrap()
+        };
+
+        let style = CursorStyle::Background(Color::BLUE);
+        let laid_out = layout(
+            &tokens,
+            &Highlights {
+                cursor,
+                cursor_style: &style,
+                review_nodes: &[root.children()[0]],
+                search_pattern: None,
+                format_style: &JSONFormat::Pretty,
+                theme: &Theme::default(),
+            },
+        );
+        let cursor_span = span_of(&laid_out, "false");
+        assert_eq!((cursor_span.row, cursor_span.col), (0, 7));
+        assert_eq!(cursor_span.attr.bg, Color::BLUE);
+        assert_eq!(span_of(&laid_out, "true").attr, REVIEW_ATTR);
+        // Everything else is coloured by the theme
+        assert_eq!(span_of(&laid_out, ",").attr, Theme::default().punctuation);
+        // The delimiters enclosing the cursor are the nearest pair
+        let open = span_of(&laid_out, "[");
+        assert!(open.attr.effect.contains(Effect::BOLD | Effect::UNDERLINE));
+
+        // Markers shift the rest of the line along
+        let style = CursorStyle::Markers("»".to_string(), "«".to_string());
+        let laid_out = layout(
+            &tokens,
+            &Highlights {
+                cursor,
+                cursor_style: &style,
+                review_nodes: &[],
+                search_pattern: Some(&Pattern::parse("tr")),
+                format_style: &JSONFormat::Pretty,
+                theme: &Theme::default(),
+            },
+        );
+        assert_eq!(lines(&laid_out), vec!["[true, »false«]"]);
+        assert_eq!(span_of(&laid_out, "false").col, 8);
+        assert_eq!(span_of(&laid_out, "«").col, 13);
+        assert_eq!(span_of(&laid_out, "true").attr, SEARCH_ATTR);
+        assert_eq!(span_of(&laid_out, "[").attr.bg, Color::Default);
+
+        // Kind patterns highlight all of each node which matches them
+        let laid_out = layout(
+            &tokens,
+            &Highlights {
+                cursor,
+                cursor_style: &CursorStyle::Underline,
+                review_nodes: &[],
+                search_pattern: Some(&Pattern::parse("<array>")),
+                format_style: &JSONFormat::Pretty,
+                theme: &Theme::default(),
+            },
+        );
+        assert_eq!(span_of(&laid_out, "[").attr.bg, SEARCH_ATTR.bg);
+        assert_eq!(span_of(&laid_out, "]").attr.bg, SEARCH_ATTR.bg);
+        assert_eq!(span_of(&laid_out, "true").attr, Theme::default().keyword);
+
+        // The cursor keeps the colour of its category
+        let theme = Theme {
+            keyword: Attr::default().fg(Color::RED),
+            ..Theme::default()
+        };
+        let laid_out = layout(
+            &tokens,
+            &Highlights {
+                cursor,
+                cursor_style: &CursorStyle::Underline,
+                review_nodes: &[],
+                search_pattern: None,
+                format_style: &JSONFormat::Pretty,
+                theme: &theme,
+            },
+        );
+        assert_eq!(span_of(&laid_out, "true").attr, theme.keyword);
+        assert_eq!(span_of(&laid_out, "false").attr.fg, Color::RED);
+    }
+
+    #[test]
+    fn subtree_highlight() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False]),
+            TestJSON::True,
+        ])
+        .add_to_arena(&arena);
+        let tokens = root.display_tokens(&JSONFormat::Compact);
+        let theme = Theme::default();
+        let laid_out = layout(
+            &tokens,
+            &Highlights {
+                cursor: root.children()[0],
+                cursor_style: &CursorStyle::Underline,
+                review_nodes: &[],
+                search_pattern: None,
+                format_style: &JSONFormat::Pretty,
+                theme: &theme,
+            },
+        );
+        let attrs: Vec<(&str, Attr)> = laid_out
+            .spans
+            .iter()
+            .map(|span| (span.text.as_str(), span.attr))
+            .collect();
+        let in_subtree = |attr: Attr| attr.extend(theme.subtree);
+        // The descendants of the cursor, and the whitespace between them, are drawn on top of the
+        // subtree attributes, but the rest of the tree isn't
+        assert_eq!(attrs[2], ("true", in_subtree(theme.keyword)));
+        // The cursor's own tokens are still drawn in the cursor style
+        let cursor_attr = CursorStyle::Underline.attr(theme.punctuation.fg);
+        assert_eq!(attrs[3], (",", cursor_attr));
+        assert_eq!(attrs[4], (" ", in_subtree(Attr::default())));
+        assert_eq!(attrs[5], ("false", in_subtree(theme.keyword)));
+        assert_eq!(attrs[8], ("true", theme.keyword));
+        assert_eq!(lines(&laid_out), vec!["[[true, false], true]"]);
+    }
+}
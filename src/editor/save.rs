@@ -0,0 +1,104 @@
+//! Saving files safely.  The new contents are written to a temporary file next to the target,
+//! which is then renamed over the target.  Renaming within a directory is atomic, so a crash or
+//! full disk part-way through a save leaves either the old file or the new one, never a truncated
+//! mixture of the two.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replaces the contents of the file at `path` with `bytes`, keeping the file's permissions.  If
+/// `path` is a symlink, the file it points to is replaced rather than the link itself.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    // Saving through a symlink shouldn't replace the link with a regular file
+    let target = match std::fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => path.to_path_buf(),
+    };
+    let permissions = std::fs::metadata(&target).ok().map(|m| m.permissions());
+    let temp_path = temp_path_for(&target);
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        file.write_all(bytes)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        // The data has to be on disk before the rename, or a crash could leave an empty file
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &target)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+        return result;
+    }
+    // Make the rename itself durable.  Not every platform can sync a directory, and the file has
+    // already been saved, so failing to do so isn't an error.
+    if let Some(dir) = target
+        .parent()
+        .and_then(|dir| File::open(dir_or_cwd(dir)).ok())
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Returns the path of the temporary file used whilst saving to `target`, which is a hidden file
+/// in the same directory (so that renaming it over `target` doesn't move it between filesystems)
+fn temp_path_for(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_name = format!(".{}.sapling-{}.tmp", name, std::process::id());
+    target.with_file_name(temp_name)
+}
+
+/// Returns `dir`, or the working directory if `dir` is empty (as the parent of a bare file name
+/// is)
+fn dir_or_cwd(dir: &Path) -> &Path {
+    if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{temp_path_for, write_atomically};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn atomic_writes() {
+        let dir = std::env::temp_dir().join(format!("sapling-save-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("a.json");
+        write_atomically(&path, b"[]").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"[]");
+
+        // Permissions are kept, and writes through a symlink replace the file it points to
+        let permissions = std::fs::Permissions::from_mode(0o640);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        let link = dir.join("link.json");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        write_atomically(&link, b"{}").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"{}");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(!temp_path_for(&path).exists());
+
+        // A failed write leaves nothing behind
+        let missing = dir.join("missing").join("a.json");
+        assert!(write_atomically(&missing, b"[]").is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+//! Search for the nodes of a tree which match a [`Pattern`].  Large trees are searched for text
+//! through a [`SearchIndex`], so that a search doesn't have to generate the tokens of every node.
+
+use crate::ast::display_token::{DisplayToken, RecTok};
+use crate::ast::pattern::Pattern;
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::{self, CursorPath};
+use std::collections::HashMap;
+
+/// How many nodes a tree needs before it's worth building a [`SearchIndex`] for it
+pub const INDEX_THRESHOLD: usize = 10_000;
+
+/// Returns the paths to every node in the tree under `root` which matches a pattern, in
+/// depth-first pre-order.
+pub fn matching_paths<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    format_style: &Node::FormatStyle,
+    pattern: &Pattern,
+) -> Vec<CursorPath> {
+    cursor_path::all_paths(root)
+        .into_iter()
+        .filter(|(_, node)| node.matches(pattern, format_style))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Returns the path of the first node matching a pattern that comes after `cursor` in
+/// depth-first pre-order (or before it, if `forward` is `false`), wrapping round the ends of the
+/// tree if there are no matches in that direction.  Returns [`None`] if nothing in the tree
+/// matches.
+pub fn next_match<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    format_style: &Node::FormatStyle,
+    pattern: &Pattern,
+    cursor: &CursorPath,
+    forward: bool,
+) -> Option<CursorPath> {
+    let all_paths = cursor_path::all_paths(root);
+    let cursor_index = all_paths.iter().position(|(path, _)| path == cursor)?;
+    // Search the nodes after the cursor, then the nodes up to and including the cursor
+    let (before, after) = all_paths.split_at(cursor_index + 1);
+    let is_match = |(_, node): &&(CursorPath, &'arena Node)| node.matches(pattern, format_style);
+    let found = match forward {
+        true => after.iter().chain(before.iter()).find(is_match),
+        // Going backwards, the cursor itself is the last node to be searched
+        false => before[..cursor_index]
+            .iter()
+            .rev()
+            .chain(after.iter().rev())
+            .chain(before[cursor_index..].iter())
+            .find(is_match),
+    };
+    found.map(|(path, _)| path.clone())
+}
+
+/// Returns the first of `paths` (which are in depth-first pre-order) after `cursor`, or before it
+/// if `forward` is `false`, wrapping round the ends of the list
+fn step_through(paths: &[CursorPath], cursor: &CursorPath, forward: bool) -> Option<CursorPath> {
+    let found = match forward {
+        true => paths
+            .iter()
+            .find(|path| *path > cursor)
+            .or_else(|| paths.first()),
+        false => paths
+            .iter()
+            .rev()
+            .find(|path| *path < cursor)
+            .or_else(|| paths.last()),
+    };
+    found.cloned()
+}
+
+/// Maps the text of every token in a tree to the paths of the nodes which generate that token, so
+/// that searches only have to look through the distinct texts of the tree rather than every node.
+/// An index is built for one root, and is brought up to date after an edit by re-indexing only
+/// the subtree which the edit changed.
+#[derive(Debug, Clone)]
+pub struct SearchIndex<'arena, Node: Ast<'arena>> {
+    root: &'arena Node,
+    /// The paths for each text, in depth-first pre-order
+    paths_by_text: HashMap<String, Vec<CursorPath>>,
+}
+
+impl<'arena, Node: Ast<'arena>> SearchIndex<'arena, Node> {
+    /// Builds an index of every node in the tree under `root`
+    pub fn build(root: &'arena Node, format_style: &Node::FormatStyle) -> Self {
+        let mut index = SearchIndex {
+            root,
+            paths_by_text: HashMap::new(),
+        };
+        index.add_subtree(CursorPath::root(), root, format_style);
+        index
+    }
+
+    /// Brings the index up to date with the tree under `root`, which was made by editing the tree
+    /// that the index was built for.  Subtrees which the edit didn't touch are shared between the
+    /// trees, so only the subtree containing the edit has its tokens generated again.
+    pub fn update(&mut self, root: &'arena Node, format_style: &Node::FormatStyle) {
+        if self.is_for(root) {
+            return;
+        }
+        // Nodes generate the tokens between their children, so a changed node is re-indexed along
+        // with all of its descendants
+        if let Some(changed_path) = cursor_path::changed_subtree(self.root, root) {
+            for paths in self.paths_by_text.values_mut() {
+                paths.retain(|path| !path.starts_with(&changed_path));
+            }
+            self.paths_by_text.retain(|_, paths| !paths.is_empty());
+            let changed_node = changed_path.cursor(root);
+            self.add_subtree(changed_path, changed_node, format_style);
+        }
+        self.root = root;
+    }
+
+    /// Adds every node of the subtree at `subtree_path` to the index
+    fn add_subtree(
+        &mut self,
+        subtree_path: CursorPath,
+        subtree: &'arena Node,
+        format_style: &Node::FormatStyle,
+    ) {
+        for (relative_path, node) in cursor_path::all_paths(subtree) {
+            let mut path = subtree_path.clone();
+            path.extend(relative_path.iter().copied());
+            for rec_tok in node.display_tokens_rec(format_style) {
+                match rec_tok {
+                    RecTok::Tok(DisplayToken::Text(s))
+                    | RecTok::Tok(DisplayToken::Categorised(s, _))
+                    | RecTok::Tok(DisplayToken::OpenDelim(s))
+                    | RecTok::Tok(DisplayToken::CloseDelim(s)) => {
+                        let paths = self.paths_by_text.entry(s).or_default();
+                        // A node can generate the same text more than once (e.g. `"` twice)
+                        let position = paths.partition_point(|p| *p < path);
+                        if paths.get(position) != Some(&path) {
+                            paths.insert(position, path.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if this index was built for the tree under `root` (and so hasn't been made
+    /// out of date by an edit)
+    pub fn is_for(&self, root: &'arena Node) -> bool {
+        std::ptr::eq(self.root, root)
+    }
+
+    /// Returns the paths to every node whose text contains `text`, in depth-first pre-order.
+    /// This gives the same result as [`matching_paths`] for a [`Pattern::Text`] (unless the
+    /// language refines how text is matched).
+    pub fn matching_paths(&self, text: &str) -> Vec<CursorPath> {
+        let mut paths: Vec<CursorPath> = self
+            .paths_by_text
+            .iter()
+            .filter(|(s, _)| s.contains(text))
+            .flat_map(|(_, paths)| paths.iter().cloned())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Returns the path of the first node containing `text` after `cursor` (or before it, if
+    /// `forward` is `false`), wrapping round the ends of the tree.  This gives the same result as
+    /// [`next_match`] for a [`Pattern::Text`].
+    pub fn next_match(&self, text: &str, cursor: &CursorPath, forward: bool) -> Option<CursorPath> {
+        step_through(&self.matching_paths(text), cursor, forward)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matching_paths, next_match, SearchIndex};
+    use crate::arena::Arena;
+    use crate::ast::json::{JSONFormat, JSON};
+    use crate::ast::pattern::Pattern;
+    use crate::ast::test_json::TestJSON;
+    use crate::ast::{restructure, Ast};
+    use crate::editable_tree::cursor_path::{changed_subtree, CursorPath};
+
+    #[test]
+    fn search() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::False,
+            TestJSON::Object(vec![("true_value".to_string(), TestJSON::True)]),
+        ])
+        .add_to_arena(&arena);
+        let format = JSONFormat::Pretty;
+        let paths = |v: &[&[usize]]| {
+            v.iter()
+                .map(|p| CursorPath::from_vec(p.to_vec()))
+                .collect::<Vec<_>>()
+        };
+        let tru = Pattern::parse("tru");
+        // "tru" matches both the `true`s and the key of the field
+        assert_eq!(
+            matching_paths(root, &format, &tru),
+            paths(&[&[0], &[2, 0, 0], &[2, 0, 1]])
+        );
+        assert_eq!(
+            matching_paths(root, &format, &Pattern::parse("["))[..],
+            paths(&[&[]])[..]
+        );
+        let null = Pattern::parse("null");
+        assert!(matching_paths(root, &format, &null).is_empty());
+        // Searching forward from the first `true` skips to the field key, and searching from the
+        // last match wraps around to the start
+        let next = |path: &[usize], forward| {
+            next_match(
+                root,
+                &format,
+                &tru,
+                &CursorPath::from_vec(path.to_vec()),
+                forward,
+            )
+        };
+        assert_eq!(next(&[0], true), Some(CursorPath::from_vec(vec![2, 0, 0])));
+        assert_eq!(next(&[2, 0, 1], true), Some(CursorPath::from_vec(vec![0])));
+        // Searching backwards does the same in the other direction
+        assert_eq!(
+            next(&[2, 0, 1], false),
+            Some(CursorPath::from_vec(vec![2, 0, 0]))
+        );
+        assert_eq!(next(&[0], false), Some(CursorPath::from_vec(vec![2, 0, 1])));
+        assert_eq!(next(&[1], false), Some(CursorPath::from_vec(vec![0])));
+        let cursor = CursorPath::root();
+        assert_eq!(next_match(root, &format, &null, &cursor, true), None);
+        assert_eq!(next_match(root, &format, &null, &cursor, false), None);
+    }
+
+    #[test]
+    fn kinds() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![
+            ("a".to_string(), TestJSON::Array(vec![TestJSON::True])),
+            ("b".to_string(), TestJSON::Array(vec![TestJSON::False])),
+        ])
+        .add_to_arena(&arena);
+        let format = JSONFormat::Pretty;
+        let paths = |pattern: &str| {
+            matching_paths(root, &format, &Pattern::parse(pattern))
+                .into_iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>()
+        };
+        let arrays = paths("<array>");
+        assert_eq!(arrays.len(), 2);
+        assert_eq!(paths("<field>").len(), 2);
+        assert_eq!(paths("<string>").len(), 2);
+        assert_eq!(paths("<object>"), vec![CursorPath::root().to_string()]);
+        assert!(paths("<number>").is_empty());
+        // JSON refines kind patterns, so that `<bool>` matches both `true` and `false`
+        assert_eq!(
+            paths("<bool>"),
+            [paths("<true>"), paths("<false>")].concat()
+        );
+        assert_eq!(paths("<bool>").len(), 2);
+        // A kind's name without brackets is just text
+        assert!(paths("array").is_empty());
+    }
+
+    #[test]
+    fn index_agrees_with_search() {
+        let arena = Arena::new();
+        let root = TestJSON::Object(vec![
+            ("true".to_string(), TestJSON::Array(vec![TestJSON::True])),
+            ("f".to_string(), TestJSON::False),
+            ("t".to_string(), TestJSON::True),
+        ])
+        .add_to_arena(&arena);
+        let format = JSONFormat::Pretty;
+        let index = SearchIndex::build(root, &format);
+        assert!(index.is_for(root));
+        for query in &["tru", "\"", "{", "]", "f", "null"] {
+            assert_eq!(
+                index.matching_paths(query),
+                matching_paths(root, &format, &Pattern::Text(query.to_string())),
+                "{}",
+                query
+            );
+            let pattern = Pattern::Text(query.to_string());
+            for (cursor, _) in crate::editable_tree::cursor_path::all_paths(root) {
+                for &forward in &[true, false] {
+                    assert_eq!(
+                        index.next_match(query, &cursor, forward),
+                        next_match(root, &format, &pattern, &cursor, forward),
+                        "{} from {}",
+                        query,
+                        cursor
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn index_updates() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::Array(vec![TestJSON::True, TestJSON::False, TestJSON::True]),
+            TestJSON::Object(vec![("t".to_string(), TestJSON::True)]),
+            TestJSON::Array(vec![TestJSON::False]),
+        ])
+        .add_to_arena(&arena);
+        let format = JSONFormat::Pretty;
+        let path = |p: &[usize]| CursorPath::from_vec(p.to_vec());
+        let mut index = SearchIndex::build(root, &format);
+        let check = |index: &SearchIndex<JSON>, root| {
+            assert!(index.is_for(root));
+            for query in &["tru", "fals", "\"", "[", "t"] {
+                assert_eq!(
+                    index.matching_paths(query),
+                    SearchIndex::build(root, &format).matching_paths(query),
+                    "{}",
+                    query
+                );
+            }
+        };
+        // Removing a child moves the paths of its later siblings
+        let shortened = restructure::remove_child(root.children()[0], 1).unwrap();
+        let new_root = arena
+            .alloc(restructure::replace_each(&arena, root, vec![(path(&[0]), shortened)]).unwrap());
+        assert_eq!(changed_subtree(root, new_root), Some(path(&[0])));
+        index.update(new_root, &format);
+        check(&index, new_root);
+        // Replacing a node changes the text under it
+        let root = new_root;
+        let replacement = JSON::Str("false".to_string());
+        let new_root = arena.alloc(
+            restructure::replace_each(&arena, root, vec![(path(&[1, 0, 1]), replacement)]).unwrap(),
+        );
+        assert_eq!(changed_subtree(root, new_root), Some(path(&[1, 0, 1])));
+        index.update(new_root, &format);
+        check(&index, new_root);
+        // Updating the index for the same tree changes nothing
+        assert_eq!(changed_subtree(new_root, new_root), None);
+        index.update(new_root, &format);
+        check(&index, new_root);
+    }
+}
@@ -0,0 +1,183 @@
+//! The status bar along the bottom of the screen, which is made of [`Segment`]s that each show
+//! one part of the editor's state.  Which segments are shown, and in what order, can be set with
+//! `:set status=` followed by a comma-separated list of segment names (e.g.
+//! `:set status=mode,path`).
+
+/// One part of the editor's state which can be shown in the status bar
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Segment {
+    /// Whether key presses run commands or type into a node
+    Mode,
+    /// The file that the buffer is written to
+    FileName,
+    /// A marker shown whilst there are unsaved changes
+    Modified,
+    /// The kinds of the nodes from the root down to the cursor, e.g. `object → field[2] → true[1]`
+    Path,
+    /// The cursor's position among its siblings and its depth, e.g. `child 3/7, depth 4`
+    Position,
+    /// How many changes can be undone
+    UndoDepth,
+    /// The keys of the command which is being typed
+    Command,
+}
+
+/// The segments shown when none have been configured
+pub const DEFAULT_SEGMENTS: &[Segment] = &[
+    Segment::Mode,
+    Segment::FileName,
+    Segment::Modified,
+    Segment::Path,
+    Segment::Position,
+    Segment::UndoDepth,
+    Segment::Command,
+];
+
+/// What goes between two segments
+const SEPARATOR: &str = " | ";
+/// What goes between two nodes of the [`Segment::Path`]
+const PATH_SEPARATOR: &str = " → ";
+/// What replaces the nodes at the start of the [`Segment::Path`] when it's too long to fit
+const ELLIPSIS: &str = "…";
+
+impl Segment {
+    /// The name of this segment in `:set status=`
+    pub fn name(self) -> &'static str {
+        match self {
+            Segment::Mode => "mode",
+            Segment::FileName => "file",
+            Segment::Modified => "modified",
+            Segment::Path => "path",
+            Segment::Position => "position",
+            Segment::UndoDepth => "undo",
+            Segment::Command => "command",
+        }
+    }
+
+    /// Returns the segment with a given [`name`](Segment::name), or [`None`] if there isn't one
+    pub fn from_name(name: &str) -> Option<Segment> {
+        DEFAULT_SEGMENTS
+            .iter()
+            .copied()
+            .find(|segment| segment.name() == name)
+    }
+}
+
+/// Parses a comma-separated list of segment names, returning [`None`] if any of them aren't the
+/// name of a segment.  An empty list hides the status bar.
+pub fn parse_segments(text: &str) -> Option<Vec<Segment>> {
+    if text.is_empty() {
+        return Some(Vec::new());
+    }
+    text.split(',')
+        .map(|name| Segment::from_name(name.trim()))
+        .collect()
+}
+
+/// Returns the names of `segments` as a comma-separated list, in the form read by
+/// [`parse_segments`]
+pub fn segment_names(segments: &[Segment]) -> String {
+    let names: Vec<&str> = segments.iter().map(|segment| segment.name()).collect();
+    names.join(",")
+}
+
+/// Returns the text of the [`Segment::Path`], given the kind of each node from the root down to
+/// the cursor along with its index among its siblings (which the root doesn't have)
+pub fn path_text(nodes: &[(&str, Option<usize>)]) -> String {
+    let steps: Vec<String> = nodes
+        .iter()
+        .map(|(kind, index)| match index {
+            Some(index) => format!("{}[{}]", kind, index),
+            None => kind.to_string(),
+        })
+        .collect();
+    steps.join(PATH_SEPARATOR)
+}
+
+/// Joins the text of each segment into the text of the status bar, at most `width` chars long.
+/// Empty segments are left out.  If the bar is too long, then the nodes at the start of the
+/// [`Segment::Path`] are dropped first (since the nodes nearest the cursor matter most), and then
+/// the end of the bar is cut off.
+pub fn compose(segments: &[(Segment, String)], width: usize) -> String {
+    let mut texts: Vec<(Segment, String)> = segments
+        .iter()
+        .filter(|(_, text)| !text.is_empty())
+        .cloned()
+        .collect();
+    let len = |texts: &[(Segment, String)]| -> usize {
+        let text_len: usize = texts.iter().map(|(_, text)| text.chars().count()).sum();
+        text_len + SEPARATOR.chars().count() * texts.len().saturating_sub(1)
+    };
+    while len(&texts) > width {
+        let path = match texts
+            .iter_mut()
+            .find(|(segment, _)| *segment == Segment::Path)
+        {
+            Some((_, path)) => path,
+            None => break,
+        };
+        let rest = path.strip_prefix(ELLIPSIS).unwrap_or(path);
+        let steps: Vec<&str> = rest
+            .split(PATH_SEPARATOR)
+            .filter(|s| !s.is_empty())
+            .collect();
+        if steps.len() <= 1 {
+            break;
+        }
+        *path = format!(
+            "{}{}{}",
+            ELLIPSIS,
+            PATH_SEPARATOR,
+            steps[1..].join(PATH_SEPARATOR)
+        );
+    }
+    let texts: Vec<String> = texts.into_iter().map(|(_, text)| text).collect();
+    texts.join(SEPARATOR).chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compose, parse_segments, path_text, segment_names, Segment};
+
+    #[test]
+    fn segments() {
+        assert_eq!(
+            parse_segments("mode, path"),
+            Some(vec![Segment::Mode, Segment::Path])
+        );
+        assert_eq!(
+            parse_segments("position,undo"),
+            Some(vec![Segment::Position, Segment::UndoDepth])
+        );
+        assert_eq!(parse_segments(""), Some(vec![]));
+        assert_eq!(parse_segments("mode,colour"), None);
+        assert_eq!(
+            segment_names(&[Segment::UndoDepth, Segment::FileName]),
+            "undo,file"
+        );
+    }
+
+    #[test]
+    fn composing() {
+        let path = path_text(&[("object", None), ("field", Some(2)), ("true", Some(1))]);
+        assert_eq!(path, "object → field[2] → true[1]");
+        let segments = vec![
+            (Segment::Mode, "NORMAL".to_string()),
+            (Segment::Modified, String::new()),
+            (Segment::Path, path),
+            (Segment::UndoDepth, "undo:3".to_string()),
+        ];
+        assert_eq!(
+            compose(&segments, 80),
+            "NORMAL | object → field[2] → true[1] | undo:3"
+        );
+        // The start of the path is dropped to make room
+        assert_eq!(
+            compose(&segments, 40),
+            "NORMAL | … → field[2] → true[1] | undo:3"
+        );
+        assert_eq!(compose(&segments, 35), "NORMAL | … → true[1] | undo:3");
+        // Once only the cursor's node is left, the end of the bar is cut off
+        assert_eq!(compose(&segments, 20), "NORMAL | … → true[1]");
+    }
+}
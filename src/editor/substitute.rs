@@ -0,0 +1,88 @@
+//! Expansion of Vim-style placeholders in the paths given to `:` commands, so that (for example)
+//! `:w %.bak` writes a backup next to the buffer's file.  The placeholders are:
+//! - `%`: the buffer's file, as it was given when it was opened
+//! - `%:p`: the absolute path of the buffer's file
+//! - `<cpath>`: the path to the cursor's node (like `0.1.2`)
+//!
+//! A `%` which is preceded by `\` is left as a literal `%`.
+
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::messages::Message;
+use std::path::Path;
+
+/// The placeholder for the path to the cursor's node
+const CURSOR_PATH: &str = "<cpath>";
+
+/// Expands the placeholders in `text`, where `file` is the buffer's file (if it has one).
+/// Returns an error if `text` refers to the buffer's file but the buffer doesn't have one.
+pub fn expand(text: &str, file: Option<&str>, cursor_path: &CursorPath) -> Result<String, Message> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("\\%") {
+            expanded.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(CURSOR_PATH) {
+            expanded.push_str(&cursor_path.to_string());
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('%') {
+            let file = file.ok_or(Message::NoFileToSubstitute)?;
+            match after.strip_prefix(":p") {
+                Some(after) => {
+                    expanded.push_str(&absolute(file));
+                    rest = after;
+                }
+                None => {
+                    expanded.push_str(file);
+                    rest = after;
+                }
+            }
+        } else {
+            expanded.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    Ok(expanded)
+}
+
+/// Returns the absolute version of a path, which is relative to the working directory if it
+/// isn't already absolute
+fn absolute(path: &str) -> String {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.to_string_lossy().into_owned();
+    }
+    match std::env::current_dir() {
+        Ok(dir) => dir.join(path).to_string_lossy().into_owned(),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::messages::Message;
+
+    #[test]
+    fn expands() {
+        let cursor_path = CursorPath::from_vec(vec![0, 2]);
+        let expand = |text: &str, file: Option<&str>| expand(text, file, &cursor_path);
+        assert_eq!(
+            expand("%.bak", Some("a.json")),
+            Ok("a.json.bak".to_string())
+        );
+        assert_eq!(
+            expand("%:p", Some("/tmp/a.json")),
+            Ok("/tmp/a.json".to_string())
+        );
+        let relative = expand("%:p", Some("a.json")).unwrap();
+        assert!(relative.ends_with("a.json") && relative.len() > "a.json".len());
+        assert_eq!(
+            expand("out-<cpath>.json", None),
+            Ok("out-0.2.json".to_string())
+        );
+        assert_eq!(expand("100\\%é", None), Ok("100%é".to_string()));
+        assert_eq!(expand("%", None), Err(Message::NoFileToSubstitute));
+    }
+}
@@ -0,0 +1,235 @@
+//! A tabular view of nodes whose children are records with identical keys (like a JSON array of
+//! objects), with one row per record and one column per key.
+
+use crate::ast::Ast;
+use crate::editable_tree::cursor_path::CursorPath;
+use crate::editable_tree::Direction;
+
+/// The maximum width of a column, in chars.  Longer cells are truncated.
+pub const MAX_COLUMN_WIDTH: usize = 20;
+
+/// A node which can be shown as a table
+#[derive(Debug, Clone)]
+pub struct Table<'arena, Node: Ast<'arena>> {
+    /// The path to the node containing the records
+    pub path: CursorPath,
+    /// The records, each of which is one row
+    pub rows: &'arena [&'arena Node],
+    /// The key of each column, shared by every record
+    pub keys: Vec<String>,
+}
+
+/// Returns the keys of a record (i.e. the [`outline_label`](Ast::outline_label)s of its
+/// children), or [`None`] if it isn't a record
+fn record_keys<'arena, Node: Ast<'arena>>(node: &'arena Node) -> Option<Vec<String>> {
+    if node.children().is_empty() {
+        return None;
+    }
+    node.children()
+        .iter()
+        .map(|field| field.outline_label())
+        .collect()
+}
+
+/// Returns the keys of the columns if `node` can be shown as a table, i.e. it has children and
+/// every child is a record with the same keys in the same order
+fn table_keys<'arena, Node: Ast<'arena>>(node: &'arena Node) -> Option<Vec<String>> {
+    let (first, rest) = node.children().split_first()?;
+    let keys = record_keys(*first)?;
+    rest.iter()
+        .all(|row| record_keys(*row).as_ref() == Some(&keys))
+        .then_some(keys)
+}
+
+impl<'arena, Node: Ast<'arena>> Table<'arena, Node> {
+    /// Returns the innermost node containing the node at `cursor_path` (or that node itself) which
+    /// can be shown as a table
+    pub fn enclosing(root: &'arena Node, cursor_path: &CursorPath) -> Option<Self> {
+        let nodes: Vec<&Node> = cursor_path.node_iter(root).collect();
+        let indices: Vec<usize> = cursor_path.iter().copied().collect();
+        (0..nodes.len()).rev().find_map(|depth| {
+            Some(Table {
+                path: CursorPath::from_vec(indices[..depth].to_vec()),
+                rows: nodes[depth].children(),
+                keys: table_keys(nodes[depth])?,
+            })
+        })
+    }
+
+    /// Returns the `(row, column)` of the cell containing the node at `cursor_path`, or [`None`]
+    /// if that node isn't inside a cell
+    pub fn cell(&self, cursor_path: &CursorPath) -> Option<(usize, usize)> {
+        let mut table_path = self.path.iter();
+        let mut path = cursor_path.iter();
+        // The cursor must be inside the table
+        if !table_path.all(|i| path.next() == Some(i)) {
+            return None;
+        }
+        Some((*path.next()?, *path.next()?))
+    }
+
+    /// Returns the path to the value of a cell, which is where the cursor goes when it moves onto
+    /// the cell.  The value is the last child of the cell (e.g. the value of a key-value pair).
+    pub fn cell_path(&self, row: usize, column: usize) -> CursorPath {
+        let mut path = self.path.clone();
+        path.push(row);
+        path.push(column);
+        let cell = self.rows[row].children()[column];
+        if let Some(last) = cell.children().len().checked_sub(1) {
+            path.push(last);
+        }
+        path
+    }
+
+    /// Returns the text shown in a cell, which summarises its value
+    pub fn cell_text(&self, row: usize, column: usize) -> String {
+        let cell = self.rows[row].children()[column];
+        cell.children().last().unwrap_or(&cell).summary()
+    }
+
+    /// Returns the cell reached by moving in a given direction from a cell, or [`None`] if that
+    /// would leave the table.  [`Direction::Prev`] and [`Direction::Next`] move between columns,
+    /// and [`Direction::Up`] and [`Direction::Down`] move between rows.
+    pub fn move_cell(
+        &self,
+        (row, column): (usize, usize),
+        direction: Direction,
+    ) -> Option<(usize, usize)> {
+        match direction {
+            Direction::Up => Some((row.checked_sub(1)?, column)),
+            Direction::Down => Some((row + 1, column)).filter(|(r, _)| *r < self.rows.len()),
+            Direction::Prev => Some((row, column.checked_sub(1)?)),
+            Direction::Next => Some((row, column + 1)).filter(|(_, c)| *c < self.keys.len()),
+        }
+    }
+
+    /// Returns the width of each column, which is wide enough for its key and all its cells (up to
+    /// [`MAX_COLUMN_WIDTH`])
+    pub fn column_widths(&self) -> Vec<usize> {
+        (0..self.keys.len())
+            .map(|column| {
+                (0..self.rows.len())
+                    .map(|row| self.cell_text(row, column).chars().count())
+                    .chain(std::iter::once(self.keys[column].chars().count()))
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect()
+    }
+}
+
+/// Finds the field of a record which contains the node at `cursor_path` (e.g. a key-value pair of a
+/// JSON object in an array), and the field with the same key in the next record (or the previous
+/// one if `forward` is `false`).  The records don't have to have the same keys, so this works
+/// even where the records can't be shown as a [`Table`].  Returns the paths to the cursor's field
+/// and to the corresponding field, or [`None`] if there's no corresponding field.
+pub fn corresponding_field<'arena, Node: Ast<'arena>>(
+    root: &'arena Node,
+    cursor_path: &CursorPath,
+    forward: bool,
+) -> Option<(CursorPath, CursorPath)> {
+    let nodes: Vec<&Node> = cursor_path.node_iter(root).collect();
+    let indices: Vec<usize> = cursor_path.iter().copied().collect();
+    // Fields inside nested records are tried before the fields which contain them
+    (2..nodes.len()).rev().find_map(|depth| {
+        let label = nodes[depth].outline_label()?;
+        let record_index = indices[depth - 2];
+        let neighbour_index = if forward {
+            record_index + 1
+        } else {
+            record_index.checked_sub(1)?
+        };
+        let neighbour = nodes[depth - 2].children().get(neighbour_index)?;
+        let field_index = neighbour
+            .children()
+            .iter()
+            .position(|field| field.outline_label().as_ref() == Some(&label))?;
+        let mut neighbour_path = CursorPath::from_vec(indices[..depth - 2].to_vec());
+        neighbour_path.push(neighbour_index);
+        neighbour_path.push(field_index);
+        Some((
+            CursorPath::from_vec(indices[..depth].to_vec()),
+            neighbour_path,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{corresponding_field, Table};
+    use crate::arena::Arena;
+    use crate::ast::test_json::TestJSON;
+    use crate::editable_tree::cursor_path::CursorPath;
+    use crate::editable_tree::Direction;
+
+    fn record(a: TestJSON, b: TestJSON) -> TestJSON {
+        TestJSON::Object(vec![("a".to_string(), a), ("b".to_string(), b)])
+    }
+
+    #[test]
+    fn table() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            TestJSON::True,
+            TestJSON::Array(vec![
+                record(TestJSON::True, TestJSON::Array(vec![])),
+                record(TestJSON::False, TestJSON::True),
+            ]),
+        ])
+        .add_to_arena(&arena);
+        let path = |v: &[usize]| CursorPath::from_vec(v.to_vec());
+        // The outer array isn't a table, because its children aren't records
+        assert!(Table::enclosing(root, &path(&[0])).is_none());
+        let table = Table::enclosing(root, &path(&[1, 1, 0, 1])).unwrap();
+        assert_eq!(table.path, path(&[1]));
+        assert_eq!(table.keys, vec![r#""a""#, r#""b""#]);
+        assert_eq!(table.cell(&path(&[1, 1, 0, 1])), Some((1, 0)));
+        assert_eq!(table.cell(&path(&[1, 1])), None);
+        assert_eq!(table.cell(&path(&[0])), None);
+        assert_eq!(table.cell_path(0, 1), path(&[1, 0, 1, 1]));
+        assert_eq!(table.cell_text(0, 1), "[ 0 items ]");
+        assert_eq!(table.column_widths(), vec![5, 11]);
+        // Moving around the cells
+        assert_eq!(table.move_cell((0, 0), Direction::Down), Some((1, 0)));
+        assert_eq!(table.move_cell((1, 0), Direction::Down), None);
+        assert_eq!(table.move_cell((1, 0), Direction::Next), Some((1, 1)));
+        assert_eq!(table.move_cell((1, 1), Direction::Next), None);
+        assert_eq!(table.move_cell((0, 1), Direction::Up), None);
+        assert_eq!(table.move_cell((0, 1), Direction::Prev), Some((0, 0)));
+        // Records with different keys can't be shown as a table
+        let root = TestJSON::Array(vec![
+            record(TestJSON::True, TestJSON::True),
+            TestJSON::Object(vec![("a".to_string(), TestJSON::True)]),
+        ])
+        .add_to_arena(&arena);
+        assert!(Table::enclosing(root, &path(&[0])).is_none());
+    }
+
+    #[test]
+    fn corresponding_fields() {
+        let arena = Arena::new();
+        let root = TestJSON::Array(vec![
+            record(TestJSON::True, TestJSON::False),
+            TestJSON::Object(vec![
+                ("b".to_string(), TestJSON::True),
+                ("a".to_string(), TestJSON::False),
+            ]),
+            TestJSON::Object(vec![("c".to_string(), TestJSON::True)]),
+        ])
+        .add_to_arena(&arena);
+        let path = |v: &[usize]| CursorPath::from_vec(v.to_vec());
+        // Fields are matched by key, not by position
+        assert_eq!(
+            corresponding_field(root, &path(&[0, 0, 1]), true),
+            Some((path(&[0, 0]), path(&[1, 1])))
+        );
+        assert_eq!(
+            corresponding_field(root, &path(&[1, 0]), false),
+            Some((path(&[1, 0]), path(&[0, 1])))
+        );
+        assert_eq!(corresponding_field(root, &path(&[0, 0]), false), None);
+        assert_eq!(corresponding_field(root, &path(&[1, 0]), true), None);
+        assert_eq!(corresponding_field(root, &path(&[0]), true), None);
+    }
+}
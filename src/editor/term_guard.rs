@@ -0,0 +1,98 @@
+//! A guard around the terminal which restores it (showing the cursor again and leaving the
+//! alternate screen) however Sapling exits: by quitting, by panicking or by being sent `SIGTERM`
+//! or `SIGINT`.
+
+use nix::sys::signal::{raise, SigSet, Signal};
+use std::ops::Deref;
+use std::panic;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tuikit::prelude::*;
+
+/// The number of the signal that asked Sapling to close, or `0` if there hasn't been one
+static RECEIVED_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Owns the [`Term`], and restores the terminal when dropped
+pub struct TermGuard {
+    term: Arc<Term>,
+}
+
+impl TermGuard {
+    /// Takes control of the terminal.  `SIGTERM` and `SIGINT` are blocked on every thread (so this
+    /// must be called before any other threads are spawned) and are instead waited for by a
+    /// dedicated thread, which sends an [`Event::User`] to ask the mainloop to close.
+    pub fn new() -> tuikit::Result<TermGuard> {
+        let mut signals = SigSet::empty();
+        signals.add(Signal::SIGTERM);
+        signals.add(Signal::SIGINT);
+        signals.thread_block()?;
+
+        let term = Arc::new(Term::new()?);
+
+        let signal_term = Arc::downgrade(&term);
+        thread::spawn(move || {
+            if let Ok(signal) = signals.wait() {
+                RECEIVED_SIGNAL.store(signal as i32, Ordering::SeqCst);
+                if let Some(term) = signal_term.upgrade() {
+                    let _ = term.send_event(Event::User(()));
+                }
+            }
+        });
+
+        // Restore the terminal before the panic message is printed, or it would be printed to
+        // (and cleared along with) the alternate screen
+        let panic_term = Arc::downgrade(&term);
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Some(term) = panic_term.upgrade() {
+                restore(&term);
+            }
+            default_hook(info);
+        }));
+
+        Ok(TermGuard { term })
+    }
+
+    /// Hands the terminal back to the shell and stops Sapling with `SIGTSTP`, like pressing
+    /// `Ctrl-Z` in a cooked terminal would.  Once the shell resumes Sapling (e.g. with `fg`), the
+    /// terminal is put back into raw mode and this returns.  The caller should then redraw
+    /// everything, since the shell will have drawn over the screen.
+    pub fn suspend(&self) -> tuikit::Result<()> {
+        restore(&self.term);
+        raise(Signal::SIGTSTP)?;
+        self.term.restart()
+    }
+}
+
+impl Deref for TermGuard {
+    type Target = Term;
+
+    fn deref(&self) -> &Term {
+        &self.term
+    }
+}
+
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        restore(&self.term);
+    }
+}
+
+/// Hands the terminal back to the shell.  Errors are ignored, since this is only called whilst
+/// exiting and there's nothing useful to do about them.
+fn restore(term: &Term) {
+    // Show the cursor so that it isn't permanently disabled
+    // (see issue https://github.com/lotabout/tuikit/issues/28)
+    let _ = term.show_cursor(true);
+    let _ = term.present();
+    let _ = term.pause();
+}
+
+/// Returns the signal which asked Sapling to close, if there was one
+pub fn received_signal() -> Option<Signal> {
+    match RECEIVED_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Signal::from_c_int(signal).ok(),
+    }
+}
@@ -0,0 +1,229 @@
+//! The colours which text is drawn in, chosen by the [`SyntaxCategory`] of each
+//! [display token](crate::ast::display_token::DisplayToken).  The theme can be changed in the
+//! `[theme]` table of the config file, which maps each category to a description of its
+//! attributes, e.g.:
+//!
+//! ```toml
+//! [theme]
+//! keyword = "bold light-magenta"
+//! literal = "green"
+//! punctuation = "#808080 on black"
+//! subtree = "underline"
+//! ```
+
+use crate::ast::display_token::SyntaxCategory;
+use crate::messages::Message;
+use std::path::Path;
+use tuikit::prelude::{Attr, Color, Effect};
+
+/// The attributes which each kind of text is drawn with
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Theme {
+    /// The attributes of [`SyntaxCategory::Keyword`] text
+    pub keyword: Attr,
+    /// The attributes of [`SyntaxCategory::Literal`] text
+    pub literal: Attr,
+    /// The attributes of [`SyntaxCategory::Identifier`] text
+    pub identifier: Attr,
+    /// The attributes of [`SyntaxCategory::Punctuation`] text
+    pub punctuation: Attr,
+    /// The attributes of text which doesn't have a category
+    pub text: Attr,
+    /// The attributes added to the text of the cursor's descendants (on top of the attributes
+    /// of their categories), which show how much of the tree the cursor covers
+    pub subtree: Attr,
+}
+
+/// The names of the colours which can be used in a theme, in the order of their ANSI values
+const COLOUR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "light-black",
+    "light-red",
+    "light-green",
+    "light-yellow",
+    "light-blue",
+    "light-magenta",
+    "light-cyan",
+    "light-white",
+];
+
+/// The names of the effects which can be used in a theme
+const EFFECT_NAMES: [(&str, Effect); 5] = [
+    ("bold", Effect::BOLD),
+    ("dim", Effect::DIM),
+    ("underline", Effect::UNDERLINE),
+    ("blink", Effect::BLINK),
+    ("reverse", Effect::REVERSE),
+];
+
+impl Theme {
+    /// Returns the attributes of text in a given category, or of text without a category if
+    /// `category` is [`None`]
+    pub fn attr(&self, category: Option<SyntaxCategory>) -> Attr {
+        match category {
+            Some(SyntaxCategory::Keyword) => self.keyword,
+            Some(SyntaxCategory::Literal) => self.literal,
+            Some(SyntaxCategory::Identifier) => self.identifier,
+            Some(SyntaxCategory::Punctuation) => self.punctuation,
+            None => self.text,
+        }
+    }
+
+    /// Loads the theme from the `[theme]` table of `config`, the parsed TOML config file at
+    /// `path` (see [`read_config_file`](super::config::read_config_file)).  Categories which
+    /// aren't in the table keep their default attributes.  If the theme can't be loaded (e.g.
+    /// because it names a colour which doesn't exist) then the default theme is returned along
+    /// with a warning to show the user.
+    pub fn from_config(path: &Path, config: &toml::Value) -> (Theme, Option<Message>) {
+        match parse_config(config) {
+            Ok(theme) => (theme, None),
+            Err(e) => (Theme::default(), Some(load_error(path, e))),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            keyword: Attr::default().fg(Color::LIGHT_MAGENTA),
+            literal: Attr::default().fg(Color::GREEN),
+            identifier: Attr::default().fg(Color::CYAN),
+            punctuation: Attr::default().fg(Color::LIGHT_BLACK),
+            text: Attr::default().fg(Color::WHITE),
+            subtree: Attr::default().bg(Color::AnsiValue(236)),
+        }
+    }
+}
+
+/// Returns the warning shown when the theme in the config file at `path` can't be loaded
+fn load_error(path: &Path, reason: String) -> Message {
+    Message::CannotLoadTheme(path.to_string_lossy().into_owned(), reason)
+}
+
+/// Parses the `[theme]` table of a TOML config file, starting from the default theme.  Returns an
+/// error saying what is wrong with the config if it can't be parsed.
+fn parse_config(config: &toml::Value) -> Result<Theme, String> {
+    let mut theme = Theme::default();
+    let table = match config.get("theme") {
+        Some(toml::Value::Table(table)) => table,
+        Some(_) => return Err("'theme' isn't a table".to_string()),
+        None => return Ok(theme),
+    };
+    for (name, value) in table {
+        let slot = match name.as_str() {
+            "keyword" => &mut theme.keyword,
+            "literal" => &mut theme.literal,
+            "identifier" => &mut theme.identifier,
+            "punctuation" => &mut theme.punctuation,
+            "text" => &mut theme.text,
+            "subtree" => &mut theme.subtree,
+            _ => return Err(format!("'{}' isn't part of the theme", name)),
+        };
+        let description = value
+            .as_str()
+            .ok_or_else(|| format!("the attributes of '{}' aren't a string", name))?;
+        *slot = parse_attr(description)?;
+    }
+    Ok(theme)
+}
+
+/// Parses a description of some attributes, which is a list of effects and a colour (e.g.
+/// `bold red`), optionally followed by `on` and a background colour
+fn parse_attr(description: &str) -> Result<Attr, String> {
+    let mut attr = Attr::default();
+    let mut words = description.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == "on" {
+            let bg = words
+                .next()
+                .ok_or_else(|| format!("'{}' has no colour after 'on'", description))?;
+            attr.bg = parse_colour(bg)?;
+        } else if let Some((_, effect)) = EFFECT_NAMES.iter().find(|(name, _)| *name == word) {
+            attr.effect |= *effect;
+        } else {
+            attr.fg = parse_colour(word)?;
+        }
+    }
+    Ok(attr)
+}
+
+/// Parses a colour, which is either one of [`COLOUR_NAMES`], an ANSI colour number, `default` or
+/// an RGB colour written as `#rrggbb`
+pub fn parse_colour(word: &str) -> Result<Color, String> {
+    if word == "default" {
+        return Ok(Color::Default);
+    }
+    if let Some(i) = COLOUR_NAMES.iter().position(|name| *name == word) {
+        return Ok(Color::AnsiValue(i as u8));
+    }
+    if let Ok(value) = word.parse::<u8>() {
+        return Ok(Color::AnsiValue(value));
+    }
+    let hex = word.strip_prefix('#').filter(|hex| hex.len() == 6);
+    let channel = |i: usize| hex.and_then(|hex| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok());
+    match (channel(0), channel(2), channel(4)) {
+        (Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+        _ => Err(format!("'{}' isn't a colour", word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_attr, Theme};
+    use crate::ast::display_token::SyntaxCategory;
+    use tuikit::prelude::{Attr, Color, Effect};
+
+    /// Parses the theme from the text of a TOML config file
+    fn parse_config(text: &str) -> Result<Theme, String> {
+        let config: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        super::parse_config(&config)
+    }
+
+    #[test]
+    fn attrs() {
+        assert_eq!(
+            parse_attr("bold red"),
+            Ok(Attr::default().fg(Color::RED).effect(Effect::BOLD))
+        );
+        assert_eq!(
+            parse_attr("light-blue on 236"),
+            Ok(Attr::default()
+                .fg(Color::LIGHT_BLUE)
+                .bg(Color::AnsiValue(236)))
+        );
+        assert_eq!(
+            parse_attr("#ff8000 underline"),
+            Ok(Attr::default()
+                .fg(Color::Rgb(255, 128, 0))
+                .effect(Effect::UNDERLINE))
+        );
+        assert!(parse_attr("mauve").is_err());
+        assert!(parse_attr("#12345").is_err());
+        assert!(parse_attr("red on").is_err());
+    }
+
+    #[test]
+    fn config() {
+        let theme = parse_config("[theme]\nkeyword = \"yellow\"\npunctuation = \"dim\"\n").unwrap();
+        assert_eq!(
+            theme.attr(Some(SyntaxCategory::Keyword)),
+            Attr::default().fg(Color::YELLOW)
+        );
+        assert_eq!(
+            theme.attr(Some(SyntaxCategory::Punctuation)),
+            Attr::default().effect(Effect::DIM)
+        );
+        // Categories which aren't set keep their defaults
+        assert_eq!(theme.literal, Theme::default().literal);
+        assert_eq!(parse_config("[keymap]\n"), Ok(Theme::default()));
+        assert!(parse_config("[theme]\nnumber = \"red\"\n").is_err());
+        assert!(parse_config("[theme]\nliteral = 3\n").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Watch expressions, whose values are shown in a pane and recomputed after every edit so that
+//! the user gets live feedback whilst editing.
+
+/// A single watched expression, along with its latest value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Watch {
+    /// The text of the expression
+    pub expression: String,
+    /// The result of the latest evaluation, or [`None`] if it hasn't been evaluated yet
+    pub value: Option<Result<String, String>>,
+}
+
+/// The list of watched expressions, in the order that they were added
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Watches {
+    watches: Vec<Watch>,
+}
+
+impl Watches {
+    /// Creates an empty list of watches
+    pub fn new() -> Watches {
+        Watches::default()
+    }
+
+    /// Starts watching an expression.  Its value is computed by the next [`Watches::update`].
+    pub fn add(&mut self, expression: String) {
+        self.watches.push(Watch {
+            expression,
+            value: None,
+        });
+    }
+
+    /// Stops watching the expression at some index, returning `false` if there is no such watch
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.watches.len() {
+            return false;
+        }
+        self.watches.remove(index);
+        true
+    }
+
+    /// Stops watching every expression
+    pub fn clear(&mut self) {
+        self.watches.clear();
+    }
+
+    /// Returns `true` if no expressions are being watched
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Returns the number of expressions being watched
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    /// Returns the watches, in the order that they were added
+    pub fn iter(&self) -> std::slice::Iter<'_, Watch> {
+        self.watches.iter()
+    }
+
+    /// Recomputes the value of every watch, using a function which evaluates an expression (and
+    /// returns [`None`] if it can't be evaluated at all)
+    pub fn update(&mut self, mut evaluate: impl FnMut(&str) -> Option<Result<String, String>>) {
+        for watch in &mut self.watches {
+            watch.value = evaluate(&watch.expression);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watches;
+
+    #[test]
+    fn update() {
+        let mut watches = Watches::new();
+        watches.add("a".to_string());
+        watches.add("bad".to_string());
+        assert_eq!(watches.iter().next().unwrap().value, None);
+        let mut evaluations = 0;
+        watches.update(|expression| {
+            evaluations += 1;
+            Some(match expression {
+                "bad" => Err("oops".to_string()),
+                e => Ok(e.to_uppercase()),
+            })
+        });
+        assert_eq!(evaluations, 2);
+        let values: Vec<_> = watches.iter().map(|w| w.value.clone()).collect();
+        assert_eq!(
+            values,
+            vec![Some(Ok("A".to_string())), Some(Err("oops".to_string()))]
+        );
+        assert!(!watches.remove(2));
+        assert!(watches.remove(0));
+        assert_eq!(watches.len(), 1);
+        assert_eq!(watches.iter().next().unwrap().expression, "bad");
+        watches.clear();
+        assert!(watches.is_empty());
+    }
+}
@@ -1,26 +1,145 @@
 pub mod arena;
 pub mod ast;
+pub mod cli;
 pub mod editable_tree;
 pub mod editor;
+pub mod messages;
 
 use crate::arena::Arena;
-use crate::ast::json::JSONFormat;
+use crate::ast::fixtures::{self, Shape};
+use crate::ast::json::{JSONFormat, JSON};
 use crate::ast::test_json::TestJSON;
+use crate::ast::Ast;
+use crate::cli::Args;
 use crate::editable_tree::{dag::DAG, EditableTree};
-use crate::editor::Editor;
+use crate::editor::config::{self, Config, ProjectConfig};
+use crate::editor::demo::Demo;
+use crate::editor::theme::Theme;
+use crate::editor::{keymap, log, Editor, KeyMap};
+use crate::messages::Message;
+
+/// Print an error about the command line arguments, and exit before the terminal is taken over
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("sapling: {}", error);
+    eprintln!("{}", cli::USAGE);
+    std::process::exit(2);
+}
 
 fn main() {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| exit_with_usage(&e));
+    match (args.language(), &args.path) {
+        (Some(language), _) if language != JSON::language_name() => exit_with_usage(&format!(
+            "cannot edit {} files (only {} is supported)",
+            language,
+            JSON::language_name()
+        )),
+        (None, Some(path)) => exit_with_usage(&format!(
+            "cannot tell what language '{}' is written in, so give it with '--language'",
+            path
+        )),
+        _ => {}
+    }
+    if let Some(name) = &args.keymap {
+        if keymap::preset(name).is_none() {
+            exit_with_usage(&format!(
+                "there is no keymap called '{}' (the presets are {})",
+                name,
+                keymap::PRESET_NAMES.join(", ")
+            ));
+        }
+    }
+
+    // The demo script is read before the terminal is taken over, so that errors can be printed
+    let demo = args.demo.as_ref().map(|path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Demo::parse(&text, std::time::Instant::now()))
+            .unwrap_or_else(|e| {
+                eprintln!("sapling: cannot play demo '{}': {}", path, e);
+                std::process::exit(1);
+            })
+    });
+
     // Create an empty arena for Sapling to use
     let arena = Arena::new();
-    // For the time being, start the editor with some pre-made JSON
-    let root = TestJSON::Array(vec![
-        TestJSON::True,
-        TestJSON::False,
-        TestJSON::Object(vec![("value".to_string(), TestJSON::True)]),
-    ])
+    // Without a file, start the editor with a sample tree.  With one, start with an empty
+    // object which is replaced as soon as the start of the file has been parsed (or, for a new
+    // document, by the template once the editor has loaded the user's config).
+    let root = if args.path.is_some() || args.new {
+        TestJSON::Object(Vec::new())
+    } else {
+        fixtures::json(Shape::DEMO)
+    }
     .add_to_arena(&arena);
 
+    // The config file is read once, and everything is loaded from the same version of it
+    let config_path = config::config_path();
+    let (config_file, file_warning) = match &config_path {
+        Some(path) => config::read_config_file(path),
+        None => (None, None),
+    };
+    let config_file = config_path.as_deref().zip(config_file.as_ref());
+    let preset = args.keymap.as_deref();
+    let (keymap, keymap_warning) = match config_file {
+        Some((path, file)) => KeyMap::from_config(path, file, preset),
+        None => (
+            preset
+                .and_then(keymap::preset)
+                .unwrap_or_else(editor::default_keymap),
+            None,
+        ),
+    };
+    let (theme, theme_warning) = match config_file {
+        Some((path, file)) => Theme::from_config(path, file),
+        None => (Theme::default(), None),
+    };
     let mut tree = DAG::new(&arena, root);
-    let editor = Editor::new(&mut tree, JSONFormat::Pretty, editor::default_keymap());
+    let (config, config_warning) = match config_file {
+        Some((path, file)) => Config::from_config(path, file),
+        None => (Config::default(), None),
+    };
+    let mut config = Config {
+        log_file_path: config.log_file_path.or_else(log::default_file_path),
+        theme,
+        ..config
+    };
+    // A project's shared settings override the user's own
+    let working_dir = std::env::current_dir().unwrap_or_default();
+    let project_warning =
+        config::find_project_config(&args.project_dir(&working_dir)).and_then(|path| {
+            match ProjectConfig::load(&path) {
+                Ok(project) => {
+                    project.apply(&mut config);
+                    None
+                }
+                Err(e) => Some(Message::CannotLoadProjectConfig(
+                    path.to_string_lossy().into_owned(),
+                    e,
+                )),
+            }
+        });
+    let mut editor = Editor::new(&mut tree, JSONFormat::Pretty, keymap, config);
+    let warnings = file_warning
+        .into_iter()
+        .chain(keymap_warning)
+        .chain(theme_warning)
+        .chain(config_warning);
+    for warning in warnings.chain(project_warning) {
+        editor.warn(warning);
+    }
+    if let Some(path) = &args.path {
+        editor.open(path);
+    }
+    if args.new {
+        editor.new_document();
+    }
+    if let Some(demo) = demo {
+        editor.play_demo(demo);
+    }
     editor.run();
+    // Exit with the conventional status for being killed by a signal, now that the terminal has
+    // been restored
+    if let Some(signal) = editor::term_guard::received_signal() {
+        std::process::exit(128 + signal as i32);
+    }
 }
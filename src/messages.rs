@@ -0,0 +1,1907 @@
+//! The catalog of every message that Sapling shows to the user.  Messages are identified by
+//! [`Message`] values rather than English strings, so that they can be translated by looking them
+//! up in a [`Catalog`] for the user's locale.
+
+use std::collections::HashMap;
+
+/// Every message that Sapling can show to the user, along with the values that are substituted
+/// into it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Message {
+    /* CURSOR MOVEMENT */
+    /// The cursor can't move down because it has no children
+    CannotMoveDown,
+    /// The cursor can't move up because it's at the root
+    CannotMoveUp,
+    /// The cursor can't move back because it's on the first child of its parent
+    CannotMovePrev,
+    /// The cursor can't move on because it's on the last child of its parent
+    CannotMoveNext,
+    /// The cursor can't move to a sibling because it's at the root
+    CannotMoveToSiblingOfRoot,
+    /// The cursor can't jump to a path because the path doesn't point to a node
+    CannotJump,
+    /// There are no older cursor positions to move back to
+    NoOlderPositions,
+    /// There are no newer cursor positions to move forward to
+    NoNewerPositions,
+    /// There are no older edit positions to move back to
+    NoOlderEdits,
+    /// There are no newer edit positions to move forward to
+    NoNewerEdits,
+
+    /* EDITING */
+    /// The cursor is being replaced with a node, given by its `char` and its debug representation
+    Replacing(char, String),
+    /// The cursor can't be replaced by the node represented by a `char`
+    CannotReplace(char),
+    /// A child represented by a `char` is being inserted
+    Inserting(char),
+    /// A child represented by a `char` can't be inserted into the cursor
+    CannotInsert(char),
+    /// A key chosen from the completion menu can't be inserted into the cursor
+    CannotInsertKey(String),
+    /// A schema was loaded from the file at the given path
+    SchemaLoaded(String),
+    /// The schema at a path (the first string) couldn't be loaded, for the given reason
+    CannotLoadSchema(String, String),
+    /// The label of the progress of opening the file at the given path
+    Opening(String),
+    /// The whole of the file at the given path has been opened
+    Opened(String),
+    /// The given number of bytes were written to the file at a path
+    Written(String, usize),
+    /// The file at a path (the first string) couldn't be written, for the given reason
+    CannotWrite(String, String),
+    /// The user tried to write a buffer which doesn't have a file, without giving a path
+    NoFileName,
+    /// The user used `%` in a command, but the buffer doesn't have a file
+    NoFileToSubstitute,
+    /// A new document was started from the template for the given language
+    NewDocument(String),
+    /// The given language has no template for new documents
+    NoTemplate(String),
+    /// The template for new documents in the given language couldn't be parsed
+    InvalidTemplate(String),
+    /// The file at a path (the first string) couldn't be opened, for the given reason
+    CannotOpen(String, String),
+    /// Files can't be opened in the given language
+    CannotOpenLanguage(String),
+    /// The given number of parts of an opened file couldn't be parsed, so they were kept as text
+    /// and put in the quickfix list
+    ParseErrorsRecovered(usize),
+    /// The part of a file starting on a given line couldn't be parsed, for the given reason
+    ParseError(usize, String),
+    /// The file at the given path couldn't be opened because it has nothing in it
+    EmptyFile(String),
+    /// The given number of parts of the tree aren't valid, e.g. because they are text which
+    /// couldn't be parsed
+    TreeInvalid(usize),
+    /// Every part of the tree is valid again
+    TreeValid,
+    /// Text being parsed has nothing in it
+    EmptyDocument,
+    /// Text being parsed ended before its value was complete
+    IncompleteDocument,
+    /// Text being parsed has the given text where it can't go
+    UnexpectedText(String),
+    /// Text being parsed carries on after the end of its value
+    TextAfterDocument,
+    /// Text being parsed is missing a value
+    ExpectedValue,
+    /// Text being parsed has a comma which doesn't come after a value
+    ExpectedValueBeforeComma,
+    /// Text being parsed has a comma before the end of an array or object
+    CommaBeforeEnd,
+    /// Text being parsed is missing a comma or the given closing delimiter
+    ExpectedCommaOr(char),
+    /// Text being parsed is missing the key of a field
+    ExpectedKey,
+    /// Text being parsed is missing the colon after the key of a field
+    ExpectedColon,
+    /// Text being parsed has an array which isn't closed
+    UnclosedArray,
+    /// Text being parsed has an object which isn't closed
+    UnclosedObject,
+    /// Text being parsed has a string which isn't closed
+    UnclosedString,
+    /// Text being parsed has a comment which isn't closed
+    UnclosedComment,
+    /// Text being parsed has a string with the given char after a `\`, which isn't an escape
+    /// sequence
+    InvalidEscape(char),
+    /// Text being parsed has a string with `\u` followed by the given text, which isn't a char
+    InvalidUnicodeEscape(String),
+    /// Describes the language command which sorts the keys of objects
+    DescribeSortKeys,
+    /// Describes the language command which wraps a value in an array
+    DescribeWrapInArray,
+    /// Describes the language command which parses text which couldn't be parsed before
+    DescribeReparse,
+    /// A language command which sorts objects was run where there are no objects
+    NoObjectsToSort,
+    /// A language command tried to wrap the key of a field
+    CannotWrapKey,
+    /// A language command which reparses text was run on something other than error text
+    NotErrorText,
+    /// Text still couldn't be parsed when it was reparsed
+    StillInvalid,
+    /// Text reparsed in an object was something other than one field
+    NotSingleField,
+    /// Text being parsed is missing the given char
+    ExpectedChar(char),
+    /// A query has nothing in it
+    EmptyQuery,
+    /// A query has the given text where an index should be
+    NotAnIndex(String),
+    /// A reference points to the given location in another document
+    ExternalReference(String),
+    /// A reference has the given text, which isn't a pointer
+    NotAPointer(String),
+    /// A reference has the given pointer, which doesn't point to anything
+    DanglingPointer(String),
+    /// A reference being renamed doesn't point to the value of a field
+    NotAFieldValue,
+    /// A field couldn't be renamed, because its object already has a field with the given name
+    FieldExists(String),
+    /// The user tried to edit whilst the file at the given path was still being opened
+    CannotEditWhileOpening(String),
+    /// The user tried to write whilst the file at the given path was still being opened
+    CannotWriteWhileOpening(String),
+    /// The user tried to write over the file at the given path, which couldn't be fully opened
+    CannotWriteFailedOpen(String),
+    /// The user tried to edit the parent of the root
+    RootHasNoParent,
+    /// The user tried to slurp when the cursor's node is the last of its siblings
+    NothingToSlurp,
+    /// The user tried to barf out of a node with no children
+    NothingToBarf,
+    /// The user tried to transpose the cursor's node with a sibling that doesn't exist
+    NothingToTranspose,
+    /// The user tried to join the cursor's node with a sibling of a different kind (the kinds of
+    /// the two nodes are given)
+    CannotJoin(String, String),
+    /// The user tried to join the cursor's node when there's no next sibling, or neither node has
+    /// any children
+    NothingToJoin,
+    /// The user tried to sort or group the children of a node with no children
+    NothingToSort,
+    /// The user tried to unwrap a node with no children
+    NothingToUnwrap,
+    /// The cursor's node can't be wrapped in the node represented by a `char`
+    CannotWrap(char),
+    /// The cursor's node can't be converted into the node represented by a `char`, keeping its
+    /// children
+    CannotConvert(char),
+    /// The user tried to convert the cursor's node into the kind it already is
+    AlreadyOfKind(String),
+    /// None of the transformations in the transform menu can be made to the cursor's node
+    NoTransforms,
+    /// The config named a format style which the given language doesn't have
+    NoSuchFormatStyle(String, String),
+    /// Describes wrapping the cursor in a new node of the given kind, in the transform menu
+    DescribeWrap(String),
+    /// Describes replacing the cursor with its children, in the transform menu
+    DescribeUnwrap,
+    /// Describes replacing the cursor's parent with the cursor, in the transform menu
+    DescribeRaise,
+    /// Describes turning the cursor into the given kind of node, in the transform menu
+    DescribeConvert(String),
+    /// Describes sorting the cursor's children, in the transform menu
+    DescribeSort,
+    /// Describes moving the cursor's next sibling into it, in the transform menu
+    DescribeSlurp,
+    /// Describes moving the cursor's last child out after it, in the transform menu
+    DescribeBarf,
+    /// Describes merging the cursor's next sibling into it, in the transform menu
+    DescribeJoin,
+    /// Describes swapping the cursor with its next sibling, in the transform menu
+    DescribeTransposeNext,
+    /// Describes swapping the cursor with its previous sibling, in the transform menu
+    DescribeTransposePrev,
+    /// The user tried to sort or group by a key which none of the children have
+    NoSuchKey(String),
+    /// The children of the cursor's node were grouped into the given number of groups, by the
+    /// given key
+    Grouped(usize, String),
+    /// An edit would have put a node somewhere that it isn't allowed
+    InvalidStructure,
+    /// The next edit will be made to every child of the node of the given kind
+    BatchEditArmed(String),
+    /// A batch edit was made to the given number of children
+    BatchEdited(usize),
+    /// A batch edit was cancelled before any edit was made
+    BatchEditCancelled,
+    /// An edit was made outside the node that the batch edit was waiting for, so it was only
+    /// made once
+    EditOutsideBatch,
+    /// A batch edit couldn't be made to any of the children
+    NothingToBatchEdit,
+    /// An edit can't be made to every child of a batch, so it was only made once
+    EditNotBatched,
+    /// An edit would change the node that a batch edit was armed on, rather than its children
+    EditChangesBatchNode,
+    /// Some number of nodes were copied to the clipboard
+    Copied(usize),
+    /// Some number of nodes were copied to the given register
+    CopiedToRegister(usize, char),
+    /// The user tried to paste from a register which is empty
+    EmptyRegister(char),
+    /// The user tried to cut the root, which has no parent to remove it from
+    CannotCutRoot,
+    /// The clipboard couldn't be shared with other instances, because of the given IO error
+    ClipboardNotShared(String),
+    /// The user tried to paste, but nothing has been copied
+    NothingToPaste,
+    /// The clipboard only contains plain text, which can't be parsed into nodes
+    CannotPasteText,
+    /// Text was pasted which stopped part-way through a node, so it was completed with some number
+    /// of holes which need reviewing
+    CompletedPaste(usize),
+    /// The clipboard's structure doesn't describe valid nodes
+    CannotPasteStructure,
+    /// Nodes copied from a buffer of one language were converted into another language to be
+    /// pasted
+    ConvertedPaste(String, String),
+    /// Nodes copied from a buffer of one language can't be converted into another language, so
+    /// their text was pasted instead
+    PastedAsText(String, String),
+    /// Nodes copied from a buffer of one language can't be converted into another language, and
+    /// their text isn't valid in that language either
+    CannotConvertPaste(String, String),
+    /// The user tried to repeat the last replacement, but nothing has been replaced
+    NothingToRepeat,
+    /// Asks the user whether or not to replace some number of nodes
+    ConfirmReplace(usize),
+    /// Asks the user whether or not to paste some number of nodes, which are nested to some
+    /// depth and whose text starts with the given line
+    ConfirmPaste(usize, usize, String),
+    /// The file at a path (the first string) couldn't be read, for a reason (the second string)
+    CannotRead(String, String),
+    /// Asks the user whether or not to make an edit which throws away some number of changes that
+    /// could be redone
+    ConfirmDiscardRedo(usize),
+    /// Asks the user whether or not to quit without saving the changes made since the file was
+    /// last saved
+    ConfirmQuit,
+    /// Asks whether to throw away the unsaved changes to start a new document
+    ConfirmNewDocument,
+    /// The tree from before a number of changes were thrown away was copied to the clipboard
+    RedoSnapshotted(usize),
+    /// The user cancelled an operation at a prompt
+    Cancelled,
+
+    /* HISTORY */
+    UndoSuccessful,
+    NothingToUndo,
+    RedoSuccessful,
+    NothingToRedo,
+    /// Some number of changes were undone at once
+    UndoneSteps(usize),
+    /// Some number of changes were redone at once
+    RedoneSteps(usize),
+    /// The tree was moved back or forward to the state which was last saved
+    ReturnedToSaved,
+    /// The user tried to return to the saved state when the tree is already in it
+    AlreadySaved,
+    /// The saved state was thrown away by an edit made after undoing past it
+    SavedStateLost,
+    /// The summary of a change which replaced the node at a path with a node of some kind
+    SummaryReplace(String, String),
+    /// The summary of a change which inserted a node of some kind into the node at a path
+    SummaryInsert(String, String),
+    /// The summary of a change which pasted a node of some kind at a path
+    SummaryPaste(String, String),
+    /// The summary of a change which slurped a sibling into the node at a path
+    SummarySlurp(String),
+    /// The summary of a change which barfed a child out of the node at a path
+    SummaryBarf(String),
+    /// The summary of a change which moved a node to a path by swapping it with a sibling
+    SummaryTranspose(String),
+    /// The summary of a change which joined a sibling into the node at a path
+    SummaryJoin(String),
+    /// The summary of a change which sorted the children of the node at a path
+    SummarySort(String),
+    /// The summary of a change which grouped the children of the node at a path
+    SummaryGroup(String),
+    /// The summary of a change which wrapped the node at a path in a node of some kind
+    SummaryWrap(String, String),
+    /// The summary of a change which replaced a node with its children, the first of which is now
+    /// at a path
+    SummaryUnwrap(String),
+    /// The summary of a change which replaced the parent of a node with that node, which is now
+    /// at a path
+    SummaryRaise(String),
+    /// The summary of a change which converted the node at a path into a node of some kind
+    SummaryConvert(String, String),
+    /// The summary of a change which cut the node at a path
+    SummaryCut(String),
+    /// The summary of a change which renamed the symbol at a path, and its references
+    SummaryRename(String),
+    /// The summary of a change which ran a language command (the second string) at a path
+    SummaryLanguageCommand(String, String),
+    /// The summary of a batch edit, which made a change (the summary of which is given) to some
+    /// number of children
+    SummaryBatch(String, usize),
+    /// A change (given by its summary) was undone
+    UndoneChange(String),
+    /// A change (given by its summary) was redone
+    RedoneChange(String),
+    /// The title of the history view
+    HistoryTitle,
+    /// A line of the history view, giving an entry's number, how long ago it was made and a
+    /// summary of what it changed
+    HistoryEntry(usize, String, String),
+    /// The summary of the first entry of the history, which is the tree that was loaded
+    HistoryOriginal,
+    /// Every entry of the history (of which there are the given number) restores its tree and
+    /// cursor
+    HistoryConsistent(usize),
+    /// Moving to the history entry with the given number didn't restore its tree
+    HistoryWrongRoot(usize),
+    /// Moving to the history entry with the given number didn't restore its cursor
+    HistoryWrongCursor(usize),
+    /// The cursor of the history entry with the given number isn't in that entry's tree
+    HistoryInvalidCursor(usize),
+    /// Undoing or redoing from the history entry with the given number went to the wrong entry
+    HistoryWrongStep(usize),
+    /// The heading of the entries shown by `:journal`, giving the path of the journal file
+    JournalHeading(String),
+    /// An entry of the journal, giving how many seconds into the session it was and the action
+    JournalEntry(String, String),
+    /// No journal is being kept for this session
+    NoJournal,
+    /// The journal couldn't be written for the given reason, so it's no longer being kept
+    CannotWriteJournal(String),
+    /// The journal couldn't be read for the given reason
+    CannotReadJournal(String),
+    /// The summary of an entry of the history which didn't change the tree
+    HistoryUnchanged,
+
+    /* KEYMAP */
+    /// A key is bound to more than one command (given as a comma-separated list)
+    DuplicateBinding(String, String),
+    /// One key sequence (the first string) is bound, but is also the start of another binding
+    PrefixBinding(String, String),
+    /// Some commands (given as a comma-separated list) aren't bound to any key
+    UnboundCommands(String),
+    /// The keymap binds a key to several different commands, so it can't be used
+    AmbiguousKeymap,
+    /// The keymap in the config file (the first string) couldn't be loaded for the given reason,
+    /// so the default keymap is used instead
+    CannotLoadKeymap(String, String),
+    /// The active keymap was written to the file at the given path
+    KeymapExported(String),
+    /// The active keymap couldn't be written to a file (the first string) for the given reason
+    CannotExportKeymap(String, String),
+    /// The keymap was replaced by the one in the file at the given path
+    KeymapImported(String),
+    /// The keymap couldn't be loaded from a file (the first string) for the given reason, so it
+    /// wasn't changed
+    CannotImportKeymap(String, String),
+    /// The theme in the config file (the first string) couldn't be loaded for the given reason,
+    /// so the default theme is used instead
+    CannotLoadTheme(String, String),
+    /// The project config file (the first string) couldn't be loaded for the given reason, so
+    /// only the user's own settings are used
+    CannotLoadProjectConfig(String, String),
+    /// The settings in the config file (the first string) couldn't be loaded for the given
+    /// reason, so the default settings are used instead
+    CannotLoadConfig(String, String),
+
+    /* MACROS */
+    /// The user tried to play or edit a macro from an empty register
+    EmptyMacroRegister(char),
+    /// A macro was stored in a register
+    MacroSet(char),
+    /// The keys that the user presses are being recorded into the given macro register
+    RecordingMacro(char),
+    /// The given number of keys were recorded into a macro register
+    MacroRecorded(usize, char),
+    /// The user tried to stop recording a macro when no macro was being recorded
+    NotRecording,
+    /// Shown in the bottom bar whilst a macro is being recorded into the given register
+    RecordingIndicator(char),
+    /// Shown in the bottom bar whilst the tree differs from the one which was last saved
+    ModifiedIndicator,
+    /// Key presses now type into the text of the node under the cursor
+    TypingText,
+    /// Shown in the status bar's mode segment whilst key presses are typing into the text of a
+    /// node
+    TypingIndicator,
+    /// Shown in the bottom bar whilst a command (the first string) is waiting to see if it's the
+    /// start of a longer binding, with the seconds left until it runs anyway (the second string)
+    CommandCountdown(String, String),
+    /// The node under the cursor (of the given kind) has no text which can be typed into
+    NoTextToEdit(String),
+    /// The text of a macro contains something which isn't a valid key
+    InvalidMacroKey(String),
+    /// Too many macros were playing at once (probably because a macro plays itself)
+    MacrosTooDeep,
+
+    /* COMMANDS */
+    /// The user typed a command which doesn't exist
+    NotACommand(String),
+    /// Shown in the hints for an incomplete command next to a key which starts some number of
+    /// longer bindings
+    HintPrefix(usize),
+    /// A sequence of keys (the first string) was bound to a command (the second string)
+    KeyMapped(String, String),
+    /// The cursor has no ancestor of the given kind
+    NoEnclosing(String),
+    /// The user searched for an empty string
+    EmptySearch,
+    /// A search found no matches
+    NoMatches(String),
+    /// The user asked for the next match of a search before searching for anything
+    NoSearch,
+    /// The language being edited (given by name) can't be queried
+    CannotQuery(String),
+    /// A query wasn't valid, for the given reason
+    InvalidQuery(String),
+    /// A query matched the given number of nodes, which were put in the quickfix list
+    QueryMatches(usize),
+    /// The cursor moved to an entry (the first number) of the quickfix list (whose length is the
+    /// second number)
+    QuickfixEntry(usize, usize),
+    /// The user tried to step through the quickfix list when it's empty
+    QuickfixEmpty,
+    /// The given number of nodes have duplicate siblings, and were put in the quickfix list
+    DuplicatesFound(usize),
+    /// No node has a duplicate sibling
+    NoDuplicates,
+    /// The field with the given key was copied from the previous record
+    CopiedFromAbove(String),
+    /// The field with the given key was copied from the next record
+    CopiedFromBelow(String),
+    /// The cursor isn't inside a field which the neighbouring record also has
+    NoCorrespondingField,
+    /// The buffer will be saved in the given format (an encoding and line ending)
+    FileFormatSet(String),
+    /// The placeholder for a node whose given number of children are too deep to be rendered
+    Elided(usize),
+    /// The placeholder for a folded node with the given number of children
+    Folded(usize),
+    /// The node under the cursor, of the given kind, has no children to fold
+    NothingToFold(String),
+    /// The given number of folded nodes were unfolded
+    FoldsOpened(usize),
+    /// Only the given number of levels below the cursor's path will be rendered
+    MaxDepthSet(usize),
+    /// The whole tree will be rendered, however deep it is
+    MaxDepthOff,
+    /// The log file couldn't be written for the given reason, so it is no longer being written
+    CannotWriteLogFile(String),
+    /// The screen will be redrawn at most the given number of times per second
+    MaxFpsSet(usize),
+    /// Only log entries at or above the level with the given name will be shown
+    LogLevelSet(String),
+    /// The cursor will land at the place with the given name after inserts and replaces
+    LandingSet(String),
+    /// Shown on the last line of a scrolled log pane, with the number of newer entries below it
+    NewerLogEntries(usize),
+    /// Some number of parts of the tree are nested more than the given number of levels deep,
+    /// which makes them slow to draw
+    TreeTooDeep(usize, usize),
+    /// Some number of nodes have more than the given number of children, which makes them slow
+    /// to draw
+    TreeTooWide(usize, usize),
+    /// Asks whether to fold the given number of parts of the tree which go over its limits
+    ConfirmFoldOversized(usize),
+    /// The given number of parts of the tree which went over its limits were folded
+    FoldedOversized(usize),
+    /// No part of the tree goes over its limits, so there's nothing to fold
+    NothingOversized,
+    /// Trees nested more than the given number of levels deep will be warned about
+    DepthLimitSet(usize),
+    /// Nodes with more than the given number of children will be warned about
+    ChildLimitSet(usize),
+    /// Line numbers are shown in the gutter of the tree view
+    LineNumbersOn,
+    /// Line numbers are no longer shown
+    LineNumbersOff,
+    /// Lines will be joined wherever they fit within the given number of columns
+    LineWidthSet(usize),
+    /// Lines will be broken wherever the format style puts them, however long they are
+    LineWidthOff,
+    /// The given language can't be laid out to fit a width
+    CannotSetLineWidth(String),
+    /// Commands which start longer bindings run after the given number of milliseconds without a
+    /// key press
+    CommandTimeoutSet(usize),
+    /// Commands which start longer bindings wait for the next key however long it takes
+    CommandTimeoutOff,
+    /// A character (the first string) of the buffer can't be encoded in an encoding (the second
+    /// string)
+    CannotEncode(String, String),
+    /// The user tried to rename a node which isn't a symbol
+    NotASymbol,
+    /// A symbol couldn't be renamed, for the given reason
+    CannotRename(String),
+    /// A symbol was renamed to the given name, along with the given number of references
+    Renamed(String, usize),
+    /// The buffer's language has no command with the given name
+    NoSuchLanguageCommand(String),
+    /// A language command (the first string) couldn't be run, for the given reason
+    LanguageCommandFailed(String, String),
+    /// The language command with the given name was run
+    RanLanguageCommand(String),
+    /// The user tried to go to the definition of a node which isn't a reference
+    NotAReference,
+    /// A reference couldn't be resolved, for the given reason
+    InvalidReference(String),
+    /// Nothing refers to the node under the cursor
+    NoReferences,
+    /// The given number of references were found and put in the quickfix list
+    ReferencesFound(usize),
+    /// The selected nodes have no leaves to compute statistics of
+    NothingToAggregate,
+    /// Some number of leaves (the first number), with some number of distinct values (the second
+    /// number), were found under some number of selected nodes (the third number)
+    StatsCount(usize, usize, usize),
+    /// The most common values of the leaves, with their counts
+    StatsCommon(String),
+    /// Some number of leaves are numbers, which add up to the given sum
+    StatsNumbers(usize, String),
+    /// The smallest and largest values of the leaves
+    StatsRange(String, String),
+
+    /* LANGUAGES */
+    /// The user tried to switch to a language (the first name) which this buffer can't be edited
+    /// as, since it's being edited as another language (the second name)
+    UnsupportedLanguage(String, String),
+    /// The buffer's text couldn't be parsed as the given language
+    CannotReparse(String),
+    /// The buffer was re-parsed as the given language
+    Reparsed(String),
+    /// Re-reading the buffer in the given language didn't change it
+    LanguageUnchanged(String),
+
+    /* EMBEDDED LANGUAGES */
+    /// The node under the cursor doesn't contain text in the given language
+    NotEmbedded(String),
+    /// A nested session was opened to edit the text of the node under the cursor
+    EditingEmbedded,
+    /// The text edited in a nested session was written back into its node
+    EmbeddedWritten,
+    /// The text edited in a nested session can't be stored in its node
+    CannotWriteEmbedded,
+
+    /* EVENTS */
+    /// Edit events can't be written, because of the given IO error
+    EventsUnavailable(String),
+
+    /* REPL */
+    /// The REPL was opened
+    ReplOpened,
+    /// The REPL was closed
+    ReplClosed,
+    /// An expression was evaluated to a result
+    Evaluated(String, String),
+    /// An expression couldn't be evaluated, for the given reason
+    EvaluationFailed(String, String),
+    /// The language being edited (given by name) can't evaluate expressions
+    CannotEvaluate(String),
+
+    /* WATCHES */
+    /// The title of the watches pane
+    WatchesTitle,
+    /// The user tried to remove a watch (given by its number) which doesn't exist
+    NoSuchWatch(usize),
+    /// Every watch was removed
+    WatchesCleared,
+    /// The title of the pins pane
+    PinsTitle,
+    /// The node at the given path was pinned
+    Pinned(String),
+    /// The node at the given path was unpinned to make room for another pin
+    Unpinned(String),
+    /// The user tried to unpin a node (given by its number) which isn't pinned
+    NoSuchPin(usize),
+    /// Every node was unpinned
+    PinsCleared,
+    /// Shown in place of the text of a pinned node which no longer exists
+    PinMissing,
+
+    /* BOOKMARKS */
+    /// A bookmark was set on the node at the given path
+    BookmarkSet(char, String),
+    /// The user tried to jump to a bookmark which hasn't been set
+    NoSuchBookmark(char),
+    /// The user tried to jump to a bookmark whose node no longer exists
+    BookmarkMissing(char),
+    /// The user jumped to a bookmark whose node has changed since the bookmark was set
+    StaleBookmark(char),
+    /// The nodes of some bookmarks (given as a list of names) have changed since they were saved,
+    /// e.g. because the file was edited by another program
+    StaleBookmarks(String),
+    /// Bookmarks can only be used in the tree of the buffer's file, not in a nested session
+    BookmarksInNestedSession,
+    /// The bookmarks couldn't be loaded from the given file, for the given reason
+    CannotLoadBookmarks(String, String),
+    /// The bookmarks couldn't be saved to the given file, for the given reason
+    CannotSaveBookmarks(String, String),
+
+    /* PREVIEW */
+    /// The title of the preview pane
+    PreviewTitle,
+
+    /* EDITOR */
+    /// The config tried to override the template of a message key that doesn't exist
+    UnknownMessageKey(String),
+    StartingUp,
+    Closing,
+    /// Sapling was sent a signal (e.g. `SIGTERM`) asking it to close
+    ReceivedSignal(String),
+    /// Sapling was resumed by the shell after being suspended with `Ctrl-Z`
+    Resumed,
+    /// Suspending Sapling (or taking back the terminal afterwards) failed for a given reason
+    CannotSuspend(String),
+    /// Shown in the status bar's mode segment whilst key presses run commands
+    NormalIndicator,
+    /// Shown in the status bar's undo segment, with how many changes can be undone
+    UndoDepthIndicator(usize),
+    /// Shown in the status bar's position segment, with the cursor's (1-based) index among its
+    /// siblings, the number of siblings (including the cursor) and the cursor's depth
+    PositionIndicator(usize, usize, usize),
+    /// Shown in the status bar's position segment whilst the cursor is at the root
+    RootPositionIndicator,
+    /// Shown in the status bar's file segment when the buffer hasn't been given a file
+    UnnamedFile,
+    /// The status bar will show the segments with the given names
+    StatusSegmentsSet(String),
+}
+
+impl Message {
+    /// Returns the key which identifies this message's template in a [`Catalog`]
+    pub fn key(&self) -> &'static str {
+        match self {
+            Message::CannotMoveDown => "cannot-move-down",
+            Message::CannotMoveUp => "cannot-move-up",
+            Message::CannotMovePrev => "cannot-move-prev",
+            Message::CannotMoveNext => "cannot-move-next",
+            Message::CannotMoveToSiblingOfRoot => "cannot-move-to-sibling-of-root",
+            Message::CannotJump => "cannot-jump",
+            Message::NoOlderPositions => "no-older-positions",
+            Message::NoNewerPositions => "no-newer-positions",
+            Message::NoOlderEdits => "no-older-edits",
+            Message::NoNewerEdits => "no-newer-edits",
+            Message::Replacing(..) => "replacing",
+            Message::CannotReplace(_) => "cannot-replace",
+            Message::Inserting(_) => "inserting",
+            Message::CannotInsert(_) => "cannot-insert",
+            Message::CannotInsertKey(_) => "cannot-insert-key",
+            Message::SchemaLoaded(_) => "schema-loaded",
+            Message::CannotLoadSchema(..) => "cannot-load-schema",
+            Message::Opening(_) => "opening",
+            Message::Opened(_) => "opened",
+            Message::Written(..) => "written",
+            Message::CannotWrite(..) => "cannot-write",
+            Message::NoFileName => "no-file-name",
+            Message::NoFileToSubstitute => "no-file-to-substitute",
+            Message::NewDocument(_) => "new-document",
+            Message::NoTemplate(_) => "no-template",
+            Message::InvalidTemplate(_) => "invalid-template",
+            Message::CannotOpen(..) => "cannot-open",
+            Message::CannotOpenLanguage(_) => "cannot-open-language",
+            Message::ParseErrorsRecovered(_) => "parse-errors-recovered",
+            Message::TreeInvalid(_) => "tree-invalid",
+            Message::TreeValid => "tree-valid",
+            Message::ParseError(..) => "parse-error",
+            Message::EmptyFile(_) => "empty-file",
+            Message::EmptyDocument => "empty-document",
+            Message::IncompleteDocument => "incomplete-document",
+            Message::UnexpectedText(_) => "unexpected-text",
+            Message::TextAfterDocument => "text-after-document",
+            Message::ExpectedValue => "expected-value",
+            Message::ExpectedValueBeforeComma => "expected-value-before-comma",
+            Message::CommaBeforeEnd => "comma-before-end",
+            Message::ExpectedCommaOr(_) => "expected-comma-or",
+            Message::ExpectedKey => "expected-key",
+            Message::ExpectedColon => "expected-colon",
+            Message::UnclosedArray => "unclosed-array",
+            Message::UnclosedObject => "unclosed-object",
+            Message::UnclosedString => "unclosed-string",
+            Message::UnclosedComment => "unclosed-comment",
+            Message::InvalidEscape(_) => "invalid-escape",
+            Message::InvalidUnicodeEscape(_) => "invalid-unicode-escape",
+            Message::DescribeSortKeys => "describe-sort-keys",
+            Message::DescribeWrapInArray => "describe-wrap-in-array",
+            Message::DescribeReparse => "describe-reparse",
+            Message::NoObjectsToSort => "no-objects-to-sort",
+            Message::CannotWrapKey => "cannot-wrap-key",
+            Message::NotErrorText => "not-error-text",
+            Message::StillInvalid => "still-invalid",
+            Message::NotSingleField => "not-single-field",
+            Message::ExpectedChar(_) => "expected-char",
+            Message::EmptyQuery => "empty-query",
+            Message::NotAnIndex(_) => "not-an-index",
+            Message::ExternalReference(_) => "external-reference",
+            Message::NotAPointer(_) => "not-a-pointer",
+            Message::DanglingPointer(_) => "dangling-pointer",
+            Message::NotAFieldValue => "not-a-field-value",
+            Message::FieldExists(_) => "field-exists",
+            Message::CannotEditWhileOpening(_) => "cannot-edit-while-opening",
+            Message::CannotWriteWhileOpening(_) => "cannot-write-while-opening",
+            Message::CannotWriteFailedOpen(_) => "cannot-write-failed-open",
+            Message::RootHasNoParent => "root-has-no-parent",
+            Message::NothingToSlurp => "nothing-to-slurp",
+            Message::NothingToBarf => "nothing-to-barf",
+            Message::NothingToTranspose => "nothing-to-transpose",
+            Message::CannotJoin(..) => "cannot-join",
+            Message::NothingToJoin => "nothing-to-join",
+            Message::NothingToSort => "nothing-to-sort",
+            Message::NothingToUnwrap => "nothing-to-unwrap",
+            Message::CannotWrap(_) => "cannot-wrap",
+            Message::CannotConvert(_) => "cannot-convert",
+            Message::AlreadyOfKind(_) => "already-of-kind",
+            Message::NoTransforms => "no-transforms",
+            Message::NoSuchFormatStyle(..) => "no-such-format-style",
+            Message::DescribeWrap(_) => "describe-wrap",
+            Message::DescribeUnwrap => "describe-unwrap",
+            Message::DescribeRaise => "describe-raise",
+            Message::DescribeConvert(_) => "describe-convert",
+            Message::DescribeSort => "describe-sort",
+            Message::DescribeSlurp => "describe-slurp",
+            Message::DescribeBarf => "describe-barf",
+            Message::DescribeJoin => "describe-join",
+            Message::DescribeTransposeNext => "describe-transpose-next",
+            Message::DescribeTransposePrev => "describe-transpose-prev",
+            Message::NoSuchKey(_) => "no-such-key",
+            Message::Grouped(..) => "grouped",
+            Message::InvalidStructure => "invalid-structure",
+            Message::BatchEditArmed(_) => "batch-edit-armed",
+            Message::BatchEdited(_) => "batch-edited",
+            Message::BatchEditCancelled => "batch-edit-cancelled",
+            Message::EditOutsideBatch => "edit-outside-batch",
+            Message::NothingToBatchEdit => "nothing-to-batch-edit",
+            Message::EditNotBatched => "edit-not-batched",
+            Message::EditChangesBatchNode => "edit-changes-batch-node",
+            Message::Copied(_) => "copied",
+            Message::CopiedToRegister(..) => "copied-to-register",
+            Message::EmptyRegister(_) => "empty-register",
+            Message::CannotCutRoot => "cannot-cut-root",
+            Message::ClipboardNotShared(_) => "clipboard-not-shared",
+            Message::NothingToPaste => "nothing-to-paste",
+            Message::CannotPasteText => "cannot-paste-text",
+            Message::CompletedPaste(_) => "completed-paste",
+            Message::CannotPasteStructure => "cannot-paste-structure",
+            Message::ConvertedPaste(..) => "converted-paste",
+            Message::PastedAsText(..) => "pasted-as-text",
+            Message::CannotConvertPaste(..) => "cannot-convert-paste",
+            Message::NothingToRepeat => "nothing-to-repeat",
+            Message::ConfirmReplace(_) => "confirm-replace",
+            Message::ConfirmPaste(..) => "confirm-paste",
+            Message::CannotRead(..) => "cannot-read",
+            Message::ConfirmDiscardRedo(_) => "confirm-discard-redo",
+            Message::ConfirmQuit => "confirm-quit",
+            Message::ConfirmNewDocument => "confirm-new-document",
+            Message::RedoSnapshotted(_) => "redo-snapshotted",
+            Message::Cancelled => "cancelled",
+            Message::UndoSuccessful => "undo-successful",
+            Message::NothingToUndo => "nothing-to-undo",
+            Message::RedoSuccessful => "redo-successful",
+            Message::NothingToRedo => "nothing-to-redo",
+            Message::UndoneSteps(_) => "undone-steps",
+            Message::RedoneSteps(_) => "redone-steps",
+            Message::ReturnedToSaved => "returned-to-saved",
+            Message::AlreadySaved => "already-saved",
+            Message::SavedStateLost => "saved-state-lost",
+            Message::SummaryReplace(..) => "summary-replace",
+            Message::SummaryInsert(..) => "summary-insert",
+            Message::SummaryPaste(..) => "summary-paste",
+            Message::SummarySlurp(_) => "summary-slurp",
+            Message::SummaryBarf(_) => "summary-barf",
+            Message::SummaryTranspose(_) => "summary-transpose",
+            Message::SummaryJoin(_) => "summary-join",
+            Message::SummarySort(_) => "summary-sort",
+            Message::SummaryGroup(_) => "summary-group",
+            Message::SummaryWrap(..) => "summary-wrap",
+            Message::SummaryUnwrap(_) => "summary-unwrap",
+            Message::SummaryRaise(_) => "summary-raise",
+            Message::SummaryConvert(..) => "summary-convert",
+            Message::SummaryRename(_) => "summary-rename",
+            Message::SummaryCut(_) => "summary-cut",
+            Message::SummaryLanguageCommand(..) => "summary-language-command",
+            Message::SummaryBatch(..) => "summary-batch",
+            Message::UndoneChange(_) => "undone-change",
+            Message::RedoneChange(_) => "redone-change",
+            Message::HistoryTitle => "history-title",
+            Message::HistoryEntry(..) => "history-entry",
+            Message::HistoryConsistent(_) => "history-consistent",
+            Message::HistoryWrongRoot(_) => "history-wrong-root",
+            Message::HistoryWrongCursor(_) => "history-wrong-cursor",
+            Message::HistoryInvalidCursor(_) => "history-invalid-cursor",
+            Message::HistoryWrongStep(_) => "history-wrong-step",
+            Message::JournalHeading(_) => "journal-heading",
+            Message::JournalEntry(..) => "journal-entry",
+            Message::NoJournal => "no-journal",
+            Message::CannotWriteJournal(_) => "cannot-write-journal",
+            Message::CannotReadJournal(_) => "cannot-read-journal",
+            Message::HistoryOriginal => "history-original",
+            Message::HistoryUnchanged => "history-unchanged",
+            Message::DuplicateBinding(..) => "duplicate-binding",
+            Message::PrefixBinding(..) => "prefix-binding",
+            Message::UnboundCommands(_) => "unbound-commands",
+            Message::AmbiguousKeymap => "ambiguous-keymap",
+            Message::CannotLoadKeymap(..) => "cannot-load-keymap",
+            Message::KeymapExported(_) => "keymap-exported",
+            Message::CannotExportKeymap(..) => "cannot-export-keymap",
+            Message::KeymapImported(_) => "keymap-imported",
+            Message::CannotImportKeymap(..) => "cannot-import-keymap",
+            Message::CannotLoadTheme(..) => "cannot-load-theme",
+            Message::CannotLoadProjectConfig(..) => "cannot-load-project-config",
+            Message::CannotLoadConfig(..) => "cannot-load-config",
+            Message::EmptyMacroRegister(_) => "empty-macro-register",
+            Message::MacroSet(_) => "macro-set",
+            Message::RecordingMacro(_) => "recording-macro",
+            Message::MacroRecorded(..) => "macro-recorded",
+            Message::NotRecording => "not-recording",
+            Message::RecordingIndicator(_) => "recording-indicator",
+            Message::ModifiedIndicator => "modified-indicator",
+            Message::TypingText => "typing-text",
+            Message::TypingIndicator => "typing-indicator",
+            Message::CommandCountdown(..) => "command-countdown",
+            Message::NoTextToEdit(_) => "no-text-to-edit",
+            Message::InvalidMacroKey(_) => "invalid-macro-key",
+            Message::MacrosTooDeep => "macros-too-deep",
+            Message::NotACommand(_) => "not-a-command",
+            Message::HintPrefix(_) => "hint-prefix",
+            Message::KeyMapped(..) => "key-mapped",
+            Message::NoEnclosing(_) => "no-enclosing",
+            Message::EmptySearch => "empty-search",
+            Message::NoMatches(_) => "no-matches",
+            Message::NoSearch => "no-search",
+            Message::CannotQuery(_) => "cannot-query",
+            Message::InvalidQuery(_) => "invalid-query",
+            Message::QueryMatches(_) => "query-matches",
+            Message::QuickfixEntry(..) => "quickfix-entry",
+            Message::QuickfixEmpty => "quickfix-empty",
+            Message::DuplicatesFound(_) => "duplicates-found",
+            Message::NoDuplicates => "no-duplicates",
+            Message::CopiedFromAbove(_) => "copied-from-above",
+            Message::CopiedFromBelow(_) => "copied-from-below",
+            Message::NoCorrespondingField => "no-corresponding-field",
+            Message::FileFormatSet(_) => "file-format-set",
+            Message::Elided(_) => "elided",
+            Message::Folded(_) => "folded",
+            Message::NothingToFold(_) => "nothing-to-fold",
+            Message::FoldsOpened(_) => "folds-opened",
+            Message::MaxDepthSet(_) => "max-depth-set",
+            Message::MaxDepthOff => "max-depth-off",
+            Message::CannotWriteLogFile(_) => "cannot-write-log-file",
+            Message::MaxFpsSet(_) => "max-fps-set",
+            Message::LogLevelSet(_) => "log-level-set",
+            Message::LandingSet(_) => "landing-set",
+            Message::NewerLogEntries(_) => "newer-log-entries",
+            Message::TreeTooDeep(..) => "tree-too-deep",
+            Message::TreeTooWide(..) => "tree-too-wide",
+            Message::ConfirmFoldOversized(_) => "confirm-fold-oversized",
+            Message::FoldedOversized(_) => "folded-oversized",
+            Message::NothingOversized => "nothing-oversized",
+            Message::DepthLimitSet(_) => "depth-limit-set",
+            Message::ChildLimitSet(_) => "child-limit-set",
+            Message::LineNumbersOn => "line-numbers-on",
+            Message::LineNumbersOff => "line-numbers-off",
+            Message::LineWidthSet(_) => "line-width-set",
+            Message::LineWidthOff => "line-width-off",
+            Message::CannotSetLineWidth(_) => "cannot-set-line-width",
+            Message::CommandTimeoutSet(_) => "command-timeout-set",
+            Message::CommandTimeoutOff => "command-timeout-off",
+            Message::CannotEncode(..) => "cannot-encode",
+            Message::NotASymbol => "not-a-symbol",
+            Message::CannotRename(_) => "cannot-rename",
+            Message::Renamed(..) => "renamed",
+            Message::NoSuchLanguageCommand(_) => "no-such-language-command",
+            Message::LanguageCommandFailed(..) => "language-command-failed",
+            Message::RanLanguageCommand(_) => "ran-language-command",
+            Message::NotAReference => "not-a-reference",
+            Message::InvalidReference(_) => "invalid-reference",
+            Message::NoReferences => "no-references",
+            Message::ReferencesFound(_) => "references-found",
+            Message::NothingToAggregate => "nothing-to-aggregate",
+            Message::StatsCount(..) => "stats-count",
+            Message::StatsCommon(_) => "stats-common",
+            Message::StatsNumbers(..) => "stats-numbers",
+            Message::StatsRange(..) => "stats-range",
+            Message::UnknownMessageKey(_) => "unknown-message-key",
+            Message::EventsUnavailable(_) => "events-unavailable",
+            Message::ReplOpened => "repl-opened",
+            Message::ReplClosed => "repl-closed",
+            Message::Evaluated(..) => "evaluated",
+            Message::EvaluationFailed(..) => "evaluation-failed",
+            Message::CannotEvaluate(_) => "cannot-evaluate",
+            Message::WatchesTitle => "watches-title",
+            Message::NoSuchWatch(_) => "no-such-watch",
+            Message::WatchesCleared => "watches-cleared",
+            Message::PinsTitle => "pins-title",
+            Message::Pinned(_) => "pinned",
+            Message::Unpinned(_) => "unpinned",
+            Message::NoSuchPin(_) => "no-such-pin",
+            Message::PinsCleared => "pins-cleared",
+            Message::PinMissing => "pin-missing",
+            Message::BookmarkSet(..) => "bookmark-set",
+            Message::NoSuchBookmark(_) => "no-such-bookmark",
+            Message::BookmarkMissing(_) => "bookmark-missing",
+            Message::StaleBookmark(_) => "stale-bookmark",
+            Message::StaleBookmarks(_) => "stale-bookmarks",
+            Message::BookmarksInNestedSession => "bookmarks-in-nested-session",
+            Message::CannotLoadBookmarks(..) => "cannot-load-bookmarks",
+            Message::CannotSaveBookmarks(..) => "cannot-save-bookmarks",
+            Message::UnsupportedLanguage(..) => "unsupported-language",
+            Message::CannotReparse(_) => "cannot-reparse",
+            Message::Reparsed(_) => "reparsed",
+            Message::LanguageUnchanged(_) => "language-unchanged",
+            Message::NotEmbedded(_) => "not-embedded",
+            Message::EditingEmbedded => "editing-embedded",
+            Message::EmbeddedWritten => "embedded-written",
+            Message::CannotWriteEmbedded => "cannot-write-embedded",
+            Message::PreviewTitle => "preview-title",
+            Message::StartingUp => "starting-up",
+            Message::Closing => "closing",
+            Message::ReceivedSignal(_) => "received-signal",
+            Message::Resumed => "resumed",
+            Message::CannotSuspend(_) => "cannot-suspend",
+            Message::NormalIndicator => "normal-indicator",
+            Message::UndoDepthIndicator(_) => "undo-depth-indicator",
+            Message::PositionIndicator(..) => "position-indicator",
+            Message::RootPositionIndicator => "root-position-indicator",
+            Message::UnnamedFile => "unnamed-file",
+            Message::StatusSegmentsSet(_) => "status-segments-set",
+        }
+    }
+
+    /// Returns the values which are substituted into this message's template.  The `n`th value
+    /// replaces every `{n}` in the template.
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Message::Replacing(c, s) | Message::BookmarkSet(c, s) => {
+                vec![c.to_string(), s.clone()]
+            }
+            Message::Evaluated(s, t)
+            | Message::ConvertedPaste(s, t)
+            | Message::CommandCountdown(s, t)
+            | Message::KeyMapped(s, t)
+            | Message::SummaryLanguageCommand(s, t)
+            | Message::LanguageCommandFailed(s, t)
+            | Message::PastedAsText(s, t)
+            | Message::CannotConvertPaste(s, t)
+            | Message::DuplicateBinding(s, t)
+            | Message::PrefixBinding(s, t)
+            | Message::EvaluationFailed(s, t)
+            | Message::CannotJoin(s, t)
+            | Message::NoSuchFormatStyle(s, t)
+            | Message::UnsupportedLanguage(s, t)
+            | Message::CannotLoadSchema(s, t)
+            | Message::CannotLoadKeymap(s, t)
+            | Message::CannotExportKeymap(s, t)
+            | Message::CannotImportKeymap(s, t)
+            | Message::CannotLoadTheme(s, t)
+            | Message::CannotLoadProjectConfig(s, t)
+            | Message::CannotLoadConfig(s, t)
+            | Message::CannotLoadBookmarks(s, t)
+            | Message::CannotSaveBookmarks(s, t)
+            | Message::CannotOpen(s, t)
+            | Message::SummaryReplace(s, t)
+            | Message::SummaryInsert(s, t)
+            | Message::SummaryPaste(s, t)
+            | Message::SummaryWrap(s, t)
+            | Message::SummaryConvert(s, t) => {
+                vec![s.clone(), t.clone()]
+            }
+            Message::CannotReplace(c)
+            | Message::Inserting(c)
+            | Message::CannotInsert(c)
+            | Message::CannotWrap(c)
+            | Message::CannotConvert(c)
+            | Message::EmptyMacroRegister(c)
+            | Message::EmptyRegister(c)
+            | Message::MacroSet(c)
+            | Message::RecordingMacro(c)
+            | Message::RecordingIndicator(c)
+            | Message::NoSuchBookmark(c)
+            | Message::BookmarkMissing(c)
+            | Message::StaleBookmark(c)
+            | Message::ExpectedCommaOr(c)
+            | Message::InvalidEscape(c)
+            | Message::ExpectedChar(c) => vec![c.to_string()],
+            Message::ConfirmReplace(n)
+            | Message::ConfirmDiscardRedo(n)
+            | Message::RedoSnapshotted(n)
+            | Message::UndoneSteps(n)
+            | Message::ConfirmFoldOversized(n)
+            | Message::FoldedOversized(n)
+            | Message::DepthLimitSet(n)
+            | Message::ChildLimitSet(n)
+            | Message::HistoryConsistent(n)
+            | Message::HistoryWrongRoot(n)
+            | Message::HistoryWrongCursor(n)
+            | Message::HistoryInvalidCursor(n)
+            | Message::HistoryWrongStep(n)
+            | Message::RedoneSteps(n)
+            | Message::Copied(n)
+            | Message::UndoDepthIndicator(n)
+            | Message::NewerLogEntries(n)
+            | Message::MaxFpsSet(n)
+            | Message::NoSuchWatch(n)
+            | Message::NoSuchPin(n)
+            | Message::HintPrefix(n)
+            | Message::CompletedPaste(n)
+            | Message::BatchEdited(n)
+            | Message::QueryMatches(n)
+            | Message::ParseErrorsRecovered(n)
+            | Message::TreeInvalid(n)
+            | Message::DuplicatesFound(n)
+            | Message::ReferencesFound(n)
+            | Message::Elided(n)
+            | Message::Folded(n)
+            | Message::FoldsOpened(n)
+            | Message::MaxDepthSet(n)
+            | Message::LineWidthSet(n)
+            | Message::CommandTimeoutSet(n) => {
+                vec![n.to_string()]
+            }
+            Message::NotACommand(s)
+            | Message::CannotSetLineWidth(s)
+            | Message::NoMatches(s)
+            | Message::UnknownMessageKey(s)
+            | Message::ReceivedSignal(s)
+            | Message::CannotSuspend(s)
+            | Message::ClipboardNotShared(s)
+            | Message::InvalidMacroKey(s)
+            | Message::UnboundCommands(s)
+            | Message::EventsUnavailable(s)
+            | Message::CannotEvaluate(s)
+            | Message::NoEnclosing(s)
+            | Message::CannotReparse(s)
+            | Message::Reparsed(s)
+            | Message::LanguageUnchanged(s)
+            | Message::NotEmbedded(s)
+            | Message::BatchEditArmed(s)
+            | Message::CannotQuery(s)
+            | Message::InvalidQuery(s)
+            | Message::CannotInsertKey(s)
+            | Message::NoTextToEdit(s)
+            | Message::NothingToFold(s)
+            | Message::SchemaLoaded(s)
+            | Message::Opening(s)
+            | Message::Opened(s)
+            | Message::NewDocument(s)
+            | Message::NoTemplate(s)
+            | Message::InvalidTemplate(s)
+            | Message::CannotOpenLanguage(s)
+            | Message::EmptyFile(s)
+            | Message::UnexpectedText(s)
+            | Message::InvalidUnicodeEscape(s)
+            | Message::NotAnIndex(s)
+            | Message::ExternalReference(s)
+            | Message::NotAPointer(s)
+            | Message::DanglingPointer(s)
+            | Message::FieldExists(s)
+            | Message::JournalHeading(s)
+            | Message::CannotWriteJournal(s)
+            | Message::CannotReadJournal(s)
+            | Message::CannotEditWhileOpening(s)
+            | Message::CannotWriteWhileOpening(s)
+            | Message::CannotWriteFailedOpen(s)
+            | Message::CopiedFromAbove(s)
+            | Message::CopiedFromBelow(s)
+            | Message::StatsCommon(s)
+            | Message::StatusSegmentsSet(s)
+            | Message::LogLevelSet(s)
+            | Message::LandingSet(s)
+            | Message::KeymapExported(s)
+            | Message::KeymapImported(s)
+            | Message::CannotWriteLogFile(s)
+            | Message::InvalidReference(s)
+            | Message::Pinned(s)
+            | Message::StaleBookmarks(s)
+            | Message::Unpinned(s)
+            | Message::FileFormatSet(s)
+            | Message::SummarySlurp(s)
+            | Message::SummaryBarf(s)
+            | Message::SummaryTranspose(s)
+            | Message::SummaryJoin(s)
+            | Message::SummarySort(s)
+            | Message::SummaryGroup(s)
+            | Message::SummaryUnwrap(s)
+            | Message::SummaryRaise(s)
+            | Message::AlreadyOfKind(s)
+            | Message::SummaryRename(s)
+            | Message::SummaryCut(s)
+            | Message::CannotRename(s)
+            | Message::NoSuchLanguageCommand(s)
+            | Message::RanLanguageCommand(s)
+            | Message::NoSuchKey(s)
+            | Message::DescribeWrap(s)
+            | Message::DescribeConvert(s)
+            | Message::UndoneChange(s)
+            | Message::RedoneChange(s) => vec![s.clone()],
+            Message::SummaryBatch(s, n) | Message::Renamed(s, n) | Message::Written(s, n) => {
+                vec![s.clone(), n.to_string()]
+            }
+            Message::Grouped(n, s) | Message::StatsNumbers(n, s) => vec![n.to_string(), s.clone()],
+            Message::CopiedToRegister(n, c) | Message::MacroRecorded(n, c) => {
+                vec![n.to_string(), c.to_string()]
+            }
+            Message::StatsCount(n, m, l) | Message::PositionIndicator(n, m, l) => {
+                vec![n.to_string(), m.to_string(), l.to_string()]
+            }
+            Message::StatsRange(s, t)
+            | Message::CannotEncode(s, t)
+            | Message::CannotRead(s, t)
+            | Message::CannotWrite(s, t)
+            | Message::JournalEntry(s, t) => {
+                vec![s.clone(), t.clone()]
+            }
+            Message::ConfirmPaste(n, m, s) => vec![n.to_string(), m.to_string(), s.clone()],
+            Message::QuickfixEntry(n, m)
+            | Message::TreeTooDeep(n, m)
+            | Message::TreeTooWide(n, m) => vec![n.to_string(), m.to_string()],
+            Message::ParseError(n, s) => vec![n.to_string(), s.clone()],
+            Message::HistoryEntry(n, s, t) => vec![n.to_string(), s.clone(), t.clone()],
+            _ => vec![],
+        }
+    }
+}
+
+/// The English templates for every message.  This is the fallback for any message which isn't
+/// translated in the user's locale, so it must contain every [`Message::key`].
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "cannot-move-down",
+        "Cannot move down the tree if the cursor has no children.",
+    ),
+    ("cannot-move-up", "Cannot move to the parent of the root."),
+    (
+        "cannot-move-prev",
+        "Cannot move before the first child of a node.",
+    ),
+    (
+        "cannot-move-next",
+        "Cannot move past the last sibling of a node.",
+    ),
+    (
+        "cannot-move-to-sibling-of-root",
+        "Cannot move to a sibling of the root.",
+    ),
+    (
+        "cannot-jump",
+        "Cannot jump to a node that isn't in the tree.",
+    ),
+    ("no-older-positions", "No older cursor positions"),
+    ("no-newer-positions", "No newer cursor positions"),
+    ("no-older-edits", "No older edit positions"),
+    ("no-newer-edits", "No newer edit positions"),
+    ("replacing", "Replacing with '{0}'/{1}"),
+    ("cannot-replace", "Cannot replace node with '{0}'"),
+    ("inserting", "Inserting with '{0}'"),
+    ("cannot-insert", "Cannot insert node with '{0}'"),
+    ("cannot-insert-key", "Cannot insert the key '{0}' here"),
+    ("schema-loaded", "Loaded the schema from '{0}'"),
+    ("opening", "Opening '{0}'"),
+    ("opened", "Opened '{0}'"),
+    ("written", "Wrote {1} bytes to '{0}'"),
+    ("cannot-write", "Cannot write '{0}': {1}"),
+    (
+        "no-file-name",
+        "This document has no file yet, so give one with ':w <path>'",
+    ),
+    (
+        "no-file-to-substitute",
+        "This document has no file for '%' to stand for (use '\\%' for a literal '%')",
+    ),
+    ("new-document", "Started a new {0} document"),
+    ("no-template", "There is no template for new {0} documents"),
+    (
+        "invalid-template",
+        "The template for new {0} documents is not valid {0}",
+    ),
+    ("cannot-open", "Cannot open '{0}': {1}"),
+    ("cannot-open-language", "Files cannot be opened as '{0}'"),
+    (
+        "parse-errors-recovered",
+        "{0} parts of the file couldn't be parsed, so they are kept as text (:cn steps through them)",
+    ),
+    ("parse-error", "Line {0}: {1}"),
+    ("empty-file", "Cannot open '{0}': the file is empty"),
+    ("tree-invalid", "{0} parts of the tree aren't valid"),
+    ("tree-valid", "Every part of the tree is valid again"),
+    ("empty-document", "the text is empty"),
+    ("incomplete-document", "the text ended before the value was complete"),
+    ("unexpected-text", "unexpected '{0}'"),
+    ("text-after-document", "unexpected text after the end of the document"),
+    ("expected-value", "expected a value"),
+    ("expected-value-before-comma", "expected a value before ','"),
+    ("comma-before-end", "a comma comes before the end"),
+    ("expected-comma-or", "expected ',' or '{0}'"),
+    ("expected-key", "expected a key"),
+    ("expected-colon", "expected ':' after a key"),
+    ("unclosed-array", "an array isn't closed"),
+    ("unclosed-object", "an object isn't closed"),
+    ("unclosed-string", "a string isn't closed"),
+    ("unclosed-comment", "a comment isn't closed"),
+    ("invalid-escape", "'\\{0}' is not an escape sequence"),
+    ("invalid-unicode-escape", "'\\u{0}' is not a character"),
+    (
+        "describe-sort-keys",
+        "Sort the fields of every object in the cursor's subtree by their keys",
+    ),
+    (
+        "describe-wrap-in-array",
+        "Replace the cursor's value with an array containing it",
+    ),
+    (
+        "describe-reparse",
+        "Parse the text under the cursor which couldn't be parsed when the file was opened",
+    ),
+    ("no-objects-to-sort", "there are no objects to sort"),
+    ("cannot-wrap-key", "the key of a field can't be wrapped"),
+    (
+        "not-error-text",
+        "only text which couldn't be parsed can be reparsed",
+    ),
+    ("still-invalid", "the text still isn't valid JSON"),
+    ("not-single-field", "the text must be a single field"),
+    ("expected-char", "expected '{0}'"),
+    ("empty-query", "the query is empty"),
+    ("not-an-index", "'{0}' is not an index"),
+    ("external-reference", "'{0}' refers to another document"),
+    ("not-a-pointer", "'{0}' is not a JSON pointer"),
+    ("dangling-pointer", "'{0}' doesn't point to anything"),
+    ("not-a-field-value", "the referenced node isn't a field's value"),
+    ("field-exists", "there is already a field called '{0}'"),
+    (
+        "cannot-edit-while-opening",
+        "Cannot edit until '{0}' has finished opening",
+    ),
+    (
+        "cannot-write-while-opening",
+        "Cannot write until '{0}' has finished opening",
+    ),
+    (
+        "cannot-write-failed-open",
+        "Not writing over '{0}', because only part of it could be opened",
+    ),
+    (
+        "cannot-load-schema",
+        "Cannot load the schema from '{0}': {1}",
+    ),
+    ("root-has-no-parent", "The root has no parent to edit"),
+    (
+        "nothing-to-slurp",
+        "There is no node after the cursor to slurp",
+    ),
+    ("nothing-to-barf", "The cursor has no children to barf"),
+    (
+        "nothing-to-transpose",
+        "There is no node on that side to transpose with",
+    ),
+    ("cannot-join", "Cannot join '{0}' with '{1}'"),
+    (
+        "nothing-to-join",
+        "There is nothing after the cursor to join with",
+    ),
+    ("nothing-to-sort", "There are no children to sort"),
+    ("nothing-to-unwrap", "There are no children to unwrap"),
+    ("cannot-wrap", "Cannot wrap the cursor in the node represented by '{0}'"),
+    (
+        "cannot-convert",
+        "Cannot convert the cursor into the node represented by '{0}' and keep its children",
+    ),
+    ("already-of-kind", "The cursor is already {0}"),
+    (
+        "no-transforms",
+        "There are no transformations which can be made to the cursor",
+    ),
+    ("no-such-format-style", "{1} has no format style called '{0}'"),
+    ("describe-wrap", "Wrap the cursor in a new {0}"),
+    ("describe-unwrap", "Replace the cursor with its children"),
+    ("describe-raise", "Replace the cursor's parent with the cursor"),
+    (
+        "describe-convert",
+        "Turn the cursor into {0}, keeping its children",
+    ),
+    ("describe-sort", "Sort the children of the cursor"),
+    ("describe-slurp", "Move the next sibling into the cursor"),
+    ("describe-barf", "Move the cursor's last child out after it"),
+    ("describe-join", "Merge the next sibling into the cursor"),
+    (
+        "describe-transpose-next",
+        "Swap the cursor with its next sibling",
+    ),
+    (
+        "describe-transpose-prev",
+        "Swap the cursor with its previous sibling",
+    ),
+    ("no-such-key", "None of the children have the key '{0}'"),
+    ("grouped", "Grouped the children into {0} groups by '{1}'"),
+    ("invalid-structure", "That edit would make the tree invalid"),
+    (
+        "batch-edit-armed",
+        "The next edit inside this {0} will be made to every one of its children (replace, \
+         insert, cut, wrap, unwrap, convert, raise, slurp, barf, join or transpose)",
+    ),
+    ("batch-edited", "Made the edit to {0} children"),
+    ("batch-edit-cancelled", "Cancelled the batch edit"),
+    (
+        "edit-outside-batch",
+        "That edit wasn't inside the batch, so it was only made once",
+    ),
+    (
+        "nothing-to-batch-edit",
+        "None of the children have a node where that edit can be made",
+    ),
+    (
+        "edit-not-batched",
+        "That edit can't be made to every child, so it was only made once",
+    ),
+    (
+        "edit-changes-batch-node",
+        "That edit would change the node the batch edit was waiting on, so it wasn't made",
+    ),
+    ("copied", "Copied {0} nodes"),
+    ("copied-to-register", "Copied {0} nodes to register '{1}'"),
+    ("empty-register", "Register '{0}' is empty"),
+    ("cannot-cut-root", "Cannot cut the root"),
+    (
+        "clipboard-not-shared",
+        "Cannot share the clipboard with other instances: {0}",
+    ),
+    ("nothing-to-paste", "Nothing to paste"),
+    (
+        "completed-paste",
+        "The pasted text was incomplete, so it was completed with {0} highlighted holes to review",
+    ),
+    (
+        "cannot-paste-text",
+        "Cannot paste text which is not a node of this language.",
+    ),
+    (
+        "cannot-paste-structure",
+        "Cannot paste; the clipboard doesn't contain valid nodes.",
+    ),
+    ("converted-paste", "Converted the pasted {0} nodes into {1}"),
+    (
+        "pasted-as-text",
+        "Pasted the text of the {0} nodes, which can't be converted into {1}",
+    ),
+    (
+        "cannot-convert-paste",
+        "Cannot paste {0} nodes into a {1} buffer",
+    ),
+    ("nothing-to-repeat", "No replacement to repeat"),
+    ("confirm-replace", "Replace {0} nodes?"),
+    (
+        "confirm-paste",
+        "Paste {0} nodes, {1} levels deep, starting '{2}'?",
+    ),
+    ("cannot-read", "Cannot read '{0}': {1}"),
+    (
+        "confirm-discard-redo",
+        "This edit throws away {0} changes which could be redone. Continue?",
+    ),
+    (
+        "confirm-quit",
+        "There are unsaved changes. Quit without saving?",
+    ),
+    (
+        "confirm-new-document",
+        "There are unsaved changes. Start a new document without saving?",
+    ),
+    (
+        "redo-snapshotted",
+        "Copied the tree with the {0} thrown away changes to the clipboard",
+    ),
+    ("cancelled", "Cancelled."),
+    ("undo-successful", "Undo successful"),
+    ("nothing-to-undo", "No changes to undo"),
+    ("redo-successful", "Redo successful"),
+    ("nothing-to-redo", "No changes to redo"),
+    ("undone-steps", "Undid {0} changes"),
+    ("redone-steps", "Redid {0} changes"),
+    ("returned-to-saved", "Returned to the saved state"),
+    ("already-saved", "Already at the saved state"),
+    ("summary-replace", "replace node at {0} with {1}"),
+    ("summary-insert", "insert {1} into {0}"),
+    ("summary-paste", "paste {1} at {0}"),
+    ("summary-slurp", "slurp next sibling into {0}"),
+    ("summary-barf", "barf last child out of {0}"),
+    ("summary-transpose", "swap node into {0}"),
+    ("summary-join", "join next sibling into {0}"),
+    ("summary-sort", "sort children of {0}"),
+    ("summary-group", "group children of {0}"),
+    ("summary-wrap", "wrap {0} in {1}"),
+    ("summary-unwrap", "unwrap children into {0}"),
+    ("summary-raise", "raise node to {0}"),
+    ("summary-convert", "convert {0} into {1}"),
+    ("summary-cut", "cut the node at {0}"),
+    ("summary-rename", "rename {0} and its references"),
+    ("summary-language-command", "run {1} on {0}"),
+    ("summary-batch", "{0} in each of {1} children"),
+    ("undone-change", "Undid: {0}"),
+    ("redone-change", "Redid: {0}"),
+    (
+        "history-title",
+        "History (* is the current state, S is the saved state)",
+    ),
+    ("history-entry", "#{0}  {1} ago  {2}"),
+    (
+        "history-consistent",
+        "All {0} history entries restore their tree and cursor",
+    ),
+    (
+        "history-wrong-root",
+        "Moving to history entry #{0} doesn't restore its tree",
+    ),
+    (
+        "history-wrong-cursor",
+        "Moving to history entry #{0} doesn't restore its cursor",
+    ),
+    (
+        "history-invalid-cursor",
+        "The cursor of history entry #{0} isn't in its tree",
+    ),
+    (
+        "history-wrong-step",
+        "Undoing or redoing from history entry #{0} goes to the wrong entry",
+    ),
+    ("journal-heading", "The latest actions in the journal '{0}':"),
+    ("journal-entry", "{0}s  {1}"),
+    (
+        "no-journal",
+        "No journal is being kept (set `enabled = true` under `[journal]` in the config file)",
+    ),
+    (
+        "cannot-write-journal",
+        "Cannot write to the journal, so it is no longer being kept: {0}",
+    ),
+    ("cannot-read-journal", "Cannot read the journal: {0}"),
+    ("history-original", "original tree"),
+    ("history-unchanged", "no change"),
+    (
+        "saved-state-lost",
+        "The saved state was thrown away by an edit made after undoing",
+    ),
+    ("duplicate-binding", "'{0}' is bound more than once: {1}"),
+    (
+        "prefix-binding",
+        "'{0}' only runs if the key after it doesn't continue '{1}'",
+    ),
+    ("unbound-commands", "Commands with no key: {0}"),
+    (
+        "ambiguous-keymap",
+        "The keymap binds keys to different commands, so it can't be used.",
+    ),
+    (
+        "cannot-load-keymap",
+        "Can't load the keymap from '{0}' ({1}), so the default keys are used",
+    ),
+    ("keymap-exported", "Wrote the keymap to '{0}'"),
+    (
+        "cannot-export-keymap",
+        "Can't write the keymap to '{0}' ({1})",
+    ),
+    ("keymap-imported", "Loaded the keymap from '{0}'"),
+    (
+        "cannot-import-keymap",
+        "Can't load the keymap from '{0}' ({1}), so it hasn't changed",
+    ),
+    (
+        "cannot-load-theme",
+        "Can't load the theme from '{0}' ({1}), so the default colours are used",
+    ),
+    (
+        "cannot-load-project-config",
+        "Can't load the project config '{0}' ({1}), so only your own settings are used",
+    ),
+    (
+        "cannot-load-config",
+        "Can't load the settings from '{0}' ({1}), so the default settings are used",
+    ),
+    (
+        "empty-macro-register",
+        "Register '{0}' doesn't contain a macro",
+    ),
+    ("macro-set", "Stored macro in register '{0}'"),
+    ("recording-macro", "Recording macro into register '{0}'"),
+    (
+        "macro-recorded",
+        "Recorded {0} keys into macro register '{1}'",
+    ),
+    ("not-recording", "No macro is being recorded"),
+    ("recording-indicator", "recording @{0}"),
+    ("modified-indicator", "[+]"),
+    (
+        "typing-text",
+        "Typing into the node; press enter or escape to finish",
+    ),
+    ("typing-indicator", "TYPING"),
+    ("command-countdown", "{0} ({1}s)"),
+    ("no-text-to-edit", "{0} nodes have no text to type into"),
+    ("invalid-macro-key", "'{0}' is not a valid key"),
+    (
+        "macros-too-deep",
+        "Too many macros are playing at once; does a macro play itself?",
+    ),
+    ("not-a-command", "'{0}' not a command."),
+    ("hint-prefix", "+{0} commands"),
+    ("key-mapped", "Bound {0} to {1}"),
+    ("no-enclosing", "The cursor is not inside a '{0}'"),
+    ("empty-search", "Cannot search for nothing."),
+    ("no-matches", "No matches for '{0}'"),
+    ("no-search", "Nothing has been searched for yet, so there are no matches to go to"),
+    ("cannot-query", "Queries cannot be run in {0}"),
+    ("invalid-query", "Invalid query: {0}"),
+    (
+        "query-matches",
+        "Found {0} matches (use :cn and :cp to move between them)",
+    ),
+    ("quickfix-entry", "Match {0} of {1}"),
+    ("quickfix-empty", "There are no matches to move between"),
+    (
+        "duplicates-found",
+        "Found {0} duplicated nodes (use :cn and :cp to move between them)",
+    ),
+    ("no-duplicates", "No node has a duplicate sibling"),
+    ("copied-from-above", "Copied {0} from the previous record"),
+    ("copied-from-below", "Copied {0} from the next record"),
+    (
+        "no-corresponding-field",
+        "The neighbouring record has no field matching the cursor's",
+    ),
+    ("elided", "… {0} hidden …"),
+    ("folded", "…{0} children…"),
+    ("nothing-to-fold", "{0} nodes have no children to fold"),
+    ("folds-opened", "Unfolded {0} nodes"),
+    ("max-depth-set", "Rendering {0} levels below the cursor"),
+    ("max-depth-off", "Rendering the whole tree"),
+    ("line-numbers-on", "Showing line numbers"),
+    ("line-numbers-off", "Hiding line numbers"),
+    (
+        "cannot-write-log-file",
+        "Cannot write to the log file, so it is no longer being written: {0}",
+    ),
+    ("max-fps-set", "Redrawing the screen at most {0} times a second"),
+    ("log-level-set", "Showing log entries of level '{0}' and above"),
+    ("landing-set", "Cursor landing after inserts set to '{0}'"),
+    ("newer-log-entries", "↓ {0} newer entries"),
+    (
+        "tree-too-deep",
+        "{0} parts of the tree are nested more than {1} levels deep, so they will be slow to draw",
+    ),
+    (
+        "tree-too-wide",
+        "{0} nodes have more than {1} children, so they will be slow to draw",
+    ),
+    (
+        "confirm-fold-oversized",
+        "Fold the {0} parts of the tree which are too big to draw quickly?",
+    ),
+    (
+        "folded-oversized",
+        "Folded {0} parts of the tree which are too big to draw quickly",
+    ),
+    (
+        "nothing-oversized",
+        "No part of the tree is deeper or wider than the limits",
+    ),
+    (
+        "depth-limit-set",
+        "Warning about trees nested more than {0} levels deep",
+    ),
+    (
+        "child-limit-set",
+        "Warning about nodes with more than {0} children",
+    ),
+    ("line-width-set", "Joining lines which fit in {0} columns"),
+    (
+        "line-width-off",
+        "Lines are no longer joined to fit a width",
+    ),
+    (
+        "cannot-set-line-width",
+        "{0} can't be laid out to fit a width",
+    ),
+    (
+        "command-timeout-set",
+        "Commands which start longer ones run after {0}ms without a key press",
+    ),
+    (
+        "command-timeout-off",
+        "Commands which start longer ones wait for the next key",
+    ),
+    ("file-format-set", "The file will be saved as {0}"),
+    ("cannot-encode", "'{0}' cannot be encoded in {1}"),
+    (
+        "not-a-symbol",
+        "The cursor is not on anything which can be renamed",
+    ),
+    ("cannot-rename", "Cannot rename: {0}"),
+    ("renamed", "Renamed to '{0}', updating {1} references"),
+    (
+        "no-such-language-command",
+        "This language has no command called '{0}'",
+    ),
+    ("language-command-failed", "Cannot run {0}: {1}"),
+    ("ran-language-command", "Ran {0}"),
+    ("not-a-reference", "The cursor is not on a reference"),
+    ("invalid-reference", "Cannot follow reference: {0}"),
+    ("no-references", "Nothing refers to this node"),
+    (
+        "references-found",
+        "Found {0} references (use :cn and :cp to move between them)",
+    ),
+    (
+        "nothing-to-aggregate",
+        "There are no values to compute statistics of",
+    ),
+    ("stats-count", "{0} values ({1} distinct) in {2} nodes"),
+    ("stats-common", "Most common: {0}"),
+    ("stats-numbers", "{0} numbers, adding up to {1}"),
+    ("stats-range", "Smallest: {0}, largest: {1}"),
+    (
+        "unknown-message-key",
+        "Cannot override unknown message '{0}'.",
+    ),
+    ("events-unavailable", "Cannot write edit events: {0}"),
+    (
+        "repl-opened",
+        "Opened the REPL; type expressions to evaluate them, or press ESC to close it",
+    ),
+    ("repl-closed", "Closed the REPL"),
+    ("evaluated", "{0} => {1}"),
+    ("evaluation-failed", "Cannot evaluate '{0}': {1}"),
+    ("cannot-evaluate", "Expressions cannot be evaluated in {0}"),
+    ("watches-title", "Watches"),
+    ("no-such-watch", "There is no watch number {0}"),
+    ("watches-cleared", "Removed every watch"),
+    ("pins-title", "Pinned"),
+    ("pinned", "Pinned the node at {0}"),
+    ("unpinned", "Unpinned the node at {0} to make room"),
+    ("no-such-pin", "There is no pin number {0}"),
+    ("pins-cleared", "Unpinned every node"),
+    ("pin-missing", "(no longer exists)"),
+    ("bookmark-set", "Set bookmark '{0}' at {1}"),
+    ("no-such-bookmark", "There is no bookmark '{0}'"),
+    (
+        "bookmark-missing",
+        "The node of bookmark '{0}' no longer exists",
+    ),
+    (
+        "stale-bookmark",
+        "The node of bookmark '{0}' has changed since the bookmark was set",
+    ),
+    (
+        "stale-bookmarks",
+        "The nodes of some bookmarks have changed since the file was saved: {0}",
+    ),
+    (
+        "bookmarks-in-nested-session",
+        "Bookmarks can't be used in a nested session",
+    ),
+    (
+        "cannot-load-bookmarks",
+        "Can't load the bookmarks from '{0}': {1}",
+    ),
+    (
+        "cannot-save-bookmarks",
+        "Can't save the bookmarks to '{0}': {1}",
+    ),
+    (
+        "unsupported-language",
+        "Cannot edit this buffer as '{0}'; it can only be edited as '{1}'",
+    ),
+    ("cannot-reparse", "The buffer is not valid '{0}'"),
+    ("reparsed", "Re-read the buffer as '{0}'"),
+    (
+        "language-unchanged",
+        "The buffer is already read as '{0}', so nothing changed",
+    ),
+    (
+        "not-embedded",
+        "The cursor does not contain any embedded '{0}' to edit",
+    ),
+    (
+        "editing-embedded",
+        "Editing the embedded text; quit to write it back into its node",
+    ),
+    (
+        "embedded-written",
+        "Wrote the embedded text back into its node",
+    ),
+    (
+        "cannot-write-embedded",
+        "Cannot write the embedded text back into its node",
+    ),
+    ("preview-title", "Preview"),
+    ("starting-up", "Starting Up..."),
+    ("closing", "Closing..."),
+    ("received-signal", "Received {0}, closing."),
+    ("resumed", "Resumed after being suspended."),
+    ("cannot-suspend", "Cannot suspend: {0}"),
+    ("normal-indicator", "NORMAL"),
+    ("undo-depth-indicator", "undo:{0}"),
+    ("position-indicator", "child {0}/{1}, depth {2}"),
+    ("root-position-indicator", "root"),
+    ("unnamed-file", "[no file]"),
+    ("status-segments-set", "The status bar now shows: {0}"),
+];
+
+/// Returns the templates for a given language code (e.g. `"en"`), or [`None`] if Sapling hasn't
+/// been translated into that language.  Translations don't have to contain every message, since
+/// any missing messages fall back to English.
+fn templates_for_language(language: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match language {
+        "en" => Some(ENGLISH),
+        _ => None,
+    }
+}
+
+/// Reads the user's locale from the environment, using the same variables as `gettext` (`LC_ALL`,
+/// then `LC_MESSAGES`, then `LANG`).  Returns [`None`] if no locale is set.
+pub fn locale_from_env() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Extracts the language code from a POSIX locale string, e.g. `"en_GB.UTF-8"` becomes `"en"`.
+fn language_of_locale(locale: &str) -> &str {
+    locale.split(['_', '.', '@']).next().unwrap_or(locale)
+}
+
+/// A set of templates for every [`Message`], in a single locale
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Catalog {
+    templates: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Creates a `Catalog` containing the English templates
+    pub fn english() -> Catalog {
+        Catalog {
+            templates: ENGLISH
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Creates a `Catalog` for a given POSIX locale string (e.g. `"de_DE.UTF-8"`), falling back
+    /// on English for any messages which haven't been translated.
+    pub fn for_locale(locale: &str) -> Catalog {
+        let mut catalog = Catalog::english();
+        if let Some(templates) = templates_for_language(language_of_locale(locale)) {
+            for (key, template) in templates {
+                catalog.set_template(key, template);
+            }
+        }
+        catalog
+    }
+
+    /// Creates a `Catalog` for the locale set in the environment (see [`locale_from_env`])
+    pub fn from_env() -> Catalog {
+        match locale_from_env() {
+            Some(locale) => Catalog::for_locale(&locale),
+            None => Catalog::english(),
+        }
+    }
+
+    /// Replaces the template for the message with a given key
+    pub fn set_template(&mut self, key: &str, template: &str) {
+        self.templates.insert(key.to_string(), template.to_string());
+    }
+
+    /// Replaces the templates of the messages named in `overrides` (a map from
+    /// [`Message::key`]s to templates).  Returns the keys in `overrides` which don't name any
+    /// message, sorted alphabetically; these are ignored.
+    pub fn apply_overrides(&mut self, overrides: &HashMap<String, String>) -> Vec<String> {
+        let mut unknown_keys = Vec::new();
+        for (key, template) in overrides {
+            if self.templates.contains_key(key) {
+                self.set_template(key, template);
+            } else {
+                unknown_keys.push(key.clone());
+            }
+        }
+        unknown_keys.sort();
+        unknown_keys
+    }
+
+    /// Generates the text of a [`Message`] by substituting its arguments into its template
+    pub fn format(&self, message: &Message) -> String {
+        let template = match self.templates.get(message.key()) {
+            Some(template) => template.as_str(),
+            // Every key is in `ENGLISH`, so this can only happen if a message was added without a
+            // template
+            None => return message.key().to_string(),
+        };
+        let mut text = template.to_string();
+        for (i, arg) in message.args().iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), arg);
+        }
+        text
+    }
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{language_of_locale, Catalog, Message, ENGLISH};
+    use std::collections::HashMap;
+
+    #[test]
+    fn english_is_complete() {
+        let catalog = Catalog::english();
+        for message in &[
+            Message::CannotMoveDown,
+            Message::CannotJump,
+            Message::Replacing('t', "True".to_string()),
+            Message::ConfirmReplace(3),
+            Message::NotACommand("x".to_string()),
+            Message::NormalIndicator,
+        ] {
+            assert_ne!(catalog.format(message), message.key());
+        }
+        // No key should be given two templates
+        let mut keys: Vec<_> = ENGLISH.iter().map(|(k, _)| k).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), ENGLISH.len());
+    }
+
+    #[test]
+    fn format() {
+        let mut catalog = Catalog::english();
+        assert_eq!(
+            catalog.format(&Message::Replacing('t', "True".to_string())),
+            "Replacing with 't'/True"
+        );
+        assert_eq!(
+            catalog.format(&Message::NotACommand("X".to_string())),
+            "'X' not a command."
+        );
+        // Templates can use their arguments in any order, any number of times
+        catalog.set_template("replacing", "{1} <- {0} ({1})");
+        assert_eq!(
+            catalog.format(&Message::Replacing('t', "True".to_string())),
+            "True <- t (True)"
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let mut catalog = Catalog::english();
+        let mut overrides = HashMap::new();
+        overrides.insert("normal-indicator".to_string(), "Hi!".to_string());
+        overrides.insert("not-a-command".to_string(), "Unknown: {0}".to_string());
+        overrides.insert("no-such-message".to_string(), "???".to_string());
+        assert_eq!(
+            catalog.apply_overrides(&overrides),
+            vec!["no-such-message".to_string()]
+        );
+        assert_eq!(catalog.format(&Message::NormalIndicator), "Hi!");
+        assert_eq!(
+            catalog.format(&Message::NotACommand("X".to_string())),
+            "Unknown: X"
+        );
+        // Messages that aren't overridden are unchanged
+        assert_eq!(catalog.format(&Message::Cancelled), "Cancelled.");
+    }
+
+    #[test]
+    fn locales() {
+        assert_eq!(language_of_locale("en_GB.UTF-8"), "en");
+        assert_eq!(language_of_locale("fr"), "fr");
+        assert_eq!(language_of_locale("de_DE@euro"), "de");
+        // Untranslated languages fall back to English
+        assert_eq!(Catalog::for_locale("xx_XX.UTF-8"), Catalog::english());
+    }
+}